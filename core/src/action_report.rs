@@ -1,6 +1,8 @@
 use derive_more::From;
 use ergo_lib::ergo_chain_types::EcPoint;
 
+use crate::datapoint_source::FetchedDatapoint;
+use crate::datapoint_source::SourceRate;
 use crate::oracle_types::Rate;
 
 #[derive(Debug)]
@@ -11,12 +13,36 @@ pub struct RefreshActionReport {
 #[derive(Debug)]
 pub struct PublishDatapointActionReport {
     pub posted_datapoint: Rate,
+    /// Raw rates of the sources the datapoint was aggregated from
+    pub sources: Vec<SourceRate>,
+}
+
+#[derive(Debug)]
+pub struct PublishAndRefreshActionReport {
+    pub publish: PublishDatapointActionReport,
+    pub refresh: RefreshActionReport,
 }
 
 #[derive(Debug, From)]
 pub enum PoolActionReport {
     Refresh(RefreshActionReport),
     PublishDatapoint(PublishDatapointActionReport),
+    PublishAndRefresh(PublishAndRefreshActionReport),
+}
+
+impl PoolActionReport {
+    /// Datapoint fetched for a publish action
+    pub fn fetched_datapoint(&self) -> Option<FetchedDatapoint> {
+        let publish = match self {
+            PoolActionReport::Refresh(_) => return None,
+            PoolActionReport::PublishDatapoint(report) => report,
+            PoolActionReport::PublishAndRefresh(report) => &report.publish,
+        };
+        Some(FetchedDatapoint {
+            rate: publish.posted_datapoint,
+            sources: publish.sources.clone(),
+        })
+    }
 }
 
 #[derive(Debug)]
@@ -37,6 +63,10 @@ impl ActionReportStorage {
         match report {
             PoolActionReport::Refresh(report) => self.refresh = Some(report),
             PoolActionReport::PublishDatapoint(report) => self.publish_datapoint = Some(report),
+            PoolActionReport::PublishAndRefresh(report) => {
+                self.publish_datapoint = Some(report.publish);
+                self.refresh = Some(report.refresh);
+            }
         }
     }
 
@@ -1,16 +1,20 @@
 /// This file holds all the actions which can be performed
 /// by an oracle part of the oracle pool. These actions
 /// are implemented on the `OraclePool` struct.
+use std::time::Duration;
+
 use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::Transaction;
 
 use derive_more::From;
 use ergo_node_interface::node_interface::NodeError;
+use rand::Rng;
 use thiserror::Error;
 
 use crate::explorer_api::ergo_explorer_transaction_link;
 use crate::node_interface::node_api::NodeApi;
 use crate::node_interface::node_api::NodeApiError;
-use crate::oracle_config::ORACLE_CONFIG;
+use crate::oracle_config::OracleConfig;
 
 mod action_result;
 
@@ -19,6 +23,7 @@ mod action_result;
 pub enum PoolAction {
     Refresh(RefreshAction),
     PublishDatapoint(PublishDataPointAction),
+    PublishAndRefresh(PublishAndRefreshAction),
 }
 
 #[derive(Debug)]
@@ -31,19 +36,65 @@ pub struct PublishDataPointAction {
     pub tx: UnsignedTransaction,
 }
 
+/// Publish tx and a refresh tx spending its unconfirmed oracle box
+#[derive(Debug)]
+pub struct PublishAndRefreshAction {
+    pub publish: PublishDataPointAction,
+    pub refresh: RefreshAction,
+}
+
 #[derive(Error, Debug)]
 pub enum ActionExecError {
     #[error("node error: {0}")]
     NodeError(#[from] NodeApiError),
 }
 
-pub fn execute_action(action: PoolAction, node_api: &NodeApi) -> Result<(), anyhow::Error> {
+/// Submission failure of an action, along with its txs accepted by the node before it (the
+/// publish tx of a chained refresh)
+#[derive(Error, Debug)]
+#[error("{error}")]
+pub struct ActionSubmitError {
+    pub submitted_txs: Vec<Transaction>,
+    pub error: anyhow::Error,
+}
+
+/// Random delay up to `max_secs` to wait before submitting a tx, so oracles running the same
+/// loop schedule do not hit the mempool at the same moment
+pub fn random_submit_delay(max_secs: u64) -> Duration {
+    if max_secs == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::thread_rng().gen_range(0..=max_secs * 1000))
+}
+
+/// Sign and submit the txs of the action, returns the signed txs accepted by the node
+pub fn execute_action(
+    action: PoolAction,
+    node_api: &NodeApi,
+    oracle_config: &OracleConfig,
+) -> Result<Vec<Transaction>, ActionSubmitError> {
+    let mut submitted_txs = Vec::new();
     let exec_res = match action {
-        PoolAction::Refresh(action) => execute_refresh_action(action, node_api),
-        PoolAction::PublishDatapoint(action) => execute_publish_datapoint_action(action, node_api),
+        PoolAction::Refresh(action) => {
+            execute_refresh_action(action, node_api, oracle_config).map(|tx| submitted_txs.push(tx))
+        }
+        PoolAction::PublishDatapoint(action) => {
+            execute_publish_datapoint_action(action, node_api, oracle_config)
+                .map(|tx| submitted_txs.push(tx))
+        }
+        PoolAction::PublishAndRefresh(action) => {
+            match execute_publish_datapoint_action(action.publish, node_api, oracle_config) {
+                Ok(tx) => {
+                    submitted_txs.push(tx);
+                    execute_refresh_action(action.refresh, node_api, oracle_config)
+                        .map(|tx| submitted_txs.push(tx))
+                }
+                Err(e) => Err(e),
+            }
+        }
     };
     match exec_res {
-        Ok(_) => Ok(()),
+        Ok(_) => Ok(submitted_txs),
         Err(ActionExecError::NodeError(NodeApiError::NodeInterfaceError(
             NodeError::BadRequest(msg),
         ))) if msg.as_str() == "Double spending attempt"
@@ -51,34 +102,54 @@ pub fn execute_action(action: PoolAction, node_api: &NodeApi) -> Result<(), anyh
             || msg.contains("it is already in the mempool") =>
         {
             log::debug!("Node rejected tx with error: {msg}");
-            Ok(())
+            Ok(submitted_txs)
         }
-        Err(e) => Err(e.into()),
+        Err(e) => Err(ActionSubmitError {
+            submitted_txs,
+            error: e.into(),
+        }),
     }
 }
 
 fn execute_refresh_action(
     action: RefreshAction,
     node_api: &NodeApi,
-) -> Result<(), ActionExecError> {
-    let tx_id = node_api.sign_and_submit_transaction(&action.tx)?;
-    let network_prefix = &ORACLE_CONFIG.oracle_address.network();
+    oracle_config: &OracleConfig,
+) -> Result<Transaction, ActionExecError> {
+    let signed_tx = node_api.sign_and_submit_transaction_returning_signed(&action.tx)?;
+    let network_prefix = &oracle_config.oracle_address.network();
+    let _tx_id = log_mdc::insert_scoped("tx_id", signed_tx.id().to_string());
     log::info!(
         "Refresh tx published. Check status: {}",
-        ergo_explorer_transaction_link(tx_id, *network_prefix)
+        ergo_explorer_transaction_link(signed_tx.id(), *network_prefix, oracle_config)
     );
-    Ok(())
+    Ok(signed_tx)
 }
 
 fn execute_publish_datapoint_action(
     action: PublishDataPointAction,
     node_api: &NodeApi,
-) -> Result<(), ActionExecError> {
-    let tx_id = node_api.sign_and_submit_transaction(&action.tx)?;
-    let network_prefix = &ORACLE_CONFIG.oracle_address.network();
+    oracle_config: &OracleConfig,
+) -> Result<Transaction, ActionExecError> {
+    let signed_tx = node_api.sign_and_submit_transaction_returning_signed(&action.tx)?;
+    let network_prefix = &oracle_config.oracle_address.network();
+    let _tx_id = log_mdc::insert_scoped("tx_id", signed_tx.id().to_string());
     log::info!(
         "Datapoint tx published. Check status: {}",
-        ergo_explorer_transaction_link(tx_id, *network_prefix)
+        ergo_explorer_transaction_link(signed_tx.id(), *network_prefix, oracle_config)
     );
-    Ok(())
+    Ok(signed_tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_submit_delay() {
+        assert_eq!(random_submit_delay(0), Duration::ZERO);
+        for _ in 0..100 {
+            assert!(random_submit_delay(3) <= Duration::from_secs(3));
+        }
+    }
 }
@@ -2,37 +2,64 @@ use std::convert::From;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
+use crate::attestation::LatestAttestation;
+use crate::audit_log::{get_audit_log_file_path, read_recent_entries, AuditLogError};
 use crate::box_kind::{OracleBoxWrapper, PoolBox};
-use crate::node_interface::node_api::NodeApi;
-use crate::oracle_config::{get_core_api_port, ORACLE_CONFIG};
-use crate::oracle_state::{DataSourceError, LocalDatapointState, OraclePool};
-use crate::pool_config::POOL_CONFIG;
+use crate::context::OracleContext;
+use crate::epoch_history::{
+    get_epoch_history_file_path, read_recent_entries as read_recent_epochs, EpochHistoryError,
+};
+use crate::governance::governance_status;
+#[cfg(feature = "metrics")]
+use crate::metrics::encode_metrics;
+use crate::monitor::{MonitorSnapshot, PoolMonitor};
+use crate::multi_pool::PoolRunner;
+use crate::oracle_state::{DataSourceError, LocalBallotState, LocalDatapointState, OraclePool};
+use crate::pool_commands::refresh::COLLECTOR_REWARD_TOKENS_PER_DATAPOINT;
+use crate::spec_token::TokenIdKind;
+#[cfg(feature = "graphql")]
+use async_graphql_axum::GraphQLRequest;
+use axum::extract::Query;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::routing::get;
 use axum::{Json, Router};
-use crossbeam::channel::Receiver;
 use ergo_lib::ergotree_ir::chain::address::{Address, AddressEncoder};
 use ergo_node_interface::scanning::NodeError;
+use serde::Deserialize;
 use serde_json::json;
+use tokio::sync::watch;
 use tokio::task;
 use tower_http::cors::CorsLayer;
 
+mod eip23;
+#[cfg(feature = "graphql")]
+mod graphql;
+
 /// Basic welcome endpoint
 async fn root() -> &'static str {
     "This is an Oracle Core. Please use one of the endpoints to interact with it: 
         /poolInfo - basic information about the oracle pool
         /poolStatus - status of the oracle pool
         /oracleInfo - basic information about the oracle
-        /oracleStatus - status of the oracle
+        /oracleStatus - status of the oracle and of its ballot box
         /oracleHealth - returns OK if our collected datapoint box height is the same as the pool box height OR our posted datapoint box height is greater than the pool box height
         /poolHealth - returns OK if the pool box height is greater or equal to (current height - epoch length)
+        /pools - summary of every pool driven by this oracle core
+        /metrics - main loop metrics in the Prometheus text format (with the `metrics` feature)
+        /auditLog - recent entries of the action audit log (optional `limit` and `pool` query parameters)
+        /attestation - latest datapoint signed with the oracle key (optional `pool` query parameter, with `datapoint_attestations`)
+        /governance - update proposals voted for with the current update box and their votes (optional `pool` query parameter)
+        /poolBox - pool box in the node JSON format with its serialized bytes, for EIP-23 consumers
+        /latestRate - rate and epoch counter of the pool box
+        /frontendData - pool status in the format of the oracle-core v1 frontends
+        /graphql - GraphQL queries of the pool status, datapoints, history and oracles of every pool (with the `graphql` feature)
         "
 }
 
 /// Basic oracle information
-async fn oracle_info() -> impl IntoResponse {
-    let conf = &ORACLE_CONFIG;
+async fn oracle_info(ctx: Arc<OracleContext>) -> impl IntoResponse {
+    let conf = &ctx.oracle_config;
     Json(json! ( {
         "oracle_address": conf.oracle_address.to_base58(),
         "base_fee": conf.base_fee,
@@ -48,6 +75,7 @@ async fn oracle_status(oracle_pool: Arc<OraclePool>) -> Result<Json<serde_json::
 }
 
 fn oracle_status_sync(oracle_pool: Arc<OraclePool>) -> Result<Json<serde_json::Value>, ApiError> {
+    let local_ballot_box_state = local_ballot_state_json(&oracle_pool)?;
     let live_epoch = oracle_pool.get_live_epoch_state()?;
     if let Some(local_datapoint_box_state) = live_epoch.local_datapoint_box_state {
         let json = match local_datapoint_box_state {
@@ -64,19 +92,47 @@ fn oracle_status_sync(oracle_pool: Arc<OraclePool>) -> Result<Json<serde_json::V
         let oracle_health = oracle_health_sync(oracle_pool)?;
         Ok(Json(json!({
                 "local_datapoint_box_state": json,
+                "local_ballot_box_state": local_ballot_box_state,
                 "oracle_health": oracle_health,
         })))
     } else {
         Ok(Json(json!({
                 "local_datapoint_box_state": "No local datapoint box",
+                "local_ballot_box_state": local_ballot_box_state,
         })))
     }
 }
 
+fn local_ballot_state_json(oracle_pool: &OraclePool) -> Result<serde_json::Value, ApiError> {
+    let json = match oracle_pool.get_local_ballot_state()? {
+        None => json!("No local ballot box"),
+        Some(LocalBallotState::NoVote { height }) => json!({
+            "status": "no_vote",
+            "height": height,
+        }),
+        Some(LocalBallotState::Voted {
+            vote_parameters,
+            height,
+            is_current,
+        }) => {
+            let reward_token = vote_parameters.reward_token_opt.as_ref();
+            json!({
+                "status": if is_current { "voted" } else { "stale_vote" },
+                "height": height,
+                "pool_box_address_hash": String::from(vote_parameters.pool_box_address_hash),
+                "reward_token_id": reward_token.map(|t| String::from(t.token_id.token_id())),
+                "reward_token_amount": reward_token.map(|t| *t.amount.as_u64()),
+                "update_box_creation_height": vote_parameters.update_box_creation_height,
+            })
+        }
+    };
+    Ok(json)
+}
+
 // Basic information about the oracle pool
-async fn pool_info() -> impl IntoResponse {
-    let conf = &POOL_CONFIG;
-    let network = &ORACLE_CONFIG.oracle_address.network();
+async fn pool_info(ctx: Arc<OracleContext>) -> impl IntoResponse {
+    let conf = &ctx.pool_config;
+    let network = &ctx.oracle_config.oracle_address.network();
     let address_encoder = AddressEncoder::new(*network);
     let pool_box_address = Address::P2S(
         conf.pool_box_wrapper_inputs
@@ -110,6 +166,8 @@ async fn pool_info() -> impl IntoResponse {
         "max_deviation_percent": conf.refresh_box_wrapper_inputs.contract_inputs.contract_parameters().max_deviation_percent(),
         "min_data_points": conf.refresh_box_wrapper_inputs.contract_inputs.contract_parameters().min_data_points(),
         "min_votes": conf.update_box_wrapper_inputs.contract_inputs.contract_parameters().min_votes(),
+        "oracle_reward_tokens_per_datapoint": conf.refresh_box_wrapper_inputs.contract_inputs.contract_parameters().oracle_reward_tokens_per_datapoint(),
+        "collector_reward_tokens_per_datapoint": COLLECTOR_REWARD_TOKENS_PER_DATAPOINT,
         "pool_box_address": address_encoder.address_to_str(&pool_box_address),
         "refresh_box_address": address_encoder.address_to_str(&refresh_box_address),
         "update_box_address": address_encoder.address_to_str(&update_box_address),
@@ -117,18 +175,25 @@ async fn pool_info() -> impl IntoResponse {
 }
 
 /// Status of the oracle pool
-async fn pool_status(oracle_pool: Arc<OraclePool>) -> Result<Json<serde_json::Value>, ApiError> {
-    let json = task::spawn_blocking(|| pool_status_sync(oracle_pool))
+async fn pool_status(
+    oracle_pool: Arc<OraclePool>,
+    ctx: Arc<OracleContext>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let json = task::spawn_blocking(|| pool_status_sync(oracle_pool, ctx))
         .await
         .unwrap()?;
     Ok(json)
 }
 
-fn pool_status_sync(oracle_pool: Arc<OraclePool>) -> Result<Json<serde_json::Value>, ApiError> {
-    let node_api = NodeApi::new(ORACLE_CONFIG.node_api_key.clone(), &ORACLE_CONFIG.node_url);
+fn pool_status_sync(
+    oracle_pool: Arc<OraclePool>,
+    ctx: Arc<OracleContext>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let node_api = ctx.node_api();
     let current_height = node_api.node.current_block_height()? as u32;
     let pool_box = oracle_pool.get_pool_box_source().get_pool_box()?;
-    let epoch_length = POOL_CONFIG
+    let epoch_length = ctx
+        .pool_config
         .refresh_box_wrapper_inputs
         .contract_inputs
         .contract_parameters()
@@ -136,6 +201,24 @@ fn pool_status_sync(oracle_pool: Arc<OraclePool>) -> Result<Json<serde_json::Val
     let pool_box_height = pool_box.get_box().creation_height;
     let epoch_end_height = pool_box_height + epoch_length.0 as u32;
 
+    let active_oracle_count = active_oracle_count(&oracle_pool, pool_box_height)?;
+    let pool_health = pool_health_sync(oracle_pool, ctx)?;
+
+    let json = Json(json!({
+        "latest_pool_datapoint": pool_box.rate(),
+        "latest_pool_box_height": pool_box_height,
+        "pool_box_epoch_id" : pool_box.epoch_counter(),
+        "current_block_height": current_height,
+        "epoch_end_height": epoch_end_height,
+        "reward_tokens_in_pool_box": pool_box.reward_token().amount.as_u64(),
+        "number_of_oracles": active_oracle_count,
+        "pool_health": pool_health,
+    }));
+    Ok(json)
+}
+
+/// Oracles whose datapoint was collected into the pool box or posted since
+fn active_oracle_count(oracle_pool: &OraclePool, pool_box_height: u32) -> Result<usize, ApiError> {
     let posted_boxes = oracle_pool
         .get_posted_datapoint_boxes_source()
         .get_posted_datapoint_boxes()?;
@@ -152,40 +235,70 @@ fn pool_status_sync(oracle_pool: Arc<OraclePool>) -> Result<Json<serde_json::Val
         .filter(|b| b.get_box().creation_height == pool_box_height)
         .count();
 
-    let active_oracle_count = collected_count_previous_epoch + posted_count_current_epoch;
-    let pool_health = pool_health_sync(oracle_pool)?;
+    Ok(collected_count_previous_epoch + posted_count_current_epoch)
+}
 
-    let json = Json(json!({
-        "latest_pool_datapoint": pool_box.rate(),
-        "latest_pool_box_height": pool_box_height,
-        "pool_box_epoch_id" : pool_box.epoch_counter(),
-        "current_block_height": current_height,
-        "epoch_end_height": epoch_end_height,
-        "reward_tokens_in_pool_box": pool_box.reward_token().amount.as_u64(),
-        "number_of_oracles": active_oracle_count,
-        "pool_health": pool_health,
-    }));
-    Ok(json)
+/// Pool box for EIP-23 consumers
+async fn pool_box(oracle_pool: Arc<OraclePool>) -> Result<Json<serde_json::Value>, ApiError> {
+    task::spawn_blocking(move || -> Result<Json<serde_json::Value>, ApiError> {
+        let pool_box = oracle_pool.get_pool_box_source().get_pool_box()?;
+        let json = eip23::pool_box_json(&pool_box)
+            .map_err(|e| ApiError(format!("SigmaSerializationError: {}", e)))?;
+        Ok(Json(json))
+    })
+    .await
+    .unwrap()
 }
 
-/// Block height of the Ergo blockchain
-async fn block_height() -> Result<impl IntoResponse, ApiError> {
-    let current_height = task::spawn_blocking(move || {
-        let node_api = NodeApi::new(ORACLE_CONFIG.node_api_key.clone(), &ORACLE_CONFIG.node_url);
-        node_api.node.current_block_height()
+/// Rate and epoch counter of the pool box
+async fn latest_rate(oracle_pool: Arc<OraclePool>) -> Result<Json<serde_json::Value>, ApiError> {
+    task::spawn_blocking(move || -> Result<Json<serde_json::Value>, ApiError> {
+        let pool_box = oracle_pool.get_pool_box_source().get_pool_box()?;
+        Ok(Json(eip23::latest_rate_json(&pool_box)))
     })
     .await
-    .unwrap()?;
+    .unwrap()
+}
+
+/// Pool status in the format of the oracle-core v1 frontends
+async fn frontend_data(
+    oracle_pool: Arc<OraclePool>,
+    ctx: Arc<OracleContext>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    task::spawn_blocking(move || -> Result<Json<serde_json::Value>, ApiError> {
+        let current_height = ctx.node_api().node.current_block_height()? as u32;
+        let pool_box = oracle_pool.get_pool_box_source().get_pool_box()?;
+        let refresh_contract_parameters = ctx
+            .pool_config
+            .refresh_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters();
+        let number_of_oracles =
+            active_oracle_count(&oracle_pool, pool_box.get_box().creation_height)?;
+        Ok(Json(eip23::frontend_data_json(
+            &pool_box,
+            refresh_contract_parameters.epoch_length(),
+            current_height,
+            number_of_oracles,
+            refresh_contract_parameters.oracle_reward_tokens_per_datapoint(),
+        )))
+    })
+    .await
+    .unwrap()
+}
+
+/// Block height of the Ergo blockchain
+async fn block_height(ctx: Arc<OracleContext>) -> Result<impl IntoResponse, ApiError> {
+    let current_height = task::spawn_blocking(move || ctx.node_api().node.current_block_height())
+        .await
+        .unwrap()?;
     Ok(format!("{}", current_height))
 }
 
 /// Whether the Core requires the Connector to repost a new Datapoint
-async fn require_datapoint_repost(repost_receiver: Receiver<bool>) -> impl IntoResponse {
-    let mut response_text = "false".to_string();
-    if let Ok(b) = repost_receiver.try_recv() {
-        response_text = b.to_string();
-    }
-    response_text
+async fn require_datapoint_repost(repost_receiver: watch::Receiver<bool>) -> impl IntoResponse {
+    let repost = *repost_receiver.borrow();
+    repost.to_string()
 }
 
 /// Return true if the our collected datapoint box height is the same as the pool box height
@@ -231,15 +344,21 @@ fn oracle_health_sync(oracle_pool: Arc<OraclePool>) -> Result<serde_json::Value,
     Ok(json)
 }
 
-async fn pool_health(oracle_pool: Arc<OraclePool>) -> Result<Json<serde_json::Value>, ApiError> {
-    let json = task::spawn_blocking(|| pool_health_sync(oracle_pool))
+async fn pool_health(
+    oracle_pool: Arc<OraclePool>,
+    ctx: Arc<OracleContext>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let json = task::spawn_blocking(|| pool_health_sync(oracle_pool, ctx))
         .await
         .unwrap()?;
     Ok(Json(json))
 }
-fn pool_health_sync(oracle_pool: Arc<OraclePool>) -> Result<serde_json::Value, ApiError> {
-    let pool_conf = &POOL_CONFIG;
-    let node_api = NodeApi::new(ORACLE_CONFIG.node_api_key.clone(), &ORACLE_CONFIG.node_url);
+fn pool_health_sync(
+    oracle_pool: Arc<OraclePool>,
+    ctx: Arc<OracleContext>,
+) -> Result<serde_json::Value, ApiError> {
+    let pool_conf = &ctx.pool_config;
+    let node_api = ctx.node_api();
     let current_height = node_api.node.current_block_height()? as u32;
     let pool_box_height = oracle_pool
         .get_pool_box_source()
@@ -265,34 +384,344 @@ fn pool_health_sync(oracle_pool: Arc<OraclePool>) -> Result<serde_json::Value, A
     Ok(json)
 }
 
+/// Summary of the primary pool and every extra pool run by this process
+async fn pools(
+    oracle_pool: Arc<OraclePool>,
+    ctx: Arc<OracleContext>,
+    extra_pools: Vec<Arc<PoolRunner>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let json = task::spawn_blocking(|| pools_sync(oracle_pool, ctx, extra_pools))
+        .await
+        .unwrap()?;
+    Ok(json)
+}
+
+fn pools_sync(
+    oracle_pool: Arc<OraclePool>,
+    ctx: Arc<OracleContext>,
+    extra_pools: Vec<Arc<PoolRunner>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let mut pools = vec![pool_summary("primary", &ctx, &oracle_pool)?];
+    for runner in extra_pools.iter() {
+        pools.push(pool_summary(
+            &runner.name,
+            &runner.ctx,
+            &runner.oracle_pool,
+        )?);
+    }
+    Ok(Json(json!(pools)))
+}
+
+fn pool_summary(
+    name: &str,
+    ctx: &OracleContext,
+    oracle_pool: &OraclePool,
+) -> Result<serde_json::Value, ApiError> {
+    let pool_box = oracle_pool.get_pool_box_source().get_pool_box()?;
+    Ok(json!({
+        "name": name,
+        "pool_nft_id": ctx.pool_config.token_ids.pool_nft_token_id,
+        "oracle_address": ctx.oracle_config.oracle_address.to_base58(),
+        "latest_pool_datapoint": pool_box.rate(),
+        "latest_pool_box_height": pool_box.get_box().creation_height,
+        "pool_box_epoch_id": pool_box.epoch_counter(),
+    }))
+}
+
+/// Main loop metrics in the Prometheus text format
+#[cfg(feature = "metrics")]
+async fn metrics() -> Result<String, ApiError> {
+    Ok(encode_metrics()?)
+}
+
+const DEFAULT_AUDIT_LOG_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct AuditLogParams {
+    limit: Option<usize>,
+    /// Name of an extra pool, the primary pool if not set
+    pool: Option<String>,
+}
+
+/// Recent entries of the action audit log, oldest first
+async fn audit_log(
+    params: AuditLogParams,
+    extra_pools: Vec<Arc<PoolRunner>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let path = match params.pool {
+        None => get_audit_log_file_path(),
+        Some(name) => extra_pools
+            .iter()
+            .find(|runner| runner.name == name)
+            .map(|runner| runner.audit_log_path.clone())
+            .ok_or_else(|| ApiError(format!("Unknown pool: {}", name)))?,
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_AUDIT_LOG_LIMIT);
+    let entries = task::spawn_blocking(move || read_recent_entries(&path, limit))
+        .await
+        .unwrap()?;
+    Ok(Json(json!(entries)))
+}
+
+const DEFAULT_POOL_HISTORY_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct PoolHistoryParams {
+    limit: Option<usize>,
+    /// Name of an extra pool, the primary pool if not set
+    pool: Option<String>,
+}
+
+/// Recently recorded pool epochs, oldest first
+async fn pool_history(
+    params: PoolHistoryParams,
+    extra_pools: Vec<Arc<PoolRunner>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let path = match params.pool {
+        None => get_epoch_history_file_path(),
+        Some(name) => extra_pools
+            .iter()
+            .find(|runner| runner.name == name)
+            .map(|runner| runner.epoch_history_path.clone())
+            .ok_or_else(|| ApiError(format!("Unknown pool: {}", name)))?,
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_POOL_HISTORY_LIMIT);
+    let entries = task::spawn_blocking(move || read_recent_epochs(&path, limit))
+        .await
+        .unwrap()?;
+    Ok(Json(json!(entries)))
+}
+
+#[derive(Debug, Deserialize)]
+struct GovernanceParams {
+    /// Name of an extra pool, the primary pool if not set
+    pool: Option<String>,
+}
+
+/// Votes of the ballot boxes for the pool updates
+async fn governance(
+    params: GovernanceParams,
+    oracle_pool: Arc<OraclePool>,
+    extra_pools: Vec<Arc<PoolRunner>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let oracle_pool = match params.pool {
+        None => oracle_pool,
+        Some(name) => extra_pools
+            .iter()
+            .find(|runner| runner.name == name)
+            .map(|runner| runner.oracle_pool.clone())
+            .ok_or_else(|| ApiError(format!("Unknown pool: {}", name)))?,
+    };
+    let status = task::spawn_blocking(move || {
+        governance_status(
+            oracle_pool.get_update_box_source(),
+            oracle_pool.get_ballot_boxes_source(),
+        )
+    })
+    .await
+    .unwrap()?;
+    Ok(Json(json!(status)))
+}
+
+#[derive(Debug, Deserialize)]
+struct AttestationParams {
+    /// Name of an extra pool, the primary pool if not set
+    pool: Option<String>,
+}
+
+/// Latest datapoint signed by the oracle, null if none was signed since the start
+async fn attestation(
+    params: AttestationParams,
+    primary_attestation: LatestAttestation,
+    extra_pools: Vec<Arc<PoolRunner>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let latest = match params.pool {
+        None => primary_attestation,
+        Some(name) => extra_pools
+            .iter()
+            .find(|runner| runner.name == name)
+            .map(|runner| runner.latest_attestation.clone())
+            .ok_or_else(|| ApiError(format!("Unknown pool: {}", name)))?,
+    };
+    Ok(Json(json!(latest.get())))
+}
+
 pub async fn start_rest_server(
-    repost_receiver: Receiver<bool>,
+    repost_receiver: watch::Receiver<bool>,
+    ctx: Arc<OracleContext>,
     oracle_pool: Arc<OraclePool>,
+    extra_pools: Vec<Arc<PoolRunner>>,
+    primary_attestation: LatestAttestation,
+    mut shutdown: watch::Receiver<bool>,
 ) -> Result<(), anyhow::Error> {
     let op_clone = oracle_pool.clone();
     let op_clone2 = oracle_pool.clone();
     let op_clone3 = oracle_pool.clone();
-    let app = Router::new()
+    let op_clone4 = oracle_pool.clone();
+    let op_clone5 = oracle_pool.clone();
+    let op_clone6 = oracle_pool.clone();
+    let op_clone7 = oracle_pool.clone();
+    let op_clone8 = oracle_pool.clone();
+    let ctx_clone = ctx.clone();
+    let ctx_clone2 = ctx.clone();
+    let ctx_clone3 = ctx.clone();
+    let ctx_clone4 = ctx.clone();
+    let ctx_clone5 = ctx.clone();
+    let ctx_clone6 = ctx.clone();
+    let extra_pools_clone = extra_pools.clone();
+    let extra_pools_clone2 = extra_pools.clone();
+    let extra_pools_clone3 = extra_pools.clone();
+    let extra_pools_clone4 = extra_pools.clone();
+    #[cfg(feature = "graphql")]
+    let schema = graphql::build_schema(ctx.clone(), oracle_pool.clone(), extra_pools.clone());
+    let port = ctx.oracle_config.core_api_port;
+    let router = Router::new()
         .route("/", get(root))
-        .route("/oracleInfo", get(oracle_info))
+        .route("/oracleInfo", get(|| oracle_info(ctx)))
         .route("/oracleStatus", get(|| oracle_status(oracle_pool)))
-        .route("/poolInfo", get(pool_info))
-        .route("/poolStatus", get(|| pool_status(op_clone)))
-        .route("/blockHeight", get(block_height))
+        .route("/poolInfo", get(|| pool_info(ctx_clone)))
+        .route("/poolStatus", get(|| pool_status(op_clone, ctx_clone2)))
+        .route("/blockHeight", get(|| block_height(ctx_clone3)))
+        .route("/poolBox", get(|| pool_box(op_clone6)))
+        .route("/latestRate", get(|| latest_rate(op_clone7)))
+        .route(
+            "/frontendData",
+            get(|| frontend_data(op_clone8, ctx_clone6)),
+        )
         .route("/oracleHealth", get(|| oracle_health(op_clone2)))
-        .route("/poolHealth", get(|| pool_health(op_clone3)))
+        .route("/poolHealth", get(|| pool_health(op_clone3, ctx_clone4)))
+        .route(
+            "/pools",
+            get(|| pools(op_clone4, ctx_clone5, extra_pools_clone)),
+        )
+        .route(
+            "/auditLog",
+            get(|Query(params): Query<AuditLogParams>| audit_log(params, extra_pools)),
+        )
+        .route(
+            "/poolHistory",
+            get(|Query(params): Query<PoolHistoryParams>| pool_history(params, extra_pools_clone2)),
+        )
+        .route(
+            "/governance",
+            get(|Query(params): Query<GovernanceParams>| {
+                governance(params, op_clone5, extra_pools_clone3)
+            }),
+        )
+        .route(
+            "/attestation",
+            get(|Query(params): Query<AttestationParams>| {
+                attestation(params, primary_attestation, extra_pools_clone4)
+            }),
+        )
         .route(
             "/requireDatapointRepost",
             get(|| require_datapoint_repost(repost_receiver)),
-        )
-        .layer(
-            CorsLayer::new()
-                .allow_origin(tower_http::cors::Any)
-                .allow_methods([axum::http::Method::GET]),
         );
-    let addr = SocketAddr::from(([0, 0, 0, 0], get_core_api_port().parse().unwrap()));
+    #[cfg(feature = "metrics")]
+    let router = router.route("/metrics", get(metrics));
+    let cors = CorsLayer::new()
+        .allow_origin(tower_http::cors::Any)
+        .allow_methods([axum::http::Method::GET]);
+    #[cfg(feature = "graphql")]
+    let (router, cors) = {
+        let schema_clone = schema.clone();
+        let router = router.route(
+            "/graphql",
+            get(|req: GraphQLRequest| graphql::execute(schema, req))
+                .post(|req: GraphQLRequest| graphql::execute(schema_clone, req)),
+        );
+        let cors = cors
+            .allow_methods([axum::http::Method::GET, axum::http::Method::POST])
+            .allow_headers([axum::http::header::CONTENT_TYPE]);
+        (router, cors)
+    };
+    let app = router.layer(cors);
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    axum::Server::try_bind(&addr)?
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(async move {
+            let _ = shutdown.changed().await;
+        })
+        .await?;
+    Ok(())
+}
+
+/// Basic welcome endpoint of the watch-only monitor
+async fn monitor_root() -> &'static str {
+    "This is a watch-only Oracle Core monitor. Please use one of the endpoints to read the pool state:
+        /poolStatus - pool box, datapoints posted in the current epoch and raised alerts, as of the last check
+        /poolHealth - returns OK if no alert was raised at the last check
+        /poolBox - pool box in the node JSON format with its serialized bytes, for EIP-23 consumers
+        /latestRate - rate and epoch counter of the pool box
+        /metrics - monitor metrics in the Prometheus text format (with the `metrics` feature)
+        "
+}
+
+fn latest_monitor_snapshot(monitor: &PoolMonitor) -> Result<MonitorSnapshot, ApiError> {
+    monitor
+        .latest()
+        .ok_or_else(|| ApiError("The pool has not been checked yet".to_string()))
+}
+
+async fn monitor_pool_status(
+    monitor: Arc<PoolMonitor>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let snapshot = latest_monitor_snapshot(&monitor)?;
+    Ok(Json(json!(snapshot.status)))
+}
+
+async fn monitor_pool_health(
+    monitor: Arc<PoolMonitor>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let snapshot = latest_monitor_snapshot(&monitor)?;
+    Ok(Json(json!({
+        "status": if snapshot.status.is_healthy() { "OK" } else { "DOWN" },
+        "details": snapshot.status,
+    })))
+}
+
+async fn monitor_pool_box(monitor: Arc<PoolMonitor>) -> Result<Json<serde_json::Value>, ApiError> {
+    let snapshot = latest_monitor_snapshot(&monitor)?;
+    let json = eip23::pool_box_json(&snapshot.pool_box)
+        .map_err(|e| ApiError(format!("SigmaSerializationError: {}", e)))?;
+    Ok(Json(json))
+}
+
+async fn monitor_latest_rate(
+    monitor: Arc<PoolMonitor>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let snapshot = latest_monitor_snapshot(&monitor)?;
+    Ok(Json(eip23::latest_rate_json(&snapshot.pool_box)))
+}
+
+/// Read-only API of the `monitor` command, serving the result of its last check
+pub async fn start_monitor_server(
+    monitor: Arc<PoolMonitor>,
+    port: u16,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<(), anyhow::Error> {
+    let monitor_clone = monitor.clone();
+    let monitor_clone2 = monitor.clone();
+    let monitor_clone3 = monitor.clone();
+    let router = Router::new()
+        .route("/", get(monitor_root))
+        .route("/poolStatus", get(|| monitor_pool_status(monitor)))
+        .route("/poolHealth", get(|| monitor_pool_health(monitor_clone)))
+        .route("/poolBox", get(|| monitor_pool_box(monitor_clone2)))
+        .route("/latestRate", get(|| monitor_latest_rate(monitor_clone3)));
+    #[cfg(feature = "metrics")]
+    let router = router.route("/metrics", get(metrics));
+    let cors = CorsLayer::new()
+        .allow_origin(tower_http::cors::Any)
+        .allow_methods([axum::http::Method::GET]);
+    let app = router.layer(cors);
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
     axum::Server::try_bind(&addr)?
         .serve(app.into_make_service())
+        .with_graceful_shutdown(async move {
+            let _ = shutdown.changed().await;
+        })
         .await?;
     Ok(())
 }
@@ -311,6 +740,18 @@ impl From<NodeError> for ApiError {
     }
 }
 
+impl From<EpochHistoryError> for ApiError {
+    fn from(err: EpochHistoryError) -> Self {
+        ApiError(format!("EpochHistoryError: {}", err))
+    }
+}
+
+impl From<AuditLogError> for ApiError {
+    fn from(err: AuditLogError) -> Self {
+        ApiError(format!("AuditLogError: {}", err))
+    }
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         (StatusCode::INTERNAL_SERVER_ERROR, self.0).into_response()
@@ -0,0 +1,92 @@
+//! Endpoints in the formats of the EIP-23 consumers and of the oracle-core v1 frontends, so the
+//! dApps and explorer frontends integrating with those APIs can point at this oracle core.
+//!
+//! `/poolBox` returns the pool box in the node's JSON format along with its serialized bytes
+//! (base16) for the txs using it as a data input, `/latestRate` the rate and epoch counter of the
+//! pool box (R4 and R5 of EIP-23), and `/frontendData` the fields of the oracle-core v1 endpoint.
+//! The v1 epoch preparation stage no longer exists, the pool is always in its live epoch.
+
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
+use serde_json::json;
+
+use crate::box_kind::PoolBox;
+use crate::box_kind::PoolBoxWrapper;
+use crate::oracle_types::EpochLength;
+use crate::pool_commands::refresh::pool_reward_tokens_per_datapoint;
+use crate::spec_token::TokenIdKind;
+
+/// Average time between two Ergo blocks
+pub const BLOCK_TIME_MINUTES: u32 = 2;
+
+pub const LIVE_EPOCH_STAGE: &str = "Live Epoch";
+
+pub fn pool_box_json(
+    pool_box: &PoolBoxWrapper,
+) -> Result<serde_json::Value, SigmaSerializationError> {
+    let ergo_box = pool_box.get_box();
+    let reward_token = pool_box.reward_token();
+    Ok(json!({
+        "box_id": String::from(ergo_box.box_id()),
+        "bytes": base16::encode_lower(&ergo_box.sigma_serialize_bytes()?),
+        "creation_height": ergo_box.creation_height,
+        "rate": pool_box.rate(),
+        "epoch_counter": pool_box.epoch_counter().0,
+        "pool_nft_id": String::from(pool_box.pool_nft_token().token_id.token_id()),
+        "reward_token_id": String::from(reward_token.token_id.token_id()),
+        "reward_token_amount": reward_token.amount.as_u64(),
+        "box": ergo_box,
+    }))
+}
+
+pub fn latest_rate_json(pool_box: &PoolBoxWrapper) -> serde_json::Value {
+    json!({
+        "rate": pool_box.rate(),
+        "epoch_counter": pool_box.epoch_counter().0,
+        "pool_box_id": String::from(pool_box.get_box().box_id()),
+        "pool_box_height": pool_box.get_box().creation_height,
+    })
+}
+
+/// Reward tokens in the pool box as a percentage (at most 100) of the rewards of an epoch in which
+/// every active oracle posts
+fn pool_funded_percentage(
+    reward_tokens: u64,
+    number_of_oracles: usize,
+    oracle_reward_per_datapoint: u64,
+) -> u64 {
+    let epoch_rewards =
+        number_of_oracles as u64 * pool_reward_tokens_per_datapoint(oracle_reward_per_datapoint);
+    if epoch_rewards == 0 {
+        return 100;
+    }
+    (reward_tokens * 100 / epoch_rewards).min(100)
+}
+
+pub fn frontend_data_json(
+    pool_box: &PoolBoxWrapper,
+    epoch_length: EpochLength,
+    current_height: u32,
+    number_of_oracles: usize,
+    oracle_reward_per_datapoint: u64,
+) -> serde_json::Value {
+    let epoch_length_blocks = epoch_length.0.max(0) as u32;
+    let epoch_end_height = pool_box.get_box().creation_height + epoch_length_blocks;
+    json!({
+        "latest_price": pool_box.rate(),
+        "live_epoch_id": pool_box.epoch_counter().0,
+        "current_pool_stage": LIVE_EPOCH_STAGE,
+        "epoch_ends": epoch_end_height.saturating_sub(current_height) * BLOCK_TIME_MINUTES,
+        "epoch_end_height": epoch_end_height,
+        "posting_schedule": {
+            "blocks": epoch_length_blocks,
+            "minutes": epoch_length_blocks * BLOCK_TIME_MINUTES,
+        },
+        "number_of_oracles": number_of_oracles,
+        "pool_funded_percentage": pool_funded_percentage(
+            *pool_box.reward_token().amount.as_u64(),
+            number_of_oracles,
+            oracle_reward_per_datapoint,
+        ),
+    })
+}
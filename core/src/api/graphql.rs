@@ -0,0 +1,235 @@
+//! GraphQL endpoint of the REST API server (`graphql` feature). It serves the data of the REST
+//! endpoints (pool status, posted datapoints, epoch history and oracle list) of every pool run by
+//! this process in one query, and only the selected fields are fetched from the node.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_graphql::{EmptyMutation, EmptySubscription, Object, Result, Schema, SimpleObject};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use tokio::task;
+
+use crate::box_kind::{OracleBox, OracleBoxWrapper, PoolBox};
+use crate::context::OracleContext;
+use crate::epoch_history::{
+    get_epoch_history_file_path, public_key_base16, read_recent_entries, EpochHistoryEntry,
+};
+use crate::multi_pool::PoolRunner;
+use crate::oracle_state::OraclePool;
+use crate::spec_token::TokenIdKind;
+
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+pub type OracleSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn build_schema(
+    ctx: Arc<OracleContext>,
+    oracle_pool: Arc<OraclePool>,
+    extra_pools: Vec<Arc<PoolRunner>>,
+) -> OracleSchema {
+    let mut pools = vec![Pool {
+        name: "primary".to_string(),
+        ctx: PoolContext::Primary(ctx),
+        oracle_pool,
+        epoch_history_path: get_epoch_history_file_path(),
+    }];
+    pools.extend(extra_pools.into_iter().map(|runner| Pool {
+        name: runner.name.clone(),
+        oracle_pool: runner.oracle_pool.clone(),
+        epoch_history_path: runner.epoch_history_path.clone(),
+        ctx: PoolContext::Extra(runner),
+    }));
+    Schema::build(QueryRoot { pools }, EmptyMutation, EmptySubscription).finish()
+}
+
+pub async fn execute(schema: OracleSchema, req: GraphQLRequest) -> GraphQLResponse {
+    schema.execute(req.into_inner()).await.into()
+}
+
+/// Run a resolver reading the node on the blocking thread pool
+async fn blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    task::spawn_blocking(f).await.unwrap()
+}
+
+pub struct QueryRoot {
+    pools: Vec<Pool>,
+}
+
+#[Object]
+impl QueryRoot {
+    /// The primary pool and every extra pool run by this process
+    async fn pools(&self) -> Vec<Pool> {
+        self.pools.clone()
+    }
+
+    /// The pool with the given name, the primary pool if not set
+    async fn pool(&self, name: Option<String>) -> Result<Pool> {
+        let name = name.unwrap_or_else(|| "primary".to_string());
+        self.pools
+            .iter()
+            .find(|pool| pool.name == name)
+            .cloned()
+            .ok_or_else(|| format!("Unknown pool: {}", name).into())
+    }
+}
+
+#[derive(Clone)]
+enum PoolContext {
+    Primary(Arc<OracleContext>),
+    Extra(Arc<PoolRunner>),
+}
+
+#[derive(Clone)]
+pub struct Pool {
+    name: String,
+    ctx: PoolContext,
+    oracle_pool: Arc<OraclePool>,
+    epoch_history_path: PathBuf,
+}
+
+impl Pool {
+    fn ctx(&self) -> &OracleContext {
+        match &self.ctx {
+            PoolContext::Primary(ctx) => ctx,
+            PoolContext::Extra(runner) => &runner.ctx,
+        }
+    }
+}
+
+#[Object]
+impl Pool {
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn pool_nft_id(&self) -> String {
+        String::from(
+            self.ctx()
+                .pool_config
+                .token_ids
+                .pool_nft_token_id
+                .token_id(),
+        )
+    }
+
+    async fn oracle_address(&self) -> String {
+        self.ctx().oracle_config.oracle_address.to_base58()
+    }
+
+    /// Latest pool box and the current epoch
+    async fn status(&self) -> Result<PoolStatus> {
+        let pool = self.clone();
+        blocking(move || {
+            let ctx = pool.ctx();
+            let current_block_height = ctx.node_api().node.current_block_height()? as u32;
+            let pool_box = pool.oracle_pool.get_pool_box_source().get_pool_box()?;
+            let epoch_length = ctx
+                .pool_config
+                .refresh_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .epoch_length();
+            let latest_pool_box_height = pool_box.get_box().creation_height;
+            Ok(PoolStatus {
+                latest_pool_datapoint: pool_box.rate(),
+                latest_pool_box_height,
+                pool_box_epoch_id: pool_box.epoch_counter().0,
+                current_block_height,
+                epoch_end_height: latest_pool_box_height + epoch_length.0 as u32,
+                reward_tokens_in_pool_box: *pool_box.reward_token().amount.as_u64(),
+            })
+        })
+        .await
+    }
+
+    /// Datapoints posted and not collected yet
+    async fn datapoints(&self) -> Result<Vec<Datapoint>> {
+        let oracle_pool = self.oracle_pool.clone();
+        blocking(move || {
+            let posted_boxes = oracle_pool
+                .get_posted_datapoint_boxes_source()
+                .get_posted_datapoint_boxes()?;
+            Ok(posted_boxes
+                .iter()
+                .map(|b| Datapoint {
+                    oracle_public_key: public_key_base16(&b.public_key()),
+                    rate: i64::from(b.rate()),
+                    epoch_id: b.epoch_counter().0,
+                    height: b.get_box().creation_height,
+                })
+                .collect())
+        })
+        .await
+    }
+
+    /// Oracle boxes of the pool, posted and collected
+    async fn oracles(&self) -> Result<Vec<Oracle>> {
+        let oracle_pool = self.oracle_pool.clone();
+        blocking(move || {
+            let posted_boxes = oracle_pool
+                .get_posted_datapoint_boxes_source()
+                .get_posted_datapoint_boxes()?
+                .into_iter()
+                .map(OracleBoxWrapper::Posted);
+            let collected_boxes = oracle_pool
+                .get_collected_datapoint_boxes_source()
+                .get_collected_datapoint_boxes()?
+                .into_iter()
+                .map(OracleBoxWrapper::Collected);
+            Ok(posted_boxes
+                .chain(collected_boxes)
+                .map(|b| Oracle {
+                    public_key: public_key_base16(&b.public_key()),
+                    status: match b {
+                        OracleBoxWrapper::Posted(_) => "posted".to_string(),
+                        OracleBoxWrapper::Collected(_) => "collected".to_string(),
+                    },
+                    height: b.get_box().creation_height,
+                    reward_tokens: *b.reward_token().amount.as_u64(),
+                })
+                .collect())
+        })
+        .await
+    }
+
+    /// Recently recorded pool epochs, oldest first
+    async fn history(&self, limit: Option<u32>) -> Result<Vec<EpochHistoryEntry>> {
+        let path = self.epoch_history_path.clone();
+        let limit = limit.map_or(DEFAULT_HISTORY_LIMIT, |limit| limit as usize);
+        blocking(move || Ok(read_recent_entries(&path, limit)?)).await
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct PoolStatus {
+    latest_pool_datapoint: i64,
+    latest_pool_box_height: u32,
+    pool_box_epoch_id: u32,
+    current_block_height: u32,
+    epoch_end_height: u32,
+    reward_tokens_in_pool_box: u64,
+}
+
+#[derive(SimpleObject)]
+pub struct Datapoint {
+    /// Public key (base16) of the oracle
+    oracle_public_key: String,
+    rate: i64,
+    epoch_id: u32,
+    height: u32,
+}
+
+#[derive(SimpleObject)]
+pub struct Oracle {
+    /// Public key (base16) of the oracle
+    public_key: String,
+    /// `posted` or `collected`
+    status: String,
+    /// Creation height of the oracle box
+    height: u32,
+    reward_tokens: u64,
+}
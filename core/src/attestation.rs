@@ -0,0 +1,264 @@
+//! Signed off-chain attestations of the aggregated datapoints (`datapoint_attestations` in the
+//! oracle config).
+//!
+//! Every datapoint fetched for a post is signed with the oracle key, so consumers reading the rate
+//! between two on-chain posts can check that it comes from the oracle. The signed message is the
+//! UTF-8 string `oracle-core-attestation:<pool NFT id>:<rate>:<timestamp>:<height>` and the
+//! signature is the Schnorr signature of the oracle P2PK address over it, as made by the Ergo
+//! wallets (`verify_signature` of sigma-rust). The secret of the oracle address is read from the
+//! node wallet once, when the main loop starts.
+//!
+//! The latest attestation of a pool is kept in memory in its [`LatestAttestation`], shared with
+//! the REST API, along with its IPFS CID once pinned (see [`crate::ipfs`]).
+
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use ergo_lib::ergo_chain_types::EcPoint;
+use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+use ergo_lib::ergotree_interpreter::sigma_protocol::verifier::verify_signature;
+use ergo_lib::ergotree_interpreter::sigma_protocol::verifier::VerifierError;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
+use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::SigmaBoolean;
+use ergo_lib::wallet::Wallet;
+use ergo_lib::wallet::WalletError;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::audit_log::unix_time_secs;
+use crate::epoch_history::public_key_base16;
+use crate::node_interface::node_api::NodeApi;
+use crate::node_interface::node_api::NodeApiError;
+use crate::oracle_config::OracleConfig;
+use crate::oracle_types::BlockHeight;
+use crate::oracle_types::Rate;
+
+const MESSAGE_PREFIX: &str = "oracle-core-attestation";
+
+#[derive(Debug, Error)]
+pub enum AttestationError {
+    #[error("node api error: {0}")]
+    NodeApi(#[from] NodeApiError),
+    #[error("oracle_address is not a P2PK address")]
+    NotP2PkOracleAddress,
+    #[error("the node returned an invalid secret for the oracle address")]
+    InvalidSecret,
+    #[error("the secret returned by the node does not belong to the oracle address")]
+    SecretMismatch,
+    #[error("wallet error: {0}")]
+    Wallet(#[from] WalletError),
+    #[error("invalid base16 field: {0}")]
+    InvalidEncoding(String),
+    #[error("verifier error: {0}")]
+    Verifier(#[from] VerifierError),
+}
+
+/// Datapoint signed by the oracle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attestation {
+    pub pool_nft_id: String,
+    pub rate: i64,
+    pub timestamp: u64,
+    pub height: u32,
+    pub oracle_address: String,
+    /// Public key (base16) of the oracle address
+    pub public_key: String,
+    /// Base16 Schnorr signature of the message
+    pub signature: String,
+    /// CID of the attestation pinned to IPFS, not signed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipfs_cid: Option<String>,
+}
+
+impl Attestation {
+    /// The signed message
+    pub fn message(&self) -> String {
+        message(&self.pool_nft_id, self.rate, self.timestamp, self.height)
+    }
+
+    /// Whether the signature of the message is valid for the `public_key`
+    pub fn verify(&self) -> Result<bool, AttestationError> {
+        let public_key_bytes = base16::decode(&self.public_key)
+            .map_err(|_| AttestationError::InvalidEncoding("public_key".to_string()))?;
+        let public_key = EcPoint::sigma_parse_bytes(&public_key_bytes)
+            .map_err(|_| AttestationError::InvalidEncoding("public_key".to_string()))?;
+        let signature = base16::decode(&self.signature)
+            .map_err(|_| AttestationError::InvalidEncoding("signature".to_string()))?;
+        Ok(verify_signature(
+            SigmaBoolean::from(ProveDlog::new(public_key)),
+            self.message().as_bytes(),
+            &signature,
+        )?)
+    }
+}
+
+fn message(pool_nft_id: &str, rate: i64, timestamp: u64, height: u32) -> String {
+    format!(
+        "{}:{}:{}:{}:{}",
+        MESSAGE_PREFIX, pool_nft_id, rate, timestamp, height
+    )
+}
+
+/// Latest attestation signed for a pool since the start, shared by its main loop, its IPFS pins and
+/// the REST API
+#[derive(Debug, Clone, Default)]
+pub struct LatestAttestation(Arc<Mutex<Option<Attestation>>>);
+
+impl LatestAttestation {
+    pub fn get(&self) -> Option<Attestation> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn set(&self, attestation: Attestation) {
+        *self.0.lock().unwrap() = Some(attestation);
+    }
+
+    /// Set the CID of the latest attestation, unless a newer one was signed meanwhile
+    pub fn record_ipfs_cid(&self, signature: &str, cid: String) {
+        if let Some(latest) = self.0.lock().unwrap().as_mut() {
+            if latest.signature == signature {
+                latest.ipfs_cid = Some(cid);
+            }
+        }
+    }
+}
+
+/// Signer of the datapoints of a pool with the oracle key
+pub struct AttestationSigner {
+    latest: LatestAttestation,
+    pool_nft_id: String,
+    oracle_address: String,
+    public_key: ProveDlog,
+    secret: DlogProverInput,
+}
+
+impl AttestationSigner {
+    pub fn new(
+        latest: LatestAttestation,
+        pool_nft_id: String,
+        oracle_address: String,
+        public_key: ProveDlog,
+        secret: DlogProverInput,
+    ) -> Result<Self, AttestationError> {
+        if secret.public_image() != public_key {
+            return Err(AttestationError::SecretMismatch);
+        }
+        Ok(AttestationSigner {
+            latest,
+            pool_nft_id,
+            oracle_address,
+            public_key,
+            secret,
+        })
+    }
+
+    /// Read the secret of the oracle address from the node wallet
+    pub fn from_node(
+        latest: LatestAttestation,
+        pool_nft_id: String,
+        oracle_config: &OracleConfig,
+        node_api: &NodeApi,
+    ) -> Result<Self, AttestationError> {
+        let public_key = oracle_config
+            .oracle_address_p2pk()
+            .map_err(|_| AttestationError::NotP2PkOracleAddress)?;
+        let secret_bytes = node_api.get_private_key(&oracle_config.oracle_address)?;
+        let secret = <[u8; 32]>::try_from(secret_bytes)
+            .ok()
+            .and_then(|bytes| DlogProverInput::from_bytes(&bytes))
+            .ok_or(AttestationError::InvalidSecret)?;
+        Self::new(
+            latest,
+            pool_nft_id,
+            oracle_config.oracle_address.to_base58(),
+            public_key,
+            secret,
+        )
+    }
+
+    /// Sign the datapoint and keep it as the latest attestation of the pool
+    pub fn sign(&self, rate: Rate, height: BlockHeight) -> Result<Attestation, AttestationError> {
+        let rate: i64 = rate.into();
+        let timestamp = unix_time_secs();
+        let wallet = Wallet::from_secrets(vec![self.secret.clone().into()]);
+        let signature = wallet.sign_message(
+            SigmaBoolean::from(self.public_key.clone()),
+            message(&self.pool_nft_id, rate, timestamp, height.0).as_bytes(),
+        )?;
+        let attestation = Attestation {
+            pool_nft_id: self.pool_nft_id.clone(),
+            rate,
+            timestamp,
+            height: height.0,
+            oracle_address: self.oracle_address.clone(),
+            public_key: public_key_base16(&self.public_key.h),
+            signature: base16::encode_lower(&signature),
+            ipfs_cid: None,
+        };
+        self.latest.set(attestation.clone());
+        Ok(attestation)
+    }
+
+    pub fn latest_attestation(&self) -> &LatestAttestation {
+        &self.latest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer(latest: &LatestAttestation) -> AttestationSigner {
+        let secret = DlogProverInput::random();
+        AttestationSigner::new(
+            latest.clone(),
+            "abcd".to_string(),
+            "9hEQHEMyY1K1vs79vJXFtNjr2dbQbtWXF99oVWGJ5c4xbcLdBsw".to_string(),
+            secret.public_image(),
+            secret,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let latest = LatestAttestation::default();
+        let attestation = signer(&latest)
+            .sign(Rate::from(250), BlockHeight(1000))
+            .unwrap();
+        assert!(attestation
+            .message()
+            .starts_with("oracle-core-attestation:abcd:250:"));
+        assert!(attestation.verify().unwrap());
+        assert_eq!(latest.get(), Some(attestation.clone()));
+        // the attestations of another pool are kept apart
+        assert_eq!(LatestAttestation::default().get(), None);
+        latest.record_ipfs_cid("other signature", "bafkreo".to_string());
+        assert_eq!(latest.get().unwrap().ipfs_cid, None);
+        latest.record_ipfs_cid(&attestation.signature, "bafkrei".to_string());
+        assert_eq!(latest.get().unwrap().ipfs_cid.as_deref(), Some("bafkrei"));
+
+        let tampered = Attestation {
+            rate: 251,
+            ..attestation
+        };
+        assert!(!tampered.verify().unwrap());
+    }
+
+    #[test]
+    fn test_secret_mismatch() {
+        let secret = DlogProverInput::random();
+        assert!(matches!(
+            AttestationSigner::new(
+                LatestAttestation::default(),
+                "abcd".to_string(),
+                "9hEQHEMyY1K1vs79vJXFtNjr2dbQbtWXF99oVWGJ5c4xbcLdBsw".to_string(),
+                DlogProverInput::random().public_image(),
+                secret,
+            ),
+            Err(AttestationError::SecretMismatch)
+        ));
+    }
+}
@@ -0,0 +1,506 @@
+//! Append-only audit log of the actions built by the main loop.
+//!
+//! Every tx of a built action is written as a JSON line with its inputs, outputs and submission
+//! result. Confirmations are appended later as separate lines referencing the tx id, so the file is
+//! never rewritten. Entries are merged with their confirmations when the log is read back (REST API
+//! `/auditLog` and the `print-audit-log` command). Posted datapoints can be exported to CSV with
+//! the `export-history` command.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::ergotree_ir::chain::address::Address;
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+use once_cell::sync;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::actions::PoolAction;
+use crate::datapoint_source::SourceRate;
+use crate::node_interface::node_api::NodeApiError;
+
+pub static AUDIT_LOG_DIR_PATH: sync::OnceCell<PathBuf> = sync::OnceCell::new();
+
+pub const AUDIT_LOG_FILE_NAME: &str = "audit_log.jsonl";
+
+/// Submitted txs that are not confirmed within this time are no longer checked for confirmation
+const CONFIRMATION_TRACKING_SECS: u64 = 60 * 60;
+
+pub fn get_audit_log_file_path() -> PathBuf {
+    AUDIT_LOG_DIR_PATH.get().unwrap().join(AUDIT_LOG_FILE_NAME)
+}
+
+#[derive(Debug, Error)]
+pub enum AuditLogError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serde error: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SubmissionResult {
+    Submitted,
+    Failed {
+        error: String,
+    },
+    /// Built in read-only mode, never submitted
+    NotSubmitted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditLogToken {
+    pub token_id: String,
+    pub amount: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditLogOutput {
+    pub address: String,
+    pub value: u64,
+    pub tokens: Vec<AuditLogToken>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    /// Unix time in seconds
+    pub timestamp: u64,
+    pub action: String,
+    pub tx_id: String,
+    pub input_box_ids: Vec<String>,
+    pub outputs: Vec<AuditLogOutput>,
+    pub submission: SubmissionResult,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmation_height: Option<u32>,
+    /// Datapoint posted by a publish tx
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub datapoint: Option<AuditLogDatapoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditLogDatapoint {
+    /// Height the tx was built at
+    pub height: u32,
+    pub rate: i64,
+    /// Raw rates of the sources the datapoint was aggregated from
+    pub sources: Vec<SourceRate>,
+}
+
+impl AuditLogEntry {
+    pub fn new(action: &str, tx: &UnsignedTransaction, network_prefix: NetworkPrefix) -> Self {
+        let outputs = tx
+            .output_candidates
+            .iter()
+            .map(|candidate| AuditLogOutput {
+                address: Address::recreate_from_ergo_tree(&candidate.ergo_tree)
+                    .map(|addr| NetworkAddress::new(network_prefix, &addr).to_base58())
+                    .unwrap_or_else(|_| "<unknown>".to_string()),
+                value: *candidate.value.as_u64(),
+                tokens: candidate
+                    .tokens
+                    .iter()
+                    .flat_map(|tokens| tokens.iter())
+                    .map(|token| AuditLogToken {
+                        token_id: String::from(token.token_id),
+                        amount: *token.amount.as_u64(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        AuditLogEntry {
+            timestamp: unix_time_secs(),
+            action: action.to_string(),
+            tx_id: String::from(tx.id()),
+            input_box_ids: tx
+                .inputs
+                .iter()
+                .map(|input| String::from(input.box_id))
+                .collect(),
+            outputs,
+            submission: SubmissionResult::NotSubmitted,
+            confirmation_height: None,
+            datapoint: None,
+        }
+    }
+}
+
+/// Line of the log file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum AuditLogRecord {
+    Action(AuditLogEntry),
+    Confirmation {
+        timestamp: u64,
+        tx_id: String,
+        height: u32,
+    },
+}
+
+/// Source of the inclusion height of the submitted txs
+pub trait TxConfirmationSource {
+    /// Height of the block including the tx, `None` if it is not confirmed (yet)
+    fn get_tx_inclusion_height(&self, tx_id: &str) -> Result<Option<u32>, NodeApiError>;
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    /// Submitted txs waiting for a confirmation (tx id, submission time)
+    pending: Vec<(String, u64)>,
+}
+
+impl AuditLog {
+    /// Open the log at the given path, picking up the submitted txs that are still unconfirmed
+    pub fn open(path: PathBuf) -> Result<Self, AuditLogError> {
+        let now = unix_time_secs();
+        let pending = read_entries(&path)?
+            .into_iter()
+            .filter(|e| {
+                e.submission == SubmissionResult::Submitted
+                    && e.confirmation_height.is_none()
+                    && e.timestamp + CONFIRMATION_TRACKING_SECS > now
+            })
+            .map(|e| (e.tx_id, e.timestamp))
+            .collect();
+        Ok(AuditLog { path, pending })
+    }
+
+    /// Entries for every tx of the action, not submitted yet. The datapoint is attached to the
+    /// publish tx.
+    pub fn entries_for_action(
+        action_label: &str,
+        action: &PoolAction,
+        network_prefix: NetworkPrefix,
+        datapoint: Option<AuditLogDatapoint>,
+    ) -> Vec<AuditLogEntry> {
+        match action {
+            PoolAction::Refresh(action) => {
+                vec![AuditLogEntry::new(action_label, &action.tx, network_prefix)]
+            }
+            PoolAction::PublishDatapoint(action) => vec![AuditLogEntry {
+                datapoint,
+                ..AuditLogEntry::new(action_label, &action.tx, network_prefix)
+            }],
+            PoolAction::PublishAndRefresh(action) => vec![
+                AuditLogEntry {
+                    datapoint,
+                    ..AuditLogEntry::new(action_label, &action.publish.tx, network_prefix)
+                },
+                AuditLogEntry::new(action_label, &action.refresh.tx, network_prefix),
+            ],
+        }
+    }
+
+    pub fn append(&mut self, entry: AuditLogEntry) -> Result<(), AuditLogError> {
+        if entry.submission == SubmissionResult::Submitted {
+            self.pending.push((entry.tx_id.clone(), entry.timestamp));
+        }
+        self.append_record(&AuditLogRecord::Action(entry))
+    }
+
+    /// Append a confirmation for every pending tx included in a block by now
+    pub fn record_confirmations(
+        &mut self,
+        source: &dyn TxConfirmationSource,
+    ) -> Result<(), AuditLogError> {
+        let now = unix_time_secs();
+        let mut still_pending = Vec::new();
+        for (tx_id, submitted_at) in std::mem::take(&mut self.pending) {
+            match source.get_tx_inclusion_height(&tx_id) {
+                Ok(Some(height)) => self.append_record(&AuditLogRecord::Confirmation {
+                    timestamp: now,
+                    tx_id,
+                    height,
+                })?,
+                Ok(None) if submitted_at + CONFIRMATION_TRACKING_SECS > now => {
+                    still_pending.push((tx_id, submitted_at))
+                }
+                Ok(None) => log::warn!("Audit log: tx {} was not confirmed in time", tx_id),
+                Err(e) => {
+                    log::debug!("Audit log: failed to check tx {}: {}", tx_id, e);
+                    still_pending.push((tx_id, submitted_at));
+                }
+            }
+        }
+        self.pending = still_pending;
+        Ok(())
+    }
+
+    fn append_record(&self, record: &AuditLogRecord) -> Result<(), AuditLogError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// All entries of the log with their confirmation heights, oldest first
+pub fn read_entries(path: &Path) -> Result<Vec<AuditLogEntry>, AuditLogError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    let mut confirmations = HashMap::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line)? {
+            AuditLogRecord::Action(entry) => entries.push(entry),
+            AuditLogRecord::Confirmation { tx_id, height, .. } => {
+                confirmations.insert(tx_id, height);
+            }
+        }
+    }
+    for entry in entries.iter_mut() {
+        if let Some(height) = confirmations.get(&entry.tx_id) {
+            entry.confirmation_height = Some(*height);
+        }
+    }
+    Ok(entries)
+}
+
+/// The last `limit` entries of the log, oldest first
+pub fn read_recent_entries(path: &Path, limit: usize) -> Result<Vec<AuditLogEntry>, AuditLogError> {
+    let entries = read_entries(path)?;
+    let skip = entries.len().saturating_sub(limit);
+    Ok(entries.into_iter().skip(skip).collect())
+}
+
+/// Print the last `limit` entries of the log
+pub fn print_audit_log(path: &Path, limit: usize) -> Result<(), AuditLogError> {
+    for entry in read_recent_entries(path, limit)? {
+        let submission = match &entry.submission {
+            SubmissionResult::Submitted => "submitted".to_string(),
+            SubmissionResult::Failed { error } => format!("failed ({})", error),
+            SubmissionResult::NotSubmitted => "not submitted".to_string(),
+        };
+        let confirmation = match entry.confirmation_height {
+            Some(height) => format!("confirmed at height {}", height),
+            None => "unconfirmed".to_string(),
+        };
+        println!(
+            "{} {} tx {}: {}, {}",
+            entry.timestamp, entry.action, entry.tx_id, submission, confirmation
+        );
+        println!("  inputs: {}", entry.input_box_ids.join(", "));
+        for output in entry.outputs {
+            let tokens = output
+                .tokens
+                .iter()
+                .map(|t| format!("{} {}", t.amount, t.token_id))
+                .collect::<Vec<String>>()
+                .join(", ");
+            println!(
+                "  output: {} nanoERG to {} [{}]",
+                output.value, output.address, tokens
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Write the submitted datapoints built between the given heights (inclusive) as CSV, with a
+/// column per source holding its raw rate
+pub fn export_datapoints_csv(
+    path: &Path,
+    from_height: Option<u32>,
+    to_height: Option<u32>,
+    out: &mut dyn Write,
+) -> Result<(), AuditLogError> {
+    let posted: Vec<(AuditLogEntry, AuditLogDatapoint)> = read_entries(path)?
+        .into_iter()
+        .filter(|e| e.submission == SubmissionResult::Submitted)
+        .filter_map(|e| e.datapoint.clone().map(|d| (e, d)))
+        .filter(|(_, d)| {
+            from_height.map_or(true, |h| d.height >= h) && to_height.map_or(true, |h| d.height <= h)
+        })
+        .collect();
+    let source_names: BTreeSet<&str> = posted
+        .iter()
+        .flat_map(|(_, d)| d.sources.iter().map(|s| s.source.as_str()))
+        .collect();
+    let mut header = vec!["height", "timestamp", "value", "tx_id"];
+    header.extend(source_names.iter());
+    writeln!(out, "{}", header.join(","))?;
+    for (entry, datapoint) in posted.iter() {
+        let mut row = vec![
+            datapoint.height.to_string(),
+            entry.timestamp.to_string(),
+            datapoint.rate.to_string(),
+            entry.tx_id.clone(),
+        ];
+        // empty when the source did not respond
+        row.extend(source_names.iter().map(|name| {
+            datapoint
+                .sources
+                .iter()
+                .find(|s| s.source == *name)
+                .map(|s| s.rate.to_string())
+                .unwrap_or_default()
+        }));
+        writeln!(out, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn unix_time_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    struct ConfirmationsMock {
+        heights: RefCell<HashMap<String, u32>>,
+    }
+
+    impl TxConfirmationSource for ConfirmationsMock {
+        fn get_tx_inclusion_height(&self, tx_id: &str) -> Result<Option<u32>, NodeApiError> {
+            Ok(self.heights.borrow().get(tx_id).copied())
+        }
+    }
+
+    fn entry(tx_id: &str, submission: SubmissionResult) -> AuditLogEntry {
+        AuditLogEntry {
+            timestamp: unix_time_secs(),
+            action: "refresh".to_string(),
+            tx_id: tx_id.to_string(),
+            input_box_ids: vec!["input".to_string()],
+            outputs: vec![AuditLogOutput {
+                address: "address".to_string(),
+                value: 1000000,
+                tokens: vec![],
+            }],
+            submission,
+            confirmation_height: None,
+            datapoint: None,
+        }
+    }
+
+    #[test]
+    fn test_audit_log_confirmations() {
+        let path = std::env::temp_dir().join(format!(
+            "oracle-core-audit-log-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let mut audit_log = AuditLog::open(path.clone()).unwrap();
+        audit_log
+            .append(entry("tx1", SubmissionResult::Submitted))
+            .unwrap();
+        audit_log
+            .append(entry(
+                "tx2",
+                SubmissionResult::Failed {
+                    error: "rejected".to_string(),
+                },
+            ))
+            .unwrap();
+        audit_log
+            .append(entry("tx3", SubmissionResult::Submitted))
+            .unwrap();
+
+        let confirmations = ConfirmationsMock {
+            heights: RefCell::new(HashMap::new()),
+        };
+        confirmations.heights.borrow_mut().insert("tx1".into(), 100);
+        audit_log.record_confirmations(&confirmations).unwrap();
+        // tx3 is still pending after reopening the log
+        let mut reopened = AuditLog::open(path.clone()).unwrap();
+        confirmations.heights.borrow_mut().insert("tx3".into(), 101);
+        reopened.record_confirmations(&confirmations).unwrap();
+
+        let entries = read_entries(&path).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].confirmation_height, Some(100));
+        assert_eq!(entries[1].confirmation_height, None);
+        assert_eq!(entries[2].confirmation_height, Some(101));
+        let recent = read_recent_entries(&path, 2).unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].tx_id, "tx2");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_export_datapoints_csv() {
+        let path = std::env::temp_dir().join(format!(
+            "oracle-core-audit-log-export-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let mut audit_log = AuditLog::open(path.clone()).unwrap();
+        let datapoint = |height: u32, sources: Vec<(&str, f64)>| AuditLogDatapoint {
+            height,
+            rate: 100 + height as i64,
+            sources: sources
+                .into_iter()
+                .map(|(source, rate)| SourceRate {
+                    source: source.to_string(),
+                    rate,
+                })
+                .collect(),
+        };
+        for (tx_id, height, sources) in [
+            ("tx1", 10, vec![("coincap", 110.5), ("coingecko", 109.5)]),
+            ("tx2", 20, vec![("coingecko", 120.0)]),
+            ("tx3", 30, vec![("coincap", 130.0)]),
+        ] {
+            audit_log
+                .append(AuditLogEntry {
+                    datapoint: Some(datapoint(height, sources)),
+                    ..entry(tx_id, SubmissionResult::Submitted)
+                })
+                .unwrap();
+        }
+        // refresh without a datapoint and a rejected publish are not exported
+        audit_log
+            .append(entry("tx4", SubmissionResult::Submitted))
+            .unwrap();
+        audit_log
+            .append(AuditLogEntry {
+                datapoint: Some(datapoint(25, vec![])),
+                ..entry(
+                    "tx5",
+                    SubmissionResult::Failed {
+                        error: "rejected".to_string(),
+                    },
+                )
+            })
+            .unwrap();
+
+        let mut out = Vec::new();
+        export_datapoints_csv(&path, Some(10), Some(20), &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "height,timestamp,value,tx_id,coincap,coingecko");
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("10,"));
+        assert!(lines[1].ends_with(",110,tx1,110.5,109.5"));
+        assert!(lines[2].ends_with(",120,tx2,,120"));
+        let _ = std::fs::remove_file(&path);
+    }
+}
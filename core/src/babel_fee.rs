@@ -0,0 +1,432 @@
+//! Paying tx fees in tokens with EIP-31 babel fee boxes.
+//!
+//! A babel fee box holds ERG and sells them for a particular token at the price stored in its R5
+//! register (nanoERG per token). When the wallet does not have enough ERG for a tx, the missing
+//! ERG are taken from a babel box and paid for with tokens from the wallet. The babel box is
+//! recreated in the same tx with less ERG, more tokens and the id of the spent box in R6.
+
+use std::convert::TryFrom;
+use std::convert::TryInto;
+
+use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilder;
+use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilderError;
+use ergo_lib::ergo_chain_types::Digest32;
+use ergo_lib::ergo_chain_types::DigestNError;
+use ergo_lib::ergotree_interpreter::sigma_protocol::prover::ContextExtension;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValueError;
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBoxCandidate;
+use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisterId;
+use ergo_lib::ergotree_ir::chain::token::Token;
+use ergo_lib::ergotree_ir::chain::token::TokenAmountError;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+use ergo_lib::ergotree_ir::ergo_tree::ErgoTree;
+use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
+use ergo_lib::ergotree_ir::serialization::SigmaParsingError;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
+use ergo_lib::wallet::box_selector::BoxSelection;
+use ergo_lib::wallet::box_selector::BoxSelector;
+use ergo_lib::wallet::box_selector::BoxSelectorError;
+use ergo_lib::wallet::box_selector::SimpleBoxSelector;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::node_interface::node_api::NodeApiError;
+use crate::oracle_types::BlockHeight;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BabelFeeConfig {
+    /// Base16-encoded ergo tree of the babel fee boxes accepting the token
+    pub babel_box_ergo_tree: String,
+    /// Base16-encoded id of the token used to pay for ERG
+    pub token_id: String,
+    /// Maximum accepted price in nanoERG per token
+    pub max_price: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum BabelFeeError {
+    #[error("box selector error: {0}")]
+    BoxSelector(#[from] BoxSelectorError),
+    #[error("node api error: {0}")]
+    NodeApi(#[from] NodeApiError),
+    #[error("invalid babel fee token id: {0}")]
+    TokenId(#[from] DigestNError),
+    #[error("invalid babel box ergo tree: {0}")]
+    Base16(#[from] base16::DecodeError),
+    #[error("sigma parsing error: {0}")]
+    SigmaParsing(#[from] SigmaParsingError),
+    #[error("sigma serialization error: {0}")]
+    SigmaSerialization(#[from] SigmaSerializationError),
+    #[error("box value error: {0}")]
+    BoxValue(#[from] BoxValueError),
+    #[error("token amount error: {0}")]
+    TokenAmount(#[from] TokenAmountError),
+    #[error("box builder error: {0}")]
+    ErgoBoxCandidateBuilder(#[from] ErgoBoxCandidateBuilderError),
+    #[error(
+        "no babel box with a price up to {max_price} nanoERG per token holds {nano_ergs} nanoERG"
+    )]
+    NoSuitableBabelBox { nano_ergs: u64, max_price: u64 },
+}
+
+pub trait BabelBoxSource {
+    fn get_unspent_babel_boxes(
+        &self,
+        babel_box_ergo_tree: &str,
+    ) -> Result<Vec<ErgoBox>, BabelFeeError>;
+}
+
+/// Babel fee settings of the oracle with the source of babel boxes
+pub struct BabelFee<'a> {
+    source: &'a dyn BabelBoxSource,
+    babel_box_ergo_tree: String,
+    token_id: TokenId,
+    max_price: u64,
+    /// Value the babel box keeps after selling ERG
+    min_box_value: BoxValue,
+}
+
+/// Babel box spent by a tx and its recreated output
+#[derive(Debug, Clone)]
+pub struct BabelFeeSwap {
+    pub babel_box: ErgoBox,
+    pub out_babel_box: ErgoBoxCandidate,
+}
+
+impl<'a> BabelFee<'a> {
+    pub fn new(
+        config: &BabelFeeConfig,
+        source: &'a dyn BabelBoxSource,
+        min_box_value: BoxValue,
+    ) -> Result<Self, BabelFeeError> {
+        // fail early on a malformed ergo tree
+        ErgoTree::sigma_parse_bytes(&base16::decode(&config.babel_box_ergo_tree)?)?;
+        Ok(BabelFee {
+            source,
+            babel_box_ergo_tree: config.babel_box_ergo_tree.clone(),
+            token_id: Digest32::try_from(config.token_id.clone())?.into(),
+            max_price: config.max_price,
+            min_box_value,
+        })
+    }
+
+    /// Select wallet boxes for `target_balance` minus the ERG bought from a babel box
+    fn select(
+        &self,
+        unspent_boxes: Vec<ErgoBox>,
+        target_balance: BoxValue,
+        target_tokens: &[Token],
+        height: BlockHeight,
+    ) -> Result<(BoxSelection<ErgoBox>, Option<BabelFeeSwap>), BabelFeeError> {
+        let wallet_nano_ergs: u64 = unspent_boxes.iter().map(|b| *b.value.as_u64()).sum();
+        let target_nano_ergs = *target_balance.as_u64();
+        // missing ERG plus enough for the change box
+        let nano_ergs =
+            target_nano_ergs.saturating_sub(wallet_nano_ergs) + *BoxValue::SAFE_USER_MIN.as_u64();
+        let (babel_box, price) = self.find_babel_box(nano_ergs)?;
+        let token_amount = (nano_ergs + price - 1) / price;
+        log::info!(
+            "Paying {token_amount} of token {:?} for {nano_ergs} nanoERG from babel box {:?}",
+            self.token_id,
+            babel_box.box_id()
+        );
+        let mut tokens = target_tokens.to_vec();
+        add_token(&mut tokens, self.token_id, token_amount)?;
+        let selection = SimpleBoxSelector::new().select(
+            unspent_boxes,
+            BoxValue::try_from(target_nano_ergs.saturating_sub(nano_ergs))?,
+            &tokens,
+        )?;
+        let out_babel_box =
+            recreate_babel_box(&babel_box, nano_ergs, self.token_id, token_amount, height)?;
+        Ok((
+            selection,
+            Some(BabelFeeSwap {
+                babel_box,
+                out_babel_box,
+            }),
+        ))
+    }
+
+    pub fn token_id(&self) -> &TokenId {
+        &self.token_id
+    }
+
+    /// The highest price in nanoERG per token offered by the babel boxes, the value of the token
+    /// for an oracle selling it
+    pub fn best_price(&self) -> Result<Option<u64>, BabelFeeError> {
+        Ok(self
+            .source
+            .get_unspent_babel_boxes(&self.babel_box_ergo_tree)?
+            .iter()
+            .filter_map(babel_box_price)
+            .filter(|price| *price > 0)
+            .max())
+    }
+
+    /// The cheapest babel box that can sell the given amount of nanoERG, with its price
+    fn find_babel_box(&self, nano_ergs: u64) -> Result<(ErgoBox, u64), BabelFeeError> {
+        let min_value_left = *self.min_box_value.as_u64();
+        self.source
+            .get_unspent_babel_boxes(&self.babel_box_ergo_tree)?
+            .into_iter()
+            .filter_map(|b| {
+                let price = babel_box_price(&b)?;
+                Some((b, price))
+            })
+            .filter(|(b, price)| {
+                *price > 0
+                    && *price <= self.max_price
+                    && *b.value.as_u64() >= nano_ergs + min_value_left
+            })
+            .min_by_key(|(_, price)| *price)
+            .ok_or(BabelFeeError::NoSuitableBabelBox {
+                nano_ergs,
+                max_price: self.max_price,
+            })
+    }
+}
+
+impl BabelFeeSwap {
+    /// Add the babel box to the tx inputs and its recreated box to the tx outputs. Returns the
+    /// context extension to set for the babel box input.
+    pub fn add_to_tx(
+        &self,
+        input_boxes: &mut Vec<ErgoBox>,
+        output_candidates: &mut Vec<ErgoBoxCandidate>,
+    ) -> (BoxId, ContextExtension) {
+        input_boxes.push(self.babel_box.clone());
+        // the babel contract finds its recreated box by the output index in the context variable 0
+        let out_index = output_candidates.len() as i32;
+        output_candidates.push(self.out_babel_box.clone());
+        let ctx_ext = ContextExtension {
+            values: vec![(0, out_index.into())].into_iter().collect(),
+        };
+        (self.babel_box.box_id(), ctx_ext)
+    }
+}
+
+/// Select wallet boxes covering `target_balance` and `target_tokens`. If the wallet does not have
+/// enough ERG and babel fees are enabled, the missing ERG are bought from a babel box.
+pub fn select_boxes(
+    unspent_boxes: Vec<ErgoBox>,
+    target_balance: BoxValue,
+    target_tokens: &[Token],
+    babel_fee: Option<&BabelFee>,
+    height: BlockHeight,
+) -> Result<(BoxSelection<ErgoBox>, Option<BabelFeeSwap>), BabelFeeError> {
+    match SimpleBoxSelector::new().select(unspent_boxes.clone(), target_balance, target_tokens) {
+        Ok(selection) => Ok((selection, None)),
+        Err(e) => match babel_fee {
+            Some(babel_fee) => {
+                log::info!("Not enough coins in the wallet ({e}), trying to pay with babel fee");
+                babel_fee.select(unspent_boxes, target_balance, target_tokens, height)
+            }
+            None => Err(e.into()),
+        },
+    }
+}
+
+fn add_token(tokens: &mut Vec<Token>, token_id: TokenId, amount: u64) -> Result<(), BabelFeeError> {
+    match tokens.iter_mut().find(|t| t.token_id == token_id) {
+        Some(token) => token.amount = token.amount.checked_add(&amount.try_into()?)?,
+        None => tokens.push(Token {
+            token_id,
+            amount: amount.try_into()?,
+        }),
+    }
+    Ok(())
+}
+
+fn recreate_babel_box(
+    babel_box: &ErgoBox,
+    nano_ergs: u64,
+    token_id: TokenId,
+    token_amount: u64,
+    height: BlockHeight,
+) -> Result<ErgoBoxCandidate, BabelFeeError> {
+    let mut tokens: Vec<Token> = babel_box
+        .tokens
+        .as_ref()
+        .map(|t| t.as_vec().clone())
+        .unwrap_or_default();
+    add_token(&mut tokens, token_id, token_amount)?;
+    let mut builder = ErgoBoxCandidateBuilder::new(
+        BoxValue::try_from(*babel_box.value.as_u64() - nano_ergs)?,
+        babel_box.ergo_tree.clone(),
+        height.0,
+    );
+    for token in tokens {
+        builder.add_token(token);
+    }
+    for register_id in [NonMandatoryRegisterId::R4, NonMandatoryRegisterId::R5] {
+        if let Some(value) = babel_box.get_register(register_id.into()) {
+            builder.set_register_value(register_id, value);
+        }
+    }
+    builder.set_register_value(
+        NonMandatoryRegisterId::R6,
+        babel_box.box_id().sigma_serialize_bytes()?.into(),
+    );
+    Ok(builder.build()?)
+}
+
+/// Price in nanoERG per token stored in R5 of the babel box
+fn babel_box_price(babel_box: &ErgoBox) -> Option<u64> {
+    let price = babel_box
+        .get_register(NonMandatoryRegisterId::R5.into())?
+        .try_extract_into::<i64>()
+        .ok()?;
+    u64::try_from(price).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::chain::transaction::TxId;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisters;
+    use ergo_lib::ergotree_ir::mir::constant::Constant;
+    use sigma_test_util::force_any_val;
+
+    use crate::pool_commands::test_utils::make_wallet_unspent_box;
+
+    use super::*;
+
+    struct BabelBoxesMock {
+        babel_boxes: Vec<ErgoBox>,
+    }
+
+    impl BabelBoxSource for BabelBoxesMock {
+        fn get_unspent_babel_boxes(
+            &self,
+            _babel_box_ergo_tree: &str,
+        ) -> Result<Vec<ErgoBox>, BabelFeeError> {
+            Ok(self.babel_boxes.clone())
+        }
+    }
+
+    fn make_babel_box(ergo_tree: &ErgoTree, value: u64, price: i64) -> ErgoBox {
+        let owner: Constant = force_any_val::<DlogProverInput>().public_image().into();
+        ErgoBox::new(
+            BoxValue::try_from(value).unwrap(),
+            ergo_tree.clone(),
+            None,
+            NonMandatoryRegisters::new(
+                vec![
+                    (NonMandatoryRegisterId::R4, owner),
+                    (NonMandatoryRegisterId::R5, price.into()),
+                ]
+                .into_iter()
+                .collect(),
+            )
+            .unwrap(),
+            1,
+            force_any_val::<TxId>(),
+            0,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_select_boxes_with_babel_fee() {
+        let height = BlockHeight(100);
+        let secret = force_any_val::<DlogProverInput>();
+        let token_id = force_any_val::<TokenId>();
+        let wallet_box = make_wallet_unspent_box(
+            secret.public_image(),
+            BoxValue::try_from(2_000_000u64).unwrap(),
+            Some(
+                vec![Token {
+                    token_id,
+                    amount: 1000u64.try_into().unwrap(),
+                }]
+                .try_into()
+                .unwrap(),
+            ),
+        );
+        let babel_tree = make_wallet_unspent_box(
+            force_any_val::<DlogProverInput>().public_image(),
+            BoxValue::SAFE_USER_MIN,
+            None,
+        )
+        .ergo_tree;
+        let cheap_babel_box = make_babel_box(&babel_tree, 100_000_000, 1_000_000);
+        let source = BabelBoxesMock {
+            babel_boxes: vec![
+                make_babel_box(&babel_tree, 100_000_000, 3_000_000),
+                cheap_babel_box.clone(),
+                make_babel_box(&babel_tree, 100_000_000, 9_000_000),
+            ],
+        };
+        let config = BabelFeeConfig {
+            babel_box_ergo_tree: base16::encode_lower(&babel_tree.sigma_serialize_bytes().unwrap()),
+            token_id: String::from(token_id),
+            max_price: 5_000_000,
+        };
+        let babel_fee = BabelFee::new(&config, &source, BoxValue::SAFE_USER_MIN).unwrap();
+        assert_eq!(babel_fee.best_price().unwrap(), Some(9_000_000));
+        let target_balance = BoxValue::try_from(5_000_000u64).unwrap();
+
+        assert!(select_boxes(vec![wallet_box.clone()], target_balance, &[], None, height).is_err());
+
+        let (selection, swap) = select_boxes(
+            vec![wallet_box.clone()],
+            target_balance,
+            &[],
+            Some(&babel_fee),
+            height,
+        )
+        .unwrap();
+        let swap = swap.unwrap();
+        assert_eq!(swap.babel_box.box_id(), cheap_babel_box.box_id());
+        assert_eq!(selection.boxes.len(), 1);
+
+        // 3_000_000 missing nanoERG and 1_000_000 for the wallet change box are bought
+        assert_eq!(*swap.out_babel_box.value.as_u64(), 96_000_000);
+        let paid_tokens = swap.out_babel_box.tokens.as_ref().unwrap().get(0).unwrap();
+        assert_eq!(paid_tokens.token_id, token_id);
+        assert_eq!(*paid_tokens.amount.as_u64(), 4);
+        assert_eq!(
+            ErgoBox::from_box_candidate(&swap.out_babel_box, force_any_val::<TxId>(), 0)
+                .unwrap()
+                .get_register(NonMandatoryRegisterId::R6.into())
+                .unwrap()
+                .try_extract_into::<Vec<u8>>()
+                .unwrap(),
+            cheap_babel_box.box_id().sigma_serialize_bytes().unwrap()
+        );
+
+        let mut inputs = selection.boxes.as_vec().clone();
+        let mut outputs = Vec::new();
+        let (babel_box_id, ctx_ext) = swap.add_to_tx(&mut inputs, &mut outputs);
+        assert_eq!(babel_box_id, cheap_babel_box.box_id());
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(ctx_ext.values.len(), 1);
+
+        let no_babel_box = BabelFee::new(
+            &BabelFeeConfig {
+                max_price: 500_000,
+                ..config
+            },
+            &source,
+            BoxValue::SAFE_USER_MIN,
+        )
+        .unwrap();
+        assert!(matches!(
+            select_boxes(
+                vec![wallet_box],
+                target_balance,
+                &[],
+                Some(&no_babel_box),
+                height
+            ),
+            Err(BabelFeeError::NoSuitableBabelBox { .. })
+        ));
+    }
+}
@@ -1,5 +1,6 @@
 mod ballot_box;
 mod buyback_box;
+mod legacy_v1_box;
 mod oracle_box;
 mod pool_box;
 mod refresh_box;
@@ -7,7 +8,66 @@ mod update_box;
 
 pub use ballot_box::*;
 pub use buyback_box::*;
+pub use legacy_v1_box::*;
 pub use oracle_box::*;
 pub use pool_box::*;
 pub use refresh_box::*;
 pub use update_box::*;
+
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBoxCandidate;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
+use thiserror::Error;
+
+/// Minimum value in nanoERG per byte of a serialized box required by the network
+pub const MIN_VALUE_PER_BOX_BYTE: u64 = 360;
+
+#[derive(Debug, Error)]
+pub enum MinBoxValueError {
+    #[error("box value {value} is below the network minimum {min} for its size")]
+    BelowNetworkMinimum { value: u64, min: u64 },
+    #[error("sigma serialization error: {0}")]
+    SigmaSerialization(#[from] SigmaSerializationError),
+}
+
+/// Check the box value against the network rule of `MIN_VALUE_PER_BOX_BYTE` per serialized byte
+pub fn check_min_box_value(candidate: &ErgoBoxCandidate) -> Result<(), MinBoxValueError> {
+    let min = candidate.sigma_serialize_bytes()?.len() as u64 * MIN_VALUE_PER_BOX_BYTE;
+    let value = *candidate.value.as_u64();
+    if value < min {
+        return Err(MinBoxValueError::BelowNetworkMinimum { value, min });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilder;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::address::Address;
+    use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+
+    #[test]
+    fn test_check_min_box_value() {
+        let tree = Address::P2Pk(force_any_val::<DlogProverInput>().public_image())
+            .script()
+            .unwrap();
+        let candidate = ErgoBoxCandidateBuilder::new(BoxValue::SAFE_USER_MIN, tree, 1)
+            .build()
+            .unwrap();
+        assert!(check_min_box_value(&candidate).is_ok());
+        let too_low = ErgoBoxCandidate {
+            value: BoxValue::try_from(1000u64).unwrap(),
+            ..candidate
+        };
+        assert!(matches!(
+            check_min_box_value(&too_low),
+            Err(MinBoxValueError::BelowNetworkMinimum { .. })
+        ));
+    }
+}
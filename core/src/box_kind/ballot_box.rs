@@ -56,30 +56,48 @@ pub trait BallotBox {
 pub struct BallotBoxWrapper {
     ergo_box: ErgoBox,
     contract: BallotContract,
+    ballot_token: SpecToken<BallotTokenId>,
+    ballot_token_owner: EcPoint,
 }
 
 impl BallotBoxWrapper {
     pub fn new(ergo_box: ErgoBox, inputs: &BallotBoxWrapperInputs) -> Result<Self, BallotBoxError> {
-        let ballot_token_id = &ergo_box
-            .tokens
-            .as_ref()
-            .ok_or(BallotBoxError::NoBallotToken)?
-            .get(0)
-            .ok_or(BallotBoxError::NoBallotToken)?
-            .token_id;
-        if *ballot_token_id != inputs.ballot_token_id.token_id() {
-            return Err(BallotBoxError::UnknownBallotTokenId);
-        }
-        let _ = ergo_box
+        let ballot_token = parse_ballot_token(&ergo_box, inputs)?;
+        let ballot_token_owner = ergo_box
             .get_register(NonMandatoryRegisterId::R4.into())
             .ok_or(BallotBoxError::NoGroupElementInR4)?
             .try_extract_into::<EcPoint>()?;
         let contract =
             BallotContract::from_ergo_tree(ergo_box.ergo_tree.clone(), &inputs.contract_inputs)?;
-        Ok(Self { ergo_box, contract })
+        Ok(Self {
+            ergo_box,
+            contract,
+            ballot_token,
+            ballot_token_owner,
+        })
     }
 }
 
+/// Ballot token in `TOKENS(0)`, checked against the expected token id
+fn parse_ballot_token(
+    ergo_box: &ErgoBox,
+    inputs: &BallotBoxWrapperInputs,
+) -> Result<SpecToken<BallotTokenId>, BallotBoxError> {
+    let token = ergo_box
+        .tokens
+        .as_ref()
+        .ok_or(BallotBoxError::NoBallotToken)?
+        .get(0)
+        .ok_or(BallotBoxError::NoBallotToken)?;
+    if token.token_id != inputs.ballot_token_id.token_id() {
+        return Err(BallotBoxError::UnknownBallotTokenId);
+    }
+    Ok(SpecToken {
+        token_id: BallotTokenId::from_token_id_unchecked(token.token_id),
+        amount: token.amount,
+    })
+}
+
 #[derive(Clone, Debug)]
 pub struct BallotBoxWrapperInputs {
     pub contract_inputs: BallotContractInputs,
@@ -128,29 +146,19 @@ pub struct VoteBallotBoxWrapper {
     ergo_box: ErgoBox,
     vote_parameters: CastBallotBoxVoteParameters,
     contract: BallotContract,
+    ballot_token: SpecToken<BallotTokenId>,
+    ballot_token_owner: EcPoint,
 }
 
 impl VoteBallotBoxWrapper {
     pub fn new(ergo_box: ErgoBox, inputs: &BallotBoxWrapperInputs) -> Result<Self, BallotBoxError> {
-        let ballot_token_id = &ergo_box
-            .tokens
-            .as_ref()
-            .ok_or(BallotBoxError::NoBallotToken)?
-            .get(0)
-            .ok_or(BallotBoxError::NoBallotToken)?
-            .token_id;
-        if *ballot_token_id != inputs.ballot_token_id.token_id() {
-            return Err(BallotBoxError::UnknownBallotTokenId);
-        }
+        let ballot_token = parse_ballot_token(&ergo_box, inputs)?;
 
-        if ergo_box
+        let ballot_token_owner = ergo_box
             .get_register(NonMandatoryRegisterId::R4.into())
             .ok_or(BallotBoxError::NoGroupElementInR4)?
             .try_extract_into::<EcPoint>()
-            .is_err()
-        {
-            return Err(BallotBoxError::NoGroupElementInR4);
-        }
+            .map_err(|_| BallotBoxError::NoGroupElementInR4)?;
         let update_box_creation_height = ergo_box
             .get_register(NonMandatoryRegisterId::R5.into())
             .ok_or(BallotBoxError::NoUpdateBoxCreationHeightInR5)?
@@ -171,7 +179,15 @@ impl VoteBallotBoxWrapper {
         let reward_token_opt = match (reward_token_id_opt, reward_token_quantity_opt) {
             (Some(Ok(reward_token_id)), Some(Ok(reward_token_quantity))) => Some(SpecToken {
                 token_id: RewardTokenId::from_token_id_unchecked(reward_token_id),
-                amount: (reward_token_quantity as u64).try_into().unwrap(),
+                amount: u64::try_from(reward_token_quantity)
+                    .ok()
+                    .and_then(|amount| amount.try_into().ok())
+                    .ok_or_else(|| {
+                        BallotBoxError::InvalidRewardToken(format!(
+                            "Reward token amount {}",
+                            reward_token_quantity
+                        ))
+                    })?,
             }),
             (None, None) => None,
             (id, amt) => {
@@ -192,6 +208,8 @@ impl VoteBallotBoxWrapper {
             ergo_box,
             contract,
             vote_parameters,
+            ballot_token,
+            ballot_token_owner,
         })
     }
 
@@ -206,12 +224,7 @@ impl BallotBox for BallotBoxWrapper {
     }
 
     fn ballot_token(&self) -> SpecToken<BallotTokenId> {
-        let ballot_token = self.ergo_box.tokens.as_ref().unwrap().get(0).unwrap();
-        SpecToken {
-            // Safe to do this here since BallotBoxWrapper::new() already checks token id
-            token_id: BallotTokenId::from_token_id_unchecked(ballot_token.token_id),
-            amount: ballot_token.amount,
-        }
+        self.ballot_token.clone()
     }
 
     fn min_storage_rent(&self) -> BoxValue {
@@ -219,11 +232,7 @@ impl BallotBox for BallotBoxWrapper {
     }
 
     fn ballot_token_owner(&self) -> EcPoint {
-        self.ergo_box
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .unwrap()
-            .try_extract_into::<EcPoint>()
-            .unwrap()
+        self.ballot_token_owner.clone()
     }
 
     fn get_box(&self) -> &ErgoBox {
@@ -237,12 +246,7 @@ impl BallotBox for VoteBallotBoxWrapper {
     }
 
     fn ballot_token(&self) -> SpecToken<BallotTokenId> {
-        let ballot_token = self.ergo_box.tokens.as_ref().unwrap().get(0).unwrap();
-        SpecToken {
-            // Safe to do this here since BallotBoxWrapper::new() already checks token id
-            token_id: BallotTokenId::from_token_id_unchecked(ballot_token.token_id),
-            amount: ballot_token.amount,
-        }
+        self.ballot_token.clone()
     }
 
     fn min_storage_rent(&self) -> BoxValue {
@@ -250,11 +254,7 @@ impl BallotBox for VoteBallotBoxWrapper {
     }
 
     fn ballot_token_owner(&self) -> EcPoint {
-        self.ergo_box
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .unwrap()
-            .try_extract_into::<EcPoint>()
-            .unwrap()
+        self.ballot_token_owner.clone()
     }
 
     fn get_box(&self) -> &ErgoBox {
@@ -2,6 +2,8 @@ use std::vec;
 
 use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
 use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBoxCandidate;
+use ergo_lib::ergotree_ir::chain::token::Token;
+use ergo_lib::ergotree_ir::chain::token::TokenAmount;
 use thiserror::Error;
 
 use crate::spec_token::RewardTokenId;
@@ -9,21 +11,32 @@ use crate::spec_token::SpecToken;
 use crate::spec_token::TokenIdKind;
 
 #[derive(Debug, Error)]
-pub enum BuybackBoxError {}
+pub enum BuybackBoxError {
+    #[error("buyback box: no buyback nft in `TOKENS(0)`")]
+    NoBuybackNft,
+}
 
 #[derive(Debug, Clone)]
 pub struct BuybackBoxWrapper {
     ergo_box: ErgoBox,
+    buyback_nft: Token,
     reward_token_id: RewardTokenId,
 }
 
 #[allow(clippy::todo)]
 impl BuybackBoxWrapper {
-    pub fn new(ergo_box: ErgoBox, reward_token_id: RewardTokenId) -> Self {
-        Self {
+    pub fn new(ergo_box: ErgoBox, reward_token_id: RewardTokenId) -> Result<Self, BuybackBoxError> {
+        let buyback_nft = ergo_box
+            .tokens
+            .as_ref()
+            .and_then(|tokens| tokens.get(0))
+            .ok_or(BuybackBoxError::NoBuybackNft)?
+            .clone();
+        Ok(Self {
             ergo_box,
+            buyback_nft,
             reward_token_id,
-        }
+        })
     }
 
     pub fn get_box(&self) -> &ErgoBox {
@@ -34,8 +47,7 @@ impl BuybackBoxWrapper {
         self.ergo_box
             .tokens
             .as_ref()
-            .unwrap()
-            .get(1)
+            .and_then(|tokens| tokens.get(1))
             .map(|token| SpecToken {
                 token_id: RewardTokenId::from_token_id_unchecked(token.token_id),
                 amount: token.amount,
@@ -44,14 +56,25 @@ impl BuybackBoxWrapper {
 
     pub fn new_without_reward_token(&self) -> ErgoBoxCandidate {
         // take only buyback nft
-        let tokens = vec![self
-            .ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone()]
+        let tokens = vec![self.buyback_nft.clone()].try_into().unwrap();
+        ErgoBoxCandidate {
+            value: self.ergo_box.value,
+            ergo_tree: self.ergo_box.ergo_tree.clone(),
+            tokens: Some(tokens),
+            additional_registers: self.ergo_box.additional_registers.clone(),
+            creation_height: self.ergo_box.creation_height,
+        }
+    }
+
+    /// Same box keeping the buyback nft and the given amount of reward tokens
+    pub fn new_with_reward_token_amount(&self, amount: TokenAmount) -> ErgoBoxCandidate {
+        let tokens = vec![
+            self.buyback_nft.clone(),
+            Token {
+                token_id: self.reward_token_id.token_id(),
+                amount,
+            },
+        ]
         .try_into()
         .unwrap();
         ErgoBoxCandidate {
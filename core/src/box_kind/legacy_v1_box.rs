@@ -0,0 +1,338 @@
+//! Boxes of the legacy oracle pool v1 contracts, read to migrate a v1 deployment to a v2 pool.
+//!
+//! The v1 pool NFT alternates between the live epoch box (R4 latest rate, R5 epoch end height,
+//! R6 hash of the epoch preparation contract) and the epoch preparation box (R4 latest rate, R5
+//! next epoch end height). Datapoint boxes hold an oracle token with the oracle public key in R4,
+//! the id of the live epoch box the datapoint was posted for in R5 and the datapoint in R6.
+//! The v1 contracts are not shipped, so only the tokens and registers are checked.
+
+use std::convert::TryFrom;
+
+use ergo_lib::ergo_chain_types::Digest32;
+use ergo_lib::ergo_chain_types::EcPoint;
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisterId;
+use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
+use thiserror::Error;
+
+use crate::oracle_types::BlockHeight;
+use crate::oracle_types::Rate;
+use crate::spec_token::OracleTokenId;
+use crate::spec_token::PoolTokenId;
+use crate::spec_token::SpecToken;
+use crate::spec_token::TokenIdKind;
+
+#[derive(Debug, Error)]
+pub enum LegacyV1BoxError {
+    #[error("v1 box: no tokens found")]
+    NoTokens,
+    #[error("v1 box: unknown pool NFT token id in `TOKENS(0)`")]
+    UnknownPoolNftId,
+    #[error("v1 box: unknown oracle token id in `TOKENS(0)`")]
+    UnknownOracleTokenId,
+    #[error("v1 box: no data point in {0:?}")]
+    NoDataPoint(NonMandatoryRegisterId),
+    #[error("v1 box: no epoch end height in R5")]
+    NoEpochEndHeight,
+    #[error("v1 box: no public key in R4")]
+    NoPublicKeyInR4,
+    #[error("v1 box: no live epoch box id in R5")]
+    NoLiveEpochBoxId,
+}
+
+#[derive(Debug, Clone)]
+pub struct LegacyV1BoxWrapperInputs {
+    pub pool_nft_token_id: PoolTokenId,
+    pub oracle_token_id: OracleTokenId,
+}
+
+/// Box holding the pool NFT during an epoch
+#[derive(Debug, Clone)]
+pub struct LegacyV1LiveEpochBox {
+    ergo_box: ErgoBox,
+    pool_nft_token: SpecToken<PoolTokenId>,
+    rate: i64,
+    epoch_end_height: BlockHeight,
+    epoch_prep_script_hash: Vec<u8>,
+}
+
+/// Box holding the pool NFT between epochs, collecting the pool deposits
+#[derive(Debug, Clone)]
+pub struct LegacyV1EpochPrepBox {
+    ergo_box: ErgoBox,
+    pool_nft_token: SpecToken<PoolTokenId>,
+    rate: i64,
+    epoch_end_height: BlockHeight,
+}
+
+#[derive(Debug, Clone)]
+pub enum LegacyV1PoolBox {
+    LiveEpoch(LegacyV1LiveEpochBox),
+    EpochPreparation(LegacyV1EpochPrepBox),
+}
+
+impl LegacyV1PoolBox {
+    /// Parse the box holding the v1 pool NFT, a live epoch box is told apart by its R6
+    pub fn new(b: ErgoBox, inputs: &LegacyV1BoxWrapperInputs) -> Result<Self, LegacyV1BoxError> {
+        let token = b
+            .tokens
+            .as_ref()
+            .ok_or(LegacyV1BoxError::NoTokens)?
+            .get(0)
+            .ok_or(LegacyV1BoxError::NoTokens)?
+            .clone();
+        if token.token_id != inputs.pool_nft_token_id.token_id() {
+            return Err(LegacyV1BoxError::UnknownPoolNftId);
+        }
+        // unchecked is safe here as the token id is validated above
+        let pool_nft_token = SpecToken {
+            token_id: PoolTokenId::from_token_id_unchecked(token.token_id),
+            amount: token.amount,
+        };
+        let rate = b
+            .get_register(NonMandatoryRegisterId::R4.into())
+            .and_then(|c| c.try_extract_into::<i64>().ok())
+            .ok_or(LegacyV1BoxError::NoDataPoint(NonMandatoryRegisterId::R4))?;
+        let epoch_end_height = b
+            .get_register(NonMandatoryRegisterId::R5.into())
+            .and_then(|c| c.try_extract_into::<i32>().ok())
+            .ok_or(LegacyV1BoxError::NoEpochEndHeight)?;
+        let epoch_end_height = BlockHeight(epoch_end_height as u32);
+        let epoch_prep_script_hash = b
+            .get_register(NonMandatoryRegisterId::R6.into())
+            .and_then(|c| c.try_extract_into::<Vec<u8>>().ok());
+        Ok(match epoch_prep_script_hash {
+            Some(epoch_prep_script_hash) => LegacyV1PoolBox::LiveEpoch(LegacyV1LiveEpochBox {
+                ergo_box: b,
+                pool_nft_token,
+                rate,
+                epoch_end_height,
+                epoch_prep_script_hash,
+            }),
+            None => LegacyV1PoolBox::EpochPreparation(LegacyV1EpochPrepBox {
+                ergo_box: b,
+                pool_nft_token,
+                rate,
+                epoch_end_height,
+            }),
+        })
+    }
+
+    pub fn get_box(&self) -> &ErgoBox {
+        match self {
+            LegacyV1PoolBox::LiveEpoch(b) => &b.ergo_box,
+            LegacyV1PoolBox::EpochPreparation(b) => &b.ergo_box,
+        }
+    }
+
+    pub fn pool_nft_token(&self) -> SpecToken<PoolTokenId> {
+        match self {
+            LegacyV1PoolBox::LiveEpoch(b) => b.pool_nft_token.clone(),
+            LegacyV1PoolBox::EpochPreparation(b) => b.pool_nft_token.clone(),
+        }
+    }
+
+    /// Rate finalized by the last v1 epoch
+    pub fn rate(&self) -> i64 {
+        match self {
+            LegacyV1PoolBox::LiveEpoch(b) => b.rate,
+            LegacyV1PoolBox::EpochPreparation(b) => b.rate,
+        }
+    }
+
+    /// End height of the live epoch, or of the next epoch for the epoch preparation box
+    pub fn epoch_end_height(&self) -> BlockHeight {
+        match self {
+            LegacyV1PoolBox::LiveEpoch(b) => b.epoch_end_height,
+            LegacyV1PoolBox::EpochPreparation(b) => b.epoch_end_height,
+        }
+    }
+}
+
+impl LegacyV1LiveEpochBox {
+    /// Hash of the epoch preparation contract the pool NFT moves to at the end of the epoch
+    pub fn epoch_prep_script_hash(&self) -> Vec<u8> {
+        self.epoch_prep_script_hash.clone()
+    }
+}
+
+/// Datapoint posted by a v1 oracle for a live epoch
+#[derive(Debug, Clone)]
+pub struct LegacyV1DatapointBox {
+    ergo_box: ErgoBox,
+    oracle_token: SpecToken<OracleTokenId>,
+    public_key: EcPoint,
+    live_epoch_box_id: BoxId,
+    rate: Rate,
+}
+
+impl LegacyV1DatapointBox {
+    pub fn new(b: ErgoBox, inputs: &LegacyV1BoxWrapperInputs) -> Result<Self, LegacyV1BoxError> {
+        let token = b
+            .tokens
+            .as_ref()
+            .ok_or(LegacyV1BoxError::NoTokens)?
+            .get(0)
+            .ok_or(LegacyV1BoxError::NoTokens)?
+            .clone();
+        if token.token_id != inputs.oracle_token_id.token_id() {
+            return Err(LegacyV1BoxError::UnknownOracleTokenId);
+        }
+        // unchecked is safe here as the token id is validated above
+        let oracle_token = SpecToken {
+            token_id: OracleTokenId::from_token_id_unchecked(token.token_id),
+            amount: token.amount,
+        };
+        let public_key = b
+            .get_register(NonMandatoryRegisterId::R4.into())
+            .and_then(|c| c.try_extract_into::<EcPoint>().ok())
+            .ok_or(LegacyV1BoxError::NoPublicKeyInR4)?;
+        let live_epoch_box_id = b
+            .get_register(NonMandatoryRegisterId::R5.into())
+            .and_then(|c| c.try_extract_into::<Vec<u8>>().ok())
+            .and_then(|bytes| Digest32::try_from(bytes).ok())
+            .ok_or(LegacyV1BoxError::NoLiveEpochBoxId)?
+            .into();
+        let rate = b
+            .get_register(NonMandatoryRegisterId::R6.into())
+            .and_then(|c| c.try_extract_into::<i64>().ok())
+            .ok_or(LegacyV1BoxError::NoDataPoint(NonMandatoryRegisterId::R6))?
+            .into();
+        Ok(Self {
+            ergo_box: b,
+            oracle_token,
+            public_key,
+            live_epoch_box_id,
+            rate,
+        })
+    }
+
+    pub fn get_box(&self) -> &ErgoBox {
+        &self.ergo_box
+    }
+
+    pub fn oracle_token(&self) -> SpecToken<OracleTokenId> {
+        self.oracle_token.clone()
+    }
+
+    pub fn public_key(&self) -> EcPoint {
+        self.public_key.clone()
+    }
+
+    /// Id of the live epoch box the datapoint was posted for
+    pub fn live_epoch_box_id(&self) -> BoxId {
+        self.live_epoch_box_id
+    }
+
+    pub fn rate(&self) -> Rate {
+        self.rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use ergo_lib::chain::transaction::TxId;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::address::Address;
+    use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+    use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisters;
+    use ergo_lib::ergotree_ir::chain::token::Token;
+    use ergo_lib::ergotree_ir::chain::token::TokenId;
+    use ergo_lib::ergotree_ir::mir::constant::Constant;
+    use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+    use sigma_test_util::force_any_val;
+
+    use crate::pool_commands::test_utils::generate_token_ids;
+
+    use super::*;
+
+    fn make_box(token_id: TokenId, registers: Vec<(NonMandatoryRegisterId, Constant)>) -> ErgoBox {
+        let tree = Address::P2Pk(force_any_val::<DlogProverInput>().public_image())
+            .script()
+            .unwrap();
+        ErgoBox::new(
+            BoxValue::SAFE_USER_MIN,
+            tree,
+            Some(
+                vec![Token::from((token_id, 1u64.try_into().unwrap()))]
+                    .try_into()
+                    .unwrap(),
+            ),
+            NonMandatoryRegisters::new(registers.into_iter().collect()).unwrap(),
+            100,
+            force_any_val::<TxId>(),
+            0,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_v1_boxes() {
+        let token_ids = generate_token_ids();
+        let inputs = LegacyV1BoxWrapperInputs {
+            pool_nft_token_id: token_ids.pool_nft_token_id.clone(),
+            oracle_token_id: token_ids.oracle_token_id.clone(),
+        };
+        let pool_nft = token_ids.pool_nft_token_id.token_id();
+        let live_epoch_box = make_box(
+            pool_nft,
+            vec![
+                (NonMandatoryRegisterId::R4, Constant::from(200i64)),
+                (NonMandatoryRegisterId::R5, Constant::from(150i32)),
+                (NonMandatoryRegisterId::R6, Constant::from(vec![1u8; 32])),
+            ],
+        );
+        let live_epoch = LegacyV1PoolBox::new(live_epoch_box.clone(), &inputs).unwrap();
+        assert!(matches!(
+            live_epoch,
+            LegacyV1PoolBox::LiveEpoch(ref b) if b.epoch_prep_script_hash() == vec![1u8; 32]
+        ));
+        assert_eq!(live_epoch.rate(), 200);
+        assert_eq!(live_epoch.epoch_end_height(), BlockHeight(150));
+
+        let epoch_prep = LegacyV1PoolBox::new(
+            make_box(
+                pool_nft,
+                vec![
+                    (NonMandatoryRegisterId::R4, Constant::from(200i64)),
+                    (NonMandatoryRegisterId::R5, Constant::from(180i32)),
+                ],
+            ),
+            &inputs,
+        )
+        .unwrap();
+        assert!(matches!(epoch_prep, LegacyV1PoolBox::EpochPreparation(_)));
+
+        let public_key = force_any_val::<DlogProverInput>().public_image().h;
+        let datapoint = LegacyV1DatapointBox::new(
+            make_box(
+                token_ids.oracle_token_id.token_id(),
+                vec![
+                    (
+                        NonMandatoryRegisterId::R4,
+                        Constant::from(*public_key.clone()),
+                    ),
+                    (
+                        NonMandatoryRegisterId::R5,
+                        Constant::from(live_epoch_box.box_id().sigma_serialize_bytes().unwrap()),
+                    ),
+                    (NonMandatoryRegisterId::R6, Constant::from(205i64)),
+                ],
+            ),
+            &inputs,
+        )
+        .unwrap();
+        assert_eq!(datapoint.public_key(), *public_key);
+        assert_eq!(datapoint.live_epoch_box_id(), live_epoch_box.box_id());
+        assert_eq!(datapoint.rate(), 205);
+
+        // the datapoint box doesn't hold the pool NFT
+        assert!(matches!(
+            LegacyV1PoolBox::new(datapoint.get_box().clone(), &inputs),
+            Err(LegacyV1BoxError::UnknownPoolNftId)
+        ));
+    }
+}
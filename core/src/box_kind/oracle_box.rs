@@ -62,12 +62,20 @@ pub enum OracleBoxError {
 pub struct PostedOracleBox {
     ergo_box: ErgoBox,
     contract: OracleContract,
+    oracle_token: SpecToken<OracleTokenId>,
+    reward_token: SpecToken<RewardTokenId>,
+    public_key: EcPoint,
+    epoch_counter: EpochCounter,
+    rate: Rate,
 }
 
 #[derive(Clone, Debug)]
 pub struct CollectedOracleBox {
     ergo_box: ErgoBox,
     contract: OracleContract,
+    oracle_token: SpecToken<OracleTokenId>,
+    reward_token: SpecToken<RewardTokenId>,
+    public_key: EcPoint,
 }
 
 #[derive(Clone)]
@@ -78,36 +86,35 @@ pub enum OracleBoxWrapper {
 
 impl OracleBoxWrapper {
     pub fn new(b: ErgoBox, inputs: &OracleBoxWrapperInputs) -> Result<Self, OracleBoxError> {
-        let oracle_token_id = b
-            .tokens
-            .as_ref()
-            .ok_or(OracleBoxError::NoTokens)?
-            .first()
-            .token_id;
-
-        if oracle_token_id != inputs.oracle_token_id.token_id() {
+        let tokens = b.tokens.as_ref().ok_or(OracleBoxError::NoTokens)?;
+        let oracle_token = tokens.first().clone();
+
+        if oracle_token.token_id != inputs.oracle_token_id.token_id() {
             return Err(OracleBoxError::UnknownOracleTokenId);
         }
+        let oracle_token = SpecToken {
+            // unchecked is safe here since the token id is checked above
+            token_id: OracleTokenId::from_token_id_unchecked(oracle_token.token_id),
+            amount: oracle_token.amount,
+        };
 
-        let reward_token_id = b
-            .tokens
-            .as_ref()
-            .ok_or(OracleBoxError::NoTokens)?
-            .get(1)
-            .ok_or(OracleBoxError::NoRewardToken)?
-            .token_id;
+        let reward_token = tokens.get(1).ok_or(OracleBoxError::NoRewardToken)?.clone();
 
-        if reward_token_id != inputs.reward_token_id.token_id() {
+        if reward_token.token_id != inputs.reward_token_id.token_id() {
             log::error!(
                 "found reward token id {reward_token_id:?} in oracle box but expected {expected_reward_token_id:?}",
-                reward_token_id = reward_token_id,
+                reward_token_id = reward_token.token_id,
                 expected_reward_token_id = inputs.reward_token_id.token_id()
             );
         }
+        let reward_token = SpecToken {
+            token_id: RewardTokenId::from_token_id_unchecked(reward_token.token_id),
+            amount: reward_token.amount,
+        };
 
         // We won't be analysing the actual address since there exists multiple oracle boxes that
         // will be inputs for the 'refresh pool' operation.
-        let _ = b
+        let public_key = b
             .get_register(NonMandatoryRegisterId::R4.into())
             .ok_or(OracleBoxError::NoPublicKeyInR4)?
             .try_extract_into::<EcPoint>()?;
@@ -123,19 +130,23 @@ impl OracleBoxWrapper {
         let contract =
             OracleContract::from_ergo_tree(b.ergo_tree.clone(), &inputs.contract_inputs)?;
 
-        let collected_oracle_box = OracleBoxWrapper::Collected(CollectedOracleBox {
-            ergo_box: b.clone(),
-            contract: contract.clone(),
-        });
-
-        let posted_oracle_box = OracleBoxWrapper::Posted(PostedOracleBox {
-            ergo_box: b,
-            contract,
-        });
-
         match (epoch_counter_opt, rate_opt) {
-            (Some(_), Some(_)) => Ok(posted_oracle_box),
-            (None, None) => Ok(collected_oracle_box),
+            (Some(epoch_counter), Some(rate)) => Ok(OracleBoxWrapper::Posted(PostedOracleBox {
+                ergo_box: b,
+                contract,
+                oracle_token,
+                reward_token,
+                public_key,
+                epoch_counter: EpochCounter(epoch_counter as u32),
+                rate: rate.into(),
+            })),
+            (None, None) => Ok(OracleBoxWrapper::Collected(CollectedOracleBox {
+                ergo_box: b,
+                contract,
+                oracle_token,
+                reward_token,
+                public_key,
+            })),
             (Some(_), None) => Err(OracleBoxError::NoDataPoint),
             (None, Some(_)) => Err(OracleBoxError::NoEpochCounter),
         }
@@ -144,42 +155,24 @@ impl OracleBoxWrapper {
 
 impl OracleBox for OracleBoxWrapper {
     fn oracle_token(&self) -> SpecToken<OracleTokenId> {
-        let token = self
-            .get_box()
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone();
-        SpecToken {
-            // unchecked is safe here since OracleBoxWrapper::new checks if token id is valid
-            token_id: OracleTokenId::from_token_id_unchecked(token.token_id),
-            amount: token.amount,
+        match self {
+            OracleBoxWrapper::Posted(p) => p.oracle_token.clone(),
+            OracleBoxWrapper::Collected(c) => c.oracle_token.clone(),
         }
     }
 
     fn reward_token(&self) -> SpecToken<RewardTokenId> {
-        let token = self
-            .get_box()
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(1)
-            .unwrap()
-            .clone();
-        SpecToken {
-            token_id: RewardTokenId::from_token_id_unchecked(token.token_id),
-            amount: token.amount,
+        match self {
+            OracleBoxWrapper::Posted(p) => p.reward_token.clone(),
+            OracleBoxWrapper::Collected(c) => c.reward_token.clone(),
         }
     }
 
     fn public_key(&self) -> EcPoint {
-        self.get_box()
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .unwrap()
-            .try_extract_into::<EcPoint>()
-            .unwrap()
+        match self {
+            OracleBoxWrapper::Posted(p) => p.public_key.clone(),
+            OracleBoxWrapper::Collected(c) => c.public_key.clone(),
+        }
     }
 
     fn get_box(&self) -> &ErgoBox {
@@ -206,42 +199,15 @@ impl PostedOracleBox {
     }
 
     pub fn oracle_token(&self) -> SpecToken<OracleTokenId> {
-        let token = self
-            .get_box()
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone();
-        SpecToken {
-            // unchecked is safe here since OracleBoxWrapper::new checks if token id is valid
-            token_id: OracleTokenId::from_token_id_unchecked(token.token_id),
-            amount: token.amount,
-        }
+        self.oracle_token.clone()
     }
 
     pub fn reward_token(&self) -> SpecToken<RewardTokenId> {
-        let token = self
-            .get_box()
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(1)
-            .unwrap()
-            .clone();
-        SpecToken {
-            token_id: RewardTokenId::from_token_id_unchecked(token.token_id),
-            amount: token.amount,
-        }
+        self.reward_token.clone()
     }
 
     pub fn public_key(&self) -> EcPoint {
-        self.ergo_box
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .unwrap()
-            .try_extract_into::<EcPoint>()
-            .unwrap()
+        self.public_key.clone()
     }
 
     pub fn contract(&self) -> &OracleContract {
@@ -253,22 +219,11 @@ impl PostedOracleBox {
     }
 
     pub fn epoch_counter(&self) -> EpochCounter {
-        EpochCounter(
-            self.ergo_box
-                .get_register(NonMandatoryRegisterId::R5.into())
-                .unwrap()
-                .try_extract_into::<i32>()
-                .unwrap() as u32,
-        )
+        self.epoch_counter
     }
 
     pub fn rate(&self) -> Rate {
-        self.ergo_box
-            .get_register(NonMandatoryRegisterId::R6.into())
-            .unwrap()
-            .try_extract_into::<i64>()
-            .unwrap()
-            .into()
+        self.rate
     }
 }
 
@@ -367,3 +322,62 @@ pub fn make_collected_oracle_box_candidate(
     builder.add_token(reward_token.into());
     builder.build()
 }
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::chain::transaction::TxId;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisters;
+    use ergo_lib::ergotree_ir::mir::constant::Constant;
+    use sigma_test_util::force_any_val;
+
+    use crate::pool_commands::test_utils::{generate_token_ids, make_datapoint_box};
+
+    use super::*;
+
+    #[test]
+    fn test_malformed_datapoint_box_is_an_error() {
+        let token_ids = generate_token_ids();
+        let inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let public_key = *force_any_val::<DlogProverInput>().public_image().h;
+        let posted_box = make_datapoint_box(
+            public_key.clone(),
+            200,
+            EpochCounter(1),
+            &token_ids,
+            BoxValue::SAFE_USER_MIN,
+            BlockHeight(100),
+            100,
+        );
+        let posted = PostedOracleBox::new(posted_box.clone(), &inputs).unwrap();
+        assert_eq!(posted.rate(), Rate::from(200));
+        assert_eq!(posted.epoch_counter(), EpochCounter(1));
+
+        // datapoint of another type in R6, e.g. posted by a third party
+        let malformed_box = ErgoBox::new(
+            posted_box.value,
+            posted_box.ergo_tree.clone(),
+            posted_box.tokens.clone(),
+            NonMandatoryRegisters::new(
+                vec![
+                    (NonMandatoryRegisterId::R4, Constant::from(public_key)),
+                    (NonMandatoryRegisterId::R5, Constant::from(1i32)),
+                    (NonMandatoryRegisterId::R6, Constant::from(vec![1u8])),
+                ]
+                .into_iter()
+                .collect(),
+            )
+            .unwrap(),
+            100,
+            force_any_val::<TxId>(),
+            0,
+        )
+        .unwrap();
+        assert!(matches!(
+            OracleBoxWrapper::new(malformed_box, &inputs),
+            Err(OracleBoxError::NoDataPoint)
+        ));
+    }
+}
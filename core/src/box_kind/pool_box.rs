@@ -51,100 +51,76 @@ pub enum PoolBoxError {
 pub struct PoolBoxWrapper {
     ergo_box: ErgoBox,
     contract: PoolContract,
+    pool_nft_token: SpecToken<PoolTokenId>,
+    reward_token: SpecToken<RewardTokenId>,
+    epoch_counter: EpochCounter,
+    rate: i64,
 }
 
 impl PoolBoxWrapper {
     pub fn new(b: ErgoBox, inputs: &PoolBoxWrapperInputs) -> Result<Self, PoolBoxError> {
-        if let Some(token) = b.tokens.as_ref().ok_or(PoolBoxError::NoTokens)?.get(0) {
-            if token.token_id != inputs.pool_nft_token_id.token_id() {
-                return Err(PoolBoxError::UnknownPoolNftId);
-            }
-        } else {
-            return Err(PoolBoxError::NoTokens);
+        let tokens = b.tokens.as_ref().ok_or(PoolBoxError::NoTokens)?;
+        let token = tokens.get(0).ok_or(PoolBoxError::NoTokens)?;
+        if token.token_id != inputs.pool_nft_token_id.token_id() {
+            return Err(PoolBoxError::UnknownPoolNftId);
         }
+        // unchecked is safe here as the token id is validated above
+        let pool_nft_token = SpecToken {
+            token_id: PoolTokenId::from_token_id_unchecked(token.token_id),
+            amount: token.amount,
+        };
 
         // No need to analyse the data point as its validity is checked within the refresh contract.
-        if b.get_register(NonMandatoryRegisterId::R4.into())
+        let rate = b
+            .get_register(NonMandatoryRegisterId::R4.into())
             .ok_or(PoolBoxError::NoDataPoint)?
             .try_extract_into::<i64>()
-            .is_err()
-        {
-            return Err(PoolBoxError::NoDataPoint);
-        }
+            .map_err(|_| PoolBoxError::NoDataPoint)?;
 
         // No need to analyse the epoch counter as its validity is checked within the pool and
         // oracle contracts.
-        if b.get_register(NonMandatoryRegisterId::R5.into())
+        let epoch_counter = b
+            .get_register(NonMandatoryRegisterId::R5.into())
             .ok_or(PoolBoxError::NoEpochCounter)?
             .try_extract_into::<i32>()
-            .is_err()
-        {
-            return Err(PoolBoxError::NoEpochCounter);
-        }
+            .map_err(|_| PoolBoxError::NoEpochCounter)?;
+        let epoch_counter = EpochCounter(epoch_counter as u32);
 
-        if let Some(reward_token) = b.tokens.as_ref().ok_or(PoolBoxError::NoTokens)?.get(1) {
-            if reward_token.token_id != inputs.reward_token_id.token_id() {
-                return Err(PoolBoxError::UnknownRewardTokenId);
-            }
-        } else {
-            return Err(PoolBoxError::NoRewardToken);
+        let reward_token = tokens.get(1).ok_or(PoolBoxError::NoRewardToken)?;
+        if reward_token.token_id != inputs.reward_token_id.token_id() {
+            return Err(PoolBoxError::UnknownRewardTokenId);
         }
+        let reward_token = SpecToken {
+            token_id: RewardTokenId::from_token_id_unchecked(reward_token.token_id),
+            amount: reward_token.amount,
+        };
         let contract = PoolContract::from_ergo_tree(b.ergo_tree.clone(), &inputs.contract_inputs)?;
         Ok(Self {
             ergo_box: b,
             contract,
+            pool_nft_token,
+            reward_token,
+            epoch_counter,
+            rate,
         })
     }
 }
 
 impl PoolBox for PoolBoxWrapper {
     fn pool_nft_token(&self) -> SpecToken<PoolTokenId> {
-        let token = self
-            .ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone();
-        // unchecked is safe here as PoolBoxWrapper::new validates token id
-        SpecToken {
-            token_id: PoolTokenId::from_token_id_unchecked(token.token_id),
-            amount: token.amount,
-        }
+        self.pool_nft_token.clone()
     }
 
     fn epoch_counter(&self) -> EpochCounter {
-        EpochCounter(
-            self.ergo_box
-                .get_register(NonMandatoryRegisterId::R5.into())
-                .unwrap()
-                .try_extract_into::<i32>()
-                .unwrap() as u32,
-        )
+        self.epoch_counter
     }
 
     fn rate(&self) -> i64 {
-        self.ergo_box
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .unwrap()
-            .try_extract_into::<i64>()
-            .unwrap()
+        self.rate
     }
 
     fn reward_token(&self) -> SpecToken<RewardTokenId> {
-        let token = self
-            .ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(1)
-            .unwrap()
-            .clone();
-        SpecToken {
-            token_id: RewardTokenId::from_token_id_unchecked(token.token_id),
-            amount: token.amount,
-        }
+        self.reward_token.clone()
     }
 
     fn get_box(&self) -> &ErgoBox {
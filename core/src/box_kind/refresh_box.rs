@@ -41,6 +41,7 @@ pub enum RefreshBoxError {
 pub struct RefreshBoxWrapper {
     ergo_box: ErgoBox,
     contract: RefreshContract,
+    refresh_nft_token: Token,
 }
 
 #[derive(Clone, Debug)]
@@ -88,15 +89,17 @@ impl RefreshBoxWrapperInputs {
 
 impl RefreshBoxWrapper {
     pub fn new(b: ErgoBox, inputs: &RefreshBoxWrapperInputs) -> Result<Self, RefreshBoxError> {
-        let refresh_token_id = b
+        let refresh_nft_token = b
             .tokens
             .as_ref()
             .ok_or(RefreshBoxError::NoTokens)?
             .get(0)
             .ok_or(RefreshBoxError::NoTokens)?
-            .token_id;
-        if refresh_token_id != inputs.refresh_nft_token_id.token_id() {
-            return Err(RefreshBoxError::IncorrectRefreshTokenId(refresh_token_id));
+            .clone();
+        if refresh_nft_token.token_id != inputs.refresh_nft_token_id.token_id() {
+            return Err(RefreshBoxError::IncorrectRefreshTokenId(
+                refresh_nft_token.token_id,
+            ));
         }
 
         let contract =
@@ -104,19 +107,14 @@ impl RefreshBoxWrapper {
         Ok(Self {
             ergo_box: b,
             contract,
+            refresh_nft_token,
         })
     }
 }
 
 impl RefreshBox for RefreshBoxWrapper {
     fn refresh_nft_token(&self) -> Token {
-        self.ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone()
+        self.refresh_nft_token.clone()
     }
 
     fn get_box(&self) -> &ErgoBox {
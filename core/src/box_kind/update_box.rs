@@ -27,19 +27,20 @@ pub enum UpdateBoxError {
 pub struct UpdateBoxWrapper {
     ergo_box: ErgoBox,
     contract: UpdateContract,
+    update_nft: Token,
 }
 
 impl UpdateBoxWrapper {
     pub fn new(b: ErgoBox, inputs: &UpdateBoxWrapperInputs) -> Result<Self, UpdateBoxError> {
-        let update_token_id = b
+        let update_nft = b
             .tokens
             .as_ref()
             .ok_or(UpdateBoxError::NoTokens)?
             .get(0)
             .ok_or(UpdateBoxError::NoTokens)?
-            .token_id;
-        if update_token_id != inputs.update_nft_token_id.token_id() {
-            return Err(UpdateBoxError::IncorrectUpdateTokenId(update_token_id));
+            .clone();
+        if update_nft.token_id != inputs.update_nft_token_id.token_id() {
+            return Err(UpdateBoxError::IncorrectUpdateTokenId(update_nft.token_id));
         }
         let contract =
             UpdateContract::from_ergo_tree(b.ergo_tree.clone(), &inputs.contract_inputs)?;
@@ -47,19 +48,14 @@ impl UpdateBoxWrapper {
         Ok(Self {
             ergo_box: b,
             contract,
+            update_nft,
         })
     }
     pub fn ergo_tree(&self) -> ErgoTree {
         self.contract.ergo_tree()
     }
     pub fn update_nft(&self) -> Token {
-        self.ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone()
+        self.update_nft.clone()
     }
     pub fn ballot_token_id(&self) -> TokenId {
         self.contract.ballot_token_id()
@@ -1,8 +1,17 @@
+pub mod backtest;
 pub mod bootstrap;
+pub mod diff_contracts;
+pub mod doctor;
 pub mod extract_reward_tokens;
 pub mod import_pool_update;
 pub mod prepare_update;
 pub mod print_reward_tokens;
+pub mod replay;
+pub mod rotate_address;
+#[cfg(any(test, feature = "sim"))]
+pub mod simulate;
+pub mod snapshot_pool;
+pub mod test_sources;
 pub mod transfer_oracle_token;
 pub mod update_pool;
 pub mod vote_update_pool;
@@ -0,0 +1,484 @@
+//! Replay of recorded per-source rates through a proposed aggregation config, to tune it before
+//! it is used for posting.
+//!
+//! The raw rates of the sources come from the datapoints of the audit log, or from a CSV file in
+//! the format of `export-history` (a `height` column, an optional `value` column with the posted
+//! datapoint and a column per source), e.g. prices downloaded from the exchanges. Every sample is
+//! aggregated with the proposed config and compared with the datapoint actually posted and with the
+//! pool rate of the epoch that followed it in the local epoch history.
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::audit_log;
+use crate::audit_log::get_audit_log_file_path;
+use crate::audit_log::AuditLogError;
+use crate::audit_log::SubmissionResult;
+use crate::datapoint_source::SourceRate;
+use crate::epoch_history;
+use crate::epoch_history::get_epoch_history_file_path;
+use crate::epoch_history::EpochHistoryEntry;
+use crate::epoch_history::EpochHistoryError;
+
+#[derive(Debug, Error)]
+pub enum BacktestError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("yaml error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("audit log error: {0}")]
+    AuditLog(#[from] AuditLogError),
+    #[error("epoch history error: {0}")]
+    EpochHistory(#[from] EpochHistoryError),
+    #[error("invalid CSV line {line}: {reason}")]
+    InvalidCsv { line: usize, reason: String },
+    #[error("invalid aggregation config: {0}")]
+    InvalidConfig(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    /// Average of the source rates, as done by the predefined sources
+    #[default]
+    Mean,
+    Median,
+}
+
+/// Aggregation config to try
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct AggregationConfig {
+    #[serde(default)]
+    pub aggregation: Aggregation,
+    /// Samples with fewer sources left after dropping the outliers are not posted (default 1)
+    pub min_sources: Option<usize>,
+    /// Sources further than this percentage from the median of the sample are dropped
+    pub max_source_deviation_percent: Option<f64>,
+    /// Weight of the new aggregated rate in an exponential moving average of the aggregated rates,
+    /// in (0, 1]. Not smoothed if not set.
+    pub smoothing_alpha: Option<f64>,
+}
+
+impl AggregationConfig {
+    pub fn load(path: &Path) -> Result<Self, BacktestError> {
+        let config: AggregationConfig = serde_yaml::from_str(&std::fs::read_to_string(path)?)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), BacktestError> {
+        if let Some(alpha) = self.smoothing_alpha {
+            if !(alpha > 0.0 && alpha <= 1.0) {
+                return Err(BacktestError::InvalidConfig(format!(
+                    "smoothing_alpha {} is not in (0, 1]",
+                    alpha
+                )));
+            }
+        }
+        if let Some(deviation) = self.max_source_deviation_percent {
+            if deviation <= 0.0 {
+                return Err(BacktestError::InvalidConfig(format!(
+                    "max_source_deviation_percent {} is not positive",
+                    deviation
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Aggregated rate of the sources kept, with their number. `None` if fewer than `min_sources`
+    /// are kept.
+    pub fn aggregate(&self, sources: &[SourceRate]) -> Option<(f64, usize)> {
+        let rates: Vec<f64> = sources.iter().map(|s| s.rate).collect();
+        let kept: Vec<f64> = match (self.max_source_deviation_percent, median(&rates)) {
+            (Some(max_deviation), Some(median)) => rates
+                .into_iter()
+                .filter(|rate| (rate - median).abs() * 100.0 <= max_deviation * median.abs())
+                .collect(),
+            (Some(_), None) | (None, _) => rates,
+        };
+        if kept.is_empty() || kept.len() < self.min_sources.unwrap_or(1) {
+            return None;
+        }
+        let rate = match self.aggregation {
+            Aggregation::Mean => kept.iter().sum::<f64>() / kept.len() as f64,
+            Aggregation::Median => median(&kept)?,
+        };
+        Some((rate, kept.len()))
+    }
+}
+
+fn median(rates: &[f64]) -> Option<f64> {
+    if rates.is_empty() {
+        return None;
+    }
+    let mut sorted = rates.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    Some(if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    })
+}
+
+/// Raw rates of the sources at a height, with the datapoint posted for them if known
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub height: u32,
+    pub posted: Option<i64>,
+    pub sources: Vec<SourceRate>,
+}
+
+fn in_range(height: u32, from_height: Option<u32>, to_height: Option<u32>) -> bool {
+    from_height.map_or(true, |h| height >= h) && to_height.map_or(true, |h| height <= h)
+}
+
+/// Samples of the datapoints submitted by the oracle, from the audit log
+pub fn samples_from_audit_log(
+    path: &Path,
+    from_height: Option<u32>,
+    to_height: Option<u32>,
+) -> Result<Vec<Sample>, BacktestError> {
+    Ok(audit_log::read_entries(path)?
+        .into_iter()
+        .filter(|e| e.submission == SubmissionResult::Submitted)
+        .filter_map(|e| e.datapoint)
+        .filter(|d| in_range(d.height, from_height, to_height))
+        .map(|d| Sample {
+            height: d.height,
+            posted: Some(d.rate),
+            sources: d.sources,
+        })
+        .collect())
+}
+
+/// Samples of a CSV file with the `export-history` columns, empty cells for the sources that did
+/// not respond
+pub fn samples_from_csv(
+    reader: impl BufRead,
+    from_height: Option<u32>,
+    to_height: Option<u32>,
+) -> Result<Vec<Sample>, BacktestError> {
+    let mut lines = reader.lines();
+    let header: Vec<String> = match lines.next() {
+        Some(line) => line?.split(',').map(|c| c.trim().to_string()).collect(),
+        None => return Ok(Vec::new()),
+    };
+    let column = |name: &str| header.iter().position(|c| c == name);
+    let height_column = column("height").ok_or_else(|| BacktestError::InvalidCsv {
+        line: 1,
+        reason: "no height column".to_string(),
+    })?;
+    let value_column = column("value");
+    let source_columns: Vec<usize> = (0..header.len())
+        .filter(|i| !["height", "timestamp", "value", "tx_id"].contains(&header[*i].as_str()))
+        .collect();
+    let mut samples = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let line_number = i + 2;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+        let invalid = |reason: String| BacktestError::InvalidCsv {
+            line: line_number,
+            reason,
+        };
+        let height: u32 = cells
+            .get(height_column)
+            .and_then(|c| c.parse().ok())
+            .ok_or_else(|| invalid("invalid height".to_string()))?;
+        if !in_range(height, from_height, to_height) {
+            continue;
+        }
+        let posted = match value_column.and_then(|c| cells.get(c)) {
+            Some(cell) if !cell.is_empty() => Some(
+                cell.parse()
+                    .map_err(|_| invalid(format!("invalid value {}", cell)))?,
+            ),
+            Some(_) | None => None,
+        };
+        let mut sources = Vec::new();
+        for source_column in &source_columns {
+            match cells.get(*source_column) {
+                Some(cell) if !cell.is_empty() => sources.push(SourceRate {
+                    source: header[*source_column].clone(),
+                    rate: cell
+                        .parse()
+                        .map_err(|_| invalid(format!("invalid rate {}", cell)))?,
+                }),
+                Some(_) | None => (),
+            }
+        }
+        samples.push(Sample {
+            height,
+            posted,
+            sources,
+        });
+    }
+    Ok(samples)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestRow {
+    pub height: u32,
+    pub posted: Option<i64>,
+    /// Datapoint of the proposed config, `None` if the sample has too few sources
+    pub backtest: Option<i64>,
+    pub sources_used: usize,
+    /// Rate of the first pool box created after the sample
+    pub pool_rate: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+pub struct BacktestReport {
+    pub rows: Vec<BacktestRow>,
+}
+
+pub(crate) fn deviation_percent(value: i64, reference: i64) -> Option<f64> {
+    if reference == 0 {
+        return None;
+    }
+    Some((value - reference) as f64 * 100.0 / reference as f64)
+}
+
+impl BacktestReport {
+    /// Mean and maximum absolute deviation in percent of the backtest datapoints from the
+    /// reference of the rows
+    fn deviation_stats(
+        &self,
+        reference: impl Fn(&BacktestRow) -> Option<i64>,
+    ) -> Option<(f64, f64)> {
+        let deviations: Vec<f64> = self
+            .rows
+            .iter()
+            .filter_map(|row| deviation_percent(row.backtest?, reference(row)?))
+            .map(f64::abs)
+            .collect();
+        if deviations.is_empty() {
+            return None;
+        }
+        let mean = deviations.iter().sum::<f64>() / deviations.len() as f64;
+        let max = deviations.iter().cloned().fold(0.0, f64::max);
+        Some((mean, max))
+    }
+
+    pub fn deviation_from_posted(&self) -> Option<(f64, f64)> {
+        self.deviation_stats(|row| row.posted)
+    }
+
+    pub fn deviation_from_pool(&self) -> Option<(f64, f64)> {
+        self.deviation_stats(|row| row.pool_rate)
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.rows
+            .iter()
+            .filter(|row| row.backtest.is_none())
+            .count()
+    }
+}
+
+impl fmt::Display for BacktestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cell = |value: Option<i64>| value.map_or("-".to_string(), |v| v.to_string());
+        writeln!(f, "height,posted,backtest,sources,pool_rate")?;
+        for row in &self.rows {
+            writeln!(
+                f,
+                "{},{},{},{},{}",
+                row.height,
+                cell(row.posted),
+                cell(row.backtest),
+                row.sources_used,
+                cell(row.pool_rate)
+            )?;
+        }
+        writeln!(
+            f,
+            "{} samples, {} not posted for too few sources",
+            self.rows.len(),
+            self.skipped()
+        )?;
+        if let Some((mean, max)) = self.deviation_from_posted() {
+            writeln!(
+                f,
+                "deviation from the posted datapoints: mean {:.3}%, max {:.3}%",
+                mean, max
+            )?;
+        }
+        if let Some((mean, max)) = self.deviation_from_pool() {
+            writeln!(
+                f,
+                "deviation from the pool rates: mean {:.3}%, max {:.3}%",
+                mean, max
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Aggregate the samples (oldest first) with the config and compare them with the recorded epochs
+pub fn backtest(
+    config: &AggregationConfig,
+    samples: &[Sample],
+    epochs: &[EpochHistoryEntry],
+) -> BacktestReport {
+    let mut smoothed: Option<f64> = None;
+    let rows = samples
+        .iter()
+        .map(|sample| {
+            let aggregated = config.aggregate(&sample.sources);
+            let backtest = aggregated.map(|(rate, _)| {
+                let rate = match (config.smoothing_alpha, smoothed) {
+                    (Some(alpha), Some(previous)) => alpha * rate + (1.0 - alpha) * previous,
+                    (Some(_), None) | (None, _) => rate,
+                };
+                smoothed = Some(rate);
+                rate as i64
+            });
+            BacktestRow {
+                height: sample.height,
+                posted: sample.posted,
+                backtest,
+                sources_used: aggregated.map_or(0, |(_, used)| used),
+                pool_rate: epochs
+                    .iter()
+                    .filter(|e| e.height > sample.height)
+                    .min_by_key(|e| e.height)
+                    .map(|e| e.rate),
+            }
+        })
+        .collect();
+    BacktestReport { rows }
+}
+
+/// Backtest of the aggregation config file on the samples of the CSV file (the audit log if not
+/// set), compared with the local epoch history
+pub fn run_backtest(
+    config_file: &Path,
+    input: Option<&Path>,
+    from_height: Option<u32>,
+    to_height: Option<u32>,
+) -> Result<BacktestReport, BacktestError> {
+    let config = AggregationConfig::load(config_file)?;
+    let mut samples = match input {
+        Some(input) => {
+            samples_from_csv(BufReader::new(File::open(input)?), from_height, to_height)?
+        }
+        None => samples_from_audit_log(&get_audit_log_file_path(), from_height, to_height)?,
+    };
+    samples.sort_by_key(|s| s.height);
+    let epochs = epoch_history::read_entries(&get_epoch_history_file_path())?;
+    Ok(backtest(&config, &samples, &epochs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(rates: &[f64]) -> Vec<SourceRate> {
+        rates
+            .iter()
+            .enumerate()
+            .map(|(i, rate)| SourceRate {
+                source: format!("source{}", i),
+                rate: *rate,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let config = AggregationConfig {
+            aggregation: Aggregation::Mean,
+            min_sources: Some(2),
+            max_source_deviation_percent: Some(5.0),
+            smoothing_alpha: None,
+        };
+        // the outlier is dropped before the mean
+        assert_eq!(
+            config.aggregate(&sources(&[100.0, 102.0, 1000.0])),
+            Some((101.0, 2))
+        );
+        assert_eq!(config.aggregate(&sources(&[100.0, 1000.0])), None);
+        let median_config = AggregationConfig {
+            aggregation: Aggregation::Median,
+            ..Default::default()
+        };
+        assert_eq!(
+            median_config.aggregate(&sources(&[100.0, 102.0, 1000.0])),
+            Some((102.0, 3))
+        );
+        assert_eq!(median_config.aggregate(&[]), None);
+    }
+
+    #[test]
+    fn test_backtest() {
+        let csv = "height,timestamp,value,tx_id,a,b\n\
+                   100,1,200,tx1,200,200\n\
+                   130,2,,tx2,300,\n\
+                   160,3,400,tx3,,\n\
+                   190,4,250,tx4,250,250\n";
+        let samples = samples_from_csv(csv.as_bytes(), None, Some(180)).unwrap();
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[1].posted, None);
+        assert_eq!(samples[1].sources.len(), 1);
+        let epochs = vec![EpochHistoryEntry {
+            epoch_id: 2,
+            rate: 210,
+            height: 120,
+            pool_box_id: String::new(),
+            oracles: vec![],
+        }];
+        let config = AggregationConfig {
+            smoothing_alpha: Some(0.5),
+            ..Default::default()
+        };
+        let report = backtest(&config, &samples, &epochs);
+        assert_eq!(report.rows[0].backtest, Some(200));
+        assert_eq!(report.rows[0].pool_rate, Some(210));
+        // smoothed with the previous datapoint
+        assert_eq!(report.rows[1].backtest, Some(250));
+        assert_eq!(report.rows[1].pool_rate, None);
+        assert_eq!(report.rows[2].backtest, None);
+        assert_eq!(report.skipped(), 1);
+        assert_eq!(report.deviation_from_posted(), Some((0.0, 0.0)));
+        let (mean, _) = report.deviation_from_pool().unwrap();
+        assert!((mean - 100.0 * 10.0 / 210.0).abs() < 1e-9);
+
+        assert!(matches!(
+            samples_from_csv("height,a\nabc,1\n".as_bytes(), None, None),
+            Err(BacktestError::InvalidCsv { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_config() {
+        let config: AggregationConfig = serde_yaml::from_str(
+            "aggregation: median
+min_sources: 3
+max_source_deviation_percent: 2.5
+",
+        )
+        .unwrap();
+        assert_eq!(config.aggregation, Aggregation::Median);
+        assert_eq!(config.min_sources, Some(3));
+        assert!(config.validate().is_ok());
+        assert!(AggregationConfig {
+            smoothing_alpha: Some(1.5),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
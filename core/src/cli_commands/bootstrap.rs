@@ -1,5 +1,11 @@
 //! Bootstrap a new oracle pool
-use std::{convert::TryInto, io::Write, path::Path};
+pub mod air_gapped;
+
+use std::{
+    convert::TryInto,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 use ergo_lib::{
     chain::{
@@ -8,7 +14,7 @@ use ergo_lib::{
     },
     ergotree_ir::{
         chain::{
-            address::{Address, AddressEncoderError, NetworkAddress},
+            address::{Address, AddressEncoderError, NetworkAddress, NetworkPrefix},
             ergo_box::{
                 box_value::{BoxValue, BoxValueError},
                 ErgoBox,
@@ -30,25 +36,27 @@ use thiserror::Error;
 
 use crate::{
     box_kind::{make_pool_box_candidate, make_refresh_box_candidate},
+    cold_signing::ColdSigningFormat,
     contracts::{
         ballot::{BallotContractError, BallotContractParameters},
         oracle::OracleContractParameters,
         pool::{PoolContract, PoolContractError, PoolContractInputs, PoolContractParameters},
         refresh::{
-            RefreshContract, RefreshContractError, RefreshContractInputs, RefreshContractParameters,
+            RefreshContract, RefreshContractError, RefreshContractInputs,
+            RefreshContractParameters, RefreshContractParametersInputs,
         },
         update::{
             UpdateContract, UpdateContractError, UpdateContractInputs, UpdateContractParameters,
         },
     },
-    explorer_api::wait_for_txs_confirmation,
+    explorer_api::{explorer_url::default_explorer_api_url, wait_for_txs_confirmation},
     node_interface::{
         assert_wallet_unlocked,
         node_api::{NodeApi, NodeApiError},
         SignTransactionWithInputs, SubmitTransaction,
     },
-    oracle_config::{BASE_FEE, ORACLE_CONFIG},
-    oracle_types::{BlockHeight, EpochCounter},
+    oracle_config::OracleConfig,
+    oracle_types::{BlockHeight, EpochCounter, EpochLength, MinDatapoints},
     pool_config::{
         PoolConfig, PoolConfigError, PredefinedDataPointSource, TokenIds,
         DEFAULT_POOL_CONFIG_FILE_NAME,
@@ -61,34 +69,101 @@ use crate::{
     wallet::{WalletDataError, WalletDataSource},
 };
 
+use self::air_gapped::AirGappedSigner;
+
+/// Number of txs of the bootstrap chained tx: 6 token mints, the pool box and the refresh box
+const BOOTSTRAP_TX_COUNT: u32 = 8;
+/// Boxes holding the minted tokens, each with `erg_value_per_box`
+const BOOTSTRAP_TOKEN_BOX_COUNT: u32 = 6;
+
 /// Loads bootstrap configuration file and performs the chain-transactions for minting of tokens and
 /// box creations. An oracle configuration file is then created which contains the `TokenId`s of the
-/// minted tokens.
-pub fn bootstrap(config_file_name: String) -> Result<(), anyhow::Error> {
-    let oracle_config = &ORACLE_CONFIG;
+/// minted tokens. With `estimate_only` the ERG needed is printed and nothing is submitted. With
+/// `air_gapped_dir` the txs are signed offline one run at a time (see [`air_gapped`]).
+pub fn bootstrap(
+    config_file_name: String,
+    oracle_config: &OracleConfig,
+    estimate_only: bool,
+    air_gapped_dir: Option<PathBuf>,
+    cold_signing_format: ColdSigningFormat,
+) -> Result<(), anyhow::Error> {
     let s = std::fs::read_to_string(config_file_name)?;
     let config: BootstrapConfig = serde_yaml::from_str(&s)?;
 
     let node_api = NodeApi::new(oracle_config.node_api_key.clone(), &oracle_config.node_url);
-    assert_wallet_unlocked(&node_api.node);
-    let change_address = node_api.get_change_address()?;
+    let air_gapped_signer = match air_gapped_dir {
+        Some(dir) => Some(AirGappedSigner::open(
+            dir,
+            cold_signing_format,
+            oracle_config.oracle_address.clone(),
+            &node_api,
+        )?),
+        None => {
+            assert_wallet_unlocked(&node_api.node);
+            None
+        }
+    };
+    let wallet: &dyn WalletDataSource = match &air_gapped_signer {
+        Some(signer) => signer,
+        None => &node_api,
+    };
+    let change_address = wallet.get_change_address()?;
     debug!("Change address: {:?}", change_address);
-    let erg_value_per_box = config.oracle_contract_parameters.min_storage_rent;
+    let erg_value_per_box = config
+        .oracle_contract_parameters
+        .min_storage_rent
+        .max(oracle_config.min_box_value());
+    let estimate = estimate_bootstrap_cost(erg_value_per_box, oracle_config.base_fee(), wallet)?;
+    if estimate_only {
+        println!("{}", estimate);
+    }
+    if let Some(shortfall) = estimate.shortfall() {
+        return Err(BootstrapError::NotEnoughErg {
+            required: estimate.total,
+            available: estimate.wallet_balance,
+            shortfall,
+        }
+        .into());
+    }
+    if estimate_only {
+        return Ok(());
+    }
+    let (tx_signer, height): (&dyn SignTransactionWithInputs, _) = match &air_gapped_signer {
+        Some(signer) => (signer, signer.height()),
+        None => (
+            &node_api.node,
+            BlockHeight(node_api.node.current_block_height()? as u32),
+        ),
+    };
     let input = BootstrapInput {
         oracle_address: oracle_config.oracle_address.clone(),
         config,
-        wallet: &node_api as &dyn WalletDataSource,
-        tx_signer: &node_api.node as &dyn SignTransactionWithInputs,
+        wallet,
+        tx_signer,
         submit_tx: &node_api.node as &dyn SubmitTransaction,
-        tx_fee: *BASE_FEE,
+        tx_fee: oracle_config.base_fee(),
         erg_value_per_box,
         change_address: change_address.address(),
-        height: BlockHeight(node_api.node.current_block_height()? as u32),
+        height,
     };
-    let (oracle_config, submitted_tx_ids) = perform_bootstrap_chained_transaction(input)?;
-    wait_for_txs_confirmation(submitted_tx_ids);
+    let result = perform_bootstrap_chained_transaction(input);
+    if let Some(signer) = &air_gapped_signer {
+        if let Some(step) = signer.awaiting_signature() {
+            println!(
+                "Cold signing request of bootstrap tx {}/{} written to {}, sign it with a wallet holding the key of {} and save the signed tx JSON to {} (do not submit it), then run the command again",
+                step,
+                BOOTSTRAP_TX_COUNT,
+                signer.request_path(step).display(),
+                oracle_config.oracle_address.to_base58(),
+                signer.signed_path(step).display()
+            );
+            return Ok(());
+        }
+    }
+    let (pool_config, submitted_tx_ids) = result?;
+    wait_for_txs_confirmation(submitted_tx_ids, oracle_config);
     info!("Bootstrap chain-transaction complete");
-    let s = serde_yaml::to_string(&oracle_config)?;
+    let s = serde_yaml::to_string(&pool_config)?;
     let mut file = std::fs::File::create(DEFAULT_POOL_CONFIG_FILE_NAME)?;
     file.write_all(s.as_bytes())?;
     info!(
@@ -98,20 +173,127 @@ pub fn bootstrap(config_file_name: String) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
-pub fn generate_bootstrap_config_template(config_file_name: String) -> Result<(), BootstrapError> {
+pub fn generate_bootstrap_config_template(
+    config_file_name: String,
+    network: NetworkPrefix,
+) -> Result<(), BootstrapError> {
     if Path::new(&config_file_name).exists() {
         return Err(BootstrapError::ConfigFilenameAlreadyExists);
     }
 
-    let config = BootstrapConfig::default();
-    let config_serde = BootstrapConfigSerde::from(config);
-
-    let s = serde_yaml::to_string(&config_serde)?;
+    let s = bootstrap_config_template(network)?;
     let mut file = std::fs::File::create(&config_file_name)?;
     file.write_all(s.as_bytes())?;
     Ok(())
 }
 
+/// Bootstrap config of the network, headed by comments on the oracle config to bootstrap with
+pub fn bootstrap_config_template(network: NetworkPrefix) -> Result<String, BootstrapError> {
+    let config = BootstrapConfig::for_network(network);
+    let (network_name, node_port, address_prefix) = match network {
+        NetworkPrefix::Mainnet => ("mainnet", 9053, '9'),
+        NetworkPrefix::Testnet => ("testnet", 9052, '3'),
+    };
+    let header = format!(
+        "# Bootstrap config template for {network_name}.
+# The oracle_config.yaml used to bootstrap needs for this network:
+#   node_url: http://127.0.0.1:{node_port}
+#   oracle_address: <P2PK address of the node wallet, starting with {address_prefix}>
+#   explorer_url: {explorer_url}
+# Example contract parameters: epoch_length {epoch_length} blocks, min_data_points {min_data_points}, min_votes {min_votes}.
+",
+        explorer_url = default_explorer_api_url(network),
+        epoch_length = config.refresh_contract_parameters.epoch_length().0,
+        min_data_points = config.refresh_contract_parameters.min_data_points().0,
+        min_votes = config.update_contract_parameters.min_votes(),
+    );
+    let config_serde = BootstrapConfigSerde::from(config);
+    Ok(header + &serde_yaml::to_string(&config_serde)?)
+}
+
+/// ERG needed by the bootstrap chained tx, in nanoERG
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootstrapCostEstimate {
+    pub tx_count: u32,
+    pub tx_fee: u64,
+    /// Value of every created box
+    pub box_value: u64,
+    /// Boxes with the minted tokens, the oracle and ballot tokens included
+    pub token_box_count: u32,
+    pub total_fees: u64,
+    pub total_token_boxes: u64,
+    /// The pool and refresh boxes
+    pub total_pool_and_refresh_boxes: u64,
+    pub total: u64,
+    pub wallet_balance: u64,
+}
+
+impl BootstrapCostEstimate {
+    /// Missing nanoERG, if the wallet does not hold enough
+    pub fn shortfall(&self) -> Option<u64> {
+        self.total
+            .checked_sub(self.wallet_balance)
+            .filter(|shortfall| *shortfall > 0)
+    }
+}
+
+impl std::fmt::Display for BootstrapCostEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Bootstrap cost estimate (nanoERG):")?;
+        writeln!(
+            f,
+            "  tx fees:               {} ({} txs x {})",
+            self.total_fees, self.tx_count, self.tx_fee
+        )?;
+        writeln!(
+            f,
+            "  token boxes:           {} ({} boxes x {})",
+            self.total_token_boxes, self.token_box_count, self.box_value
+        )?;
+        writeln!(
+            f,
+            "  pool and refresh boxes: {} (2 boxes x {})",
+            self.total_pool_and_refresh_boxes, self.box_value
+        )?;
+        writeln!(f, "  total:                 {}", self.total)?;
+        write!(f, "  wallet balance:        {}", self.wallet_balance)?;
+        if let Some(shortfall) = self.shortfall() {
+            write!(f, "\n  shortfall:             {}", shortfall)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compute the ERG needed by the bootstrap chained tx and read the balance of the wallet
+pub fn estimate_bootstrap_cost(
+    erg_value_per_box: BoxValue,
+    tx_fee: BoxValue,
+    wallet: &dyn WalletDataSource,
+) -> Result<BootstrapCostEstimate, BootstrapError> {
+    let box_value = *erg_value_per_box.as_u64();
+    let tx_fee = *tx_fee.as_u64();
+    let pool_and_refresh_box_count = BOOTSTRAP_TX_COUNT - BOOTSTRAP_TOKEN_BOX_COUNT;
+    let total_fees = tx_fee * BOOTSTRAP_TX_COUNT as u64;
+    let total_token_boxes = box_value * BOOTSTRAP_TOKEN_BOX_COUNT as u64;
+    let total_pool_and_refresh_boxes = box_value * pool_and_refresh_box_count as u64;
+    let wallet_balance = wallet
+        .get_unspent_wallet_boxes()?
+        .iter()
+        .map(|b| *b.value.as_u64())
+        .sum();
+    Ok(BootstrapCostEstimate {
+        tx_count: BOOTSTRAP_TX_COUNT,
+        tx_fee,
+        box_value,
+        token_box_count: BOOTSTRAP_TOKEN_BOX_COUNT,
+        total_fees,
+        total_token_boxes,
+        total_pool_and_refresh_boxes,
+        total: total_fees + total_token_boxes + total_pool_and_refresh_boxes,
+        wallet_balance,
+    })
+}
+
 pub struct BootstrapInput<'a> {
     pub oracle_address: NetworkAddress,
     pub config: BootstrapConfig,
@@ -171,7 +353,7 @@ pub(crate) fn perform_bootstrap_chained_transaction(
     // And so on.
 
     // This variable represents the index `i` described above.
-    let mut num_transactions_left = 8;
+    let mut num_transactions_left = BOOTSTRAP_TX_COUNT;
 
     let wallet_pk_ergo_tree = oracle_address.address().script()?;
     let guard = wallet_pk_ergo_tree.clone();
@@ -364,7 +546,7 @@ pub(crate) fn perform_bootstrap_chained_transaction(
     // Create pool box -----------------------------------------------------------------------------
     info!("Create and sign pool box tx");
 
-    // we don't have a working ORACLE_CONFIG during bootstrap so token ids are created without any checks
+    // we don't have a pool config during bootstrap so token ids are created without any checks
     let token_ids = TokenIds {
         pool_nft_token_id: PoolTokenId::from_token_id_unchecked(pool_nft_token.token_id),
         refresh_nft_token_id: RefreshTokenId::from_token_id_unchecked(refresh_nft_token.token_id),
@@ -595,6 +777,44 @@ impl Default for BootstrapConfig {
     }
 }
 
+impl BootstrapConfig {
+    /// Example config of the network. The testnet one has shorter epochs and fewer datapoints and
+    /// votes, so a pool can be tried with a couple of oracles.
+    pub fn for_network(network: NetworkPrefix) -> Self {
+        let config = BootstrapConfig::default();
+        match network {
+            NetworkPrefix::Mainnet => config,
+            NetworkPrefix::Testnet => {
+                let refresh = &config.refresh_contract_parameters;
+                let refresh_contract_parameters =
+                    RefreshContractParameters::build_with(RefreshContractParametersInputs {
+                        ergo_tree_bytes: refresh.ergo_tree_bytes(),
+                        pool_nft_index: refresh.pool_nft_index(),
+                        oracle_token_id_index: refresh.oracle_token_id_index(),
+                        min_data_points_index: refresh.min_data_points_index(),
+                        min_data_points: MinDatapoints(2),
+                        buffer_length_index: refresh.buffer_length_index(),
+                        buffer_length: refresh.buffer_length(),
+                        max_deviation_percent_index: refresh.max_deviation_percent_index(),
+                        max_deviation_percent: refresh.max_deviation_percent(),
+                        epoch_length_index: refresh.epoch_length_index(),
+                        epoch_length: EpochLength(10),
+                        reward_per_datapoint_index: refresh.reward_per_datapoint_index(),
+                        oracle_reward_per_datapoint: refresh.oracle_reward_per_datapoint(),
+                    })
+                    .unwrap();
+                let update_contract_parameters =
+                    config.update_contract_parameters.with_min_votes(2).unwrap();
+                BootstrapConfig {
+                    refresh_contract_parameters,
+                    update_contract_parameters,
+                    ..config
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TokensToMint {
     pub pool_nft: NftMintDetails,
@@ -658,6 +878,12 @@ pub enum BootstrapError {
     PoolContractError(#[from] PoolContractError),
     #[error("WalletData error: {0}")]
     WalletData(#[from] WalletDataError),
+    #[error("Not enough ERG in the wallet: {required} nanoERG required, {available} available, {shortfall} missing")]
+    NotEnoughErg {
+        required: u64,
+        available: u64,
+        shortfall: u64,
+    },
 }
 
 #[cfg(test)]
@@ -669,7 +895,7 @@ pub(crate) mod tests {
             address::{AddressEncoder, NetworkAddress, NetworkPrefix},
             ergo_box::{ErgoBox, NonMandatoryRegisters},
         },
-        wallet::Wallet,
+        wallet::{tx_builder::SUGGESTED_TX_FEE, Wallet},
     };
     use sigma_test_util::force_any_val;
 
@@ -703,7 +929,7 @@ pub(crate) mod tests {
         let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
         let ergo_tree = address.address().script().unwrap();
 
-        let value = BASE_FEE.checked_mul_u32(10000).unwrap();
+        let value = SUGGESTED_TX_FEE().checked_mul_u32(10000).unwrap();
         let unspent_boxes = vec![ErgoBox::new(
             value,
             ergo_tree.clone(),
@@ -735,8 +961,8 @@ pub(crate) mod tests {
                 wallet: &wallet,
             },
             submit_tx: &submit_tx,
-            tx_fee: *BASE_FEE,
-            erg_value_per_box: *BASE_FEE,
+            tx_fee: SUGGESTED_TX_FEE(),
+            erg_value_per_box: SUGGESTED_TX_FEE(),
             change_address: change_address.address(),
             height,
         })
@@ -834,6 +1060,58 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn test_estimate_bootstrap_cost() {
+        let secret = force_any_val::<DlogProverInput>();
+        let ergo_tree = Address::P2Pk(secret.public_image()).script().unwrap();
+        let wallet = WalletDataMock {
+            unspent_boxes: vec![ErgoBox::new(
+                BoxValue::try_from(10_000_000u64).unwrap(),
+                ergo_tree,
+                None,
+                NonMandatoryRegisters::empty(),
+                1,
+                force_any_val::<TxId>(),
+                0,
+            )
+            .unwrap()],
+            change_address: AddressEncoder::unchecked_parse_network_address_from_str(
+                "9iHyKxXs2ZNLMp9N9gbUT9V8gTbsV7HED1C1VhttMfBUMPDyF7r",
+            )
+            .unwrap(),
+        };
+        let estimate = estimate_bootstrap_cost(
+            BoxValue::try_from(1_000_000u64).unwrap(),
+            BoxValue::try_from(1_100_000u64).unwrap(),
+            &wallet,
+        )
+        .unwrap();
+        assert_eq!(estimate.total_fees, 8_800_000);
+        assert_eq!(estimate.total_token_boxes, 6_000_000);
+        assert_eq!(estimate.total_pool_and_refresh_boxes, 2_000_000);
+        assert_eq!(estimate.total, 16_800_000);
+        assert_eq!(estimate.shortfall(), Some(6_800_000));
+        assert!(estimate
+            .to_string()
+            .contains("shortfall:             6800000"));
+    }
+
+    #[test]
+    fn test_network_templates() {
+        let mainnet = bootstrap_config_template(NetworkPrefix::Mainnet).unwrap();
+        assert!(mainnet.contains("node_url: http://127.0.0.1:9053"));
+        let config: BootstrapConfig = serde_yaml::from_str(&mainnet).unwrap();
+        assert_eq!(config.refresh_contract_parameters.epoch_length().0, 30);
+
+        let testnet = bootstrap_config_template(NetworkPrefix::Testnet).unwrap();
+        assert!(testnet.contains("node_url: http://127.0.0.1:9052"));
+        assert!(testnet.contains("https://api-testnet.ergoplatform.com/"));
+        let config: BootstrapConfig = serde_yaml::from_str(&testnet).unwrap();
+        assert_eq!(config.refresh_contract_parameters.epoch_length().0, 10);
+        assert_eq!(config.refresh_contract_parameters.min_data_points().0, 2);
+        assert_eq!(config.update_contract_parameters.min_votes(), 2);
+    }
+
     #[test]
     fn test_custom_contract_param() {
         let config: BootstrapConfig = serde_yaml::from_str("
@@ -855,6 +1133,8 @@ refresh_contract_parameters:
   max_deviation_percent: 5
   epoch_length_index: 0
   epoch_length: 30
+  reward_per_datapoint_index: 19
+  oracle_reward_per_datapoint: 1
 pool_contract_parameters:
   ergo_tree_bytes: 1004040204000e20546a576e5a7234753778214125442a472d4b614e645267556b587032733576380e206251655468576d5a7134743777217a25432a462d4a404e635266556a586e3272d801d6018cb2db6308b2a473000073010001d1ec93720173029372017303
   refresh_nft_index: 2
@@ -0,0 +1,394 @@
+//! Air-gapped bootstrap (`bootstrap --air-gapped <dir>`), for a pool creator keeping the key of the
+//! oracle address in an offline wallet instead of the node wallet.
+//!
+//! Every bootstrap tx spends an output of the previous one, so the txs are signed one at a time.
+//! Each run of the command rebuilds the chained tx from the boxes and the height saved in
+//! `<dir>/state.json` on the first run, takes the txs already signed in the directory and writes the
+//! EIP-19 cold signing request of the next one to `tx-<n>.request.json`. The tx signed by the
+//! wallet is put back as `tx-<n>.signed.json` (the JSON of the signed tx) and the command is run
+//! again. Once the 8 txs are signed, they are submitted through the node and the pool config is
+//! written as in a regular bootstrap. The wallet must not submit the signed txs itself.
+//!
+//! The boxes of the oracle address are read from the node by their ergo tree (`extraIndex` must
+//! be enabled), the node wallet is not used. The state file keeps the id of every signed tx and of
+//! the token it minted.
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::path::Path;
+use std::path::PathBuf;
+
+use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::Transaction;
+use ergo_lib::chain::transaction::TxIoVec;
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+use ergo_lib::ergotree_ir::serialization::SigmaParsingError;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
+use ergo_node_interface::node_interface::NodeError;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::cold_signing::reduce_with_boxes;
+use crate::cold_signing::ColdSigningError;
+use crate::cold_signing::ColdSigningFormat;
+use crate::cold_signing::ColdSigningRequest;
+use crate::node_interface::node_api::NodeApi;
+use crate::node_interface::node_api::NodeApiError;
+use crate::node_interface::SignTransactionWithInputs;
+use crate::oracle_types::BlockHeight;
+use crate::wallet::WalletDataError;
+use crate::wallet::WalletDataSource;
+
+pub const STATE_FILE_NAME: &str = "state.json";
+
+#[derive(Debug, Error)]
+pub enum AirGappedError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serde error: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("node error: {0}")]
+    Node(#[from] NodeError),
+    #[error("node api error: {0}")]
+    NodeApi(#[from] NodeApiError),
+    #[error("cold signing error: {0}")]
+    ColdSigning(#[from] ColdSigningError),
+    #[error("SigmaParsing error: {0}")]
+    SigmaParse(#[from] SigmaParsingError),
+    #[error("sigma serialization error: {0}")]
+    SigmaSerialization(#[from] SigmaSerializationError),
+    #[error(
+        "the signed tx {found} of step {step} is not the requested tx {expected}, remove {path} if \
+         the bootstrap config or the boxes changed since the first run"
+    )]
+    TxIdMismatch {
+        step: usize,
+        expected: String,
+        found: String,
+        path: String,
+    },
+}
+
+/// Progress of an air-gapped bootstrap, saved in the directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AirGappedState {
+    /// Height the txs are built at
+    pub height: u32,
+    /// Unspent boxes of the oracle address on the first run
+    pub wallet_boxes: Vec<ErgoBox>,
+    /// Txs signed so far, in the order of the chain
+    #[serde(default)]
+    pub signed: Vec<SignedStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedStep {
+    pub tx_id: String,
+    /// Token minted by the tx, none for the pool box and refresh box txs
+    pub minted_token_id: Option<String>,
+}
+
+impl AirGappedState {
+    pub fn load(dir: &Path) -> Result<Option<Self>, AirGappedError> {
+        let path = dir.join(STATE_FILE_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&std::fs::read_to_string(path)?)?))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), AirGappedError> {
+        std::fs::write(
+            dir.join(STATE_FILE_NAME),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// Takes the txs signed offline from the directory and writes the request of the next one
+pub struct AirGappedSigner {
+    dir: PathBuf,
+    format: ColdSigningFormat,
+    sender: NetworkAddress,
+    state_context: ErgoStateContext,
+    state: RefCell<AirGappedState>,
+    step: Cell<usize>,
+    awaiting: Cell<Option<usize>>,
+}
+
+impl AirGappedSigner {
+    pub fn new(
+        dir: PathBuf,
+        format: ColdSigningFormat,
+        sender: NetworkAddress,
+        state: AirGappedState,
+        state_context: ErgoStateContext,
+    ) -> Self {
+        AirGappedSigner {
+            dir,
+            format,
+            sender,
+            state_context,
+            state: RefCell::new(state),
+            step: Cell::new(0),
+            awaiting: Cell::new(None),
+        }
+    }
+
+    /// Resume the bootstrap of the directory, or start it with the current boxes of the oracle
+    /// address and the current height
+    pub fn open(
+        dir: PathBuf,
+        format: ColdSigningFormat,
+        sender: NetworkAddress,
+        node_api: &NodeApi,
+    ) -> Result<Self, AirGappedError> {
+        std::fs::create_dir_all(&dir)?;
+        let state = match AirGappedState::load(&dir)? {
+            Some(state) => state,
+            None => {
+                let ergo_tree = sender.address().script()?.to_base16_bytes()?;
+                let state = AirGappedState {
+                    height: node_api.node.current_block_height()? as u32,
+                    wallet_boxes: node_api.get_unspent_boxes_by_ergo_tree(&ergo_tree)?,
+                    signed: Vec::new(),
+                };
+                state.save(&dir)?;
+                state
+            }
+        };
+        let state_context = node_api.get_state_context()?;
+        Ok(Self::new(dir, format, sender, state, state_context))
+    }
+
+    pub fn height(&self) -> BlockHeight {
+        BlockHeight(self.state.borrow().height)
+    }
+
+    pub fn state(&self) -> AirGappedState {
+        self.state.borrow().clone()
+    }
+
+    pub fn request_path(&self, step: usize) -> PathBuf {
+        self.dir.join(format!("tx-{}.request.json", step))
+    }
+
+    pub fn signed_path(&self, step: usize) -> PathBuf {
+        self.dir.join(format!("tx-{}.signed.json", step))
+    }
+
+    /// The step whose request was written by the last run of the chained tx, if any
+    pub fn awaiting_signature(&self) -> Option<usize> {
+        self.awaiting.get()
+    }
+
+    fn read_signed(
+        &self,
+        step: usize,
+        unsigned_tx: &UnsignedTransaction,
+    ) -> Result<Transaction, AirGappedError> {
+        let path = self.signed_path(step);
+        let signed_tx: Transaction = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        if signed_tx.id() != unsigned_tx.id() {
+            return Err(AirGappedError::TxIdMismatch {
+                step,
+                expected: String::from(unsigned_tx.id()),
+                found: String::from(signed_tx.id()),
+                path: self.dir.display().to_string(),
+            });
+        }
+        // a minted token has the id of the first input box
+        let first_input_id: TokenId = unsigned_tx.inputs.first().box_id.into();
+        let minted_token_id = unsigned_tx
+            .output_candidates
+            .iter()
+            .flat_map(|candidate| candidate.tokens.clone().into_iter().flatten())
+            .find(|token| token.token_id == first_input_id)
+            .map(|token| String::from(token.token_id));
+        let mut state = self.state.borrow_mut();
+        state.signed.truncate(step - 1);
+        state.signed.push(SignedStep {
+            tx_id: String::from(signed_tx.id()),
+            minted_token_id,
+        });
+        state.save(&self.dir)?;
+        Ok(signed_tx)
+    }
+
+    fn write_request(
+        &self,
+        step: usize,
+        unsigned_tx: &UnsignedTransaction,
+        inputs: TxIoVec<ErgoBox>,
+        data_boxes: Option<TxIoVec<ErgoBox>>,
+    ) -> Result<(), AirGappedError> {
+        let inputs = inputs.as_vec().clone();
+        let reduced_tx = reduce_with_boxes(
+            unsigned_tx.clone(),
+            inputs.clone(),
+            data_boxes.map(|bs| bs.as_vec().clone()).unwrap_or_default(),
+            &self.state_context,
+        )?;
+        let request = ColdSigningRequest::new(&reduced_tx, &self.sender, &inputs)?;
+        request.write(&self.request_path(step), self.format)?;
+        Ok(())
+    }
+}
+
+impl SignTransactionWithInputs for AirGappedSigner {
+    fn sign_transaction_with_inputs(
+        &self,
+        unsigned_tx: &UnsignedTransaction,
+        inputs: TxIoVec<ErgoBox>,
+        data_boxes: Option<TxIoVec<ErgoBox>>,
+    ) -> crate::node_interface::Result<Transaction> {
+        let step = self.step.get() + 1;
+        self.step.set(step);
+        if self.signed_path(step).exists() {
+            return self
+                .read_signed(step, unsigned_tx)
+                .map_err(|e| NodeError::BadRequest(e.to_string()));
+        }
+        self.write_request(step, unsigned_tx, inputs, data_boxes)
+            .map_err(|e| NodeError::BadRequest(e.to_string()))?;
+        self.awaiting.set(Some(step));
+        Err(NodeError::BadRequest(format!(
+            "tx {} of the bootstrap is not signed yet",
+            step
+        )))
+    }
+}
+
+impl WalletDataSource for AirGappedSigner {
+    fn get_unspent_wallet_boxes(&self) -> Result<Vec<ErgoBox>, WalletDataError> {
+        Ok(self.state.borrow().wallet_boxes.clone())
+    }
+
+    fn get_change_address(&self) -> Result<NetworkAddress, WalletDataError> {
+        Ok(self.sender.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::chain::transaction::reduced::ReducedTransaction;
+    use ergo_lib::chain::transaction::TxId;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::address::Address;
+    use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+    use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisters;
+    use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+    use ergo_lib::wallet::tx_builder::SUGGESTED_TX_FEE;
+    use ergo_lib::wallet::Wallet;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::cli_commands::bootstrap::perform_bootstrap_chained_transaction;
+    use crate::cli_commands::bootstrap::tests::SubmitTxMock;
+    use crate::cli_commands::bootstrap::BootstrapConfig;
+    use crate::cli_commands::bootstrap::BootstrapInput;
+    use crate::cli_commands::bootstrap::BOOTSTRAP_TX_COUNT;
+
+    #[test]
+    fn test_air_gapped_bootstrap() {
+        let dir =
+            std::env::temp_dir().join(format!("oracle-core-air-gapped-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let ctx = force_any_val::<ErgoStateContext>();
+        let secret = force_any_val::<DlogProverInput>();
+        let address = NetworkAddress::new(
+            NetworkPrefix::Mainnet,
+            &Address::P2Pk(secret.public_image()),
+        );
+        let cold_wallet = Wallet::from_secrets(vec![secret.into()]);
+        let state = AirGappedState {
+            height: ctx.pre_header.height,
+            wallet_boxes: vec![ErgoBox::new(
+                SUGGESTED_TX_FEE().checked_mul_u32(10000).unwrap(),
+                address.address().script().unwrap(),
+                None,
+                NonMandatoryRegisters::empty(),
+                ctx.pre_header.height - 9,
+                force_any_val::<TxId>(),
+                0,
+            )
+            .unwrap()],
+            signed: Vec::new(),
+        };
+        state.save(&dir).unwrap();
+
+        let run = || {
+            let signer = AirGappedSigner::new(
+                dir.clone(),
+                ColdSigningFormat::Json,
+                address.clone(),
+                AirGappedState::load(&dir).unwrap().unwrap(),
+                ctx.clone(),
+            );
+            let submit_tx = SubmitTxMock::default();
+            let result = perform_bootstrap_chained_transaction(BootstrapInput {
+                oracle_address: address.clone(),
+                config: BootstrapConfig::default(),
+                wallet: &signer,
+                tx_signer: &signer,
+                submit_tx: &submit_tx,
+                tx_fee: SUGGESTED_TX_FEE(),
+                erg_value_per_box: SUGGESTED_TX_FEE(),
+                change_address: address.address(),
+                height: signer.height(),
+            });
+            (result, signer)
+        };
+
+        for step in 1..=BOOTSTRAP_TX_COUNT as usize {
+            let (result, signer) = run();
+            assert!(result.is_err());
+            assert_eq!(signer.awaiting_signature(), Some(step));
+            assert_eq!(signer.state().signed.len(), step - 1);
+            // sign the request as the offline wallet does
+            let request: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(signer.request_path(step)).unwrap())
+                    .unwrap();
+            let reduced_tx = ReducedTransaction::sigma_parse_bytes(
+                &base64::decode(request["reducedTx"].as_str().unwrap()).unwrap(),
+            )
+            .unwrap();
+            let signed_tx = cold_wallet.sign_reduced_transaction(reduced_tx).unwrap();
+            std::fs::write(
+                signer.signed_path(step),
+                serde_json::to_string(&signed_tx).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let (result, signer) = run();
+        let (pool_config, submitted_tx_ids) = result.unwrap();
+        assert_eq!(signer.awaiting_signature(), None);
+        let state = signer.state();
+        assert_eq!(
+            state
+                .signed
+                .iter()
+                .map(|step| step.tx_id.clone())
+                .collect::<Vec<_>>(),
+            submitted_tx_ids
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            state.signed[0].minted_token_id,
+            Some(String::from(
+                pool_config.token_ids.pool_nft_token_id.token_id()
+            ))
+        );
+        assert_eq!(state.signed[7].minted_token_id, None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
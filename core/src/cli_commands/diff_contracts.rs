@@ -0,0 +1,157 @@
+use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
+use ergo_lib::ergotree_ir::chain::address::AddressEncoderError;
+use ergo_lib::ergotree_ir::ergo_tree::ErgoTree;
+use ergo_lib::ergotree_ir::ergo_tree::ErgoTreeError;
+use ergo_lib::ergotree_ir::mir::constant::Constant;
+use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
+use ergo_lib::ergotree_ir::serialization::SigmaParsingError;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DiffContractsError {
+    #[error("contract {0} is neither a P2S address nor ergo tree hex: {1}")]
+    InvalidContract(String, AddressEncoderError),
+    #[error("sigma parsing error: {0}")]
+    SigmaParsing(#[from] SigmaParsingError),
+    #[error("ergo tree error: {0:?}")]
+    ErgoTree(ErgoTreeError),
+}
+
+/// Constant of the same index in both contracts, `None` if the contract has fewer constants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstantDiffRow {
+    pub index: usize,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+impl ConstantDiffRow {
+    pub fn differs(&self) -> bool {
+        self.left != self.right
+    }
+}
+
+pub fn diff_contracts(left: String, right: String) -> Result<(), DiffContractsError> {
+    let left_tree = parse_contract(&left)?;
+    let right_tree = parse_contract(&right)?;
+    let same_template = left_tree
+        .template_bytes()
+        .map_err(DiffContractsError::ErgoTree)?
+        == right_tree
+            .template_bytes()
+            .map_err(DiffContractsError::ErgoTree)?;
+    let rows = diff_constants(&left_tree, &right_tree)?;
+    let width = rows
+        .iter()
+        .filter_map(|row| row.left.as_ref().map(|s| s.len()))
+        .max()
+        .unwrap_or(0)
+        .max("left".len());
+    println!("  {:>3} | {:<width$} | right", "#", "left", width = width);
+    for row in &rows {
+        println!(
+            "{} {:>3} | {:<width$} | {}",
+            if row.differs() { "*" } else { " " },
+            row.index,
+            row.left.as_deref().unwrap_or("-"),
+            row.right.as_deref().unwrap_or("-"),
+            width = width
+        );
+    }
+    let num_differs = rows.iter().filter(|row| row.differs()).count();
+    println!(
+        "{} of {} constants differ, contract templates are {}",
+        num_differs,
+        rows.len(),
+        if same_template {
+            "the same"
+        } else {
+            "different"
+        }
+    );
+    Ok(())
+}
+
+/// Parse a contract given as a P2S address or base16-encoded ergo tree
+fn parse_contract(contract: &str) -> Result<ErgoTree, DiffContractsError> {
+    if let Ok(bytes) = base16::decode(contract) {
+        if let Ok(ergo_tree) = ErgoTree::sigma_parse_bytes(&bytes) {
+            return Ok(ergo_tree);
+        }
+    }
+    let address = AddressEncoder::unchecked_parse_network_address_from_str(contract)
+        .map_err(|e| DiffContractsError::InvalidContract(contract.to_string(), e))?;
+    Ok(address.address().script()?)
+}
+
+pub fn diff_constants(
+    left: &ErgoTree,
+    right: &ErgoTree,
+) -> Result<Vec<ConstantDiffRow>, DiffContractsError> {
+    let left_constants = left.get_constants().map_err(DiffContractsError::ErgoTree)?;
+    let right_constants = right
+        .get_constants()
+        .map_err(DiffContractsError::ErgoTree)?;
+    let len = left_constants.len().max(right_constants.len());
+    Ok((0..len)
+        .map(|index| ConstantDiffRow {
+            index,
+            left: left_constants.get(index).map(format_constant),
+            right: right_constants.get(index).map(format_constant),
+        })
+        .collect())
+}
+
+/// Constant type and value, byte collections (token ids, hashes) in base16
+fn format_constant(constant: &Constant) -> String {
+    let value = match constant.clone().try_extract_into::<Vec<u8>>() {
+        Ok(bytes) => base16::encode_lower(&bytes),
+        Err(_) => format!("{:?}", constant.v),
+    };
+    format!("{:?}: {}", constant.tp, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::contracts::refresh::RefreshContractParameters;
+    use crate::contracts::refresh::RefreshContractParametersInputs;
+    use crate::oracle_types::EpochLength;
+
+    use super::*;
+
+    #[test]
+    fn test_diff_constants() {
+        let parameters = RefreshContractParameters::default();
+        let changed_parameters =
+            RefreshContractParameters::build_with(RefreshContractParametersInputs {
+                ergo_tree_bytes: parameters.ergo_tree_bytes(),
+                pool_nft_index: parameters.pool_nft_index(),
+                oracle_token_id_index: parameters.oracle_token_id_index(),
+                min_data_points_index: parameters.min_data_points_index(),
+                min_data_points: parameters.min_data_points(),
+                buffer_length_index: parameters.buffer_length_index(),
+                buffer_length: parameters.buffer_length(),
+                max_deviation_percent_index: parameters.max_deviation_percent_index(),
+                max_deviation_percent: parameters.max_deviation_percent(),
+                epoch_length_index: parameters.epoch_length_index(),
+                epoch_length: EpochLength(parameters.epoch_length().0 + 1),
+                reward_per_datapoint_index: parameters.reward_per_datapoint_index(),
+                oracle_reward_per_datapoint: parameters.oracle_reward_per_datapoint(),
+            })
+            .unwrap();
+        let left = ErgoTree::sigma_parse_bytes(&parameters.ergo_tree_bytes()).unwrap();
+        let right = ErgoTree::sigma_parse_bytes(&changed_parameters.ergo_tree_bytes()).unwrap();
+        let rows = diff_constants(&left, &right).unwrap();
+        let differing: Vec<usize> = rows
+            .iter()
+            .filter(|row| row.differs())
+            .map(|row| row.index)
+            .collect();
+        assert_eq!(differing, vec![parameters.epoch_length_index()]);
+        assert_eq!(
+            parse_contract(&base16::encode_lower(&parameters.ergo_tree_bytes())).unwrap(),
+            left
+        );
+    }
+}
@@ -0,0 +1,427 @@
+//! Self-diagnosis of an oracle setup: an ordered series of checks from the config files to the
+//! datapoint source, each one reported as passed or failed with a hint on how to fix it. A check
+//! needing something an earlier check failed on (e.g. the node for the wallet) is skipped.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::contracts::known_templates::find_unknown_contracts;
+use crate::datapoint_source::DataPointSource;
+use crate::datapoint_source::RuntimeDataPointSource;
+use crate::datapoint_source::SourceChecks;
+use crate::node_interface::node_api::NodeApi;
+use crate::oracle_config::OracleConfig;
+use crate::pool_config::PoolConfig;
+use crate::scans::NodeScanRegistry;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    /// What was found
+    Pass(String),
+    Fail {
+        error: String,
+        hint: String,
+    },
+    /// Why the check was not run
+    Skipped(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoctorCheck {
+    pub name: &'static str,
+    pub outcome: CheckOutcome,
+}
+
+/// Checks in the order they were run
+#[derive(Debug, Default)]
+pub struct DoctorReport {
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| !matches!(check.outcome, CheckOutcome::Fail { .. }))
+    }
+
+    pub fn outcome(&self, name: &str) -> Option<&CheckOutcome> {
+        self.checks
+            .iter()
+            .find(|check| check.name == name)
+            .map(|check| &check.outcome)
+    }
+
+    fn pass(&mut self, name: &'static str, description: String) {
+        self.push(name, CheckOutcome::Pass(description));
+    }
+
+    fn fail(&mut self, name: &'static str, error: impl fmt::Display, hint: &str) {
+        self.push(
+            name,
+            CheckOutcome::Fail {
+                error: error.to_string(),
+                hint: hint.to_string(),
+            },
+        );
+    }
+
+    fn skip(&mut self, name: &'static str, reason: &str) {
+        self.push(name, CheckOutcome::Skipped(reason.to_string()));
+    }
+
+    fn push(&mut self, name: &'static str, outcome: CheckOutcome) {
+        self.checks.push(DoctorCheck { name, outcome });
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match &check.outcome {
+                CheckOutcome::Pass(description) => {
+                    writeln!(f, "[ok]      {}: {}", check.name, description)?
+                }
+                CheckOutcome::Fail { error, hint } => {
+                    writeln!(f, "[failed]  {}: {}", check.name, error)?;
+                    writeln!(f, "          hint: {}", hint)?;
+                }
+                CheckOutcome::Skipped(reason) => {
+                    writeln!(f, "[skipped] {}: {}", check.name, reason)?
+                }
+            }
+        }
+        if self.passed() {
+            write!(f, "All checks passed")
+        } else {
+            write!(f, "Some checks failed")
+        }
+    }
+}
+
+pub const CHECK_ORACLE_CONFIG: &str = "oracle config";
+pub const CHECK_POOL_CONFIG: &str = "pool config";
+pub const CHECK_NODE_REACHABLE: &str = "node reachable";
+pub const CHECK_NODE_API_KEY: &str = "node API key";
+pub const CHECK_WALLET_UNLOCKED: &str = "wallet unlocked";
+pub const CHECK_SCANS_REGISTERED: &str = "scans registered";
+pub const CHECK_WALLET_SYNCED: &str = "wallet synced";
+pub const CHECK_CONTRACTS: &str = "contracts verified";
+pub const CHECK_DATAPOINT_SOURCE: &str = "datapoint source";
+
+/// Runs all the checks, loading the configs from the given files
+pub fn doctor(
+    oracle_config_path: &Path,
+    pool_config_path: &Path,
+    scans_file_path: &Path,
+) -> DoctorReport {
+    let mut report = DoctorReport::default();
+    let oracle_config = match OracleConfig::load_from_path(oracle_config_path) {
+        Ok(oracle_config) => {
+            report.pass(
+                CHECK_ORACLE_CONFIG,
+                format!("loaded {}", oracle_config_path.display()),
+            );
+            Some(oracle_config)
+        }
+        Err(e) => {
+            report.fail(
+                CHECK_ORACLE_CONFIG,
+                e,
+                "fix the oracle config file, a default one is generated if the file is missing",
+            );
+            None
+        }
+    };
+    let pool_config = match PoolConfig::load_from_path(pool_config_path) {
+        Ok(pool_config) => {
+            report.pass(
+                CHECK_POOL_CONFIG,
+                format!("loaded {}", pool_config_path.display()),
+            );
+            Some(pool_config)
+        }
+        Err(e) => {
+            report.fail(
+                CHECK_POOL_CONFIG,
+                e,
+                "get the pool config from the pool operator or create it with the `bootstrap` command",
+            );
+            None
+        }
+    };
+    match (oracle_config, pool_config) {
+        (Some(oracle_config), Some(pool_config)) => {
+            run_checks(&mut report, &oracle_config, &pool_config, scans_file_path)
+        }
+        _ => {
+            for name in [
+                CHECK_NODE_REACHABLE,
+                CHECK_NODE_API_KEY,
+                CHECK_WALLET_UNLOCKED,
+                CHECK_SCANS_REGISTERED,
+                CHECK_WALLET_SYNCED,
+                CHECK_CONTRACTS,
+                CHECK_DATAPOINT_SOURCE,
+            ] {
+                report.skip(name, "the configs are not loaded");
+            }
+        }
+    }
+    report
+}
+
+fn run_checks(
+    report: &mut DoctorReport,
+    oracle_config: &OracleConfig,
+    pool_config: &PoolConfig,
+    scans_file_path: &Path,
+) {
+    let node_api = NodeApi::new(oracle_config.node_api_key.clone(), &oracle_config.node_url);
+    check_node(report, &node_api, oracle_config, scans_file_path);
+
+    let unknown_contracts = find_unknown_contracts(pool_config);
+    if unknown_contracts.is_empty() {
+        report.pass(
+            CHECK_CONTRACTS,
+            "all the contracts match a known template".to_string(),
+        );
+    } else {
+        let kinds: Vec<String> = unknown_contracts
+            .iter()
+            .map(|unknown| unknown.kind.to_string())
+            .collect();
+        report.fail(
+            CHECK_CONTRACTS,
+            format!("unknown contract templates: {}", kinds.join(", ")),
+            "make sure the pool config comes from the pool operator, compare the contracts with the `diff-contracts` command",
+        );
+    }
+
+    match RuntimeDataPointSource::new(
+        pool_config.data_point_source,
+        oracle_config.data_point_source_custom_script.clone(),
+    ) {
+        Ok(datapoint_source) => match datapoint_source
+            .with_checks(SourceChecks::from_oracle_config(oracle_config))
+            .get_datapoint()
+        {
+            Ok(rate) => report.pass(CHECK_DATAPOINT_SOURCE, format!("fetched rate {}", rate)),
+            Err(e) => report.fail(
+                CHECK_DATAPOINT_SOURCE,
+                e,
+                "check the network access to the datapoint source APIs or the output of the custom script",
+            ),
+        },
+        Err(e) => report.fail(
+            CHECK_DATAPOINT_SOURCE,
+            e,
+            "set data_point_source in the pool config or data_point_source_custom_script in the oracle config",
+        ),
+    }
+}
+
+/// Node, wallet and scan checks, skipped from the first one the node fails
+fn check_node(
+    report: &mut DoctorReport,
+    node_api: &NodeApi,
+    oracle_config: &OracleConfig,
+    scans_file_path: &Path,
+) {
+    let node_checks = [
+        CHECK_NODE_API_KEY,
+        CHECK_WALLET_UNLOCKED,
+        CHECK_SCANS_REGISTERED,
+        CHECK_WALLET_SYNCED,
+    ];
+    let skip_from = |report: &mut DoctorReport, index: usize, reason: &str| {
+        for name in &node_checks[index..] {
+            report.skip(name, reason);
+        }
+    };
+
+    match node_api.get_heights() {
+        Ok(heights) => report.pass(
+            CHECK_NODE_REACHABLE,
+            format!(
+                "{} at height {}, {} blocks behind",
+                oracle_config.node_url, heights.full_height.0, heights.height_lag
+            ),
+        ),
+        Err(e) => {
+            report.fail(
+                CHECK_NODE_REACHABLE,
+                e,
+                "check node_url in the oracle config and that the node is running and synced",
+            );
+            return skip_from(report, 0, "the node is not reachable");
+        }
+    }
+
+    let wallet_status = match node_api.node.wallet_status() {
+        Ok(wallet_status) => {
+            report.pass(CHECK_NODE_API_KEY, "the node accepted the key".to_string());
+            wallet_status
+        }
+        Err(e) => {
+            report.fail(
+                CHECK_NODE_API_KEY,
+                e,
+                "set node_api_key in the oracle config to the key the node's apiKeyHash is made from",
+            );
+            return skip_from(report, 1, "the node API key is not accepted");
+        }
+    };
+
+    if wallet_status.unlocked {
+        report.pass(CHECK_WALLET_UNLOCKED, "the wallet is unlocked".to_string());
+    } else {
+        report.fail(
+            CHECK_WALLET_UNLOCKED,
+            "the wallet is locked",
+            "unlock the node wallet with `/wallet/unlock` or the node panel",
+        );
+    }
+
+    match NodeScanRegistry::load_at(scans_file_path) {
+        Ok(registry) => {
+            match node_api.list_scan_ids() {
+                Ok(node_scan_ids) => {
+                    let missing: Vec<String> = registry
+                        .scan_ids()
+                        .iter()
+                        .map(|scan_id| scan_id.to_string())
+                        .filter(|scan_id| {
+                            !node_scan_ids
+                                .iter()
+                                .any(|node_scan_id| node_scan_id.to_string() == *scan_id)
+                        })
+                        .collect();
+                    if missing.is_empty() {
+                        report.pass(
+                            CHECK_SCANS_REGISTERED,
+                            format!("{} scans registered", registry.scan_ids().len()),
+                        );
+                    } else {
+                        report.fail(
+                        CHECK_SCANS_REGISTERED,
+                        format!("scans {} are not registered in the node", missing.join(", ")),
+                        "remove the scans file, the scans are registered again on the next `run`",
+                    );
+                    }
+                }
+                Err(e) => report.fail(
+                    CHECK_SCANS_REGISTERED,
+                    e,
+                    "check that the node allows listing the scans",
+                ),
+            }
+        }
+        Err(e) => report.fail(
+            CHECK_SCANS_REGISTERED,
+            e,
+            "the scans are registered on the first `run`",
+        ),
+    }
+
+    match node_api.node.current_block_height() {
+        Ok(block_height) if wallet_status.height == block_height => report.pass(
+            CHECK_WALLET_SYNCED,
+            format!("the wallet is scanned to height {}", block_height),
+        ),
+        Ok(block_height) => report.fail(
+            CHECK_WALLET_SYNCED,
+            format!(
+                "the wallet is scanned to height {} of {}",
+                wallet_status.height, block_height
+            ),
+            "wait for the wallet rescan to finish",
+        ),
+        Err(e) => report.fail(CHECK_WALLET_SYNCED, e, "check that the node is synced"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli_commands::bootstrap::BootstrapConfig;
+    use crate::node_interface::mock_node::MockNode;
+    use crate::node_interface::mock_node::MOCK_NODE_API_KEY;
+    use crate::pool_commands::test_utils::generate_token_ids;
+
+    fn oracle_config(mock_node: &MockNode) -> OracleConfig {
+        OracleConfig {
+            node_url: mock_node.url(),
+            node_api_key: MOCK_NODE_API_KEY.to_string(),
+            // fails right away without fetching from the network
+            data_point_source_custom_script: Some("oracle-core-doctor-missing-script".to_string()),
+            ..OracleConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_doctor_checks() {
+        let scans_file_path = std::env::temp_dir().join(format!(
+            "oracle-core-doctor-scans-{}.sqlite",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&scans_file_path);
+        let pool_config =
+            PoolConfig::create(BootstrapConfig::default(), generate_token_ids()).unwrap();
+        let mock_node = MockNode::start(1000);
+        mock_node.state().wallet_height = 990;
+
+        let mut report = DoctorReport::default();
+        run_checks(
+            &mut report,
+            &oracle_config(&mock_node),
+            &pool_config,
+            &scans_file_path,
+        );
+        let is_pass = |name: &str| matches!(report.outcome(name), Some(CheckOutcome::Pass(_)));
+        let is_fail = |name: &str| matches!(report.outcome(name), Some(CheckOutcome::Fail { .. }));
+        assert!(is_pass(CHECK_NODE_REACHABLE));
+        assert!(is_pass(CHECK_NODE_API_KEY));
+        assert!(is_pass(CHECK_WALLET_UNLOCKED));
+        assert!(is_fail(CHECK_SCANS_REGISTERED));
+        assert!(is_fail(CHECK_WALLET_SYNCED));
+        assert!(is_pass(CHECK_CONTRACTS));
+        assert!(is_fail(CHECK_DATAPOINT_SOURCE));
+        assert!(!report.passed());
+        assert!(report
+            .to_string()
+            .contains("hint: wait for the wallet rescan"));
+    }
+
+    #[test]
+    fn test_doctor_skips_checks_after_unreachable_node() {
+        let oracle_config = OracleConfig {
+            node_url: "http://127.0.0.1:1".parse().unwrap(),
+            data_point_source_custom_script: Some("oracle-core-doctor-missing-script".to_string()),
+            ..OracleConfig::default()
+        };
+        let pool_config =
+            PoolConfig::create(BootstrapConfig::default(), generate_token_ids()).unwrap();
+
+        let mut report = DoctorReport::default();
+        run_checks(
+            &mut report,
+            &oracle_config,
+            &pool_config,
+            Path::new("missing-scans.sqlite"),
+        );
+        assert!(matches!(
+            report.outcome(CHECK_NODE_REACHABLE),
+            Some(CheckOutcome::Fail { .. })
+        ));
+        assert!(matches!(
+            report.outcome(CHECK_WALLET_SYNCED),
+            Some(CheckOutcome::Skipped(_))
+        ));
+        // the local checks are run anyway
+        assert!(matches!(
+            report.outcome(CHECK_CONTRACTS),
+            Some(CheckOutcome::Pass(_))
+        ));
+    }
+}
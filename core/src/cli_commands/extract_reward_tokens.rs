@@ -9,6 +9,10 @@ use ergo_lib::{
     ergotree_ir::{
         chain::{
             address::{Address, AddressEncoder, AddressEncoderError},
+            ergo_box::{
+                box_value::{BoxValue, BoxValueError},
+                ErgoBoxCandidate,
+            },
             token::Token,
         },
         serialization::SigmaParsingError,
@@ -25,19 +29,28 @@ use crate::{
     box_kind::{
         make_collected_oracle_box_candidate, make_oracle_box_candidate, OracleBox, OracleBoxWrapper,
     },
+    cold_signing::ColdSigner,
+    contracts::oracle::OracleContractError,
     explorer_api::ergo_explorer_transaction_link,
     node_interface::{SignTransaction, SubmitTransaction},
-    oracle_config::BASE_FEE,
+    oracle_config::{OracleConfig, TxParams},
     oracle_state::{DataSourceError, LocalDatapointBoxSource},
     oracle_types::BlockHeight,
-    spec_token::SpecToken,
+    spec_token::{RewardTokenId, SpecToken},
     wallet::{WalletDataError, WalletDataSource},
 };
 
+/// Reward tokens left in the oracle box by default, the oracle contract expects a reward token
+pub const DEFAULT_KEPT_REWARD_TOKENS: u64 = 1;
+
 #[derive(Debug, Error)]
 pub enum ExtractRewardTokensActionError {
-    #[error("Oracle box must contain at least 2 reward tokens. It contains {0} tokens")]
-    InsufficientRewardTokensInOracleBox(usize),
+    #[error("Oracle box must contain more than {keep} reward tokens to keep {keep}. It contains {found} tokens")]
+    InsufficientRewardTokensInOracleBox { found: u64, keep: u64 },
+    #[error("The oracle box must keep at least 1 reward token")]
+    NoRewardTokenKept,
+    #[error("Oracle box must contain at least {min} nanoERG of ERG rewards. It contains {found} nanoERG")]
+    InsufficientErgRewardsInOracleBox { found: u64, min: u64 },
     #[error("Destination address not P2PK")]
     IncorrectDestinationAddress,
     #[error("box builder error: {0}")]
@@ -62,15 +75,24 @@ pub enum ExtractRewardTokensActionError {
     Io(#[from] std::io::Error),
     #[error("WalletData error: {0}")]
     WalletData(#[from] WalletDataError),
+    #[error("box value error: {0}")]
+    BoxValue(#[from] BoxValueError),
+    #[error("oracle contract error: {0}")]
+    OracleContract(#[from] OracleContractError),
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn extract_reward_tokens(
     wallet: &dyn WalletDataSource,
     tx_signer: &dyn SignTransaction,
     tx_submit: &dyn SubmitTransaction,
     local_datapoint_box_source: &dyn LocalDatapointBoxSource,
     rewards_destination_str: String,
+    keep: u64,
     height: BlockHeight,
+    erg_rewards: bool,
+    oracle_config: &OracleConfig,
+    cold_signer: Option<&ColdSigner>,
 ) -> Result<(), anyhow::Error> {
     let rewards_destination =
         AddressEncoder::unchecked_parse_network_address_from_str(&rewards_destination_str)?;
@@ -78,27 +100,52 @@ pub fn extract_reward_tokens(
     let change_address = wallet
         .get_change_address()
         .map_err(ExtractRewardTokensActionError::WalletData)?;
-    let (unsigned_tx, num_reward_tokens) = build_extract_reward_tokens_tx(
-        local_datapoint_box_source,
-        wallet,
-        rewards_destination.address(),
-        height,
-        change_address.address(),
-    )?;
+    let (unsigned_tx, amount_desc) = if erg_rewards {
+        let (tx, nano_ergs) = build_extract_erg_rewards_tx(
+            local_datapoint_box_source,
+            wallet,
+            rewards_destination.address(),
+            height,
+            change_address.address(),
+            &oracle_config.tx_params(oracle_config.extract_fee()),
+        )?;
+        (tx, format!("{} NANOERG OF ERG REWARDS", nano_ergs))
+    } else {
+        let (tx, num_reward_tokens) = build_extract_reward_tokens_tx(
+            local_datapoint_box_source,
+            wallet,
+            rewards_destination.address(),
+            keep,
+            height,
+            change_address.address(),
+            &oracle_config.tx_params(oracle_config.extract_fee()),
+        )?;
+        (
+            tx,
+            format!(
+                "{} REWARD TOKENS (KEEPING {} IN THE ORACLE BOX)",
+                num_reward_tokens, keep
+            ),
+        )
+    };
 
     println!(
-        "YOU WILL BE TRANSFERRING {} REWARD TOKENS TO {}. TYPE 'YES' TO INITIATE THE TRANSACTION.",
-        num_reward_tokens, rewards_destination_str
+        "YOU WILL BE TRANSFERRING {} TO {}. TYPE 'YES' TO INITIATE THE TRANSACTION.",
+        amount_desc, rewards_destination_str
     );
     let mut input = String::new();
     std::io::stdin().read_line(&mut input)?;
     if input.trim() == "YES" {
+        if let Some(cold_signer) = cold_signer {
+            cold_signer.export(&unsigned_tx)?;
+            return Ok(());
+        }
         let signed_tx = tx_signer.sign_transaction(&unsigned_tx)?;
         let tx_id = tx_submit.submit_transaction(&signed_tx)?;
-        crate::explorer_api::wait_for_tx_confirmation(signed_tx.id());
+        crate::explorer_api::wait_for_tx_confirmation(signed_tx.id(), oracle_config);
         println!(
             "Transaction made. Check status here: {}",
-            ergo_explorer_transaction_link(tx_id, network_prefix)
+            ergo_explorer_transaction_link(tx_id, network_prefix, oracle_config)
         );
     } else {
         println!("Aborting the transaction.")
@@ -106,59 +153,54 @@ pub fn extract_reward_tokens(
     Ok(())
 }
 
-fn build_extract_reward_tokens_tx(
+/// Move all reward tokens of the oracle box but `keep` to `rewards_destination`
+#[allow(clippy::too_many_arguments)]
+pub fn build_extract_reward_tokens_tx(
     local_datapoint_box_source: &dyn LocalDatapointBoxSource,
     wallet: &dyn WalletDataSource,
     rewards_destination: Address,
+    keep: u64,
     height: BlockHeight,
     change_address: Address,
+    tx_params: &TxParams,
 ) -> Result<(UnsignedTransaction, u64), ExtractRewardTokensActionError> {
     let in_oracle_box = local_datapoint_box_source
         .get_local_oracle_datapoint_box()?
         .ok_or(ExtractRewardTokensActionError::NoLocalDatapointBox)?;
+    if keep == 0 {
+        return Err(ExtractRewardTokensActionError::NoRewardTokenKept);
+    }
     let num_reward_tokens = *in_oracle_box.reward_token().amount.as_u64();
-    if num_reward_tokens <= 1 {
+    if num_reward_tokens <= keep {
         return Err(
-            ExtractRewardTokensActionError::InsufficientRewardTokensInOracleBox(
-                num_reward_tokens as usize,
-            ),
+            ExtractRewardTokensActionError::InsufficientRewardTokensInOracleBox {
+                found: num_reward_tokens,
+                keep,
+            },
         );
     }
     if let Address::P2Pk(_) = &rewards_destination {
-        let single_reward_token = SpecToken {
+        let kept_reward_tokens = SpecToken {
             token_id: in_oracle_box.reward_token().token_id,
-            amount: 1.try_into().unwrap(),
+            amount: keep.try_into().unwrap(),
         };
-        let oracle_box_candidate =
-            if let OracleBoxWrapper::Posted(ref posted_oracle_box) = in_oracle_box {
-                make_oracle_box_candidate(
-                    posted_oracle_box.contract(),
-                    posted_oracle_box.public_key(),
-                    posted_oracle_box.rate(),
-                    posted_oracle_box.epoch_counter(),
-                    posted_oracle_box.oracle_token(),
-                    single_reward_token,
-                    posted_oracle_box.get_box().value,
-                    height,
-                )?
-            } else {
-                make_collected_oracle_box_candidate(
-                    in_oracle_box.contract(),
-                    in_oracle_box.public_key(),
-                    in_oracle_box.oracle_token(),
-                    single_reward_token,
-                    in_oracle_box.get_box().value,
-                    height,
-                )?
-            };
+        let oracle_box_candidate = make_out_oracle_box_candidate(
+            &in_oracle_box,
+            kept_reward_tokens,
+            in_oracle_box.get_box().value,
+            height,
+        )?;
 
         // Build box to hold extracted tokens
-        let mut builder =
-            ErgoBoxCandidateBuilder::new(*BASE_FEE, rewards_destination.script()?, height.0);
+        let mut builder = ErgoBoxCandidateBuilder::new(
+            tx_params.min_box_value,
+            rewards_destination.script()?,
+            height.0,
+        );
 
         let extracted_reward_tokens = Token {
             token_id: in_oracle_box.reward_token().token_id(),
-            amount: (num_reward_tokens - 1).try_into().unwrap(),
+            amount: (num_reward_tokens - keep).try_into().unwrap(),
         };
 
         builder.add_token(extracted_reward_tokens);
@@ -166,8 +208,8 @@ fn build_extract_reward_tokens_tx(
 
         let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
 
-        // `BASE_FEE` each for the fee and the box holding the extracted reward tokens.
-        let target_balance = BASE_FEE.checked_mul_u32(2).unwrap();
+        // The fee and the min box value for the box holding the extracted reward tokens.
+        let target_balance = tx_params.fee.checked_add(&tx_params.min_box_value)?;
 
         let box_selector = SimpleBoxSelector::new();
         let selection = box_selector.select(unspent_boxes, target_balance, &[])?;
@@ -181,7 +223,78 @@ fn build_extract_reward_tokens_tx(
             box_selection,
             vec![oracle_box_candidate, reward_box_candidate],
             height.0,
-            *BASE_FEE,
+            tx_params.fee,
+            change_address,
+        );
+        // The following context value ensures that `outIndex` in the oracle contract is properly set.
+        let ctx_ext = ContextExtension {
+            values: vec![(0, 0i32.into())].into_iter().collect(),
+        };
+        tx_builder.set_context_extension(in_oracle_box.get_box().box_id(), ctx_ext);
+        let tx = tx_builder.build()?;
+        Ok((tx, num_reward_tokens - keep))
+    } else {
+        Err(ExtractRewardTokensActionError::IncorrectDestinationAddress)
+    }
+}
+
+/// Extract the ERG rewards of the oracle box, everything above the `min_storage_rent` of the oracle
+/// contract, keeping its tokens
+pub fn build_extract_erg_rewards_tx(
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    wallet: &dyn WalletDataSource,
+    rewards_destination: Address,
+    height: BlockHeight,
+    change_address: Address,
+    tx_params: &TxParams,
+) -> Result<(UnsignedTransaction, u64), ExtractRewardTokensActionError> {
+    let in_oracle_box = local_datapoint_box_source
+        .get_local_oracle_datapoint_box()?
+        .ok_or(ExtractRewardTokensActionError::NoLocalDatapointBox)?;
+    // the oracle contract requires the box to keep its min storage rent
+    let min_storage_rent = in_oracle_box.contract().min_storage_rent()?;
+    let nano_ergs = in_oracle_box
+        .get_box()
+        .value
+        .as_u64()
+        .saturating_sub(*min_storage_rent.as_u64());
+    // the box holding the extracted ERG must be at least the min box value as well
+    if nano_ergs < *tx_params.min_box_value.as_u64() {
+        return Err(
+            ExtractRewardTokensActionError::InsufficientErgRewardsInOracleBox {
+                found: nano_ergs,
+                min: *tx_params.min_box_value.as_u64(),
+            },
+        );
+    }
+    if let Address::P2Pk(_) = &rewards_destination {
+        let oracle_box_candidate = make_out_oracle_box_candidate(
+            &in_oracle_box,
+            in_oracle_box.reward_token(),
+            min_storage_rent,
+            height,
+        )?;
+        let reward_box_candidate = ErgoBoxCandidateBuilder::new(
+            BoxValue::try_from(nano_ergs)?,
+            rewards_destination.script()?,
+            height.0,
+        )
+        .build()?;
+
+        let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
+        let box_selector = SimpleBoxSelector::new();
+        let selection = box_selector.select(unspent_boxes, tx_params.fee, &[])?;
+        let mut input_boxes = vec![in_oracle_box.get_box().clone()];
+        input_boxes.append(selection.boxes.as_vec().clone().as_mut());
+        let box_selection = BoxSelection {
+            boxes: input_boxes.try_into().unwrap(),
+            change_boxes: selection.change_boxes,
+        };
+        let mut tx_builder = TxBuilder::new(
+            box_selection,
+            vec![oracle_box_candidate, reward_box_candidate],
+            height.0,
+            tx_params.fee,
             change_address,
         );
         // The following context value ensures that `outIndex` in the oracle contract is properly set.
@@ -190,12 +303,42 @@ fn build_extract_reward_tokens_tx(
         };
         tx_builder.set_context_extension(in_oracle_box.get_box().box_id(), ctx_ext);
         let tx = tx_builder.build()?;
-        Ok((tx, num_reward_tokens - 1))
+        Ok((tx, nano_ergs))
     } else {
         Err(ExtractRewardTokensActionError::IncorrectDestinationAddress)
     }
 }
 
+/// Oracle box spent by the extraction with the given reward token and value
+fn make_out_oracle_box_candidate(
+    in_oracle_box: &OracleBoxWrapper,
+    reward_token: SpecToken<RewardTokenId>,
+    value: BoxValue,
+    height: BlockHeight,
+) -> Result<ErgoBoxCandidate, ErgoBoxCandidateBuilderError> {
+    if let OracleBoxWrapper::Posted(ref posted_oracle_box) = in_oracle_box {
+        make_oracle_box_candidate(
+            posted_oracle_box.contract(),
+            posted_oracle_box.public_key(),
+            posted_oracle_box.rate(),
+            posted_oracle_box.epoch_counter(),
+            posted_oracle_box.oracle_token(),
+            reward_token,
+            value,
+            height,
+        )
+    } else {
+        make_collected_oracle_box_candidate(
+            in_oracle_box.contract(),
+            in_oracle_box.public_key(),
+            in_oracle_box.oracle_token(),
+            reward_token,
+            value,
+            height,
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -213,6 +356,7 @@ mod tests {
     use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
     use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
     use ergo_lib::wallet::signing::TransactionContext;
+    use ergo_lib::wallet::tx_builder::SUGGESTED_TX_FEE;
     use ergo_lib::wallet::Wallet;
     use sigma_test_util::force_any_val;
 
@@ -236,7 +380,7 @@ mod tests {
                 200,
                 EpochCounter(1),
                 &token_ids,
-                BASE_FEE.checked_mul_u32(100).unwrap(),
+                SUGGESTED_TX_FEE().checked_mul_u32(100).unwrap(),
                 BlockHeight(height.0),
                 num_reward_tokens_in_box,
             ),
@@ -252,7 +396,7 @@ mod tests {
 
         let wallet_unspent_box = make_wallet_unspent_box(
             secret.public_image(),
-            BASE_FEE.checked_mul_u32(10000).unwrap(),
+            SUGGESTED_TX_FEE().checked_mul_u32(10000).unwrap(),
             None,
         );
         let wallet_mock = WalletDataMock {
@@ -263,12 +407,44 @@ mod tests {
             &local_datapoint_box_source,
             &wallet_mock,
             change_address.address(),
+            DEFAULT_KEPT_REWARD_TOKENS,
             height,
             change_address.address(),
+            &TxParams::default(),
         )
         .unwrap();
 
         assert_eq!(num_reward_tokens, num_reward_tokens_in_box - 1);
+        // nothing left to extract when keeping all of them
+        assert!(matches!(
+            build_extract_reward_tokens_tx(
+                &local_datapoint_box_source,
+                &wallet_mock,
+                change_address.address(),
+                num_reward_tokens_in_box,
+                height,
+                change_address.address(),
+                &TxParams::default(),
+            ),
+            Err(
+                ExtractRewardTokensActionError::InsufficientRewardTokensInOracleBox {
+                    found: 2,
+                    keep: 2
+                }
+            )
+        ));
+        assert!(matches!(
+            build_extract_reward_tokens_tx(
+                &local_datapoint_box_source,
+                &wallet_mock,
+                change_address.address(),
+                0,
+                height,
+                change_address.address(),
+                &TxParams::default(),
+            ),
+            Err(ExtractRewardTokensActionError::NoRewardTokenKept)
+        ));
         let mut possible_input_boxes = vec![local_datapoint_box_source
             .get_local_oracle_datapoint_box()
             .unwrap()
@@ -279,11 +455,50 @@ mod tests {
 
         let tx_context = TransactionContext::new(
             tx.clone(),
-            find_input_boxes(tx, possible_input_boxes),
+            find_input_boxes(tx, possible_input_boxes.clone()),
             Vec::new(),
         )
         .unwrap();
 
         let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
+
+        let (erg_tx, nano_ergs) = build_extract_erg_rewards_tx(
+            &local_datapoint_box_source,
+            &wallet_mock,
+            change_address.address(),
+            height,
+            change_address.address(),
+            &TxParams::default(),
+        )
+        .unwrap();
+        // the oracle box keeps the min storage rent of the contract, above the configured min
+        // box value
+        let min_storage_rent = OracleContractParameters::default().min_storage_rent;
+        assert!(min_storage_rent.as_u64() > TxParams::default().min_box_value.as_u64());
+        assert_eq!(
+            nano_ergs,
+            SUGGESTED_TX_FEE().checked_mul_u32(100).unwrap().as_u64() - min_storage_rent.as_u64()
+        );
+        let out_oracle_box = erg_tx.output_candidates.get(0).unwrap();
+        assert_eq!(out_oracle_box.value, min_storage_rent);
+        assert_eq!(
+            *out_oracle_box
+                .tokens
+                .as_ref()
+                .unwrap()
+                .get(1)
+                .unwrap()
+                .amount
+                .as_u64(),
+            num_reward_tokens_in_box,
+            "reward tokens stay in the oracle box"
+        );
+        let erg_tx_context = TransactionContext::new(
+            erg_tx.clone(),
+            find_input_boxes(erg_tx, possible_input_boxes),
+            Vec::new(),
+        )
+        .unwrap();
+        let _signed_erg_tx = wallet.sign_transaction(erg_tx_context, &ctx, None).unwrap();
     }
 }
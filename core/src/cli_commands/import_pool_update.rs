@@ -6,16 +6,12 @@ use crate::box_kind::OracleBox;
 use crate::node_interface::node_api::NodeApi;
 use crate::oracle_state::LocalDatapointBoxSource;
 use crate::pool_config::PoolConfig;
-use crate::pool_config::POOL_CONFIG;
 use crate::scans::NodeScanRegistry;
-use crate::spec_token::OracleTokenId;
-use crate::spec_token::RewardTokenId;
 
 #[allow(clippy::too_many_arguments)]
 pub fn import_pool_update(
     new_pool_config_file: String,
-    oracle_token_id: &OracleTokenId,
-    reward_token_id: &RewardTokenId,
+    current_pool_config: &PoolConfig,
     current_pool_config_path: &Path,
     local_datapoint_box_source: &dyn LocalDatapointBoxSource,
     scan_ids_path: &Path,
@@ -37,7 +33,8 @@ pub fn import_pool_update(
             e
         )
     })?;
-    if &new_pool_config.token_ids.oracle_token_id != oracle_token_id {
+    let old_token_ids = &current_pool_config.token_ids;
+    if new_pool_config.token_ids.oracle_token_id != old_token_ids.oracle_token_id {
         let in_oracle_box = local_datapoint_box_source
             .get_local_oracle_datapoint_box()
             .map_err(|e| anyhow!("Failed to get local oracle datapoint box: {}", e))?
@@ -49,23 +46,28 @@ pub fn import_pool_update(
             );
         }
     }
-    if &new_pool_config.token_ids.reward_token_id != reward_token_id {
+    if new_pool_config.token_ids.reward_token_id != old_token_ids.reward_token_id {
         return Err(
                 anyhow!("Since new reward token is minted reward tokens from the current oracle box will be lost. Please transfer them to a different address with extract-reward-tokens command before importing new pool config.")
             );
     }
 
     let new_token_ids = &new_pool_config.token_ids;
-    let old_token_ids = &POOL_CONFIG.token_ids;
     if new_token_ids.pool_nft_token_id != old_token_ids.pool_nft_token_id
         || new_token_ids.refresh_nft_token_id != old_token_ids.refresh_nft_token_id
         || new_token_ids.oracle_token_id != old_token_ids.oracle_token_id
         || new_token_ids.update_nft_token_id != old_token_ids.update_nft_token_id
         || new_token_ids.ballot_token_id != old_token_ids.ballot_token_id
     {
-        node_scan_registry.deregister_all_scans(node_api).unwrap();
-        std::fs::remove_file(scan_ids_path)
-            .map_err(|e| anyhow!("Failed to remove scan ids file {:?}: {}", scan_ids_path, e))?;
+        node_scan_registry
+            .deregister_all_scans_at(node_api, scan_ids_path)
+            .map_err(|e| {
+                anyhow!(
+                    "Failed to deregister the scans in {:?}: {}",
+                    scan_ids_path,
+                    e
+                )
+            })?;
     }
     new_pool_config.save(current_pool_config_path)?;
     Ok(())
@@ -37,18 +37,20 @@ use thiserror::Error;
 
 use crate::{
     box_kind::{
-        make_refresh_box_candidate, BallotBoxWrapperInputs, PoolBox, PoolBoxWrapperInputs,
-        RefreshBoxWrapperInputs, UpdateBoxWrapperInputs,
+        check_min_box_value, make_pool_box_candidate_unchecked, make_refresh_box_candidate,
+        BallotBoxWrapperInputs, PoolBox, PoolBoxWrapper, PoolBoxWrapperInputs,
+        RefreshBoxWrapperInputs, UpdateBoxWrapper, UpdateBoxWrapperInputs,
     },
+    context::OracleContext,
     contracts::{
         ballot::BallotContractError,
-        pool::{PoolContractError, PoolContractParameters},
+        pool::{PoolContract, PoolContractError, PoolContractParameters},
         refresh::{
             RefreshContract, RefreshContractError, RefreshContractInputs, RefreshContractParameters,
         },
         update::{
             self, UpdateContract, UpdateContractError, UpdateContractInputs,
-            UpdateContractParameters,
+            UpdateContractParameters, UpdateContractParametersError,
         },
     },
     explorer_api::wait_for_txs_confirmation,
@@ -56,13 +58,14 @@ use crate::{
         node_api::{NodeApi, NodeApiError},
         SignTransactionWithInputs, SubmitTransaction,
     },
-    oracle_config::{OracleConfig, BASE_FEE, ORACLE_CONFIG},
-    oracle_state::{DataSourceError, OraclePool},
+    oracle_config::OracleConfig,
+    oracle_state::{DataSourceError, OraclePool, PoolBoxSource, UpdateBoxSource},
     oracle_types::BlockHeight,
-    pool_config::{PoolConfig, POOL_CONFIG},
+    pool_config::PoolConfig,
     serde::{PoolConfigSerde, SerdeConversionError, UpdateBootstrapConfigSerde},
     spec_token::{
-        BallotTokenId, OracleTokenId, RefreshTokenId, RewardTokenId, TokenIdKind, UpdateTokenId,
+        BallotTokenId, OracleTokenId, RefreshTokenId, RewardTokenId, SpecToken, TokenIdKind,
+        UpdateTokenId,
     },
     wallet::{WalletDataError, WalletDataSource},
 };
@@ -83,6 +86,9 @@ pub struct UpdateBootstrapConfig {
     pub pool_contract_parameters: Option<PoolContractParameters>, // New pool script, etc. Note that we don't actually mint any new pool NFT in the update step, instead this is simply passed to the new oracle config for convenience
     pub refresh_contract_parameters: Option<RefreshContractParameters>,
     pub update_contract_parameters: Option<UpdateContractParameters>,
+    /// New min votes of the update contract, applied on top of `update_contract_parameters` (or
+    /// the current update contract if not set)
+    pub min_votes: Option<u64>,
     pub tokens_to_mint: UpdateTokensToMint,
 }
 
@@ -90,23 +96,29 @@ pub fn prepare_update(
     config_file_name: String,
     node_api: &NodeApi,
     height: BlockHeight,
+    ctx: &OracleContext,
 ) -> Result<(), anyhow::Error> {
     let s = std::fs::read_to_string(config_file_name)?;
     let config_serde: UpdateBootstrapConfigSerde = serde_yaml::from_str(&s)?;
 
     let change_address = node_api.get_change_address()?.address();
     let config = UpdateBootstrapConfig::try_from(config_serde)?;
+    let op = OraclePool::load(ctx)?;
     let update_bootstrap_input = PrepareUpdateInput {
         wallet: node_api,
         tx_signer: &node_api.node,
         submit_tx: &node_api.node,
-        tx_fee: *BASE_FEE,
-        erg_value_per_box: *BASE_FEE,
+        tx_fee: ctx.oracle_config.update_fee(),
+        erg_value_per_box: ctx.oracle_config.min_box_value(),
         change_address,
         height,
+        live_pool: Some(LivePool {
+            pool_box_source: op.get_pool_box_source(),
+            update_box_source: op.get_update_box_source(),
+        }),
     };
 
-    let prepare = PrepareUpdate::new(update_bootstrap_input, &POOL_CONFIG, &ORACLE_CONFIG)?;
+    let prepare = PrepareUpdate::new(update_bootstrap_input, &ctx.pool_config, &ctx.oracle_config)?;
     let (new_config, submitted_tx_ids) = prepare.execute(config)?;
     let blake2b_pool_ergo_tree: String = blake2b256_hash(
         new_config
@@ -129,19 +141,23 @@ pub fn prepare_update(
         "Base16-encoded blake2b hash of the serialized new pool box contract(ErgoTree): {}",
         blake2b_pool_ergo_tree
     );
-    print_hints_for_voting(height)?;
-    wait_for_txs_confirmation(submitted_tx_ids);
+    print_hints_for_voting(height, ctx)?;
+    wait_for_txs_confirmation(submitted_tx_ids, &ctx.oracle_config);
     Ok(())
 }
 
-fn print_hints_for_voting(height: BlockHeight) -> Result<(), PrepareUpdateError> {
-    let epoch_length = POOL_CONFIG
+fn print_hints_for_voting(
+    height: BlockHeight,
+    ctx: &OracleContext,
+) -> Result<(), PrepareUpdateError> {
+    let epoch_length = ctx
+        .pool_config
         .refresh_box_wrapper_inputs
         .contract_inputs
         .contract_parameters()
         .epoch_length()
         .0 as u32;
-    let op = OraclePool::load().unwrap();
+    let op = OraclePool::load(ctx).unwrap();
     let oracle_boxes = op
         .get_posted_datapoint_boxes_source()
         .get_posted_datapoint_boxes()?;
@@ -180,6 +196,12 @@ fn print_hints_for_voting(height: BlockHeight) -> Result<(), PrepareUpdateError>
     Ok(())
 }
 
+/// Boxes of the running pool the new config is checked against before submitting anything
+struct LivePool<'a> {
+    pub pool_box_source: &'a dyn PoolBoxSource,
+    pub update_box_source: &'a dyn UpdateBoxSource,
+}
+
 struct PrepareUpdateInput<'a> {
     pub wallet: &'a dyn WalletDataSource,
     pub tx_signer: &'a dyn SignTransactionWithInputs,
@@ -188,6 +210,81 @@ struct PrepareUpdateInput<'a> {
     pub erg_value_per_box: BoxValue,
     pub change_address: Address,
     pub height: BlockHeight,
+    pub live_pool: Option<LivePool<'a>>,
+}
+
+/// Check that the update tx moving the live pool box to the contracts of `new_pool_config` can
+/// validate: the pool NFT and the reward tokens are carried over, the votes are counted by the
+/// live update box and the live datapoint and epoch fit in a pool box of the new contract
+pub fn check_update_against_live_pool(
+    old_pool_config: &PoolConfig,
+    new_pool_config: &PoolConfig,
+    pool_box: &PoolBoxWrapper,
+    update_box: &UpdateBoxWrapper,
+    reward_tokens_minted: bool,
+    height: BlockHeight,
+) -> Result<(), PrepareUpdateError> {
+    let mismatch = |reason: String| Err(PrepareUpdateError::LivePoolMismatch(reason));
+    let pool_nft_token = pool_box.pool_nft_token();
+    if pool_nft_token.token_id != new_pool_config.token_ids.pool_nft_token_id {
+        return mismatch(format!(
+            "the live pool box holds the pool NFT {}, the new config expects {}",
+            String::from(pool_nft_token.token_id.token_id()),
+            String::from(new_pool_config.token_ids.pool_nft_token_id.token_id())
+        ));
+    }
+    let live_reward_token = pool_box.reward_token();
+    if !reward_tokens_minted
+        && live_reward_token.token_id != new_pool_config.token_ids.reward_token_id
+    {
+        return mismatch(format!(
+            "the live pool box holds the reward token {}, the new config expects {} and no reward \
+             tokens are minted",
+            String::from(live_reward_token.token_id.token_id()),
+            String::from(new_pool_config.token_ids.reward_token_id.token_id())
+        ));
+    }
+    if update_box.ballot_token_id() != old_pool_config.token_ids.ballot_token_id.token_id() {
+        return mismatch(format!(
+            "the live update box counts the votes of the ballot token {}, the pool config has {}",
+            String::from(update_box.ballot_token_id()),
+            String::from(old_pool_config.token_ids.ballot_token_id.token_id())
+        ));
+    }
+    let new_pool_contract =
+        PoolContract::checked_load(&new_pool_config.pool_box_wrapper_inputs.contract_inputs)?;
+    let reward_token = if reward_tokens_minted {
+        SpecToken {
+            token_id: new_pool_config.token_ids.reward_token_id.clone(),
+            amount: live_reward_token.amount,
+        }
+    } else {
+        live_reward_token
+    };
+    let new_pool_box_candidate = make_pool_box_candidate_unchecked(
+        &new_pool_contract,
+        pool_box.rate(),
+        pool_box.epoch_counter(),
+        pool_nft_token,
+        reward_token,
+        pool_box.get_box().value,
+        height,
+    )?;
+    if let Err(e) = check_min_box_value(&new_pool_box_candidate) {
+        return mismatch(format!("the new pool box would be invalid: {}", e));
+    }
+    let new_pool_box = ErgoBox::from_box_candidate(&new_pool_box_candidate, TxId::zero(), 0)
+        .map_err(|e| PrepareUpdateError::LivePoolMismatch(e.to_string()))?;
+    if let Err(e) = PoolBoxWrapper::new(new_pool_box, &new_pool_config.pool_box_wrapper_inputs) {
+        return mismatch(format!(
+            "the live datapoint does not fit the new pool contract: {}",
+            e
+        ));
+    }
+    if old_pool_config.token_ids.oracle_token_id != new_pool_config.token_ids.oracle_token_id {
+        log::warn!("New oracle tokens are minted, the current datapoint boxes cannot be collected by the new refresh contract until the oracles hold the new tokens");
+    }
+    Ok(())
 }
 
 struct PrepareUpdate<'a> {
@@ -339,6 +436,7 @@ impl<'a> PrepareUpdate<'a> {
         config: UpdateBootstrapConfig,
     ) -> Result<(PoolConfig, Vec<TxId>), PrepareUpdateError> {
         self.num_transactions_left = 7; // 5 for the tokens, 1 for the refresh box, 1 for the change
+        let reward_tokens_minted = config.tokens_to_mint.reward_tokens.is_some();
 
         let mut need_pool_contract_update = false;
         let mut need_ballot_contract_update = false;
@@ -431,9 +529,10 @@ impl<'a> PrepareUpdate<'a> {
         }
 
         if config.update_contract_parameters.is_some()
+            || config.min_votes.is_some()
             || config.tokens_to_mint.ballot_tokens.is_some()
         {
-            let update_contract_parameters =
+            let mut update_contract_parameters =
                 config.update_contract_parameters.unwrap_or_else(|| {
                     new_pool_config
                         .update_box_wrapper_inputs
@@ -441,6 +540,11 @@ impl<'a> PrepareUpdate<'a> {
                         .contract_parameters()
                         .clone()
                 });
+            if let Some(min_votes) = config.min_votes {
+                info!("Setting min votes of the update contract to {}", min_votes);
+                update_contract_parameters =
+                    update_contract_parameters.with_min_votes(min_votes)?;
+            }
             info!("Creating new update NFT and update box");
             let update_contract_inputs = UpdateContractInputs::build_with(
                 update_contract_parameters.clone(),
@@ -500,6 +604,18 @@ impl<'a> PrepareUpdate<'a> {
             new_pool_config.pool_box_wrapper_inputs = new_pool_box_wrapper_inputs;
         }
 
+        if let Some(live_pool) = &self.input.live_pool {
+            check_update_against_live_pool(
+                self.pool_config,
+                &new_pool_config,
+                &live_pool.pool_box_source.get_pool_box()?,
+                &live_pool.update_box_source.get_update_box()?,
+                reward_tokens_minted,
+                self.input.height,
+            )?;
+            info!("The new config is consistent with the live pool box and update box");
+        }
+
         let mut submitted_tx_ids = Vec::new();
         for tx in self.built_txs {
             let _ = self.input.submit_tx.submit_transaction(&tx)?;
@@ -537,6 +653,8 @@ pub enum PrepareUpdateError {
     RefreshContract(#[from] RefreshContractError),
     #[error("Update contract error: {0}")]
     UpdateContract(#[from] UpdateContractError),
+    #[error("Update contract parameters error: {0}")]
+    UpdateContractParameters(#[from] UpdateContractParametersError),
     #[error("Pool contract failed: {0}")]
     PoolContract(#[from] PoolContractError),
     #[error("Bootstrap config file already exists")]
@@ -555,6 +673,8 @@ pub enum PrepareUpdateError {
     NodeApiError(#[from] NodeApiError),
     #[error("Data source error: {0}")]
     DataSourceError(#[from] DataSourceError),
+    #[error("The update tx could not validate against the live pool: {0}")]
+    LivePoolMismatch(String),
 }
 
 #[cfg(test)]
@@ -566,14 +686,17 @@ mod test {
             address::{AddressEncoder, NetworkAddress, NetworkPrefix},
             ergo_box::{ErgoBox, NonMandatoryRegisters},
         },
-        wallet::Wallet,
+        wallet::{tx_builder::SUGGESTED_TX_FEE, Wallet},
     };
     use sigma_test_util::force_any_val;
 
     use super::*;
     use crate::{
         cli_commands::bootstrap::tests::SubmitTxMock,
-        pool_commands::test_utils::{LocalTxSigner, WalletDataMock},
+        oracle_types::EpochCounter,
+        pool_commands::test_utils::{
+            generate_token_ids, make_pool_box, LocalTxSigner, WalletDataMock,
+        },
     };
 
     #[test]
@@ -602,6 +725,8 @@ refresh_contract_parameters:
   max_deviation_percent: 5
   epoch_length_index: 0
   epoch_length: 30
+  reward_per_datapoint_index: 19
+  oracle_reward_per_datapoint: 1
 update_contract_parameters:
   ergo_tree_bytes: 100e040004000400040204020e20193ad1f35c7dc8ac7e27dee7c2bc15e11fa9df24b2984c31e7a3a423e25c17e80400040004000e204ef9c5fa01d634eea5177eb9d5d73889a4b4a458c4024b1b646fc332c2346c270100050004000404d806d601b2a4730000d602b2db63087201730100d603b2a5730200d604db63087203d605b2a5730300d606b27204730400d1ededed938c7202017305edededed937202b2720473060093c17201c1720393c672010405c67203040593c672010504c672030504efe6c672030661edededed93db63087205db6308a793c27205c2a792c17205c1a7918cc77205018cc7a701efe6c67205046192b0b5a4d9010763d801d609db630872079591b172097307edededed938cb2720973080001730993e4c6720705048cc7a70193e4c67207060ecbc2720393e4c67207070e8c72060193e4c6720708058c720602730a730bd9010741639a8c7207018cb2db63088c720702730c00027e730d05
   pool_nft_index: 5
@@ -651,7 +776,7 @@ data_point_source_custom_script: ~
         let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
         let ergo_tree = network_address.address().script().unwrap();
 
-        let value = BASE_FEE.checked_mul_u32(10000).unwrap();
+        let value = SUGGESTED_TX_FEE().checked_mul_u32(10000).unwrap();
         let unspent_boxes = vec![ErgoBox::new(
             value,
             ergo_tree.clone(),
@@ -696,6 +821,7 @@ data_point_source_custom_script: ~
             refresh_contract_parameters: Some(RefreshContractParameters::default()),
             pool_contract_parameters: Some(PoolContractParameters::default()),
             update_contract_parameters: Some(UpdateContractParameters::default()),
+            min_votes: None,
         };
 
         let height = BlockHeight(ctx.pre_header.height);
@@ -710,15 +836,78 @@ data_point_source_custom_script: ~
                 wallet: &wallet,
             },
             submit_tx: &submit_tx,
-            tx_fee: *BASE_FEE,
-            erg_value_per_box: *BASE_FEE,
+            tx_fee: SUGGESTED_TX_FEE(),
+            erg_value_per_box: SUGGESTED_TX_FEE(),
             change_address: change_address.address(),
             height,
+            live_pool: None,
         };
 
         let prepare =
             PrepareUpdate::new(prepare_update_input, &old_pool_config, &old_oracle_config).unwrap();
         let (new_pool_config, _) = prepare.execute(state).unwrap();
         assert!(new_pool_config.token_ids != old_pool_config.token_ids);
+
+        let pool_contract_parameters = old_pool_config
+            .pool_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters();
+        let pool_box = make_pool_box(
+            200,
+            EpochCounter(1),
+            SUGGESTED_TX_FEE(),
+            height,
+            pool_contract_parameters,
+            &old_pool_config.token_ids,
+        );
+        let update_contract = UpdateContract::checked_load(
+            &old_pool_config.update_box_wrapper_inputs.contract_inputs,
+        )
+        .unwrap();
+        let mut update_box_candidate =
+            ErgoBoxCandidateBuilder::new(SUGGESTED_TX_FEE(), update_contract.ergo_tree(), height.0);
+        update_box_candidate.add_token(Token {
+            token_id: old_pool_config.token_ids.update_nft_token_id.token_id(),
+            amount: 1.try_into().unwrap(),
+        });
+        let update_box = UpdateBoxWrapper::new(
+            ErgoBox::from_box_candidate(
+                &update_box_candidate.build().unwrap(),
+                force_any_val::<TxId>(),
+                0,
+            )
+            .unwrap(),
+            &old_pool_config.update_box_wrapper_inputs,
+        )
+        .unwrap();
+        let check = |pool_box: &PoolBoxWrapper, reward_tokens_minted| {
+            check_update_against_live_pool(
+                &old_pool_config,
+                &new_pool_config,
+                pool_box,
+                &update_box,
+                reward_tokens_minted,
+                height,
+            )
+        };
+        check(&pool_box, true).unwrap();
+        // the new reward token is neither in the pool box nor minted
+        assert!(matches!(
+            check(&pool_box, false),
+            Err(PrepareUpdateError::LivePoolMismatch(_))
+        ));
+        // the pool box of another pool
+        let other_pool_box = make_pool_box(
+            200,
+            EpochCounter(1),
+            SUGGESTED_TX_FEE(),
+            height,
+            pool_contract_parameters,
+            &generate_token_ids(),
+        );
+        assert!(matches!(
+            check(&other_pool_box, true),
+            Err(PrepareUpdateError::LivePoolMismatch(_))
+        ));
     }
 }
@@ -1,17 +1,220 @@
-use crate::{box_kind::OracleBox, oracle_state::LocalDatapointBoxSource};
+use crate::babel_fee::BabelFee;
+use crate::box_kind::OracleBox;
+use crate::datapoint_source::DataPointSource;
+use crate::datapoint_source::RuntimeDataPointSource;
+use crate::datapoint_source::SourceChecks;
+use crate::epoch_history::get_epoch_history_file_path;
+use crate::epoch_history::public_key_base16;
+use crate::epoch_history::read_recent_entries;
+use crate::epoch_history::EpochHistoryEntry;
+use crate::node_interface::node_api::NodeApi;
+use crate::oracle_config::OracleConfig;
+use crate::oracle_state::LocalDatapointBoxSource;
+use crate::pool_config::PoolConfig;
+use crate::pool_config::PredefinedDataPointSource;
+use crate::spec_token::TokenIdKind;
+
+/// Recorded epochs the accrual rate is computed over
+const ACCRUAL_EPOCHS: usize = 30;
+
+const NANO_ERGS_PER_ERG: f64 = 1_000_000_000.0;
 
 pub fn print_reward_tokens(
+    node_api: &NodeApi,
     local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    pool_config: &PoolConfig,
+    oracle_config: &OracleConfig,
 ) -> Result<(), anyhow::Error> {
-    if let Some(oracle_box) = local_datapoint_box_source.get_local_oracle_datapoint_box()? {
-        let num_tokens = *oracle_box.reward_token().amount.as_u64();
-        if num_tokens == 0 {
-            println!("Oracle box contains zero reward tokens");
-        } else {
-            println!("Number of claimable reward tokens: {}", num_tokens - 1);
+    let oracle_box = match local_datapoint_box_source.get_local_oracle_datapoint_box()? {
+        Some(oracle_box) => oracle_box,
+        None => {
+            println!("No datapoint box exists");
+            return Ok(());
+        }
+    };
+    let erg_rewards = pool_config.erg_rewards.as_ref();
+    let reward_token_id = String::from(pool_config.token_ids.reward_token_id.token_id());
+    // claimable rewards and the nanoERG value of one reward unit, if known
+    let (claimable, nano_ergs_per_unit, reward_per_datapoint) = match erg_rewards {
+        Some(erg_rewards) => {
+            // the oracle contract requires the box to keep its min storage rent
+            let min_storage_rent = oracle_box.contract().min_storage_rent()?;
+            let value = *oracle_box.get_box().value.as_u64();
+            let claimable = value.saturating_sub(*min_storage_rent.as_u64());
+            println!("Claimable ERG rewards (nanoERG): {}", claimable);
+            (
+                claimable,
+                Some(1.0),
+                erg_rewards.oracle_reward_per_datapoint,
+            )
+        }
+        None => {
+            match node_api.get_token_name(&reward_token_id) {
+                Ok(Some(name)) => println!("Reward token: {} ({})", name, reward_token_id),
+                Ok(None) => println!("Reward token: {}", reward_token_id),
+                Err(e) => {
+                    log::warn!("Failed to get the reward token name from the node: {}", e);
+                    println!("Reward token: {}", reward_token_id);
+                }
+            }
+            let num_tokens = *oracle_box.reward_token().amount.as_u64();
+            if num_tokens == 0 {
+                println!("Oracle box contains zero reward tokens");
+            } else {
+                println!("Number of claimable reward tokens: {}", num_tokens - 1);
+            }
+            (
+                num_tokens.saturating_sub(1),
+                reward_token_price(node_api, oracle_config, &reward_token_id).map(|p| p as f64),
+                pool_config
+                    .refresh_box_wrapper_inputs
+                    .contract_inputs
+                    .contract_parameters()
+                    .oracle_reward_tokens_per_datapoint(),
+            )
         }
+    };
+
+    let unit = if erg_rewards.is_some() {
+        "nanoERG"
     } else {
-        println!("No datapoint box exists");
+        "reward tokens"
+    };
+    let recent_epochs = read_recent_entries(&get_epoch_history_file_path(), ACCRUAL_EPOCHS)?;
+    let oracle_public_key = public_key_base16(&oracle_config.oracle_address_p2pk()?.h);
+    match accrual_per_epoch(&recent_epochs, &oracle_public_key, reward_per_datapoint) {
+        Some(accrual) => println!(
+            "Accrual over the last {} recorded epochs: {:.2} {} per epoch (datapoints collected in {} epochs)",
+            recent_epochs.len(),
+            accrual.per_epoch,
+            unit,
+            accrual.collected_epochs
+        ),
+        None => println!("Accrual: no epochs recorded in the local history yet"),
+    }
+
+    match nano_ergs_per_unit {
+        Some(nano_ergs_per_unit) => {
+            let nano_ergs = estimated_nano_ergs(claimable, nano_ergs_per_unit);
+            let erg_usd_source =
+                RuntimeDataPointSource::Predefined(
+                PredefinedDataPointSource::NanoErgUsd,
+                SourceChecks::from_oracle_config(oracle_config),
+            );
+            match erg_usd_source.get_datapoint() {
+                Ok(nano_ergs_per_usd) => println!(
+                    "Estimated value: {:.4} ERG ({:.2} USD)",
+                    nano_ergs / NANO_ERGS_PER_ERG,
+                    nano_ergs / i64::from(nano_ergs_per_usd) as f64
+                ),
+                Err(e) => {
+                    log::warn!("Failed to fetch the ERG/USD rate: {}", e);
+                    println!("Estimated value: {:.4} ERG", nano_ergs / NANO_ERGS_PER_ERG);
+                }
+            }
+        }
+        None => println!(
+            "Estimated value: unknown, no babel box buys the reward token (see babel_fee in the oracle config)"
+        ),
     }
     Ok(())
 }
+
+/// Highest nanoERG price per reward token offered by the babel boxes of the oracle config, if they
+/// accept the reward token
+fn reward_token_price(
+    node_api: &NodeApi,
+    oracle_config: &OracleConfig,
+    reward_token_id: &str,
+) -> Option<u64> {
+    let config = oracle_config.babel_fee.as_ref()?;
+    if config.token_id != reward_token_id {
+        return None;
+    }
+    match BabelFee::new(config, node_api, oracle_config.min_box_value())
+        .and_then(|babel_fee| babel_fee.best_price())
+    {
+        Ok(price) => price,
+        Err(e) => {
+            log::warn!(
+                "Failed to get the reward token price from the babel boxes: {}",
+                e
+            );
+            None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Accrual {
+    /// Recorded epochs in which the local datapoint was collected
+    pub collected_epochs: usize,
+    /// Average rewards per recorded epoch, without the collector reward
+    pub per_epoch: f64,
+}
+
+/// Rewards the oracle with the given public key (base16) earned per epoch in the recorded epochs,
+/// `None` without recorded epochs
+pub fn accrual_per_epoch(
+    recent_epochs: &[EpochHistoryEntry],
+    oracle_public_key: &str,
+    reward_per_datapoint: u64,
+) -> Option<Accrual> {
+    if recent_epochs.is_empty() {
+        return None;
+    }
+    let collected_epochs = recent_epochs
+        .iter()
+        .filter(|e| e.oracles.iter().any(|o| o == oracle_public_key))
+        .count();
+    Some(Accrual {
+        collected_epochs,
+        per_epoch: (collected_epochs as u64 * reward_per_datapoint) as f64
+            / recent_epochs.len() as f64,
+    })
+}
+
+fn estimated_nano_ergs(claimable: u64, nano_ergs_per_unit: f64) -> f64 {
+    claimable as f64 * nano_ergs_per_unit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(epoch_id: u32, oracles: &[&str]) -> EpochHistoryEntry {
+        EpochHistoryEntry {
+            epoch_id,
+            rate: 250,
+            height: 1000 + epoch_id * 30,
+            pool_box_id: String::new(),
+            oracles: oracles.iter().map(|o| o.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_accrual_per_epoch() {
+        assert_eq!(accrual_per_epoch(&[], "ab", 1), None);
+        let epochs = vec![
+            entry(1, &["ab", "cd"]),
+            entry(2, &["cd"]),
+            entry(3, &["ab"]),
+            entry(4, &["cd", "ab"]),
+        ];
+        assert_eq!(
+            accrual_per_epoch(&epochs, "ab", 1),
+            Some(Accrual {
+                collected_epochs: 3,
+                per_epoch: 0.75
+            })
+        );
+        assert_eq!(
+            accrual_per_epoch(&epochs, "ef", 1_000_000),
+            Some(Accrual {
+                collected_epochs: 0,
+                per_epoch: 0.0
+            })
+        );
+        assert_eq!(estimated_nano_ergs(4, 2_500_000.0), 10_000_000.0);
+    }
+}
@@ -0,0 +1,426 @@
+//! Replay of the main loop decisions over a height range from explorer data, to debug missed or
+//! wrong actions of the local oracle.
+//!
+//! All the boxes that held the pool NFT or the oracle token are fetched from the explorer. At every
+//! height of the range the pool box and the oracle boxes unspent after that block make up the state
+//! the main loop would have seen, and the command it would have run is printed along with the pool
+//! and local oracle boxes actually created on-chain. A box is spent at the height of the spending
+//! tx, known from the boxes of that tx holding either token; a box spent by a tx without such a box
+//! (e.g. one burning the oracle token) is treated as unspent.
+//!
+//! The replay has no datapoint source, the posting policy is given the pool rate as the current
+//! rate. The HA coordinator is not replayed.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use ergo_lib::chain::transaction::TxId;
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
+use thiserror::Error;
+
+use crate::box_kind::OracleBox;
+use crate::box_kind::OracleBoxWrapper;
+use crate::box_kind::PoolBox;
+use crate::box_kind::PoolBoxWrapper;
+use crate::box_kind::PostedOracleBox;
+use crate::datapoint_source::DataPointSource;
+use crate::datapoint_source::DataPointSourceError;
+use crate::explorer_api::explorer_url::default_explorer_api_url;
+use crate::explorer_api::ExplorerApi;
+use crate::explorer_api::ExplorerApiError;
+use crate::explorer_api::ExplorerBox;
+use crate::oracle_config::OracleConfig;
+use crate::oracle_config::OracleConfigFileError;
+use crate::oracle_state::live_epoch_state;
+use crate::oracle_state::local_oracle_box;
+use crate::oracle_state::posted_oracle_boxes;
+use crate::oracle_state::DataSourceError;
+use crate::oracle_state::LocalDatapointBoxSource;
+use crate::oracle_state::PoolBoxSource;
+use crate::oracle_state::PostedDatapointBoxesSource;
+use crate::oracle_types::BlockHeight;
+use crate::oracle_types::Rate;
+use crate::pool_commands::PoolCommand;
+use crate::pool_config::PoolConfig;
+use crate::posting_policy::CurrentRate;
+use crate::spec_token::TokenIdKind;
+use crate::state::process;
+use crate::state::EpochParams;
+use crate::state::PoolState;
+use crate::state::DEFAULT_REFRESH_DELAY_BLOCKS_PER_RANK;
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("explorer error: {0}")]
+    Explorer(#[from] ExplorerApiError),
+    #[error("oracle config error: {0}")]
+    OracleConfig(#[from] OracleConfigFileError),
+    #[error("invalid height range {from_height}-{to_height}")]
+    InvalidRange { from_height: u32, to_height: u32 },
+}
+
+/// Boxes of the explorer with the heights they were spent at
+pub struct BoxHistory {
+    boxes: Vec<ExplorerBox>,
+    spent_heights: HashMap<BoxId, u32>,
+}
+
+impl BoxHistory {
+    pub fn new(boxes: Vec<ExplorerBox>) -> Self {
+        let tx_heights: HashMap<TxId, u32> = boxes
+            .iter()
+            .map(|b| (b.ergo_box.transaction_id, b.settlement_height))
+            .collect();
+        let spent_heights = boxes
+            .iter()
+            .filter_map(|b| {
+                let spent_height = tx_heights.get(b.spent_transaction_id.as_ref()?)?;
+                Some((b.ergo_box.box_id(), *spent_height))
+            })
+            .collect();
+        BoxHistory {
+            boxes,
+            spent_heights,
+        }
+    }
+
+    /// Boxes unspent after the block at the height
+    pub fn unspent_at(&self, height: u32) -> Vec<ErgoBox> {
+        self.boxes
+            .iter()
+            .filter(|b| {
+                b.settlement_height <= height
+                    && self
+                        .spent_heights
+                        .get(&b.ergo_box.box_id())
+                        .map_or(true, |spent_height| *spent_height > height)
+            })
+            .map(|b| b.ergo_box.clone())
+            .collect()
+    }
+
+    /// Boxes included in the block at the height
+    pub fn included_at(&self, height: u32) -> impl Iterator<Item = &ErgoBox> {
+        self.boxes
+            .iter()
+            .filter(move |b| b.settlement_height == height)
+            .map(|b| &b.ergo_box)
+    }
+}
+
+/// Pool and oracle boxes unspent at a height
+struct ChainSnapshot<'a> {
+    boxes: Vec<ErgoBox>,
+    pool_config: &'a PoolConfig,
+    oracle_pk: &'a ProveDlog,
+}
+
+impl<'a> PoolBoxSource for ChainSnapshot<'a> {
+    fn get_pool_box(&self) -> Result<PoolBoxWrapper, DataSourceError> {
+        self.boxes
+            .iter()
+            .find_map(|b| {
+                PoolBoxWrapper::new(b.clone(), &self.pool_config.pool_box_wrapper_inputs).ok()
+            })
+            .ok_or(DataSourceError::PoolBoxNotFoundError)
+    }
+}
+
+impl<'a> LocalDatapointBoxSource for ChainSnapshot<'a> {
+    fn get_local_oracle_datapoint_box(&self) -> Result<Option<OracleBoxWrapper>, DataSourceError> {
+        Ok(local_oracle_box(
+            self.boxes.clone(),
+            &self.pool_config.oracle_box_wrapper_inputs,
+            self.oracle_pk,
+        ))
+    }
+}
+
+impl<'a> PostedDatapointBoxesSource for ChainSnapshot<'a> {
+    fn get_posted_datapoint_boxes(&self) -> Result<Vec<PostedOracleBox>, DataSourceError> {
+        Ok(posted_oracle_boxes(
+            self.boxes.clone(),
+            &self.pool_config.oracle_box_wrapper_inputs,
+        ))
+    }
+}
+
+/// The pool rate given to the posting policy as the current rate
+struct PoolRate(Rate);
+
+impl DataPointSource for PoolRate {
+    fn get_datapoint(&self) -> Result<Rate, DataPointSourceError> {
+        Ok(self.0)
+    }
+}
+
+/// Box created on-chain in the replayed range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OnChainEvent {
+    PoolBox { epoch_id: u32, rate: i64 },
+    LocalDatapointPosted { epoch_id: u32, rate: i64 },
+    LocalDatapointCollected,
+}
+
+impl fmt::Display for OnChainEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OnChainEvent::PoolBox { epoch_id, rate } => {
+                write!(f, "pool box of epoch {} with rate {}", epoch_id, rate)
+            }
+            OnChainEvent::LocalDatapointPosted { epoch_id, rate } => {
+                write!(f, "local datapoint {} posted in epoch {}", rate, epoch_id)
+            }
+            OnChainEvent::LocalDatapointCollected => write!(f, "local datapoint collected"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayStep {
+    pub height: u32,
+    /// Command the main loop would have run at the height
+    pub command: Option<PoolCommand>,
+    pub on_chain: Vec<OnChainEvent>,
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub steps: Vec<ReplayStep>,
+}
+
+impl ReplayReport {
+    /// Consecutive heights with the same command, as (first height, last height, command)
+    pub fn command_ranges(&self) -> Vec<(u32, u32, &PoolCommand)> {
+        let mut ranges: Vec<(u32, u32, &PoolCommand)> = Vec::new();
+        for step in &self.steps {
+            let command = match &step.command {
+                Some(command) => command,
+                None => continue,
+            };
+            match ranges.last_mut() {
+                Some((_, last, last_command))
+                    if *last + 1 == step.height && *last_command == command =>
+                {
+                    *last = step.height
+                }
+                Some(_) | None => ranges.push((step.height, step.height, command)),
+            }
+        }
+        ranges
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges = self.command_ranges();
+        let mut ranges = ranges.iter().peekable();
+        for step in &self.steps {
+            while let Some((first, last, command)) = ranges.peek() {
+                if *first > step.height {
+                    break;
+                }
+                if first == last {
+                    writeln!(f, "{} would run {:?}", first, command)?;
+                } else {
+                    writeln!(f, "{}-{} would run {:?}", first, last, command)?;
+                }
+                ranges.next();
+            }
+            for event in &step.on_chain {
+                writeln!(f, "{} on-chain: {}", step.height, event)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Boxes of the pool created at the height
+fn on_chain_events(
+    history: &BoxHistory,
+    height: u32,
+    pool_config: &PoolConfig,
+    oracle_pk: &ProveDlog,
+) -> Vec<OnChainEvent> {
+    history
+        .included_at(height)
+        .filter_map(|b| {
+            if let Ok(pool_box) =
+                PoolBoxWrapper::new(b.clone(), &pool_config.pool_box_wrapper_inputs)
+            {
+                return Some(OnChainEvent::PoolBox {
+                    epoch_id: pool_box.epoch_counter().0,
+                    rate: pool_box.rate(),
+                });
+            }
+            let oracle_box =
+                OracleBoxWrapper::new(b.clone(), &pool_config.oracle_box_wrapper_inputs).ok()?;
+            if oracle_box.public_key() != *oracle_pk.h {
+                return None;
+            }
+            Some(match oracle_box {
+                OracleBoxWrapper::Posted(posted) => OnChainEvent::LocalDatapointPosted {
+                    epoch_id: posted.epoch_counter().0,
+                    rate: i64::from(posted.rate()),
+                },
+                OracleBoxWrapper::Collected(_) => OnChainEvent::LocalDatapointCollected,
+            })
+        })
+        .collect()
+}
+
+/// Run the main loop decisions at every height of the range on the boxes of the history
+pub fn replay(
+    history: &BoxHistory,
+    from_height: u32,
+    to_height: u32,
+    pool_config: &PoolConfig,
+    oracle_config: &OracleConfig,
+) -> Result<ReplayReport, ReplayError> {
+    if from_height > to_height {
+        return Err(ReplayError::InvalidRange {
+            from_height,
+            to_height,
+        });
+    }
+    let oracle_pk = oracle_config.oracle_address_p2pk()?;
+    let epoch_params = EpochParams::from(
+        pool_config
+            .refresh_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters(),
+    );
+    let refresh_delay_blocks_per_rank = oracle_config
+        .refresh_delay_blocks_per_rank
+        .unwrap_or(DEFAULT_REFRESH_DELAY_BLOCKS_PER_RANK);
+    let posting_policy = oracle_config
+        .posting_policy
+        .clone()
+        .unwrap_or_default()
+        .build();
+    let mut steps = Vec::new();
+    for height in from_height..=to_height {
+        let snapshot = ChainSnapshot {
+            boxes: history.unspent_at(height),
+            pool_config,
+            oracle_pk: &oracle_pk,
+        };
+        let (pool_state, pool_rate) =
+            match live_epoch_state(&snapshot, &snapshot, &snapshot, &oracle_pk.h) {
+                Ok(live_epoch) => {
+                    let pool_rate = Rate::from(live_epoch.latest_pool_datapoint as i64);
+                    (PoolState::LiveEpoch(live_epoch), pool_rate)
+                }
+                Err(_) => (PoolState::NeedsBootstrap, Rate::from(0)),
+            };
+        let pool_rate = PoolRate(pool_rate);
+        let command = process(
+            pool_state,
+            &epoch_params,
+            BlockHeight(height),
+            refresh_delay_blocks_per_rank,
+            posting_policy.as_ref(),
+            &CurrentRate::new(&pool_rate),
+        );
+        steps.push(ReplayStep {
+            height,
+            command,
+            on_chain: on_chain_events(history, height, pool_config, &oracle_pk),
+        });
+    }
+    Ok(ReplayReport { steps })
+}
+
+/// Fetch the boxes of the pool from the explorer and replay the range
+pub fn replay_from_explorer(
+    from_height: u32,
+    to_height: u32,
+    pool_config: &PoolConfig,
+    oracle_config: &OracleConfig,
+) -> Result<ReplayReport, ReplayError> {
+    let explorer_api = ExplorerApi::new(
+        oracle_config
+            .explorer_url
+            .clone()
+            .unwrap_or_else(|| default_explorer_api_url(oracle_config.oracle_address.network())),
+    );
+    let mut boxes = explorer_api
+        .get_boxes_by_token_id_v1(pool_config.token_ids.pool_nft_token_id.token_id())?;
+    boxes.extend(
+        explorer_api.get_boxes_by_token_id_v1(pool_config.token_ids.oracle_token_id.token_id())?,
+    );
+    log::info!("Replaying {} boxes fetched from the explorer", boxes.len());
+    replay(
+        &BoxHistory::new(boxes),
+        from_height,
+        to_height,
+        pool_config,
+        oracle_config,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+
+    fn explorer_box(settlement_height: u32, spent_transaction_id: Option<TxId>) -> ExplorerBox {
+        ExplorerBox {
+            ergo_box: force_any_val::<ErgoBox>(),
+            settlement_height,
+            spent_transaction_id,
+        }
+    }
+
+    #[test]
+    fn test_box_history() {
+        let successor = explorer_box(110, None);
+        let spent = explorer_box(100, Some(successor.ergo_box.transaction_id));
+        // spent by a tx without a box of the history
+        let burnt = explorer_box(100, Some(force_any_val::<TxId>()));
+        let history = BoxHistory::new(vec![spent.clone(), successor.clone(), burnt.clone()]);
+        assert!(history.unspent_at(99).is_empty());
+        assert_eq!(
+            history.unspent_at(109),
+            vec![spent.ergo_box.clone(), burnt.ergo_box.clone()]
+        );
+        assert_eq!(
+            history.unspent_at(110),
+            vec![successor.ergo_box.clone(), burnt.ergo_box]
+        );
+        assert_eq!(history.included_at(110).count(), 1);
+    }
+
+    #[test]
+    fn test_command_ranges() {
+        let step = |height, command| ReplayStep {
+            height,
+            command,
+            on_chain: Vec::new(),
+        };
+        let report = ReplayReport {
+            steps: vec![
+                step(10, Some(PoolCommand::Refresh)),
+                step(11, Some(PoolCommand::Refresh)),
+                step(12, None),
+                step(13, Some(PoolCommand::Refresh)),
+                step(14, Some(PoolCommand::PublishFirstDataPoint)),
+            ],
+        };
+        assert_eq!(
+            report.command_ranges(),
+            vec![
+                (10, 11, &PoolCommand::Refresh),
+                (13, 13, &PoolCommand::Refresh),
+                (14, 14, &PoolCommand::PublishFirstDataPoint),
+            ]
+        );
+        assert_eq!(
+            report.to_string(),
+            "10-11 would run Refresh\n13 would run Refresh\n14 would run PublishFirstDataPoint\n"
+        );
+    }
+}
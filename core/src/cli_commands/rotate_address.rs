@@ -0,0 +1,297 @@
+//! Rotation of the oracle key to another address of the node wallet, for key-hygiene rotations
+//! without re-bootstrapping the oracle.
+//!
+//! The oracle token and the reward token are moved in one tx with the local datapoint box, which
+//! keeps its datapoint, so no epoch is missed. Once the tx is confirmed `oracle_address` is
+//! rewritten in the oracle config and the oracle token scans are registered for the new address,
+//! so the next start of the oracle finds its box without a node rescan. The command then waits for
+//! the first datapoint posted from the new address.
+
+use std::path::Path;
+use std::time::Duration;
+
+use ergo_lib::ergotree_ir::chain::address::Address;
+use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+use thiserror::Error;
+
+use crate::box_kind::OracleBox;
+use crate::box_kind::OracleBoxWrapper;
+use crate::cli_commands::transfer_oracle_token::build_transfer_oracle_token_tx;
+use crate::cli_commands::transfer_oracle_token::check_destination;
+use crate::context::OracleContext;
+use crate::explorer_api::ergo_explorer_transaction_link;
+use crate::node_interface::node_api::NodeApi;
+use crate::node_interface::SignTransaction;
+use crate::node_interface::SubmitTransaction;
+use crate::oracle_config::OracleConfig;
+use crate::oracle_config::OracleConfigFileError;
+use crate::oracle_state::OraclePool;
+use crate::oracle_types::BlockHeight;
+use crate::scans::NodeScanRegistry;
+use crate::state::EpochParams;
+
+/// Delay between two checks for the first datapoint of the new address
+const FIRST_POST_POLL_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum RotateAddressError {
+    #[error("{0} is already the oracle address")]
+    SameAddress(String),
+    #[error(
+        "The node wallet has no key for {0}, the oracle could not post from it. \
+        Use `transfer-oracle-token` to move the oracle token to another wallet"
+    )]
+    NotInNodeWallet(String),
+    #[error("No local datapoint box is found for the new address {0} after the rotation tx")]
+    RotatedBoxNotFound(String),
+    #[error("No datapoint was posted from {address} in {blocks} blocks after the rotation")]
+    FirstPostNotFound { address: String, blocks: u32 },
+    #[error("Error writing the oracle config: {0}")]
+    OracleConfig(#[from] OracleConfigFileError),
+    #[error(
+        "The oracle config has {0} top-level `oracle_address:` lines instead of one, \
+        set oracle_address by hand after the rotation"
+    )]
+    OracleAddressLines(usize),
+    #[error("oracle_address of the rewritten oracle config is not the new address")]
+    OracleAddressNotRewritten,
+}
+
+/// The oracle config file with the value of its `oracle_address` line replaced. The rest of the
+/// text is kept as it is, comments, key order and `keyring:` references included.
+pub fn rotated_oracle_config_str(
+    oracle_config_str: &str,
+    new_address: &NetworkAddress,
+) -> Result<String, RotateAddressError> {
+    OracleConfig::load_from_str(oracle_config_str)?;
+    let mut address_lines = 0;
+    let rotated_config_str: String = oracle_config_str
+        .split_inclusive('\n')
+        .map(|line| match line.strip_prefix("oracle_address:") {
+            Some(value) => {
+                address_lines += 1;
+                // the comment and the line ending after the value are kept
+                let value_end = value.find('#').unwrap_or(value.len());
+                let kept = &value[value[..value_end].trim_end().len()..];
+                format!("oracle_address: {}{}", new_address.to_base58(), kept)
+            }
+            None => line.to_string(),
+        })
+        .collect();
+    if address_lines != 1 {
+        return Err(RotateAddressError::OracleAddressLines(address_lines));
+    }
+    let rotated_address = OracleConfig::load_from_str(&rotated_config_str)?.oracle_address;
+    if rotated_address.to_base58() != new_address.to_base58() {
+        return Err(RotateAddressError::OracleAddressNotRewritten);
+    }
+    Ok(rotated_config_str)
+}
+
+/// Whether the local datapoint box is a datapoint posted after the rotation, i.e. a posted box
+/// other than the one the rotation tx created
+pub fn is_first_post(local_box: &OracleBoxWrapper, rotated_box_id: BoxId) -> bool {
+    match local_box {
+        OracleBoxWrapper::Posted(posted) => posted.get_box().box_id() != rotated_box_id,
+        OracleBoxWrapper::Collected(_) => false,
+    }
+}
+
+pub fn rotate_address(
+    node_api: &NodeApi,
+    op: &OraclePool,
+    ctx: &OracleContext,
+    oracle_config_path: &Path,
+    new_address_str: String,
+    height: BlockHeight,
+    verify_timeout_blocks: Option<u32>,
+) -> Result<(), anyhow::Error> {
+    let oracle_config = &ctx.oracle_config;
+    let (change_address, network_prefix) = {
+        let net_address = node_api.get_change_address()?;
+        (net_address.address(), net_address.network())
+    };
+    let new_pk = check_destination(&new_address_str, network_prefix)?;
+    if new_pk == oracle_config.oracle_address_p2pk()? {
+        return Err(RotateAddressError::SameAddress(new_address_str).into());
+    }
+    let new_address = AddressEncoder::unchecked_parse_network_address_from_str(&new_address_str)?;
+    if node_api.get_private_key(&new_address).is_err() {
+        return Err(RotateAddressError::NotInNodeWallet(new_address_str).into());
+    }
+    // checked before the tx, so a config that can't be rewritten leaves the oracle untouched
+    let oracle_config_str = std::fs::read_to_string(oracle_config_path)?;
+    let rotated_config_str = rotated_oracle_config_str(&oracle_config_str, &new_address)?;
+
+    let unsigned_tx = build_transfer_oracle_token_tx(
+        op.get_local_datapoint_box_source(),
+        node_api,
+        Address::P2Pk(new_pk),
+        height,
+        change_address,
+        oracle_config.base_fee(),
+    )?;
+    let signed_tx = SignTransaction::sign_transaction(&node_api.node, &unsigned_tx)?;
+    let tx_id = SubmitTransaction::submit_transaction(&node_api.node, &signed_tx)?;
+    println!(
+        "Rotation tx submitted: {}",
+        ergo_explorer_transaction_link(tx_id, network_prefix, oracle_config)
+    );
+    crate::explorer_api::wait_for_tx_confirmation(signed_tx.id(), oracle_config);
+
+    std::fs::write(oracle_config_path, rotated_config_str)?;
+    println!(
+        "oracle_address set to {} in {}",
+        new_address_str,
+        oracle_config_path.display()
+    );
+    let rotated_ctx = OracleContext::new(
+        OracleConfig {
+            oracle_address: new_address,
+            ..oracle_config.clone()
+        },
+        ctx.pool_config.clone(),
+    );
+    let node_scan_registry = NodeScanRegistry::ensure_node_registered_scans(
+        node_api,
+        &rotated_ctx.pool_config,
+        &rotated_ctx.oracle_config.oracle_address,
+    )?;
+    let rotated_op = OraclePool::new(&node_scan_registry, &rotated_ctx)?;
+    let rotated_box = rotated_op
+        .get_local_datapoint_box_source()
+        .get_local_oracle_datapoint_box()?
+        .ok_or_else(|| RotateAddressError::RotatedBoxNotFound(new_address_str.clone()))?;
+
+    let verify_timeout_blocks = match verify_timeout_blocks {
+        Some(0) => {
+            println!("Restart the oracle to post from the new address.");
+            return Ok(());
+        }
+        Some(blocks) => blocks,
+        // two epochs, so an epoch ending right after the rotation is not a failure
+        None => {
+            let epoch_params = EpochParams::from(
+                ctx.pool_config
+                    .refresh_box_wrapper_inputs
+                    .contract_inputs
+                    .contract_parameters(),
+            );
+            2 * epoch_params.epoch_length.0.max(0) as u32
+        }
+    };
+    println!(
+        "Restart the oracle to post from the new address. Waiting up to {} blocks for its first datapoint...",
+        verify_timeout_blocks
+    );
+    let start_height = node_api.node.current_block_height()? as u32;
+    loop {
+        if let Some(local_box) = rotated_op
+            .get_local_datapoint_box_source()
+            .get_local_oracle_datapoint_box()?
+        {
+            if is_first_post(&local_box, rotated_box.get_box().box_id()) {
+                println!(
+                    "First datapoint from {} posted in box {}",
+                    new_address_str,
+                    String::from(local_box.get_box().box_id())
+                );
+                return Ok(());
+            }
+        }
+        let current_height = node_api.node.current_block_height()? as u32;
+        if current_height.saturating_sub(start_height) > verify_timeout_blocks {
+            return Err(RotateAddressError::FirstPostNotFound {
+                address: new_address_str,
+                blocks: verify_timeout_blocks,
+            }
+            .into());
+        }
+        std::thread::sleep(FIRST_POST_POLL_DELAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use sigma_test_util::force_any_val;
+
+    use crate::box_kind::OracleBoxWrapperInputs;
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::oracle_types::EpochCounter;
+    use crate::pool_commands::test_utils::generate_token_ids;
+    use crate::pool_commands::test_utils::make_datapoint_box;
+
+    use super::*;
+
+    #[test]
+    fn test_rotated_oracle_config_keeps_the_text() {
+        let oracle_config = OracleConfig {
+            node_api_key: "keyring:oracle-node".to_string(),
+            ..OracleConfig::default()
+        };
+        let new_address = AddressEncoder::unchecked_parse_network_address_from_str(
+            "9iHyKxXs2ZNLMp9N9gbUT9V8gTbsV7HED1C1VhttMfBUMPDyF7r",
+        )
+        .unwrap();
+        let with_address_line = |address_line: &str| {
+            let config_str = serde_yaml::to_string(&oracle_config).unwrap();
+            let lines: Vec<String> = config_str
+                .lines()
+                .map(|line| match line.strip_prefix("oracle_address:") {
+                    Some(_) => address_line.to_string(),
+                    None => line.to_string(),
+                })
+                .collect();
+            format!("# primary pool\n{}\n", lines.join("\n"))
+        };
+        let config_str = with_address_line(&format!(
+            "oracle_address: '{}'  # rotated yearly",
+            oracle_config.oracle_address.to_base58()
+        ));
+        let rotated_str = rotated_oracle_config_str(&config_str, &new_address).unwrap();
+        assert_eq!(
+            rotated_str,
+            with_address_line(&format!(
+                "oracle_address: {}  # rotated yearly",
+                new_address.to_base58()
+            ))
+        );
+        let rotated = OracleConfig::load_from_str(&rotated_str).unwrap();
+        assert_eq!(rotated.oracle_address, new_address);
+        assert_eq!(rotated.node_api_key, "keyring:oracle-node");
+        assert_eq!(rotated.base_fee, oracle_config.base_fee);
+    }
+
+    #[test]
+    fn test_is_first_post() {
+        let token_ids = generate_token_ids();
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let public_key = *force_any_val::<DlogProverInput>().public_image().h;
+        let datapoint_box = |rate| {
+            OracleBoxWrapper::new(
+                make_datapoint_box(
+                    public_key.clone(),
+                    rate,
+                    EpochCounter(3),
+                    &token_ids,
+                    force_any_val(),
+                    BlockHeight(100),
+                    1,
+                ),
+                &oracle_box_wrapper_inputs,
+            )
+            .unwrap()
+        };
+        let rotated_box = datapoint_box(200);
+        let rotated_box_id = rotated_box.get_box().box_id();
+        assert!(!is_first_post(&rotated_box, rotated_box_id));
+        assert!(is_first_post(&datapoint_box(201), rotated_box_id));
+    }
+}
@@ -0,0 +1,209 @@
+//! Rehearsal of a pool on the simulated chain of [`crate::sim`] before spending real ERG: the pool
+//! is bootstrapped with the contracts and parameters of a bootstrap config (or of the current pool
+//! config), the simulated oracles post datapoints and the pool is refreshed for a few epochs.
+//! Every step reports whether its tx could be built and signed. Built with the `sim` feature only.
+
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::box_kind::PoolBox;
+use crate::cli_commands::bootstrap::BootstrapConfig;
+use crate::oracle_config::TxParams;
+use crate::oracle_types::Rate;
+use crate::pool_config::PoolConfig;
+use crate::sim::PoolSim;
+use crate::sim::SimError;
+
+#[derive(Debug, Error)]
+pub enum SimulateError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("yaml error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+}
+
+pub struct SimulateInput {
+    pub config: BootstrapConfig,
+    pub oracle_count: usize,
+    pub epochs: u32,
+    /// Datapoint posted by every oracle
+    pub rate: Rate,
+    pub tx_params: TxParams,
+}
+
+/// Outcome of a step of the rehearsal, the description of what was done or the error
+#[derive(Debug)]
+pub struct SimulationStep {
+    pub name: String,
+    pub outcome: Result<String, String>,
+}
+
+/// Steps run until the first failed one
+#[derive(Debug, Default)]
+pub struct SimulationReport {
+    pub steps: Vec<SimulationStep>,
+}
+
+impl SimulationReport {
+    pub fn succeeded(&self) -> bool {
+        self.steps.iter().all(|step| step.outcome.is_ok())
+    }
+
+    fn record<T>(
+        &mut self,
+        name: String,
+        result: Result<T, SimError>,
+        describe: impl FnOnce(&T) -> String,
+    ) -> Option<T> {
+        let (outcome, value) = match result {
+            Ok(value) => (Ok(describe(&value)), Some(value)),
+            Err(e) => (Err(e.to_string()), None),
+        };
+        self.steps.push(SimulationStep { name, outcome });
+        value
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            match &step.outcome {
+                Ok(description) => writeln!(f, "[ok]     {}: {}", step.name, description)?,
+                Err(e) => writeln!(f, "[failed] {}: {}", step.name, e)?,
+            }
+        }
+        if self.succeeded() {
+            write!(f, "Simulation succeeded")
+        } else {
+            write!(f, "Simulation failed")
+        }
+    }
+}
+
+/// Bootstrap config with the contracts and parameters of the pool config and the default tokens
+/// to mint, for rehearsing a pool that is already bootstrapped
+pub fn bootstrap_config_from_pool_config(pool_config: &PoolConfig) -> BootstrapConfig {
+    BootstrapConfig {
+        data_point_source: pool_config.data_point_source,
+        oracle_contract_parameters: pool_config
+            .oracle_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters()
+            .clone(),
+        refresh_contract_parameters: pool_config
+            .refresh_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters()
+            .clone(),
+        pool_contract_parameters: pool_config
+            .pool_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters()
+            .clone(),
+        update_contract_parameters: pool_config
+            .update_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters()
+            .clone(),
+        ballot_contract_parameters: pool_config
+            .ballot_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters()
+            .clone(),
+        tokens_to_mint: BootstrapConfig::default().tokens_to_mint,
+    }
+}
+
+/// Bootstrap config of the given bootstrap config file
+pub fn load_bootstrap_config(file_name: &str) -> Result<BootstrapConfig, SimulateError> {
+    let s = std::fs::read_to_string(file_name)?;
+    Ok(serde_yaml::from_str(&s)?)
+}
+
+pub fn simulate(input: SimulateInput) -> Result<SimulationReport, SimulateError> {
+    let mut report = SimulationReport::default();
+    let mut sim = match report.record(
+        "bootstrap".to_string(),
+        PoolSim::bootstrap_with_tx_params(input.config, input.oracle_count, input.tx_params),
+        |sim| {
+            format!(
+                "pool bootstrapped at height {}, tokens sent to {} oracles",
+                sim.height().0,
+                sim.oracles().len()
+            )
+        },
+    ) {
+        Some(sim) => sim,
+        None => return Ok(report),
+    };
+    let rates = vec![input.rate; sim.oracles().len()];
+    for epoch in 1..=input.epochs {
+        let posted = sim
+            .advance_epoch()
+            .and_then(|_| sim.post_datapoints(&rates));
+        if report
+            .record(format!("epoch {}: publish", epoch), posted, |tx_ids| {
+                format!("{} datapoints posted", tx_ids.len())
+            })
+            .is_none()
+        {
+            break;
+        }
+        // the collector changes every epoch, like the oracles taking turns
+        let collector = (epoch as usize - 1) % rates.len();
+        let refreshed = sim.refresh(collector).and_then(|_| sim.pool_box());
+        if report
+            .record(format!("epoch {}: refresh", epoch), refreshed, |pool_box| {
+                format!(
+                    "pool rate {} in epoch {}",
+                    pool_box.rate(),
+                    pool_box.epoch_counter().0
+                )
+            })
+            .is_none()
+        {
+            break;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulate_default_pool() {
+        let report = simulate(SimulateInput {
+            config: BootstrapConfig::default(),
+            oracle_count: 4,
+            epochs: 2,
+            rate: Rate::from(1_000i64),
+            tx_params: TxParams::default(),
+        })
+        .unwrap();
+        assert!(report.succeeded(), "{}", report);
+        assert_eq!(report.steps.len(), 5);
+        assert_eq!(
+            report.steps[4].outcome.as_ref().unwrap(),
+            "pool rate 1000 in epoch 3"
+        );
+    }
+
+    #[test]
+    fn test_simulate_too_few_oracles() {
+        // the default refresh contract needs 4 datapoints
+        let report = simulate(SimulateInput {
+            config: BootstrapConfig::default(),
+            oracle_count: 3,
+            epochs: 1,
+            rate: Rate::from(1_000i64),
+            tx_params: TxParams::default(),
+        })
+        .unwrap();
+        assert!(!report.succeeded());
+        assert_eq!(report.steps.len(), 3);
+        assert!(report.steps[2].outcome.is_err());
+    }
+}
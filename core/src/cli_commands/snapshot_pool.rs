@@ -0,0 +1,261 @@
+//! Export of the current pool state as JSON, for audits, bug reports and governance reviews.
+//!
+//! Every box is written in the node's JSON format (`box`) along with the values parsed from its
+//! tokens and registers. The datapoint boxes are the posted and collected boxes of the oracle token
+//! scan, the ballot boxes are all the boxes of the ballot token scan, with or without a vote.
+
+use std::io::Write;
+
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use serde::Serialize;
+
+use crate::box_kind::BallotBox;
+use crate::box_kind::BallotBoxWrapper;
+use crate::box_kind::OracleBox;
+use crate::box_kind::OracleBoxWrapper;
+use crate::box_kind::PoolBox;
+use crate::box_kind::PoolBoxWrapper;
+use crate::box_kind::RefreshBox;
+use crate::box_kind::UpdateBoxWrapper;
+use crate::box_kind::VoteBallotBoxWrapper;
+use crate::epoch_history::public_key_base16;
+use crate::oracle_state::CollectedDatapointBoxesSource;
+use crate::oracle_state::OraclePool;
+use crate::oracle_state::PoolBoxSource;
+use crate::oracle_state::PostedDatapointBoxesSource;
+use crate::oracle_state::RefreshBoxSource;
+use crate::oracle_state::UpdateBoxSource;
+use crate::oracle_types::BlockHeight;
+use crate::pool_config::PoolConfig;
+use crate::spec_token::TokenIdKind;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolBoxSnapshot {
+    #[serde(rename = "box")]
+    pub ergo_box: ErgoBox,
+    pub epoch_id: u32,
+    pub rate: i64,
+    pub reward_token_id: String,
+    pub reward_token_amount: u64,
+}
+
+impl PoolBoxSnapshot {
+    pub fn new(pool_box: &PoolBoxWrapper) -> Self {
+        let reward_token = pool_box.reward_token();
+        PoolBoxSnapshot {
+            ergo_box: pool_box.get_box().clone(),
+            epoch_id: pool_box.epoch_counter().0,
+            rate: pool_box.rate(),
+            reward_token_id: String::from(reward_token.token_id.token_id()),
+            reward_token_amount: *reward_token.amount.as_u64(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RefreshBoxSnapshot {
+    #[serde(rename = "box")]
+    pub ergo_box: ErgoBox,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateBoxSnapshot {
+    #[serde(rename = "box")]
+    pub ergo_box: ErgoBox,
+    pub min_votes: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatapointBoxSnapshot {
+    #[serde(rename = "box")]
+    pub ergo_box: ErgoBox,
+    /// Public key (base16) of the oracle
+    pub public_key: String,
+    /// Epoch and rate of a posted datapoint, `None` for a collected box
+    pub epoch_id: Option<u32>,
+    pub rate: Option<i64>,
+    pub reward_token_amount: u64,
+}
+
+impl DatapointBoxSnapshot {
+    pub fn new(oracle_box: &OracleBoxWrapper) -> Self {
+        let (epoch_id, rate) = match oracle_box {
+            OracleBoxWrapper::Posted(posted) => (
+                Some(posted.epoch_counter().0),
+                Some(i64::from(posted.rate())),
+            ),
+            OracleBoxWrapper::Collected(_) => (None, None),
+        };
+        DatapointBoxSnapshot {
+            ergo_box: oracle_box.get_box().clone(),
+            public_key: public_key_base16(&oracle_box.public_key()),
+            epoch_id,
+            rate,
+            reward_token_amount: *oracle_box.reward_token().amount.as_u64(),
+        }
+    }
+}
+
+/// Vote parameters in R5-R7 of a ballot box
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BallotVoteSnapshot {
+    pub pool_box_address_hash: String,
+    pub reward_token_id: Option<String>,
+    pub reward_token_amount: Option<u64>,
+    /// Creation height of the update box the vote was cast with
+    pub update_box_creation_height: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BallotBoxSnapshot {
+    #[serde(rename = "box")]
+    pub ergo_box: ErgoBox,
+    /// Public key (base16) of the ballot token owner
+    pub owner: String,
+    pub ballot_token_amount: u64,
+    pub vote: Option<BallotVoteSnapshot>,
+}
+
+impl BallotBoxSnapshot {
+    pub fn new(ballot_box: &BallotBoxWrapper, pool_config: &PoolConfig) -> Self {
+        let vote = VoteBallotBoxWrapper::new(
+            ballot_box.get_box().clone(),
+            &pool_config.ballot_box_wrapper_inputs,
+        )
+        .ok()
+        .map(|vote_ballot_box| {
+            let vote_parameters = vote_ballot_box.vote_parameters();
+            let reward_token = vote_parameters.reward_token_opt.as_ref();
+            BallotVoteSnapshot {
+                pool_box_address_hash: String::from(vote_parameters.pool_box_address_hash),
+                reward_token_id: reward_token.map(|t| String::from(t.token_id.token_id())),
+                reward_token_amount: reward_token.map(|t| *t.amount.as_u64()),
+                update_box_creation_height: vote_parameters.update_box_creation_height,
+            }
+        });
+        BallotBoxSnapshot {
+            ergo_box: ballot_box.get_box().clone(),
+            owner: public_key_base16(&ballot_box.ballot_token_owner()),
+            ballot_token_amount: *ballot_box.ballot_token().amount.as_u64(),
+            vote,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolSnapshot {
+    /// Node height the snapshot was taken at
+    pub height: u32,
+    pub pool_box: PoolBoxSnapshot,
+    pub refresh_box: RefreshBoxSnapshot,
+    pub update_box: UpdateBoxSnapshot,
+    pub datapoint_boxes: Vec<DatapointBoxSnapshot>,
+    pub ballot_boxes: Vec<BallotBoxSnapshot>,
+}
+
+fn update_box_snapshot(update_box: &UpdateBoxWrapper) -> UpdateBoxSnapshot {
+    UpdateBoxSnapshot {
+        ergo_box: update_box.get_box().clone(),
+        min_votes: update_box.min_votes(),
+    }
+}
+
+pub fn snapshot_pool(
+    op: &OraclePool,
+    pool_config: &PoolConfig,
+    height: BlockHeight,
+) -> Result<PoolSnapshot, anyhow::Error> {
+    let posted_boxes = op
+        .get_posted_datapoint_boxes_source()
+        .get_posted_datapoint_boxes()?
+        .into_iter()
+        .map(OracleBoxWrapper::Posted);
+    let collected_boxes = op
+        .get_collected_datapoint_boxes_source()
+        .get_collected_datapoint_boxes()?
+        .into_iter()
+        .map(OracleBoxWrapper::Collected);
+    Ok(PoolSnapshot {
+        height: height.0,
+        pool_box: PoolBoxSnapshot::new(&op.get_pool_box_source().get_pool_box()?),
+        refresh_box: RefreshBoxSnapshot {
+            ergo_box: op
+                .get_refresh_box_source()
+                .get_refresh_box()?
+                .get_box()
+                .clone(),
+        },
+        update_box: update_box_snapshot(&op.get_update_box_source().get_update_box()?),
+        datapoint_boxes: posted_boxes
+            .chain(collected_boxes)
+            .map(|b| DatapointBoxSnapshot::new(&b))
+            .collect(),
+        ballot_boxes: op
+            .get_all_ballot_boxes()?
+            .iter()
+            .map(|b| BallotBoxSnapshot::new(b, pool_config))
+            .collect(),
+    })
+}
+
+/// Write the snapshot of the pool as pretty-printed JSON
+pub fn write_pool_snapshot(
+    op: &OraclePool,
+    pool_config: &PoolConfig,
+    height: BlockHeight,
+    writer: &mut dyn Write,
+) -> Result<(), anyhow::Error> {
+    let snapshot = snapshot_pool(op, pool_config, height)?;
+    serde_json::to_writer_pretty(&mut *writer, &snapshot)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use sigma_test_util::force_any_val;
+
+    use crate::box_kind::OracleBoxWrapperInputs;
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::oracle_types::EpochCounter;
+    use crate::pool_commands::test_utils::generate_token_ids;
+    use crate::pool_commands::test_utils::make_datapoint_box;
+
+    use super::*;
+
+    #[test]
+    fn test_datapoint_box_snapshot() {
+        let token_ids = generate_token_ids();
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let public_key = force_any_val::<DlogProverInput>().public_image().h;
+        let oracle_box = OracleBoxWrapper::new(
+            make_datapoint_box(
+                *public_key.clone(),
+                200,
+                EpochCounter(3),
+                &token_ids,
+                force_any_val(),
+                BlockHeight(100),
+                5,
+            ),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap();
+        let snapshot = DatapointBoxSnapshot::new(&oracle_box);
+        assert_eq!(snapshot.public_key, public_key_base16(&public_key));
+        assert_eq!(snapshot.epoch_id, Some(3));
+        assert_eq!(snapshot.rate, Some(200));
+        assert_eq!(snapshot.reward_token_amount, 5);
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(
+            json["box"]["boxId"],
+            String::from(oracle_box.get_box().box_id())
+        );
+        assert_eq!(json["rate"], 200);
+    }
+}
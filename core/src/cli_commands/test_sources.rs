@@ -0,0 +1,105 @@
+//! Dry run of the datapoint source of the pool, for checking a new pool config before running the
+//! oracle.
+//!
+//! The datapoint is fetched once like the main loop does, printing the raw rate, the latency and
+//! the error of every source it is aggregated from, and the datapoint that would be posted. Nothing
+//! is posted and no node is needed.
+
+use std::fmt::Write;
+
+use crate::datapoint_source::DataPointSourceError;
+use crate::datapoint_source::FetchedDatapoint;
+use crate::datapoint_source::RuntimeDataPointSource;
+use crate::datapoint_source::SourceChecks;
+use crate::datapoint_source::SourceTrial;
+use crate::oracle_config::OracleConfig;
+use crate::pool_config::PoolConfig;
+
+pub fn test_sources(
+    pool_config: &PoolConfig,
+    oracle_config: &OracleConfig,
+) -> Result<(), anyhow::Error> {
+    let datapoint_source = RuntimeDataPointSource::new(
+        pool_config.data_point_source,
+        oracle_config.data_point_source_custom_script.clone(),
+    )?
+    .with_checks(SourceChecks::from_oracle_config(oracle_config));
+    let (fetched, trials) = datapoint_source.dry_run();
+    print!("{}", format_dry_run(&trials, &fetched));
+    fetched?;
+    Ok(())
+}
+
+/// One line per source followed by the datapoint
+pub fn format_dry_run(
+    trials: &[SourceTrial],
+    fetched: &Result<FetchedDatapoint, DataPointSourceError>,
+) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:<16} {:<20} {:>20} {:>10}  error",
+        "source", "pair", "rate", "latency"
+    )
+    .unwrap();
+    for trial in trials {
+        writeln!(
+            out,
+            "{:<16} {:<20} {:>20} {:>8}ms  {}",
+            trial.source,
+            trial.pair,
+            trial
+                .rate
+                .map(|rate| format!("{:.2}", rate))
+                .unwrap_or_else(|| "-".to_string()),
+            trial.latency.as_millis(),
+            trial.error.as_deref().unwrap_or("")
+        )
+        .unwrap();
+    }
+    match fetched {
+        Ok(fetched) => writeln!(out, "Datapoint that would be posted: {}", fetched.rate),
+        Err(e) => writeln!(out, "No datapoint would be posted: {}", e),
+    }
+    .unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_format_dry_run() {
+        let trials = vec![
+            SourceTrial {
+                source: "coingecko".to_string(),
+                pair: "NanoErg per Usd".to_string(),
+                rate: Some(1_500_000_000.0),
+                error: None,
+                latency: Duration::from_millis(120),
+            },
+            SourceTrial {
+                source: "htx".to_string(),
+                pair: "NanoErg per Usd".to_string(),
+                rate: None,
+                error: Some("No datapoints from any source".to_string()),
+                latency: Duration::from_millis(30),
+            },
+        ];
+        let fetched = Ok(FetchedDatapoint {
+            rate: 1_500_000_000i64.into(),
+            sources: Vec::new(),
+        });
+        let out = format_dry_run(&trials, &fetched);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].contains("1500000000.00") && lines[1].contains("120ms"));
+        assert!(lines[2].contains(" - ") && lines[2].ends_with("No datapoints from any source"));
+        assert_eq!(lines[3], "Datapoint that would be posted: 1500000000");
+        let failed = format_dry_run(&trials, &Err(DataPointSourceError::NoDataPoints));
+        assert!(failed.ends_with("No datapoint would be posted: No datapoints from any source\n"));
+    }
+}
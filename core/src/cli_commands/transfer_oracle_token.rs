@@ -5,10 +5,17 @@ use ergo_lib::{
         ergo_box::box_builder::ErgoBoxCandidateBuilderError,
         transaction::unsigned::UnsignedTransaction,
     },
-    ergotree_interpreter::sigma_protocol::prover::ContextExtension,
+    ergotree_interpreter::sigma_protocol::{
+        prover::ContextExtension,
+        verifier::{verify_signature, VerifierError},
+    },
     ergotree_ir::{
-        chain::address::{Address, AddressEncoder, AddressEncoderError},
+        chain::{
+            address::{Address, AddressEncoder, AddressEncoderError, NetworkPrefix},
+            ergo_box::{box_value::BoxValue, BoxId},
+        },
         serialization::SigmaParsingError,
+        sigma_protocol::sigma_boolean::{ProveDlog, SigmaBoolean},
     },
     wallet::{
         box_selector::{BoxSelection, BoxSelector, BoxSelectorError, SimpleBoxSelector},
@@ -22,9 +29,10 @@ use crate::{
     box_kind::{
         make_collected_oracle_box_candidate, make_oracle_box_candidate, OracleBox, OracleBoxWrapper,
     },
+    cold_signing::ColdSigner,
     explorer_api::ergo_explorer_transaction_link,
     node_interface::{SignTransaction, SubmitTransaction},
-    oracle_config::BASE_FEE,
+    oracle_config::OracleConfig,
     oracle_state::{DataSourceError, LocalDatapointBoxSource},
     oracle_types::BlockHeight,
     wallet::{WalletDataError, WalletDataSource},
@@ -37,8 +45,24 @@ pub enum TransferOracleTokenActionError {
         Use `extract-reward-tokens` command to extract reward tokens from the oracle box.`"
     )]
     IncorrectNumberOfRewardTokensInOracleBox(usize),
-    #[error("Destination address not P2PK")]
+    #[error(
+        "Destination address not P2PK. An oracle token sent to a contract address is unrecoverable."
+    )]
     IncorrectDestinationAddress,
+    #[error(
+        "Destination address {address} is for the {found:?} network, the node runs on {expected:?}"
+    )]
+    WrongNetwork {
+        address: String,
+        expected: NetworkPrefix,
+        found: NetworkPrefix,
+    },
+    #[error("The challenge signature is not a valid signature of the destination address")]
+    InvalidChallengeSignature,
+    #[error("invalid base16 challenge signature: {0}")]
+    ChallengeSignatureEncoding(#[from] base16::DecodeError),
+    #[error("verifier error: {0}")]
+    Verifier(#[from] VerifierError),
     #[error("box builder error: {0}")]
     ErgoBoxCandidateBuilder(#[from] ErgoBoxCandidateBuilderError),
     #[error("data source error: {0}")]
@@ -69,47 +93,126 @@ pub fn transfer_oracle_token(
     tx_submit: &dyn SubmitTransaction,
     local_datapoint_box_source: &dyn LocalDatapointBoxSource,
     rewards_destination_str: String,
+    challenge_signature: Option<String>,
     height: BlockHeight,
+    oracle_config: &OracleConfig,
+    cold_signer: Option<&ColdSigner>,
 ) -> Result<(), anyhow::Error> {
-    let rewards_destination =
-        AddressEncoder::unchecked_parse_network_address_from_str(&rewards_destination_str)?;
     let (change_address, network_prefix) = {
         let net_address = wallet.get_change_address()?;
         (net_address.address(), net_address.network())
     };
+    let destination_pk = check_destination(&rewards_destination_str, network_prefix)?;
+    let in_oracle_box = local_datapoint_box_source
+        .get_local_oracle_datapoint_box()?
+        .ok_or(TransferOracleTokenActionError::NoLocalDatapointBox)?;
+    let challenge = transfer_challenge(in_oracle_box.get_box().box_id(), &rewards_destination_str);
     let unsigned_tx = build_transfer_oracle_token_tx(
         local_datapoint_box_source,
         wallet,
-        rewards_destination.address(),
+        Address::P2Pk(destination_pk.clone()),
         height,
         change_address,
+        oracle_config.base_fee(),
     )?;
 
-    println!(
-        "YOU WILL BE TRANSFERRING YOUR ORACLE TOKEN TO {}. TYPE 'YES' TO INITIATE THE TRANSACTION.",
-        rewards_destination_str
-    );
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)?;
-    if input.trim() == "YES" {
+    let confirmed = if let Some(challenge_signature) = challenge_signature {
+        check_challenge_signature(&destination_pk, &challenge, &challenge_signature)?;
+        println!(
+            "The destination {} signed the transfer challenge",
+            rewards_destination_str
+        );
+        true
+    } else {
+        println!(
+            "To have the destination prove it holds the key, let it sign the message '{}' and pass the \
+            base16 signature with --challenge-signature.",
+            challenge
+        );
+        println!(
+            "YOU WILL BE TRANSFERRING YOUR ORACLE TOKEN TO {}. TYPE 'YES' TO INITIATE THE TRANSACTION.",
+            rewards_destination_str
+        );
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        input.trim() == "YES"
+    };
+    if confirmed {
+        if let Some(cold_signer) = cold_signer {
+            cold_signer.export(&unsigned_tx)?;
+            return Ok(());
+        }
         let signed_tx = tx_signer.sign_transaction(&unsigned_tx)?;
         let tx_id = tx_submit.submit_transaction(&signed_tx)?;
-        crate::explorer_api::wait_for_tx_confirmation(signed_tx.id());
+        crate::explorer_api::wait_for_tx_confirmation(signed_tx.id(), oracle_config);
         println!(
             "Transaction made. Check status here: {}",
-            ergo_explorer_transaction_link(tx_id, network_prefix)
+            ergo_explorer_transaction_link(tx_id, network_prefix, oracle_config)
         );
     } else {
         println!("Aborting the transaction.")
     }
     Ok(())
 }
-fn build_transfer_oracle_token_tx(
+
+/// The destination must be a P2PK address of the node network, an oracle token sent to a contract
+/// or to an address of another network is unrecoverable
+pub fn check_destination(
+    destination_str: &str,
+    network_prefix: NetworkPrefix,
+) -> Result<ProveDlog, TransferOracleTokenActionError> {
+    let destination = AddressEncoder::unchecked_parse_network_address_from_str(destination_str)?;
+    if destination.network() != network_prefix {
+        return Err(TransferOracleTokenActionError::WrongNetwork {
+            address: destination_str.to_string(),
+            expected: network_prefix,
+            found: destination.network(),
+        });
+    }
+    match destination.address() {
+        Address::P2Pk(destination_pk) => Ok(destination_pk),
+        Address::P2S(_) | Address::P2SH(_) => {
+            Err(TransferOracleTokenActionError::IncorrectDestinationAddress)
+        }
+    }
+}
+
+/// Message the destination signs to prove it holds the key of the address. Bound to the current
+/// oracle box, so a signature can't be reused for a later transfer.
+pub fn transfer_challenge(oracle_box_id: BoxId, destination_str: &str) -> String {
+    format!(
+        "oracle-core-transfer:{}:{}",
+        String::from(oracle_box_id),
+        destination_str
+    )
+}
+
+/// Check the base16 Schnorr signature of the challenge by the destination key, as made by the Ergo
+/// wallets
+pub fn check_challenge_signature(
+    destination_pk: &ProveDlog,
+    challenge: &str,
+    challenge_signature: &str,
+) -> Result<(), TransferOracleTokenActionError> {
+    let signature = base16::decode(challenge_signature)?;
+    if verify_signature(
+        SigmaBoolean::from(destination_pk.clone()),
+        challenge.as_bytes(),
+        &signature,
+    )? {
+        Ok(())
+    } else {
+        Err(TransferOracleTokenActionError::InvalidChallengeSignature)
+    }
+}
+
+pub(crate) fn build_transfer_oracle_token_tx(
     local_datapoint_box_source: &dyn LocalDatapointBoxSource,
     wallet: &dyn WalletDataSource,
     oracle_token_destination: Address,
     height: BlockHeight,
     change_address: Address,
+    tx_fee: BoxValue,
 ) -> Result<UnsignedTransaction, TransferOracleTokenActionError> {
     let in_oracle_box = local_datapoint_box_source
         .get_local_oracle_datapoint_box()?
@@ -148,7 +251,7 @@ fn build_transfer_oracle_token_tx(
 
         let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
 
-        let target_balance = *BASE_FEE;
+        let target_balance = tx_fee;
 
         let box_selector = SimpleBoxSelector::new();
         let selection = box_selector.select(unspent_boxes, target_balance, &[])?;
@@ -193,7 +296,9 @@ mod tests {
     use ergo_lib::chain::ergo_state_context::ErgoStateContext;
     use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
     use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
+    use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
     use ergo_lib::wallet::signing::TransactionContext;
+    use ergo_lib::wallet::tx_builder::SUGGESTED_TX_FEE;
     use ergo_lib::wallet::Wallet;
     use sigma_test_util::force_any_val;
 
@@ -215,7 +320,7 @@ mod tests {
                 200,
                 EpochCounter(1),
                 &token_ids,
-                BASE_FEE.checked_mul_u32(100).unwrap(),
+                SUGGESTED_TX_FEE().checked_mul_u32(100).unwrap(),
                 BlockHeight(height.0) - 9,
                 1,
             ),
@@ -231,7 +336,7 @@ mod tests {
 
         let wallet_unspent_box = make_wallet_unspent_box(
             secret.public_image(),
-            BASE_FEE.checked_mul_u32(10000).unwrap(),
+            SUGGESTED_TX_FEE().checked_mul_u32(10000).unwrap(),
             None,
         );
         let wallet_mock = WalletDataMock {
@@ -244,6 +349,7 @@ mod tests {
             change_address.address(),
             height,
             change_address.address(),
+            SUGGESTED_TX_FEE(),
         )
         .unwrap();
 
@@ -264,4 +370,53 @@ mod tests {
 
         let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
     }
+
+    #[test]
+    fn test_check_destination() {
+        let secret = force_any_val::<DlogProverInput>();
+        let p2pk = Address::P2Pk(secret.public_image());
+        let mainnet_p2pk = AddressEncoder::new(NetworkPrefix::Mainnet).address_to_str(&p2pk);
+        let testnet_p2pk = AddressEncoder::new(NetworkPrefix::Testnet).address_to_str(&p2pk);
+        assert_eq!(
+            check_destination(&mainnet_p2pk, NetworkPrefix::Mainnet).unwrap(),
+            secret.public_image()
+        );
+        assert!(matches!(
+            check_destination(&testnet_p2pk, NetworkPrefix::Mainnet),
+            Err(TransferOracleTokenActionError::WrongNetwork { .. })
+        ));
+        let contract = make_wallet_unspent_box(secret.public_image(), SUGGESTED_TX_FEE(), None)
+            .ergo_tree
+            .sigma_serialize_bytes()
+            .unwrap();
+        let p2s =
+            AddressEncoder::new(NetworkPrefix::Mainnet).address_to_str(&Address::P2S(contract));
+        assert!(matches!(
+            check_destination(&p2s, NetworkPrefix::Mainnet),
+            Err(TransferOracleTokenActionError::IncorrectDestinationAddress)
+        ));
+    }
+
+    #[test]
+    fn test_challenge_signature() {
+        let secret = force_any_val::<DlogProverInput>();
+        let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
+        let challenge = transfer_challenge(force_any_val::<BoxId>(), "9fdestination");
+        let signature = wallet
+            .sign_message(
+                SigmaBoolean::from(secret.public_image()),
+                challenge.as_bytes(),
+            )
+            .unwrap();
+        let signature = base16::encode_lower(&signature);
+        assert!(check_challenge_signature(&secret.public_image(), &challenge, &signature).is_ok());
+        assert!(matches!(
+            check_challenge_signature(
+                &force_any_val::<DlogProverInput>().public_image(),
+                &challenge,
+                &signature
+            ),
+            Err(TransferOracleTokenActionError::InvalidChallengeSignature)
+        ));
+    }
 }
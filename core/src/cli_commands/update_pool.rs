@@ -1,3 +1,5 @@
+pub mod multi_party;
+
 use ergo_lib::{
     chain::{
         ergo_box::box_builder::ErgoBoxCandidateBuilder,
@@ -8,7 +10,8 @@ use ergo_lib::{
     ergotree_interpreter::sigma_protocol::prover::ContextExtension,
     ergotree_ir::chain::{
         address::Address,
-        ergo_box::{ErgoBox, NonMandatoryRegisterId},
+        ergo_box::{box_value::BoxValue, ErgoBox, ErgoBoxCandidate, NonMandatoryRegisterId},
+        token::{Token, TokenId},
     },
     ergotree_ir::serialization::SigmaSerializable,
     wallet::{
@@ -19,22 +22,29 @@ use ergo_lib::{
 };
 use ergo_node_interface::node_interface::NodeError;
 use log::{error, info};
+use multi_party::PartialUpdateTx;
 use std::convert::TryInto;
+use std::path::Path;
 
 use crate::{
     box_kind::{
         make_pool_box_candidate_unchecked, BallotBox, CastBallotBoxVoteParameters, PoolBox,
         PoolBoxWrapper, VoteBallotBoxWrapper,
     },
+    cold_signing::ColdSigner,
+    context::OracleContext,
     contracts::pool::PoolContract,
-    explorer_api::ergo_explorer_transaction_link,
+    explorer_api::{
+        ballot_fallback::ExplorerFallbackBallotSource, ergo_explorer_transaction_link,
+        explorer_url::default_explorer_api_url, ExplorerApi,
+    },
     node_interface::{SignTransaction, SubmitTransaction},
-    oracle_config::BASE_FEE,
+    notifications::NotificationEvent,
     oracle_state::{
         DataSourceError, OraclePool, PoolBoxSource, UpdateBoxSource, VoteBallotBoxesSource,
     },
     oracle_types::BlockHeight,
-    pool_config::{PoolConfig, POOL_CONFIG},
+    pool_config::PoolConfig,
     spec_token::{RewardTokenId, SpecToken, TokenIdKind},
     wallet::{WalletDataError, WalletDataSource},
 };
@@ -79,6 +89,9 @@ pub fn update_pool(
     tx_submit: &dyn SubmitTransaction,
     new_reward_tokens: Option<SpecToken<RewardTokenId>>,
     height: BlockHeight,
+    ctx: &OracleContext,
+    cold_signer: Option<&ColdSigner>,
+    export_partial: Option<&Path>,
 ) -> Result<(), anyhow::Error> {
     info!("Opening pool_config_updated.yaml");
     let s = std::fs::read_to_string("pool_config_updated.yaml")?;
@@ -105,25 +118,67 @@ pub fn update_pool(
     );
 
     display_update_diff(
-        &POOL_CONFIG,
+        &ctx.pool_config,
         &new_pool_config,
         op.get_pool_box_source().get_pool_box()?,
         new_reward_tokens.clone(),
     );
 
+    // the ballot boxes the scan missed are read from the explorer
+    let explorer_api =
+        ExplorerApi::new(ctx.oracle_config.explorer_url.clone().unwrap_or_else(|| {
+            default_explorer_api_url(ctx.oracle_config.oracle_address.network())
+        }));
+    let ballot_boxes_source = ExplorerFallbackBallotSource {
+        scan: op.get_ballot_boxes_source(),
+        explorer: &explorer_api,
+        ballot_box_wrapper_inputs: &ctx.pool_config.ballot_box_wrapper_inputs,
+    };
+    // funded and signed by several parties with the multi-party workflow
+    if let Some(path) = export_partial {
+        let parts = build_update_tx_parts(
+            op.get_pool_box_source(),
+            &ballot_boxes_source,
+            op.get_update_box_source(),
+            new_pool_contract,
+            new_reward_tokens,
+            height,
+        )?;
+        let partial_tx = PartialUpdateTx::new(
+            parts,
+            new_pool_box_hash,
+            height,
+            ctx.oracle_config.update_fee(),
+        );
+        partial_tx.save(path)?;
+        println!(
+            "Partial update tx written to {}, the parties funding it merge their boxes with \
+             `update-pool --contribute {}`",
+            path.display(),
+            path.display()
+        );
+        println!("{}", partial_tx.missing());
+        return Ok(());
+    }
     let tx = build_update_pool_box_tx(
         op.get_pool_box_source(),
-        op.get_ballot_boxes_source(),
+        &ballot_boxes_source,
         wallet,
         op.get_update_box_source(),
         new_pool_contract,
         new_reward_tokens.clone(),
         height,
         change_address,
+        ctx.oracle_config.update_fee(),
     )?;
 
-    log::debug!("Signing update pool box tx: {:#?}", tx);
-    let signed_tx = tx_signer.sign_transaction(&tx.spending_tx)?;
+    // signed by the cold wallet after the confirmation
+    let signed_tx = if cold_signer.is_none() {
+        log::debug!("Signing update pool box tx: {:#?}", tx);
+        Some(tx_signer.sign_transaction(&tx.spending_tx)?)
+    } else {
+        None
+    };
 
     println!(
         "YOU WILL BE SUBMITTING AN UPDATE TO THE POOL CONTRACT:\
@@ -142,15 +197,25 @@ pub fn update_pool(
     let mut input = String::new();
     std::io::stdin().read_line(&mut input)?;
     if input.trim_end() == "YES" {
-        let tx_id_str = tx_submit.submit_transaction(&signed_tx)?;
-        crate::explorer_api::wait_for_tx_confirmation(signed_tx.id());
-        println!(
-            "Update pool box transaction submitted: view here, {}",
-            ergo_explorer_transaction_link(tx_id_str, network_prefix)
-        );
+        if let Some(cold_signer) = cold_signer {
+            cold_signer.export(&tx.spending_tx)?;
+        } else if let Some(signed_tx) = signed_tx {
+            let tx_id_str = tx_submit.submit_transaction(&signed_tx)?;
+            crate::explorer_api::wait_for_tx_confirmation(signed_tx.id(), &ctx.oracle_config);
+            ctx.notifier.notify(
+                "primary",
+                &NotificationEvent::PoolUpdated {
+                    tx_id: tx_id_str.to_string(),
+                },
+            );
+            println!(
+                "Update pool box transaction submitted: view here, {}",
+                ergo_explorer_transaction_link(tx_id_str, network_prefix, &ctx.oracle_config)
+            );
+        }
         println!("Send the new pool_config_updated.yaml to the oracle operators.");
         println!("The operators should import it with `import-pool-update` command.");
-        remind_send_minted_tokens_to_oracles(&POOL_CONFIG, &new_pool_config);
+        remind_send_minted_tokens_to_oracles(&ctx.pool_config, &new_pool_config);
     } else {
         println!("Aborting the transaction.")
     }
@@ -263,8 +328,70 @@ fn remind_send_minted_tokens_to_oracles(
     }
 }
 
+/// Inputs and outputs of the update tx guarded by the contracts, without the wallet boxes paying
+/// the fee (and holding the new reward tokens)
+#[derive(Debug, Clone)]
+pub struct UpdateTxParts {
+    /// The pool box, the update box and the spent ballot boxes, in this order
+    pub contract_inputs: Vec<ErgoBox>,
+    /// The new pool box, the update box and the ballot boxes, in this order
+    pub outputs: Vec<ErgoBoxCandidate>,
+    /// Tokens the wallet boxes must hold, the new reward tokens if they change
+    pub target_tokens: Vec<Token>,
+    /// Reward tokens of the old pool box, burnt if they change
+    pub burnt_tokens: Vec<Token>,
+    /// Wallet boxes holding ballot tokens can't be spent in the update
+    pub ballot_token_id: TokenId,
+}
+
+/// Context extension of an input of the update tx with `contract_input_count` contract inputs, the
+/// ballot boxes get the index of their output
+pub fn update_context_extension(
+    input_index: usize,
+    contract_input_count: usize,
+) -> ContextExtension {
+    if (2..contract_input_count).contains(&input_index) {
+        // first 2 outputs are pool and update box, ballot indexes start at 2
+        ContextExtension {
+            values: IntoIterator::into_iter([(0, (input_index as i32).into())]).collect(),
+        }
+    } else {
+        ContextExtension::empty()
+    }
+}
+
+/// Unspent wallet boxes that can be spent in the update
+pub fn usable_wallet_boxes(
+    wallet: &dyn WalletDataSource,
+    ballot_token_id: TokenId,
+) -> Result<Vec<ErgoBox>, UpdatePoolError> {
+    // Find unspent boxes without ballot token, see: https://github.com/ergoplatform/oracle-core/pull/80#issuecomment-1200258458
+    let unspent_boxes: Vec<ErgoBox> = wallet
+        .get_unspent_wallet_boxes()?
+        .into_iter()
+        .filter(|wallet_box| {
+            wallet_box
+                .tokens
+                .as_ref()
+                .and_then(|tokens| {
+                    tokens
+                        .iter()
+                        .find(|token| token.token_id == ballot_token_id)
+                })
+                .is_none()
+        })
+        .collect();
+    if unspent_boxes.is_empty() {
+        error!("Could not find unspent wallet boxes that do not contain ballot token. Please move ballot tokens to another address");
+        return Err(UpdatePoolError::NoUsableWalletBoxes);
+    }
+    Ok(unspent_boxes)
+}
+
+/// Update the pool box to `new_pool_contract` (and `new_reward_tokens`), spending the ballot boxes
+/// voting for it
 #[allow(clippy::too_many_arguments)]
-fn build_update_pool_box_tx(
+pub fn build_update_pool_box_tx(
     pool_box_source: &dyn PoolBoxSource,
     ballot_boxes: &dyn VoteBallotBoxesSource,
     wallet: &dyn WalletDataSource,
@@ -273,7 +400,66 @@ fn build_update_pool_box_tx(
     new_reward_tokens: Option<SpecToken<RewardTokenId>>,
     height: BlockHeight,
     change_address: Address,
+    tx_fee: BoxValue,
 ) -> Result<TransactionContext<UnsignedTransaction>, UpdatePoolError> {
+    let parts = build_update_tx_parts(
+        pool_box_source,
+        ballot_boxes,
+        update_box,
+        new_pool_contract,
+        new_reward_tokens,
+        height,
+    )?;
+    let unspent_boxes = usable_wallet_boxes(wallet, parts.ballot_token_id)?;
+
+    let target_balance = tx_fee;
+    let box_selector = SimpleBoxSelector::new();
+    let selection = box_selector.select(unspent_boxes, target_balance, &parts.target_tokens)?;
+    let mut input_boxes = parts.contract_inputs.clone();
+    input_boxes.extend_from_slice(selection.boxes.as_vec());
+    let box_selection = BoxSelection {
+        boxes: input_boxes.try_into().unwrap(),
+        change_boxes: selection.change_boxes,
+    };
+
+    let mut tx_builder = TxBuilder::new(
+        box_selection.clone(),
+        parts.outputs.clone(),
+        height.0,
+        tx_fee,
+        change_address,
+    );
+
+    if !parts.burnt_tokens.is_empty() {
+        tx_builder.set_token_burn_permit(parts.burnt_tokens.clone());
+    }
+
+    let contract_input_count = parts.contract_inputs.len();
+    for (i, input_box) in parts.contract_inputs.iter().enumerate().skip(2) {
+        tx_builder.set_context_extension(
+            input_box.box_id(),
+            update_context_extension(i, contract_input_count),
+        )
+    }
+    let unsigned_tx = tx_builder.build()?;
+    Ok(TransactionContext::new(
+        unsigned_tx,
+        box_selection.boxes.into(),
+        vec![],
+    )?)
+}
+
+/// The contract inputs and outputs of the update of the pool box to `new_pool_contract` (and
+/// `new_reward_tokens`), spending as many ballot boxes voting for it as needed to reach the min
+/// votes of the update box
+pub fn build_update_tx_parts(
+    pool_box_source: &dyn PoolBoxSource,
+    ballot_boxes: &dyn VoteBallotBoxesSource,
+    update_box: &dyn UpdateBoxSource,
+    new_pool_contract: PoolContract,
+    new_reward_tokens: Option<SpecToken<RewardTokenId>>,
+    height: BlockHeight,
+) -> Result<UpdateTxParts, UpdatePoolError> {
     let update_box = update_box.get_update_box()?;
     let min_votes = update_box.min_votes();
     let old_pool_box = pool_box_source.get_pool_box()?;
@@ -304,12 +490,16 @@ fn build_update_pool_box_tx(
     });
     sorted_ballot_boxes.reverse();
 
+    // Only spend as many ballot boxes as needed to reach min_votes (largest first), so the number
+    // of inputs does not grow with the number of oracles in the pool
     let mut votes_cast = 0;
-    let vote_ballot_boxes: Vec<VoteBallotBoxWrapper> = ballot_boxes
-        .get_ballot_boxes()?
+    let vote_ballot_boxes: Vec<VoteBallotBoxWrapper> = sorted_ballot_boxes
         .into_iter()
         .filter(|ballot_box| *ballot_box.vote_parameters() == vote_parameters)
         .scan(&mut votes_cast, |votes_cast, ballot_box| {
+            if **votes_cast >= min_votes as u64 {
+                return None;
+            }
             **votes_cast += *ballot_box.ballot_token().amount.as_u64();
             Some(ballot_box)
         })
@@ -336,48 +526,23 @@ fn build_update_pool_box_tx(
     update_box_candidate.add_token(update_box.update_nft());
     let update_box_candidate = update_box_candidate.build()?;
 
-    // Find unspent boxes without ballot token, see: https://github.com/ergoplatform/oracle-core/pull/80#issuecomment-1200258458
-    let unspent_boxes: Vec<ErgoBox> = wallet
-        .get_unspent_wallet_boxes()?
-        .into_iter()
-        .filter(|wallet_box| {
-            wallet_box
-                .tokens
-                .as_ref()
-                .and_then(|tokens| {
-                    tokens
-                        .iter()
-                        .find(|token| token.token_id == update_box.ballot_token_id())
-                })
-                .is_none()
-        })
-        .collect();
-    if unspent_boxes.is_empty() {
-        error!("Could not find unspent wallet boxes that do not contain ballot token. Please move ballot tokens to another address");
-        return Err(UpdatePoolError::NoUsableWalletBoxes);
-    }
-
-    let target_balance = *BASE_FEE;
-    let target_tokens =
-        if reward_tokens.token_id.token_id() != old_pool_box.reward_token().token_id() {
-            vec![reward_tokens.clone().into()]
-        } else {
-            vec![]
-        };
-    let box_selector = SimpleBoxSelector::new();
-    let selection = box_selector.select(unspent_boxes, target_balance, &target_tokens)?;
-    let mut input_boxes = vec![old_pool_box.get_box().clone(), update_box.get_box().clone()];
-    input_boxes.extend(
+    let reward_tokens_changed =
+        reward_tokens.token_id.token_id() != old_pool_box.reward_token().token_id();
+    let (target_tokens, burnt_tokens) = if reward_tokens_changed {
+        (
+            vec![reward_tokens.clone().into()],
+            vec![old_pool_box.reward_token().into()],
+        )
+    } else {
+        (vec![], vec![])
+    };
+    let mut contract_inputs = vec![old_pool_box.get_box().clone(), update_box.get_box().clone()];
+    contract_inputs.extend(
         vote_ballot_boxes
             .iter()
             .map(|ballot_box| ballot_box.get_box())
             .cloned(),
     );
-    input_boxes.extend_from_slice(selection.boxes.as_vec());
-    let box_selection = BoxSelection {
-        boxes: input_boxes.try_into().unwrap(),
-        change_boxes: selection.change_boxes,
-    };
 
     let mut outputs = vec![pool_box_candidate, update_box_candidate];
     for ballot_box in vote_ballot_boxes.iter() {
@@ -393,33 +558,13 @@ fn build_update_pool_box_tx(
         );
         outputs.push(ballot_box_candidate.build()?)
     }
-
-    let mut tx_builder = TxBuilder::new(
-        box_selection.clone(),
-        outputs.clone(),
-        height.0,
-        *BASE_FEE,
-        change_address,
-    );
-
-    if reward_tokens.token_id.token_id() != old_pool_box.reward_token().token_id() {
-        tx_builder.set_token_burn_permit(vec![old_pool_box.reward_token().into()]);
-    }
-
-    for (i, input_ballot) in vote_ballot_boxes.iter().enumerate() {
-        tx_builder.set_context_extension(
-            input_ballot.get_box().box_id(),
-            ContextExtension {
-                values: IntoIterator::into_iter([(0, ((i + 2) as i32).into())]).collect(), // first 2 outputs are pool and update box, ballot indexes start at 2
-            },
-        )
-    }
-    let unsigned_tx = tx_builder.build()?;
-    Ok(TransactionContext::new(
-        unsigned_tx,
-        box_selection.boxes.into(),
-        vec![],
-    )?)
+    Ok(UpdateTxParts {
+        contract_inputs,
+        outputs,
+        target_tokens,
+        burnt_tokens,
+        ballot_token_id: update_box.ballot_token_id(),
+    })
 }
 
 #[cfg(test)]
@@ -433,13 +578,13 @@ mod tests {
         ergotree_interpreter::sigma_protocol::private_input::DlogProverInput,
         ergotree_ir::{
             chain::{
-                address::AddressEncoder,
+                address::{AddressEncoder, NetworkAddress},
                 ergo_box::ErgoBox,
                 token::{Token, TokenId},
             },
             serialization::SigmaSerializable,
         },
-        wallet::Wallet,
+        wallet::{tx_builder::SUGGESTED_TX_FEE, Wallet},
     };
     use sigma_test_util::force_any_val;
     use std::convert::TryInto;
@@ -454,7 +599,6 @@ mod tests {
             pool::{PoolContract, PoolContractInputs},
             update::{UpdateContract, UpdateContractInputs, UpdateContractParameters},
         },
-        oracle_config::BASE_FEE,
         oracle_types::{BlockHeight, EpochCounter},
         pool_commands::test_utils::{
             generate_token_ids, make_wallet_unspent_box, BallotBoxesMock, PoolBoxMock,
@@ -475,8 +619,23 @@ mod tests {
         .current()
     }
 
-    #[test]
-    fn test_update_pool_box() {
+    pub(super) struct UpdateFixture {
+        pub ctx: ErgoStateContext,
+        pub height: BlockHeight,
+        pub pool_mock: PoolBoxMock,
+        pub ballot_boxes_mock: BallotBoxesMock,
+        pub update_mock: UpdateBoxMock,
+        /// Holds a box with the new reward tokens
+        pub wallet_mock: WalletDataMock,
+        pub wallet_secret: DlogProverInput,
+        pub new_pool_contract: PoolContract,
+        pub new_reward_tokens: SpecToken<RewardTokenId>,
+        pub change_address: NetworkAddress,
+    }
+
+    /// A pool box, an update box and 6 ballot boxes voting for a new pool contract and new reward
+    /// tokens
+    pub(super) fn update_fixture() -> UpdateFixture {
         let ctx = force_any_val::<ErgoStateContext>();
         let height = BlockHeight(ctx.pre_header.height);
 
@@ -509,7 +668,7 @@ mod tests {
         .unwrap();
         let update_contract = UpdateContract::checked_load(&update_contract_inputs).unwrap();
         let mut update_box_candidate =
-            ErgoBoxCandidateBuilder::new(*BASE_FEE, update_contract.ergo_tree(), height.0);
+            ErgoBoxCandidateBuilder::new(SUGGESTED_TX_FEE(), update_contract.ergo_tree(), height.0);
         update_box_candidate.add_token(Token {
             token_id: token_ids.update_nft_token_id.token_id(),
             amount: 1.try_into().unwrap(),
@@ -539,7 +698,7 @@ mod tests {
                 amount: 1.try_into().unwrap(),
             },
             reward_tokens.clone(),
-            *BASE_FEE,
+            SUGGESTED_TX_FEE(),
             height,
         )
         .unwrap();
@@ -604,7 +763,7 @@ mod tests {
         let wallet_unspent_box = make_wallet_unspent_box(
             // create a wallet box with new reward tokens
             secret.public_image(),
-            BASE_FEE.checked_mul_u32(4_000_000_000).unwrap(),
+            SUGGESTED_TX_FEE().checked_mul_u32(4_000_000_000).unwrap(),
             Some(vec![new_reward_tokens.clone().into()].try_into().unwrap()),
         );
         let change_address = AddressEncoder::unchecked_parse_network_address_from_str(
@@ -615,7 +774,6 @@ mod tests {
             unspent_boxes: vec![wallet_unspent_box],
             change_address: change_address.clone(),
         };
-        let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
         let update_mock = UpdateBoxMock {
             update_box: UpdateBoxWrapper::new(
                 update_box,
@@ -638,18 +796,39 @@ mod tests {
             .unwrap(),
         };
 
-        let update_tx = build_update_pool_box_tx(
-            &pool_mock,
-            &ballot_boxes_mock,
-            &wallet_mock,
-            &update_mock,
+        UpdateFixture {
+            ctx,
+            height,
+            pool_mock,
+            ballot_boxes_mock,
+            update_mock,
+            wallet_mock,
+            wallet_secret: secret,
             new_pool_contract,
-            Some(new_reward_tokens),
-            BlockHeight(height.0 + 1),
-            change_address.address(),
+            new_reward_tokens,
+            change_address,
+        }
+    }
+
+    #[test]
+    fn test_update_pool_box() {
+        let fixture = update_fixture();
+        let wallet = Wallet::from_secrets(vec![fixture.wallet_secret.clone().into()]);
+        let update_tx = build_update_pool_box_tx(
+            &fixture.pool_mock,
+            &fixture.ballot_boxes_mock,
+            &fixture.wallet_mock,
+            &fixture.update_mock,
+            fixture.new_pool_contract,
+            Some(fixture.new_reward_tokens),
+            BlockHeight(fixture.height.0 + 1),
+            fixture.change_address.address(),
+            SUGGESTED_TX_FEE(),
         )
         .unwrap();
 
-        wallet.sign_transaction(update_tx, &ctx, None).unwrap();
+        wallet
+            .sign_transaction(update_tx, &fixture.ctx, None)
+            .unwrap();
     }
 }
@@ -0,0 +1,539 @@
+//! Multi-party update tx (`update-pool --export-partial/--contribute/--finalize`), for pools where
+//! the boxes paying the fee or holding the new reward tokens belong to several parties, so
+//! executing a voted update doesn't need one operator to control everything.
+//!
+//! The coordinator writes the contract part of the update (the pool, update and vote ballot boxes
+//! and their outputs) to a JSON file, which is then passed from party to party. Each contributor
+//! merges boxes of its node wallet covering what is still missing of the fee and the new reward
+//! tokens into the file, the change going back to its change address. Once everything is covered,
+//! every party holding an input finalizes the file in turn: the first run fixes the unsigned tx,
+//! each run signs the inputs of the local wallet (and the contract inputs, which need no secret),
+//! and the run adding the last signature submits the tx.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::convert::TryInto;
+use std::fmt;
+use std::path::Path;
+
+use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilder;
+use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilderError;
+use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::Input;
+use ergo_lib::chain::transaction::Transaction;
+use ergo_lib::chain::transaction::UnsignedInput;
+use ergo_lib::ergo_chain_types::Digest32;
+use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+use ergo_lib::ergotree_ir::chain::address::Address;
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValueError;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBoxCandidate;
+use ergo_lib::ergotree_ir::chain::token::Token;
+use ergo_lib::ergotree_ir::chain::token::TokenAmount;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
+use ergo_lib::wallet::box_selector::BoxSelector;
+use ergo_lib::wallet::box_selector::BoxSelectorError;
+use ergo_lib::wallet::box_selector::SimpleBoxSelector;
+use ergo_lib::wallet::miner_fee::MINERS_FEE_ADDRESS;
+use ergo_lib::wallet::signing::TransactionContext;
+use ergo_lib::wallet::Wallet;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::context::OracleContext;
+use crate::explorer_api::ergo_explorer_transaction_link;
+use crate::node_interface::node_api::NodeApi;
+use crate::node_interface::SubmitTransaction;
+use crate::notifications::NotificationEvent;
+use crate::oracle_types::BlockHeight;
+use crate::wallet::WalletDataError;
+use crate::wallet::WalletDataSource;
+
+use super::update_context_extension;
+use super::usable_wallet_boxes;
+use super::UpdatePoolError;
+use super::UpdateTxParts;
+
+#[derive(Debug, Error)]
+pub enum MultiPartyError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serde error: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("{0}")]
+    UpdatePool(#[from] UpdatePoolError),
+    #[error("wallet data error: {0}")]
+    WalletData(#[from] WalletDataError),
+    #[error("box selector error: {0}")]
+    BoxSelector(#[from] BoxSelectorError),
+    #[error("box value error: {0}")]
+    BoxValue(#[from] BoxValueError),
+    #[error("box builder error: {0}")]
+    ErgoBoxCandidateBuilder(#[from] ErgoBoxCandidateBuilderError),
+    #[error("invalid change address {0}")]
+    InvalidChangeAddress(String),
+    #[error("tx error: {0}")]
+    Transaction(String),
+    #[error("signing error of input {index}: {error}")]
+    Signing { index: usize, error: String },
+    #[error("the partial update tx is finalized, contributions can't be added anymore")]
+    AlreadyFinalized,
+    #[error("the contributions already cover the fee and the reward tokens")]
+    NothingMissing,
+    #[error("the contributions don't cover the update yet, {0}")]
+    NotCovered(Missing),
+}
+
+/// Boxes of a party merged into the update
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contribution {
+    /// Change address (base58) of the contributor
+    pub change_address: String,
+    pub boxes: Vec<ErgoBox>,
+    /// Change of the boxes, sent to the change address
+    pub change: Vec<ErgoBoxCandidate>,
+    /// Part of the tx fee in nanoERG paid by the boxes
+    pub fee: u64,
+    /// Target tokens provided by the boxes
+    pub tokens: Vec<Token>,
+}
+
+/// Fee and target tokens not covered by the contributions yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Missing {
+    pub fee: u64,
+    pub tokens: Vec<(TokenId, u64)>,
+}
+
+impl Missing {
+    pub fn is_empty(&self) -> bool {
+        self.fee == 0 && self.tokens.is_empty()
+    }
+}
+
+impl fmt::Display for Missing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "nothing is missing");
+        }
+        write!(f, "missing {} nanoERG of the fee", self.fee)?;
+        for (token_id, amount) in &self.tokens {
+            write!(f, ", {} of token {}", amount, String::from(*token_id))?;
+        }
+        Ok(())
+    }
+}
+
+/// Secrets of the local wallet for the inputs it owns
+pub trait InputSecretSource {
+    /// The secret of the public key, `None` if the wallet doesn't hold it
+    fn get_secret(&self, public_key: &ProveDlog) -> Option<DlogProverInput>;
+}
+
+/// Secrets of the node wallet, from `/wallet/getPrivateKey`
+pub struct NodeWalletSecrets<'a> {
+    pub node_api: &'a NodeApi,
+    pub network: NetworkPrefix,
+}
+
+impl InputSecretSource for NodeWalletSecrets<'_> {
+    fn get_secret(&self, public_key: &ProveDlog) -> Option<DlogProverInput> {
+        let address = NetworkAddress::new(self.network, &Address::P2Pk(public_key.clone()));
+        let secret_bytes = self.node_api.get_private_key(&address).ok()?;
+        <[u8; 32]>::try_from(secret_bytes)
+            .ok()
+            .and_then(|bytes| DlogProverInput::from_bytes(&bytes))
+    }
+}
+
+/// The update tx being funded and signed by several parties, saved as JSON between the steps
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartialUpdateTx {
+    /// Base16 blake2b256 hash of the new pool box contract voted for
+    pub new_pool_box_address_hash: String,
+    /// Height the tx is built at
+    pub height: u32,
+    pub tx_fee: u64,
+    /// The pool box, the update box and the spent ballot boxes, in this order
+    pub contract_inputs: Vec<ErgoBox>,
+    /// The new pool box, the update box and the ballot boxes, in this order
+    pub outputs: Vec<ErgoBoxCandidate>,
+    /// Tokens the contributions must provide, the new reward tokens if they change
+    pub target_tokens: Vec<Token>,
+    /// Boxes holding ballot tokens can't be contributed
+    pub ballot_token_id: TokenId,
+    #[serde(default)]
+    pub contributions: Vec<Contribution>,
+    /// Set by the first finalize, the inputs and outputs are fixed from then on
+    #[serde(default)]
+    pub unsigned_tx: Option<UnsignedTransaction>,
+    /// Signed inputs by their index in the tx
+    #[serde(default)]
+    pub signed_inputs: BTreeMap<usize, Input>,
+}
+
+impl PartialUpdateTx {
+    pub fn new(
+        parts: UpdateTxParts,
+        new_pool_box_address_hash: Digest32,
+        height: BlockHeight,
+        tx_fee: BoxValue,
+    ) -> Self {
+        PartialUpdateTx {
+            new_pool_box_address_hash: String::from(new_pool_box_address_hash),
+            height: height.0,
+            tx_fee: *tx_fee.as_u64(),
+            contract_inputs: parts.contract_inputs,
+            outputs: parts.outputs,
+            target_tokens: parts.target_tokens,
+            ballot_token_id: parts.ballot_token_id,
+            contributions: Vec::new(),
+            unsigned_tx: None,
+            signed_inputs: BTreeMap::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, MultiPartyError> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MultiPartyError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Fee and target tokens the contributions don't cover yet
+    pub fn missing(&self) -> Missing {
+        let covered_fee: u64 = self.contributions.iter().map(|c| c.fee).sum();
+        let tokens = self
+            .target_tokens
+            .iter()
+            .filter_map(|target| {
+                let covered: u64 = self
+                    .contributions
+                    .iter()
+                    .flat_map(|c| c.tokens.iter())
+                    .filter(|t| t.token_id == target.token_id)
+                    .map(|t| *t.amount.as_u64())
+                    .sum();
+                let missing = target.amount.as_u64().saturating_sub(covered);
+                (missing > 0).then_some((target.token_id, missing))
+            })
+            .collect();
+        Missing {
+            fee: self.tx_fee.saturating_sub(covered_fee),
+            tokens,
+        }
+    }
+
+    /// Merge boxes of the wallet covering what is missing, with their change to the change
+    /// address of the wallet
+    pub fn contribute(&mut self, wallet: &dyn WalletDataSource) -> Result<(), MultiPartyError> {
+        if self.unsigned_tx.is_some() {
+            return Err(MultiPartyError::AlreadyFinalized);
+        }
+        let missing = self.missing();
+        if missing.is_empty() {
+            return Err(MultiPartyError::NothingMissing);
+        }
+        let spent_box_ids: Vec<_> = self
+            .contributions
+            .iter()
+            .flat_map(|c| c.boxes.iter())
+            .map(|b| b.box_id())
+            .collect();
+        let wallet_boxes: Vec<ErgoBox> = usable_wallet_boxes(wallet, self.ballot_token_id)?
+            .into_iter()
+            .filter(|b| !spent_box_ids.contains(&b.box_id()))
+            .collect();
+        let target_tokens = missing
+            .tokens
+            .iter()
+            .map(|(token_id, amount)| Ok(Token::from((*token_id, TokenAmount::try_from(*amount)?))))
+            .collect::<Result<Vec<Token>, MultiPartyError>>()?;
+        // a box value can't be zero, a contribution of tokens only adds 1 nanoERG to the fee
+        let target_balance = BoxValue::try_from(missing.fee.max(1))?;
+        let selection =
+            SimpleBoxSelector::new().select(wallet_boxes, target_balance, &target_tokens)?;
+        let change_address = wallet.get_change_address()?;
+        let change_ergo_tree = change_address
+            .address()
+            .script()
+            .map_err(|e| MultiPartyError::InvalidChangeAddress(e.to_string()))?;
+        let change = selection
+            .change_boxes
+            .iter()
+            .map(|change_box| {
+                let mut candidate = ErgoBoxCandidateBuilder::new(
+                    change_box.value,
+                    change_ergo_tree.clone(),
+                    self.height,
+                );
+                for token in change_box.tokens.iter().flat_map(|tokens| tokens.iter()) {
+                    candidate.add_token(token.clone());
+                }
+                Ok(candidate.build()?)
+            })
+            .collect::<Result<Vec<_>, MultiPartyError>>()?;
+        self.contributions.push(Contribution {
+            change_address: change_address.to_base58(),
+            boxes: selection.boxes.as_vec().clone(),
+            change,
+            fee: *target_balance.as_u64(),
+            tokens: target_tokens,
+        });
+        Ok(())
+    }
+
+    /// The contract inputs followed by the boxes of the contributions
+    pub fn input_boxes(&self) -> Vec<ErgoBox> {
+        self.contract_inputs
+            .iter()
+            .chain(self.contributions.iter().flat_map(|c| c.boxes.iter()))
+            .cloned()
+            .collect()
+    }
+
+    fn build_unsigned_tx(&self) -> Result<UnsignedTransaction, MultiPartyError> {
+        let contract_input_count = self.contract_inputs.len();
+        let inputs: Vec<UnsignedInput> = self
+            .input_boxes()
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                UnsignedInput::new(
+                    b.box_id(),
+                    update_context_extension(i, contract_input_count),
+                )
+            })
+            .collect();
+        let fee: u64 = self.contributions.iter().map(|c| c.fee).sum();
+        let fee_ergo_tree = MINERS_FEE_ADDRESS
+            .script()
+            .map_err(|e| MultiPartyError::Transaction(e.to_string()))?;
+        let fee_box =
+            ErgoBoxCandidateBuilder::new(BoxValue::try_from(fee)?, fee_ergo_tree, self.height)
+                .build()?;
+        let outputs: Vec<ErgoBoxCandidate> = self
+            .outputs
+            .iter()
+            .chain(self.contributions.iter().flat_map(|c| c.change.iter()))
+            .cloned()
+            .chain(std::iter::once(fee_box))
+            .collect();
+        UnsignedTransaction::new(
+            inputs
+                .try_into()
+                .map_err(|e| MultiPartyError::Transaction(format!("{:?}", e)))?,
+            None,
+            outputs
+                .try_into()
+                .map_err(|e| MultiPartyError::Transaction(format!("{:?}", e)))?,
+        )
+        .map_err(|e| MultiPartyError::Transaction(e.to_string()))
+    }
+
+    /// Sign the contract inputs and the inputs the wallet holds the secrets of. Fixes the unsigned
+    /// tx on the first run. Returns the signed tx once every input is signed.
+    pub fn finalize(
+        &mut self,
+        secrets: &dyn InputSecretSource,
+        state_context: &ErgoStateContext,
+    ) -> Result<Option<Transaction>, MultiPartyError> {
+        let unsigned_tx = match &self.unsigned_tx {
+            Some(unsigned_tx) => unsigned_tx.clone(),
+            None => {
+                let missing = self.missing();
+                if !missing.is_empty() {
+                    return Err(MultiPartyError::NotCovered(missing));
+                }
+                let unsigned_tx = self.build_unsigned_tx()?;
+                self.unsigned_tx = Some(unsigned_tx.clone());
+                unsigned_tx
+            }
+        };
+        let input_boxes = self.input_boxes();
+        let tx_context = TransactionContext::new(unsigned_tx.clone(), input_boxes.clone(), vec![])
+            .map_err(|e| MultiPartyError::Transaction(e.to_string()))?;
+        for (index, input_box) in input_boxes.iter().enumerate() {
+            if self.signed_inputs.contains_key(&index) {
+                continue;
+            }
+            // the contracts of the pool, update and ballot boxes need no secret in the update
+            let wallet_secrets = if index < self.contract_inputs.len() {
+                Vec::new()
+            } else {
+                match Address::recreate_from_ergo_tree(&input_box.ergo_tree) {
+                    Ok(Address::P2Pk(public_key)) => match secrets.get_secret(&public_key) {
+                        Some(secret) => vec![secret.into()],
+                        None => continue,
+                    },
+                    Ok(_) | Err(_) => continue,
+                }
+            };
+            let input = Wallet::from_secrets(wallet_secrets)
+                .sign_tx_input(index, tx_context.clone(), state_context, None)
+                .map_err(|e| MultiPartyError::Signing {
+                    index,
+                    error: e.to_string(),
+                })?;
+            self.signed_inputs.insert(index, input);
+        }
+        if self.signed_inputs.len() < input_boxes.len() {
+            return Ok(None);
+        }
+        let inputs: Vec<Input> = self.signed_inputs.values().cloned().collect();
+        let tx = Transaction::new(
+            inputs
+                .try_into()
+                .map_err(|e| MultiPartyError::Transaction(format!("{:?}", e)))?,
+            None,
+            unsigned_tx.output_candidates,
+        )
+        .map_err(|e| MultiPartyError::Transaction(e.to_string()))?;
+        Ok(Some(tx))
+    }
+}
+
+/// `update-pool --contribute`: merge boxes of the node wallet into the partial update tx file
+pub fn contribute_to_partial_update(
+    path: &Path,
+    wallet: &dyn WalletDataSource,
+) -> Result<(), MultiPartyError> {
+    let mut partial_tx = PartialUpdateTx::load(path)?;
+    println!(
+        "Contributing to the update to the pool box contract with hash {}, {}",
+        partial_tx.new_pool_box_address_hash,
+        partial_tx.missing()
+    );
+    partial_tx.contribute(wallet)?;
+    partial_tx.save(path)?;
+    let missing = partial_tx.missing();
+    if missing.is_empty() {
+        println!(
+            "The update is covered, the parties sign it with `update-pool --finalize {}`",
+            path.display()
+        );
+    } else {
+        println!("Contribution merged into {}, {}", path.display(), missing);
+    }
+    Ok(())
+}
+
+/// `update-pool --finalize`: sign the inputs of the node wallet in the partial update tx file and
+/// submit the tx once every input is signed
+pub fn finalize_partial_update(
+    path: &Path,
+    node_api: &NodeApi,
+    tx_submit: &dyn SubmitTransaction,
+    ctx: &OracleContext,
+) -> Result<(), anyhow::Error> {
+    let mut partial_tx = PartialUpdateTx::load(path)?;
+    let network = ctx.oracle_config.oracle_address.network();
+    let secrets = NodeWalletSecrets { node_api, network };
+    let signed_tx = partial_tx.finalize(&secrets, &node_api.get_state_context()?)?;
+    partial_tx.save(path)?;
+    match signed_tx {
+        Some(signed_tx) => {
+            let tx_id = tx_submit.submit_transaction(&signed_tx)?;
+            crate::explorer_api::wait_for_tx_confirmation(signed_tx.id(), &ctx.oracle_config);
+            ctx.notifier.notify(
+                "primary",
+                &NotificationEvent::PoolUpdated {
+                    tx_id: tx_id.to_string(),
+                },
+            );
+            println!(
+                "Update pool box transaction submitted: view here, {}",
+                ergo_explorer_transaction_link(tx_id, network, &ctx.oracle_config)
+            );
+        }
+        None => println!(
+            "Signed {} of the {} inputs, pass {} to the parties holding the other inputs",
+            partial_tx.signed_inputs.len(),
+            partial_tx.input_boxes().len(),
+            path.display()
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::wallet::tx_builder::SUGGESTED_TX_FEE;
+    use sigma_test_util::force_any_val;
+
+    use super::super::build_update_tx_parts;
+    use super::super::tests::update_fixture;
+    use super::*;
+
+    struct SecretsMock(Vec<DlogProverInput>);
+
+    impl InputSecretSource for SecretsMock {
+        fn get_secret(&self, public_key: &ProveDlog) -> Option<DlogProverInput> {
+            self.0
+                .iter()
+                .find(|secret| &secret.public_image() == public_key)
+                .cloned()
+        }
+    }
+
+    #[test]
+    fn test_multi_party_update() {
+        let fixture = update_fixture();
+        let height = BlockHeight(fixture.height.0 + 1);
+        let parts = build_update_tx_parts(
+            &fixture.pool_mock,
+            &fixture.ballot_boxes_mock,
+            &fixture.update_mock,
+            fixture.new_pool_contract,
+            Some(fixture.new_reward_tokens),
+            height,
+        )
+        .unwrap();
+        let partial_tx = PartialUpdateTx::new(
+            parts,
+            force_any_val::<Digest32>(),
+            height,
+            SUGGESTED_TX_FEE(),
+        );
+        // passed between the parties as JSON
+        let mut partial_tx: PartialUpdateTx =
+            serde_json::from_str(&serde_json::to_string(&partial_tx).unwrap()).unwrap();
+        let missing = partial_tx.missing();
+        assert_eq!(missing.fee, *SUGGESTED_TX_FEE().as_u64());
+        assert_eq!(missing.tokens.len(), 1);
+        assert!(matches!(
+            partial_tx.finalize(&SecretsMock(vec![]), &fixture.ctx),
+            Err(MultiPartyError::NotCovered(_))
+        ));
+
+        partial_tx.contribute(&fixture.wallet_mock).unwrap();
+        assert!(partial_tx.missing().is_empty());
+        assert!(matches!(
+            partial_tx.contribute(&fixture.wallet_mock),
+            Err(MultiPartyError::NothingMissing)
+        ));
+
+        // a party without the key of the contributed box only signs the contract inputs
+        assert!(partial_tx
+            .finalize(&SecretsMock(vec![]), &fixture.ctx)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            partial_tx.signed_inputs.len(),
+            partial_tx.contract_inputs.len()
+        );
+        let tx = partial_tx
+            .finalize(&SecretsMock(vec![fixture.wallet_secret]), &fixture.ctx)
+            .unwrap()
+            .unwrap();
+        assert_eq!(tx.inputs.len(), partial_tx.contract_inputs.len() + 1);
+    }
+}
@@ -7,7 +7,7 @@ use ergo_lib::{
     },
     ergo_chain_types::{Digest32, DigestNError},
     ergotree_interpreter::sigma_protocol::prover::ContextExtension,
-    ergotree_ir::chain::address::Address,
+    ergotree_ir::chain::{address::Address, ergo_box::box_value::BoxValue},
     wallet::{
         box_selector::{BoxSelection, BoxSelector, BoxSelectorError, SimpleBoxSelector},
         tx_builder::{TxBuilder, TxBuilderError},
@@ -17,15 +17,17 @@ use ergo_node_interface::node_interface::NodeError;
 
 use crate::{
     box_kind::{make_local_ballot_box_candidate, BallotBox, BallotBoxWrapper},
+    cold_signing::ColdSigner,
+    context::OracleContext,
     contracts::ballot::{
         BallotContract, BallotContractError, BallotContractInputs, BallotContractParameters,
     },
     explorer_api::ergo_explorer_transaction_link,
     node_interface::{SignTransaction, SubmitTransaction},
-    oracle_config::{BASE_FEE, ORACLE_CONFIG},
-    oracle_state::{DataSourceError, LocalBallotBoxSource},
+    notifications::NotificationEvent,
+    oracle_state::{DataSourceError, LocalBallotBoxSource, UpdateBoxSource},
     oracle_types::BlockHeight,
-    pool_config::{TokenIds, POOL_CONFIG},
+    pool_config::TokenIds,
     spec_token::{RewardTokenId, SpecToken, TokenIdKind},
     wallet::{WalletDataError, WalletDataSource},
 };
@@ -63,11 +65,23 @@ pub fn vote_update_pool(
     tx_signer: &dyn SignTransaction,
     tx_submit: &dyn SubmitTransaction,
     local_ballot_box_source: &dyn LocalBallotBoxSource,
+    update_box_source: &dyn UpdateBoxSource,
     new_pool_box_address_hash_str: String,
     reward_token_opt: Option<SpecToken<RewardTokenId>>,
-    update_box_creation_height: BlockHeight,
+    update_box_creation_height: Option<BlockHeight>,
     height: BlockHeight,
+    ctx: &OracleContext,
+    cold_signer: Option<&ColdSigner>,
 ) -> Result<(), anyhow::Error> {
+    let update_box_creation_height = match update_box_creation_height {
+        Some(update_box_creation_height) => update_box_creation_height,
+        None => BlockHeight(
+            update_box_source
+                .get_update_box()?
+                .get_box()
+                .creation_height,
+        ),
+    };
     let change_network_address = wallet.get_change_address()?;
     let network_prefix = change_network_address.network();
     let new_pool_box_address_hash = Digest32::try_from(new_pool_box_address_hash_str)?;
@@ -82,6 +96,7 @@ pub fn vote_update_pool(
             update_box_creation_height,
             height,
             change_network_address.address(),
+            ctx.oracle_config.base_fee(),
         )?
     } else {
         // Ballot token is assumed to be in some unspent box of the node's wallet.
@@ -90,20 +105,23 @@ pub fn vote_update_pool(
             new_pool_box_address_hash,
             reward_token_opt.clone(),
             update_box_creation_height,
-            ORACLE_CONFIG.oracle_address.address(),
-            POOL_CONFIG
+            ctx.oracle_config.oracle_address.address(),
+            ctx.pool_config
                 .ballot_box_wrapper_inputs
                 .contract_inputs
                 .contract_parameters(),
-            &POOL_CONFIG.token_ids,
+            &ctx.pool_config.token_ids,
             height,
             change_network_address.address(),
+            ctx.oracle_config.base_fee(),
         )?
     };
     println!(
         "YOU WILL BE CASTING A VOTE FOR THE FOLLOWING ITEMS:\
-           - Hash of new pool box contract: {}",
+           - Hash of new pool box contract: {}\
+           - Update box creation height: {}",
         String::from(new_pool_box_address_hash),
+        update_box_creation_height.0,
     );
     if let Some(reward_token) = reward_token_opt {
         println!(
@@ -117,12 +135,22 @@ pub fn vote_update_pool(
     let mut input = String::new();
     std::io::stdin().read_line(&mut input)?;
     if input.trim_end() == "YES" {
+        if let Some(cold_signer) = cold_signer {
+            cold_signer.export(&unsigned_tx)?;
+            return Ok(());
+        }
         let signed_tx = tx_signer.sign_transaction(&unsigned_tx)?;
         let tx_id_str = tx_submit.submit_transaction(&signed_tx)?;
-        crate::explorer_api::wait_for_tx_confirmation(signed_tx.id());
+        crate::explorer_api::wait_for_tx_confirmation(signed_tx.id(), &ctx.oracle_config);
+        ctx.notifier.notify(
+            "primary",
+            &NotificationEvent::VoteCast {
+                tx_id: tx_id_str.to_string(),
+            },
+        );
         println!(
             "Transaction made. Check status here: {}",
-            ergo_explorer_transaction_link(tx_id_str, network_prefix)
+            ergo_explorer_transaction_link(tx_id_str, network_prefix, &ctx.oracle_config)
         );
     } else {
         println!("Aborting the transaction.")
@@ -130,8 +158,9 @@ pub fn vote_update_pool(
     Ok(())
 }
 
+/// Vote with the existing ballot box `in_ballot_box`
 #[allow(clippy::too_many_arguments)]
-fn build_tx_with_existing_ballot_box(
+pub fn build_tx_with_existing_ballot_box(
     in_ballot_box: BallotBoxWrapper,
     wallet: &dyn WalletDataSource,
     new_pool_box_address_hash: Digest32,
@@ -139,6 +168,7 @@ fn build_tx_with_existing_ballot_box(
     update_box_creation_height: BlockHeight,
     height: BlockHeight,
     change_address: Address,
+    tx_fee: BoxValue,
 ) -> Result<UnsignedTransaction, VoteUpdatePoolError> {
     let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
     let ballot_box_candidate = make_local_ballot_box_candidate(
@@ -152,7 +182,7 @@ fn build_tx_with_existing_ballot_box(
         height,
     )?;
     let box_selector = SimpleBoxSelector::new();
-    let selection = box_selector.select(unspent_boxes, *BASE_FEE, &[])?;
+    let selection = box_selector.select(unspent_boxes, tx_fee, &[])?;
     let mut input_boxes = vec![in_ballot_box.get_box().clone()];
     input_boxes.append(selection.boxes.as_vec().clone().as_mut());
     let box_selection = BoxSelection {
@@ -163,7 +193,7 @@ fn build_tx_with_existing_ballot_box(
         box_selection,
         vec![ballot_box_candidate],
         height.0,
-        *BASE_FEE,
+        tx_fee,
         change_address,
     );
     // The following context value ensures that `outIndex` in the ballot contract is properly set.
@@ -175,8 +205,9 @@ fn build_tx_with_existing_ballot_box(
     Ok(tx)
 }
 
+/// Vote by creating the ballot box from the ballot token in the wallet
 #[allow(clippy::too_many_arguments)]
-fn build_tx_for_first_ballot_box(
+pub fn build_tx_for_first_ballot_box(
     wallet: &dyn WalletDataSource,
     new_pool_box_address_hash: Digest32,
     reward_token_opt: Option<SpecToken<RewardTokenId>>,
@@ -186,10 +217,11 @@ fn build_tx_for_first_ballot_box(
     token_ids: &TokenIds,
     height: BlockHeight,
     change_address: Address,
+    tx_fee: BoxValue,
 ) -> Result<UnsignedTransaction, VoteUpdatePoolError> {
     let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
     let out_ballot_box_value = ballot_contract_parameters.min_storage_rent();
-    let inputs = BallotContractInputs::build_with(
+    let inputs = BallotContractInputs::checked_load(
         ballot_contract_parameters.clone(),
         token_ids.update_nft_token_id.clone(),
     )?;
@@ -210,7 +242,7 @@ fn build_tx_for_first_ballot_box(
             height,
         )?;
         let box_selector = SimpleBoxSelector::new();
-        let selection_target_balance = out_ballot_box_value.checked_add(&BASE_FEE).unwrap();
+        let selection_target_balance = out_ballot_box_value.checked_add(&tx_fee).unwrap();
         let selection = box_selector.select(
             unspent_boxes,
             selection_target_balance,
@@ -224,7 +256,7 @@ fn build_tx_for_first_ballot_box(
             box_selection,
             vec![ballot_box_candidate],
             height.0,
-            *BASE_FEE,
+            tx_fee,
             change_address,
         );
         // The following context value ensures that `outIndex` in the ballot contract is properly set.
@@ -252,14 +284,13 @@ mod tests {
             ergo_box::{box_value::BoxValue, BoxTokens, ErgoBox},
             token::{Token, TokenId},
         },
-        wallet::{signing::TransactionContext, Wallet},
+        wallet::{signing::TransactionContext, tx_builder::SUGGESTED_TX_FEE, Wallet},
     };
     use sigma_test_util::force_any_val;
 
     use crate::{
         box_kind::{make_local_ballot_box_candidate, BallotBoxWrapper, BallotBoxWrapperInputs},
         contracts::ballot::{BallotContract, BallotContractInputs, BallotContractParameters},
-        oracle_config::BASE_FEE,
         oracle_types::{BlockHeight, EpochLength},
         pool_commands::test_utils::{
             find_input_boxes, generate_token_ids, make_wallet_unspent_box, WalletDataMock,
@@ -296,7 +327,7 @@ mod tests {
         };
         let wallet_unspent_box = make_wallet_unspent_box(
             secret.public_image(),
-            BASE_FEE.checked_mul_u32(100_000_000).unwrap(),
+            SUGGESTED_TX_FEE().checked_mul_u32(100_000_000).unwrap(),
             Some(BoxTokens::from_vec(vec![ballot_token]).unwrap()),
         );
         let wallet_mock = WalletDataMock {
@@ -318,6 +349,7 @@ mod tests {
             &token_ids,
             height,
             change_address.address(),
+            SUGGESTED_TX_FEE(),
         )
         .unwrap();
 
@@ -380,7 +412,7 @@ mod tests {
         let ballot_box = BallotBoxWrapper::new(in_ballot_box.clone(), &inputs).unwrap();
         let wallet_unspent_box = make_wallet_unspent_box(
             secret.public_image(),
-            BASE_FEE.checked_mul_u32(100_000_000).unwrap(),
+            SUGGESTED_TX_FEE().checked_mul_u32(100_000_000).unwrap(),
             None,
         );
         let wallet_mock = WalletDataMock {
@@ -398,6 +430,7 @@ mod tests {
             height - EpochLength(3),
             height,
             change_address.address(),
+            SUGGESTED_TX_FEE(),
         )
         .unwrap();
 
@@ -0,0 +1,240 @@
+//! Export of the txs of the CLI commands as EIP-19 cold signing requests, for signing them with
+//! a wallet holding the oracle key offline (Ergo mobile wallet, SATERGO) instead of the node.
+//!
+//! The unsigned tx is reduced against the current state context of the node and written as the
+//! JSON request `{"reducedTx", "sender", "inputs"}`, or as the chunks of it to show as a sequence
+//! of QR codes, one `{"CSR": <part>, "p": <page>, "n": <pages>}` per line. The wallet submits the
+//! signed tx itself.
+
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+use ergo_lib::chain::transaction::reduced::reduce_tx;
+use ergo_lib::chain::transaction::reduced::ReducedTransaction;
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
+use ergo_lib::wallet::signing::TransactionContext;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::node_interface::node_api::NodeApiError;
+
+/// Prefix of the QR code chunks of a cold signing request
+pub const CHUNK_PREFIX: &str = "CSR";
+
+/// Length of the request JSON in a chunk, readable in a QR code by a phone camera
+pub const DEFAULT_CHUNK_SIZE: usize = 1000;
+
+#[derive(Debug, Error)]
+pub enum ColdSigningError {
+    #[error("node api error: {0}")]
+    NodeApi(#[from] NodeApiError),
+    #[error("tx context error: {0}")]
+    TxContext(String),
+    #[error("tx reduction error: {0}")]
+    Reduce(String),
+    #[error("sigma serialization error: {0}")]
+    SigmaSerialization(#[from] SigmaSerializationError),
+    #[error("state context error: {0}")]
+    StateContext(String),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid cold signing format {0}, expected `json` or `chunks`")]
+    InvalidFormat(String),
+}
+
+/// File format of the exported request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColdSigningFormat {
+    /// The request JSON
+    #[default]
+    Json,
+    /// One QR code chunk of the request per line
+    Chunks,
+}
+
+impl FromStr for ColdSigningFormat {
+    type Err = ColdSigningError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ColdSigningFormat::Json),
+            "chunks" => Ok(ColdSigningFormat::Chunks),
+            _ => Err(ColdSigningError::InvalidFormat(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ColdSigningFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColdSigningFormat::Json => write!(f, "json"),
+            ColdSigningFormat::Chunks => write!(f, "chunks"),
+        }
+    }
+}
+
+/// EIP-19 cold signing request, the reduced tx and the input boxes in base64
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColdSigningRequest {
+    pub reduced_tx: String,
+    /// Address of the wallet expected to sign
+    pub sender: String,
+    pub inputs: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ColdSigningChunk<'a> {
+    #[serde(rename = "CSR")]
+    part: &'a str,
+    p: usize,
+    n: usize,
+}
+
+impl ColdSigningRequest {
+    pub fn new(
+        reduced_tx: &ReducedTransaction,
+        sender: &NetworkAddress,
+        inputs: &[ErgoBox],
+    ) -> Result<Self, ColdSigningError> {
+        Ok(ColdSigningRequest {
+            reduced_tx: base64::encode(reduced_tx.sigma_serialize_bytes()?),
+            sender: sender.to_base58(),
+            inputs: inputs
+                .iter()
+                .map(|b| Ok(base64::encode(b.sigma_serialize_bytes()?)))
+                .collect::<Result<_, SigmaSerializationError>>()?,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// The request JSON split in parts of at most `chunk_size` chars, each in a
+    /// `{"CSR": <part>, "p": <page>, "n": <pages>}` JSON to show as a QR code
+    pub fn chunks(&self, chunk_size: usize) -> Vec<String> {
+        // base64, addresses and JSON punctuation are all ASCII
+        let json = self.to_json();
+        let parts: Vec<&str> = json
+            .as_bytes()
+            .chunks(chunk_size.max(1))
+            .map(|part| std::str::from_utf8(part).unwrap())
+            .collect();
+        parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| {
+                serde_json::to_string(&ColdSigningChunk {
+                    part,
+                    p: i + 1,
+                    n: parts.len(),
+                })
+                .unwrap()
+            })
+            .collect()
+    }
+
+    /// Write the request to the file in the format
+    pub fn write(&self, path: &Path, format: ColdSigningFormat) -> Result<(), ColdSigningError> {
+        let contents = match format {
+            ColdSigningFormat::Json => self.to_json(),
+            ColdSigningFormat::Chunks => self.chunks(DEFAULT_CHUNK_SIZE).join("\n"),
+        };
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+/// Reduces a tx with the boxes it spends and the state context of the chain
+pub trait ReduceTransaction {
+    /// The reduced tx along with its input boxes
+    fn reduce_transaction(
+        &self,
+        unsigned_tx: &UnsignedTransaction,
+    ) -> Result<(ReducedTransaction, Vec<ErgoBox>), ColdSigningError>;
+}
+
+pub fn reduce_with_boxes(
+    unsigned_tx: UnsignedTransaction,
+    inputs: Vec<ErgoBox>,
+    data_boxes: Vec<ErgoBox>,
+    state_context: &ErgoStateContext,
+) -> Result<ReducedTransaction, ColdSigningError> {
+    let tx_context = TransactionContext::new(unsigned_tx, inputs, data_boxes)
+        .map_err(|e| ColdSigningError::TxContext(e.to_string()))?;
+    reduce_tx(tx_context, state_context).map_err(|e| ColdSigningError::Reduce(e.to_string()))
+}
+
+/// Writes the txs of a command to a file as cold signing requests instead of signing them
+pub struct ColdSigner<'a> {
+    pub reducer: &'a dyn ReduceTransaction,
+    pub path: PathBuf,
+    pub format: ColdSigningFormat,
+    pub sender: NetworkAddress,
+}
+
+impl ColdSigner<'_> {
+    pub fn export(&self, unsigned_tx: &UnsignedTransaction) -> Result<(), ColdSigningError> {
+        let (reduced_tx, inputs) = self.reducer.reduce_transaction(unsigned_tx)?;
+        let request = ColdSigningRequest::new(&reduced_tx, &self.sender, &inputs)?;
+        request.write(&self.path, self.format)?;
+        println!(
+            "Cold signing request written to {}, sign and submit it with a wallet holding the key of {}",
+            self.path.display(),
+            request.sender
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> ColdSigningRequest {
+        ColdSigningRequest {
+            reduced_tx: "a".repeat(2500),
+            sender: "9hEQHEMyY1K1vs79vJXFtNjr2dbQbtWXF99oVWGJ5c4xbcLdBsw".to_string(),
+            inputs: vec!["b".repeat(100)],
+        }
+    }
+
+    #[test]
+    fn test_request_json() {
+        let json: serde_json::Value = serde_json::from_str(&request().to_json()).unwrap();
+        assert_eq!(json["reducedTx"].as_str().unwrap().len(), 2500);
+        assert_eq!(
+            json["sender"],
+            "9hEQHEMyY1K1vs79vJXFtNjr2dbQbtWXF99oVWGJ5c4xbcLdBsw"
+        );
+        assert_eq!(json["inputs"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_request_chunks() {
+        let request = request();
+        let chunks = request.chunks(DEFAULT_CHUNK_SIZE);
+        assert_eq!(chunks.len(), 3);
+        let mut joined = String::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let chunk: serde_json::Value = serde_json::from_str(chunk).unwrap();
+            assert_eq!(chunk["p"], i + 1);
+            assert_eq!(chunk["n"], 3);
+            joined.push_str(chunk[CHUNK_PREFIX].as_str().unwrap());
+        }
+        assert_eq!(joined, request.to_json());
+        assert_eq!(
+            "chunks".parse::<ColdSigningFormat>().unwrap(),
+            ColdSigningFormat::Chunks
+        );
+        assert!("qr".parse::<ColdSigningFormat>().is_err());
+    }
+}
@@ -0,0 +1,50 @@
+//! Configs passed to the pool state, the actions and the commands, instead of being read from
+//! globals, so one process can drive several pools and the configs can be replaced in tests
+
+use std::sync::Arc;
+
+use crate::node_interface::node_api::NodeApi;
+use crate::notifications::Notifier;
+use crate::oracle_config::OracleConfig;
+use crate::pool_config::PoolConfig;
+use crate::scans::ScanNode;
+
+#[derive(Debug, Clone)]
+pub struct OracleContext {
+    pub oracle_config: OracleConfig,
+    pub pool_config: PoolConfig,
+    /// Alerts of the `notifications` section of the oracle config, shared by the clones so an
+    /// alert is not repeated within `repeat_after_secs`
+    pub notifier: Arc<Notifier>,
+}
+
+impl OracleContext {
+    pub fn new(oracle_config: OracleConfig, pool_config: PoolConfig) -> Self {
+        let notifier = Arc::new(Notifier::from_oracle_config(&oracle_config));
+        OracleContext {
+            oracle_config,
+            pool_config,
+            notifier,
+        }
+    }
+
+    /// Send the alerts with the given notifier, e.g. the one of the primary oracle config
+    pub fn with_notifier(self, notifier: Arc<Notifier>) -> Self {
+        OracleContext { notifier, ..self }
+    }
+
+    /// Load the configs from `ORACLE_CONFIG_FILE_PATH` and `POOL_CONFIG_FILE_PATH`
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let oracle_config = OracleConfig::load()?;
+        let pool_config = PoolConfig::load()?;
+        Ok(OracleContext::new(oracle_config, pool_config))
+    }
+
+    pub fn node_api(&self) -> NodeApi {
+        self.scan_node().node_api()
+    }
+
+    pub fn scan_node(&self) -> ScanNode {
+        ScanNode::from(&self.oracle_config)
+    }
+}
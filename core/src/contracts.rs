@@ -1,4 +1,8 @@
 pub mod ballot;
+pub mod cache;
+pub mod constants;
+pub mod ergo_script;
+pub mod known_templates;
 pub mod oracle;
 pub mod pool;
 pub mod refresh;
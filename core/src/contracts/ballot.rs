@@ -20,15 +20,20 @@ use crate::spec_token::UpdateTokenId;
 pub struct BallotContract {
     ergo_tree: ErgoTree,
     min_storage_rent_index: usize,
+    min_storage_rent: BoxValue,
     update_nft_index: usize,
+    update_nft_token_id: TokenId,
 }
 
 #[derive(Debug, Error)]
 pub enum BallotContractError {
     #[error("ballot contract: parameter error: {0}")]
     ParametersError(#[from] BallotContractParametersError),
-    #[error("ballot contract: unknown update NFT defined in constant")]
-    UnknownUpdateNftId,
+    #[error("ballot contract: expected update NFT {expected:?}, got {got:?} defined in constant")]
+    UnknownUpdateNftId {
+        expected: UpdateTokenId,
+        got: TokenId,
+    },
     #[error("ballot contract: sigma parsing error {0}")]
     SigmaParsing(#[from] SigmaParsingError),
     #[error("ballot contract: ergo tree error {0:?}")]
@@ -82,15 +87,23 @@ impl BallotContractInputs {
 
 impl BallotContract {
     pub fn checked_load(inputs: &BallotContractInputs) -> Result<Self, BallotContractError> {
+        let checked_contract_parameters = BallotContractParameters::checked_load(
+            inputs.contract_parameters.ergo_tree_bytes(),
+            inputs.contract_parameters.min_storage_rent,
+            inputs.contract_parameters.min_storage_rent_index,
+            inputs.contract_parameters.update_nft_index,
+        )?;
         let ergo_tree =
-            ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?;
+            ErgoTree::sigma_parse_bytes(checked_contract_parameters.ergo_tree_bytes.as_slice())?;
         let contract = Self::from_ergo_tree(ergo_tree, inputs).map_err(|e| {
-            let expected_base16 = Self::build_with(inputs)
-                .unwrap()
-                .ergo_tree
-                .to_base16_bytes()
-                .unwrap();
-            BallotContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
+            // the expected tree is only a hint, keep the original error if it can't be built
+            if let Ok(Ok(expected_base16)) =
+                Self::build_with(inputs).map(|c| c.ergo_tree.to_base16_bytes())
+            {
+                BallotContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
+            } else {
+                e
+            }
         })?;
         Ok(contract)
     }
@@ -150,33 +163,34 @@ impl BallotContract {
             ))?
             .try_extract_into::<TokenId>()?;
         if token_id != inputs.update_nft_token_id.token_id() {
-            return Err(BallotContractError::UnknownUpdateNftId);
+            return Err(BallotContractError::UnknownUpdateNftId {
+                expected: inputs.update_nft_token_id.clone(),
+                got: token_id,
+            });
         }
         Ok(Self {
             ergo_tree,
             min_storage_rent_index: parameters.min_storage_rent_index,
+            min_storage_rent,
             update_nft_index: parameters.update_nft_index,
+            update_nft_token_id: token_id,
         })
     }
 
     pub fn min_storage_rent(&self) -> BoxValue {
-        self.ergo_tree
-            .get_constant(self.min_storage_rent_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<i64>()
-            .unwrap()
-            .try_into()
-            .unwrap()
+        self.min_storage_rent
     }
 
     pub fn update_nft_token_id(&self) -> TokenId {
-        self.ergo_tree
-            .get_constant(self.update_nft_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<TokenId>()
-            .unwrap()
+        self.update_nft_token_id
+    }
+
+    pub fn min_storage_rent_index(&self) -> usize {
+        self.min_storage_rent_index
+    }
+
+    pub fn update_nft_index(&self) -> usize {
+        self.update_nft_index
     }
 
     pub fn ergo_tree(&self) -> ErgoTree {
@@ -347,4 +361,46 @@ mod tests {
         );
         assert_eq!(new_contract.min_storage_rent(), new_min_storage_rent);
     }
+
+    #[test]
+    fn test_checked_load() {
+        let update_nft_token_id =
+            UpdateTokenId::from_token_id_unchecked(force_any_val::<TokenId>());
+        let contract_inputs = BallotContractInputs::build_with(
+            BallotContractParameters::default(),
+            update_nft_token_id.clone(),
+        )
+        .unwrap();
+        let contract = BallotContract::checked_load(&contract_inputs).unwrap();
+        assert_eq!(
+            contract.update_nft_token_id(),
+            update_nft_token_id.token_id()
+        );
+        assert_eq!(
+            contract.min_storage_rent(),
+            contract_inputs.contract_parameters().min_storage_rent()
+        );
+
+        let other_update_nft_token_id =
+            UpdateTokenId::from_token_id_unchecked(force_any_val::<TokenId>());
+        assert!(matches!(
+            BallotContractInputs::checked_load(
+                contract_inputs.contract_parameters().clone(),
+                other_update_nft_token_id,
+            ),
+            Err(BallotContractError::WrappedWithExpectedP2SAddress(_, _))
+        ));
+
+        let mut wrong_min_storage_rent = contract_inputs.contract_parameters().clone();
+        wrong_min_storage_rent.min_storage_rent =
+            (*wrong_min_storage_rent.min_storage_rent.as_u64() + 1)
+                .try_into()
+                .unwrap();
+        assert!(matches!(
+            BallotContractInputs::checked_load(wrong_min_storage_rent, update_nft_token_id),
+            Err(BallotContractError::ParametersError(
+                BallotContractParametersError::MinStorageRentDiffers { .. }
+            ))
+        ));
+    }
 }
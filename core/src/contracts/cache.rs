@@ -0,0 +1,100 @@
+//! Process-wide memo of the pool and oracle contracts, keyed by the ergo tree bytes and the
+//! parameters they are loaded with. The main loop loads the same contracts from the config and
+//! from the scanned boxes every iteration, the memo saves re-parsing the trees and extracting
+//! their constants each time. Only successfully loaded contracts are kept.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+
+/// Ergo tree bytes and parameters a contract is loaded from
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ContractKey(Vec<u8>);
+
+impl ContractKey {
+    /// `kind` tells apart the contracts built differently from the same tree, e.g. loaded as is or
+    /// with the token ids substituted
+    pub fn new(kind: &'static str, ergo_tree_bytes: &[u8]) -> Self {
+        let mut key = Vec::with_capacity(kind.len() + ergo_tree_bytes.len() + 80);
+        key.extend_from_slice(kind.as_bytes());
+        key.push(0);
+        key.extend_from_slice(&(ergo_tree_bytes.len() as u64).to_be_bytes());
+        key.extend_from_slice(ergo_tree_bytes);
+        ContractKey(key)
+    }
+
+    pub fn with_value(mut self, value: u64) -> Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn with_index(self, index: usize) -> Self {
+        self.with_value(index as u64)
+    }
+
+    pub fn with_token_id(mut self, token_id: TokenId) -> Self {
+        self.0.extend_from_slice(String::from(token_id).as_bytes());
+        self
+    }
+}
+
+pub struct ContractCache<T> {
+    entries: Mutex<HashMap<ContractKey, T>>,
+}
+
+impl<T: Clone> ContractCache<T> {
+    pub fn new() -> Self {
+        ContractCache {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The memoized contract, or the one loaded with `load` if it succeeds
+    pub fn get_or_try_load<E>(
+        &self,
+        key: ContractKey,
+        load: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        if let Some(contract) = self.entries.lock().unwrap().get(&key) {
+            return Ok(contract.clone());
+        }
+        // loaded without holding the lock, a concurrent load of the same contract is harmless
+        let contract = load()?;
+        self.entries.lock().unwrap().insert(key, contract.clone());
+        Ok(contract)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Clone> Default for ContractCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_keeps_only_loaded_contracts() {
+        let cache: ContractCache<u32> = ContractCache::new();
+        let key = || ContractKey::new("test", &[1, 2, 3]).with_index(1);
+        assert_eq!(cache.get_or_try_load(key(), || Err("boom")), Err("boom"));
+        assert!(cache.is_empty());
+        assert_eq!(cache.get_or_try_load(key(), || Ok::<_, &str>(7)), Ok(7));
+        // memoized, not loaded again
+        assert_eq!(cache.get_or_try_load(key(), || Err("boom")), Ok(7));
+        assert_ne!(key(), ContractKey::new("test", &[1, 2, 3]).with_index(2));
+        assert_ne!(key(), ContractKey::new("other", &[1, 2, 3]).with_index(1));
+        assert_eq!(cache.len(), 1);
+    }
+}
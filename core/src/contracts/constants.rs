@@ -0,0 +1,43 @@
+//! Typed view of the contract constants referenced by the contract parameters.
+
+use std::fmt;
+
+use derive_more::From;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+
+#[derive(Debug, Clone, PartialEq, Eq, From)]
+pub enum ContractConstantValue {
+    TokenId(TokenId),
+    Long(i64),
+    Int(i32),
+}
+
+impl fmt::Display for ContractConstantValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContractConstantValue::TokenId(token_id) => write!(f, "{}", String::from(*token_id)),
+            ContractConstantValue::Long(v) => write!(f, "{}", v),
+            ContractConstantValue::Int(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// Constant of a contract at `index` known by the contract parameters as `name`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractConstant {
+    pub name: &'static str,
+    pub index: usize,
+    pub value: ContractConstantValue,
+}
+
+pub fn log_contract_constants(contract_name: &str, constants: &[ContractConstant]) {
+    for constant in constants {
+        log::debug!(
+            "{} contract constant {} (index {}): {}",
+            contract_name,
+            constant.name,
+            constant.index,
+            constant.value
+        );
+    }
+}
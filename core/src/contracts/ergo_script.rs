@@ -0,0 +1,68 @@
+//! Compiling contracts from ErgoScript source.
+//!
+//! The compiled tree is re-serialized with constant segregation so the `*_index` contract
+//! parameters refer to its constants the same way they do for the pre-compiled contracts.
+
+use ergo_lib::ergoscript_compiler::compiler::compile;
+use ergo_lib::ergoscript_compiler::compiler::CompileError;
+use ergo_lib::ergoscript_compiler::script_env::ScriptEnv;
+use ergo_lib::ergotree_ir::ergo_tree::ErgoTree;
+use ergo_lib::ergotree_ir::ergo_tree::ErgoTreeError;
+use ergo_lib::ergotree_ir::ergo_tree::ErgoTreeHeader;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ErgoScriptError {
+    #[error("ErgoScript compile error: {0:?}")]
+    Compile(CompileError),
+    #[error("ErgoScript compiled to an invalid ergo tree: {0:?}")]
+    ErgoTree(ErgoTreeError),
+    #[error("ErgoScript compiled ergo tree serialization error: {0}")]
+    Serialization(#[from] SigmaSerializationError),
+    #[error("contract parameters: either ergo_tree_bytes or ergo_script must be set")]
+    NoContract,
+    #[error("contract parameters: only one of ergo_tree_bytes and ergo_script can be set")]
+    BothContracts,
+}
+
+/// Compile ErgoScript `source` into ergo tree bytes with segregated constants
+pub fn compile_ergo_script(source: &str) -> Result<Vec<u8>, ErgoScriptError> {
+    let compiled = compile(source, ScriptEnv::new()).map_err(ErgoScriptError::Compile)?;
+    let proposition = compiled.proposition().map_err(ErgoScriptError::ErgoTree)?;
+    let ergo_tree =
+        ErgoTree::new(ErgoTreeHeader::v0(true), &proposition).map_err(ErgoScriptError::ErgoTree)?;
+    Ok(ergo_tree.sigma_serialize_bytes()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergotree_ir::mir::constant::Constant;
+
+    use super::*;
+
+    #[test]
+    fn test_compile_segregates_constants() {
+        let ergo_tree_bytes = compile_ergo_script("HEIGHT > 100").unwrap();
+        let ergo_tree = ErgoTree::sigma_parse_bytes(&ergo_tree_bytes).unwrap();
+        assert_eq!(
+            ergo_tree.get_constant(0).unwrap(),
+            Some(Constant::from(100i32))
+        );
+        assert_eq!(ergo_tree.get_constant(1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_pool_contract_source_does_not_compile() {
+        // the box accessors of the pool contracts are beyond the compiler of ergo-lib
+        let refresh_guard = r#"{
+            val refreshNftId = fromBase64("VGpXblpyNHU3eCFBJUQqRy1LYU5kUmdVa1hwMnM1djg=")
+            sigmaProp(INPUTS(1).tokens(0)._1 == refreshNftId)
+        }"#;
+        assert!(matches!(
+            compile_ergo_script(refresh_guard),
+            Err(ErgoScriptError::Compile(_))
+        ));
+    }
+}
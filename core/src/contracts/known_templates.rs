@@ -0,0 +1,176 @@
+//! Registry of known contract templates.
+//!
+//! A template is the ergo tree without its segregated constants, so contracts that only differ in
+//! parameters (token ids, epoch length, min votes, etc.) share one. A configured contract with an
+//! unknown template is not one of the published oracle pool contracts and might have been
+//! tampered with.
+
+use derive_more::Display;
+use ergo_lib::ergo_chain_types::blake2b256_hash;
+use ergo_lib::ergo_chain_types::Digest32;
+use ergo_lib::ergotree_ir::ergo_tree::ErgoTree;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+
+use crate::contracts::ballot::BallotContractParameters;
+use crate::contracts::oracle::OracleContractParameters;
+use crate::contracts::pool::PoolContractParameters;
+use crate::contracts::refresh::RefreshContractParameters;
+use crate::contracts::update::UpdateContractParameters;
+use crate::pool_config::PoolConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum ContractKind {
+    Pool,
+    Refresh,
+    Oracle,
+    Ballot,
+    Update,
+}
+
+#[derive(Debug, Clone)]
+pub struct KnownContractTemplate {
+    pub kind: ContractKind,
+    /// Where the contract is published
+    pub version: &'static str,
+    pub template_hash: Digest32,
+}
+
+/// Configured contract not matching any known template of its kind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownContract {
+    pub kind: ContractKind,
+    /// Base16-encoded blake2b256 hash of the template, `None` if the ergo tree can't be parsed
+    pub template_hash: Option<String>,
+}
+
+lazy_static! {
+    /// Templates of the EIP-23 contracts shipped as defaults
+    pub static ref KNOWN_CONTRACT_TEMPLATES: Vec<KnownContractTemplate> = {
+        let eip23 = "EIP-23 v2.0";
+        [
+            (ContractKind::Pool, PoolContractParameters::default().ergo_tree_bytes()),
+            (ContractKind::Refresh, RefreshContractParameters::default().ergo_tree_bytes()),
+            (ContractKind::Oracle, OracleContractParameters::default().ergo_tree_bytes()),
+            (ContractKind::Ballot, BallotContractParameters::default().ergo_tree_bytes()),
+            (ContractKind::Update, UpdateContractParameters::default().ergo_tree_bytes()),
+        ]
+        .into_iter()
+        .filter_map(|(kind, ergo_tree_bytes)| {
+            Some(KnownContractTemplate {
+                kind,
+                version: eip23,
+                template_hash: template_hash(&ergo_tree_bytes)?,
+            })
+        })
+        .collect()
+    };
+}
+
+fn template_hash(ergo_tree_bytes: &[u8]) -> Option<Digest32> {
+    let ergo_tree = ErgoTree::sigma_parse_bytes(ergo_tree_bytes).ok()?;
+    let template = ergo_tree.template_bytes().ok()?;
+    Some(blake2b256_hash(&template))
+}
+
+/// Known version of the contract, `None` if its template is unknown
+pub fn find_known_template(
+    kind: ContractKind,
+    ergo_tree_bytes: &[u8],
+) -> Option<&'static KnownContractTemplate> {
+    let hash = template_hash(ergo_tree_bytes)?;
+    KNOWN_CONTRACT_TEMPLATES
+        .iter()
+        .find(|known| known.kind == kind && known.template_hash == hash)
+}
+
+pub fn find_unknown_contracts(pool_config: &PoolConfig) -> Vec<UnknownContract> {
+    let contracts = [
+        (
+            ContractKind::Pool,
+            pool_config
+                .pool_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .ergo_tree_bytes(),
+        ),
+        (
+            ContractKind::Refresh,
+            pool_config
+                .refresh_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .ergo_tree_bytes(),
+        ),
+        (
+            ContractKind::Oracle,
+            pool_config
+                .oracle_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .ergo_tree_bytes(),
+        ),
+        (
+            ContractKind::Ballot,
+            pool_config
+                .ballot_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .ergo_tree_bytes(),
+        ),
+        (
+            ContractKind::Update,
+            pool_config
+                .update_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .ergo_tree_bytes(),
+        ),
+    ];
+    contracts
+        .into_iter()
+        .filter(|(kind, ergo_tree_bytes)| find_known_template(*kind, ergo_tree_bytes).is_none())
+        .map(|(kind, ergo_tree_bytes)| UnknownContract {
+            kind,
+            template_hash: template_hash(&ergo_tree_bytes).map(String::from),
+        })
+        .collect()
+}
+
+/// Log an error for every configured contract with an unknown template
+pub fn warn_on_unknown_contracts(pool_config: &PoolConfig, pool_name: &str) {
+    for unknown in find_unknown_contracts(pool_config) {
+        log::error!(
+            "!!! pool {}: {} contract template (hash {}) does not match any known oracle pool contract. \
+             Do not run this pool unless you have audited the contract (see `diff-contracts`) !!!",
+            pool_name,
+            unknown.kind,
+            unknown.template_hash.as_deref().unwrap_or("<invalid ergo tree>")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_known_template() {
+        let pool_ergo_tree_bytes = PoolContractParameters::default().ergo_tree_bytes();
+        assert_eq!(
+            find_known_template(ContractKind::Pool, &pool_ergo_tree_bytes)
+                .unwrap()
+                .kind,
+            ContractKind::Pool
+        );
+        // a known template used for another kind of contract
+        assert!(find_known_template(ContractKind::Refresh, &pool_ergo_tree_bytes).is_none());
+        assert!(find_known_template(ContractKind::Pool, &[0, 1, 2]).is_none());
+
+        // changing a parameter keeps the template
+        let update_ergo_tree_bytes = UpdateContractParameters::default()
+            .with_min_votes(1)
+            .unwrap()
+            .ergo_tree_bytes();
+        assert!(find_known_template(ContractKind::Update, &update_ergo_tree_bytes).is_some());
+    }
+}
@@ -12,9 +12,17 @@ use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
 use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
 use thiserror::Error;
 
+use crate::contracts::cache::ContractCache;
+use crate::contracts::cache::ContractKey;
+use crate::contracts::constants::log_contract_constants;
+use crate::contracts::constants::ContractConstant;
 use crate::spec_token::PoolTokenId;
 use crate::spec_token::TokenIdKind;
 
+lazy_static! {
+    static ref ORACLE_CONTRACTS: ContractCache<OracleContract> = ContractCache::new();
+}
+
 #[derive(Clone, Debug)]
 pub struct OracleContract {
     ergo_tree: ErgoTree,
@@ -34,6 +42,10 @@ pub enum OracleContractError {
     ErgoTreeError(ErgoTreeError),
     #[error("oracle contract: TryExtractFrom error {0:?}")]
     TryExtractFrom(#[from] TryExtractFromError),
+    #[error("oracle contract: BoxValue error {0:?}")]
+    BoxValue(#[from] BoxValueError),
+    #[error("oracle contract: sigma serialization error {0}")]
+    SigmaSerialization(#[from] SigmaSerializationError),
     #[error("contract error: {1:?}, expected P2S: {0}")]
     WrappedWithExpectedP2SAddress(String, Box<Self>),
     #[error("oracle contract paramaters error: {0}")]
@@ -55,7 +67,7 @@ impl OracleContractInputs {
             contract_parameters,
             pool_nft_token_id: pool_nft_token_id.clone(),
         })?;
-        let new_parameters = oracle_contract.parameters();
+        let new_parameters = oracle_contract.parameters()?;
         Ok(Self {
             contract_parameters: new_parameters,
             pool_nft_token_id,
@@ -79,8 +91,23 @@ impl OracleContractInputs {
     }
 }
 
+impl OracleContractInputs {
+    fn cache_key(&self, kind: &'static str, ergo_tree_bytes: &[u8]) -> ContractKey {
+        ContractKey::new(kind, ergo_tree_bytes)
+            .with_index(self.contract_parameters.pool_nft_index)
+            .with_index(self.contract_parameters.min_storage_rent_index)
+            .with_value(*self.contract_parameters.min_storage_rent.as_u64())
+            .with_token_id(self.pool_nft_token_id.token_id())
+    }
+}
+
 impl OracleContract {
     pub fn checked_load(inputs: &OracleContractInputs) -> Result<Self, OracleContractError> {
+        let key = inputs.cache_key("loaded", &inputs.contract_parameters.ergo_tree_bytes);
+        ORACLE_CONTRACTS.get_or_try_load(key, || Self::checked_load_uncached(inputs))
+    }
+
+    fn checked_load_uncached(inputs: &OracleContractInputs) -> Result<Self, OracleContractError> {
         let checked_contract_parameters = OracleContractParameters::checked_load(
             inputs.contract_parameters.ergo_tree_bytes(),
             inputs.contract_parameters.pool_nft_index,
@@ -90,17 +117,24 @@ impl OracleContract {
         let ergo_tree =
             ErgoTree::sigma_parse_bytes(checked_contract_parameters.ergo_tree_bytes.as_slice())?;
         let contract = Self::from_ergo_tree(ergo_tree, inputs).map_err(|e| {
-            let expected_base16 = Self::build_with(inputs)
-                .unwrap()
-                .ergo_tree
-                .to_base16_bytes()
-                .unwrap();
-            OracleContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
+            // the expected tree is only a hint, keep the original error if it can't be built
+            if let Ok(Ok(expected_base16)) =
+                Self::build_with(inputs).map(|c| c.ergo_tree.to_base16_bytes())
+            {
+                OracleContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
+            } else {
+                e
+            }
         })?;
         Ok(contract)
     }
 
     fn build_with(inputs: &OracleContractInputs) -> Result<Self, OracleContractError> {
+        let key = inputs.cache_key("built", &inputs.contract_parameters.ergo_tree_bytes);
+        ORACLE_CONTRACTS.get_or_try_load(key, || Self::build_uncached(inputs))
+    }
+
+    fn build_uncached(inputs: &OracleContractInputs) -> Result<Self, OracleContractError> {
         let new_contract_parameters = OracleContractParameters::build_with(
             inputs.contract_parameters.ergo_tree_bytes(),
             inputs.contract_parameters.pool_nft_index,
@@ -123,10 +157,20 @@ impl OracleContract {
         ergo_tree: ErgoTree,
         inputs: &OracleContractInputs,
     ) -> Result<Self, OracleContractError> {
-        // dbg!(ergo_tree.get_constants().unwrap());
+        let ergo_tree_bytes = ergo_tree.sigma_serialize_bytes()?;
+        let key = inputs.cache_key("loaded", &ergo_tree_bytes);
+        ORACLE_CONTRACTS.get_or_try_load(key, || {
+            Self::from_ergo_tree_uncached(ergo_tree, ergo_tree_bytes, inputs)
+        })
+    }
 
+    fn from_ergo_tree_uncached(
+        ergo_tree: ErgoTree,
+        ergo_tree_bytes: Vec<u8>,
+        inputs: &OracleContractInputs,
+    ) -> Result<Self, OracleContractError> {
         let checked_contract_parameters = OracleContractParameters::checked_load(
-            ergo_tree.sigma_serialize_bytes().unwrap(),
+            ergo_tree_bytes,
             inputs.contract_parameters.pool_nft_index,
             inputs.contract_parameters.min_storage_rent_index,
             inputs.contract_parameters.min_storage_rent,
@@ -140,42 +184,71 @@ impl OracleContract {
             });
         }
 
-        Ok(Self {
+        let contract = Self {
             ergo_tree,
             pool_nft_index: inputs.contract_parameters.pool_nft_index,
             min_storage_rent_index: inputs.contract_parameters.min_storage_rent_index,
-        })
+        };
+        log_contract_constants("oracle", &contract.inspect_constants()?);
+        Ok(contract)
     }
 
     pub fn ergo_tree(&self) -> ErgoTree {
         self.ergo_tree.clone()
     }
 
-    pub fn pool_nft_token_id(&self) -> TokenId {
-        self.ergo_tree
+    pub fn pool_nft_token_id(&self) -> Result<TokenId, OracleContractError> {
+        Ok(self
+            .ergo_tree
             .get_constant(self.pool_nft_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<TokenId>()
-            .unwrap()
-    }
-
-    pub fn parameters(&self) -> OracleContractParameters {
-        OracleContractParameters {
-            ergo_tree_bytes: self.ergo_tree.sigma_serialize_bytes().unwrap(),
-            pool_nft_index: self.pool_nft_index,
-            min_storage_rent_index: self.min_storage_rent_index,
-            min_storage_rent: self.min_storage_rent(),
-        }
+            .map_err(|_| {
+                OracleContractError::ParametersError(OracleContractParametersError::NoPoolNftId)
+            })?
+            .ok_or(OracleContractError::ParametersError(
+                OracleContractParametersError::NoPoolNftId,
+            ))?
+            .try_extract_into::<TokenId>()?)
     }
 
-    fn min_storage_rent(&self) -> BoxValue {
-        let c = self
+    pub fn min_storage_rent(&self) -> Result<BoxValue, OracleContractError> {
+        Ok(self
             .ergo_tree
             .get_constant(self.min_storage_rent_index)
-            .unwrap()
-            .unwrap();
-        c.try_extract_into::<i64>().unwrap().try_into().unwrap()
+            .map_err(|_| {
+                OracleContractError::ParametersError(
+                    OracleContractParametersError::NoMinStorageRent,
+                )
+            })?
+            .ok_or(OracleContractError::ParametersError(
+                OracleContractParametersError::NoMinStorageRent,
+            ))?
+            .try_extract_into::<i64>()?
+            .try_into()?)
+    }
+
+    /// Constants referenced by the contract parameters
+    pub fn inspect_constants(&self) -> Result<Vec<ContractConstant>, OracleContractError> {
+        Ok(vec![
+            ContractConstant {
+                name: "pool_nft",
+                index: self.pool_nft_index,
+                value: self.pool_nft_token_id()?.into(),
+            },
+            ContractConstant {
+                name: "min_storage_rent",
+                index: self.min_storage_rent_index,
+                value: (*self.min_storage_rent()?.as_u64() as i64).into(),
+            },
+        ])
+    }
+
+    pub fn parameters(&self) -> Result<OracleContractParameters, OracleContractError> {
+        Ok(OracleContractParameters {
+            ergo_tree_bytes: self.ergo_tree.sigma_serialize_bytes()?,
+            pool_nft_index: self.pool_nft_index,
+            min_storage_rent_index: self.min_storage_rent_index,
+            min_storage_rent: self.min_storage_rent()?,
+        })
     }
 }
 
@@ -219,8 +292,6 @@ impl OracleContractParameters {
         min_storage_rent: BoxValue,
     ) -> Result<Self, OracleContractParametersError> {
         let ergo_tree = ErgoTree::sigma_parse_bytes(ergo_tree_bytes.as_slice())?;
-        // dbg!(ergo_tree.get_constants().unwrap());
-
         let min_storage_rent_from_tree: BoxValue = ergo_tree
             .get_constant(min_storage_rent_index)
             .map_err(|_| OracleContractParametersError::NoMinStorageRent)?
@@ -279,10 +350,9 @@ impl OracleContractParameters {
         let ergo_tree = ErgoTree::sigma_parse_bytes(self.ergo_tree_bytes.as_slice())?;
         Ok(ergo_tree
             .get_constant(self.pool_nft_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<TokenId>()
-            .unwrap())
+            .map_err(|_| OracleContractParametersError::NoPoolNftId)?
+            .ok_or(OracleContractParametersError::NoPoolNftId)?
+            .try_extract_into::<TokenId>()?)
     }
 }
 
@@ -305,9 +375,15 @@ mod tests {
         };
         let c = OracleContract::build_with(&inputs).unwrap();
         assert_eq!(
-            c.pool_nft_token_id(),
+            c.pool_nft_token_id().unwrap(),
             token_ids.pool_nft_token_id.token_id(),
         );
+        let constants = c.inspect_constants().unwrap();
+        assert_eq!(constants[0].name, "pool_nft");
+        assert_eq!(
+            constants[1].value,
+            (*c.min_storage_rent().unwrap().as_u64() as i64).into()
+        );
     }
 
     #[test]
@@ -329,9 +405,12 @@ mod tests {
         };
         let new_contract = OracleContract::build_with(&inputs).unwrap();
         assert_eq!(
-            new_contract.pool_nft_token_id(),
+            new_contract.pool_nft_token_id().unwrap(),
             new_pool_nft_token_id.token_id()
         );
-        assert_eq!(new_contract.min_storage_rent(), new_min_storage_rent);
+        assert_eq!(
+            new_contract.min_storage_rent().unwrap(),
+            new_min_storage_rent
+        );
     }
 }
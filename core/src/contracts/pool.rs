@@ -6,12 +6,21 @@ use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
 
 use ergo_lib::ergotree_ir::serialization::SigmaParsingError;
 use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
 use thiserror::Error;
 
+use crate::contracts::cache::ContractCache;
+use crate::contracts::cache::ContractKey;
+use crate::contracts::constants::log_contract_constants;
+use crate::contracts::constants::ContractConstant;
 use crate::spec_token::RefreshTokenId;
 use crate::spec_token::TokenIdKind;
 use crate::spec_token::UpdateTokenId;
 
+lazy_static! {
+    static ref POOL_CONTRACTS: ContractCache<PoolContract> = ContractCache::new();
+}
+
 #[derive(Clone, Debug)]
 pub struct PoolContract {
     ergo_tree: ErgoTree,
@@ -33,6 +42,8 @@ pub enum PoolContractError {
     ErgoTreeError(#[from] ErgoTreeError),
     #[error("pool contract: TryExtractFrom error {0:?}")]
     TryExtractFrom(#[from] TryExtractFromError),
+    #[error("pool contract: sigma serialization error {0}")]
+    SigmaSerialization(#[from] SigmaSerializationError),
     #[error("contract error: {1:?}, expected P2S: {0}")]
     WrappedWithExpectedP2SAddress(String, Box<Self>),
 }
@@ -56,7 +67,7 @@ impl PoolContractInputs {
             update_nft_token_id,
         };
         let pool_contract = PoolContract::build_with(&contract_inputs)?;
-        let new_parameters = pool_contract.parameters();
+        let new_parameters = pool_contract.parameters()?;
         Ok(Self {
             contract_parameters: new_parameters,
             ..contract_inputs
@@ -82,22 +93,44 @@ impl PoolContractInputs {
     }
 }
 
+impl PoolContractInputs {
+    fn cache_key(&self, kind: &'static str) -> ContractKey {
+        ContractKey::new(kind, &self.contract_parameters.ergo_tree_bytes)
+            .with_index(self.contract_parameters.refresh_nft_index)
+            .with_index(self.contract_parameters.update_nft_index)
+            .with_token_id(self.refresh_nft_token_id.token_id())
+            .with_token_id(self.update_nft_token_id.token_id())
+    }
+}
+
 impl PoolContract {
     pub fn checked_load(inputs: &PoolContractInputs) -> Result<Self, PoolContractError> {
+        POOL_CONTRACTS.get_or_try_load(inputs.cache_key("loaded"), || {
+            Self::checked_load_uncached(inputs)
+        })
+    }
+
+    fn checked_load_uncached(inputs: &PoolContractInputs) -> Result<Self, PoolContractError> {
         let ergo_tree =
             ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?;
         let contract = Self::from_ergo_tree(ergo_tree, inputs).map_err(|e| {
-            let expected_base16 = Self::build_with(inputs)
-                .unwrap()
-                .ergo_tree
-                .to_base16_bytes()
-                .unwrap();
-            PoolContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
+            // the expected tree is only a hint, keep the original error if it can't be built
+            if let Ok(Ok(expected_base16)) =
+                Self::build_with(inputs).map(|c| c.ergo_tree.to_base16_bytes())
+            {
+                PoolContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
+            } else {
+                e
+            }
         })?;
         Ok(contract)
     }
 
     pub fn build_with(inputs: &PoolContractInputs) -> Result<Self, PoolContractError> {
+        POOL_CONTRACTS.get_or_try_load(inputs.cache_key("built"), || Self::build_uncached(inputs))
+    }
+
+    fn build_uncached(inputs: &PoolContractInputs) -> Result<Self, PoolContractError> {
         let ergo_tree_orig =
             ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?;
         log::debug!("pool contract ergo_tree_orig: {:#?}", ergo_tree_orig);
@@ -118,67 +151,73 @@ impl PoolContract {
         ergo_tree: ErgoTree,
         inputs: &PoolContractInputs,
     ) -> Result<Self, PoolContractError> {
-        // dbg!(ergo_tree.get_constants().unwrap());
-        let refresh_nft_token_id = ergo_tree
-            .get_constant(inputs.contract_parameters.refresh_nft_index)
+        let contract = Self {
+            ergo_tree,
+            refresh_nft_index: inputs.contract_parameters.refresh_nft_index,
+            update_nft_index: inputs.contract_parameters.update_nft_index,
+        };
+        log_contract_constants("pool", &contract.inspect_constants()?);
+        if contract.refresh_nft_token_id()? != inputs.refresh_nft_token_id.token_id() {
+            return Err(PoolContractError::UnknownRefreshNftId);
+        }
+        if contract.update_nft_token_id()? != inputs.update_nft_token_id.token_id() {
+            return Err(PoolContractError::UnknownUpdateNftId);
+        }
+        Ok(contract)
+    }
+
+    pub fn ergo_tree(&self) -> ErgoTree {
+        self.ergo_tree.clone()
+    }
+
+    pub fn refresh_nft_token_id(&self) -> Result<TokenId, PoolContractError> {
+        Ok(self
+            .ergo_tree
+            .get_constant(self.refresh_nft_index)
             .map_err(|_| {
                 PoolContractError::ParametersError(PoolContractParametersError::NoRefreshNftId)
             })?
             .ok_or(PoolContractError::ParametersError(
                 PoolContractParametersError::NoRefreshNftId,
             ))?
-            .try_extract_into::<TokenId>()?;
-        if refresh_nft_token_id != inputs.refresh_nft_token_id.token_id() {
-            return Err(PoolContractError::UnknownRefreshNftId);
-        }
+            .try_extract_into::<TokenId>()?)
+    }
 
-        let update_nft_token_id = ergo_tree
-            .get_constant(inputs.contract_parameters.update_nft_index)
+    pub fn update_nft_token_id(&self) -> Result<TokenId, PoolContractError> {
+        Ok(self
+            .ergo_tree
+            .get_constant(self.update_nft_index)
             .map_err(|_| {
                 PoolContractError::ParametersError(PoolContractParametersError::NoUpdateNftId)
             })?
             .ok_or(PoolContractError::ParametersError(
                 PoolContractParametersError::NoUpdateNftId,
             ))?
-            .try_extract_into::<TokenId>()?;
-        if update_nft_token_id != inputs.update_nft_token_id.token_id() {
-            return Err(PoolContractError::UnknownUpdateNftId);
-        }
-        Ok(Self {
-            ergo_tree,
-            refresh_nft_index: inputs.contract_parameters.refresh_nft_index,
-            update_nft_index: inputs.contract_parameters.update_nft_index,
-        })
+            .try_extract_into::<TokenId>()?)
     }
 
-    pub fn ergo_tree(&self) -> ErgoTree {
-        self.ergo_tree.clone()
+    /// Constants referenced by the contract parameters
+    pub fn inspect_constants(&self) -> Result<Vec<ContractConstant>, PoolContractError> {
+        Ok(vec![
+            ContractConstant {
+                name: "refresh_nft",
+                index: self.refresh_nft_index,
+                value: self.refresh_nft_token_id()?.into(),
+            },
+            ContractConstant {
+                name: "update_nft",
+                index: self.update_nft_index,
+                value: self.update_nft_token_id()?.into(),
+            },
+        ])
     }
 
-    pub fn refresh_nft_token_id(&self) -> TokenId {
-        self.ergo_tree
-            .get_constant(self.refresh_nft_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<TokenId>()
-            .unwrap()
-    }
-
-    pub fn update_nft_token_id(&self) -> TokenId {
-        self.ergo_tree
-            .get_constant(self.update_nft_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<TokenId>()
-            .unwrap()
-    }
-
-    pub fn parameters(&self) -> PoolContractParameters {
-        PoolContractParameters {
-            ergo_tree_bytes: self.ergo_tree.sigma_serialize_bytes().unwrap(),
+    pub fn parameters(&self) -> Result<PoolContractParameters, PoolContractError> {
+        Ok(PoolContractParameters {
+            ergo_tree_bytes: self.ergo_tree.sigma_serialize_bytes()?,
             refresh_nft_index: self.refresh_nft_index,
             update_nft_index: self.update_nft_index,
-        }
+        })
     }
 }
 
@@ -217,7 +256,7 @@ impl PoolContractParameters {
             .try_extract_into::<TokenId>()?;
 
         let _update_nft = ergo_tree
-            .get_constant(refresh_nft_index)
+            .get_constant(update_nft_index)
             .map_err(|_| PoolContractParametersError::NoUpdateNftId)?
             .ok_or(PoolContractParametersError::NoUpdateNftId)?
             .try_extract_into::<TokenId>()?;
@@ -258,13 +297,20 @@ mod tests {
         };
         let c = PoolContract::build_with(&inputs).unwrap();
         assert_eq!(
-            c.refresh_nft_token_id(),
+            c.refresh_nft_token_id().unwrap(),
             token_ids.refresh_nft_token_id.token_id(),
         );
         assert_eq!(
-            c.update_nft_token_id(),
+            c.update_nft_token_id().unwrap(),
             token_ids.update_nft_token_id.token_id(),
         );
+        let constants = c.inspect_constants().unwrap();
+        assert_eq!(constants[0].name, "refresh_nft");
+        assert_eq!(constants[0].index, c.refresh_nft_index);
+        assert_eq!(
+            constants[1].value,
+            token_ids.update_nft_token_id.token_id().into()
+        );
     }
 
     #[test]
@@ -278,11 +324,11 @@ mod tests {
         };
         let c = PoolContract::build_with(&inputs).unwrap();
         assert_eq!(
-            c.refresh_nft_token_id(),
+            c.refresh_nft_token_id().unwrap(),
             token_ids.refresh_nft_token_id.token_id(),
         );
         assert_eq!(
-            c.update_nft_token_id(),
+            c.update_nft_token_id().unwrap(),
             token_ids.update_nft_token_id.token_id(),
         );
     }
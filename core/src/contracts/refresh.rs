@@ -11,6 +11,7 @@ use thiserror::Error;
 
 use crate::oracle_types::EpochLength;
 use crate::oracle_types::MinDatapoints;
+use crate::oracle_types::RewardTokensPerDatapoint;
 use crate::spec_token::OracleTokenId;
 use crate::spec_token::PoolTokenId;
 use crate::spec_token::TokenIdKind;
@@ -19,13 +20,26 @@ use crate::spec_token::TokenIdKind;
 pub struct RefreshContract {
     ergo_tree: ErgoTree,
     pool_nft_index: usize,
+    pool_nft_token_id: TokenId,
     oracle_token_id_index: usize,
+    oracle_token_id: TokenId,
     min_data_points_index: usize,
+    min_data_points: MinDatapoints,
     buffer_index: usize,
+    buffer: i32,
     max_deviation_percent_index: usize,
+    max_deviation_percent: i32,
     epoch_length_index: usize,
+    epoch_length: EpochLength,
+    reward_per_datapoint_index: usize,
+    oracle_reward_per_datapoint: RewardTokensPerDatapoint,
 }
 
+/// Reward tokens the collector gets for each collected datapoint on top of its own reward. The
+/// refresh contract caps the reward tokens leaving the pool box per datapoint at the oracle reward
+/// plus this amount.
+pub const COLLECTOR_REWARD_PER_DATAPOINT: i32 = 1;
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug, Error)]
 pub enum RefreshContractError {
@@ -60,12 +74,14 @@ impl RefreshContract {
         let ergo_tree =
             ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?;
         let contract = Self::from_ergo_tree(ergo_tree, inputs).map_err(|e| {
-            let expected_base16 = Self::build_with(inputs)
-                .unwrap()
-                .ergo_tree
-                .to_base16_bytes()
-                .unwrap();
-            RefreshContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
+            // the expected tree is only a hint, keep the original error if it can't be built
+            if let Ok(Ok(expected_base16)) =
+                Self::build_with(inputs).map(|c| c.ergo_tree.to_base16_bytes())
+            {
+                RefreshContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
+            } else {
+                e
+            }
         })?;
         Ok(contract)
     }
@@ -185,14 +201,42 @@ impl RefreshContract {
             ));
         }
 
+        let reward_per_datapoint = ergo_tree
+            .get_constant(parameters.reward_per_datapoint_index)
+            .map_err(|_| {
+                RefreshContractError::Parameters(
+                    RefreshContractParametersError::NoRewardPerDatapoint,
+                )
+            })?
+            .ok_or(RefreshContractError::Parameters(
+                RefreshContractParametersError::NoRewardPerDatapoint,
+            ))?
+            .try_extract_into::<i32>()?;
+        if reward_per_datapoint != parameters.reward_per_datapoint() {
+            return Err(RefreshContractError::Parameters(
+                RefreshContractParametersError::RewardPerDatapointDiffers {
+                    expected: parameters.reward_per_datapoint(),
+                    actual: reward_per_datapoint,
+                },
+            ));
+        }
+
         Ok(Self {
             ergo_tree,
             pool_nft_index: parameters.pool_nft_index,
+            pool_nft_token_id,
             oracle_token_id_index: parameters.oracle_token_id_index,
+            oracle_token_id,
             min_data_points_index: parameters.min_data_points_index,
+            min_data_points: MinDatapoints(min_data_points),
             buffer_index: parameters.buffer_length_index,
+            buffer: buffer_length,
             max_deviation_percent_index: parameters.max_deviation_percent_index,
+            max_deviation_percent,
             epoch_length_index: parameters.epoch_length_index,
+            epoch_length,
+            reward_per_datapoint_index: parameters.reward_per_datapoint_index,
+            oracle_reward_per_datapoint: parameters.oracle_reward_per_datapoint,
         })
     }
 
@@ -228,16 +272,13 @@ impl RefreshContract {
                     inputs.contract_parameters.epoch_length_index,
                     (inputs.contract_parameters.epoch_length.0).into(),
                 )
+                .map_err(RefreshContractError::ErgoTreeError)?
+                .with_constant(
+                    inputs.contract_parameters.reward_per_datapoint_index,
+                    inputs.contract_parameters.reward_per_datapoint().into(),
+                )
                 .map_err(RefreshContractError::ErgoTreeError)?;
-        Ok(Self {
-            ergo_tree,
-            pool_nft_index: inputs.contract_parameters.pool_nft_index,
-            oracle_token_id_index: inputs.contract_parameters.oracle_token_id_index,
-            min_data_points_index: inputs.contract_parameters.min_data_points_index,
-            buffer_index: inputs.contract_parameters.buffer_length_index,
-            max_deviation_percent_index: inputs.contract_parameters.max_deviation_percent_index,
-            epoch_length_index: inputs.contract_parameters.epoch_length_index,
-        })
+        Self::from_ergo_tree(ergo_tree, inputs)
     }
 
     pub fn ergo_tree(&self) -> ErgoTree {
@@ -245,61 +286,32 @@ impl RefreshContract {
     }
 
     pub fn epoch_length(&self) -> EpochLength {
-        EpochLength(
-            self.ergo_tree
-                .get_constant(self.epoch_length_index)
-                .unwrap()
-                .unwrap()
-                .try_extract_into::<i32>()
-                .unwrap(),
-        )
+        self.epoch_length
     }
 
     pub fn buffer(&self) -> i32 {
-        self.ergo_tree
-            .get_constant(self.buffer_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<i32>()
-            .unwrap()
+        self.buffer
     }
 
     pub fn min_data_points(&self) -> MinDatapoints {
-        MinDatapoints(
-            self.ergo_tree
-                .get_constant(self.min_data_points_index)
-                .unwrap()
-                .unwrap()
-                .try_extract_into::<i32>()
-                .unwrap(),
-        )
+        self.min_data_points
     }
 
     pub fn max_deviation_percent(&self) -> i32 {
-        self.ergo_tree
-            .get_constant(self.max_deviation_percent_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<i32>()
-            .unwrap()
+        self.max_deviation_percent
     }
 
     pub fn oracle_token_id(&self) -> TokenId {
-        self.ergo_tree
-            .get_constant(self.oracle_token_id_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<TokenId>()
-            .unwrap()
+        self.oracle_token_id
+    }
+
+    /// Reward tokens an oracle gets for each of its collected datapoints
+    pub fn oracle_reward_tokens_per_datapoint(&self) -> u64 {
+        self.oracle_reward_per_datapoint.0 as u64
     }
 
     pub fn pool_nft_token_id(&self) -> TokenId {
-        self.ergo_tree
-            .get_constant(self.pool_nft_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<TokenId>()
-            .unwrap()
+        self.pool_nft_token_id
     }
 
     pub fn parameters(&self) -> RefreshContractParameters {
@@ -315,6 +327,8 @@ impl RefreshContract {
             max_deviation_percent: self.max_deviation_percent(),
             epoch_length_index: self.epoch_length_index,
             epoch_length: self.epoch_length(),
+            reward_per_datapoint_index: self.reward_per_datapoint_index,
+            oracle_reward_per_datapoint: self.oracle_reward_per_datapoint,
         }
     }
 }
@@ -378,8 +392,11 @@ pub struct RefreshContractParameters {
     max_deviation_percent: i32,
     epoch_length_index: usize,
     epoch_length: EpochLength,
+    reward_per_datapoint_index: usize,
+    oracle_reward_per_datapoint: RewardTokensPerDatapoint,
 }
 
+#[derive(Clone)]
 pub struct RefreshContractParametersInputs {
     pub ergo_tree_bytes: Vec<u8>,
     pub pool_nft_index: usize,
@@ -392,6 +409,10 @@ pub struct RefreshContractParametersInputs {
     pub max_deviation_percent: i32,
     pub epoch_length_index: usize,
     pub epoch_length: EpochLength,
+    /// Index of the reward tokens the pool box pays per collected datapoint, the oracle reward
+    /// plus [`COLLECTOR_REWARD_PER_DATAPOINT`]
+    pub reward_per_datapoint_index: usize,
+    pub oracle_reward_per_datapoint: RewardTokensPerDatapoint,
 }
 
 #[derive(Debug, Error)]
@@ -427,6 +448,12 @@ pub enum RefreshContractParametersError {
         expected: EpochLength,
         actual: EpochLength,
     },
+    #[error("refresh contract parameters: failed to get reward per datapoint from constants")]
+    NoRewardPerDatapoint,
+    #[error(
+        "refresh contract parameters: unexpected `reward per datapoint` value from constants (oracle reward + {COLLECTOR_REWARD_PER_DATAPOINT} for the collector). Expected {expected}, got {actual}"
+    )]
+    RewardPerDatapointDiffers { expected: i32, actual: i32 },
     #[error("refresh contract parameters: sigma parsing error {0}")]
     SigmaParsing(#[from] SigmaParsingError),
     #[error("refresh contract parameters: sigma serialization error {0}")]
@@ -437,12 +464,32 @@ pub enum RefreshContractParametersError {
     TryExtractFrom(#[from] TryExtractFromError),
     #[error("refresh contract parameters: ergo tree error {0:?}")]
     ErgoTreeError(ErgoTreeError),
+    #[error("refresh contract parameters: epoch length must be positive, got {0:?}")]
+    InvalidEpochLength(EpochLength),
+    #[error(
+        "refresh contract parameters: buffer length must be non-negative and less than the epoch length {epoch_length:?}, got {buffer_length}"
+    )]
+    InvalidBufferLength {
+        buffer_length: i32,
+        epoch_length: EpochLength,
+    },
+    #[error("refresh contract parameters: min data points must be positive, got {0}")]
+    InvalidMinDataPoints(i32),
+    #[error(
+        "refresh contract parameters: max deviation percent must be between 0 and 100, got {0}"
+    )]
+    InvalidMaxDeviationPercent(i32),
+    #[error(
+        "refresh contract parameters: oracle reward per datapoint must be positive, got {0:?}"
+    )]
+    InvalidOracleRewardPerDatapoint(RewardTokensPerDatapoint),
 }
 
 impl RefreshContractParameters {
     pub fn build_with(
         inputs: RefreshContractParametersInputs,
     ) -> Result<Self, RefreshContractParametersError> {
+        validate_parameter_values(&inputs)?;
         let ergo_tree_orig = ErgoTree::sigma_parse_bytes(inputs.ergo_tree_bytes.as_slice())?;
         log::debug!("refresh contract ergo_tree_orig: {:#?}", ergo_tree_orig);
         let ergo_tree = ergo_tree_orig
@@ -459,6 +506,11 @@ impl RefreshContractParameters {
             )
             .map_err(RefreshContractParametersError::ErgoTreeError)?
             .with_constant(inputs.epoch_length_index, inputs.epoch_length.0.into())
+            .map_err(RefreshContractParametersError::ErgoTreeError)?
+            .with_constant(
+                inputs.reward_per_datapoint_index,
+                reward_per_datapoint(inputs.oracle_reward_per_datapoint).into(),
+            )
             .map_err(RefreshContractParametersError::ErgoTreeError)?;
         let _pool_nft = ergo_tree
             .get_constant(inputs.pool_nft_index)
@@ -482,12 +534,15 @@ impl RefreshContractParameters {
             max_deviation_percent: inputs.max_deviation_percent,
             epoch_length_index: inputs.epoch_length_index,
             epoch_length: inputs.epoch_length,
+            reward_per_datapoint_index: inputs.reward_per_datapoint_index,
+            oracle_reward_per_datapoint: inputs.oracle_reward_per_datapoint,
         })
     }
 
     pub fn checked_load(
         inputs: RefreshContractParametersInputs,
     ) -> Result<Self, RefreshContractParametersError> {
+        validate_parameter_values(&inputs)?;
         let ergo_tree = ErgoTree::sigma_parse_bytes(inputs.ergo_tree_bytes.as_slice())?;
         let min_data_points = ergo_tree
             .get_constant(inputs.min_data_points_index)
@@ -542,6 +597,20 @@ impl RefreshContractParameters {
             });
         }
 
+        let reward_per_datapoint_constant = ergo_tree
+            .get_constant(inputs.reward_per_datapoint_index)
+            .map_err(|_| RefreshContractParametersError::NoRewardPerDatapoint)?
+            .ok_or(RefreshContractParametersError::NoRewardPerDatapoint)?
+            .try_extract_into::<i32>()?;
+
+        if reward_per_datapoint_constant != reward_per_datapoint(inputs.oracle_reward_per_datapoint)
+        {
+            return Err(RefreshContractParametersError::RewardPerDatapointDiffers {
+                expected: reward_per_datapoint(inputs.oracle_reward_per_datapoint),
+                actual: reward_per_datapoint_constant,
+            });
+        }
+
         let _pool_nft = ergo_tree
             .get_constant(inputs.pool_nft_index)
             .map_err(|_| RefreshContractParametersError::NoPoolNftId)?
@@ -564,6 +633,8 @@ impl RefreshContractParameters {
             max_deviation_percent: inputs.max_deviation_percent,
             epoch_length_index: inputs.epoch_length_index,
             epoch_length: inputs.epoch_length,
+            reward_per_datapoint_index: inputs.reward_per_datapoint_index,
+            oracle_reward_per_datapoint: inputs.oracle_reward_per_datapoint,
         })
     }
 
@@ -610,6 +681,82 @@ impl RefreshContractParameters {
     pub fn epoch_length(&self) -> EpochLength {
         self.epoch_length
     }
+
+    /// Epoch length in blocks, positive and greater than the buffer length
+    pub fn epoch_length_blocks(&self) -> u32 {
+        self.epoch_length.0 as u32
+    }
+
+    /// Buffer length in blocks, less than the epoch length
+    pub fn buffer_length_blocks(&self) -> u32 {
+        self.buffer_length as u32
+    }
+
+    /// Max deviation percent, between 0 and 100
+    pub fn max_deviation_percent_value(&self) -> u32 {
+        self.max_deviation_percent as u32
+    }
+
+    pub fn reward_per_datapoint_index(&self) -> usize {
+        self.reward_per_datapoint_index
+    }
+
+    pub fn oracle_reward_per_datapoint(&self) -> RewardTokensPerDatapoint {
+        self.oracle_reward_per_datapoint
+    }
+
+    /// Value of the reward per datapoint constant in the contract
+    pub fn reward_per_datapoint(&self) -> i32 {
+        reward_per_datapoint(self.oracle_reward_per_datapoint)
+    }
+
+    /// Reward tokens an oracle gets for each of its collected datapoints, positive
+    pub fn oracle_reward_tokens_per_datapoint(&self) -> u64 {
+        self.oracle_reward_per_datapoint.0 as u64
+    }
+}
+
+/// Reward tokens the pool box pays per collected datapoint for the given oracle reward
+fn reward_per_datapoint(oracle_reward_per_datapoint: RewardTokensPerDatapoint) -> i32 {
+    oracle_reward_per_datapoint.0 + COLLECTOR_REWARD_PER_DATAPOINT
+}
+
+/// Check the values the refresh contract relies on being in range. All parameters load with
+/// these checks, so the accessors converting them to unsigned values cannot wrap.
+fn validate_parameter_values(
+    inputs: &RefreshContractParametersInputs,
+) -> Result<(), RefreshContractParametersError> {
+    if inputs.epoch_length.0 <= 0 {
+        return Err(RefreshContractParametersError::InvalidEpochLength(
+            inputs.epoch_length,
+        ));
+    }
+    if inputs.buffer_length < 0 || inputs.buffer_length >= inputs.epoch_length.0 {
+        return Err(RefreshContractParametersError::InvalidBufferLength {
+            buffer_length: inputs.buffer_length,
+            epoch_length: inputs.epoch_length,
+        });
+    }
+    if inputs.min_data_points.0 <= 0 {
+        return Err(RefreshContractParametersError::InvalidMinDataPoints(
+            inputs.min_data_points.0,
+        ));
+    }
+    if !(0..=100).contains(&inputs.max_deviation_percent) {
+        return Err(RefreshContractParametersError::InvalidMaxDeviationPercent(
+            inputs.max_deviation_percent,
+        ));
+    }
+    if !(1..=i32::MAX - COLLECTOR_REWARD_PER_DATAPOINT)
+        .contains(&inputs.oracle_reward_per_datapoint.0)
+    {
+        return Err(
+            RefreshContractParametersError::InvalidOracleRewardPerDatapoint(
+                inputs.oracle_reward_per_datapoint,
+            ),
+        );
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -641,6 +788,10 @@ mod tests {
         assert_eq!(c.buffer(), parameters.buffer_length);
         assert_eq!(c.max_deviation_percent(), parameters.max_deviation_percent);
         assert_eq!(c.epoch_length(), parameters.epoch_length);
+        assert_eq!(
+            c.oracle_reward_tokens_per_datapoint(),
+            parameters.oracle_reward_tokens_per_datapoint()
+        );
     }
 
     #[test]
@@ -650,6 +801,7 @@ mod tests {
         let expected_buffer_length = 100;
         let expected_max_deviation_percent = 88;
         let expected_epoch_length = EpochLength(1000);
+        let expected_oracle_reward_per_datapoint = RewardTokensPerDatapoint(5);
         let new_contract_parameter_inputs = RefreshContractParametersInputs {
             ergo_tree_bytes: contract_parameters.ergo_tree_bytes(),
             pool_nft_index: contract_parameters.pool_nft_index(),
@@ -662,6 +814,8 @@ mod tests {
             max_deviation_percent: expected_max_deviation_percent,
             epoch_length_index: contract_parameters.epoch_length_index(),
             epoch_length: expected_epoch_length,
+            reward_per_datapoint_index: contract_parameters.reward_per_datapoint_index(),
+            oracle_reward_per_datapoint: expected_oracle_reward_per_datapoint,
         };
         let new_contract_parameters =
             RefreshContractParameters::build_with(new_contract_parameter_inputs).unwrap();
@@ -682,6 +836,7 @@ mod tests {
             expected_max_deviation_percent
         );
         assert_eq!(new_contract.epoch_length(), expected_epoch_length);
+        assert_eq!(new_contract.oracle_reward_tokens_per_datapoint(), 5);
         assert_eq!(
             new_contract.oracle_token_id(),
             inputs.oracle_token_id.token_id()
@@ -691,4 +846,77 @@ mod tests {
             inputs.pool_nft_token_id.token_id()
         );
     }
+
+    #[test]
+    fn test_parameter_validation() {
+        let contract_parameters = RefreshContractParameters::default();
+        let valid_inputs = RefreshContractParametersInputs {
+            ergo_tree_bytes: contract_parameters.ergo_tree_bytes(),
+            pool_nft_index: contract_parameters.pool_nft_index(),
+            oracle_token_id_index: contract_parameters.oracle_token_id_index(),
+            min_data_points_index: contract_parameters.min_data_points_index(),
+            min_data_points: contract_parameters.min_data_points(),
+            buffer_length_index: contract_parameters.buffer_length_index(),
+            buffer_length: contract_parameters.buffer_length(),
+            max_deviation_percent_index: contract_parameters.max_deviation_percent_index(),
+            max_deviation_percent: contract_parameters.max_deviation_percent(),
+            epoch_length_index: contract_parameters.epoch_length_index(),
+            epoch_length: contract_parameters.epoch_length(),
+            reward_per_datapoint_index: contract_parameters.reward_per_datapoint_index(),
+            oracle_reward_per_datapoint: contract_parameters.oracle_reward_per_datapoint(),
+        };
+        assert!(matches!(
+            RefreshContractParameters::build_with(RefreshContractParametersInputs {
+                epoch_length: EpochLength(0),
+                ..valid_inputs.clone()
+            }),
+            Err(RefreshContractParametersError::InvalidEpochLength(_))
+        ));
+        assert!(matches!(
+            RefreshContractParameters::build_with(RefreshContractParametersInputs {
+                buffer_length: contract_parameters.epoch_length().0,
+                ..valid_inputs.clone()
+            }),
+            Err(RefreshContractParametersError::InvalidBufferLength { .. })
+        ));
+        assert!(matches!(
+            RefreshContractParameters::build_with(RefreshContractParametersInputs {
+                min_data_points: MinDatapoints(0),
+                ..valid_inputs.clone()
+            }),
+            Err(RefreshContractParametersError::InvalidMinDataPoints(0))
+        ));
+        assert!(matches!(
+            RefreshContractParameters::checked_load(RefreshContractParametersInputs {
+                max_deviation_percent: 101,
+                ..valid_inputs.clone()
+            }),
+            Err(RefreshContractParametersError::InvalidMaxDeviationPercent(
+                101
+            ))
+        ));
+        assert!(matches!(
+            RefreshContractParameters::build_with(RefreshContractParametersInputs {
+                oracle_reward_per_datapoint: RewardTokensPerDatapoint(0),
+                ..valid_inputs.clone()
+            }),
+            Err(RefreshContractParametersError::InvalidOracleRewardPerDatapoint(_))
+        ));
+        // the EIP-23 refresh contract pays 1 reward token per datapoint to the oracle
+        assert!(matches!(
+            RefreshContractParameters::checked_load(RefreshContractParametersInputs {
+                oracle_reward_per_datapoint: RewardTokensPerDatapoint(2),
+                ..valid_inputs.clone()
+            }),
+            Err(RefreshContractParametersError::RewardPerDatapointDiffers {
+                expected: 3,
+                actual: 2
+            })
+        ));
+        let parameters = RefreshContractParameters::checked_load(valid_inputs).unwrap();
+        assert_eq!(
+            parameters.epoch_length_blocks(),
+            contract_parameters.epoch_length().0 as u32
+        );
+    }
 }
@@ -3,7 +3,7 @@ use ergo_lib::ergotree_ir::chain::token::TokenId;
 use ergo_lib::ergotree_ir::ergo_tree::ErgoTree;
 use ergo_lib::ergotree_ir::ergo_tree::ErgoTreeError;
 use ergo_lib::ergotree_ir::mir::constant::TryExtractFromError;
-use ergo_lib::ergotree_ir::mir::constant::{Literal, TryExtractInto};
+use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
 use ergo_lib::ergotree_ir::serialization::SigmaParsingError;
 
 use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
@@ -18,8 +18,11 @@ use crate::spec_token::TokenIdKind;
 pub struct UpdateContract {
     ergo_tree: ErgoTree,
     pool_nft_index: usize,
+    pool_nft_token_id: TokenId,
     ballot_token_index: usize,
+    ballot_token_id: TokenId,
     min_votes_index: usize,
+    min_votes: u64,
 }
 
 #[derive(Debug, Error)]
@@ -117,12 +120,14 @@ impl UpdateContract {
         let ergo_tree =
             ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?;
         let contract = Self::from_ergo_tree(ergo_tree, inputs).map_err(|e| {
-            let expected_base16 = Self::build_with(inputs)
-                .unwrap()
-                .ergo_tree
-                .to_base16_bytes()
-                .unwrap();
-            UpdateContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
+            // the expected tree is only a hint, keep the original error if it can't be built
+            if let Ok(Ok(expected_base16)) =
+                Self::build_with(inputs).map(|c| c.ergo_tree.to_base16_bytes())
+            {
+                UpdateContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
+            } else {
+                e
+            }
         })?;
         Ok(contract)
     }
@@ -163,8 +168,11 @@ impl UpdateContract {
         Ok(Self {
             ergo_tree,
             pool_nft_index: inputs.contract_parameters.pool_nft_index,
+            pool_nft_token_id,
             ballot_token_index: inputs.contract_parameters.ballot_token_index,
+            ballot_token_id,
             min_votes_index: inputs.contract_parameters.min_votes_index,
+            min_votes,
         })
     }
 
@@ -173,37 +181,15 @@ impl UpdateContract {
     }
 
     pub fn min_votes(&self) -> u64 {
-        let vote_constant = self
-            .ergo_tree
-            .get_constant(self.min_votes_index)
-            .unwrap()
-            .unwrap();
-        if let Literal::Int(votes) = vote_constant.v {
-            votes as u64
-        } else {
-            panic!(
-                "update: minimum votes is wrong type, expected SInt, found {:?}",
-                vote_constant.tpe
-            );
-        }
+        self.min_votes
     }
 
     pub fn pool_nft_token_id(&self) -> TokenId {
-        self.ergo_tree
-            .get_constant(self.pool_nft_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<TokenId>()
-            .unwrap()
+        self.pool_nft_token_id
     }
 
     pub fn ballot_token_id(&self) -> TokenId {
-        self.ergo_tree
-            .get_constant(self.ballot_token_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<TokenId>()
-            .unwrap()
+        self.ballot_token_id
     }
 
     pub fn parameters(&self) -> UpdateContractParameters {
@@ -249,6 +235,11 @@ pub enum UpdateContractParametersError {
     SigmaSerialization(#[from] SigmaSerializationError),
     #[error("update contract parameters: base16 decoding error {0}")]
     Decode(#[from] DecodeError),
+    #[error(
+        "update contract parameters: min votes must be between 1 and {}, got {0}",
+        i32::MAX
+    )]
+    InvalidMinVotes(u64),
 }
 
 impl UpdateContractParameters {
@@ -259,6 +250,7 @@ impl UpdateContractParameters {
         min_votes_index: usize,
         min_votes: u64,
     ) -> Result<Self, UpdateContractParametersError> {
+        validate_min_votes(min_votes)?;
         let ergo_tree_orig = ErgoTree::sigma_parse_bytes(ergo_tree_bytes.as_slice())?;
         log::debug!("update contract ergo_tree_orig: {:#?}", ergo_tree_orig);
         let ergo_tree = ergo_tree_orig
@@ -290,6 +282,7 @@ impl UpdateContractParameters {
         min_votes_index: usize,
         min_votes: u64,
     ) -> Result<Self, UpdateContractParametersError> {
+        validate_min_votes(min_votes)?;
         let ergo_tree = ErgoTree::sigma_parse_bytes(ergo_tree_bytes.as_slice())?;
         let min_votes_ergo_tree = ergo_tree
             .get_constant(min_votes_index)
@@ -344,6 +337,26 @@ impl UpdateContractParameters {
     pub fn min_votes(&self) -> u64 {
         self.min_votes
     }
+
+    /// Same contract with the `min votes` constant set to `min_votes`
+    pub fn with_min_votes(&self, min_votes: u64) -> Result<Self, UpdateContractParametersError> {
+        Self::build_with(
+            self.ergo_tree_bytes(),
+            self.pool_nft_index,
+            self.ballot_token_index,
+            self.min_votes_index,
+            min_votes,
+        )
+    }
+}
+
+/// The contract stores min votes as SInt and an update with zero votes would let anyone update
+/// the pool
+fn validate_min_votes(min_votes: u64) -> Result<(), UpdateContractParametersError> {
+    if min_votes == 0 || min_votes > i32::MAX as u64 {
+        return Err(UpdateContractParametersError::InvalidMinVotes(min_votes));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -396,4 +409,27 @@ mod tests {
         assert_eq!(c.ballot_token_id(), token_ids.ballot_token_id.token_id(),);
         assert_eq!(c.min_votes(), new_parameters.min_votes);
     }
+
+    #[test]
+    fn test_with_min_votes() {
+        let default_parameters = UpdateContractParameters::default();
+        let new_parameters = default_parameters.with_min_votes(7).unwrap();
+        assert_eq!(new_parameters.min_votes(), 7);
+        assert!(UpdateContractParameters::checked_load(
+            new_parameters.ergo_tree_bytes(),
+            new_parameters.pool_nft_index(),
+            new_parameters.ballot_token_index(),
+            new_parameters.min_votes_index(),
+            7,
+        )
+        .is_ok());
+        assert!(matches!(
+            default_parameters.with_min_votes(0),
+            Err(UpdateContractParametersError::InvalidMinVotes(0))
+        ));
+        assert!(matches!(
+            default_parameters.with_min_votes(i32::MAX as u64 + 1),
+            Err(UpdateContractParametersError::InvalidMinVotes(_))
+        ));
+    }
 }
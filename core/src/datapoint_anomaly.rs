@@ -0,0 +1,308 @@
+//! Rolling statistics check of the aggregated datapoints (`datapoint_anomaly` in the oracle
+//! config).
+//!
+//! The last `window` datapoints fetched for the pool are kept in memory. A datapoint further than
+//! `max_sigmas` standard deviations from their mean is reported, and with `action: refuse` it is
+//! not published: the fetch fails as if the datapoint sources failed. This catches the glitches
+//! passing the checks of the individual sources, e.g. a wrong decimal place returned by most of
+//! them.
+//!
+//! Every fetched datapoint joins the window, including the reported ones, so a lasting move of the
+//! rate is accepted once it makes up enough of the window. The check starts after `min_samples`
+//! datapoints. The standard deviation is floored at `min_relative_spread` of the mean, so a window
+//! of identical (or nearly identical) datapoints is still checked.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::datapoint_source::DataPointSource;
+use crate::datapoint_source::DataPointSourceError;
+use crate::datapoint_source::FetchedDatapoint;
+use crate::notifications::NotificationEvent;
+use crate::notifications::Notifier;
+use crate::oracle_types::Rate;
+
+pub const DEFAULT_WINDOW: usize = 30;
+pub const DEFAULT_MIN_SAMPLES: usize = 10;
+pub const DEFAULT_MIN_RELATIVE_SPREAD: f64 = 0.001;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DatapointAnomalyConfig {
+    /// Datapoints further than this many standard deviations from the mean are reported
+    pub max_sigmas: f64,
+    /// Number of recent datapoints the mean and standard deviation are computed over (default 30)
+    pub window: Option<usize>,
+    /// Datapoints needed before the check starts (default 10)
+    pub min_samples: Option<usize>,
+    /// Floor of the standard deviation as a fraction of the mean (default 0.001)
+    pub min_relative_spread: Option<f64>,
+    /// `warn` (default) to only report the datapoint or `refuse` to not publish it
+    #[serde(default)]
+    pub action: AnomalyAction,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyAction {
+    #[default]
+    Warn,
+    Refuse,
+}
+
+/// Datapoint too far from the recent ones
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatapointAnomaly {
+    pub rate: i64,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub sigmas: f64,
+}
+
+/// Mean and standard deviation of the last `window` samples
+#[derive(Debug, Clone)]
+pub struct RollingStats {
+    window: usize,
+    samples: VecDeque<f64>,
+}
+
+impl RollingStats {
+    pub fn new(window: usize) -> Self {
+        RollingStats {
+            window: window.max(1),
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, sample: f64) {
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Mean and (population) standard deviation, `None` without samples
+    pub fn mean_std_dev(&self) -> Option<(f64, f64)> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let n = self.samples.len() as f64;
+        let mean = self.samples.iter().sum::<f64>() / n;
+        let variance = self.samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n;
+        Some((mean, variance.sqrt()))
+    }
+}
+
+/// Rolling statistics of the datapoints of a pool, kept across the main loop iterations
+pub struct AnomalyDetector {
+    config: DatapointAnomalyConfig,
+    stats: RefCell<RollingStats>,
+}
+
+impl AnomalyDetector {
+    pub fn new(config: DatapointAnomalyConfig) -> Self {
+        let stats = RollingStats::new(config.window.unwrap_or(DEFAULT_WINDOW));
+        AnomalyDetector {
+            config,
+            stats: RefCell::new(stats),
+        }
+    }
+
+    pub fn action(&self) -> AnomalyAction {
+        self.config.action
+    }
+
+    /// Compare the datapoint to the recent ones and add it to them
+    pub fn check(&self, rate: Rate) -> Option<DatapointAnomaly> {
+        let rate = i64::from(rate);
+        let mut stats = self.stats.borrow_mut();
+        let min_samples = self.config.min_samples.unwrap_or(DEFAULT_MIN_SAMPLES);
+        let min_relative_spread = self
+            .config
+            .min_relative_spread
+            .unwrap_or(DEFAULT_MIN_RELATIVE_SPREAD);
+        let anomaly = match stats.mean_std_dev() {
+            Some((mean, std_dev)) if stats.len() >= min_samples => {
+                let std_dev = std_dev.max(mean.abs() * min_relative_spread);
+                let sigmas = (rate as f64 - mean).abs() / std_dev;
+                // no spread to compare to with a zero mean
+                if std_dev > 0.0 && sigmas > self.config.max_sigmas {
+                    Some(DatapointAnomaly {
+                        rate,
+                        mean,
+                        std_dev,
+                        sigmas,
+                    })
+                } else {
+                    None
+                }
+            }
+            Some(_) | None => None,
+        };
+        stats.push(rate as f64);
+        anomaly
+    }
+
+    /// Datapoint source checking the datapoints of `source`
+    pub fn checked<'a>(
+        &'a self,
+        source: &'a dyn DataPointSource,
+        pool_name: &'a str,
+        notifier: &'a Notifier,
+    ) -> AnomalyCheckedSource<'a> {
+        AnomalyCheckedSource {
+            source,
+            detector: self,
+            pool_name,
+            notifier,
+        }
+    }
+}
+
+pub struct AnomalyCheckedSource<'a> {
+    source: &'a dyn DataPointSource,
+    detector: &'a AnomalyDetector,
+    pool_name: &'a str,
+    notifier: &'a Notifier,
+}
+
+impl<'a> DataPointSource for AnomalyCheckedSource<'a> {
+    fn get_datapoint(&self) -> Result<Rate, DataPointSourceError> {
+        Ok(self.fetch_datapoint()?.rate)
+    }
+
+    fn fetch_datapoint(&self) -> Result<FetchedDatapoint, DataPointSourceError> {
+        let fetched = self.source.fetch_datapoint()?;
+        if let Some(anomaly) = self.detector.check(fetched.rate) {
+            let refused = self.detector.action() == AnomalyAction::Refuse;
+            let event = NotificationEvent::DatapointAnomaly {
+                rate: anomaly.rate,
+                mean: anomaly.mean as i64,
+                sigmas: anomaly.sigmas as u32,
+                refused,
+            };
+            log::warn!("{}", event.message());
+            self.notifier.notify(self.pool_name, &event);
+            if refused {
+                return Err(DataPointSourceError::Anomaly {
+                    rate: anomaly.rate,
+                    mean: anomaly.mean,
+                    sigmas: anomaly.sigmas,
+                });
+            }
+        }
+        Ok(fetched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pool_commands::test_utils::MockDatapointSource;
+
+    use super::*;
+
+    fn config(action: AnomalyAction) -> DatapointAnomalyConfig {
+        DatapointAnomalyConfig {
+            max_sigmas: 4.0,
+            window: Some(5),
+            min_samples: Some(3),
+            min_relative_spread: None,
+            action,
+        }
+    }
+
+    #[test]
+    fn test_rolling_stats() {
+        let mut stats = RollingStats::new(3);
+        assert_eq!(stats.mean_std_dev(), None);
+        for sample in [1.0, 100.0, 2.0, 4.0, 6.0] {
+            stats.push(sample);
+        }
+        assert_eq!(stats.len(), 3);
+        let (mean, std_dev) = stats.mean_std_dev().unwrap();
+        assert_eq!(mean, 4.0);
+        assert!((std_dev - (8.0f64 / 3.0).sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_anomaly_check() {
+        let detector = AnomalyDetector::new(config(AnomalyAction::Warn));
+        // not checked before min_samples
+        assert_eq!(detector.check(1000.into()), None);
+        assert_eq!(detector.check(1010.into()), None);
+        assert_eq!(detector.check(5000.into()), None);
+        let detector = AnomalyDetector::new(config(AnomalyAction::Warn));
+        for rate in [1000, 1010, 990, 1005] {
+            assert_eq!(detector.check(rate.into()), None);
+        }
+        let anomaly = detector.check(10_000.into()).unwrap();
+        assert_eq!(anomaly.rate, 10_000);
+        assert!(anomaly.sigmas > 4.0);
+        // identical datapoints are checked against the spread floor, 1 per 1000 of the mean
+        let detector = AnomalyDetector::new(config(AnomalyAction::Warn));
+        for _ in 0..5 {
+            assert_eq!(detector.check(1000.into()), None);
+        }
+        let anomaly = detector.check(1010.into()).unwrap();
+        assert_eq!(anomaly.std_dev, 1.0);
+        assert_eq!(anomaly.sigmas, 10.0);
+    }
+
+    #[test]
+    fn test_min_relative_spread() {
+        let detector = AnomalyDetector::new(DatapointAnomalyConfig {
+            min_relative_spread: Some(0.01),
+            ..config(AnomalyAction::Warn)
+        });
+        for _ in 0..5 {
+            assert_eq!(detector.check(1000.into()), None);
+        }
+        // 4 sigmas of a 10 floor
+        assert_eq!(detector.check(1040.into()), None);
+        assert!(detector.check(1100.into()).is_some());
+    }
+
+    #[test]
+    fn test_refused_datapoint() {
+        let notifier = Notifier::new(Vec::new(), std::time::Duration::from_secs(0));
+        let detector = AnomalyDetector::new(config(AnomalyAction::Refuse));
+        for rate in [1000, 1010, 990, 1005] {
+            let source = MockDatapointSource {
+                datapoint: rate.into(),
+            };
+            assert!(detector
+                .checked(&source, "test", &notifier)
+                .get_datapoint()
+                .is_ok());
+        }
+        let glitch = MockDatapointSource {
+            datapoint: 100_000.into(),
+        };
+        assert!(matches!(
+            detector.checked(&glitch, "test", &notifier).get_datapoint(),
+            Err(DataPointSourceError::Anomaly { rate: 100_000, .. })
+        ));
+
+        let detector = AnomalyDetector::new(config(AnomalyAction::Warn));
+        for rate in [1000, 1010, 990, 1005] {
+            detector.check(rate.into());
+        }
+        assert_eq!(
+            detector
+                .checked(&glitch, "test", &notifier)
+                .get_datapoint()
+                .unwrap(),
+            100_000.into()
+        );
+    }
+}
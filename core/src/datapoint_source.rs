@@ -2,26 +2,69 @@
 mod ada_usd;
 mod aggregator;
 mod assets_exchange_rate;
+#[cfg(feature = "bitpanda")]
 mod bitpanda;
+#[cfg(feature = "chainlink")]
+mod chainlink;
+mod circuit_breaker;
+#[cfg(feature = "coincap")]
 mod coincap;
+#[cfg(feature = "coingecko")]
 mod coingecko;
 mod custom_ext_script;
+mod erg_sigusd;
 mod erg_usd;
 mod erg_xau;
+#[cfg(feature = "gold-api")]
+mod gold_api;
+#[cfg(feature = "htx")]
+mod htx;
+#[cfg(feature = "kraken")]
+mod kraken;
+#[cfg(feature = "mexc")]
+mod mexc;
+mod milkada_usd;
 mod predef;
+#[cfg(feature = "pyth")]
+mod pyth;
+mod shared;
+mod stablecoin;
 
+use crate::oracle_config::OracleConfig;
 use crate::oracle_types::Rate;
 use crate::pool_config::PredefinedDataPointSource;
 
+pub use self::circuit_breaker::BreakerThresholds;
+pub use self::circuit_breaker::CircuitBreaker;
+pub use self::circuit_breaker::CircuitBreakerConfig;
+pub use self::circuit_breaker::SourceQuarantine;
 use self::custom_ext_script::ExternalScript;
 use self::custom_ext_script::ExternalScriptError;
 use self::predef::sync_fetch_predef_source_aggregated;
+use self::shared::fetch_shared;
+pub use self::stablecoin::DepegAction;
+pub use self::stablecoin::StablecoinCheckConfig;
+
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
 use anyhow::anyhow;
+use serde::Deserialize;
+use serde::Serialize;
 use thiserror::Error;
 
 pub trait DataPointSource {
     fn get_datapoint(&self) -> Result<Rate, DataPointSourceError>;
+
+    /// The datapoint along with the raw rates of the sources it is aggregated from, none by
+    /// default
+    fn fetch_datapoint(&self) -> Result<FetchedDatapoint, DataPointSourceError> {
+        Ok(FetchedDatapoint {
+            rate: self.get_datapoint()?,
+            sources: Vec::new(),
+        })
+    }
 }
 
 #[derive(Debug, Error)]
@@ -36,11 +79,93 @@ pub enum DataPointSourceError {
     JsonMissingField { field: String, json: String },
     #[error("No datapoints from any source")]
     NoDataPoints,
+    #[error("Only {responded} datapoint sources responded, {required} are required")]
+    NotEnoughSources { responded: usize, required: usize },
+    #[error("Price feed {feed} was last updated {age_secs} seconds ago")]
+    StaleFeed { feed: &'static str, age_secs: u64 },
+    #[error("The prices of {sources} are more than {max_deviation_percent}% apart")]
+    SourcesDisagree {
+        sources: String,
+        max_deviation_percent: f64,
+    },
+    #[error("{stablecoin} is at {usd_price} USD, off its USD peg")]
+    StablecoinDepeg { stablecoin: String, usd_price: f64 },
+    #[error("Datapoint sources did not respond within {0:?}")]
+    Timeout(std::time::Duration),
+    #[error("Datapoint {rate} is {sigmas:.1} standard deviations from the mean {mean:.0} of the recent datapoints")]
+    Anomaly { rate: i64, mean: f64, sigmas: f64 },
+    #[error("Datapoint sources diverge by {spread_percent}% ({lowest_source} lowest, {highest_source} highest), not publishing until they agree")]
+    SourceDivergence {
+        spread_percent: u32,
+        lowest_source: String,
+        highest_source: String,
+    },
+}
+
+/// Raw rate of a single source before the aggregation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceRate {
+    pub source: String,
+    pub rate: f64,
+}
+
+/// Datapoint along with the raw rates it was aggregated from
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedDatapoint {
+    pub rate: Rate,
+    pub sources: Vec<SourceRate>,
+}
+
+/// Outcome of a single source in a fetch of the datapoint source
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceTrial {
+    pub source: String,
+    /// Assets of the rate, e.g. `NanoErg per Usd`
+    pub pair: String,
+    pub rate: Option<f64>,
+    pub error: Option<String>,
+    pub latency: Duration,
+}
+
+/// Checks of the predefined source rates set in the oracle config of the pool
+#[derive(Debug, Clone, Default)]
+pub struct SourceChecks {
+    pub stablecoin_check: Option<StablecoinCheckConfig>,
+    /// Failures of the sources followed across the fetches of the pool
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl SourceChecks {
+    pub fn from_oracle_config(oracle_config: &OracleConfig) -> Self {
+        SourceChecks {
+            stablecoin_check: oracle_config.stablecoin_check.clone(),
+            circuit_breaker: oracle_config
+                .source_circuit_breaker
+                .clone()
+                .map(|config| Arc::new(CircuitBreaker::new(config))),
+        }
+    }
+
+    /// Identifies the checks config, the pools with other checks don't share their datapoint
+    fn share_key(&self) -> String {
+        format!(
+            "stablecoin_check:{:?} source_circuit_breaker:{:?}",
+            self.stablecoin_check,
+            self.circuit_breaker
+                .as_ref()
+                .map(|breaker| breaker.config())
+        )
+    }
 }
 
 pub enum RuntimeDataPointSource {
-    Predefined(PredefinedDataPointSource),
+    Predefined(PredefinedDataPointSource, SourceChecks),
     ExternalScript(ExternalScript),
+    /// Fetch shared with the other pools of the process following the same source
+    Shared {
+        source: Box<RuntimeDataPointSource>,
+        max_age: Duration,
+    },
 }
 
 impl RuntimeDataPointSource {
@@ -54,22 +179,176 @@ impl RuntimeDataPointSource {
             )))
         } else {
             match predef_datapoint_source {
-                Some(predef_datasource) => Ok(RuntimeDataPointSource::Predefined(predef_datasource)),
+                Some(predef_datasource) => Ok(RuntimeDataPointSource::Predefined(
+                    predef_datasource,
+                    SourceChecks::default(),
+                )),
                 _ => Err(anyhow!(
                     "pool config data_point_source is empty along with data_point_source_custom_script in the oracle config"
                 )),
             }
         }
     }
-}
 
-impl DataPointSource for RuntimeDataPointSource {
-    fn get_datapoint(&self) -> Result<Rate, DataPointSourceError> {
+    /// Check the rates of a predefined source with `checks`
+    pub fn with_checks(self, checks: SourceChecks) -> RuntimeDataPointSource {
+        match self {
+            RuntimeDataPointSource::Predefined(predef, _) => {
+                RuntimeDataPointSource::Predefined(predef, checks)
+            }
+            RuntimeDataPointSource::ExternalScript(_) => self,
+            RuntimeDataPointSource::Shared { source, max_age } => RuntimeDataPointSource::Shared {
+                source: Box::new(source.with_checks(checks)),
+                max_age,
+            },
+        }
+    }
+
+    /// Reuse the datapoint fetched by another pool with the same source within `max_age`
+    pub fn shared(self, max_age: Option<Duration>) -> RuntimeDataPointSource {
+        match max_age {
+            Some(max_age) => RuntimeDataPointSource::Shared {
+                source: Box::new(self),
+                max_age,
+            },
+            None => self,
+        }
+    }
+
+    /// Identifies the pools following the same feed with the same checks
+    fn share_key(&self) -> String {
         match self {
-            RuntimeDataPointSource::Predefined(predef) => {
-                sync_fetch_predef_source_aggregated(predef)
+            RuntimeDataPointSource::Predefined(predef, checks) => {
+                format!("{:?} {}", predef, checks.share_key())
             }
-            RuntimeDataPointSource::ExternalScript(script) => script.get_datapoint(),
+            RuntimeDataPointSource::ExternalScript(script) => format!("script:{}", script.name()),
+            RuntimeDataPointSource::Shared { source, .. } => source.share_key(),
         }
     }
+
+    /// Sources quarantined by the circuit breaker of the pool since the last call
+    pub fn take_quarantines(&self) -> Vec<SourceQuarantine> {
+        match self {
+            RuntimeDataPointSource::Predefined(_, checks) => checks
+                .circuit_breaker
+                .as_ref()
+                .map(|breaker| breaker.take_quarantines())
+                .unwrap_or_default(),
+            RuntimeDataPointSource::ExternalScript(_) => Vec::new(),
+            RuntimeDataPointSource::Shared { source, .. } => source.take_quarantines(),
+        }
+    }
+
+    /// Fetch the datapoint once, along with the outcome of every source it is aggregated from
+    pub fn dry_run(
+        &self,
+    ) -> (
+        Result<FetchedDatapoint, DataPointSourceError>,
+        Vec<SourceTrial>,
+    ) {
+        match self {
+            RuntimeDataPointSource::ExternalScript(script) => {
+                let started = Instant::now();
+                let res = self.fetch(&mut Vec::new());
+                let trial = SourceTrial {
+                    source: format!("script:{}", script.name()),
+                    pair: "datapoint".to_string(),
+                    rate: res
+                        .as_ref()
+                        .ok()
+                        .map(|fetched| i64::from(fetched.rate) as f64),
+                    error: res.as_ref().err().map(ToString::to_string),
+                    latency: started.elapsed(),
+                };
+                (res, vec![trial])
+            }
+            RuntimeDataPointSource::Predefined(..) => {
+                let mut trials = Vec::new();
+                let res = self.fetch(&mut trials);
+                (res, trials)
+            }
+            RuntimeDataPointSource::Shared { source, .. } => source.dry_run(),
+        }
+    }
+
+    /// The outcome of every source fetched is added to `trials`
+    fn fetch(
+        &self,
+        trials: &mut Vec<SourceTrial>,
+    ) -> Result<FetchedDatapoint, DataPointSourceError> {
+        Ok(match self {
+            RuntimeDataPointSource::Predefined(predef, checks) => {
+                sync_fetch_predef_source_aggregated(predef, checks, trials)?
+            }
+            RuntimeDataPointSource::ExternalScript(script) => {
+                let rate = script.get_datapoint()?;
+                FetchedDatapoint {
+                    rate,
+                    sources: vec![SourceRate {
+                        source: "external_script".to_string(),
+                        rate: i64::from(rate) as f64,
+                    }],
+                }
+            }
+            RuntimeDataPointSource::Shared { source, max_age } => {
+                fetch_shared(&source.share_key(), *max_age, || source.fetch(trials))?
+            }
+        })
+    }
+}
+
+impl DataPointSource for RuntimeDataPointSource {
+    fn get_datapoint(&self) -> Result<Rate, DataPointSourceError> {
+        Ok(self.fetch_datapoint()?.rate)
+    }
+
+    fn fetch_datapoint(&self) -> Result<FetchedDatapoint, DataPointSourceError> {
+        let _span = tracing::info_span!("datapoint_fetch").entered();
+        self.fetch(&mut Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool_source(stablecoin_check: Option<StablecoinCheckConfig>) -> RuntimeDataPointSource {
+        RuntimeDataPointSource::new(Some(PredefinedDataPointSource::NanoErgUsd), None)
+            .unwrap()
+            .with_checks(SourceChecks {
+                stablecoin_check,
+                circuit_breaker: Some(Arc::new(CircuitBreaker::new(
+                    CircuitBreakerConfig::default(),
+                ))),
+            })
+            .shared(Some(Duration::from_secs(60)))
+    }
+
+    #[test]
+    fn test_share_key_follows_the_checks() {
+        let reject = StablecoinCheckConfig {
+            max_depeg_percent: None,
+            action: DepegAction::Reject,
+        };
+        // the same checks config, each pool with its own breaker
+        assert_eq!(
+            pool_source(Some(reject.clone())).share_key(),
+            pool_source(Some(reject.clone())).share_key()
+        );
+        assert_ne!(
+            pool_source(Some(reject)).share_key(),
+            pool_source(None).share_key()
+        );
+        let strict_breaker =
+            RuntimeDataPointSource::new(Some(PredefinedDataPointSource::NanoErgUsd), None)
+                .unwrap()
+                .with_checks(SourceChecks {
+                    stablecoin_check: None,
+                    circuit_breaker: Some(Arc::new(CircuitBreaker::new(CircuitBreakerConfig {
+                        consecutive_failures: Some(1),
+                        ..CircuitBreakerConfig::default()
+                    }))),
+                });
+        assert_ne!(strict_breaker.share_key(), pool_source(None).share_key());
+    }
 }
@@ -1,14 +1,10 @@
 //! Obtains the lovelace per 1 USD rate.
 
-use std::pin::Pin;
-
-use futures::Future;
-
+use super::aggregator::NamedSource;
 use super::assets_exchange_rate::Asset;
-use super::assets_exchange_rate::AssetsExchangeRate;
 use super::assets_exchange_rate::Usd;
+#[cfg(feature = "coingecko")]
 use super::coingecko;
-use super::DataPointSourceError;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Ada {}
@@ -25,9 +21,11 @@ impl Lovelace {
     }
 }
 
-#[allow(clippy::type_complexity)]
-pub fn usd_lovelace_sources() -> Vec<
-    Pin<Box<dyn Future<Output = Result<AssetsExchangeRate<Usd, Lovelace>, DataPointSourceError>>>>,
-> {
-    vec![Box::pin(coingecko::get_usd_lovelace())]
+/// Only the exchanges enabled by their cargo features
+pub fn usd_lovelace_sources() -> Vec<NamedSource<Usd, Lovelace>> {
+    #[allow(unused_mut)]
+    let mut sources: Vec<NamedSource<Usd, Lovelace>> = Vec::new();
+    #[cfg(feature = "coingecko")]
+    sources.push(("coingecko", Box::pin(coingecko::get_usd_lovelace())));
+    sources
 }
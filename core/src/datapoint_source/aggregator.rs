@@ -1,10 +1,27 @@
 use std::pin::Pin;
+use std::time::Duration;
+use std::time::Instant;
 
 use futures::Future;
 
 use super::assets_exchange_rate::Asset;
 use super::assets_exchange_rate::AssetsExchangeRate;
 use super::DataPointSourceError;
+use super::SourceChecks;
+use super::SourceRate;
+use super::SourceTrial;
+
+/// Last segment of the asset type name, e.g. `NanoErg`
+fn asset_name<A: Asset>() -> &'static str {
+    let type_name = std::any::type_name::<A>();
+    type_name.rsplit("::").next().unwrap_or(type_name)
+}
+
+/// Name of a source with the future fetching its rate
+pub type NamedSource<PER1, GET> = (
+    &'static str,
+    Pin<Box<dyn Future<Output = Result<AssetsExchangeRate<PER1, GET>, DataPointSourceError>>>>,
+);
 
 pub fn aggregate<PER1: Asset, GET: Asset>(
     rates: Vec<AssetsExchangeRate<PER1, GET>>,
@@ -17,28 +34,189 @@ pub fn aggregate<PER1: Asset, GET: Asset>(
     }
 }
 
-#[allow(clippy::type_complexity)]
 pub async fn fetch_aggregated<PER1: Asset, GET: Asset>(
-    sources: Vec<
-        Pin<Box<dyn Future<Output = Result<AssetsExchangeRate<PER1, GET>, DataPointSourceError>>>>,
-    >,
+    sources: Vec<NamedSource<PER1, GET>>,
+    checks: &SourceChecks,
+    trials: &mut Vec<SourceTrial>,
 ) -> Result<AssetsExchangeRate<PER1, GET>, DataPointSourceError> {
-    let ok_results: Vec<AssetsExchangeRate<PER1, GET>> = fetch(sources).await?;
+    let (rate, _) = fetch_aggregated_with_sources(sources, checks, trials).await?;
+    Ok(rate)
+}
+
+/// Aggregated rate along with the raw rates of the sources that responded
+pub async fn fetch_aggregated_with_sources<PER1: Asset, GET: Asset>(
+    sources: Vec<NamedSource<PER1, GET>>,
+    checks: &SourceChecks,
+    trials: &mut Vec<SourceTrial>,
+) -> Result<(AssetsExchangeRate<PER1, GET>, Vec<SourceRate>), DataPointSourceError> {
+    aggregate_with_sources(fetch(sources, checks, trials).await?)
+}
+
+/// Average of the rates of the sources that responded, along with their raw rates
+pub fn aggregate_with_sources<PER1: Asset, GET: Asset>(
+    ok_results: Vec<(&'static str, AssetsExchangeRate<PER1, GET>)>,
+) -> Result<(AssetsExchangeRate<PER1, GET>, Vec<SourceRate>), DataPointSourceError> {
     if ok_results.is_empty() {
         return Err(DataPointSourceError::NoDataPoints);
     }
-    let rate = aggregate(ok_results);
-    Ok(rate)
+    let source_rates = ok_results
+        .iter()
+        .map(|(source, rate)| SourceRate {
+            source: source.to_string(),
+            rate: rate.rate,
+        })
+        .collect();
+    let rate = aggregate(ok_results.into_iter().map(|(_, rate)| rate).collect());
+    Ok((rate, source_rates))
+}
+
+/// Median rate of the sources that responded, at least `min_sources` of them
+pub async fn fetch_median_with_sources<PER1: Asset, GET: Asset>(
+    sources: Vec<NamedSource<PER1, GET>>,
+    min_sources: usize,
+    checks: &SourceChecks,
+    trials: &mut Vec<SourceTrial>,
+) -> Result<(AssetsExchangeRate<PER1, GET>, Vec<SourceRate>), DataPointSourceError> {
+    let ok_results = fetch(sources, checks, trials).await?;
+    if ok_results.is_empty() {
+        return Err(DataPointSourceError::NoDataPoints);
+    }
+    if ok_results.len() < min_sources {
+        return Err(DataPointSourceError::NotEnoughSources {
+            responded: ok_results.len(),
+            required: min_sources,
+        });
+    }
+    let source_rates = ok_results
+        .iter()
+        .map(|(source, rate)| SourceRate {
+            source: source.to_string(),
+            rate: rate.rate,
+        })
+        .collect();
+    let rate = median(ok_results.into_iter().map(|(_, rate)| rate).collect());
+    Ok((rate, source_rates))
 }
 
-#[allow(clippy::type_complexity)]
+/// Less sensitive than the average to a single broken source, the mean of the two middle rates
+/// for an even number of rates
+pub fn median<PER1: Asset, GET: Asset>(
+    mut rates: Vec<AssetsExchangeRate<PER1, GET>>,
+) -> AssetsExchangeRate<PER1, GET> {
+    rates.sort_by(|a, b| a.rate.total_cmp(&b.rate));
+    let mid = rates.len() / 2;
+    let rate = if rates.len() % 2 == 0 {
+        (rates[mid - 1].rate + rates[mid].rate) / 2.0
+    } else {
+        rates[mid].rate
+    };
+    AssetsExchangeRate { rate, ..rates[0] }
+}
+
+/// Rates of the sources that responded, the sources quarantined by the circuit breaker of
+/// `checks` are not fetched. The outcome of every source is added to `trials`.
 pub async fn fetch<PER1: Asset, GET: Asset>(
-    sources: Vec<
-        Pin<Box<dyn Future<Output = Result<AssetsExchangeRate<PER1, GET>, DataPointSourceError>>>>,
-    >,
-) -> Result<Vec<AssetsExchangeRate<PER1, GET>>, DataPointSourceError> {
-    let results = futures::future::join_all(sources).await;
-    let ok_results: Vec<AssetsExchangeRate<PER1, GET>> =
-        results.into_iter().flat_map(|res| res.ok()).collect();
+    sources: Vec<NamedSource<PER1, GET>>,
+    checks: &SourceChecks,
+    trials: &mut Vec<SourceTrial>,
+) -> Result<Vec<(&'static str, AssetsExchangeRate<PER1, GET>)>, DataPointSourceError> {
+    let pair = format!("{} per {}", asset_name::<GET>(), asset_name::<PER1>());
+    let breaker = checks.circuit_breaker.as_deref();
+    let (sources, quarantined): (Vec<_>, Vec<_>) = sources.into_iter().partition(|(name, _)| {
+        !breaker.map_or(false, |breaker| breaker.is_quarantined(name, &pair))
+    });
+    let (names, futures): (Vec<_>, Vec<_>) = sources.into_iter().unzip();
+    let results = futures::future::join_all(futures.into_iter().map(|future| async move {
+        let started = Instant::now();
+        let res = future.await;
+        (res, started.elapsed())
+    }))
+    .await;
+    if let Some(breaker) = breaker {
+        for (name, (res, _)) in names.iter().zip(&results) {
+            // the quarantines are taken by the pool loop for the alerts
+            let _ = breaker.record(name, &pair, res.is_err());
+        }
+    }
+    for (name, (res, latency)) in names.iter().zip(&results) {
+        trials.push(SourceTrial {
+            source: name.to_string(),
+            pair: pair.clone(),
+            rate: res.as_ref().ok().map(|rate| rate.rate),
+            error: res.as_ref().err().map(ToString::to_string),
+            latency: *latency,
+        });
+    }
+    for (name, _) in &quarantined {
+        trials.push(SourceTrial {
+            source: name.to_string(),
+            pair: pair.clone(),
+            rate: None,
+            error: Some("quarantined by the circuit breaker".to_string()),
+            latency: Duration::ZERO,
+        });
+    }
+    let ok_results = names
+        .into_iter()
+        .zip(results)
+        .flat_map(|(name, (res, _))| res.ok().map(|rate| (name, rate)))
+        .collect();
     Ok(ok_results)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datapoint_source::assets_exchange_rate::NanoErg;
+    use crate::datapoint_source::assets_exchange_rate::Usd;
+
+    fn rates(rates: &[f64]) -> Vec<AssetsExchangeRate<Usd, NanoErg>> {
+        rates
+            .iter()
+            .map(|&rate| AssetsExchangeRate {
+                per1: Usd {},
+                get: NanoErg {},
+                rate,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(rates(&[3.0, 100.0, 1.0])).rate, 3.0);
+        assert_eq!(median(rates(&[4.0, 1.0, 2.0, 100.0])).rate, 3.0);
+        assert_eq!(median(rates(&[5.0])).rate, 5.0);
+    }
+
+    #[test]
+    fn test_fetch_median_needs_min_sources() {
+        let sources: Vec<NamedSource<Usd, NanoErg>> = vec![
+            (
+                "a",
+                Box::pin(async {
+                    Ok(AssetsExchangeRate {
+                        per1: Usd {},
+                        get: NanoErg {},
+                        rate: 1.0,
+                    })
+                }),
+            ),
+            (
+                "b",
+                Box::pin(async { Err(DataPointSourceError::NoDataPoints) }),
+            ),
+        ];
+        assert!(matches!(
+            tokio_test::block_on(fetch_median_with_sources(
+                sources,
+                2,
+                &SourceChecks::default(),
+                &mut Vec::new()
+            )),
+            Err(DataPointSourceError::NotEnoughSources {
+                responded: 1,
+                required: 2
+            })
+        ));
+    }
+}
@@ -0,0 +1,118 @@
+//! Chainlink price feeds, read with `latestRoundData()` of the feed aggregator contract on
+//! Ethereum over a public JSON-RPC endpoint
+
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use super::ada_usd::Lovelace;
+use super::assets_exchange_rate::AssetsExchangeRate;
+use super::assets_exchange_rate::Usd;
+use super::DataPointSourceError;
+
+const ETHEREUM_RPC_URL: &str = "https://ethereum-rpc.publicnode.com";
+
+/// ADA/USD feed on Ethereum mainnet, see https://data.chain.link/feeds/ethereum/mainnet/ada-usd
+const ADA_USD_FEED: &str = "0xAE48c91dF1fE419994FfDa27da09D5aC69c30f55";
+
+/// Selector of `latestRoundData()`
+const LATEST_ROUND_DATA: &str = "0xfeaf968c";
+
+/// The ADA/USD feed is updated at least once a day
+const MAX_FEED_AGE_SECS: u64 = 25 * 60 * 60;
+
+/// Feed answer along with the time it was updated
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RoundData {
+    answer: f64,
+    updated_at: u64,
+}
+
+/// Decode the `(roundId, answer, startedAt, updatedAt, answeredInRound)` tuple returned by
+/// `latestRoundData()`, the answer has the given decimals
+fn parse_round_data(hex_result: &str, decimals: i32) -> Option<RoundData> {
+    let hex = hex_result.strip_prefix("0x")?;
+    if hex.len() < 5 * 64 {
+        return None;
+    }
+    // words are big endian, the values of a price feed fit in the low 128 bits
+    let word = |i: usize| u128::from_str_radix(&hex[i * 64 + 32..(i + 1) * 64], 16).ok();
+    // a negative answer would have the high bits set
+    if u128::from_str_radix(&hex[64..96], 16).ok()? != 0 {
+        return None;
+    }
+    Some(RoundData {
+        answer: word(1)? as f64 / 10f64.powi(decimals),
+        updated_at: word(3)? as u64,
+    })
+}
+
+async fn latest_round_data(feed: &str, decimals: i32) -> Result<RoundData, DataPointSourceError> {
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "eth_call",
+        "params": [{ "to": feed, "data": LATEST_ROUND_DATA }, "latest"],
+        "id": 1,
+    });
+    let resp = reqwest::Client::new()
+        .post(ETHEREUM_RPC_URL)
+        .header("Content-Type", "application/json")
+        .body(request.to_string())
+        .send()
+        .await?;
+    let resp_json = json::parse(&resp.text().await?)?;
+    resp_json["result"]
+        .as_str()
+        .and_then(|result| parse_round_data(result, decimals))
+        .ok_or_else(|| DataPointSourceError::JsonMissingField {
+            field: "result as latestRoundData".to_string(),
+            json: resp_json.dump(),
+        })
+}
+
+fn check_age(feed: &'static str, updated_at: u64) -> Result<(), DataPointSourceError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let age_secs = now.saturating_sub(updated_at);
+    if age_secs > MAX_FEED_AGE_SECS {
+        return Err(DataPointSourceError::StaleFeed { feed, age_secs });
+    }
+    Ok(())
+}
+
+pub async fn get_usd_lovelace() -> Result<AssetsExchangeRate<Usd, Lovelace>, DataPointSourceError> {
+    let round = latest_round_data(ADA_USD_FEED, 8).await?;
+    check_age("chainlink ADA/USD", round.updated_at)?;
+    Ok(AssetsExchangeRate {
+        per1: Usd {},
+        get: Lovelace {},
+        rate: Lovelace::from_ada(1.0 / round.answer),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_round_data() {
+        let word = |v: u64| format!("{:064x}", v);
+        let result = format!(
+            "0x{}{}{}{}{}",
+            word(1234),
+            word(45_000_000),
+            word(1_700_000_000),
+            word(1_700_000_100),
+            word(1234)
+        );
+        assert_eq!(
+            parse_round_data(&result, 8),
+            Some(RoundData {
+                answer: 0.45,
+                updated_at: 1_700_000_100
+            })
+        );
+        assert_eq!(parse_round_data("0x", 8), None);
+    }
+}
@@ -0,0 +1,301 @@
+//! Quarantine of the failing datapoint sources (`source_circuit_breaker` in the oracle config).
+//!
+//! A source is quarantined after `consecutive_failures` failed fetches in a row, or when more than
+//! `max_error_rate_percent` of its last `error_rate_window` fetches failed. A quarantined source is
+//! left out of the aggregated rate without being fetched for `cooldown_secs`, then it is tried
+//! again: a success ends the quarantine, a failure starts a new one right away. The thresholds are
+//! set for all the sources and can be overridden per source name in `sources`.
+//!
+//! The state is kept per pool, source and rate (e.g. CoinGecko ERG/USD and ERG/XAU apart) in
+//! memory, so it starts over when oracle-core restarts.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+pub const DEFAULT_CONSECUTIVE_FAILURES: u32 = 5;
+pub const DEFAULT_ERROR_RATE_WINDOW: usize = 20;
+pub const DEFAULT_MAX_ERROR_RATE_PERCENT: f64 = 50.0;
+pub const DEFAULT_COOLDOWN_SECS: u64 = 300;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct BreakerThresholds {
+    /// Failed fetches in a row quarantining the source (default 5)
+    pub consecutive_failures: Option<u32>,
+    /// Number of recent fetches the error rate is computed over (default 20)
+    pub error_rate_window: Option<usize>,
+    /// Error rate over the window quarantining the source (default 50%)
+    pub max_error_rate_percent: Option<f64>,
+    /// Seconds the source is left out before it is tried again (default 300)
+    pub cooldown_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct CircuitBreakerConfig {
+    pub consecutive_failures: Option<u32>,
+    pub error_rate_window: Option<usize>,
+    pub max_error_rate_percent: Option<f64>,
+    pub cooldown_secs: Option<u64>,
+    /// Thresholds overridden per source name, e.g. `htx`
+    #[serde(default)]
+    pub sources: BTreeMap<String, BreakerThresholds>,
+}
+
+/// Thresholds of a source with the defaults filled in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceThresholds {
+    pub consecutive_failures: u32,
+    pub error_rate_window: usize,
+    pub max_error_rate_percent: f64,
+    pub cooldown: Duration,
+}
+
+impl CircuitBreakerConfig {
+    pub fn thresholds(&self, source: &str) -> SourceThresholds {
+        let overrides = self.sources.get(source).cloned().unwrap_or_default();
+        SourceThresholds {
+            consecutive_failures: overrides
+                .consecutive_failures
+                .or(self.consecutive_failures)
+                .unwrap_or(DEFAULT_CONSECUTIVE_FAILURES)
+                .max(1),
+            error_rate_window: overrides
+                .error_rate_window
+                .or(self.error_rate_window)
+                .unwrap_or(DEFAULT_ERROR_RATE_WINDOW)
+                .max(1),
+            max_error_rate_percent: overrides
+                .max_error_rate_percent
+                .or(self.max_error_rate_percent)
+                .unwrap_or(DEFAULT_MAX_ERROR_RATE_PERCENT),
+            cooldown: Duration::from_secs(
+                overrides
+                    .cooldown_secs
+                    .or(self.cooldown_secs)
+                    .unwrap_or(DEFAULT_COOLDOWN_SECS),
+            ),
+        }
+    }
+}
+
+/// Recent fetches of a single source
+#[derive(Debug, Clone, Default)]
+pub struct SourceBreaker {
+    consecutive_failures: u32,
+    /// Outcomes of the last fetches, `true` for a failure
+    failures: VecDeque<bool>,
+    quarantined_until: Option<Instant>,
+}
+
+impl SourceBreaker {
+    pub fn is_quarantined(&self, now: Instant) -> bool {
+        self.quarantined_until.map_or(false, |until| now < until)
+    }
+
+    /// Record a fetch, returns whether it quarantined the source
+    pub fn record(&mut self, failed: bool, now: Instant, thresholds: &SourceThresholds) -> bool {
+        // the first fetch after the cool-down decides alone
+        if self.quarantined_until.take().is_some() {
+            if failed {
+                self.quarantined_until = Some(now + thresholds.cooldown);
+                return true;
+            }
+            *self = SourceBreaker::default();
+            return false;
+        }
+        if failed {
+            self.consecutive_failures += 1;
+        } else {
+            self.consecutive_failures = 0;
+        }
+        if self.failures.len() >= thresholds.error_rate_window {
+            self.failures.pop_front();
+        }
+        self.failures.push_back(failed);
+        let window_failures = self.failures.iter().filter(|failed| **failed).count();
+        let error_rate_exceeded = self.failures.len() == thresholds.error_rate_window
+            && window_failures as f64 * 100.0
+                > thresholds.max_error_rate_percent * thresholds.error_rate_window as f64;
+        if self.consecutive_failures >= thresholds.consecutive_failures || error_rate_exceeded {
+            *self = SourceBreaker {
+                quarantined_until: Some(now + thresholds.cooldown),
+                ..SourceBreaker::default()
+            };
+            return true;
+        }
+        false
+    }
+}
+
+/// A source quarantined for the rate of `pair` for `cooldown`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceQuarantine {
+    pub source: String,
+    pub pair: String,
+    pub cooldown: Duration,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    breakers: Mutex<HashMap<(String, String), SourceBreaker>>,
+    /// Quarantines not taken by the pool loop yet, sent as alerts
+    quarantines: Mutex<Vec<SourceQuarantine>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        CircuitBreaker {
+            config,
+            breakers: Mutex::new(HashMap::new()),
+            quarantines: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn config(&self) -> &CircuitBreakerConfig {
+        &self.config
+    }
+
+    /// Whether the source is quarantined for the rate of `pair`
+    pub fn is_quarantined(&self, source: &str, pair: &str) -> bool {
+        self.breakers
+            .lock()
+            .unwrap()
+            .get(&(source.to_string(), pair.to_string()))
+            .map_or(false, |breaker| breaker.is_quarantined(Instant::now()))
+    }
+
+    /// Record a fetch of the source, returns the quarantine it started. The quarantine is also
+    /// kept for [`CircuitBreaker::take_quarantines`].
+    pub fn record(&self, source: &str, pair: &str, failed: bool) -> Option<SourceQuarantine> {
+        let thresholds = self.config.thresholds(source);
+        let quarantined = self
+            .breakers
+            .lock()
+            .unwrap()
+            .entry((source.to_string(), pair.to_string()))
+            .or_default()
+            .record(failed, Instant::now(), &thresholds);
+        if !quarantined {
+            return None;
+        }
+        log::warn!(
+            "Datapoint source {} ({}) is quarantined for {}s by the circuit breaker",
+            source,
+            pair,
+            thresholds.cooldown.as_secs()
+        );
+        let quarantine = SourceQuarantine {
+            source: source.to_string(),
+            pair: pair.to_string(),
+            cooldown: thresholds.cooldown,
+        };
+        self.quarantines.lock().unwrap().push(quarantine.clone());
+        Some(quarantine)
+    }
+
+    /// The quarantines started since the last call
+    pub fn take_quarantines(&self) -> Vec<SourceQuarantine> {
+        std::mem::take(&mut *self.quarantines.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thresholds() -> SourceThresholds {
+        CircuitBreakerConfig {
+            consecutive_failures: Some(3),
+            error_rate_window: Some(4),
+            max_error_rate_percent: Some(50.0),
+            cooldown_secs: Some(60),
+            sources: BTreeMap::new(),
+        }
+        .thresholds("htx")
+    }
+
+    #[test]
+    fn test_thresholds_overrides() {
+        let config = CircuitBreakerConfig {
+            cooldown_secs: Some(60),
+            sources: [(
+                "htx".to_string(),
+                BreakerThresholds {
+                    consecutive_failures: Some(2),
+                    ..BreakerThresholds::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..CircuitBreakerConfig::default()
+        };
+        let htx = config.thresholds("htx");
+        assert_eq!(htx.consecutive_failures, 2);
+        assert_eq!(htx.cooldown, Duration::from_secs(60));
+        let mexc = config.thresholds("mexc");
+        assert_eq!(mexc.consecutive_failures, DEFAULT_CONSECUTIVE_FAILURES);
+        assert_eq!(mexc.error_rate_window, DEFAULT_ERROR_RATE_WINDOW);
+    }
+
+    #[test]
+    fn test_consecutive_failures_and_cooldown() {
+        let thresholds = thresholds();
+        let start = Instant::now();
+        let mut breaker = SourceBreaker::default();
+        assert!(!breaker.record(true, start, &thresholds));
+        assert!(!breaker.record(true, start, &thresholds));
+        assert!(breaker.record(true, start, &thresholds));
+        assert!(breaker.is_quarantined(start + Duration::from_secs(59)));
+        let after_cooldown = start + Duration::from_secs(60);
+        assert!(!breaker.is_quarantined(after_cooldown));
+        // a failure right after the cool-down quarantines the source again
+        assert!(breaker.record(true, after_cooldown, &thresholds));
+        assert!(breaker.is_quarantined(after_cooldown));
+        let after_cooldown = after_cooldown + Duration::from_secs(60);
+        assert!(!breaker.record(false, after_cooldown, &thresholds));
+        assert!(!breaker.record(true, after_cooldown, &thresholds));
+        assert!(!breaker.is_quarantined(after_cooldown));
+    }
+
+    #[test]
+    fn test_error_rate() {
+        let thresholds = thresholds();
+        let now = Instant::now();
+        let mut breaker = SourceBreaker::default();
+        // 2 failures out of 4 is not above 50%
+        for failed in [true, false, true, false] {
+            assert!(!breaker.record(failed, now, &thresholds));
+        }
+        // the oldest failure leaves the window
+        assert!(!breaker.record(true, now, &thresholds));
+        assert!(breaker.record(true, now, &thresholds));
+    }
+
+    #[test]
+    fn test_quarantines_are_taken_once() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            consecutive_failures: Some(2),
+            cooldown_secs: Some(60),
+            ..CircuitBreakerConfig::default()
+        });
+        assert_eq!(breaker.record("htx", "ERG per USD", true), None);
+        let quarantine = SourceQuarantine {
+            source: "htx".to_string(),
+            pair: "ERG per USD".to_string(),
+            cooldown: Duration::from_secs(60),
+        };
+        assert_eq!(
+            breaker.record("htx", "ERG per USD", true),
+            Some(quarantine.clone())
+        );
+        assert_eq!(breaker.take_quarantines(), vec![quarantine]);
+        assert!(breaker.take_quarantines().is_empty());
+    }
+}
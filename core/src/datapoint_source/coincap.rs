@@ -1,3 +1,4 @@
+use super::ada_usd::Lovelace;
 use super::assets_exchange_rate::AssetsExchangeRate;
 use super::assets_exchange_rate::NanoErg;
 use super::assets_exchange_rate::Usd;
@@ -33,7 +34,33 @@ pub async fn get_usd_nanoerg() -> Result<AssetsExchangeRate<Usd, NanoErg>, DataP
     }
 }
 
-#[cfg(test)]
+pub async fn get_usd_lovelace() -> Result<AssetsExchangeRate<Usd, Lovelace>, DataPointSourceError> {
+    // see https://coincap.io/assets/cardano
+    let url = "https://api.coincap.io/v2/assets/cardano";
+    let resp = reqwest::get(url).await?;
+    let price_json = json::parse(&resp.text().await?)?;
+    if let Some(p) = price_json["data"]["priceUsd"].as_str() {
+        let p_float = p
+            .parse::<f64>()
+            .map_err(|_| DataPointSourceError::JsonMissingField {
+                field: "data.priceUsd as f64".to_string(),
+                json: price_json.dump(),
+            })?;
+        let rate = AssetsExchangeRate {
+            per1: Usd {},
+            get: Lovelace {},
+            rate: Lovelace::from_ada(1.0 / p_float),
+        };
+        Ok(rate)
+    } else {
+        Err(DataPointSourceError::JsonMissingField {
+            field: "cardano.priceUsd as string".to_string(),
+            json: price_json.dump(),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "coingecko"))]
 mod tests {
     use super::super::coingecko;
     use super::*;
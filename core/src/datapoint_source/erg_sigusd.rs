@@ -0,0 +1,58 @@
+//! Obtains the nanoErg per 1 SigUSD cent rate, the scaling of the stablecoin price read by the
+//! Djed (SigmaUSD) bank contract on Ergo
+
+use futures::Future;
+
+use super::aggregator::NamedSource;
+use super::assets_exchange_rate::Asset;
+use super::assets_exchange_rate::AssetsExchangeRate;
+use super::assets_exchange_rate::NanoErg;
+use super::assets_exchange_rate::Usd;
+use super::erg_usd::nanoerg_usd_sources;
+use super::DataPointSourceError;
+use super::SourceChecks;
+
+/// Smallest unit of SigUSD (2 decimals), pegged to 0.01 USD
+#[derive(Debug, Clone, Copy)]
+pub struct SigUsdCent {}
+
+impl Asset for SigUsdCent {}
+
+impl SigUsdCent {
+    pub fn from_usd(usd: f64) -> f64 {
+        usd / 100.0
+    }
+}
+
+/// The nanoErg/USD sources scaled to the SigUSD cent
+pub fn nanoerg_sigusd_sources(checks: &SourceChecks) -> Vec<NamedSource<SigUsdCent, NanoErg>> {
+    nanoerg_usd_sources(checks)
+        .into_iter()
+        .map(|(name, usd_rate)| -> NamedSource<SigUsdCent, NanoErg> {
+            (name, Box::pin(per_sigusd_cent(usd_rate)))
+        })
+        .collect()
+}
+
+async fn per_sigusd_cent(
+    usd_rate: impl Future<Output = Result<AssetsExchangeRate<Usd, NanoErg>, DataPointSourceError>>,
+) -> Result<AssetsExchangeRate<SigUsdCent, NanoErg>, DataPointSourceError> {
+    let usd_rate = usd_rate.await?;
+    Ok(AssetsExchangeRate {
+        per1: SigUsdCent {},
+        get: NanoErg {},
+        rate: SigUsdCent::from_usd(usd_rate.rate),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sigusd_cent_scaling() {
+        // 1 ERG = 2 USD
+        let nanoerg_per_usd = NanoErg::from_erg(0.5);
+        assert_eq!(SigUsdCent::from_usd(nanoerg_per_usd), 5_000_000.0);
+    }
+}
@@ -1,22 +1,43 @@
 //! Obtains the nanoErg/USD rate
 
-use std::pin::Pin;
-
-use futures::Future;
-
-use super::assets_exchange_rate::AssetsExchangeRate;
+use super::aggregator::NamedSource;
 use super::assets_exchange_rate::NanoErg;
 use super::assets_exchange_rate::Usd;
+#[cfg(feature = "coincap")]
 use super::coincap;
+#[cfg(feature = "coingecko")]
 use super::coingecko;
-use super::DataPointSourceError;
+#[cfg(feature = "htx")]
+use super::htx;
+#[cfg(feature = "mexc")]
+use super::mexc;
+use super::stablecoin::checked_sources;
+use super::stablecoin::Stablecoin;
+use super::SourceChecks;
+
+/// Only the exchanges enabled by their cargo features, the rates quoted in a stablecoin are
+/// checked against its USD price with `stablecoin_check` in the oracle config
+pub fn nanoerg_usd_sources(checks: &SourceChecks) -> Vec<NamedSource<Usd, NanoErg>> {
+    #[allow(unused_mut)]
+    let mut sources: Vec<NamedSource<Usd, NanoErg>> = Vec::new();
+    #[cfg(feature = "coincap")]
+    sources.push(("coincap", Box::pin(coincap::get_usd_nanoerg())));
+    #[cfg(feature = "coingecko")]
+    sources.push(("coingecko", Box::pin(coingecko::get_usd_nanoerg())));
+    #[cfg(feature = "htx")]
+    sources.push(("htx", Box::pin(htx::get_usd_nanoerg())));
+    #[cfg(feature = "mexc")]
+    sources.push(("mexc", Box::pin(mexc::get_usd_nanoerg())));
+    match &checks.stablecoin_check {
+        Some(config) => checked_sources(sources, quote_stablecoin, config),
+        None => sources,
+    }
+}
 
-#[allow(clippy::type_complexity)]
-pub fn nanoerg_usd_sources() -> Vec<
-    Pin<Box<dyn Future<Output = Result<AssetsExchangeRate<Usd, NanoErg>, DataPointSourceError>>>>,
-> {
-    vec![
-        Box::pin(coincap::get_usd_nanoerg()),
-        Box::pin(coingecko::get_usd_nanoerg()),
-    ]
+/// Stablecoin the exchange quotes ERG in, taken as USD
+pub fn quote_stablecoin(source: &str) -> Option<Stablecoin> {
+    match source {
+        "htx" | "mexc" => Some(Stablecoin::Usdt),
+        _ => None,
+    }
 }
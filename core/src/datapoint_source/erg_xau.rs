@@ -1,17 +1,32 @@
 //! Obtains the nanoErg per 1 XAU (troy ounce of gold) rate
+//!
+//! The USD gold prices of the independent gold price providers are cross-validated against each
+//! other, and each price kept is combined with the aggregated nanoErg/USD rate. The direct ERG/XAU
+//! rate of CoinGecko is averaged with them, and used alone when no gold price is available.
 
-use std::pin::Pin;
-
-use futures::Future;
-
+use super::aggregator::aggregate_with_sources;
+use super::aggregator::fetch;
 use super::aggregator::fetch_aggregated;
+use super::aggregator::median;
+use super::aggregator::NamedSource;
 use super::assets_exchange_rate::Asset;
 use super::assets_exchange_rate::AssetsExchangeRate;
 use super::assets_exchange_rate::NanoErg;
+use super::assets_exchange_rate::Usd;
+#[cfg(feature = "bitpanda")]
 use super::bitpanda;
+#[cfg(feature = "coingecko")]
 use super::coingecko;
 use super::erg_usd::nanoerg_usd_sources;
+#[cfg(feature = "gold-api")]
+use super::gold_api;
 use super::DataPointSourceError;
+use super::SourceChecks;
+use super::SourceRate;
+use super::SourceTrial;
+
+/// Gold prices further than this from the median gold price are left out
+pub const MAX_GOLD_PRICE_DEVIATION_PERCENT: f64 = 2.0;
 
 #[derive(Debug, Clone, Copy)]
 pub struct KgAu {}
@@ -34,26 +49,104 @@ impl KgAu {
     }
 }
 
-#[allow(clippy::type_complexity)]
-pub fn nanoerg_kgau_sources() -> Vec<
-    Pin<Box<dyn Future<Output = Result<AssetsExchangeRate<KgAu, NanoErg>, DataPointSourceError>>>>,
-> {
-    vec![
-        Box::pin(coingecko::get_kgau_nanoerg()),
-        Box::pin(combined_kgau_nanoerg()),
-    ]
+/// Sources of the direct ERG/XAU rate, only the exchanges enabled by their cargo features
+pub fn nanoerg_kgau_sources() -> Vec<NamedSource<KgAu, NanoErg>> {
+    #[allow(unused_mut)]
+    let mut sources: Vec<NamedSource<KgAu, NanoErg>> = Vec::new();
+    #[cfg(feature = "coingecko")]
+    sources.push(("coingecko", Box::pin(coingecko::get_kgau_nanoerg())));
+    sources
+}
+
+/// Gold price providers, only the ones enabled by their cargo features
+pub fn kgau_usd_sources() -> Vec<NamedSource<KgAu, Usd>> {
+    #[allow(unused_mut)]
+    let mut sources: Vec<NamedSource<KgAu, Usd>> = Vec::new();
+    #[cfg(feature = "bitpanda")]
+    sources.push(("bitpanda", Box::pin(bitpanda::get_kgau_usd())));
+    #[cfg(feature = "gold-api")]
+    sources.push(("gold_api", Box::pin(gold_api::get_kgau_usd())));
+    sources
 }
 
-pub async fn combined_kgau_nanoerg(
-) -> Result<AssetsExchangeRate<KgAu, NanoErg>, DataPointSourceError> {
-    let kgau_usd_rate = bitpanda::get_kgau_usd().await?;
-    let aggregated_usd_nanoerg_rate = fetch_aggregated(nanoerg_usd_sources()).await?;
-    let rate = kgau_usd_rate.rate * aggregated_usd_nanoerg_rate.rate;
-    Ok(AssetsExchangeRate {
-        per1: KgAu {},
-        get: NanoErg {},
-        rate,
-    })
+/// The gold prices within `MAX_GOLD_PRICE_DEVIATION_PERCENT` of their median. A single price is
+/// kept as it is. Fails when no price is close to the median, e.g. two providers too far apart to
+/// tell which one is wrong.
+pub fn cross_validated_gold_prices(
+    prices: Vec<(&'static str, AssetsExchangeRate<KgAu, Usd>)>,
+) -> Result<Vec<(&'static str, AssetsExchangeRate<KgAu, Usd>)>, DataPointSourceError> {
+    if prices.len() < 2 {
+        return Ok(prices);
+    }
+    let median_price = median(prices.iter().map(|(_, price)| *price).collect()).rate;
+    let (kept, left_out): (Vec<_>, Vec<_>) = prices.into_iter().partition(|(_, price)| {
+        (price.rate - median_price).abs() * 100.0 <= MAX_GOLD_PRICE_DEVIATION_PERCENT * median_price
+    });
+    if kept.is_empty() {
+        return Err(DataPointSourceError::SourcesDisagree {
+            sources: left_out
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", "),
+            max_deviation_percent: MAX_GOLD_PRICE_DEVIATION_PERCENT,
+        });
+    }
+    for (name, price) in &left_out {
+        log::warn!(
+            "Gold price {} of {} is more than {}% from the median {}, leaving it out",
+            price.rate,
+            name,
+            MAX_GOLD_PRICE_DEVIATION_PERCENT,
+            median_price
+        );
+    }
+    Ok(kept)
+}
+
+/// The aggregated nanoErg/KgAu rate along with the raw rates of the sources, the cross-validated
+/// gold prices combined with the nanoErg/USD rate and the direct ERG/XAU rates
+pub async fn fetch_nanoerg_kgau_with_sources(
+    checks: &SourceChecks,
+    trials: &mut Vec<SourceTrial>,
+) -> Result<(AssetsExchangeRate<KgAu, NanoErg>, Vec<SourceRate>), DataPointSourceError> {
+    let (mut gold_price_trials, mut direct_rate_trials) = (Vec::new(), Vec::new());
+    let (gold_prices, direct_rates) = futures::join!(
+        fetch(kgau_usd_sources(), checks, &mut gold_price_trials),
+        fetch(nanoerg_kgau_sources(), checks, &mut direct_rate_trials)
+    );
+    trials.append(&mut gold_price_trials);
+    trials.append(&mut direct_rate_trials);
+    let mut rates = direct_rates?;
+    let gold_prices = match cross_validated_gold_prices(gold_prices?) {
+        Ok(gold_prices) => gold_prices,
+        Err(e) if !rates.is_empty() => {
+            log::warn!("{}, using the direct ERG/XAU rate only", e);
+            Vec::new()
+        }
+        Err(e) => return Err(e),
+    };
+    if !gold_prices.is_empty() {
+        match fetch_aggregated(nanoerg_usd_sources(checks), checks, trials).await {
+            Ok(usd_nanoerg_rate) => {
+                rates.extend(gold_prices.into_iter().map(|(name, kgau_usd_rate)| {
+                    (
+                        name,
+                        AssetsExchangeRate {
+                            per1: KgAu {},
+                            get: NanoErg {},
+                            rate: kgau_usd_rate.rate * usd_nanoerg_rate.rate,
+                        },
+                    )
+                }))
+            }
+            Err(e) => log::warn!(
+                "Failed to fetch the nanoErg/USD rate for the gold prices: {}",
+                e
+            ),
+        }
+    }
+    aggregate_with_sources(rates)
 }
 
 #[cfg(test)]
@@ -61,9 +154,54 @@ mod tests {
 
     use super::*;
 
+    fn gold_prices(
+        prices: &[(&'static str, f64)],
+    ) -> Vec<(&'static str, AssetsExchangeRate<KgAu, Usd>)> {
+        prices
+            .iter()
+            .map(|&(name, rate)| {
+                (
+                    name,
+                    AssetsExchangeRate {
+                        per1: KgAu {},
+                        get: Usd {},
+                        rate,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn names(prices: &[(&'static str, AssetsExchangeRate<KgAu, Usd>)]) -> Vec<&'static str> {
+        prices.iter().map(|(name, _)| *name).collect()
+    }
+
+    #[test]
+    fn test_cross_validated_gold_prices() {
+        // a single provider is the fallback
+        let single = cross_validated_gold_prices(gold_prices(&[("a", 100.0)])).unwrap();
+        assert_eq!(names(&single), vec!["a"]);
+        let agreeing =
+            cross_validated_gold_prices(gold_prices(&[("a", 100.0), ("b", 101.0)])).unwrap();
+        assert_eq!(names(&agreeing), vec!["a", "b"]);
+        let one_off =
+            cross_validated_gold_prices(gold_prices(&[("a", 100.0), ("b", 150.0), ("c", 101.0)]))
+                .unwrap();
+        assert_eq!(names(&one_off), vec!["a", "c"]);
+        assert!(matches!(
+            cross_validated_gold_prices(gold_prices(&[("a", 100.0), ("b", 110.0)])),
+            Err(DataPointSourceError::SourcesDisagree { .. })
+        ));
+    }
+
+    #[cfg(all(feature = "coingecko", feature = "bitpanda"))]
     #[test]
     fn test_kgau_nanoerg_combined() {
-        let combined = tokio_test::block_on(combined_kgau_nanoerg()).unwrap();
+        let (combined, _) = tokio_test::block_on(fetch_nanoerg_kgau_with_sources(
+            &SourceChecks::default(),
+            &mut Vec::new(),
+        ))
+        .unwrap();
         let coingecko = tokio_test::block_on(coingecko::get_kgau_nanoerg()).unwrap();
         let deviation_from_coingecko = (combined.rate - coingecko.rate).abs() / coingecko.rate;
         assert!(
@@ -0,0 +1,47 @@
+use super::assets_exchange_rate::AssetsExchangeRate;
+use super::assets_exchange_rate::Usd;
+use super::erg_xau::KgAu;
+use super::DataPointSourceError;
+
+#[derive(Debug, Clone)]
+pub struct GoldApi;
+
+pub async fn get_kgau_usd() -> Result<AssetsExchangeRate<KgAu, Usd>, DataPointSourceError> {
+    // see https://gold-api.com, free and without an API key
+    let url = "https://api.gold-api.com/price/XAU";
+    let resp = reqwest::get(url).await?;
+    let json = json::parse(&resp.text().await?)?;
+    if let Some(p) = json["price"].as_f64() {
+        // USD price of 1 troy ounce of gold
+        let usd_per_kgau = KgAu::from_troy_ounce(p);
+        let rate = AssetsExchangeRate {
+            per1: KgAu {},
+            get: Usd {},
+            rate: usd_per_kgau,
+        };
+        Ok(rate)
+    } else {
+        Err(DataPointSourceError::JsonMissingField {
+            field: "price as f64".to_string(),
+            json: json.dump(),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "bitpanda"))]
+mod tests {
+    use super::super::bitpanda;
+    use super::*;
+
+    #[test]
+    fn test_kgau_usd_price() {
+        let pair = tokio_test::block_on(get_kgau_usd()).unwrap();
+        let bitpanda = tokio_test::block_on(bitpanda::get_kgau_usd()).unwrap();
+        assert!(pair.rate > 0.0);
+        let deviation_from_bitpanda = (pair.rate - bitpanda.rate).abs() / bitpanda.rate;
+        assert!(
+            deviation_from_bitpanda < 0.05,
+            "up to 5% deviation is allowed"
+        );
+    }
+}
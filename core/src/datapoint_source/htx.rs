@@ -0,0 +1,52 @@
+use super::assets_exchange_rate::AssetsExchangeRate;
+use super::assets_exchange_rate::NanoErg;
+use super::assets_exchange_rate::Usd;
+use super::DataPointSourceError;
+
+#[derive(Debug, Clone)]
+pub struct Htx;
+
+pub async fn get_usd_nanoerg() -> Result<AssetsExchangeRate<Usd, NanoErg>, DataPointSourceError> {
+    // see https://www.htx.com/en-us/trade/erg_usdt, USDT is taken as USD
+    let url = "https://api.huobi.pro/market/detail/merged?symbol=ergusdt";
+    let resp = reqwest::get(url).await?;
+    let price_json = json::parse(&resp.text().await?)?;
+    if price_json["status"].as_str() != Some("ok") {
+        return Err(DataPointSourceError::JsonMissingField {
+            field: "status ok".to_string(),
+            json: price_json.dump(),
+        });
+    }
+    if let Some(p) = price_json["tick"]["close"].as_f64() {
+        let nanoerg_per_usd = NanoErg::from_erg(1.0 / p);
+        let rate = AssetsExchangeRate {
+            per1: Usd {},
+            get: NanoErg {},
+            rate: nanoerg_per_usd,
+        };
+        Ok(rate)
+    } else {
+        Err(DataPointSourceError::JsonMissingField {
+            field: "tick.close as f64".to_string(),
+            json: price_json.dump(),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "coingecko"))]
+mod tests {
+    use super::super::coingecko;
+    use super::*;
+
+    #[test]
+    fn test_erg_usd_price() {
+        let pair = tokio_test::block_on(get_usd_nanoerg()).unwrap();
+        let coingecko = tokio_test::block_on(coingecko::get_usd_nanoerg()).unwrap();
+        assert!(pair.rate > 0.0);
+        let deviation_from_coingecko = (pair.rate - coingecko.rate).abs() / coingecko.rate;
+        assert!(
+            deviation_from_coingecko < 0.05,
+            "up to 5% deviation is allowed"
+        );
+    }
+}
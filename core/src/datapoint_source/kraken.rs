@@ -0,0 +1,46 @@
+use super::stablecoin::Stablecoin;
+use super::DataPointSourceError;
+
+#[derive(Debug, Clone)]
+pub struct Kraken;
+
+/// USD price of the stablecoin on its fiat USD pair
+pub async fn get_stablecoin_usd(stablecoin: Stablecoin) -> Result<f64, DataPointSourceError> {
+    // see https://docs.kraken.com/rest/#tag/Spot-Market-Data/operation/getTickerInformation
+    let pair = match stablecoin {
+        Stablecoin::Usdt => "USDTZUSD",
+        Stablecoin::Usdc => "USDCUSD",
+    };
+    let url = format!("https://api.kraken.com/0/public/Ticker?pair={}", pair);
+    let resp = reqwest::get(url).await?;
+    let price_json = json::parse(&resp.text().await?)?;
+    // the result is keyed by the pair name, the last trade price is the first item of `c`
+    let price = price_json["result"]
+        .entries()
+        .next()
+        .and_then(|(_, ticker)| ticker["c"][0].as_str())
+        .and_then(|p| p.parse::<f64>().ok())
+        .filter(|p| *p > 0.0);
+    price.ok_or_else(|| DataPointSourceError::JsonMissingField {
+        field: "result.<pair>.c[0] as f64".to_string(),
+        json: price_json.dump(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stablecoin_usd_price() {
+        for stablecoin in [Stablecoin::Usdt, Stablecoin::Usdc] {
+            let price = tokio_test::block_on(get_stablecoin_usd(stablecoin)).unwrap();
+            assert!(
+                (price - 1.0).abs() < 0.05,
+                "{} is at {} USD",
+                stablecoin,
+                price
+            );
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Obtains the milkADA per 1 USD rate for the Djed deployment on Milkomeda C1.
+//!
+//! milkADA is bridged 1:1 from ADA, so the rate is the ADA/USD rate of the exchanges and the
+//! Chainlink and Pyth feeds. It is published in the lovelace-sized unit (10^-6 milkADA) as the
+//! 18 decimals of milkADA would overflow the 64 bit datapoint.
+
+use futures::Future;
+
+use super::ada_usd::Lovelace;
+use super::aggregator::NamedSource;
+use super::assets_exchange_rate::Asset;
+use super::assets_exchange_rate::AssetsExchangeRate;
+use super::assets_exchange_rate::Usd;
+#[cfg(feature = "chainlink")]
+use super::chainlink;
+#[cfg(feature = "coincap")]
+use super::coincap;
+#[cfg(feature = "coingecko")]
+use super::coingecko;
+#[cfg(feature = "pyth")]
+use super::pyth;
+use super::DataPointSourceError;
+
+/// Responding sources needed for a datapoint, the median is taken so one broken source can't
+/// move the rate
+pub const MIN_MILKADA_SOURCES: usize = 2;
+
+/// 10^-6 milkADA, worth one lovelace
+#[derive(Debug, Clone, Copy)]
+pub struct MicroMilkAda {}
+
+impl Asset for MicroMilkAda {}
+
+/// Only the exchanges and feeds enabled by their cargo features
+pub fn usd_micro_milkada_sources() -> Vec<NamedSource<Usd, MicroMilkAda>> {
+    #[allow(unused_mut)]
+    let mut sources: Vec<NamedSource<Usd, MicroMilkAda>> = Vec::new();
+    #[cfg(feature = "coingecko")]
+    sources.push((
+        "coingecko",
+        Box::pin(bridged(coingecko::get_usd_lovelace())),
+    ));
+    #[cfg(feature = "coincap")]
+    sources.push(("coincap", Box::pin(bridged(coincap::get_usd_lovelace()))));
+    #[cfg(feature = "chainlink")]
+    sources.push((
+        "chainlink",
+        Box::pin(bridged(chainlink::get_usd_lovelace())),
+    ));
+    #[cfg(feature = "pyth")]
+    sources.push(("pyth", Box::pin(bridged(pyth::get_usd_lovelace()))));
+    sources
+}
+
+/// The lovelace rate as the rate of the bridged milkADA
+async fn bridged(
+    lovelace_rate: impl Future<Output = Result<AssetsExchangeRate<Usd, Lovelace>, DataPointSourceError>>,
+) -> Result<AssetsExchangeRate<Usd, MicroMilkAda>, DataPointSourceError> {
+    let lovelace_rate = lovelace_rate.await?;
+    Ok(AssetsExchangeRate {
+        per1: Usd {},
+        get: MicroMilkAda {},
+        rate: lovelace_rate.rate,
+    })
+}
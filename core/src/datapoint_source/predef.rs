@@ -1,33 +1,84 @@
-use crate::oracle_types::Rate;
+use std::time::Duration;
 
 use super::ada_usd::usd_lovelace_sources;
-use super::aggregator::fetch_aggregated;
+use super::aggregator::fetch_aggregated_with_sources;
+use super::aggregator::fetch_median_with_sources;
+use super::erg_sigusd::nanoerg_sigusd_sources;
 use super::erg_usd::nanoerg_usd_sources;
-use super::erg_xau::nanoerg_kgau_sources;
+use super::erg_xau::fetch_nanoerg_kgau_with_sources;
+use super::milkada_usd::usd_micro_milkada_sources;
+use super::milkada_usd::MIN_MILKADA_SOURCES;
 use super::DataPointSourceError;
+use super::FetchedDatapoint;
 use super::PredefinedDataPointSource;
+use super::SourceChecks;
+use super::SourceTrial;
 
+/// The fetches still pending after this are cancelled
+const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Fetch the sources concurrently on the runtime, blocking the calling main loop iteration until
+/// they answer or time out. The iteration needs the rate right away to build its actions, which
+/// are blocking like the node client.
 pub fn sync_fetch_predef_source_aggregated(
     predef_datasource: &PredefinedDataPointSource,
-) -> Result<Rate, DataPointSourceError> {
-    let tokio_runtime = tokio::runtime::Runtime::new().unwrap();
-    let rate = tokio_runtime.block_on(fetch_predef_source_aggregated(predef_datasource))?;
-    Ok(rate)
+    checks: &SourceChecks,
+    trials: &mut Vec<SourceTrial>,
+) -> Result<FetchedDatapoint, DataPointSourceError> {
+    let fetch = async {
+        tokio::time::timeout(
+            FETCH_TIMEOUT,
+            fetch_predef_source_aggregated(predef_datasource, checks, trials),
+        )
+        .await
+        .map_err(|_| DataPointSourceError::Timeout(FETCH_TIMEOUT))?
+    };
+    match tokio::runtime::Handle::try_current() {
+        // main loop iterations run on the blocking threads of the `Run` runtime
+        Ok(handle) => handle.block_on(fetch),
+        Err(_) => tokio::runtime::Runtime::new().unwrap().block_on(fetch),
+    }
 }
 
 async fn fetch_predef_source_aggregated(
     predef_datasource: &PredefinedDataPointSource,
-) -> Result<Rate, DataPointSourceError> {
-    let rate_float = match predef_datasource {
+    checks: &SourceChecks,
+    trials: &mut Vec<SourceTrial>,
+) -> Result<FetchedDatapoint, DataPointSourceError> {
+    let (rate_float, sources) = match predef_datasource {
         PredefinedDataPointSource::NanoErgUsd => {
-            fetch_aggregated(nanoerg_usd_sources()).await?.rate
+            let (rate, sources) =
+                fetch_aggregated_with_sources(nanoerg_usd_sources(checks), checks, trials).await?;
+            (rate.rate, sources)
         }
         PredefinedDataPointSource::NanoErgXau => {
-            fetch_aggregated(nanoerg_kgau_sources()).await?.rate
+            let (rate, sources) = fetch_nanoerg_kgau_with_sources(checks, trials).await?;
+            (rate.rate, sources)
         }
         PredefinedDataPointSource::NanoAdaUsd => {
-            fetch_aggregated(usd_lovelace_sources()).await?.rate
+            let (rate, sources) =
+                fetch_aggregated_with_sources(usd_lovelace_sources(), checks, trials).await?;
+            (rate.rate, sources)
+        }
+        PredefinedDataPointSource::MicroMilkAdaUsd => {
+            let (rate, sources) = fetch_median_with_sources(
+                usd_micro_milkada_sources(),
+                MIN_MILKADA_SOURCES,
+                checks,
+                trials,
+            )
+            .await?;
+            (rate.rate, sources)
+        }
+        PredefinedDataPointSource::NanoErgSigUsd => {
+            let (rate, sources) =
+                fetch_aggregated_with_sources(nanoerg_sigusd_sources(checks), checks, trials)
+                    .await?;
+            (rate.rate, sources)
         }
     };
-    Ok((rate_float as i64).into())
+    Ok(FetchedDatapoint {
+        rate: (rate_float as i64).into(),
+        sources,
+    })
 }
@@ -0,0 +1,85 @@
+//! Pyth price feeds from the Hermes price service
+
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use super::ada_usd::Lovelace;
+use super::assets_exchange_rate::AssetsExchangeRate;
+use super::assets_exchange_rate::Usd;
+use super::DataPointSourceError;
+
+const HERMES_URL: &str = "https://hermes.pyth.network";
+
+/// ADA/USD feed id, see https://pyth.network/price-feeds/crypto-ada-usd
+const ADA_USD_FEED_ID: &str = "2a01deaec9e51a579277b34b122399984d0bbf57e2458a7e42fecd2829867a0d";
+
+/// Pyth publishes every few seconds, an older price means the feed is halted
+const MAX_PRICE_AGE_SECS: u64 = 10 * 60;
+
+/// Price of the `latest_price_feeds` response, `price * 10^expo` published at `publish_time`
+fn parse_price(feed_json: &json::JsonValue) -> Option<(f64, u64)> {
+    let price = &feed_json["price"];
+    let mantissa = price["price"].as_str()?.parse::<i64>().ok()?;
+    let expo = price["expo"].as_i32()?;
+    let publish_time = price["publish_time"].as_u64()?;
+    let price = if expo < 0 {
+        mantissa as f64 / 10f64.powi(-expo)
+    } else {
+        mantissa as f64 * 10f64.powi(expo)
+    };
+    Some((price, publish_time))
+}
+
+pub async fn get_usd_lovelace() -> Result<AssetsExchangeRate<Usd, Lovelace>, DataPointSourceError> {
+    let url = format!(
+        "{}/api/latest_price_feeds?ids[]={}",
+        HERMES_URL, ADA_USD_FEED_ID
+    );
+    let resp = reqwest::get(url).await?;
+    let price_json = json::parse(&resp.text().await?)?;
+    let (p, publish_time) = parse_price(&price_json[0])
+        .filter(|(p, _)| *p > 0.0)
+        .ok_or_else(|| DataPointSourceError::JsonMissingField {
+            field: "[0].price".to_string(),
+            json: price_json.dump(),
+        })?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let age_secs = now.saturating_sub(publish_time);
+    if age_secs > MAX_PRICE_AGE_SECS {
+        return Err(DataPointSourceError::StaleFeed {
+            feed: "pyth ADA/USD",
+            age_secs,
+        });
+    }
+    Ok(AssetsExchangeRate {
+        per1: Usd {},
+        get: Lovelace {},
+        rate: Lovelace::from_ada(1.0 / p),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_price() {
+        let feed_json = json::parse(
+            r#"{
+                "id": "2a01deaec9e51a579277b34b122399984d0bbf57e2458a7e42fecd2829867a0d",
+                "price": {
+                    "price": "45000000",
+                    "conf": "21000",
+                    "expo": -8,
+                    "publish_time": 1700000000
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(parse_price(&feed_json), Some((0.45, 1700000000)));
+        assert_eq!(parse_price(&json::JsonValue::Null), None);
+    }
+}
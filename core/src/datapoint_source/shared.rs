@@ -0,0 +1,105 @@
+//! Datapoint fetch shared by the pools of a multi-pool setup following the same feed
+//! (`share_datapoint_fetch_secs` in the oracle config).
+//!
+//! The pools with the same datapoint source (the same predefined source or custom script) reuse the
+//! datapoint fetched by one of them if it is not older than the configured age, each pool still
+//! builds and submits its own publish tx. A fetch in progress is awaited by the other pools instead
+//! of being made again, failed fetches are not shared.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+
+use super::DataPointSourceError;
+use super::FetchedDatapoint;
+
+type SharedFetch = Arc<Mutex<Option<(Instant, FetchedDatapoint)>>>;
+
+static SHARED_FETCHES: Lazy<Mutex<HashMap<String, SharedFetch>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Return the datapoint fetched for the source key within `max_age`, or fetch it
+pub(crate) fn fetch_shared<F>(
+    key: &str,
+    max_age: Duration,
+    fetch: F,
+) -> Result<FetchedDatapoint, DataPointSourceError>
+where
+    F: FnOnce() -> Result<FetchedDatapoint, DataPointSourceError>,
+{
+    let shared = SHARED_FETCHES
+        .lock()
+        .unwrap()
+        .entry(key.to_string())
+        .or_default()
+        .clone();
+    // held during the fetch, so a concurrent pool waits for it and reuses the datapoint
+    let mut last = shared.lock().unwrap();
+    if let Some((fetched_at, fetched)) = last.as_ref() {
+        if fetched_at.elapsed() < max_age {
+            log::debug!(
+                "Reusing the datapoint {} of {} fetched {:?} ago",
+                i64::from(fetched.rate),
+                key,
+                fetched_at.elapsed()
+            );
+            return Ok(fetched.clone());
+        }
+    }
+    let fetched = fetch()?;
+    *last = Some((Instant::now(), fetched.clone()));
+    Ok(fetched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datapoint(rate: i64) -> FetchedDatapoint {
+        FetchedDatapoint {
+            rate: rate.into(),
+            sources: vec![],
+        }
+    }
+
+    #[test]
+    fn test_fetch_shared() {
+        let max_age = Duration::from_secs(60);
+        assert_eq!(
+            fetch_shared("test-shared", max_age, || Ok(datapoint(250))).unwrap(),
+            datapoint(250)
+        );
+        // reused within max_age
+        assert_eq!(
+            fetch_shared("test-shared", max_age, || Ok(datapoint(251))).unwrap(),
+            datapoint(250)
+        );
+        // other sources are fetched on their own
+        assert_eq!(
+            fetch_shared("test-shared-other", max_age, || Ok(datapoint(300))).unwrap(),
+            datapoint(300)
+        );
+        // fetched again once expired
+        assert_eq!(
+            fetch_shared("test-shared", Duration::ZERO, || Ok(datapoint(252))).unwrap(),
+            datapoint(252)
+        );
+    }
+
+    #[test]
+    fn test_failed_fetch_is_not_shared() {
+        let max_age = Duration::from_secs(60);
+        assert!(fetch_shared("test-shared-failed", max_age, || Err(
+            DataPointSourceError::NoDataPoints
+        ))
+        .is_err());
+        assert_eq!(
+            fetch_shared("test-shared-failed", max_age, || Ok(datapoint(250))).unwrap(),
+            datapoint(250)
+        );
+    }
+}
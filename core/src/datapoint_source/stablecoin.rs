@@ -0,0 +1,209 @@
+//! Cross-check of the exchange rates quoted in a stablecoin (`stablecoin_check` in the oracle
+//! config).
+//!
+//! The exchanges quoting ERG in USDT are aggregated with the USD ones as if USDT was USD. With the
+//! check enabled the USD price of the stablecoin is fetched from its fiat pair on Kraken, once per
+//! fetch for all the sources quoted in it. Within `max_depeg_percent` of 1 USD the rates are used
+//! as they are, beyond it they are converted to USD with the stablecoin price (`action: correct`)
+//! or left out of the aggregated rate (`action: reject`). Without the stablecoin price the rates
+//! are used as they are.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use futures::future::LocalBoxFuture;
+use futures::future::Shared;
+use futures::Future;
+use futures::FutureExt;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::aggregator::NamedSource;
+use super::assets_exchange_rate::AssetsExchangeRate;
+use super::assets_exchange_rate::NanoErg;
+use super::assets_exchange_rate::Usd;
+use super::DataPointSourceError;
+
+pub const DEFAULT_MAX_DEPEG_PERCENT: f64 = 1.0;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StablecoinCheckConfig {
+    /// Stablecoin prices further than this from 1 USD are a depeg (default 1%)
+    pub max_depeg_percent: Option<f64>,
+    /// `correct` (default) to convert the rates to USD or `reject` to leave them out during a
+    /// depeg
+    #[serde(default)]
+    pub action: DepegAction,
+}
+
+impl StablecoinCheckConfig {
+    pub fn max_depeg_percent(&self) -> f64 {
+        self.max_depeg_percent.unwrap_or(DEFAULT_MAX_DEPEG_PERCENT)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepegAction {
+    #[default]
+    Correct,
+    Reject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stablecoin {
+    Usdt,
+    Usdc,
+}
+
+impl fmt::Display for Stablecoin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stablecoin::Usdt => write!(f, "USDT"),
+            Stablecoin::Usdc => write!(f, "USDC"),
+        }
+    }
+}
+
+/// USD price of a stablecoin awaited by all the sources quoted in it, `None` if it failed to fetch
+type SharedUsdPrice = Shared<LocalBoxFuture<'static, Option<f64>>>;
+
+fn shared_usd_price(stablecoin: Stablecoin) -> SharedUsdPrice {
+    async move {
+        match fetch_stablecoin_usd(stablecoin).await {
+            Ok(price) => Some(price),
+            Err(e) => {
+                log::warn!(
+                    "Failed to fetch the USD price of {}, its rates are used as they are: {}",
+                    stablecoin,
+                    e
+                );
+                None
+            }
+        }
+    }
+    .boxed_local()
+    .shared()
+}
+
+#[cfg(feature = "kraken")]
+async fn fetch_stablecoin_usd(stablecoin: Stablecoin) -> Result<f64, DataPointSourceError> {
+    super::kraken::get_stablecoin_usd(stablecoin).await
+}
+
+#[cfg(not(feature = "kraken"))]
+async fn fetch_stablecoin_usd(_stablecoin: Stablecoin) -> Result<f64, DataPointSourceError> {
+    Err(DataPointSourceError::NoDataPoints)
+}
+
+/// The nanoErg per USD rate of a source quoting in the stablecoin, given the USD price of the
+/// stablecoin
+pub fn usd_rate(
+    stablecoin: Stablecoin,
+    rate: f64,
+    usd_price: f64,
+    config: &StablecoinCheckConfig,
+) -> Result<f64, DataPointSourceError> {
+    if (usd_price - 1.0).abs() * 100.0 <= config.max_depeg_percent() {
+        return Ok(rate);
+    }
+    match config.action {
+        // 1 USD is 1/usd_price of the stablecoin
+        DepegAction::Correct => Ok(rate / usd_price),
+        DepegAction::Reject => Err(DataPointSourceError::StablecoinDepeg {
+            stablecoin: stablecoin.to_string(),
+            usd_price,
+        }),
+    }
+}
+
+async fn checked_rate(
+    name: &'static str,
+    stablecoin: Stablecoin,
+    rate: impl Future<Output = Result<AssetsExchangeRate<Usd, NanoErg>, DataPointSourceError>>,
+    usd_price: SharedUsdPrice,
+    config: StablecoinCheckConfig,
+) -> Result<AssetsExchangeRate<Usd, NanoErg>, DataPointSourceError> {
+    let rate = rate.await?;
+    let usd_price = match usd_price.await {
+        Some(usd_price) => usd_price,
+        None => return Ok(rate),
+    };
+    let checked = usd_rate(stablecoin, rate.rate, usd_price, &config);
+    match &checked {
+        Ok(checked) if *checked != rate.rate => log::warn!(
+            "{} is at {} USD, the rate of {} is corrected to USD",
+            stablecoin,
+            usd_price,
+            name
+        ),
+        Ok(_) => (),
+        Err(e) => log::warn!("{}, leaving {} out", e, name),
+    }
+    Ok(AssetsExchangeRate {
+        rate: checked?,
+        ..rate
+    })
+}
+
+/// The sources with the rates quoted in a stablecoin (by `quote_stablecoin`) checked against its
+/// USD price
+pub fn checked_sources(
+    sources: Vec<NamedSource<Usd, NanoErg>>,
+    quote_stablecoin: fn(&str) -> Option<Stablecoin>,
+    config: &StablecoinCheckConfig,
+) -> Vec<NamedSource<Usd, NanoErg>> {
+    let mut usd_prices: HashMap<Stablecoin, SharedUsdPrice> = HashMap::new();
+    sources
+        .into_iter()
+        .map(|(name, rate)| -> NamedSource<Usd, NanoErg> {
+            match quote_stablecoin(name) {
+                Some(stablecoin) => {
+                    let usd_price = usd_prices
+                        .entry(stablecoin)
+                        .or_insert_with(|| shared_usd_price(stablecoin))
+                        .clone();
+                    (
+                        name,
+                        Box::pin(checked_rate(
+                            name,
+                            stablecoin,
+                            rate,
+                            usd_price,
+                            config.clone(),
+                        )),
+                    )
+                }
+                None => (name, rate),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usd_rate() {
+        let config = |action| StablecoinCheckConfig {
+            max_depeg_percent: None,
+            action,
+        };
+        let correct = config(DepegAction::Correct);
+        // within the peg the rate is used as it is
+        assert_eq!(
+            usd_rate(Stablecoin::Usdt, 1000.0, 0.995, &correct).unwrap(),
+            1000.0
+        );
+        // at 0.8 USD per USDT, 1 USD buys 1.25 USDT worth of nanoErg
+        assert_eq!(
+            usd_rate(Stablecoin::Usdt, 1000.0, 0.8, &correct).unwrap(),
+            1250.0
+        );
+        assert!(matches!(
+            usd_rate(Stablecoin::Usdt, 1000.0, 0.8, &config(DepegAction::Reject)),
+            Err(DataPointSourceError::StablecoinDepeg { .. })
+        ));
+    }
+}
@@ -14,9 +14,15 @@ use crate::{
         refresh::{RefreshContractParameters, RefreshContractParametersInputs},
         update::UpdateContractParameters,
     },
-    oracle_types::{EpochLength, MinDatapoints},
+    oracle_types::{EpochLength, MinDatapoints, RewardTokensPerDatapoint},
 };
 
+/// Index of the reward per datapoint in the EIP-23 refresh contract
+pub const REFRESH_REWARD_PER_DATAPOINT_INDEX: usize = 19;
+/// Reward tokens the EIP-23 refresh contract pays an oracle for each of its collected datapoints
+pub const REFRESH_ORACLE_REWARD_PER_DATAPOINT: RewardTokensPerDatapoint =
+    RewardTokensPerDatapoint(1);
+
 impl Default for BallotContractParameters {
     fn default() -> Self {
         // compiled via
@@ -83,6 +89,8 @@ impl Default for RefreshContractParameters {
             max_deviation_percent: 5,
             epoch_length_index: 0,
             epoch_length: EpochLength(30),
+            reward_per_datapoint_index: REFRESH_REWARD_PER_DATAPOINT_INDEX,
+            oracle_reward_per_datapoint: REFRESH_ORACLE_REWARD_PER_DATAPOINT,
         })
         .unwrap()
     }
@@ -0,0 +1,168 @@
+//! Reserve ratio of the Djed bank box (the SigmaUSD bank on Ergo) the pool publishes its rate
+//! for, read to sanity check the fetched datapoints before they are posted.
+
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisterId;
+use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::node_interface::node_api::NodeApi;
+use crate::node_interface::node_api::NodeApiError;
+use crate::oracle_types::Rate;
+
+/// Minimum reserve ratio of the SigmaUSD bank for minting stablecoins
+const DEFAULT_MIN_RESERVE_RATIO_PERCENT: u32 = 400;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DjedConfig {
+    /// Base16-encoded id of the NFT identifying the bank box
+    pub bank_nft_token_id: String,
+    /// A datapoint putting the bank under this reserve ratio is reported (default 400)
+    pub min_reserve_ratio_percent: Option<u32>,
+    /// The bank contract divides the pool rate by this to get the nanoERG per smallest stablecoin
+    /// unit: 100 for the SigmaUSD bank reading a `NanoErgUsd` pool, 1 (default) for a
+    /// `NanoErgSigUsd` pool
+    pub rate_divisor: Option<u32>,
+}
+
+#[derive(Debug, Error)]
+pub enum DjedError {
+    #[error("node api error: {0}")]
+    NodeApi(#[from] NodeApiError),
+    #[error("no unspent bank box with the NFT {0}")]
+    NoBankBox(String),
+    #[error("bank box: no circulating stablecoins in R4")]
+    NoCirculatingStablecoins,
+}
+
+/// Reserve and liabilities of the bank box
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DjedBankState {
+    /// nanoERG in the bank box
+    pub reserve: u64,
+    /// Circulating stablecoins in their smallest unit (SigUSD cents)
+    pub circulating_stablecoins: i64,
+}
+
+impl DjedBankState {
+    pub fn from_box(bank_box: &ErgoBox) -> Result<Self, DjedError> {
+        let circulating_stablecoins = bank_box
+            .get_register(NonMandatoryRegisterId::R4.into())
+            .ok_or(DjedError::NoCirculatingStablecoins)?
+            .try_extract_into::<i64>()
+            .map_err(|_| DjedError::NoCirculatingStablecoins)?;
+        Ok(DjedBankState {
+            reserve: *bank_box.value.as_u64(),
+            circulating_stablecoins,
+        })
+    }
+
+    /// Reserve ratio in percent with the given nanoERG per smallest stablecoin unit, `None` when
+    /// no stablecoins circulate
+    pub fn reserve_ratio_percent(&self, nanoerg_per_stablecoin_unit: f64) -> Option<f64> {
+        let liabilities = self.circulating_stablecoins as f64 * nanoerg_per_stablecoin_unit;
+        if liabilities > 0.0 {
+            Some(self.reserve as f64 / liabilities * 100.0)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn fetch_bank_state(
+    node_api: &NodeApi,
+    config: &DjedConfig,
+) -> Result<DjedBankState, DjedError> {
+    let bank_box = node_api
+        .get_unspent_boxes_by_token_id(&config.bank_nft_token_id)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| DjedError::NoBankBox(config.bank_nft_token_id.clone()))?;
+    DjedBankState::from_box(&bank_box)
+}
+
+/// Warning if the pool rate puts the bank under the minimum reserve ratio
+pub fn reserve_ratio_warning(
+    bank: &DjedBankState,
+    config: &DjedConfig,
+    rate: Rate,
+) -> Option<String> {
+    let divisor = config.rate_divisor.unwrap_or(1).max(1);
+    let nanoerg_per_unit = i64::from(rate) as f64 / divisor as f64;
+    let min_ratio = config
+        .min_reserve_ratio_percent
+        .unwrap_or(DEFAULT_MIN_RESERVE_RATIO_PERCENT);
+    match bank.reserve_ratio_percent(nanoerg_per_unit) {
+        Some(ratio) if ratio < min_ratio as f64 => Some(format!(
+            "Datapoint {} puts the Djed bank reserve ratio at {:.0}%, under the minimum {}%",
+            rate, ratio, min_ratio
+        )),
+        Some(_) | None => None,
+    }
+}
+
+/// Log the reserve ratio of the bank at the datapoint, with a warning under the minimum ratio.
+/// Does not prevent posting the datapoint, a crash of the ERG price is a valid reason.
+pub fn check_datapoint(node_api: &NodeApi, config: &DjedConfig, rate: Rate) {
+    match fetch_bank_state(node_api, config) {
+        Ok(bank) => {
+            if let Some(warning) = reserve_ratio_warning(&bank, config, rate) {
+                log::warn!("{}", warning);
+            } else {
+                log::debug!("Djed bank state {:?} at datapoint {}", bank, rate);
+            }
+        }
+        Err(e) => log::warn!("Failed to read the Djed bank box: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(rate_divisor: Option<u32>) -> DjedConfig {
+        DjedConfig {
+            bank_nft_token_id: "7d672d1def471720ca5782fd6473e47e796d9ac0c138d9911346f118b2f6d9d9"
+                .to_string(),
+            min_reserve_ratio_percent: None,
+            rate_divisor,
+        }
+    }
+
+    #[test]
+    fn test_reserve_ratio() {
+        // 1000 ERG backing 100 USD worth 1 ERG each
+        let bank = DjedBankState {
+            reserve: 1_000_000_000_000,
+            circulating_stablecoins: 10_000,
+        };
+        assert_eq!(bank.reserve_ratio_percent(10_000_000.0), Some(1000.0));
+        let empty_bank = DjedBankState {
+            reserve: 1_000_000_000_000,
+            circulating_stablecoins: 0,
+        };
+        assert_eq!(empty_bank.reserve_ratio_percent(10_000_000.0), None);
+    }
+
+    #[test]
+    fn test_reserve_ratio_warning() {
+        let bank = DjedBankState {
+            reserve: 1_000_000_000_000,
+            circulating_stablecoins: 10_000,
+        };
+        // 1000%
+        assert_eq!(
+            reserve_ratio_warning(&bank, &config(None), 10_000_000.into()),
+            None
+        );
+        assert_eq!(
+            reserve_ratio_warning(&bank, &config(Some(100)), 1_000_000_000.into()),
+            None
+        );
+        // 250%
+        assert!(reserve_ratio_warning(&bank, &config(None), 40_000_000.into()).is_some());
+        assert!(reserve_ratio_warning(&bank, &config(Some(100)), 4_000_000_000.into()).is_some());
+    }
+}
@@ -0,0 +1,224 @@
+//! Local history of the pool epochs.
+//!
+//! When the main loop sees the pool box of a new epoch, its epoch id, rate, creation height and the
+//! oracles whose datapoints were collected by the refresh are appended as a JSON line. The history
+//! is served by the REST API `/poolHistory`, so the rate evolution can be followed without an
+//! external indexer.
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use ergo_lib::ergo_chain_types::EcPoint;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use once_cell::sync;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::box_kind::OracleBox;
+use crate::box_kind::PoolBox;
+use crate::oracle_state::CollectedDatapointBoxesSource;
+use crate::oracle_state::DataSourceError;
+use crate::oracle_state::PoolBoxSource;
+
+pub static EPOCH_HISTORY_DIR_PATH: sync::OnceCell<PathBuf> = sync::OnceCell::new();
+
+pub const EPOCH_HISTORY_FILE_NAME: &str = "epoch_history.jsonl";
+
+pub fn get_epoch_history_file_path() -> PathBuf {
+    EPOCH_HISTORY_DIR_PATH
+        .get()
+        .unwrap()
+        .join(EPOCH_HISTORY_FILE_NAME)
+}
+
+#[derive(Debug, Error)]
+pub enum EpochHistoryError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serde error: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("data source error: {0}")]
+    DataSource(#[from] DataSourceError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct EpochHistoryEntry {
+    pub epoch_id: u32,
+    pub rate: i64,
+    /// Creation height of the pool box starting the epoch
+    pub height: u32,
+    pub pool_box_id: String,
+    /// Public keys (base16) of the oracles whose datapoints were collected into the pool box. Only
+    /// the collected boxes still unspent when the epoch is recorded are found.
+    pub oracles: Vec<String>,
+}
+
+pub struct EpochHistory {
+    path: PathBuf,
+    last_epoch_id: Option<u32>,
+}
+
+impl EpochHistory {
+    /// Open the history at the given path, continuing after its last recorded epoch
+    pub fn open(path: PathBuf) -> Result<Self, EpochHistoryError> {
+        let last_epoch_id = read_entries(&path)?.last().map(|e| e.epoch_id);
+        Ok(EpochHistory {
+            path,
+            last_epoch_id,
+        })
+    }
+
+    /// Append the epoch of the current pool box unless it is already recorded
+    pub fn record(
+        &mut self,
+        pool_box_source: &dyn PoolBoxSource,
+        collected_datapoint_boxes_source: &dyn CollectedDatapointBoxesSource,
+    ) -> Result<Option<EpochHistoryEntry>, EpochHistoryError> {
+        let pool_box = pool_box_source.get_pool_box()?;
+        let epoch_id = pool_box.epoch_counter().0;
+        if self.last_epoch_id == Some(epoch_id) {
+            return Ok(None);
+        }
+        let height = pool_box.get_box().creation_height;
+        // the refresh creates the collected oracle boxes in the same tx as the pool box
+        let oracles = collected_datapoint_boxes_source
+            .get_collected_datapoint_boxes()?
+            .iter()
+            .filter(|b| b.get_box().creation_height == height)
+            .map(|b| public_key_base16(&b.public_key()))
+            .collect();
+        let entry = EpochHistoryEntry {
+            epoch_id,
+            rate: pool_box.rate(),
+            height,
+            pool_box_id: String::from(pool_box.get_box().box_id()),
+            oracles,
+        };
+        self.append(&entry)?;
+        self.last_epoch_id = Some(epoch_id);
+        Ok(Some(entry))
+    }
+
+    fn append(&self, entry: &EpochHistoryEntry) -> Result<(), EpochHistoryError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+pub(crate) fn public_key_base16(public_key: &EcPoint) -> String {
+    public_key
+        .sigma_serialize_bytes()
+        .map(|bytes| base16::encode_lower(&bytes))
+        .unwrap_or_else(|_| "<unknown>".to_string())
+}
+
+/// All recorded epochs, oldest first
+pub fn read_entries(path: &Path) -> Result<Vec<EpochHistoryEntry>, EpochHistoryError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line)?);
+    }
+    Ok(entries)
+}
+
+/// The last `limit` recorded epochs, oldest first
+pub fn read_recent_entries(
+    path: &Path,
+    limit: usize,
+) -> Result<Vec<EpochHistoryEntry>, EpochHistoryError> {
+    let entries = read_entries(path)?;
+    let skip = entries.len().saturating_sub(limit);
+    Ok(entries.into_iter().skip(skip).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+
+    use crate::box_kind::CollectedOracleBox;
+    use crate::box_kind::PoolBoxWrapper;
+    use crate::contracts::pool::PoolContractParameters;
+    use crate::oracle_state::Result;
+    use crate::oracle_types::BlockHeight;
+    use crate::oracle_types::EpochCounter;
+    use crate::pool_commands::test_utils::generate_token_ids;
+    use crate::pool_commands::test_utils::make_pool_box;
+    use crate::pool_commands::test_utils::PoolBoxMock;
+
+    use super::*;
+
+    struct CollectedBoxesMock {
+        boxes: Vec<CollectedOracleBox>,
+    }
+
+    impl CollectedDatapointBoxesSource for CollectedBoxesMock {
+        fn get_collected_datapoint_boxes(&self) -> Result<Vec<CollectedOracleBox>> {
+            Ok(self.boxes.clone())
+        }
+    }
+
+    fn pool_box(epoch_counter: u32, rate: i64) -> PoolBoxWrapper {
+        make_pool_box(
+            rate,
+            EpochCounter(epoch_counter),
+            BoxValue::SAFE_USER_MIN,
+            BlockHeight(100 + epoch_counter),
+            &PoolContractParameters::default(),
+            &generate_token_ids(),
+        )
+    }
+
+    #[test]
+    fn test_record_epochs() {
+        let path = std::env::temp_dir().join(format!(
+            "oracle-core-epoch-history-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let collected = CollectedBoxesMock { boxes: vec![] };
+        let mut history = EpochHistory::open(path.clone()).unwrap();
+        let first = PoolBoxMock {
+            pool_box: pool_box(1, 200),
+        };
+        assert_eq!(
+            history.record(&first, &collected).unwrap().unwrap().rate,
+            200
+        );
+        // the same epoch is recorded once
+        assert!(history.record(&first, &collected).unwrap().is_none());
+        let second = PoolBoxMock {
+            pool_box: pool_box(2, 210),
+        };
+        history.record(&second, &collected).unwrap();
+
+        let mut reopened = EpochHistory::open(path.clone()).unwrap();
+        assert!(reopened.record(&second, &collected).unwrap().is_none());
+        let entries = read_entries(&path).unwrap();
+        assert_eq!(
+            entries.iter().map(|e| e.epoch_id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(read_recent_entries(&path, 1).unwrap()[0].rate, 210);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,110 @@
+//! Optional Sentry reporting of panics and `error!` log records, tagged with the pool name,
+//! height and action type of the main loop iteration they happened in.
+//!
+//! Without the `sentry` feature the config is still parsed, nothing is reported and the tags are
+//! no-ops, so the main loop sets them unconditionally.
+
+#[cfg(feature = "sentry")]
+use log::Level;
+#[cfg(feature = "sentry")]
+use log::Metadata;
+#[cfg(feature = "sentry")]
+use sentry::integrations::log::LogFilter;
+#[cfg(feature = "sentry")]
+use sentry::types::Dsn;
+#[cfg(feature = "sentry")]
+use sentry::ClientInitGuard;
+#[cfg(feature = "sentry")]
+use sentry::ClientOptions;
+use serde::Deserialize;
+use serde::Serialize;
+
+#[cfg(not(feature = "sentry"))]
+type Dsn = String;
+
+/// Keeps the Sentry client bound while alive
+#[cfg(not(feature = "sentry"))]
+pub struct ClientInitGuard;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SentryConfig {
+    /// DSN of the Sentry project
+    pub dsn: Dsn,
+    /// Environment the events are reported under (e.g. `mainnet`)
+    pub environment: Option<String>,
+}
+
+/// Bind the Sentry client, the events are sent until the returned guard is dropped
+#[cfg(feature = "sentry")]
+pub fn init(config: &SentryConfig) -> ClientInitGuard {
+    sentry::init(ClientOptions {
+        dsn: Some(config.dsn.clone()),
+        release: sentry::release_name!(),
+        environment: config.environment.clone().map(Into::into),
+        ..Default::default()
+    })
+}
+
+#[cfg(not(feature = "sentry"))]
+pub fn init(_config: &SentryConfig) -> ClientInitGuard {
+    log::warn!(
+        "sentry is set in the oracle config but oracle-core is built without the `sentry` feature"
+    );
+    ClientInitGuard
+}
+
+/// Which log records become Sentry events. Without a bound client nothing is sent.
+#[cfg(feature = "sentry")]
+pub fn log_filter(metadata: &Metadata) -> LogFilter {
+    match metadata.level() {
+        // panics are captured by the sentry panic integration, the record by log_panics would
+        // report them twice
+        Level::Error if metadata.target() != "panic" => LogFilter::Event,
+        Level::Error | Level::Warn | Level::Info => LogFilter::Breadcrumb,
+        Level::Debug | Level::Trace => LogFilter::Ignore,
+    }
+}
+
+#[cfg(feature = "sentry")]
+pub fn set_loop_context(pool_name: &str) {
+    sentry::configure_scope(|scope| {
+        scope.set_tag("pool", pool_name);
+        scope.remove_tag("height");
+        scope.remove_tag("action");
+    });
+}
+
+#[cfg(not(feature = "sentry"))]
+pub fn set_loop_context(_pool_name: &str) {}
+
+#[cfg(feature = "sentry")]
+pub fn set_tag(key: &str, value: impl ToString) {
+    sentry::configure_scope(|scope| scope.set_tag(key, value));
+}
+
+#[cfg(not(feature = "sentry"))]
+pub fn set_tag(_key: &str, _value: impl ToString) {}
+
+#[cfg(all(test, feature = "sentry"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_filter() {
+        let error = Metadata::builder()
+            .level(Level::Error)
+            .target("oracle_core")
+            .build();
+        assert!(matches!(log_filter(&error), LogFilter::Event));
+        let panic = Metadata::builder()
+            .level(Level::Error)
+            .target("panic")
+            .build();
+        assert!(matches!(log_filter(&panic), LogFilter::Breadcrumb));
+        let debug = Metadata::builder()
+            .level(Level::Debug)
+            .target("oracle_core")
+            .build();
+        assert!(matches!(log_filter(&debug), LogFilter::Ignore));
+    }
+}
@@ -0,0 +1,211 @@
+//! Optional stream of the main loop events to Kafka or NATS, so the events of many oracles can be
+//! processed in one place.
+//!
+//! Every fetched datapoint, submitted tx, new pool epoch and main loop error is sent as a JSON
+//! event with the pool name, the oracle address and a timestamp. Unlike the notifications, the
+//! events are not rate limited. A failed send is logged and the event is dropped.
+//!
+//! A backend is only available with its cargo feature (`kafka`, `nats`), a configured backend
+//! that is not compiled is logged and skipped.
+
+mod kafka;
+mod nats;
+
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::audit_log::unix_time_secs;
+use crate::datapoint_source::SourceRate;
+use crate::epoch_history::EpochHistoryEntry;
+
+pub use self::kafka::KafkaConfig;
+pub use self::nats::NatsConfig;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EventStreamConfig {
+    pub kafka: Option<KafkaConfig>,
+    pub nats: Option<NatsConfig>,
+}
+
+#[derive(Debug, Error)]
+pub enum EventStreamError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serde error: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[cfg(feature = "kafka")]
+    #[error("kafka error: {0}")]
+    Kafka(#[from] ::kafka::Error),
+    #[error("oracle-core was built without the `{0}` feature")]
+    NotCompiled(&'static str),
+}
+
+/// Event of the main loop of a pool
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    DatapointFetched {
+        height: u32,
+        rate: i64,
+        sources: Vec<SourceRate>,
+    },
+    TxSubmitted {
+        height: u32,
+        action: String,
+        tx_id: String,
+    },
+    EpochRefreshed {
+        epoch_id: u32,
+        rate: i64,
+        height: u32,
+        pool_box_id: String,
+        oracles: Vec<String>,
+    },
+    Error {
+        error: String,
+    },
+}
+
+impl From<EpochHistoryEntry> for StreamEvent {
+    fn from(entry: EpochHistoryEntry) -> Self {
+        StreamEvent::EpochRefreshed {
+            epoch_id: entry.epoch_id,
+            rate: entry.rate,
+            height: entry.height,
+            pool_box_id: entry.pool_box_id,
+            oracles: entry.oracles,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    pool: &'a str,
+    oracle_address: &'a str,
+    timestamp: u64,
+    #[serde(flatten)]
+    event: &'a StreamEvent,
+}
+
+trait EventStreamBackend: Send {
+    fn name(&self) -> &'static str;
+    fn send(&mut self, payload: &str) -> Result<(), EventStreamError>;
+}
+
+/// Connected backends of a pool
+pub struct EventStream {
+    pool_name: String,
+    oracle_address: String,
+    backends: Vec<Box<dyn EventStreamBackend>>,
+}
+
+impl EventStream {
+    /// Connect the configured backends, the ones failing to connect are logged and skipped
+    pub fn new(config: EventStreamConfig, pool_name: &str, oracle_address: String) -> Self {
+        let mut backends: Vec<Box<dyn EventStreamBackend>> = Vec::new();
+        if let Some(kafka_config) = config.kafka {
+            match kafka::KafkaBackend::new(kafka_config) {
+                Ok(backend) => backends.push(Box::new(backend)),
+                Err(e) => log::error!("Failed to connect to Kafka, not streaming to it: {}", e),
+            }
+        }
+        if let Some(nats_config) = config.nats {
+            match nats::NatsBackend::new(nats_config) {
+                Ok(backend) => backends.push(Box::new(backend)),
+                Err(e) => log::error!("Failed to connect to NATS, not streaming to it: {}", e),
+            }
+        }
+        EventStream {
+            pool_name: pool_name.to_string(),
+            oracle_address,
+            backends,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.backends.is_empty()
+    }
+
+    /// Send the event to every backend
+    pub fn emit(&mut self, event: StreamEvent) {
+        let payload = match payload(&self.pool_name, &self.oracle_address, &event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                log::error!("Failed to encode the stream event: {}", e);
+                return;
+            }
+        };
+        for backend in self.backends.iter_mut() {
+            if let Err(e) = backend.send(&payload) {
+                log::error!("Failed to send the event to {}: {}", backend.name(), e);
+            }
+        }
+    }
+}
+
+fn payload(
+    pool_name: &str,
+    oracle_address: &str,
+    event: &StreamEvent,
+) -> Result<String, EventStreamError> {
+    Ok(serde_json::to_string(&Envelope {
+        pool: pool_name,
+        oracle_address,
+        timestamp: unix_time_secs(),
+        event,
+    })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload() {
+        let event = StreamEvent::TxSubmitted {
+            height: 1000,
+            action: "refresh".to_string(),
+            tx_id: "abcd".to_string(),
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&payload("primary", "9hEQ", &event).unwrap()).unwrap();
+        assert_eq!(json["pool"], "primary");
+        assert_eq!(json["oracle_address"], "9hEQ");
+        assert_eq!(json["type"], "tx_submitted");
+        assert_eq!(json["tx_id"], "abcd");
+        assert!(json["timestamp"].as_u64().unwrap() > 0);
+
+        let event = StreamEvent::from(EpochHistoryEntry {
+            epoch_id: 7,
+            rate: 250,
+            height: 1000,
+            pool_box_id: "abcd".to_string(),
+            oracles: vec![],
+        });
+        let json: serde_json::Value =
+            serde_json::from_str(&payload("primary", "9hEQ", &event).unwrap()).unwrap();
+        assert_eq!(json["type"], "epoch_refreshed");
+        assert_eq!(json["epoch_id"], 7);
+    }
+
+    #[test]
+    fn test_not_compiled_backend_is_skipped() {
+        if cfg!(feature = "nats") {
+            return;
+        }
+        let stream = EventStream::new(
+            EventStreamConfig {
+                kafka: None,
+                nats: Some(NatsConfig {
+                    url: "nats://localhost:4222".to_string(),
+                    subject: None,
+                    token: None,
+                }),
+            },
+            "primary",
+            "9hEQ".to_string(),
+        );
+        assert!(stream.is_empty());
+    }
+}
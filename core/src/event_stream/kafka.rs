@@ -0,0 +1,85 @@
+//! Kafka backend, every event is produced as a record of the topic. Only the config is compiled
+//! without the `kafka` feature.
+
+#[cfg(feature = "kafka")]
+use std::time::Duration;
+
+#[cfg(feature = "kafka")]
+use kafka::producer::Producer;
+#[cfg(feature = "kafka")]
+use kafka::producer::Record;
+#[cfg(feature = "kafka")]
+use kafka::producer::RequiredAcks;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::EventStreamBackend;
+use super::EventStreamError;
+
+#[cfg(feature = "kafka")]
+const DEFAULT_TOPIC: &str = "oracle-core-events";
+
+#[cfg(feature = "kafka")]
+const ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct KafkaConfig {
+    /// `host:port` of the bootstrap brokers
+    pub brokers: Vec<String>,
+    /// Default `oracle-core-events`
+    pub topic: Option<String>,
+}
+
+#[cfg(feature = "kafka")]
+pub struct KafkaBackend {
+    producer: Producer,
+    topic: String,
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaBackend {
+    pub fn new(config: KafkaConfig) -> Result<Self, EventStreamError> {
+        let producer = Producer::from_hosts(config.brokers)
+            .with_ack_timeout(ACK_TIMEOUT)
+            .with_required_acks(RequiredAcks::One)
+            .create()?;
+        Ok(KafkaBackend {
+            producer,
+            topic: config.topic.unwrap_or_else(|| DEFAULT_TOPIC.to_string()),
+        })
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl EventStreamBackend for KafkaBackend {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    fn send(&mut self, payload: &str) -> Result<(), EventStreamError> {
+        self.producer
+            .send(&Record::from_value(&self.topic, payload))?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "kafka"))]
+pub struct KafkaBackend;
+
+#[cfg(not(feature = "kafka"))]
+impl KafkaBackend {
+    pub fn new(_config: KafkaConfig) -> Result<Self, EventStreamError> {
+        Err(EventStreamError::NotCompiled("kafka"))
+    }
+}
+
+#[cfg(not(feature = "kafka"))]
+impl EventStreamBackend for KafkaBackend {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    fn send(&mut self, _payload: &str) -> Result<(), EventStreamError> {
+        Err(EventStreamError::NotCompiled("kafka"))
+    }
+}
@@ -0,0 +1,79 @@
+//! NATS backend, every event is published to the subject. The client reconnects on its own and
+//! buffers the events meanwhile. Only the config is compiled without the `nats` feature.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::EventStreamBackend;
+use super::EventStreamError;
+
+#[cfg(feature = "nats")]
+const DEFAULT_SUBJECT: &str = "oracle-core.events";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct NatsConfig {
+    /// Server URL, e.g. `nats://localhost:4222`
+    pub url: String,
+    /// Default `oracle-core.events`
+    pub subject: Option<String>,
+    /// The auth token or `keyring:<entry name>`
+    pub token: Option<String>,
+}
+
+#[cfg(feature = "nats")]
+pub struct NatsBackend {
+    connection: ::nats::Connection,
+    subject: String,
+}
+
+#[cfg(feature = "nats")]
+impl NatsBackend {
+    pub fn new(config: NatsConfig) -> Result<Self, EventStreamError> {
+        let options = match &config.token {
+            Some(token) => ::nats::Options::with_token(token),
+            None => ::nats::Options::new(),
+        };
+        let connection = options
+            .with_name("oracle-core")
+            .connect(config.url.as_str())?;
+        Ok(NatsBackend {
+            connection,
+            subject: config
+                .subject
+                .unwrap_or_else(|| DEFAULT_SUBJECT.to_string()),
+        })
+    }
+}
+
+#[cfg(feature = "nats")]
+impl EventStreamBackend for NatsBackend {
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    fn send(&mut self, payload: &str) -> Result<(), EventStreamError> {
+        self.connection.publish(&self.subject, payload)?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "nats"))]
+pub struct NatsBackend;
+
+#[cfg(not(feature = "nats"))]
+impl NatsBackend {
+    pub fn new(_config: NatsConfig) -> Result<Self, EventStreamError> {
+        Err(EventStreamError::NotCompiled("nats"))
+    }
+}
+
+#[cfg(not(feature = "nats"))]
+impl EventStreamBackend for NatsBackend {
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    fn send(&mut self, _payload: &str) -> Result<(), EventStreamError> {
+        Err(EventStreamError::NotCompiled("nats"))
+    }
+}
@@ -0,0 +1,358 @@
+//! Optional relay of the pool rate to a consumer contract on an EVM chain (e.g. the Djed
+//! deployment on Milkomeda C1).
+//!
+//! Every new epoch seen by the main loop (the confirmed pool box of a refresh) is submitted as a
+//! call of `<function_name>(int256 rate, uint256 epoch)` in a legacy EIP-155 tx signed with a
+//! local secp256k1 key, through the JSON-RPC endpoint of the chain. The tx is not awaited, a
+//! failed relay is logged and the next epoch is relayed as usual.
+//!
+//! Without the `evm-relay` feature the relay can't be created and fails with
+//! [`EvmRelayError::NotCompiled`].
+
+use std::path::PathBuf;
+
+#[cfg(feature = "evm-relay")]
+use k256::ecdsa::SigningKey;
+#[cfg(feature = "evm-relay")]
+use reqwest::blocking::Client;
+#[cfg(feature = "evm-relay")]
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Url;
+use serde::Deserialize;
+use serde::Serialize;
+#[cfg(feature = "evm-relay")]
+use serde_json::json;
+#[cfg(feature = "evm-relay")]
+use sha3::Digest;
+#[cfg(feature = "evm-relay")]
+use sha3::Keccak256;
+use thiserror::Error;
+
+use crate::epoch_history::EpochHistoryEntry;
+
+#[cfg(feature = "evm-relay")]
+const DEFAULT_FUNCTION_NAME: &str = "submitRate";
+
+#[cfg(feature = "evm-relay")]
+const DEFAULT_GAS_LIMIT: u64 = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EvmRelayConfig {
+    /// JSON-RPC endpoint of the chain
+    pub rpc_url: Url,
+    /// EIP-155 chain id, e.g. 2001 for Milkomeda C1 mainnet
+    pub chain_id: u64,
+    /// 0x-prefixed address of the consumer contract
+    pub contract_address: String,
+    /// Contract function taking `(int256 rate, uint256 epoch)` (default `submitRate`)
+    pub function_name: Option<String>,
+    /// File holding the hex-encoded private key of the sender account
+    pub private_key_file: PathBuf,
+    /// Default 100000
+    pub gas_limit: Option<u64>,
+}
+
+#[derive(Debug, Error)]
+pub enum EvmRelayError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid private key: {0}")]
+    InvalidKey(String),
+    #[error("invalid contract address: {0}")]
+    InvalidAddress(String),
+    #[error("reqwest error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("serde error: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("JSON-RPC error: {0}")]
+    Rpc(String),
+    #[error("signing error: {0}")]
+    Signing(String),
+    #[error("oracle-core was built without the `evm-relay` feature")]
+    NotCompiled,
+}
+
+pub struct EvmRelay {
+    config: EvmRelayConfig,
+    #[cfg(feature = "evm-relay")]
+    signing_key: SigningKey,
+    #[cfg(feature = "evm-relay")]
+    contract_address: [u8; 20],
+    #[cfg(feature = "evm-relay")]
+    client: Client,
+}
+
+#[cfg(feature = "evm-relay")]
+impl EvmRelay {
+    pub fn new(config: EvmRelayConfig) -> Result<Self, EvmRelayError> {
+        let key_hex = std::fs::read_to_string(&config.private_key_file)?;
+        let key_bytes = decode_hex(key_hex.trim())
+            .ok_or_else(|| EvmRelayError::InvalidKey("not hex-encoded".to_string()))?;
+        let signing_key = SigningKey::from_slice(&key_bytes)
+            .map_err(|e| EvmRelayError::InvalidKey(e.to_string()))?;
+        let contract_address = decode_hex(&config.contract_address)
+            .and_then(|bytes| <[u8; 20]>::try_from(bytes).ok())
+            .ok_or_else(|| EvmRelayError::InvalidAddress(config.contract_address.clone()))?;
+        let relay = EvmRelay {
+            config,
+            signing_key,
+            contract_address,
+            client: Client::new(),
+        };
+        log::info!(
+            "Relaying the pool rate to {} on chain {} from 0x{}",
+            relay.config.contract_address,
+            relay.config.chain_id,
+            base16::encode_lower(&sender_address(&relay.signing_key))
+        );
+        Ok(relay)
+    }
+
+    /// Submit the rate of the epoch, returns the tx hash
+    pub fn relay(&self, entry: &EpochHistoryEntry) -> Result<String, EvmRelayError> {
+        let from = format!(
+            "0x{}",
+            base16::encode_lower(&sender_address(&self.signing_key))
+        );
+        let nonce = hex_quantity(&self.rpc("eth_getTransactionCount", json!([from, "pending"]))?)?;
+        let gas_price = hex_quantity(&self.rpc("eth_gasPrice", json!([]))?)?;
+        let tx = LegacyTx {
+            nonce,
+            gas_price,
+            gas_limit: self.config.gas_limit.unwrap_or(DEFAULT_GAS_LIMIT) as u128,
+            to: self.contract_address,
+            value: 0,
+            data: calldata(
+                self.config
+                    .function_name
+                    .as_deref()
+                    .unwrap_or(DEFAULT_FUNCTION_NAME),
+                entry.rate,
+                entry.epoch_id,
+            ),
+        };
+        let raw_tx = tx.sign(self.config.chain_id, &self.signing_key)?;
+        let tx_hash = self.rpc(
+            "eth_sendRawTransaction",
+            json!([format!("0x{}", base16::encode_lower(&raw_tx))]),
+        )?;
+        Ok(tx_hash.as_str().unwrap_or_default().to_string())
+    }
+
+    fn rpc(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, EvmRelayError> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+        let text = self
+            .client
+            .post(self.config.rpc_url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(request.to_string())
+            .send()?
+            .error_for_status()?
+            .text()?;
+        let mut response: serde_json::Value = serde_json::from_str(&text)?;
+        if let Some(error) = response.get("error") {
+            return Err(EvmRelayError::Rpc(error.to_string()));
+        }
+        Ok(response["result"].take())
+    }
+}
+
+#[cfg(not(feature = "evm-relay"))]
+impl EvmRelay {
+    pub fn new(_config: EvmRelayConfig) -> Result<Self, EvmRelayError> {
+        Err(EvmRelayError::NotCompiled)
+    }
+
+    pub fn relay(&self, _entry: &EpochHistoryEntry) -> Result<String, EvmRelayError> {
+        Err(EvmRelayError::NotCompiled)
+    }
+}
+
+#[cfg(feature = "evm-relay")]
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    base16::decode(s.strip_prefix("0x").unwrap_or(s)).ok()
+}
+
+/// Value of a JSON-RPC quantity (`0x`-prefixed hex)
+#[cfg(feature = "evm-relay")]
+fn hex_quantity(value: &serde_json::Value) -> Result<u128, EvmRelayError> {
+    value
+        .as_str()
+        .and_then(|s| s.strip_prefix("0x"))
+        .and_then(|s| u128::from_str_radix(s, 16).ok())
+        .ok_or_else(|| EvmRelayError::Rpc(format!("expected a hex quantity, got {}", value)))
+}
+
+#[cfg(feature = "evm-relay")]
+fn sender_address(signing_key: &SigningKey) -> [u8; 20] {
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    let hash = Keccak256::digest(&public_key.as_bytes()[1..]);
+    let mut address = [0; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// ABI encoded call of `<function_name>(int256,uint256)`
+#[cfg(feature = "evm-relay")]
+fn calldata(function_name: &str, rate: i64, epoch: u32) -> Vec<u8> {
+    let selector = Keccak256::digest(format!("{}(int256,uint256)", function_name).as_bytes());
+    let mut data = selector[..4].to_vec();
+    // two's complement, sign extended to 256 bits
+    let mut rate_word = if rate < 0 { [0xff; 32] } else { [0; 32] };
+    rate_word[24..].copy_from_slice(&rate.to_be_bytes());
+    data.extend_from_slice(&rate_word);
+    let mut epoch_word = [0; 32];
+    epoch_word[28..].copy_from_slice(&epoch.to_be_bytes());
+    data.extend_from_slice(&epoch_word);
+    data
+}
+
+#[cfg(feature = "evm-relay")]
+struct LegacyTx {
+    nonce: u128,
+    gas_price: u128,
+    gas_limit: u128,
+    to: [u8; 20],
+    value: u128,
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "evm-relay")]
+impl LegacyTx {
+    fn fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            rlp_uint(self.nonce),
+            rlp_uint(self.gas_price),
+            rlp_uint(self.gas_limit),
+            rlp_bytes(&self.to),
+            rlp_uint(self.value),
+            rlp_bytes(&self.data),
+        ]
+    }
+
+    /// EIP-155 signing payload, the chain id takes the place of the signature
+    fn signing_payload(&self, chain_id: u64) -> Vec<u8> {
+        let mut fields = self.fields();
+        fields.extend([rlp_uint(chain_id as u128), rlp_uint(0), rlp_uint(0)]);
+        rlp_list(&fields)
+    }
+
+    /// Raw signed tx for `eth_sendRawTransaction`
+    fn sign(&self, chain_id: u64, signing_key: &SigningKey) -> Result<Vec<u8>, EvmRelayError> {
+        let hash = Keccak256::digest(self.signing_payload(chain_id));
+        let (signature, recovery_id) = signing_key
+            .sign_prehash_recoverable(&hash)
+            .map_err(|e| EvmRelayError::Signing(e.to_string()))?;
+        let signature = signature.to_bytes();
+        let v = recovery_id.to_byte() as u128 + chain_id as u128 * 2 + 35;
+        let mut fields = self.fields();
+        fields.extend([
+            rlp_uint(v),
+            rlp_bytes(strip_leading_zeros(&signature[..32])),
+            rlp_bytes(strip_leading_zeros(&signature[32..])),
+        ]);
+        Ok(rlp_list(&fields))
+    }
+}
+
+#[cfg(feature = "evm-relay")]
+fn strip_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let first_non_zero = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[first_non_zero..]
+}
+
+#[cfg(feature = "evm-relay")]
+fn rlp_length_prefix(len: usize, offset: u8) -> Vec<u8> {
+    if len < 56 {
+        vec![offset + len as u8]
+    } else {
+        let len_bytes = len.to_be_bytes();
+        let len_bytes = strip_leading_zeros(&len_bytes);
+        let mut prefix = vec![offset + 55 + len_bytes.len() as u8];
+        prefix.extend_from_slice(len_bytes);
+        prefix
+    }
+}
+
+#[cfg(feature = "evm-relay")]
+fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut encoded = rlp_length_prefix(bytes.len(), 0x80);
+    encoded.extend_from_slice(bytes);
+    encoded
+}
+
+#[cfg(feature = "evm-relay")]
+fn rlp_uint(value: u128) -> Vec<u8> {
+    rlp_bytes(strip_leading_zeros(&value.to_be_bytes()))
+}
+
+#[cfg(feature = "evm-relay")]
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    let mut encoded = rlp_length_prefix(payload.len(), 0xc0);
+    encoded.extend(payload);
+    encoded
+}
+
+#[cfg(all(test, feature = "evm-relay"))]
+mod tests {
+    use super::*;
+
+    // example tx of EIP-155
+    fn eip155_example() -> (LegacyTx, SigningKey) {
+        let tx = LegacyTx {
+            nonce: 9,
+            gas_price: 20_000_000_000,
+            gas_limit: 21000,
+            to: [0x35; 20],
+            value: 1_000_000_000_000_000_000,
+            data: vec![],
+        };
+        (tx, SigningKey::from_slice(&[0x46; 32]).unwrap())
+    }
+
+    #[test]
+    fn test_eip155_signing() {
+        let (tx, signing_key) = eip155_example();
+        assert_eq!(
+            base16::encode_lower(&tx.signing_payload(1)),
+            "ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080"
+        );
+        assert_eq!(
+            base16::encode_lower(&tx.sign(1, &signing_key).unwrap()),
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+        assert_eq!(
+            base16::encode_lower(&sender_address(&signing_key)),
+            "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"
+        );
+    }
+
+    #[test]
+    fn test_calldata() {
+        let data = calldata("submitRate", -2, 7);
+        assert_eq!(data.len(), 4 + 2 * 32);
+        assert_eq!(&data[4..36], &[[0xff; 31].as_slice(), &[0xfe]].concat()[..]);
+        assert_eq!(data[67], 7);
+    }
+
+    #[test]
+    fn test_rlp_long_bytes() {
+        let encoded = rlp_bytes(&[0xaa; 60]);
+        assert_eq!(&encoded[..2], &[0xb8, 60]);
+        assert_eq!(rlp_uint(0), vec![0x80]);
+        assert_eq!(rlp_uint(0x7f), vec![0x7f]);
+    }
+}
@@ -0,0 +1,203 @@
+//! Exit codes of the fatal errors of the commands, by error category.
+//!
+//! The category is found from the error chain, so orchestration tooling can tell a bad config from
+//! an unreachable node or a rejected tx by the exit code alone. The codes are stable:
+//!
+//! | category            | exit code             |
+//! |---------------------|-----------------------|
+//! | `config`            | 78 (`EX_CONFIG`)      |
+//! | `node_connectivity` | 69 (`EX_UNAVAILABLE`) |
+//! | `wallet_locked`     | 77 (`EX_NOPERM`)      |
+//! | `contract_mismatch` | 65 (`EX_DATAERR`)     |
+//! | `tx_rejected`       | 76 (`EX_PROTOCOL`)    |
+//! | `other`             | 70 (`EX_SOFTWARE`)    |
+//!
+//! With `--json-errors` the error is also written to stderr as a single line JSON object with the
+//! `category`, `exit_code`, `command` and `message` fields.
+
+use ergo_node_interface::node_interface::NodeError;
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+
+use crate::box_kind::BallotBoxError;
+use crate::box_kind::OracleBoxError;
+use crate::box_kind::PoolBoxError;
+use crate::box_kind::RefreshBoxError;
+use crate::box_kind::UpdateBoxError;
+use crate::contracts::ballot::BallotContractError;
+use crate::contracts::oracle::OracleContractError;
+use crate::contracts::pool::PoolContractError;
+use crate::contracts::refresh::RefreshContractError;
+use crate::contracts::update::UpdateContractError;
+use crate::node_interface::WalletLockedError;
+use crate::oracle_config::OracleConfigFileError;
+use crate::pool_config::PoolConfigError;
+
+/// Whether the fatal errors are written to stderr as JSON, set once from the command line
+pub static JSON_ERRORS: OnceCell<bool> = OnceCell::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    Config,
+    NodeConnectivity,
+    WalletLocked,
+    ContractMismatch,
+    TxRejected,
+    Other,
+}
+
+impl ErrorCategory {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorCategory::Config => exitcode::CONFIG,
+            ErrorCategory::NodeConnectivity => exitcode::UNAVAILABLE,
+            ErrorCategory::WalletLocked => exitcode::NOPERM,
+            ErrorCategory::ContractMismatch => exitcode::DATAERR,
+            ErrorCategory::TxRejected => exitcode::PROTOCOL,
+            ErrorCategory::Other => exitcode::SOFTWARE,
+        }
+    }
+
+    /// Category of the first error of the chain (from the outermost one) that has one
+    pub fn of(error: &anyhow::Error) -> Option<ErrorCategory> {
+        error.chain().find_map(|cause| {
+            if cause.is::<WalletLockedError>() {
+                return Some(ErrorCategory::WalletLocked);
+            }
+            if let Some(node_error) = cause.downcast_ref::<NodeError>() {
+                if let NodeError::NodeUnreachable = node_error {
+                    return Some(ErrorCategory::NodeConnectivity);
+                }
+                // the node rejects the submitted txs with a bad request
+                if let NodeError::BadRequest(_) = node_error {
+                    return Some(ErrorCategory::TxRejected);
+                }
+            }
+            if let Some(reqwest_error) = cause.downcast_ref::<reqwest::Error>() {
+                if reqwest_error.is_connect() || reqwest_error.is_timeout() {
+                    return Some(ErrorCategory::NodeConnectivity);
+                }
+            }
+            if cause.is::<OracleConfigFileError>()
+                || cause.is::<PoolConfigError>()
+                || cause.is::<serde_yaml::Error>()
+            {
+                return Some(ErrorCategory::Config);
+            }
+            if cause.is::<PoolBoxError>()
+                || cause.is::<OracleBoxError>()
+                || cause.is::<RefreshBoxError>()
+                || cause.is::<UpdateBoxError>()
+                || cause.is::<BallotBoxError>()
+                || cause.is::<PoolContractError>()
+                || cause.is::<OracleContractError>()
+                || cause.is::<RefreshContractError>()
+                || cause.is::<UpdateContractError>()
+                || cause.is::<BallotContractError>()
+            {
+                return Some(ErrorCategory::ContractMismatch);
+            }
+            None
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub category: ErrorCategory,
+    pub exit_code: i32,
+    pub command: String,
+    pub message: String,
+}
+
+impl ErrorReport {
+    /// Report of the error, in the `fallback` category if none of the errors of the chain has one
+    pub fn new(command: &str, error: &anyhow::Error, fallback: ErrorCategory) -> Self {
+        let category = ErrorCategory::of(error).unwrap_or(fallback);
+        ErrorReport {
+            category,
+            exit_code: category.exit_code(),
+            command: command.to_string(),
+            message: format!("{:#}", error),
+        }
+    }
+}
+
+/// Log the fatal error of the command and exit with the code of its category
+pub fn exit_with_error(command: &str, error: anyhow::Error, fallback: ErrorCategory) -> ! {
+    let report = ErrorReport::new(command, &error, fallback);
+    log::error!("Fatal {} error: {:?}", command, error);
+    if JSON_ERRORS.get().copied().unwrap_or(false) {
+        eprintln!("{}", serde_json::to_string(&report).unwrap());
+    }
+    std::process::exit(report.exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use anyhow::anyhow;
+    use anyhow::Context;
+
+    use crate::cli_commands::transfer_oracle_token::TransferOracleTokenActionError;
+
+    use super::*;
+
+    fn category(error: anyhow::Error) -> ErrorCategory {
+        ErrorReport::new("test", &error, ErrorCategory::Other).category
+    }
+
+    #[test]
+    fn test_error_categories() {
+        assert_eq!(
+            category(
+                Err::<(), _>(NodeError::NodeUnreachable)
+                    .context("fetching the height")
+                    .unwrap_err()
+            ),
+            ErrorCategory::NodeConnectivity
+        );
+        assert_eq!(
+            category(
+                TransferOracleTokenActionError::Node(NodeError::BadRequest(
+                    "double spending".to_string()
+                ))
+                .into()
+            ),
+            ErrorCategory::TxRejected
+        );
+        assert_eq!(
+            category(OracleConfigFileError::ParseError("bad yaml".to_string()).into()),
+            ErrorCategory::Config
+        );
+        assert_eq!(
+            category(WalletLockedError.into()),
+            ErrorCategory::WalletLocked
+        );
+        assert_eq!(category(anyhow!("unexpected")), ErrorCategory::Other);
+        assert_eq!(
+            ErrorReport::new("test", &anyhow!("unexpected"), ErrorCategory::Config).exit_code,
+            exitcode::CONFIG
+        );
+    }
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let categories = [
+            ErrorCategory::Config,
+            ErrorCategory::NodeConnectivity,
+            ErrorCategory::WalletLocked,
+            ErrorCategory::ContractMismatch,
+            ErrorCategory::TxRejected,
+            ErrorCategory::Other,
+        ];
+        let codes: HashSet<i32> = categories.iter().map(|c| c.exit_code()).collect();
+        assert_eq!(codes.len(), categories.len());
+        assert_eq!(
+            serde_json::to_value(ErrorCategory::NodeConnectivity).unwrap(),
+            "node_connectivity"
+        );
+    }
+}
@@ -3,6 +3,8 @@ use std::time::Duration;
 use ergo_lib::chain::transaction::Transaction;
 use ergo_lib::chain::transaction::TxId;
 use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
 use reqwest::blocking::RequestBuilder;
 use reqwest::blocking::Response;
 use reqwest::header::CONTENT_TYPE;
@@ -10,11 +12,13 @@ use reqwest::Url;
 use thiserror::Error;
 use url::ParseError;
 
-use crate::oracle_config::ORACLE_CONFIG;
+use crate::oracle_config::OracleConfig;
 
 use self::explorer_url::default_explorer_api_url;
 use self::explorer_url::default_explorer_url;
 
+pub mod ballot_fallback;
+pub mod datapoint_fallback;
 pub mod explorer_url;
 
 #[derive(Debug, Error)]
@@ -31,6 +35,19 @@ pub struct ExplorerApi {
     pub url: url::Url,
 }
 
+/// Box of the explorer along with the heights of its inclusion and spending tx
+#[derive(Debug, Clone)]
+pub struct ExplorerBox {
+    pub ergo_box: ErgoBox,
+    /// Height of the block that included the box
+    pub settlement_height: u32,
+    /// Tx that spent the box, `None` if the box is unspent
+    pub spent_transaction_id: Option<TxId>,
+}
+
+/// Boxes requested per page of the explorer
+const BOXES_PAGE_LIMIT: usize = 100;
+
 impl ExplorerApi {
     pub fn new(url: Url) -> Self {
         Self { url }
@@ -64,10 +81,99 @@ impl ExplorerApi {
         log::debug!("get_transaction_v1 response: {}", text);
         Ok(serde_json::from_str(&text)?)
     }
+
+    /// GET /api/v1/networkState, the height of the last block indexed by the explorer
+    pub fn get_network_height_v1(&self) -> Result<u32, ExplorerApiError> {
+        let response = self.send_get_req("/api/v1/networkState")?;
+        let json: serde_json::Value = serde_json::from_str(&response.text()?)?;
+        Ok(serde_json::from_value(json["height"].clone())?)
+    }
+
+    /// GET /api/v1/boxes/unspent/byTokenId/{id}
+    /// Boxes that fail to parse are skipped.
+    pub fn get_unspent_boxes_by_token_id_v1(
+        &self,
+        token_id: TokenId,
+    ) -> Result<Vec<ErgoBox>, ExplorerApiError> {
+        let endpoint = format!(
+            "/api/v1/boxes/unspent/byTokenId/{}?offset=0&limit=100",
+            String::from(token_id)
+        );
+        let response = self.send_get_req(&endpoint)?;
+        let json: serde_json::Value = serde_json::from_str(&response.text()?)?;
+        let items = match json["items"].as_array() {
+            Some(items) => items.clone(),
+            None => Vec::new(),
+        };
+        Ok(items
+            .into_iter()
+            .filter_map(|item| match serde_json::from_value::<ErgoBox>(item) {
+                Ok(b) => Some(b),
+                Err(e) => {
+                    log::debug!("failed to parse explorer box: {e}");
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// GET /api/v1/boxes/byTokenId/{id}, all the pages of the spent and unspent boxes.
+    /// Boxes that fail to parse are skipped.
+    pub fn get_boxes_by_token_id_v1(
+        &self,
+        token_id: TokenId,
+    ) -> Result<Vec<ExplorerBox>, ExplorerApiError> {
+        let token_id = String::from(token_id);
+        let mut boxes = Vec::new();
+        let mut offset = 0;
+        loop {
+            let endpoint = format!(
+                "/api/v1/boxes/byTokenId/{}?offset={}&limit={}",
+                token_id, offset, BOXES_PAGE_LIMIT
+            );
+            let response = self.send_get_req(&endpoint)?;
+            let json: serde_json::Value = serde_json::from_str(&response.text()?)?;
+            let items = match json["items"].as_array() {
+                Some(items) => items.clone(),
+                None => Vec::new(),
+            };
+            let page_len = items.len();
+            boxes.extend(
+                items
+                    .into_iter()
+                    .filter_map(|item| match parse_explorer_box(item) {
+                        Ok(b) => Some(b),
+                        Err(e) => {
+                            log::debug!("failed to parse explorer box: {e}");
+                            None
+                        }
+                    }),
+            );
+            if page_len < BOXES_PAGE_LIMIT {
+                break;
+            }
+            offset += page_len;
+        }
+        Ok(boxes)
+    }
+}
+
+fn parse_explorer_box(item: serde_json::Value) -> Result<ExplorerBox, serde_json::Error> {
+    let settlement_height = serde_json::from_value(item["settlementHeight"].clone())?;
+    let spent_transaction_id = serde_json::from_value(item["spentTransactionId"].clone())?;
+    Ok(ExplorerBox {
+        ergo_box: serde_json::from_value(item)?,
+        settlement_height,
+        spent_transaction_id,
+    })
 }
 
-pub(crate) fn ergo_explorer_transaction_link(tx_id: TxId, prefix: NetworkPrefix) -> String {
-    let url = ORACLE_CONFIG
+pub(crate) fn ergo_explorer_transaction_link(
+    tx_id: TxId,
+    prefix: NetworkPrefix,
+    oracle_config: &OracleConfig,
+) -> String {
+    let url = oracle_config
         .explorer_url
         .clone()
         .unwrap_or_else(|| default_explorer_url(prefix));
@@ -79,14 +185,14 @@ pub(crate) fn ergo_explorer_transaction_link(tx_id: TxId, prefix: NetworkPrefix)
         .to_string()
 }
 
-pub fn wait_for_tx_confirmation(tx_id: TxId) {
-    wait_for_txs_confirmation(vec![tx_id]);
+pub fn wait_for_tx_confirmation(tx_id: TxId, oracle_config: &OracleConfig) {
+    wait_for_txs_confirmation(vec![tx_id], oracle_config);
 }
 
-pub fn wait_for_txs_confirmation(tx_ids: Vec<TxId>) {
-    let network = ORACLE_CONFIG.oracle_address.network();
+pub fn wait_for_txs_confirmation(tx_ids: Vec<TxId>, oracle_config: &OracleConfig) {
+    let network = oracle_config.oracle_address.network();
     let timeout = Duration::from_secs(1200);
-    let explorer_url = ORACLE_CONFIG
+    let explorer_url = oracle_config
         .explorer_url
         .clone()
         .unwrap_or_else(|| default_explorer_api_url(network));
@@ -0,0 +1,158 @@
+//! Explorer fallback for the ballot boxes used in update-pool.
+//!
+//! The ballot token scan of the node only returns the boxes created after its registration (or the
+//! last rescan), so a participant who registered the scan late sees fewer votes than were cast.
+//! The unspent boxes holding the ballot token are fetched from the explorer and merged with the
+//! scan result, so the update tx can be built by anyone once the votes are on-chain.
+
+use std::collections::HashSet;
+
+use crate::box_kind::BallotBox;
+use crate::box_kind::BallotBoxWrapperInputs;
+use crate::box_kind::VoteBallotBoxWrapper;
+use crate::oracle_state::Result;
+use crate::oracle_state::VoteBallotBoxesSource;
+use crate::spec_token::TokenIdKind;
+
+use super::datapoint_fallback::UnspentBoxesByTokenSource;
+
+pub struct ExplorerFallbackBallotSource<'a> {
+    pub scan: &'a dyn VoteBallotBoxesSource,
+    pub explorer: &'a dyn UnspentBoxesByTokenSource,
+    pub ballot_box_wrapper_inputs: &'a BallotBoxWrapperInputs,
+}
+
+impl<'a> VoteBallotBoxesSource for ExplorerFallbackBallotSource<'a> {
+    fn get_ballot_boxes(&self) -> Result<Vec<VoteBallotBoxWrapper>> {
+        let mut boxes = self.scan.get_ballot_boxes()?;
+        let ballot_token_id = self.ballot_box_wrapper_inputs.ballot_token_id.token_id();
+        let explorer_boxes = match self.explorer.get_unspent_boxes_by_token_id(ballot_token_id) {
+            Ok(explorer_boxes) => explorer_boxes,
+            Err(e) => {
+                log::warn!("Explorer fallback for ballot boxes failed: {e}");
+                return Ok(boxes);
+            }
+        };
+        let known_box_ids: HashSet<_> = boxes.iter().map(|b| b.get_box().box_id()).collect();
+        let scan_count = boxes.len();
+        for explorer_box in explorer_boxes {
+            if known_box_ids.contains(&explorer_box.box_id()) {
+                continue;
+            }
+            // the ballot tokens held by wallets are not votes
+            if let Ok(ballot_box) =
+                VoteBallotBoxWrapper::new(explorer_box, self.ballot_box_wrapper_inputs)
+            {
+                boxes.push(ballot_box);
+            }
+        }
+        log::info!(
+            "Node scan returned {} ballot boxes, {} more found in the explorer",
+            scan_count,
+            boxes.len() - scan_count
+        );
+        Ok(boxes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use ergo_lib::chain::transaction::TxId;
+    use ergo_lib::ergo_chain_types::Digest32;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+    use ergo_lib::ergotree_ir::chain::token::TokenId;
+    use sigma_test_util::force_any_val;
+
+    use crate::box_kind::make_local_ballot_box_candidate;
+    use crate::contracts::ballot::BallotContract;
+    use crate::contracts::ballot::BallotContractInputs;
+    use crate::contracts::ballot::BallotContractParameters;
+    use crate::explorer_api::ExplorerApiError;
+    use crate::oracle_types::BlockHeight;
+    use crate::pool_commands::test_utils::generate_token_ids;
+    use crate::pool_commands::test_utils::make_wallet_unspent_box;
+    use crate::pool_commands::test_utils::BallotBoxesMock;
+    use crate::spec_token::SpecToken;
+
+    use super::*;
+
+    struct ExplorerMock {
+        boxes: Vec<ErgoBox>,
+    }
+
+    impl UnspentBoxesByTokenSource for ExplorerMock {
+        fn get_unspent_boxes_by_token_id(
+            &self,
+            _token_id: TokenId,
+        ) -> std::result::Result<Vec<ErgoBox>, ExplorerApiError> {
+            Ok(self.boxes.clone())
+        }
+    }
+
+    #[test]
+    fn test_explorer_ballot_boxes_are_merged() {
+        let token_ids = generate_token_ids();
+        let ballot_contract_inputs = BallotContractInputs::build_with(
+            BallotContractParameters::default(),
+            token_ids.update_nft_token_id.clone(),
+        )
+        .unwrap();
+        let ballot_contract = BallotContract::checked_load(&ballot_contract_inputs).unwrap();
+        let ballot_box_wrapper_inputs = BallotBoxWrapperInputs {
+            ballot_token_id: token_ids.ballot_token_id.clone(),
+            contract_inputs: ballot_contract_inputs,
+        };
+        let mut ballot_boxes: Vec<ErgoBox> = (0..3)
+            .map(|_| {
+                let candidate = make_local_ballot_box_candidate(
+                    &ballot_contract,
+                    *DlogProverInput::random().public_image().h,
+                    BlockHeight(100),
+                    SpecToken {
+                        token_id: token_ids.ballot_token_id.clone(),
+                        amount: 1.try_into().unwrap(),
+                    },
+                    force_any_val::<Digest32>(),
+                    None,
+                    ballot_contract.min_storage_rent(),
+                    BlockHeight(110),
+                )
+                .unwrap();
+                ErgoBox::from_box_candidate(&candidate, force_any_val::<TxId>(), 0).unwrap()
+            })
+            .collect();
+        let scan = BallotBoxesMock {
+            ballot_boxes: vec![VoteBallotBoxWrapper::new(
+                ballot_boxes[0].clone(),
+                &ballot_box_wrapper_inputs,
+            )
+            .unwrap()],
+        };
+        // a wallet box holding a ballot token is not a vote
+        ballot_boxes.push(make_wallet_unspent_box(
+            DlogProverInput::random().public_image(),
+            ballot_contract.min_storage_rent(),
+            Some(
+                vec![SpecToken {
+                    token_id: token_ids.ballot_token_id.clone(),
+                    amount: 1.try_into().unwrap(),
+                }
+                .into()]
+                .try_into()
+                .unwrap(),
+            ),
+        ));
+        let explorer = ExplorerMock {
+            boxes: ballot_boxes,
+        };
+        let source = ExplorerFallbackBallotSource {
+            scan: &scan,
+            explorer: &explorer,
+            ballot_box_wrapper_inputs: &ballot_box_wrapper_inputs,
+        };
+        assert_eq!(source.get_ballot_boxes().unwrap().len(), 3);
+    }
+}
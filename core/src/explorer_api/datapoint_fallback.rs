@@ -0,0 +1,163 @@
+//! Explorer fallback for the posted datapoint boxes used in refresh.
+//!
+//! The node scan can lag behind the chain, returning fewer datapoint boxes of the current epoch
+//! than a refresh needs. In that case the unspent boxes holding the oracle token are fetched from
+//! the explorer and merged with the scan result, so local scan staleness does not block the refresh
+//! of a healthy pool.
+
+use std::collections::HashSet;
+
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+
+use crate::box_kind::OracleBoxWrapper;
+use crate::box_kind::OracleBoxWrapperInputs;
+use crate::box_kind::PostedOracleBox;
+use crate::oracle_state::PostedDatapointBoxesSource;
+use crate::oracle_state::Result;
+use crate::oracle_types::EpochCounter;
+use crate::spec_token::TokenIdKind;
+
+use super::ExplorerApi;
+use super::ExplorerApiError;
+
+pub trait UnspentBoxesByTokenSource {
+    fn get_unspent_boxes_by_token_id(
+        &self,
+        token_id: TokenId,
+    ) -> std::result::Result<Vec<ErgoBox>, ExplorerApiError>;
+}
+
+impl UnspentBoxesByTokenSource for ExplorerApi {
+    fn get_unspent_boxes_by_token_id(
+        &self,
+        token_id: TokenId,
+    ) -> std::result::Result<Vec<ErgoBox>, ExplorerApiError> {
+        self.get_unspent_boxes_by_token_id_v1(token_id)
+    }
+}
+
+pub struct ExplorerFallbackDatapointSource<'a> {
+    pub scan: &'a dyn PostedDatapointBoxesSource,
+    pub explorer: &'a dyn UnspentBoxesByTokenSource,
+    pub oracle_box_wrapper_inputs: &'a OracleBoxWrapperInputs,
+    /// Epoch of the current pool box, only datapoint boxes of this epoch are counted
+    pub epoch_counter: EpochCounter,
+    /// Number of datapoint boxes of the current epoch below which the explorer is queried
+    pub min_data_points: usize,
+}
+
+impl<'a> PostedDatapointBoxesSource for ExplorerFallbackDatapointSource<'a> {
+    fn get_posted_datapoint_boxes(&self) -> Result<Vec<PostedOracleBox>> {
+        let mut boxes = self.scan.get_posted_datapoint_boxes()?;
+        let current_epoch_count = boxes
+            .iter()
+            .filter(|b| b.epoch_counter() == self.epoch_counter)
+            .count();
+        if current_epoch_count >= self.min_data_points {
+            return Ok(boxes);
+        }
+        let oracle_token_id = self.oracle_box_wrapper_inputs.oracle_token_id.token_id();
+        let explorer_boxes = match self.explorer.get_unspent_boxes_by_token_id(oracle_token_id) {
+            Ok(explorer_boxes) => explorer_boxes,
+            Err(e) => {
+                log::warn!("Explorer fallback for datapoint boxes failed: {e}");
+                return Ok(boxes);
+            }
+        };
+        let known_box_ids: HashSet<_> = boxes.iter().map(|b| b.get_box().box_id()).collect();
+        let mut added = 0;
+        for explorer_box in explorer_boxes {
+            if known_box_ids.contains(&explorer_box.box_id()) {
+                continue;
+            }
+            if let Ok(OracleBoxWrapper::Posted(posted)) =
+                OracleBoxWrapper::new(explorer_box, self.oracle_box_wrapper_inputs)
+            {
+                boxes.push(posted);
+                added += 1;
+            }
+        }
+        log::info!(
+            "Node scan returned {} datapoint boxes of epoch {}, {} more found in the explorer",
+            current_epoch_count,
+            self.epoch_counter.0,
+            added
+        );
+        Ok(boxes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergo_chain_types::EcPoint;
+    use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+    use sigma_test_util::force_any_val;
+
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::oracle_types::BlockHeight;
+    use crate::pool_commands::test_utils::generate_token_ids;
+    use crate::pool_commands::test_utils::make_datapoint_box;
+    use crate::pool_commands::test_utils::DatapointSourceMock;
+
+    use super::*;
+
+    struct ExplorerMock {
+        boxes: Vec<ErgoBox>,
+    }
+
+    impl UnspentBoxesByTokenSource for ExplorerMock {
+        fn get_unspent_boxes_by_token_id(
+            &self,
+            _token_id: TokenId,
+        ) -> std::result::Result<Vec<ErgoBox>, ExplorerApiError> {
+            Ok(self.boxes.clone())
+        }
+    }
+
+    #[test]
+    fn test_explorer_fallback_on_scan_lag() {
+        let token_ids = generate_token_ids();
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let datapoint_boxes: Vec<ErgoBox> = (0..3)
+            .map(|i| {
+                make_datapoint_box(
+                    force_any_val::<EcPoint>(),
+                    200 + i,
+                    EpochCounter(1),
+                    &token_ids,
+                    BoxValue::SAFE_USER_MIN,
+                    BlockHeight(100),
+                    100,
+                )
+            })
+            .collect();
+        let scan = DatapointSourceMock {
+            datapoints: vec![PostedOracleBox::new(
+                datapoint_boxes[0].clone(),
+                &oracle_box_wrapper_inputs,
+            )
+            .unwrap()],
+        };
+        let explorer = ExplorerMock {
+            boxes: datapoint_boxes,
+        };
+        let source = ExplorerFallbackDatapointSource {
+            scan: &scan,
+            explorer: &explorer,
+            oracle_box_wrapper_inputs: &oracle_box_wrapper_inputs,
+            epoch_counter: EpochCounter(1),
+            min_data_points: 2,
+        };
+        assert_eq!(source.get_posted_datapoint_boxes().unwrap().len(), 3);
+
+        // enough boxes in the scan, the explorer is not queried
+        let source = ExplorerFallbackDatapointSource {
+            min_data_points: 1,
+            ..source
+        };
+        assert_eq!(source.get_posted_datapoint_boxes().unwrap().len(), 1);
+    }
+}
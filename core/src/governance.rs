@@ -0,0 +1,245 @@
+//! Watcher of the pool governance: the ballot boxes voting with the current update box are tallied
+//! every main loop iteration. A new update proposal and a proposal getting close to the `min_votes`
+//! of the update contract are logged and sent as alerts, so a vote is not missed. Votes cast with
+//! an older update box are stale and not counted.
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::box_kind::BallotBox;
+use crate::box_kind::CastBallotBoxVoteParameters;
+use crate::notifications::NotificationEvent;
+use crate::notifications::Notifier;
+use crate::oracle_state::DataSourceError;
+use crate::oracle_state::OraclePoolCache;
+use crate::oracle_state::UpdateBoxSource;
+use crate::oracle_state::VoteBallotBoxesSource;
+use crate::spec_token::TokenIdKind;
+
+/// Votes a proposal may be short of `min_votes` to be reported as approaching the quorum
+pub const QUORUM_MARGIN_VOTES: u64 = 1;
+
+/// Pool contract and reward tokens voted for, with the votes it got
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UpdateProposal {
+    pub pool_box_address_hash: String,
+    pub reward_token_id: Option<String>,
+    pub reward_token_amount: Option<u64>,
+    /// Ballot tokens in the boxes voting for the proposal
+    pub votes: u64,
+    pub ballot_boxes: usize,
+}
+
+impl UpdateProposal {
+    fn from_vote_parameters(vote_parameters: &CastBallotBoxVoteParameters) -> Self {
+        let reward_token = vote_parameters.reward_token_opt.as_ref();
+        UpdateProposal {
+            pool_box_address_hash: String::from(vote_parameters.pool_box_address_hash),
+            reward_token_id: reward_token.map(|t| String::from(t.token_id.token_id())),
+            reward_token_amount: reward_token.map(|t| *t.amount.as_u64()),
+            votes: 0,
+            ballot_boxes: 0,
+        }
+    }
+
+    fn key(&self) -> (String, Option<String>, Option<u64>) {
+        (
+            self.pool_box_address_hash.clone(),
+            self.reward_token_id.clone(),
+            self.reward_token_amount,
+        )
+    }
+
+    pub fn is_quorum_approaching(&self, min_votes: u32) -> bool {
+        self.votes + QUORUM_MARGIN_VOTES >= min_votes as u64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GovernanceStatus {
+    pub update_box_height: u32,
+    pub min_votes: u32,
+    /// Proposals voted for with the current update box, most votes first
+    pub proposals: Vec<UpdateProposal>,
+    /// Ballot boxes voting with an older update box
+    pub stale_ballot_boxes: usize,
+}
+
+/// Tally of the votes, given as the vote parameters and the ballot token amount of every box
+pub fn tally(
+    update_box_height: u32,
+    min_votes: u32,
+    votes: impl IntoIterator<Item = (CastBallotBoxVoteParameters, u64)>,
+) -> GovernanceStatus {
+    let mut proposals: Vec<UpdateProposal> = Vec::new();
+    let mut stale_ballot_boxes = 0;
+    for (vote_parameters, amount) in votes {
+        if vote_parameters.update_box_creation_height != update_box_height as i32 {
+            stale_ballot_boxes += 1;
+            continue;
+        }
+        let voted = UpdateProposal::from_vote_parameters(&vote_parameters);
+        let proposal = match proposals.iter().position(|p| p.key() == voted.key()) {
+            Some(pos) => &mut proposals[pos],
+            None => {
+                proposals.push(voted);
+                proposals.last_mut().unwrap()
+            }
+        };
+        proposal.votes += amount;
+        proposal.ballot_boxes += 1;
+    }
+    proposals.sort_by(|a, b| b.votes.cmp(&a.votes));
+    GovernanceStatus {
+        update_box_height,
+        min_votes,
+        proposals,
+        stale_ballot_boxes,
+    }
+}
+
+pub fn governance_status(
+    update_box_source: &dyn UpdateBoxSource,
+    ballot_boxes_source: &dyn VoteBallotBoxesSource,
+) -> Result<GovernanceStatus, DataSourceError> {
+    let update_box = update_box_source.get_update_box()?;
+    let ballot_boxes = ballot_boxes_source.get_ballot_boxes()?;
+    Ok(tally(
+        update_box.get_box().creation_height,
+        update_box.min_votes(),
+        ballot_boxes.iter().map(|ballot_box| {
+            (
+                ballot_box.vote_parameters().clone(),
+                *ballot_box.ballot_token().amount.as_u64(),
+            )
+        }),
+    ))
+}
+
+/// Proposals already reported, kept between the main loop iterations
+#[derive(Debug, Default)]
+pub struct GovernanceWatcher {
+    update_box_height: Option<u32>,
+    proposed: HashSet<(String, Option<String>, Option<u64>)>,
+    approaching: HashSet<(String, Option<String>, Option<u64>)>,
+}
+
+impl GovernanceWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events for the proposals and quorums not reported yet. A proposal reported before is
+    /// reported again once it gets votes after losing all of them, or with a new update box.
+    pub fn events(&mut self, status: &GovernanceStatus) -> Vec<NotificationEvent> {
+        if self.update_box_height != Some(status.update_box_height) {
+            self.update_box_height = Some(status.update_box_height);
+            self.proposed.clear();
+            self.approaching.clear();
+        }
+        let keys: HashSet<_> = status.proposals.iter().map(UpdateProposal::key).collect();
+        self.proposed.retain(|key| keys.contains(key));
+        self.approaching.retain(|key| keys.contains(key));
+
+        let mut events = Vec::new();
+        for proposal in &status.proposals {
+            if self.proposed.insert(proposal.key()) {
+                events.push(NotificationEvent::UpdateProposed {
+                    pool_box_address_hash: proposal.pool_box_address_hash.clone(),
+                    votes: proposal.votes,
+                    min_votes: status.min_votes,
+                });
+            }
+            if proposal.is_quorum_approaching(status.min_votes)
+                && self.approaching.insert(proposal.key())
+            {
+                events.push(NotificationEvent::UpdateQuorumApproaching {
+                    pool_box_address_hash: proposal.pool_box_address_hash.clone(),
+                    votes: proposal.votes,
+                    min_votes: status.min_votes,
+                });
+            }
+        }
+        events
+    }
+
+    /// Tally the votes of the pool, log and send the new events
+    pub fn check(&mut self, pool_name: &str, oracle_pool: &OraclePoolCache, notifier: &Notifier) {
+        let status = match governance_status(
+            oracle_pool.get_update_box_source(),
+            oracle_pool.get_ballot_boxes_source(),
+        ) {
+            Ok(status) => status,
+            Err(e) => {
+                log::debug!("Failed to tally the update votes: {}", e);
+                return;
+            }
+        };
+        for event in self.events(&status) {
+            log::warn!("GOVERNANCE: {}", event.message());
+            notifier.notify(pool_name, &event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergo_chain_types::blake2b256_hash;
+
+    use super::*;
+
+    fn vote(hash_byte: u8, update_box_height: i32) -> CastBallotBoxVoteParameters {
+        CastBallotBoxVoteParameters {
+            pool_box_address_hash: blake2b256_hash(&[hash_byte]),
+            reward_token_opt: None,
+            update_box_creation_height: update_box_height,
+        }
+    }
+
+    #[test]
+    fn test_tally() {
+        let status = tally(
+            100,
+            3,
+            vec![
+                (vote(1, 100), 1),
+                (vote(2, 100), 1),
+                (vote(2, 100), 1),
+                (vote(2, 90), 1),
+            ],
+        );
+        assert_eq!(status.stale_ballot_boxes, 1);
+        assert_eq!(status.proposals.len(), 2);
+        assert_eq!(
+            status.proposals[0].pool_box_address_hash,
+            String::from(blake2b256_hash(&[2]))
+        );
+        assert_eq!(status.proposals[0].votes, 2);
+        assert_eq!(status.proposals[0].ballot_boxes, 2);
+        assert!(status.proposals[0].is_quorum_approaching(3));
+        assert!(!status.proposals[1].is_quorum_approaching(3));
+    }
+
+    #[test]
+    fn test_watcher_reports_once() {
+        let mut watcher = GovernanceWatcher::new();
+        let status = tally(100, 3, vec![(vote(1, 100), 1)]);
+        assert!(matches!(
+            watcher.events(&status).as_slice(),
+            [NotificationEvent::UpdateProposed { votes: 1, .. }]
+        ));
+        assert!(watcher.events(&status).is_empty());
+
+        let status = tally(100, 3, vec![(vote(1, 100), 1), (vote(1, 100), 1)]);
+        assert!(matches!(
+            watcher.events(&status).as_slice(),
+            [NotificationEvent::UpdateQuorumApproaching { votes: 2, .. }]
+        ));
+        assert!(watcher.events(&status).is_empty());
+
+        // a new update box starts a new vote
+        let status = tally(150, 3, vec![(vote(1, 150), 1)]);
+        assert_eq!(watcher.events(&status).len(), 1);
+    }
+}
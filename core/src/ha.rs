@@ -0,0 +1,295 @@
+//! Active/standby coordination of two oracle-core instances sharing the same oracle token.
+//!
+//! Both instances run with the same oracle address. The active one submits transactions and, if a
+//! lock file on shared storage is configured, writes a heartbeat into it on every main loop
+//! iteration. The standby one only watches the chain: once the local oracle box shows that no
+//! datapoint was posted for `takeover_after_missed_epochs` epochs it takes over, claims the lock
+//! file and starts publishing. An active instance that finds a fresh heartbeat of another instance
+//! in the lock file steps down to standby, so at most one of them keeps publishing.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::oracle_state::LiveEpochState;
+use crate::oracle_state::LocalDatapointState;
+use crate::oracle_types::BlockHeight;
+use crate::oracle_types::EpochLength;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HaRole {
+    Active,
+    Standby,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct HaConfig {
+    /// Unique name of this instance, stored in the lock file heartbeat
+    pub instance_id: String,
+    /// Role on startup
+    pub role: HaRole,
+    /// Heartbeat file on storage shared by both instances
+    pub lock_file: Option<PathBuf>,
+    /// Number of epochs without a posted datapoint after which the standby takes over
+    pub takeover_after_missed_epochs: u32,
+}
+
+/// Content of the lock file
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Heartbeat {
+    pub instance_id: String,
+    pub height: BlockHeight,
+}
+
+impl Heartbeat {
+    fn is_stale(&self, current_height: BlockHeight, stale_after_blocks: u32) -> bool {
+        self.height.0.saturating_add(stale_after_blocks) < current_height.0
+    }
+}
+
+/// Number of whole epochs passed since the local oracle last posted a datapoint.
+/// Returns `None` if there is no local oracle box yet.
+pub fn missed_epochs(
+    live_epoch: &LiveEpochState,
+    epoch_length: EpochLength,
+    current_height: BlockHeight,
+) -> Option<u32> {
+    let last_post_height = match live_epoch.local_datapoint_box_state.as_ref()? {
+        LocalDatapointState::Posted { epoch_id, .. }
+            if *epoch_id == live_epoch.pool_box_epoch_id =>
+        {
+            return Some(0)
+        }
+        LocalDatapointState::Posted { height, .. } => height,
+        LocalDatapointState::Collected { height } => height,
+    };
+    let epoch_length = epoch_length.0.max(1) as u32;
+    Some(current_height.0.saturating_sub(last_post_height.0) / epoch_length)
+}
+
+pub struct HaCoordinator {
+    config: HaConfig,
+    lock_file: Option<PathBuf>,
+    active: bool,
+}
+
+impl HaCoordinator {
+    /// `pool_name` is set for additional pools run in the same process, each of them gets its own
+    /// lock file next to the configured one.
+    pub fn new(config: HaConfig, pool_name: Option<&str>) -> Self {
+        let lock_file = match (config.lock_file.as_ref(), pool_name) {
+            (Some(path), Some(name)) => Some(PathBuf::from(format!("{}.{}", path.display(), name))),
+            (Some(path), None) => Some(path.clone()),
+            (None, _) => None,
+        };
+        let active = config.role == HaRole::Active;
+        HaCoordinator {
+            config,
+            lock_file,
+            active,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Decide whether this instance should submit transactions on this main loop iteration
+    pub fn may_act(
+        &mut self,
+        live_epoch: &LiveEpochState,
+        epoch_length: EpochLength,
+        height: BlockHeight,
+    ) -> bool {
+        let stale_after_blocks = self
+            .config
+            .takeover_after_missed_epochs
+            .saturating_mul(epoch_length.0.max(1) as u32);
+        let heartbeat = self.read_heartbeat();
+        if self.active {
+            if let Some(hb) = heartbeat.as_ref().filter(|hb| {
+                hb.instance_id != self.config.instance_id
+                    && !hb.is_stale(height, stale_after_blocks)
+            }) {
+                log::warn!(
+                    "HA: instance {} holds the lock file (heartbeat at height {}), switching to standby",
+                    hb.instance_id,
+                    hb.height
+                );
+                self.active = false;
+                return false;
+            }
+        } else {
+            let heartbeat_is_stale = heartbeat
+                .as_ref()
+                .map(|hb| hb.is_stale(height, stale_after_blocks))
+                .unwrap_or(false);
+            let take_over = match missed_epochs(live_epoch, epoch_length, height) {
+                Some(missed) => missed >= self.config.takeover_after_missed_epochs,
+                None => heartbeat_is_stale,
+            };
+            if !take_over {
+                log::debug!("HA: standby, the active instance is posting");
+                return false;
+            }
+            log::warn!(
+                "HA: no datapoint posted for {} epochs, taking over as active instance {}",
+                self.config.takeover_after_missed_epochs,
+                self.config.instance_id
+            );
+            self.active = true;
+        }
+        self.write_heartbeat(height);
+        true
+    }
+
+    fn read_heartbeat(&self) -> Option<Heartbeat> {
+        let path = self.lock_file.as_ref()?;
+        let content = std::fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(hb) => Some(hb),
+            Err(e) => {
+                log::warn!("HA: cannot parse lock file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    fn write_heartbeat(&self, height: BlockHeight) {
+        let path = match self.lock_file.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+        let heartbeat = Heartbeat {
+            instance_id: self.config.instance_id.clone(),
+            height,
+        };
+        // write to a temporary file first so the other instance never reads a partial heartbeat
+        let tmp_path = PathBuf::from(format!("{}.tmp", path.display()));
+        let res = std::fs::File::create(&tmp_path)
+            .and_then(|mut file| file.write_all(serde_json::to_string(&heartbeat)?.as_bytes()))
+            .and_then(|_| std::fs::rename(&tmp_path, path));
+        if let Err(e) = res {
+            log::error!("HA: failed to write lock file {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::oracle_types::EpochCounter;
+
+    use super::*;
+
+    fn live_epoch(local: Option<LocalDatapointState>) -> LiveEpochState {
+        LiveEpochState {
+            pool_box_epoch_id: EpochCounter(10),
+            local_datapoint_box_state: local,
+            latest_pool_datapoint: 1,
+            latest_pool_box_height: BlockHeight(1000),
+            local_refresh_rank: None,
+            other_posted_datapoint_heights: Vec::new(),
+        }
+    }
+
+    fn config(instance_id: &str, role: HaRole, lock_file: Option<PathBuf>) -> HaConfig {
+        HaConfig {
+            instance_id: instance_id.to_string(),
+            role,
+            lock_file,
+            takeover_after_missed_epochs: 3,
+        }
+    }
+
+    fn temp_lock_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "oracle-core-ha-{}-{}.lock",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_missed_epochs() {
+        let epoch_length = EpochLength(30);
+        let height = BlockHeight(1100);
+        let posted_current = live_epoch(Some(LocalDatapointState::Posted {
+            epoch_id: EpochCounter(10),
+            height: BlockHeight(900),
+        }));
+        assert_eq!(
+            missed_epochs(&posted_current, epoch_length, height),
+            Some(0)
+        );
+        let posted_old = live_epoch(Some(LocalDatapointState::Posted {
+            epoch_id: EpochCounter(8),
+            height: BlockHeight(1000),
+        }));
+        assert_eq!(missed_epochs(&posted_old, epoch_length, height), Some(3));
+        let collected = live_epoch(Some(LocalDatapointState::Collected {
+            height: BlockHeight(1080),
+        }));
+        assert_eq!(missed_epochs(&collected, epoch_length, height), Some(0));
+        assert_eq!(missed_epochs(&live_epoch(None), epoch_length, height), None);
+    }
+
+    #[test]
+    fn test_standby_takes_over_after_missed_epochs() {
+        let epoch_length = EpochLength(30);
+        let mut ha = HaCoordinator::new(config("b", HaRole::Standby, None), None);
+        let state = live_epoch(Some(LocalDatapointState::Collected {
+            height: BlockHeight(1000),
+        }));
+        assert!(!ha.may_act(&state, epoch_length, BlockHeight(1050)));
+        assert!(!ha.is_active());
+        assert!(ha.may_act(&state, epoch_length, BlockHeight(1090)));
+        assert!(ha.is_active());
+    }
+
+    #[test]
+    fn test_lock_file_handover() {
+        let epoch_length = EpochLength(30);
+        let lock_file = temp_lock_file("handover");
+        let mut active =
+            HaCoordinator::new(config("a", HaRole::Active, Some(lock_file.clone())), None);
+        let mut standby =
+            HaCoordinator::new(config("b", HaRole::Standby, Some(lock_file.clone())), None);
+        let posting = live_epoch(Some(LocalDatapointState::Posted {
+            epoch_id: EpochCounter(10),
+            height: BlockHeight(1010),
+        }));
+        assert!(active.may_act(&posting, epoch_length, BlockHeight(1020)));
+        assert!(!standby.may_act(&posting, epoch_length, BlockHeight(1020)));
+
+        // the active instance stops posting, the standby takes over and claims the lock file
+        let stalled = live_epoch(Some(LocalDatapointState::Collected {
+            height: BlockHeight(1000),
+        }));
+        assert!(standby.may_act(&stalled, epoch_length, BlockHeight(1100)));
+        // the former active instance sees the fresh heartbeat and steps down
+        assert!(!active.may_act(&stalled, epoch_length, BlockHeight(1101)));
+        assert!(!active.is_active());
+        assert!(standby.may_act(&stalled, epoch_length, BlockHeight(1101)));
+        let _ = std::fs::remove_file(&lock_file);
+    }
+
+    #[test]
+    fn test_no_local_box_takeover_on_stale_heartbeat() {
+        let epoch_length = EpochLength(30);
+        let lock_file = temp_lock_file("no-box");
+        let mut active =
+            HaCoordinator::new(config("a", HaRole::Active, Some(lock_file.clone())), None);
+        let mut standby =
+            HaCoordinator::new(config("b", HaRole::Standby, Some(lock_file.clone())), None);
+        let no_box = live_epoch(None);
+        assert!(active.may_act(&no_box, epoch_length, BlockHeight(1000)));
+        assert!(!standby.may_act(&no_box, epoch_length, BlockHeight(1050)));
+        assert!(standby.may_act(&no_box, epoch_length, BlockHeight(1091)));
+        let _ = std::fs::remove_file(&lock_file);
+    }
+}
@@ -0,0 +1,170 @@
+//! Pinning of the signed datapoint attestations to IPFS (`ipfs` in the oracle config), a public
+//! audit trail of what the oracle observed even when its datapoint was not collected on-chain.
+//!
+//! Every attestation is added through the `/api/v0/add` endpoint of a Kubo-compatible HTTP API
+//! (a local IPFS node or a pinning service) as a JSON document with the pool name, the attestation
+//! and the raw rates of the sources. Pinning runs on its own thread so the datapoint post is not
+//! delayed, the CID is logged and shown with the attestation by the REST API. A failed pin is
+//! logged and not retried.
+
+use std::thread;
+use std::time::Duration;
+
+use reqwest::blocking::multipart::Form;
+use reqwest::blocking::multipart::Part;
+use reqwest::blocking::Client;
+use reqwest::header::AUTHORIZATION;
+use reqwest::Url;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::attestation::Attestation;
+use crate::attestation::LatestAttestation;
+use crate::datapoint_source::SourceRate;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct IpfsConfig {
+    /// Base URL of the Kubo-compatible HTTP API, e.g. `http://127.0.0.1:5001`
+    pub api_url: Url,
+    /// Value of the `Authorization` header (e.g. `Basic ...` or `Bearer ...` of a pinning service)
+    /// or `keyring:<entry name>`
+    pub auth_header: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum IpfsError {
+    #[error("invalid api_url: {0}")]
+    Url(#[from] url::ParseError),
+    #[error("reqwest error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("serde error: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("IPFS API rejected the document with status {status}: {body}")]
+    Rejected { status: u16, body: String },
+    #[error("no Hash in the IPFS API response: {0}")]
+    NoHash(String),
+}
+
+/// Document pinned for every attestation
+#[derive(Debug, Serialize)]
+pub struct AttestationDocument<'a> {
+    pub pool: &'a str,
+    pub attestation: &'a Attestation,
+    /// Raw rates the datapoint was aggregated from, not covered by the signature
+    pub sources: &'a [SourceRate],
+}
+
+#[derive(Clone)]
+pub struct IpfsPinner {
+    client: Client,
+    add_url: Url,
+    auth_header: Option<String>,
+}
+
+impl IpfsPinner {
+    pub fn new(config: IpfsConfig) -> Result<Self, IpfsError> {
+        Ok(IpfsPinner {
+            client: Client::new(),
+            add_url: config.api_url.join("api/v0/add")?,
+            auth_header: config.auth_header,
+        })
+    }
+
+    /// Add and pin the document, returns its CID
+    pub fn pin(&self, document: &AttestationDocument) -> Result<String, IpfsError> {
+        let part = Part::bytes(serde_json::to_vec(document)?)
+            .file_name("attestation.json")
+            .mime_str("application/json")?;
+        let mut request = self
+            .client
+            .post(self.add_url.clone())
+            .query(&[("pin", "true"), ("cid-version", "1")])
+            .multipart(Form::new().part("file", part))
+            .timeout(REQUEST_TIMEOUT);
+        if let Some(auth_header) = &self.auth_header {
+            request = request.header(AUTHORIZATION, auth_header);
+        }
+        let response = request.send()?;
+        let status = response.status();
+        let body = response.text()?;
+        if !status.is_success() {
+            return Err(IpfsError::Rejected {
+                status: status.as_u16(),
+                body,
+            });
+        }
+        cid_from_response(&body)
+    }
+
+    /// Pin the attestation on a new thread, the CID is recorded in `latest` for the REST API
+    pub fn pin_in_background(
+        &self,
+        pool_name: &str,
+        latest: &LatestAttestation,
+        attestation: Attestation,
+        sources: Vec<SourceRate>,
+    ) {
+        let pinner = self.clone();
+        let latest = latest.clone();
+        let pool_name = pool_name.to_string();
+        let spawned = thread::Builder::new()
+            .name(format!("ipfs-{}", pool_name))
+            .spawn(move || {
+                let document = AttestationDocument {
+                    pool: &pool_name,
+                    attestation: &attestation,
+                    sources: &sources,
+                };
+                match pinner.pin(&document) {
+                    Ok(cid) => {
+                        log::info!(
+                            "Pinned the attestation of rate {} at height {} to IPFS as {}",
+                            attestation.rate,
+                            attestation.height,
+                            cid
+                        );
+                        latest.record_ipfs_cid(&attestation.signature, cid);
+                    }
+                    Err(e) => log::error!("Failed to pin the attestation to IPFS: {}", e),
+                }
+            });
+        if let Err(e) = spawned {
+            log::error!("Failed to start pinning the attestation to IPFS: {}", e);
+        }
+    }
+}
+
+fn cid_from_response(body: &str) -> Result<String, IpfsError> {
+    let json: serde_json::Value = serde_json::from_str(body)?;
+    json["Hash"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| IpfsError::NoHash(body.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_url_and_cid() {
+        let pinner = IpfsPinner::new(IpfsConfig {
+            api_url: Url::parse("http://127.0.0.1:5001").unwrap(),
+            auth_header: None,
+        })
+        .unwrap();
+        assert_eq!(pinner.add_url.as_str(), "http://127.0.0.1:5001/api/v0/add");
+        assert_eq!(
+            cid_from_response(r#"{"Name":"attestation.json","Hash":"bafkrei","Size":"120"}"#)
+                .unwrap(),
+            "bafkrei"
+        );
+        assert!(matches!(
+            cid_from_response(r#"{"Message":"error"}"#),
+            Err(IpfsError::NoHash(_))
+        ));
+    }
+}
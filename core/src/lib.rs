@@ -0,0 +1,100 @@
+//! Oracle core of the Ergo oracle pools v2.
+//!
+//! The library holds the pool logic so it can be embedded by other projects (e.g. Djed off-chain
+//! bots or dashboards) without shelling out to the `oracle-core` binary, which is a thin CLI on
+//! top of it. The main entry points are:
+//!
+//! - [`oracle_state`] - pool state (pool, refresh, oracle, ballot and update boxes) read from the
+//!   node scans, see [`oracle_state::OraclePool`];
+//! - [`box_kind`] and [`contracts`] - typed wrappers of the pool boxes and their contracts;
+//! - [`pool_commands`] and [`actions`] - building the publish/refresh txs with
+//!   [`pool_commands::build_action`] and submitting them with [`actions::execute_action`];
+//! - [`tx_api`] - building the publish, refresh, vote, update and extract txs from explicit boxes,
+//!   height, change address and fees, without a config file, for signing them elsewhere;
+//! - [`cold_signing`] - EIP-19 cold signing requests of unsigned txs for offline wallets;
+//! - [`sim`] (`sim` feature) - a pool bootstrapped on a simulated chain, driving the publish,
+//!   refresh, vote and update txs of several oracles end to end without a node;
+//! - [`datapoint_source`] - predefined and external script datapoint sources;
+//! - [`node_interface`] - node API client, see [`node_interface::node_api::NodeApi`];
+//! - [`oracle_config`] and [`pool_config`] - the configs, passed together to the pool and the
+//!   commands in a [`context::OracleContext`].
+//!
+//! The other modules (REST API, notifications, metrics, audit log, ...) serve the `run` command of
+//! the binary and are public for completeness, they are not meant as a stable API.
+
+// Coding conventions
+#![allow(dead_code)]
+#![allow(clippy::redundant_clone)]
+#![allow(clippy::ptr_arg)]
+#![allow(clippy::unit_arg)]
+#![forbid(unsafe_code)]
+#![deny(non_upper_case_globals)]
+#![deny(non_camel_case_types)]
+#![deny(non_snake_case)]
+#![deny(unused_mut)]
+#![deny(unused_imports)]
+#![deny(clippy::wildcard_enum_match_arm)]
+#![deny(clippy::todo)]
+#![deny(clippy::unimplemented)]
+
+#[macro_use]
+extern crate lazy_static;
+
+pub mod action_report;
+pub mod actions;
+pub mod address_util;
+#[cfg(feature = "api")]
+pub mod api;
+pub mod attestation;
+pub mod audit_log;
+pub mod babel_fee;
+pub mod box_kind;
+pub mod cli_commands;
+pub mod cold_signing;
+pub mod context;
+pub mod contracts;
+pub mod datapoint_anomaly;
+pub mod datapoint_source;
+pub mod default_parameters;
+pub mod djed;
+pub mod epoch_history;
+pub mod error_reporting;
+pub mod event_stream;
+pub mod evm_relay;
+pub mod exit_status;
+pub mod explorer_api;
+pub mod governance;
+pub mod ha;
+pub mod history_db;
+pub mod ipfs;
+pub mod logging;
+pub mod metrics;
+pub mod migrate;
+pub mod monitor;
+pub mod mqtt;
+pub mod multi_pool;
+pub mod node_interface;
+pub mod notifications;
+pub mod operations_report;
+pub mod oracle_config;
+pub mod oracle_state;
+pub mod oracle_types;
+pub mod pool_commands;
+pub mod pool_config;
+pub mod posting_policy;
+pub mod rebroadcast;
+pub mod redact;
+pub mod scans;
+pub mod secrets;
+pub mod serde;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+pub mod spec_token;
+pub mod state;
+pub mod telemetry;
+pub mod templates;
+pub mod tx_api;
+pub mod wallet;
+
+#[cfg(test)]
+mod tests;
@@ -9,14 +9,46 @@ use log4rs::append::rolling_file::RollingFileAppender;
 use log4rs::config::Appender;
 use log4rs::config::Logger;
 use log4rs::config::Root;
+use log4rs::encode::json::JsonEncoder;
+use log4rs::encode::pattern::PatternEncoder;
+use log4rs::encode::Encode;
 use log4rs::Config;
+#[cfg(feature = "sentry")]
+use sentry::integrations::log::SentryLogger;
+use serde::Deserialize;
+use serde::Serialize;
+
+#[cfg(feature = "sentry")]
+use crate::error_reporting;
+use crate::redact::RedactingLogger;
+
+/// Format of the log lines written to stdout and the log file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line with `time`, `level`, `target`, `message` and the `mdc` fields
+    /// (`pool`, `height`, `tx_id`) set by the oracle loop
+    Json,
+}
+
+fn encoder(log_format: LogFormat) -> Box<dyn Encode> {
+    match log_format {
+        LogFormat::Text => Box::new(PatternEncoder::default()),
+        LogFormat::Json => Box::new(JsonEncoder::new()),
+    }
+}
 
 pub fn setup_log(
     cmdline_log_level: Option<LevelFilter>,
     config_log_level: Option<LevelFilter>,
     data_dir: &Path,
+    log_format: LogFormat,
 ) {
-    let stdout = ConsoleAppender::builder().build();
+    let stdout = ConsoleAppender::builder()
+        .encoder(encoder(log_format))
+        .build();
 
     // via https://stackoverflow.com/questions/56345288/how-do-i-use-log4rs-rollingfileappender-to-incorporate-rolling-logging#
     let window_size = 3; // log0, log1, log2
@@ -51,6 +83,7 @@ pub fn setup_log(
                 "logfile",
                 Box::new(
                     RollingFileAppender::builder()
+                        .encoder(encoder(log_format))
                         .build(data_dir.join("oracle-core.log"), Box::new(compound_policy))
                         .unwrap(),
                 ),
@@ -71,7 +104,14 @@ pub fn setup_log(
         )
         .unwrap();
 
-    let _ = log4rs::init_config(config).unwrap();
+    // records are also turned into Sentry events or breadcrumbs once error_reporting::init bound
+    // a client (with the `sentry` feature), the secrets and addresses are masked in both
+    let logger = log4rs::Logger::new(config);
+    let max_log_level = logger.max_log_level();
+    #[cfg(feature = "sentry")]
+    let logger = SentryLogger::with_dest(logger).filter(error_reporting::log_filter);
+    log::set_boxed_logger(Box::new(RedactingLogger::new(logger))).unwrap();
+    log::set_max_level(max_log_level);
 
     log_panics::init();
 }
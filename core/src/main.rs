@@ -15,45 +15,9 @@
 // #![allow(clippy::correctness)]
 // #![allow(clippy::almost_swapped)]
 
-#[macro_use]
-extern crate lazy_static;
-
-mod action_report;
-mod actions;
-mod address_util;
-mod api;
-mod box_kind;
-mod cli_commands;
-mod contracts;
-mod datapoint_source;
-mod default_parameters;
-mod explorer_api;
-mod logging;
-mod migrate;
-mod node_interface;
-mod oracle_config;
-mod oracle_state;
-mod oracle_types;
-mod pool_commands;
-mod pool_config;
-mod scans;
-mod serde;
-mod spec_token;
-mod state;
-mod templates;
-mod wallet;
-
-#[cfg(test)]
-mod tests;
-
-use action_report::ActionReportStorage;
-use action_report::PoolActionReport;
-use actions::PoolAction;
 use anyhow::anyhow;
 use anyhow::Context;
 use clap::{Parser, Subcommand};
-use crossbeam::channel::bounded;
-use datapoint_source::RuntimeDataPointSource;
 use ergo_lib::ergo_chain_types::Digest32;
 use ergo_lib::ergotree_ir::chain::address::Address;
 use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
@@ -62,44 +26,125 @@ use ergo_lib::ergotree_ir::chain::token::TokenAmount;
 use ergo_lib::ergotree_ir::chain::token::TokenId;
 use log::error;
 use log::LevelFilter;
-use node_interface::assert_wallet_unlocked;
-use node_interface::node_api::NodeApi;
-use oracle_config::ORACLE_CONFIG;
-use oracle_state::OraclePool;
-use oracle_types::BlockHeight;
-use pool_commands::build_action;
-use pool_commands::publish_datapoint::PublishDatapointActionError;
-use pool_commands::refresh::RefreshActionError;
-use pool_commands::PoolCommandError;
-use pool_config::DEFAULT_POOL_CONFIG_FILE_NAME;
-use pool_config::POOL_CONFIG;
-use scans::get_scans_file_path;
-use scans::wait_for_node_rescan;
-use spec_token::RewardTokenId;
-use spec_token::SpecToken;
-use spec_token::TokenIdKind;
-use state::process;
-use state::PoolState;
+use oracle_core_lib::action_report::ActionReportStorage;
+use oracle_core_lib::action_report::PoolActionReport;
+use oracle_core_lib::actions::PoolAction;
+use oracle_core_lib::audit_log;
+use oracle_core_lib::cli_commands;
+use oracle_core_lib::cli_commands::backtest;
+use oracle_core_lib::cli_commands::extract_reward_tokens::DEFAULT_KEPT_REWARD_TOKENS;
+#[cfg(feature = "sim")]
+use oracle_core_lib::cli_commands::simulate::SimulateInput;
+#[cfg(feature = "sim")]
+use oracle_core_lib::cli_commands::simulate::SimulationReport;
+use oracle_core_lib::cold_signing::ColdSigner;
+use oracle_core_lib::cold_signing::ColdSigningFormat;
+use oracle_core_lib::datapoint_anomaly::AnomalyDetector;
+use oracle_core_lib::datapoint_source::DataPointSource;
+use oracle_core_lib::datapoint_source::RuntimeDataPointSource;
+use oracle_core_lib::datapoint_source::SourceChecks;
+use oracle_core_lib::djed;
+use oracle_core_lib::epoch_history;
+use oracle_core_lib::error_reporting;
+use oracle_core_lib::exit_status;
+use oracle_core_lib::exit_status::exit_with_error;
+use oracle_core_lib::exit_status::ErrorCategory;
+use oracle_core_lib::logging;
+use oracle_core_lib::metrics;
+use oracle_core_lib::node_interface::assert_wallet_unlocked;
+use oracle_core_lib::node_interface::node_api::parse_change_address;
+use oracle_core_lib::node_interface::node_api::NodeApi;
+use oracle_core_lib::node_interface::snapshot::NodeSnapshot;
+use oracle_core_lib::notifications;
+use oracle_core_lib::operations_report;
+use oracle_core_lib::oracle_state::log_oracle_address_change;
+use oracle_core_lib::oracle_state::LocalBallotState;
+use oracle_core_lib::oracle_state::OraclePool;
+use oracle_core_lib::oracle_state::OraclePoolCache;
+use oracle_core_lib::oracle_types::BlockHeight;
+use oracle_core_lib::oracle_types::Rate;
+use oracle_core_lib::pool_commands::build_action;
+use oracle_core_lib::pool_commands::publish_datapoint::PublishDatapointActionError;
+use oracle_core_lib::pool_commands::refresh::RefreshActionError;
+use oracle_core_lib::pool_commands::PoolCommandError;
+use oracle_core_lib::pool_config::DEFAULT_POOL_CONFIG_FILE_NAME;
+use oracle_core_lib::posting_policy::CurrentRate;
+use oracle_core_lib::posting_policy::PostingPolicy;
+use oracle_core_lib::posting_policy::PrefetchedRate;
+use oracle_core_lib::scans;
+use oracle_core_lib::scans::get_scans_file_path;
+use oracle_core_lib::scans::wait_for_node_rescan;
+use oracle_core_lib::secrets::OsKeyring;
+use oracle_core_lib::spec_token::RewardTokenId;
+use oracle_core_lib::spec_token::SpecToken;
+use oracle_core_lib::spec_token::TokenIdKind;
+use oracle_core_lib::state::process;
+use oracle_core_lib::state::EpochParams;
+use oracle_core_lib::state::PoolState;
+use oracle_core_lib::state::DEFAULT_REFRESH_DELAY_BLOCKS_PER_RANK;
+use oracle_core_lib::telemetry;
 use std::convert::TryFrom;
 use std::env;
+use std::fs::File;
 use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::RwLock;
-use std::thread;
 use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task;
 
-use crate::actions::execute_action;
-use crate::api::start_rest_server;
-use crate::default_parameters::print_contract_hashes;
-use crate::migrate::check_migration_to_split_config;
-use crate::oracle_config::OracleConfig;
-use crate::oracle_config::DEFAULT_ORACLE_CONFIG_FILE_NAME;
-use crate::oracle_config::ORACLE_CONFIG_FILE_PATH;
-use crate::oracle_config::ORACLE_CONFIG_OPT;
-use crate::pool_config::POOL_CONFIG_FILE_PATH;
-use crate::scans::NodeScanRegistry;
+use oracle_core_lib::actions::execute_action;
+use oracle_core_lib::actions::random_submit_delay;
+#[cfg(feature = "api")]
+use oracle_core_lib::api::start_monitor_server;
+#[cfg(feature = "api")]
+use oracle_core_lib::api::start_rest_server;
+use oracle_core_lib::attestation::AttestationSigner;
+use oracle_core_lib::attestation::LatestAttestation;
+use oracle_core_lib::audit_log::get_audit_log_file_path;
+use oracle_core_lib::audit_log::AuditLog;
+use oracle_core_lib::audit_log::AuditLogDatapoint;
+use oracle_core_lib::audit_log::SubmissionResult;
+use oracle_core_lib::box_kind::OracleBoxWrapper;
+use oracle_core_lib::box_kind::PoolBox;
+use oracle_core_lib::context::OracleContext;
+use oracle_core_lib::contracts::known_templates::warn_on_unknown_contracts;
+use oracle_core_lib::default_parameters::print_contract_hashes;
+use oracle_core_lib::epoch_history::get_epoch_history_file_path;
+use oracle_core_lib::epoch_history::EpochHistory;
+use oracle_core_lib::event_stream::EventStream;
+use oracle_core_lib::event_stream::StreamEvent;
+use oracle_core_lib::evm_relay::EvmRelay;
+use oracle_core_lib::explorer_api::explorer_url::default_explorer_api_url;
+use oracle_core_lib::explorer_api::ExplorerApi;
+use oracle_core_lib::governance::GovernanceWatcher;
+use oracle_core_lib::ha::HaCoordinator;
+use oracle_core_lib::history_db::HistoryDb;
+use oracle_core_lib::ipfs::IpfsPinner;
+use oracle_core_lib::migrate::check_migration_to_split_config;
+use oracle_core_lib::monitor::PoolMonitor;
+use oracle_core_lib::monitor::DEFAULT_MONITOR_INTERVAL_SECS;
+use oracle_core_lib::mqtt::MqttPublisher;
+use oracle_core_lib::multi_pool::parse_extra_pool_specs;
+use oracle_core_lib::multi_pool::PoolRunner;
+use oracle_core_lib::notifications::DivergenceCheckedSource;
+use oracle_core_lib::notifications::NotificationEvent;
+use oracle_core_lib::notifications::Notifier;
+use oracle_core_lib::operations_report::ReportFormat;
+use oracle_core_lib::operations_report::ReportPeriod;
+use oracle_core_lib::operations_report::ReportScheduler;
+use oracle_core_lib::oracle_config::OracleConfig;
+use oracle_core_lib::oracle_config::DEFAULT_ORACLE_CONFIG_FILE_NAME;
+use oracle_core_lib::oracle_config::ORACLE_CONFIG_FILE_PATH;
+use oracle_core_lib::oracle_config::ORACLE_CONFIG_OPT;
+use oracle_core_lib::pool_config::PoolConfig;
+use oracle_core_lib::pool_config::POOL_CONFIG_FILE_PATH;
+use oracle_core_lib::rebroadcast::RebroadcastTracker;
+use oracle_core_lib::rebroadcast::DEFAULT_REBROADCAST_AFTER_BLOCKS;
+use oracle_core_lib::scans::NodeScanRegistry;
+use oracle_core_lib::wallet::WalletDataSource;
 
 const APP_VERSION: &str = concat!(
     "v",
@@ -124,9 +169,21 @@ struct Args {
     /// Set path of pool configuration file to use. Default is ./pool_config.yaml
     #[clap(long)]
     pool_config_file: Option<String>,
-    /// Set folder path for the data files (scanIDs.json, logs). Default is the current folder.
+    /// Set folder path for the data files (scans.sqlite, logs). Default is the current folder.
     #[clap(short, long)]
     data_dir: Option<String>,
+    /// Write the tx of extract-reward-tokens, transfer-oracle-token, vote-update-pool or
+    /// update-pool to this file as an EIP-19 cold signing request instead of signing it with the
+    /// node wallet
+    #[clap(long)]
+    cold_signing: Option<PathBuf>,
+    /// Format of the cold signing request file: `json` (default) or `chunks` (one QR code chunk
+    /// per line)
+    #[clap(long, default_value_t = ColdSigningFormat::Json)]
+    cold_signing_format: ColdSigningFormat,
+    /// Also write a fatal error to stderr as a JSON object with its category and exit code
+    #[clap(long)]
+    json_errors: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -142,6 +199,19 @@ enum Command {
         /// Set this flag to output a bootstrap config template file to the given filename. If
         /// filename already exists, return error.
         generate_config_template: bool,
+        /// Network of the generated template, `mainnet` or `testnet`. Default is the network of
+        /// the oracle address.
+        #[clap(long, value_parser = parse_network)]
+        network: Option<NetworkPrefix>,
+        /// Print the ERG needed by the bootstrap txs (fees and box values) and check the wallet
+        /// holds it, without submitting anything
+        #[clap(long)]
+        estimate: bool,
+        /// Sign the bootstrap txs with an offline wallet: each run writes the cold signing request
+        /// of the next tx to this directory and reads back the signed ones, the node wallet is not
+        /// used
+        #[clap(long)]
+        air_gapped: Option<PathBuf>,
     },
 
     /// Run the oracle-pool
@@ -152,33 +222,67 @@ enum Command {
         #[clap(long)]
         /// Set this flag to enable the REST API. NOTE: SSL is not used!
         enable_rest_api: bool,
+        /// Run an additional pool in the same process, given as
+        /// `<pool_config.yaml>[,<oracle_config.yaml>]`. Can be repeated. When the oracle config is
+        /// omitted the primary oracle config (and wallet) is used.
+        #[clap(long = "extra-pool")]
+        extra_pools: Vec<String>,
     },
 
-    /// Send reward tokens accumulated in the oracle box to a chosen address
+    /// Send reward tokens (or ERG rewards, in ERG reward mode) accumulated in the oracle box to a
+    /// chosen address
     ExtractRewardTokens {
         /// Base58 encoded address to send reward tokens to
         rewards_address: String,
+        /// Number of reward tokens left in the datapoint box
+        #[clap(long, default_value_t = DEFAULT_KEPT_REWARD_TOKENS)]
+        keep: u64,
     },
 
-    /// Print the number of reward tokens earned by the oracle (in the last posted/collected oracle box)
+    /// Print the number of reward tokens (or ERG rewards, in ERG reward mode) earned by the oracle
+    /// (in the last posted/collected oracle box), their accrual rate and estimated value
     PrintRewardTokens,
 
+    /// Write the current pool, refresh, update, datapoint and ballot boxes with their parsed
+    /// registers as JSON
+    SnapshotPool {
+        /// JSON file to write, stdout if not set
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+
     /// Transfer an oracle token to a chosen address.
     TransferOracleToken {
         /// Base58 encoded address to send oracle token to
         oracle_token_address: String,
+        /// Base16 signature of the printed transfer challenge by the destination address, replaces
+        /// the interactive confirmation
+        #[clap(long)]
+        challenge_signature: Option<String>,
+    },
+
+    /// Move the oracle token and the local datapoint box to another address of the node wallet,
+    /// switch `oracle_address` and the scans to it and wait for its first datapoint
+    RotateAddress {
+        /// Base58 encoded P2PK address of the node wallet to rotate to
+        new_address: String,
+        /// Blocks to wait for the first datapoint from the new address (default two epochs), 0 to
+        /// skip the check
+        #[clap(long)]
+        verify_timeout_blocks: Option<u32>,
     },
 
     /// Vote to update the oracle pool
     VoteUpdatePool {
         /// The base16-encoded blake2b hash of the serialized pool box contract for the new pool box.
         new_pool_box_address_hash_str: String,
-        /// The creation height of the existing update box.
-        update_box_creation_height: u32,
         /// The base16-encoded reward token id of the new pool box (if minted)
         reward_token_id_str: Option<String>,
         /// The reward token amount in the pool box at the time of update transaction is committed (if minted).
         reward_token_amount: Option<u64>,
+        /// The creation height of the existing update box, read from the update box scan if not set.
+        #[clap(long)]
+        update_box_creation_height: Option<u32>,
     },
     /// Initiate the Update Pool transaction.
     /// Updated config file `pool_config_updated.yaml` is expected to be in the current directory
@@ -188,6 +292,18 @@ enum Command {
         reward_token_id: Option<String>,
         /// New reward token amount (only if minted)
         reward_token_amount: Option<u64>,
+        /// Write the contract part of the update tx to the file, for several parties to fund and
+        /// sign it instead of the node wallet
+        #[clap(long, conflicts_with_all = ["contribute", "finalize"])]
+        export_partial: Option<PathBuf>,
+        /// Merge boxes of the node wallet covering the missing fee and reward tokens into the
+        /// partial update tx file
+        #[clap(long, conflicts_with = "finalize")]
+        contribute: Option<PathBuf>,
+        /// Sign the inputs of the node wallet in the partial update tx file, and submit the tx once
+        /// every input is signed
+        #[clap(long)]
+        finalize: Option<PathBuf>,
     },
     /// Prepare updating oracle pool with new contracts/parameters.
     /// Creates new refresh box and pool box if needed (e.g. if new reward tokens are minted)
@@ -199,14 +315,138 @@ enum Command {
     /// Print base 64 encodings of the blake2b hash of ergo-tree bytes of each contract
     PrintContractHashes,
 
+    /// Print the constants of two contracts side by side, marking the ones that differ
+    DiffContracts {
+        /// P2S address or base16-encoded ergo tree of the first contract
+        left: String,
+        /// P2S address or base16-encoded ergo tree of the second contract
+        right: String,
+    },
+
+    /// Rehearse the pool on a simulated chain without a node: bootstrap, datapoints posted by
+    /// simulated oracles and refresh, reporting whether every step would succeed
+    #[cfg(feature = "sim")]
+    Simulate {
+        /// Bootstrap config file with the contracts and parameters to try, the ones of the pool
+        /// config are used if not set
+        bootstrap_config_file: Option<String>,
+        /// Number of simulated oracles, the min data points of the refresh contract by default
+        #[clap(long)]
+        oracles: Option<usize>,
+        /// Number of epochs to run
+        #[clap(long, default_value_t = 2)]
+        epochs: u32,
+        /// Datapoint posted by every oracle
+        #[clap(long, default_value_t = 1_000_000)]
+        rate: i64,
+    },
+
+    /// Replay the raw rates of the sources (from the audit log or a CSV file) through a proposed
+    /// aggregation config, comparing the result with the posted datapoints and the pool rates
+    Backtest {
+        /// YAML file with the aggregation config to try
+        config_file: PathBuf,
+        /// CSV file in the export-history format to read the rates from instead of the audit log
+        #[clap(long)]
+        input: Option<PathBuf>,
+        /// First height of the range
+        #[clap(long)]
+        from_height: Option<u32>,
+        /// Last height of the range
+        #[clap(long)]
+        to_height: Option<u32>,
+    },
+
+    /// Rebuild the pool states of a height range from the explorer and print the commands the
+    /// main loop would have run at each height next to the boxes created on-chain
+    Replay {
+        /// First height of the range
+        #[clap(long)]
+        from_height: u32,
+        /// Last height of the range
+        #[clap(long)]
+        to_height: u32,
+    },
+
+    /// Watch the pool without a wallet or node API key: read its state from the explorer, serve
+    /// the read-only API and metrics, and send the pool alerts
+    Monitor {
+        /// Delay in seconds between two checks of the pool
+        #[clap(long, default_value_t = DEFAULT_MONITOR_INTERVAL_SECS)]
+        interval_secs: u64,
+        #[clap(long)]
+        /// Set this flag to enable the read-only monitor API. NOTE: SSL is not used!
+        enable_rest_api: bool,
+    },
+
+    /// Print the last entries of the action audit log
+    PrintAuditLog {
+        /// Number of entries to print
+        #[clap(long, default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// Export the posted datapoints and the raw rates of their sources from the audit log as CSV
+    ExportHistory {
+        /// First height of the range
+        #[clap(long)]
+        from_height: Option<u32>,
+        /// Last height of the range
+        #[clap(long)]
+        to_height: Option<u32>,
+        /// CSV file to write, stdout if not set
+        #[clap(long)]
+        output: Option<String>,
+    },
+
+    /// Summarize the last week or month of the local history (posting success rate, rewards, fees,
+    /// divergence from the pool rate, downtime windows)
+    Report {
+        /// `week` or `month`
+        #[clap(long, default_value_t = ReportPeriod::Week)]
+        period: ReportPeriod,
+        /// `markdown` or `json`
+        #[clap(long, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+        /// File to write, stdout if not set
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Check the configs, the node, the wallet, the scans, the contracts and the datapoint source,
+    /// printing what failed and how to fix it
+    Doctor,
+
+    /// Fetch the datapoint once without posting it, printing the rate, the latency and the error
+    /// of every datapoint source along with the datapoint that would be posted
+    TestSources,
+
+    /// Store a secret read from stdin in the OS keyring (`keyring` feature), to reference it in
+    /// the oracle config as `keyring:<entry name>`
+    SetSecret {
+        /// Name of the keyring entry
+        entry_name: String,
+    },
+
     ImportPoolUpdate {
         /// Name of the pool config file (.yaml) with new contract parameters
         pool_config_file: String,
     },
 }
 
+fn parse_network(s: &str) -> Result<NetworkPrefix, String> {
+    match s {
+        "mainnet" => Ok(NetworkPrefix::Mainnet),
+        "testnet" => Ok(NetworkPrefix::Testnet),
+        _ => Err(format!(
+            "unknown network {s:?}, expected mainnet or testnet"
+        )),
+    }
+}
+
 fn main() {
     let args = Args::parse();
+    exit_status::JSON_ERRORS.set(args.json_errors).unwrap();
 
     ORACLE_CONFIG_FILE_PATH
         .set(
@@ -266,19 +506,142 @@ fn main() {
         .map(|c| c.log_level)
         .ok()
         .flatten();
-    logging::setup_log(cmdline_log_level, config_log_level, &data_dir_path);
+    let log_format = ORACLE_CONFIG_OPT
+        .clone()
+        .map(|c| c.log_format)
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    logging::setup_log(
+        cmdline_log_level,
+        config_log_level,
+        &data_dir_path,
+        log_format,
+    );
+    // kept until the end of main, dropping it flushes the pending events
+    let _sentry_guard = ORACLE_CONFIG_OPT
+        .as_ref()
+        .ok()
+        .and_then(|c| c.sentry.as_ref())
+        .map(error_reporting::init);
+    if let Some(telemetry_config) = ORACLE_CONFIG_OPT
+        .as_ref()
+        .ok()
+        .and_then(|c| c.telemetry.as_ref())
+    {
+        if let Err(e) = telemetry::init(telemetry_config) {
+            error!("Failed to set up the OpenTelemetry export: {}", e);
+        }
+    }
 
-    scans::SCANS_DIR_PATH.set(data_dir_path).unwrap();
+    scans::SCANS_DIR_PATH.set(data_dir_path.clone()).unwrap();
+    audit_log::AUDIT_LOG_DIR_PATH
+        .set(data_dir_path.clone())
+        .unwrap();
+    epoch_history::EPOCH_HISTORY_DIR_PATH
+        .set(data_dir_path.clone())
+        .unwrap();
 
     let action_report_storage: Arc<RwLock<ActionReportStorage>> =
         Arc::new(RwLock::new(ActionReportStorage::new()));
 
     log_on_launch();
-    let node_api = NodeApi::new(ORACLE_CONFIG.node_api_key.clone(), &ORACLE_CONFIG.node_url);
-    assert_wallet_unlocked(&node_api.node);
-    wait_for_node_rescan(&node_api).unwrap();
-
-    let pool_config = &POOL_CONFIG;
+    // runs before the config load, which needs the referenced secrets
+    if let Command::SetSecret { entry_name } = &args.command {
+        set_secret(entry_name);
+        return;
+    }
+    // reports a config that fails to load instead of panicking on it
+    if let Command::Doctor = args.command {
+        doctor();
+        return;
+    }
+    let oracle_config = OracleConfig::load()
+        .unwrap_or_else(|e| exit_with_error("oracle config", e.into(), ErrorCategory::Config));
+    // runs without a node
+    #[cfg(feature = "sim")]
+    if let Command::Simulate {
+        bootstrap_config_file,
+        oracles,
+        epochs,
+        rate,
+    } = &args.command
+    {
+        simulate(
+            bootstrap_config_file.as_deref(),
+            *oracles,
+            *epochs,
+            *rate,
+            &oracle_config,
+        );
+        return;
+    }
+    if let Command::Backtest {
+        config_file,
+        input,
+        from_height,
+        to_height,
+    } = &args.command
+    {
+        match backtest::run_backtest(config_file, input.as_deref(), *from_height, *to_height) {
+            Ok(report) => print!("{}", report),
+            Err(e) => exit_with_error("backtest", e.into(), ErrorCategory::Other),
+        }
+        return;
+    }
+    if let Command::TestSources = args.command {
+        if let Err(e) =
+            cli_commands::test_sources::test_sources(&load_pool_config(), &oracle_config)
+        {
+            exit_with_error("test-sources", e, ErrorCategory::Other);
+        }
+        return;
+    }
+    if let Command::Replay {
+        from_height,
+        to_height,
+    } = &args.command
+    {
+        match cli_commands::replay::replay_from_explorer(
+            *from_height,
+            *to_height,
+            &load_pool_config(),
+            &oracle_config,
+        ) {
+            Ok(report) => print!("{}", report),
+            Err(e) => exit_with_error("replay", e.into(), ErrorCategory::Other),
+        }
+        return;
+    }
+    if let Command::Monitor {
+        interval_secs,
+        enable_rest_api,
+    } = args.command
+    {
+        #[cfg(not(feature = "api"))]
+        if enable_rest_api {
+            error!("--enable-rest-api needs oracle-core built with the `api` feature");
+            std::process::exit(exitcode::CONFIG);
+        }
+        monitor(
+            oracle_config,
+            Duration::from_secs(interval_secs),
+            enable_rest_api,
+        );
+        return;
+    }
+    let node_api = NodeApi::new(oracle_config.node_api_key.clone(), &oracle_config.node_url);
+    // an air-gapped bootstrap doesn't use the node wallet
+    if !matches!(
+        &args.command,
+        Command::Bootstrap {
+            air_gapped: Some(_),
+            ..
+        }
+    ) {
+        assert_wallet_unlocked(&node_api.node);
+        wait_for_node_rescan(&node_api).unwrap();
+    }
 
     #[allow(clippy::wildcard_enum_match_arm)]
     match args.command {
@@ -294,75 +657,273 @@ fn main() {
         Command::Bootstrap {
             yaml_config_name,
             generate_config_template,
+            network,
+            estimate,
+            air_gapped,
         } => {
             if let Err(e) = (|| -> Result<(), anyhow::Error> {
                 if generate_config_template {
-                    cli_commands::bootstrap::generate_bootstrap_config_template(yaml_config_name)?;
+                    cli_commands::bootstrap::generate_bootstrap_config_template(
+                        yaml_config_name,
+                        network.unwrap_or_else(|| oracle_config.oracle_address.network()),
+                    )?;
                 } else {
-                    cli_commands::bootstrap::bootstrap(yaml_config_name)?;
+                    cli_commands::bootstrap::bootstrap(
+                        yaml_config_name,
+                        &oracle_config,
+                        estimate,
+                        air_gapped,
+                        args.cold_signing_format,
+                    )?;
                 }
                 Ok(())
             })() {
-                {
-                    error!("Fatal advanced-bootstrap error: {:?}", e);
-                    std::process::exit(exitcode::SOFTWARE);
-                }
+                exit_with_error("bootstrap", e, ErrorCategory::Other);
             };
         }
         Command::PrintContractHashes => {
             print_contract_hashes();
         }
+        Command::DiffContracts { left, right } => {
+            if let Err(e) = cli_commands::diff_contracts::diff_contracts(left, right) {
+                exit_with_error("diff-contracts", e.into(), ErrorCategory::ContractMismatch);
+            }
+        }
+        Command::PrintAuditLog { limit } => {
+            if let Err(e) = audit_log::print_audit_log(&get_audit_log_file_path(), limit) {
+                exit_with_error("print-audit-log", e.into(), ErrorCategory::Other);
+            }
+        }
+        Command::ExportHistory {
+            from_height,
+            to_height,
+            output,
+        } => {
+            let res = match output {
+                Some(output) => File::create(output)
+                    .map_err(Into::into)
+                    .and_then(|mut file| {
+                        audit_log::export_datapoints_csv(
+                            &get_audit_log_file_path(),
+                            from_height,
+                            to_height,
+                            &mut file,
+                        )
+                    }),
+                None => audit_log::export_datapoints_csv(
+                    &get_audit_log_file_path(),
+                    from_height,
+                    to_height,
+                    &mut std::io::stdout(),
+                ),
+            };
+            if let Err(e) = res {
+                exit_with_error("export-history", e.into(), ErrorCategory::Other);
+            }
+        }
+        Command::Report {
+            period,
+            format,
+            output,
+        } => {
+            let pool_config = load_pool_config();
+            if let Err(e) = (|| -> Result<(), anyhow::Error> {
+                let height = node_api.node.current_block_height()? as u32;
+                let report = operations_report::build_report(
+                    "primary",
+                    &pool_config,
+                    &oracle_config,
+                    &get_epoch_history_file_path(),
+                    &get_audit_log_file_path(),
+                    period,
+                    height,
+                )?;
+                let rendered = report.render(format)?;
+                match output {
+                    Some(output) => std::fs::write(output, rendered)?,
+                    None => print!("{}", rendered),
+                }
+                Ok(())
+            })() {
+                exit_with_error("report", e, ErrorCategory::Other);
+            }
+        }
         Command::Run {
             read_only,
             enable_rest_api,
+            extra_pools,
         } => {
-            let tokio_runtime = tokio::runtime::Runtime::new().unwrap();
-            let (_, repost_receiver) = bounded::<bool>(1);
+            #[cfg(not(feature = "api"))]
+            if enable_rest_api {
+                error!("--enable-rest-api needs oracle-core built with the `api` feature");
+                std::process::exit(exitcode::CONFIG);
+            }
+            let ctx = Arc::new(OracleContext::new(oracle_config, load_pool_config()));
+            let pool_config = &ctx.pool_config;
+            let oracle_config = &ctx.oracle_config;
+            #[cfg(feature = "api")]
+            let (_, repost_receiver) = watch::channel(false);
+            warn_on_unknown_contracts(pool_config, "primary");
 
-            let node_scan_registry =
-                NodeScanRegistry::ensure_node_registered_scans(&node_api, pool_config).unwrap();
-            let oracle_pool = Arc::new(OraclePool::new(&node_scan_registry).unwrap());
-            let datapoint_source = RuntimeDataPointSource::new(
-                POOL_CONFIG.data_point_source,
-                ORACLE_CONFIG.data_point_source_custom_script.clone(),
+            let previous_oracle_address =
+                match scans::registered_oracle_address(&scans::get_scans_file_path()) {
+                    Ok(address) => address,
+                    Err(e) => {
+                        log::debug!("Failed to read the oracle address of the scans: {}", e);
+                        None
+                    }
+                };
+            let node_scan_registry = NodeScanRegistry::ensure_node_registered_scans(
+                &node_api,
+                pool_config,
+                &oracle_config.oracle_address,
             )
             .unwrap();
+            let oracle_pool = Arc::new(OraclePool::new(&node_scan_registry, &ctx).unwrap());
+            warn_on_stale_vote(&oracle_pool);
+            if let Some(previous_oracle_address) = previous_oracle_address {
+                log_oracle_address_change(
+                    &oracle_pool,
+                    "primary",
+                    &previous_oracle_address,
+                    &oracle_config.oracle_address,
+                );
+            }
+            let datapoint_source = RuntimeDataPointSource::new(
+                pool_config.data_point_source,
+                oracle_config.data_point_source_custom_script.clone(),
+            )
+            .unwrap()
+            .with_checks(SourceChecks::from_oracle_config(oracle_config))
+            .shared(
+                oracle_config
+                    .share_datapoint_fetch_secs
+                    .map(Duration::from_secs),
+            );
 
-            // Start Oracle Core GET API Server
-            if enable_rest_api {
-                let op_clone = oracle_pool.clone();
-                tokio_runtime.spawn(async {
-                    if let Err(e) = start_rest_server(repost_receiver, op_clone).await {
-                        error!("An error occurred while starting the REST server: {}", e);
-                        std::process::exit(exitcode::SOFTWARE);
+            let extra_pool_runners: Vec<Arc<PoolRunner>> =
+                match load_extra_pools(&extra_pools, &data_dir_path, &ctx) {
+                    Ok(runners) => runners,
+                    Err(e) => {
+                        exit_with_error("extra pools", e, ErrorCategory::Config);
                     }
-                });
+                };
+            let primary_runner = Arc::new(PoolRunner {
+                name: "primary".to_string(),
+                ctx: (*ctx).clone(),
+                oracle_pool: oracle_pool.clone(),
+                datapoint_source,
+                node_api,
+                report_storage: action_report_storage,
+                audit_log_path: get_audit_log_file_path(),
+                epoch_history_path: get_epoch_history_file_path(),
+                latest_attestation: LatestAttestation::default(),
+            });
+            let primary_state = match PoolLoopState::open(&primary_runner, None) {
+                Ok(state) => state,
+                Err(e) => {
+                    error!("Fatal error opening the pool history: {:?}", e);
+                    std::process::exit(exitcode::IOERR);
+                }
+            };
+            let mut extra_pool_states = Vec::with_capacity(extra_pool_runners.len());
+            for runner in &extra_pool_runners {
+                match PoolLoopState::open(runner, Some(&runner.name)) {
+                    Ok(state) => extra_pool_states.push(state),
+                    Err(e) => {
+                        error!(
+                            "pool {} fatal error opening the pool history: {:?}",
+                            runner.name, e
+                        );
+                        std::process::exit(exitcode::IOERR);
+                    }
+                }
             }
-            loop {
-                if let Err(e) = main_loop_iteration(
-                    &oracle_pool,
+
+            // The API server and the main loops of all pools share one runtime. The iterations
+            // run on its blocking threads since the node calls are blocking.
+            let tokio_runtime = tokio::runtime::Runtime::new().unwrap();
+            tokio_runtime.block_on(async move {
+                let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+                #[cfg(feature = "api")]
+                if enable_rest_api {
+                    let shutdown = shutdown_receiver.clone();
+                    let extra_pools = extra_pool_runners.clone();
+                    let primary_attestation = primary_runner.latest_attestation.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = start_rest_server(
+                            repost_receiver,
+                            ctx,
+                            oracle_pool,
+                            extra_pools,
+                            primary_attestation,
+                            shutdown,
+                        )
+                        .await
+                        {
+                            error!("An error occurred while starting the REST server: {}", e);
+                            std::process::exit(exitcode::SOFTWARE);
+                        }
+                    });
+                }
+                let mut pool_loops = Vec::with_capacity(extra_pool_runners.len());
+                for (runner, state) in extra_pool_runners.into_iter().zip(extra_pool_states) {
+                    pool_loops.push(tokio::spawn(run_pool_loop(
+                        runner,
+                        state,
+                        read_only,
+                        shutdown_receiver.clone(),
+                    )));
+                }
+                let primary_loop = tokio::spawn(run_pool_loop(
+                    primary_runner,
+                    primary_state,
                     read_only,
-                    &datapoint_source,
-                    &node_api,
-                    action_report_storage.clone(),
-                ) {
-                    error!("error: {:?}", e);
+                    shutdown_receiver,
+                ));
+                tokio::spawn(async move {
+                    wait_for_shutdown_signal().await;
+                    log::info!("Shutting down after the current main loop iterations");
+                    let _ = shutdown_sender.send(true);
+                });
+                // the process stops with the primary pool
+                if let Ok(Ok(())) = primary_loop.await {
+                    for pool_loop in pool_loops {
+                        let _ = pool_loop.await;
+                    }
+                } else {
+                    std::process::exit(exitcode::SOFTWARE);
                 }
-                // Delay loop restart
-                thread::sleep(Duration::new(30, 0));
-            }
+            });
+        }
+        oracle_command => {
+            let ctx = OracleContext::new(oracle_config, load_pool_config());
+            let cold_signer = args.cold_signing.map(|path| ColdSigner {
+                reducer: &node_api,
+                path,
+                format: args.cold_signing_format,
+                sender: ctx.oracle_config.oracle_address.clone(),
+            });
+            handle_pool_command(oracle_command, &node_api, &ctx, cold_signer.as_ref())
         }
-        oracle_command => handle_pool_command(oracle_command, &node_api),
     }
 }
 
 /// Handle all other commands
-fn handle_pool_command(command: Command, node_api: &NodeApi) {
+fn handle_pool_command(
+    command: Command,
+    node_api: &NodeApi,
+    ctx: &OracleContext,
+    cold_signer: Option<&ColdSigner>,
+) {
     let height = BlockHeight(node_api.node.current_block_height().unwrap() as u32);
     let node_scan_registry = NodeScanRegistry::load().unwrap();
-    let op = OraclePool::new(&node_scan_registry).unwrap();
+    let op = OraclePool::new(&node_scan_registry, ctx).unwrap();
     match command {
-        Command::ExtractRewardTokens { rewards_address } => {
+        Command::ExtractRewardTokens {
+            rewards_address,
+            keep,
+        } => {
             if let Err(e) = cli_commands::extract_reward_tokens::extract_reward_tokens(
                 // TODO: pass the NodeApi instance instead of these three
                 node_api,
@@ -370,24 +931,54 @@ fn handle_pool_command(command: Command, node_api: &NodeApi) {
                 &node_api.node,
                 op.get_local_datapoint_box_source(),
                 rewards_address,
+                keep,
                 height,
+                ctx.pool_config.erg_rewards.is_some(),
+                &ctx.oracle_config,
+                cold_signer,
             ) {
-                error!("Fatal extract-rewards-token error: {:?}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                exit_with_error("extract-reward-tokens", e, ErrorCategory::Other);
             }
         }
 
         Command::PrintRewardTokens => {
             if let Err(e) = cli_commands::print_reward_tokens::print_reward_tokens(
+                node_api,
                 op.get_local_datapoint_box_source(),
+                &ctx.pool_config,
+                &ctx.oracle_config,
             ) {
-                error!("Fatal print-rewards-token error: {:?}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                exit_with_error("print-reward-tokens", e, ErrorCategory::Other);
+            }
+        }
+
+        Command::SnapshotPool { output } => {
+            let res = match output {
+                Some(output) => File::create(output)
+                    .map_err(Into::into)
+                    .and_then(|mut file| {
+                        cli_commands::snapshot_pool::write_pool_snapshot(
+                            &op,
+                            &ctx.pool_config,
+                            height,
+                            &mut file,
+                        )
+                    }),
+                None => cli_commands::snapshot_pool::write_pool_snapshot(
+                    &op,
+                    &ctx.pool_config,
+                    height,
+                    &mut std::io::stdout(),
+                ),
+            };
+            if let Err(e) = res {
+                exit_with_error("snapshot-pool", e, ErrorCategory::Other);
             }
         }
 
         Command::TransferOracleToken {
             oracle_token_address,
+            challenge_signature,
         } => {
             if let Err(e) = cli_commands::transfer_oracle_token::transfer_oracle_token(
                 node_api,
@@ -395,10 +986,36 @@ fn handle_pool_command(command: Command, node_api: &NodeApi) {
                 &node_api.node,
                 op.get_local_datapoint_box_source(),
                 oracle_token_address,
+                challenge_signature,
                 height,
+                &ctx.oracle_config,
+                cold_signer,
             ) {
-                error!("Fatal transfer-oracle-token error: {:?}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                exit_with_error("transfer-oracle-token", e, ErrorCategory::Other);
+            }
+        }
+
+        Command::RotateAddress {
+            new_address,
+            verify_timeout_blocks,
+        } => {
+            if cold_signer.is_some() {
+                exit_with_error(
+                    "rotate-address",
+                    anyhow!("rotate-address is signed by the node wallet, remove --cold-signing"),
+                    ErrorCategory::Other,
+                );
+            }
+            if let Err(e) = cli_commands::rotate_address::rotate_address(
+                node_api,
+                &op,
+                ctx,
+                ORACLE_CONFIG_FILE_PATH.get().unwrap(),
+                new_address,
+                height,
+                verify_timeout_blocks,
+            ) {
+                exit_with_error("rotate-address", e, ErrorCategory::Other);
             }
         }
 
@@ -414,18 +1031,45 @@ fn handle_pool_command(command: Command, node_api: &NodeApi) {
                 &node_api.node,
                 &node_api.node,
                 op.get_local_ballot_box_source(),
+                op.get_update_box_source(),
                 new_pool_box_address_hash_str,
                 reward_token_opt,
-                BlockHeight(update_box_creation_height),
+                update_box_creation_height.map(BlockHeight),
                 height,
+                ctx,
+                cold_signer,
             ) {
-                error!("Fatal vote-update-pool error: {:?}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                exit_with_error("vote-update-pool", e, ErrorCategory::Other);
+            }
+        }
+        Command::UpdatePool {
+            contribute: Some(path),
+            ..
+        } => {
+            if let Err(e) = cli_commands::update_pool::multi_party::contribute_to_partial_update(
+                &path, node_api,
+            ) {
+                exit_with_error("update-pool", e.into(), ErrorCategory::Other);
+            }
+        }
+        Command::UpdatePool {
+            finalize: Some(path),
+            ..
+        } => {
+            if let Err(e) = cli_commands::update_pool::multi_party::finalize_partial_update(
+                &path,
+                node_api,
+                &node_api.node,
+                ctx,
+            ) {
+                exit_with_error("update-pool", e, ErrorCategory::Other);
             }
         }
         Command::UpdatePool {
             reward_token_id,
             reward_token_amount,
+            export_partial,
+            ..
         } => {
             let reward_token_opt = check_reward_token_opt(reward_token_id, reward_token_amount);
             if let Err(e) = cli_commands::update_pool::update_pool(
@@ -435,61 +1079,524 @@ fn handle_pool_command(command: Command, node_api: &NodeApi) {
                 &node_api.node,
                 reward_token_opt,
                 height,
+                ctx,
+                cold_signer,
+                export_partial.as_deref(),
             ) {
-                error!("Fatal update-pool error: {:?}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                exit_with_error("update-pool", e, ErrorCategory::Other);
             }
         }
         Command::PrepareUpdate { update_file } => {
             if let Err(e) =
-                cli_commands::prepare_update::prepare_update(update_file, node_api, height)
+                cli_commands::prepare_update::prepare_update(update_file, node_api, height, ctx)
             {
-                error!("Fatal update error : {:?}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                exit_with_error("prepare-update", e, ErrorCategory::Other);
             }
         }
         Command::ImportPoolUpdate { pool_config_file } => {
             if let Err(e) = cli_commands::import_pool_update::import_pool_update(
                 pool_config_file,
-                &POOL_CONFIG.token_ids.oracle_token_id,
-                &POOL_CONFIG.token_ids.reward_token_id,
+                &ctx.pool_config,
                 POOL_CONFIG_FILE_PATH.get().unwrap(),
                 op.get_local_datapoint_box_source(),
                 &get_scans_file_path(),
                 node_scan_registry,
                 node_api,
             ) {
-                error!("Fatal import pool update error : {:?}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                exit_with_error("import-pool-update", e, ErrorCategory::Other);
             } else {
                 log::info!("pool config update imported successfully. Please, restart the oracle");
                 std::process::exit(exitcode::OK);
             }
         }
+        #[cfg(feature = "sim")]
+        Command::Simulate { .. } => unreachable!(),
         Command::Bootstrap { .. }
         | Command::PrintContractHashes
+        | Command::Backtest { .. }
+        | Command::Replay { .. }
+        | Command::Monitor { .. }
+        | Command::SetSecret { .. }
+        | Command::Doctor
+        | Command::TestSources
+        | Command::PrintAuditLog { .. }
+        | Command::ExportHistory { .. }
+        | Command::Report { .. }
         | Command::GenerateOracleConfig
         | Command::Run { .. } => unreachable!(),
     }
 }
 
+fn load_pool_config() -> PoolConfig {
+    PoolConfig::load().unwrap_or_else(|e| exit_with_error("pool config", e, ErrorCategory::Config))
+}
+
+/// Rehearse the pool of the bootstrap config file (or the pool config) on a simulated chain
+#[cfg(feature = "sim")]
+fn simulate(
+    bootstrap_config_file: Option<&str>,
+    oracles: Option<usize>,
+    epochs: u32,
+    rate: i64,
+    oracle_config: &OracleConfig,
+) {
+    let report = (|| -> Result<SimulationReport, anyhow::Error> {
+        let config = match bootstrap_config_file {
+            Some(file_name) => cli_commands::simulate::load_bootstrap_config(file_name)?,
+            None => cli_commands::simulate::bootstrap_config_from_pool_config(&PoolConfig::load()?),
+        };
+        let oracle_count =
+            oracles.unwrap_or(config.refresh_contract_parameters.min_data_points().0 as usize);
+        Ok(cli_commands::simulate::simulate(SimulateInput {
+            config,
+            oracle_count,
+            epochs,
+            rate: Rate::from(rate),
+            tx_params: oracle_config.tx_params(oracle_config.base_fee()),
+        })?)
+    })();
+    match report {
+        Ok(report) => {
+            println!("{}", report);
+            if !report.succeeded() {
+                std::process::exit(exitcode::SOFTWARE);
+            }
+        }
+        Err(e) => {
+            exit_with_error("simulate", e, ErrorCategory::Other);
+        }
+    }
+}
+
+/// Check the pool from the explorer until the shutdown signal, along with the monitor API
+fn monitor(oracle_config: OracleConfig, interval: Duration, enable_rest_api: bool) {
+    let explorer = ExplorerApi::new(
+        oracle_config
+            .explorer_url
+            .clone()
+            .unwrap_or_else(|| default_explorer_api_url(oracle_config.oracle_address.network())),
+    );
+    let port = oracle_config.core_api_port;
+    let monitor = Arc::new(PoolMonitor::new(
+        "primary".to_string(),
+        load_pool_config(),
+        oracle_config,
+        explorer,
+    ));
+    let tokio_runtime = tokio::runtime::Runtime::new().unwrap();
+    tokio_runtime.block_on(async move {
+        let (shutdown_sender, mut shutdown_receiver) = watch::channel(false);
+        #[cfg(feature = "api")]
+        if enable_rest_api {
+            let monitor = monitor.clone();
+            let shutdown = shutdown_receiver.clone();
+            tokio::spawn(async move {
+                if let Err(e) = start_monitor_server(monitor, port, shutdown).await {
+                    error!(
+                        "An error occurred while starting the monitor API server: {}",
+                        e
+                    );
+                    std::process::exit(exitcode::SOFTWARE);
+                }
+            });
+        }
+        #[cfg(not(feature = "api"))]
+        let _ = port;
+        tokio::spawn(async move {
+            wait_for_shutdown_signal().await;
+            log::info!("Shutting down after the current pool check");
+            let _ = shutdown_sender.send(true);
+        });
+        loop {
+            let check_monitor = monitor.clone();
+            if let Err(e) = task::spawn_blocking(move || check_monitor.run_check()).await {
+                error!("pool monitor stopped: {}", e);
+                std::process::exit(exitcode::SOFTWARE);
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = shutdown_receiver.changed() => return,
+            }
+        }
+    });
+}
+
+fn set_secret(entry_name: &str) {
+    println!("Enter the secret of keyring entry {}:", entry_name);
+    let mut secret = String::new();
+    if let Err(e) = std::io::stdin().read_line(&mut secret) {
+        error!("Failed to read the secret: {}", e);
+        std::process::exit(exitcode::IOERR);
+    }
+    match OsKeyring.set_secret(entry_name, secret.trim_end_matches(&['\r', '\n'][..])) {
+        Ok(()) => println!(
+            "Secret stored, set it in the oracle config as keyring:{}",
+            entry_name
+        ),
+        Err(e) => {
+            error!("Failed to store the secret: {}", e);
+            std::process::exit(exitcode::SOFTWARE);
+        }
+    }
+}
+
+fn doctor() {
+    let report = cli_commands::doctor::doctor(
+        ORACLE_CONFIG_FILE_PATH.get().unwrap(),
+        POOL_CONFIG_FILE_PATH.get().unwrap(),
+        &scans::get_scans_file_path(),
+    );
+    println!("{}", report);
+    if !report.passed() {
+        std::process::exit(exitcode::SOFTWARE);
+    }
+}
+
+/// Delay between two main loop iterations of a pool
+const MAIN_LOOP_DELAY: Duration = Duration::from_secs(30);
+/// An iteration still running after this long is reported, it is not interrupted since it could be
+/// in the middle of submitting a transaction
+const SLOW_ITERATION_WARNING: Duration = Duration::from_secs(300);
+
+/// Main loop state of a pool, moved to a blocking thread for every iteration
+struct PoolLoopState {
+    ha_coordinator: Option<HaCoordinator>,
+    audit_log: AuditLog,
+    epoch_history: EpochHistory,
+    rebroadcast_tracker: RebroadcastTracker,
+    governance_watcher: GovernanceWatcher,
+    history_db: Option<HistoryDb>,
+    evm_relay: Option<EvmRelay>,
+    mqtt_publisher: Option<MqttPublisher>,
+    event_stream: Option<EventStream>,
+    attestation_signer: Option<AttestationSigner>,
+    ipfs_pinner: Option<IpfsPinner>,
+    posting_policy: Box<dyn PostingPolicy>,
+    anomaly_detector: Option<AnomalyDetector>,
+    report_scheduler: Option<ReportScheduler>,
+}
+
+impl PoolLoopState {
+    /// `ha_pool_name` is only set for the extra pools, to get a lock file per pool
+    fn open(runner: &PoolRunner, ha_pool_name: Option<&str>) -> Result<Self, anyhow::Error> {
+        let oracle_config = &runner.ctx.oracle_config;
+        Ok(PoolLoopState {
+            ha_coordinator: oracle_config
+                .ha
+                .clone()
+                .map(|ha| HaCoordinator::new(ha, ha_pool_name)),
+            audit_log: AuditLog::open(runner.audit_log_path.clone())?,
+            epoch_history: EpochHistory::open(runner.epoch_history_path.clone())?,
+            rebroadcast_tracker: RebroadcastTracker::new(
+                rebroadcast_after_blocks(oracle_config),
+                oracle_config.fee_bump.clone(),
+            ),
+            governance_watcher: GovernanceWatcher::new(),
+            history_db: open_history_db(&runner.ctx.pool_config, oracle_config),
+            evm_relay: open_evm_relay(oracle_config),
+            mqtt_publisher: open_mqtt_publisher(oracle_config, &runner.name),
+            event_stream: open_event_stream(oracle_config, &runner.name),
+            attestation_signer: open_attestation_signer(runner),
+            ipfs_pinner: open_ipfs_pinner(oracle_config),
+            posting_policy: oracle_config
+                .posting_policy
+                .clone()
+                .unwrap_or_default()
+                .build(),
+            anomaly_detector: oracle_config
+                .datapoint_anomaly
+                .clone()
+                .map(AnomalyDetector::new),
+            report_scheduler: oracle_config.report.as_ref().map(|config| {
+                // the history files of the pool are in its data dir
+                let default_dir = runner
+                    .audit_log_path
+                    .parent()
+                    .map(|dir| dir.join("reports"))
+                    .unwrap_or_else(|| PathBuf::from("reports"));
+                ReportScheduler::new(config, default_dir, &runner.name)
+            }),
+        })
+    }
+}
+
+/// One main loop iteration of the pool along with its health reporting. Blocking, the node and
+/// notification calls are made with blocking clients.
+fn run_iteration(
+    runner: &PoolRunner,
+    state: &mut PoolLoopState,
+    read_only: bool,
+    shutdown: &watch::Receiver<bool>,
+) {
+    let res = main_loop_iteration(runner, state, read_only, shutdown);
+    for quarantine in runner.datapoint_source.take_quarantines() {
+        runner
+            .ctx
+            .notifier
+            .notify(&runner.name, &NotificationEvent::from(&quarantine));
+    }
+    record_health_sample(state.history_db.as_mut(), &res);
+    if let Some(report_scheduler) = state.report_scheduler.as_mut() {
+        write_scheduled_report(runner, report_scheduler);
+    }
+    match res {
+        Ok(()) => notifications::ping_healthcheck(&runner.ctx.oracle_config),
+        Err(e) => {
+            metrics::LOOP_ERRORS.inc();
+            error!("pool {} error: {:?}", runner.name, e);
+            if let Some(event_stream) = state.event_stream.as_mut() {
+                event_stream.emit(StreamEvent::Error {
+                    error: e.to_string(),
+                });
+            }
+            runner.ctx.notifier.notify(
+                &runner.name,
+                &NotificationEvent::LoopError {
+                    error: e.to_string(),
+                },
+            );
+        }
+    }
+}
+
+/// Run the main loop of the pool until the shutdown signal, an interrupted iteration is finished
+/// first. Fails if an iteration panicked.
+///
+/// Only the scheduling of the iterations is async. The node calls stay blocking since the node
+/// client of ergo-node-interface, the scans and the action builders built on it are blocking, so
+/// every iteration runs on a blocking thread of the runtime.
+async fn run_pool_loop(
+    runner: Arc<PoolRunner>,
+    mut state: PoolLoopState,
+    read_only: bool,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<(), task::JoinError> {
+    loop {
+        let iteration_runner = runner.clone();
+        let iteration_shutdown = shutdown.clone();
+        let mut iteration = task::spawn_blocking(move || {
+            run_iteration(
+                &iteration_runner,
+                &mut state,
+                read_only,
+                &iteration_shutdown,
+            );
+            state
+        });
+        state = tokio::select! {
+            res = &mut iteration => res,
+            _ = tokio::time::sleep(SLOW_ITERATION_WARNING) => {
+                log::warn!(
+                    "pool {} main loop iteration is running for more than {:?}",
+                    runner.name,
+                    SLOW_ITERATION_WARNING
+                );
+                iteration.await
+            }
+        }
+        .map_err(|e| {
+            error!("pool {} main loop stopped: {}", runner.name, e);
+            e
+        })?;
+        tokio::select! {
+            _ = tokio::time::sleep(MAIN_LOOP_DELAY) => {}
+            _ = shutdown.changed() => {
+                log::info!("pool {} main loop stopped", runner.name);
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Sleep on the runtime from a blocking thread of the main loop, returns whether the shutdown
+/// signal came first
+fn sleep_unless_shutdown(delay: Duration, mut shutdown: watch::Receiver<bool>) -> bool {
+    tokio::runtime::Handle::current().block_on(async {
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => false,
+            _ = shutdown.changed() => true,
+        }
+    })
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM on unix (e.g. `docker stop`)
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+fn load_extra_pools(
+    extra_pools: &[String],
+    data_dir: &Path,
+    primary_ctx: &OracleContext,
+) -> Result<Vec<Arc<PoolRunner>>, anyhow::Error> {
+    let specs = parse_extra_pool_specs(extra_pools)?;
+    let mut runners = Vec::with_capacity(specs.len());
+    for spec in specs {
+        let runner = PoolRunner::load(
+            &spec,
+            data_dir,
+            &primary_ctx.oracle_config,
+            primary_ctx.notifier.clone(),
+        )
+        .with_context(|| format!("Failed to load extra pool {}", spec.name()))?;
+        if runner.ctx.pool_config.token_ids.pool_nft_token_id
+            == primary_ctx.pool_config.token_ids.pool_nft_token_id
+        {
+            return Err(anyhow!(
+                "extra pool {} is the same pool as the primary one",
+                runner.name
+            ));
+        }
+        warn_on_unknown_contracts(&runner.ctx.pool_config, &runner.name);
+        runners.push(Arc::new(runner));
+    }
+    Ok(runners)
+}
+
 fn main_loop_iteration(
-    oracle_pool: &OraclePool,
+    runner: &PoolRunner,
+    state: &mut PoolLoopState,
     read_only: bool,
-    datapoint_source: &RuntimeDataPointSource,
-    node_api: &NodeApi,
-    report_storage: Arc<RwLock<ActionReportStorage>>,
+    shutdown: &watch::Receiver<bool>,
 ) -> std::result::Result<(), anyhow::Error> {
-    if !node_api.node.wallet_status()?.unlocked {
+    let pool_name = runner.name.as_str();
+    let oracle_pool: &OraclePool = &runner.oracle_pool;
+    let pool_config = &runner.ctx.pool_config;
+    let oracle_config = &runner.ctx.oracle_config;
+    let notifier: &Notifier = &runner.ctx.notifier;
+    let datapoint_source = &runner.datapoint_source;
+    let node_api = &runner.node_api;
+    let report_storage = &runner.report_storage;
+    let PoolLoopState {
+        ha_coordinator,
+        audit_log,
+        epoch_history,
+        rebroadcast_tracker,
+        governance_watcher,
+        history_db,
+        evm_relay,
+        mqtt_publisher,
+        event_stream,
+        attestation_signer,
+        ipfs_pinner,
+        posting_policy,
+        anomaly_detector,
+        report_scheduler: _,
+    } = state;
+    metrics::LOOP_ITERATIONS.inc();
+    log_mdc::insert("pool", pool_name);
+    error_reporting::set_loop_context(pool_name);
+    let loop_span = tracing::info_span!(
+        "main_loop_iteration",
+        pool = pool_name,
+        height = tracing::field::Empty
+    );
+    let _loop_span = loop_span.enter();
+    let _loop_timer = metrics::LOOP_DURATION.start_timer();
+    let wallet_status =
+        match metrics::time_node_call("wallet_status", || node_api.node.wallet_status()) {
+            Ok(wallet_status) => wallet_status,
+            Err(e) => {
+                notifier.notify(
+                    pool_name,
+                    &NotificationEvent::NodeUnreachable {
+                        error: e.to_string(),
+                    },
+                );
+                return Err(e.into());
+            }
+        };
+    if !wallet_status.unlocked {
         return Err(anyhow!("Wallet is locked!"));
     }
-    let height = BlockHeight(
-        node_api
-            .node
-            .current_block_height()
-            .context("Failed to get the current height")? as u32,
+    let heights = metrics::time_node_call("info", || node_api.get_heights())
+        .context("Failed to get the current height")?;
+    // the height and the wallet stay the same for the rest of the iteration
+    let node_snapshot = NodeSnapshot::new(
+        node_api,
+        heights,
+        parse_change_address(wallet_status.change_address)?,
     );
-    let network_change_address = node_api.get_change_address()?;
+    let height = node_snapshot.height;
+    let network_change_address = node_snapshot.change_address.clone();
+    if let Some(threshold) = notifications::low_wallet_balance_threshold(oracle_config) {
+        check_wallet_balance(pool_name, notifier, &node_snapshot, threshold);
+    }
+    log_mdc::insert("height", height.0.to_string());
+    metrics::NODE_HEIGHT_LAG.set(node_snapshot.height_lag as i64);
+    loop_span.record("height", height.0);
+    error_reporting::set_tag("height", height.0);
+    if let Err(e) = audit_log.record_confirmations(node_api) {
+        log::error!("Failed to write tx confirmations to the audit log: {}", e);
+    }
+    rebroadcast_tracker.check(
+        node_api,
+        height,
+        &network_change_address.address().script()?,
+    );
+    // pool, refresh, oracle and ballot boxes are fetched from the node once per iteration
+    let oracle_pool = OraclePoolCache::new(oracle_pool);
+    if let Err(e) = oracle_pool.prefetch() {
+        log::error!("Failed to prefetch the pool boxes: {}", e);
+    }
+    governance_watcher.check(pool_name, &oracle_pool, notifier);
+    match epoch_history.record(
+        oracle_pool.get_pool_box_source(),
+        oracle_pool.get_collected_datapoint_boxes_source(),
+    ) {
+        Ok(Some(entry)) => {
+            log::debug!("Recorded epoch {} in the epoch history", entry.epoch_id);
+            if let Some(db) = history_db.as_mut() {
+                if let Err(e) = db.record_epoch(&entry) {
+                    log::error!("Failed to write the epoch to the history database: {}", e);
+                }
+            }
+            if let Some(relay) = evm_relay.as_ref().filter(|_| !read_only) {
+                match relay.relay(&entry) {
+                    Ok(tx_hash) => log::info!(
+                        "Relayed the rate {} of epoch {} to the EVM contract in tx {}",
+                        entry.rate,
+                        entry.epoch_id,
+                        tx_hash
+                    ),
+                    Err(e) => {
+                        log::error!("Failed to relay the pool rate to the EVM contract: {}", e)
+                    }
+                }
+            }
+            if let Some(publisher) = mqtt_publisher.as_mut() {
+                if let Err(e) = publisher.publish_refresh(&entry) {
+                    log::error!("Failed to publish the refresh to the MQTT broker: {}", e);
+                }
+            }
+            if let Some(event_stream) = event_stream.as_mut() {
+                event_stream.emit(StreamEvent::from(entry));
+            }
+        }
+        Ok(None) => (),
+        Err(e) => log::debug!("Failed to record the epoch history: {}", e),
+    }
+    if let Err(e) = update_datapoint_divergence(&oracle_pool, pool_config) {
+        log::debug!("Failed to compute the datapoint divergence: {}", e);
+    }
     let pool_state = match oracle_pool.get_live_epoch_state() {
         Ok(live_epoch_state) => PoolState::LiveEpoch(live_epoch_state),
         Err(error) => {
@@ -497,34 +1604,491 @@ fn main_loop_iteration(
             PoolState::NeedsBootstrap
         }
     };
-    let epoch_length = POOL_CONFIG
-        .refresh_box_wrapper_inputs
-        .contract_inputs
-        .contract_parameters()
-        .epoch_length();
-    if let Some(cmd) = process(pool_state, epoch_length, height) {
-        log::debug!("Height {height}. Building action for command: {:?}", cmd);
-        let build_action_tuple_res = build_action(
-            cmd,
-            oracle_pool,
-            node_api,
+    let epoch_params = EpochParams::from(
+        pool_config
+            .refresh_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters(),
+    );
+    if let PoolState::LiveEpoch(live_epoch) = &pool_state {
+        for event in notifications::epoch_progress_events(
+            live_epoch,
+            &epoch_params,
             height,
-            network_change_address.address(),
-            datapoint_source,
-        );
-        if let Some((action, report)) =
-            log_and_continue_if_non_fatal(network_change_address.network(), build_action_tuple_res)?
-        {
+            notifications::alert_after_missed_epochs(oracle_config),
+        ) {
+            notifier.notify(pool_name, &event);
+        }
+    }
+    if let (Some(ha), PoolState::LiveEpoch(live_epoch)) = (ha_coordinator.as_mut(), &pool_state) {
+        if !ha.may_act(live_epoch, epoch_params.epoch_length, height) {
+            return Ok(());
+        }
+    }
+    let refresh_delay_blocks_per_rank = oracle_config
+        .refresh_delay_blocks_per_rank
+        .unwrap_or(DEFAULT_REFRESH_DELAY_BLOCKS_PER_RANK);
+    let divergence_checked_source =
+        notifications::max_source_spread_percent(oracle_config).map(|max_spread_percent| {
+            DivergenceCheckedSource {
+                source: datapoint_source,
+                max_spread_percent,
+                action: notifications::source_divergence_action(oracle_config),
+                pool_name,
+                notifier,
+            }
+        });
+    let datapoint_source: &dyn DataPointSource = match &divergence_checked_source {
+        Some(checked) => checked,
+        None => datapoint_source,
+    };
+    let checked_datapoint_source = anomaly_detector
+        .as_ref()
+        .map(|detector| detector.checked(datapoint_source, pool_name, notifier));
+    let datapoint_source: &dyn DataPointSource = match &checked_datapoint_source {
+        Some(checked) => checked,
+        None => datapoint_source,
+    };
+    let current_rate = CurrentRate::new(datapoint_source);
+    if let Some(cmd) = process(
+        pool_state,
+        &epoch_params,
+        height,
+        refresh_delay_blocks_per_rank,
+        posting_policy.as_ref(),
+        &current_rate,
+    ) {
+        // the rate fetched for the posting policy is the one published
+        let prefetched_rate = current_rate.fetched().map(PrefetchedRate);
+        let action_datapoint_source: &dyn DataPointSource = match &prefetched_rate {
+            Some(prefetched) => prefetched,
+            None => datapoint_source,
+        };
+        log::debug!("Height {height}. Building action for command: {:?}", cmd);
+        let action_label = metrics::command_label(&cmd);
+        error_reporting::set_tag("action", action_label);
+        let build_action_tuple_res = tracing::info_span!("build_action", action = action_label)
+            .in_scope(|| {
+                build_action(
+                    cmd,
+                    &oracle_pool,
+                    pool_config,
+                    oracle_config,
+                    &node_snapshot,
+                    action_datapoint_source,
+                    node_api,
+                )
+            });
+        match &build_action_tuple_res {
+            Ok(_) => metrics::ACTIONS_BUILT
+                .with_label_values(&[action_label])
+                .inc(),
+            Err(_) => metrics::ACTION_BUILD_FAILURES
+                .with_label_values(&[action_label])
+                .inc(),
+        }
+        if let Some((action, report)) = log_and_continue_if_non_fatal(
+            pool_name,
+            notifier,
+            network_change_address.network(),
+            build_action_tuple_res,
+        )? {
+            // the datapoint fetched while building a publish action
+            let datapoint = report.fetched_datapoint();
+            if let (Some(fetched), Some(publisher)) = (&datapoint, mqtt_publisher.as_mut()) {
+                if let Err(e) = publisher.publish_datapoint(height, fetched) {
+                    log::error!("Failed to publish the datapoint to the MQTT broker: {}", e);
+                }
+            }
+            if let (Some(fetched), Some(event_stream)) = (&datapoint, event_stream.as_mut()) {
+                event_stream.emit(StreamEvent::DatapointFetched {
+                    height: height.0,
+                    rate: fetched.rate.into(),
+                    sources: fetched.sources.clone(),
+                });
+            }
+            if let (Some(fetched), Some(signer)) = (&datapoint, attestation_signer.as_ref()) {
+                match signer.sign(fetched.rate, height) {
+                    Ok(attestation) => {
+                        if let Some(pinner) = ipfs_pinner.as_ref() {
+                            pinner.pin_in_background(
+                                pool_name,
+                                signer.latest_attestation(),
+                                attestation,
+                                fetched.sources.clone(),
+                            );
+                        }
+                    }
+                    Err(e) => log::error!("Failed to sign the datapoint attestation: {}", e),
+                }
+            }
+            if let (Some(fetched), Some(djed_config)) = (&datapoint, &pool_config.djed) {
+                djed::check_datapoint(node_api, djed_config, fetched.rate);
+            }
+            let datapoint = datapoint.map(|fetched| AuditLogDatapoint {
+                height: height.0,
+                rate: fetched.rate.into(),
+                sources: fetched.sources,
+            });
+            let mut audit_entries = AuditLog::entries_for_action(
+                action_label,
+                &action,
+                network_change_address.network(),
+                datapoint,
+            );
             if !read_only {
-                execute_action(action, node_api)?;
+                let jitter = random_submit_delay(oracle_config.submit_jitter_max_secs.unwrap_or(0));
+                if !jitter.is_zero() {
+                    log::debug!("Waiting {:?} before submitting the tx", jitter);
+                    if sleep_unless_shutdown(jitter, shutdown.clone()) {
+                        log::info!("Shutting down, the {} tx is not submitted", action_label);
+                        return Ok(());
+                    }
+                }
+                // execute_action submits the publish tx before the refresh tx
+                let is_refresh_tx = match &action {
+                    PoolAction::Refresh(_) => vec![true],
+                    PoolAction::PublishDatapoint(_) => vec![false],
+                    PoolAction::PublishAndRefresh(_) => vec![false, true],
+                };
+                let exec_res = metrics::time_node_call("submit_transaction", || {
+                    execute_action(action, node_api, oracle_config)
+                });
+                // the publish tx of a chained refresh stays accepted when the refresh tx fails
+                let (submitted_txs, submit_error) = match exec_res {
+                    Ok(submitted_txs) => (submitted_txs, None),
+                    Err(e) => (e.submitted_txs, Some(e.error)),
+                };
+                let submitted_count = submitted_txs.len();
+                for (i, entry) in audit_entries.iter_mut().enumerate() {
+                    entry.submission = match &submit_error {
+                        Some(e) if i >= submitted_count => SubmissionResult::Failed {
+                            error: e.to_string(),
+                        },
+                        Some(_) | None => SubmissionResult::Submitted,
+                    };
+                }
+                append_audit_entries(audit_log, history_db.as_mut(), audit_entries);
+                for (tx, is_refresh) in submitted_txs.iter().zip(is_refresh_tx) {
+                    let tx_id = String::from(tx.id());
+                    if let Some(event_stream) = event_stream.as_mut() {
+                        event_stream.emit(StreamEvent::TxSubmitted {
+                            height: height.0,
+                            action: action_label.to_string(),
+                            tx_id: tx_id.clone(),
+                        });
+                    }
+                    let event = if is_refresh {
+                        NotificationEvent::RefreshExecuted { tx_id }
+                    } else {
+                        NotificationEvent::DatapointPosted { tx_id }
+                    };
+                    notifier.notify(pool_name, &event);
+                }
+                rebroadcast_tracker.track(action_label, submitted_txs, height);
+                if let Some(e) = submit_error {
+                    metrics::ACTION_SUBMIT_FAILURES
+                        .with_label_values(&[action_label])
+                        .inc();
+                    if let PoolActionReport::PublishAndRefresh(report) = report {
+                        if submitted_count > 0 {
+                            log::warn!(
+                                "The datapoint tx was accepted but the chained refresh tx failed: {}",
+                                e
+                            );
+                            report_storage.write().unwrap().add(report.publish.into());
+                        }
+                    }
+                    return Err(e);
+                }
+                metrics::ACTIONS_SUBMITTED
+                    .with_label_values(&[action_label])
+                    .inc();
                 report_storage.write().unwrap().add(report);
+            } else {
+                append_audit_entries(audit_log, history_db.as_mut(), audit_entries);
             }
         };
     }
     Ok(())
 }
 
+/// Compare the local posted datapoint with the rate adopted by the pool at the last refresh.
+/// Persistent divergence means the local datapoint sources drift from the pool consensus.
+fn update_datapoint_divergence(
+    oracle_pool: &OraclePoolCache,
+    pool_config: &PoolConfig,
+) -> std::result::Result<(), anyhow::Error> {
+    let local_rate = match oracle_pool
+        .get_local_datapoint_box_source()
+        .get_local_oracle_datapoint_box()?
+    {
+        Some(OracleBoxWrapper::Posted(posted_box)) => posted_box.rate(),
+        Some(OracleBoxWrapper::Collected(_)) | None => return Ok(()),
+    };
+    let pool_rate = oracle_pool.get_pool_box_source().get_pool_box()?.rate();
+    if let Some(divergence) = metrics::datapoint_divergence_percent(local_rate, pool_rate) {
+        metrics::DATAPOINT_DIVERGENCE.set(divergence);
+        let max_deviation_percent = pool_config
+            .refresh_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters()
+            .max_deviation_percent();
+        if divergence.abs() > max_deviation_percent as f64 {
+            log::warn!(
+                "Local datapoint {} diverges {:.2}% from the pool rate {}, more than the refresh max deviation {}%",
+                local_rate,
+                divergence,
+                pool_rate,
+                max_deviation_percent
+            );
+        } else {
+            log::debug!(
+                "Local datapoint {} diverges {:.2}% from the pool rate {}",
+                local_rate,
+                divergence,
+                pool_rate
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Alert if the wallet holds less than `threshold` nanoERG
+fn check_wallet_balance(
+    pool_name: &str,
+    notifier: &Notifier,
+    wallet: &dyn WalletDataSource,
+    threshold: u64,
+) {
+    match wallet.get_unspent_wallet_boxes() {
+        Ok(boxes) => {
+            let balance: u64 = boxes.iter().map(|b| *b.value.as_u64()).sum();
+            if balance < threshold {
+                notifier.notify(
+                    pool_name,
+                    &NotificationEvent::LowWalletBalance { balance, threshold },
+                );
+            }
+        }
+        Err(e) => log::debug!("Failed to get the wallet balance: {}", e),
+    }
+}
+
+/// Remind the voter to vote again when the local ballot box votes for an older update box
+fn warn_on_stale_vote(oracle_pool: &OraclePool) {
+    match oracle_pool.get_local_ballot_state() {
+        Ok(Some(LocalBallotState::Voted {
+            vote_parameters,
+            height,
+            is_current: false,
+        })) => log::warn!(
+            "Local ballot box vote cast at height {} is stale: it is for the update box created at height {}, \
+             not the current one. Vote again with `vote-update-pool` for it to count",
+            height,
+            vote_parameters.update_box_creation_height
+        ),
+        Ok(Some(LocalBallotState::Voted { .. }))
+        | Ok(Some(LocalBallotState::NoVote { .. }))
+        | Ok(None) => (),
+        Err(e) => log::debug!("Failed to get the local ballot box state: {}", e),
+    }
+}
+
+fn rebroadcast_after_blocks(oracle_config: &OracleConfig) -> u32 {
+    oracle_config
+        .rebroadcast_after_blocks
+        .unwrap_or(DEFAULT_REBROADCAST_AFTER_BLOCKS)
+}
+
+fn append_audit_entries(
+    audit_log: &mut AuditLog,
+    mut history_db: Option<&mut HistoryDb>,
+    entries: Vec<audit_log::AuditLogEntry>,
+) {
+    for entry in entries {
+        if let Some(db) = history_db.as_deref_mut() {
+            if let Err(e) = db.record_action(&entry) {
+                log::error!("Failed to write the action to the history database: {}", e);
+            }
+        }
+        if let Err(e) = audit_log.append(entry) {
+            log::error!("Failed to write to the audit log: {}", e);
+        }
+    }
+}
+
+fn write_scheduled_report(runner: &PoolRunner, report_scheduler: &mut ReportScheduler) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    if !report_scheduler.is_due(now) {
+        return;
+    }
+    let height = match runner.node_api.node.current_block_height() {
+        Ok(height) => height as u32,
+        Err(e) => {
+            log::error!(
+                "pool {}: failed to get the height for the operations report: {}",
+                runner.name,
+                e
+            );
+            return;
+        }
+    };
+    let res = report_scheduler.write_if_due(now, |period| {
+        operations_report::build_report(
+            &runner.name,
+            &runner.ctx.pool_config,
+            &runner.ctx.oracle_config,
+            &runner.epoch_history_path,
+            &runner.audit_log_path,
+            period,
+            height,
+        )
+    });
+    match res {
+        Ok(Some(path)) => log::info!(
+            "pool {}: operations report written to {}",
+            runner.name,
+            path.display()
+        ),
+        Ok(None) => (),
+        Err(e) => log::error!(
+            "pool {}: failed to write the operations report: {}",
+            runner.name,
+            e
+        ),
+    }
+}
+
+fn open_history_db(pool_config: &PoolConfig, oracle_config: &OracleConfig) -> Option<HistoryDb> {
+    if cfg!(not(feature = "history-db")) && oracle_config.history_db.is_some() {
+        log::warn!("history_db is configured but oracle-core was built without the `history-db` feature, not writing the history database");
+        return None;
+    }
+    if let Some(Err(e)) = oracle_config
+        .history_db
+        .as_ref()
+        .map(|config| config.check_credentials())
+    {
+        log::error!("history_db: {}, not writing the history database", e);
+        return None;
+    }
+    oracle_config.history_db.clone().map(|config| {
+        HistoryDb::new(
+            config,
+            String::from(pool_config.token_ids.pool_nft_token_id.token_id()),
+            oracle_config.oracle_address.to_base58(),
+        )
+    })
+}
+
+fn open_evm_relay(oracle_config: &OracleConfig) -> Option<EvmRelay> {
+    let config = oracle_config.evm_relay.clone()?;
+    match EvmRelay::new(config) {
+        Ok(relay) => Some(relay),
+        Err(e) => {
+            log::error!(
+                "Failed to set up the EVM relay, not relaying the pool rate: {}",
+                e
+            );
+            None
+        }
+    }
+}
+
+fn open_mqtt_publisher(oracle_config: &OracleConfig, pool_name: &str) -> Option<MqttPublisher> {
+    let config = oracle_config.mqtt.clone()?;
+    match MqttPublisher::new(config, pool_name) {
+        Ok(publisher) => Some(publisher),
+        Err(e) => {
+            log::error!(
+                "Failed to set up the MQTT publisher, not publishing the oracle data: {}",
+                e
+            );
+            None
+        }
+    }
+}
+
+fn open_event_stream(oracle_config: &OracleConfig, pool_name: &str) -> Option<EventStream> {
+    let config = oracle_config.event_stream.clone()?;
+    let event_stream =
+        EventStream::new(config, pool_name, oracle_config.oracle_address.to_base58());
+    if event_stream.is_empty() {
+        None
+    } else {
+        Some(event_stream)
+    }
+}
+
+fn open_attestation_signer(runner: &PoolRunner) -> Option<AttestationSigner> {
+    let oracle_config = &runner.ctx.oracle_config;
+    if !oracle_config.datapoint_attestations.unwrap_or(false) {
+        return None;
+    }
+    match AttestationSigner::from_node(
+        runner.latest_attestation.clone(),
+        String::from(
+            runner
+                .ctx
+                .pool_config
+                .token_ids
+                .pool_nft_token_id
+                .token_id(),
+        ),
+        oracle_config,
+        &runner.node_api,
+    ) {
+        Ok(signer) => Some(signer),
+        Err(e) => {
+            log::error!(
+                "Failed to set up the datapoint attestations, not signing the datapoints: {}",
+                e
+            );
+            None
+        }
+    }
+}
+
+fn open_ipfs_pinner(oracle_config: &OracleConfig) -> Option<IpfsPinner> {
+    let config = oracle_config.ipfs.clone()?;
+    if !oracle_config.datapoint_attestations.unwrap_or(false) {
+        log::warn!("ipfs is set without datapoint_attestations, nothing is pinned");
+        return None;
+    }
+    match IpfsPinner::new(config) {
+        Ok(pinner) => Some(pinner),
+        Err(e) => {
+            log::error!(
+                "Failed to set up the IPFS pinning, not pinning the attestations: {}",
+                e
+            );
+            None
+        }
+    }
+}
+
+fn record_health_sample(
+    history_db: Option<&mut HistoryDb>,
+    res: &std::result::Result<(), anyhow::Error>,
+) {
+    if let Some(db) = history_db {
+        let error = res.as_ref().err().map(|e| e.to_string());
+        if let Err(e) = db.record_health(error) {
+            log::error!(
+                "Failed to write the health sample to the history database: {}",
+                e
+            );
+        }
+    }
+}
+
 fn log_and_continue_if_non_fatal(
+    pool_name: &str,
+    notifier: &Notifier,
     network_prefix: NetworkPrefix,
     res: Result<(PoolAction, PoolActionReport), PoolCommandError>,
 ) -> Result<Option<(PoolAction, PoolActionReport)>, anyhow::Error> {
@@ -543,12 +2107,27 @@ fn log_and_continue_if_non_fatal(
                 .collect::<Vec<String>>()
                 .join(", ");
             log::error!("Refresh failed, not enough datapoints. The minimum number of datapoints within the deviation range: required minumum {expected}, found {found_num} from addresses {found_oracle_addresses},");
+            notifier.notify(
+                pool_name,
+                &NotificationEvent::EpochFailed {
+                    reason: format!(
+                        "not enough datapoints within the deviation range, required {}, found {}",
+                        expected, found_num
+                    ),
+                },
+            );
             Ok(None)
         }
         Err(PoolCommandError::PublishDatapointActionError(
             PublishDatapointActionError::DataPointSource(e),
         )) => {
             log::error!("Failed to get datapoint with error: {}", e);
+            notifier.notify(
+                pool_name,
+                &NotificationEvent::DatapointSourceFailed {
+                    error: e.to_string(),
+                },
+            );
             Ok(None)
         }
         Err(e) => Err(e.into()),
@@ -0,0 +1,255 @@
+//! Prometheus metrics of the main loop, served by the REST API at `/metrics`.
+//!
+//! Without the `metrics` feature the metrics are no-ops with the same methods, so the main loop
+//! records them unconditionally.
+
+use std::time::Instant;
+
+#[cfg(feature = "metrics")]
+use prometheus::register_gauge;
+#[cfg(feature = "metrics")]
+use prometheus::register_histogram;
+#[cfg(feature = "metrics")]
+use prometheus::register_histogram_vec;
+#[cfg(feature = "metrics")]
+use prometheus::register_int_counter;
+#[cfg(feature = "metrics")]
+use prometheus::register_int_counter_vec;
+#[cfg(feature = "metrics")]
+use prometheus::register_int_gauge;
+#[cfg(feature = "metrics")]
+use prometheus::Encoder;
+#[cfg(feature = "metrics")]
+use prometheus::Gauge;
+#[cfg(feature = "metrics")]
+use prometheus::Histogram;
+#[cfg(feature = "metrics")]
+use prometheus::HistogramVec;
+#[cfg(feature = "metrics")]
+use prometheus::IntCounter;
+#[cfg(feature = "metrics")]
+use prometheus::IntCounterVec;
+#[cfg(feature = "metrics")]
+use prometheus::IntGauge;
+#[cfg(feature = "metrics")]
+use prometheus::TextEncoder;
+
+use crate::oracle_types::Rate;
+use crate::pool_commands::PoolCommand;
+
+#[cfg(feature = "metrics")]
+lazy_static! {
+    pub static ref LOOP_ITERATIONS: IntCounter = register_int_counter!(
+        "oracle_loop_iterations_total",
+        "Number of main loop iterations"
+    )
+    .unwrap();
+    pub static ref LOOP_ERRORS: IntCounter = register_int_counter!(
+        "oracle_loop_errors_total",
+        "Number of main loop iterations that ended with an error"
+    )
+    .unwrap();
+    pub static ref LOOP_DURATION: Histogram = register_histogram!(
+        "oracle_loop_duration_seconds",
+        "Duration of a main loop iteration"
+    )
+    .unwrap();
+    pub static ref ACTIONS_BUILT: IntCounterVec = register_int_counter_vec!(
+        "oracle_actions_built_total",
+        "Number of actions built per type",
+        &["action"]
+    )
+    .unwrap();
+    pub static ref ACTION_BUILD_FAILURES: IntCounterVec = register_int_counter_vec!(
+        "oracle_action_build_failures_total",
+        "Number of failed action builds per type",
+        &["action"]
+    )
+    .unwrap();
+    pub static ref ACTIONS_SUBMITTED: IntCounterVec = register_int_counter_vec!(
+        "oracle_actions_submitted_total",
+        "Number of actions submitted to the node per type",
+        &["action"]
+    )
+    .unwrap();
+    pub static ref ACTION_SUBMIT_FAILURES: IntCounterVec = register_int_counter_vec!(
+        "oracle_action_submit_failures_total",
+        "Number of actions rejected on submission per type",
+        &["action"]
+    )
+    .unwrap();
+    pub static ref TXS_REBROADCAST: IntCounterVec = register_int_counter_vec!(
+        "oracle_txs_rebroadcast_total",
+        "Number of lost txs re-broadcast per action type",
+        &["action"]
+    )
+    .unwrap();
+    pub static ref TXS_FEE_BUMPED: IntCounterVec = register_int_counter_vec!(
+        "oracle_txs_fee_bumped_total",
+        "Number of stuck txs replaced with a higher fee per action type",
+        &["action"]
+    )
+    .unwrap();
+    pub static ref NODE_CALL_DURATION: HistogramVec = register_histogram_vec!(
+        "oracle_node_call_duration_seconds",
+        "Latency of node API calls made by the main loop",
+        &["call"]
+    )
+    .unwrap();
+    pub static ref NODE_CALL_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "oracle_node_call_errors_total",
+        "Number of failed node API calls made by the main loop",
+        &["call"]
+    )
+    .unwrap();
+    pub static ref NODE_HEIGHT_LAG: IntGauge = register_int_gauge!(
+        "oracle_node_height_lag_blocks",
+        "Blocks the node full height is behind its headers and peers"
+    )
+    .unwrap();
+    pub static ref DATAPOINT_DIVERGENCE: Gauge = register_gauge!(
+        "oracle_datapoint_divergence_percent",
+        "Difference between the local posted datapoint and the pool rate, in percent of the pool rate"
+    )
+    .unwrap();
+    pub static ref POOL_BOX_AGE: IntGauge = register_int_gauge!(
+        "oracle_pool_box_age_blocks",
+        "Blocks since the pool box was created, as seen by the monitor"
+    )
+    .unwrap();
+    pub static ref POSTED_DATAPOINTS: IntGauge = register_int_gauge!(
+        "oracle_posted_datapoints",
+        "Datapoints posted in the current epoch, as seen by the monitor"
+    )
+    .unwrap();
+}
+
+/// Stand-in for every metric kind when built without the `metrics` feature
+#[cfg(not(feature = "metrics"))]
+pub struct NoopMetric;
+
+#[cfg(not(feature = "metrics"))]
+pub struct NoopTimer;
+
+#[cfg(not(feature = "metrics"))]
+impl NoopMetric {
+    pub fn inc(&self) {}
+
+    pub fn set<T>(&self, _value: T) {}
+
+    pub fn observe(&self, _value: f64) {}
+
+    pub fn start_timer(&self) -> NoopTimer {
+        NoopTimer
+    }
+
+    pub fn with_label_values(&self, _labels: &[&str]) -> &Self {
+        self
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+pub static LOOP_ITERATIONS: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static LOOP_ERRORS: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static LOOP_DURATION: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static ACTIONS_BUILT: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static ACTION_BUILD_FAILURES: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static ACTIONS_SUBMITTED: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static ACTION_SUBMIT_FAILURES: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static TXS_REBROADCAST: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static TXS_FEE_BUMPED: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static NODE_CALL_DURATION: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static NODE_CALL_ERRORS: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static NODE_HEIGHT_LAG: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static DATAPOINT_DIVERGENCE: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static POOL_BOX_AGE: NoopMetric = NoopMetric;
+#[cfg(not(feature = "metrics"))]
+pub static POSTED_DATAPOINTS: NoopMetric = NoopMetric;
+
+/// Metric label for the action built for the given command
+pub fn command_label(cmd: &PoolCommand) -> &'static str {
+    match cmd {
+        PoolCommand::Refresh => "refresh",
+        PoolCommand::PublishFirstDataPoint => "publish_first_datapoint",
+        PoolCommand::PublishSubsequentDataPoint { .. } => "publish_subsequent_datapoint",
+        PoolCommand::PublishSubsequentDataPointAndRefresh => "publish_and_refresh",
+    }
+}
+
+/// Run a node call and record its latency, and its failure
+pub fn time_node_call<T, E>(call: &str, f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+    let _span = tracing::info_span!("node_call", call).entered();
+    let start = Instant::now();
+    let res = f();
+    NODE_CALL_DURATION
+        .with_label_values(&[call])
+        .observe(start.elapsed().as_secs_f64());
+    if res.is_err() {
+        NODE_CALL_ERRORS.with_label_values(&[call]).inc();
+    }
+    res
+}
+
+/// Signed difference between the local datapoint and the rate adopted by the pool at the last
+/// refresh, in percent of the pool rate. `None` if the pool rate is zero.
+pub fn datapoint_divergence_percent(local_rate: Rate, pool_rate: i64) -> Option<f64> {
+    if pool_rate == 0 {
+        return None;
+    }
+    let local_rate: i64 = local_rate.into();
+    Some((local_rate - pool_rate) as f64 * 100.0 / pool_rate as f64)
+}
+
+/// All registered metrics in the Prometheus text format
+#[cfg(feature = "metrics")]
+pub fn encode_metrics() -> Result<String, anyhow::Error> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_metrics() {
+        LOOP_ITERATIONS.inc();
+        ACTIONS_BUILT
+            .with_label_values(&[command_label(&PoolCommand::Refresh)])
+            .inc();
+        assert_eq!(time_node_call("test_call", || Ok::<_, ()>(42)), Ok(42));
+        assert_eq!(time_node_call("test_call", || Err::<u32, _>(())), Err(()));
+        let text = encode_metrics().unwrap();
+        assert!(text.contains("oracle_loop_iterations_total"));
+        assert!(text.contains("oracle_actions_built_total{action=\"refresh\"}"));
+        assert!(text.contains("oracle_node_call_duration_seconds_count{call=\"test_call\"} 2"));
+        assert!(text.contains("oracle_node_call_errors_total{call=\"test_call\"} 1"));
+    }
+
+    #[test]
+    fn test_datapoint_divergence_percent() {
+        assert_eq!(
+            datapoint_divergence_percent(Rate::from(105), 100),
+            Some(5.0)
+        );
+        assert_eq!(
+            datapoint_divergence_percent(Rate::from(90), 100),
+            Some(-10.0)
+        );
+        assert_eq!(datapoint_divergence_percent(Rate::from(90), 0), None);
+    }
+}
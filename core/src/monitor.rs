@@ -0,0 +1,301 @@
+//! Watch-only monitoring of a pool, for community members running a watchdog against a pool they
+//! don't participate in.
+//!
+//! The pool box and the datapoint boxes are read from the public explorer API, so neither a wallet
+//! nor a node API key (for the node scans) is needed. Every check updates the status served by the
+//! monitor API and the metrics, and sends the pool stalled/underposted alerts with the notifiers of
+//! the oracle config. The healthcheck URL is only pinged while the pool is healthy, so a pool that
+//! stops refreshing shows up as missed pings.
+
+use std::sync::RwLock;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::box_kind::OracleBox;
+use crate::box_kind::PoolBox;
+use crate::box_kind::PoolBoxWrapper;
+use crate::box_kind::PostedOracleBox;
+use crate::explorer_api::datapoint_fallback::UnspentBoxesByTokenSource;
+use crate::explorer_api::ExplorerApi;
+use crate::explorer_api::ExplorerApiError;
+use crate::metrics;
+use crate::notifications;
+use crate::notifications::NotificationEvent;
+use crate::notifications::Notifier;
+use crate::oracle_config::OracleConfig;
+use crate::oracle_state::posted_oracle_boxes;
+use crate::oracle_state::LiveEpochState;
+use crate::oracle_types::BlockHeight;
+use crate::pool_config::PoolConfig;
+use crate::spec_token::TokenIdKind;
+use crate::state::EpochParams;
+
+/// Default delay between two checks of the pool
+pub const DEFAULT_MONITOR_INTERVAL_SECS: u64 = 60;
+
+#[derive(Debug, Error)]
+pub enum MonitorError {
+    #[error("explorer error: {0}")]
+    Explorer(#[from] ExplorerApiError),
+    #[error("no pool box among the unspent boxes of the pool NFT")]
+    PoolBoxNotFound,
+}
+
+/// Pool health as seen by the monitor at a height
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MonitorStatus {
+    /// Height of the last block indexed by the explorer
+    pub height: u32,
+    pub pool_box_id: String,
+    pub pool_box_height: u32,
+    pub epoch_id: u32,
+    pub rate: i64,
+    pub reward_token_amount: u64,
+    /// Blocks since the pool box was created
+    pub pool_box_age: u32,
+    pub epoch_length: u32,
+    /// Datapoints posted in the epoch of the pool box
+    pub posted_datapoints: u32,
+    pub min_data_points: u32,
+    /// Kinds of the alerts raised at this height, none for a healthy pool
+    pub alerts: Vec<&'static str>,
+}
+
+impl MonitorStatus {
+    pub fn is_healthy(&self) -> bool {
+        self.alerts.is_empty()
+    }
+}
+
+/// Pool stalled/underposted alerts, the same as the main loop raises without a local oracle
+pub fn pool_health_events(
+    pool_box: &PoolBoxWrapper,
+    posted_boxes: &[PostedOracleBox],
+    epoch_params: &EpochParams,
+    height: BlockHeight,
+) -> Vec<NotificationEvent> {
+    let epoch_id = pool_box.epoch_counter();
+    let live_epoch = LiveEpochState {
+        pool_box_epoch_id: epoch_id,
+        local_datapoint_box_state: None,
+        latest_pool_datapoint: pool_box.rate() as u64,
+        latest_pool_box_height: BlockHeight(pool_box.get_box().creation_height),
+        local_refresh_rank: None,
+        other_posted_datapoint_heights: posted_boxes
+            .iter()
+            .filter(|b| b.epoch_counter() == epoch_id)
+            .map(|b| BlockHeight(b.get_box().creation_height))
+            .collect(),
+    };
+    // without a local datapoint box there are no missed posts to alert on
+    notifications::epoch_progress_events(&live_epoch, epoch_params, height, u32::MAX)
+}
+
+pub fn monitor_status(
+    pool_box: &PoolBoxWrapper,
+    posted_boxes: &[PostedOracleBox],
+    epoch_params: &EpochParams,
+    height: BlockHeight,
+) -> MonitorStatus {
+    let epoch_id = pool_box.epoch_counter();
+    let pool_box_height = pool_box.get_box().creation_height;
+    MonitorStatus {
+        height: height.0,
+        pool_box_id: String::from(pool_box.get_box().box_id()),
+        pool_box_height,
+        epoch_id: epoch_id.0,
+        rate: pool_box.rate(),
+        reward_token_amount: *pool_box.reward_token().amount.as_u64(),
+        pool_box_age: height.0.saturating_sub(pool_box_height),
+        epoch_length: epoch_params.epoch_length.0.max(0) as u32,
+        posted_datapoints: posted_boxes
+            .iter()
+            .filter(|b| b.epoch_counter() == epoch_id)
+            .count() as u32,
+        min_data_points: epoch_params.min_data_points.0.max(0) as u32,
+        alerts: pool_health_events(pool_box, posted_boxes, epoch_params, height)
+            .iter()
+            .map(NotificationEvent::kind)
+            .collect(),
+    }
+}
+
+/// Result of the last successful check
+#[derive(Clone)]
+pub struct MonitorSnapshot {
+    pub status: MonitorStatus,
+    pub pool_box: PoolBoxWrapper,
+}
+
+pub struct PoolMonitor {
+    pub name: String,
+    pub pool_config: PoolConfig,
+    pub oracle_config: OracleConfig,
+    pub explorer: ExplorerApi,
+    notifier: Notifier,
+    latest: RwLock<Option<MonitorSnapshot>>,
+}
+
+impl PoolMonitor {
+    pub fn new(
+        name: String,
+        pool_config: PoolConfig,
+        oracle_config: OracleConfig,
+        explorer: ExplorerApi,
+    ) -> Self {
+        let notifier = Notifier::from_oracle_config(&oracle_config);
+        PoolMonitor {
+            name,
+            pool_config,
+            oracle_config,
+            explorer,
+            notifier,
+            latest: RwLock::new(None),
+        }
+    }
+
+    /// Result of the last successful check, `None` before the first one
+    pub fn latest(&self) -> Option<MonitorSnapshot> {
+        self.latest.read().unwrap().clone()
+    }
+
+    /// Read the pool state from the explorer
+    pub fn check(&self) -> Result<(MonitorSnapshot, Vec<NotificationEvent>), MonitorError> {
+        let height = BlockHeight(self.explorer.get_network_height_v1()?);
+        let pool_box_wrapper_inputs = &self.pool_config.pool_box_wrapper_inputs;
+        let pool_box = self
+            .explorer
+            .get_unspent_boxes_by_token_id(pool_box_wrapper_inputs.pool_nft_token_id.token_id())?
+            .into_iter()
+            .find_map(|b| PoolBoxWrapper::new(b, pool_box_wrapper_inputs).ok())
+            .ok_or(MonitorError::PoolBoxNotFound)?;
+        let oracle_box_wrapper_inputs = &self.pool_config.oracle_box_wrapper_inputs;
+        let posted_boxes = posted_oracle_boxes(
+            self.explorer.get_unspent_boxes_by_token_id(
+                oracle_box_wrapper_inputs.oracle_token_id.token_id(),
+            )?,
+            oracle_box_wrapper_inputs,
+        );
+        let epoch_params = EpochParams::from(
+            self.pool_config
+                .refresh_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters(),
+        );
+        let events = pool_health_events(&pool_box, &posted_boxes, &epoch_params, height);
+        let snapshot = MonitorSnapshot {
+            status: monitor_status(&pool_box, &posted_boxes, &epoch_params, height),
+            pool_box,
+        };
+        Ok((snapshot, events))
+    }
+
+    /// Check the pool, record the result and send its alerts. Blocking, the explorer and
+    /// notification calls are made with blocking clients.
+    pub fn run_check(&self) {
+        metrics::LOOP_ITERATIONS.inc();
+        match self.check() {
+            Ok((snapshot, events)) => {
+                metrics::POOL_BOX_AGE.set(snapshot.status.pool_box_age as i64);
+                metrics::POSTED_DATAPOINTS.set(snapshot.status.posted_datapoints as i64);
+                if events.is_empty() {
+                    notifications::ping_healthcheck(&self.oracle_config);
+                }
+                for event in events {
+                    log::warn!("pool {}: {:?}", self.name, event);
+                    self.notifier.notify(&self.name, &event);
+                }
+                *self.latest.write().unwrap() = Some(snapshot);
+            }
+            Err(e) => {
+                metrics::LOOP_ERRORS.inc();
+                log::error!("pool {} monitor error: {}", self.name, e);
+                self.notifier.notify(
+                    &self.name,
+                    &NotificationEvent::LoopError {
+                        error: e.to_string(),
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use sigma_test_util::force_any_val;
+
+    use crate::box_kind::OracleBoxWrapper;
+    use crate::box_kind::OracleBoxWrapperInputs;
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::contracts::pool::PoolContractParameters;
+    use crate::oracle_types::EpochCounter;
+    use crate::oracle_types::EpochLength;
+    use crate::oracle_types::MinDatapoints;
+    use crate::pool_commands::test_utils::generate_token_ids;
+    use crate::pool_commands::test_utils::make_datapoint_box;
+    use crate::pool_commands::test_utils::make_pool_box;
+
+    use super::*;
+
+    #[test]
+    fn test_monitor_status() {
+        let token_ids = generate_token_ids();
+        let pool_box = make_pool_box(
+            200,
+            EpochCounter(3),
+            force_any_val(),
+            BlockHeight(100),
+            &PoolContractParameters::default(),
+            &token_ids,
+        );
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let posted_box = |epoch_counter| match OracleBoxWrapper::new(
+            make_datapoint_box(
+                *force_any_val::<DlogProverInput>().public_image().h,
+                200,
+                epoch_counter,
+                &token_ids,
+                force_any_val(),
+                BlockHeight(101),
+                5,
+            ),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap()
+        {
+            OracleBoxWrapper::Posted(posted) => posted,
+            OracleBoxWrapper::Collected(_) => panic!("expected a posted box"),
+        };
+        // a datapoint of the previous epoch is not counted
+        let posted_boxes = vec![
+            posted_box(EpochCounter(3)),
+            posted_box(EpochCounter(3)),
+            posted_box(EpochCounter(2)),
+        ];
+        let epoch_params = EpochParams {
+            epoch_length: EpochLength(10),
+            buffer_length: 4,
+            min_data_points: MinDatapoints(3),
+            max_deviation_percent: 5,
+        };
+
+        let status = monitor_status(&pool_box, &posted_boxes, &epoch_params, BlockHeight(105));
+        assert_eq!(status.posted_datapoints, 2);
+        assert_eq!(status.pool_box_age, 5);
+        assert!(status.is_healthy());
+
+        // the epoch is over with too few datapoints
+        let status = monitor_status(&pool_box, &posted_boxes, &epoch_params, BlockHeight(110));
+        assert_eq!(status.alerts, vec!["pool_underposted"]);
+
+        let status = monitor_status(&pool_box, &posted_boxes, &epoch_params, BlockHeight(120));
+        assert_eq!(status.alerts, vec!["pool_stalled", "pool_underposted"]);
+    }
+}
@@ -0,0 +1,259 @@
+//! Optional publishing of the oracle data to an MQTT broker, for dashboards and other consumers
+//! subscribed to the broker.
+//!
+//! The datapoint fetched for a publish action and every new epoch seen by the main loop (the
+//! confirmed pool box of a refresh) are published as JSON to the datapoint and refresh topics. The
+//! connection is kept by a background thread and reconnects on its own, a message that can't be
+//! queued (e.g. the broker is down for long) is logged and dropped.
+//!
+//! Without the `mqtt` feature the publisher can't be created and fails with
+//! [`MqttError::NotCompiled`].
+
+#[cfg(feature = "mqtt")]
+use std::thread;
+#[cfg(feature = "mqtt")]
+use std::time::Duration;
+
+#[cfg(feature = "mqtt")]
+use rumqttc::Client;
+#[cfg(feature = "mqtt")]
+use rumqttc::MqttOptions;
+#[cfg(feature = "mqtt")]
+use rumqttc::QoS;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::datapoint_source::FetchedDatapoint;
+use crate::datapoint_source::SourceRate;
+use crate::epoch_history::EpochHistoryEntry;
+use crate::oracle_types::BlockHeight;
+
+const DEFAULT_PORT: u16 = 1883;
+const DEFAULT_CLIENT_ID: &str = "oracle-core";
+const DEFAULT_DATAPOINT_TOPIC: &str = "oracle-core/{pool}/datapoint";
+const DEFAULT_REFRESH_TOPIC: &str = "oracle-core/{pool}/refresh";
+
+#[cfg(feature = "mqtt")]
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+#[cfg(feature = "mqtt")]
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Messages queued while the broker is unreachable
+#[cfg(feature = "mqtt")]
+const QUEUE_CAPACITY: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MqttConfig {
+    /// Host name or IP of the broker
+    pub host: String,
+    /// Default 1883
+    pub port: Option<u16>,
+    /// Prefix of the client id, followed by the pool name (default `oracle-core`)
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    /// The password or `keyring:<entry name>`
+    pub password: Option<String>,
+    /// Topic of the fetched datapoints, `{pool}` is replaced with the pool name (default
+    /// `oracle-core/{pool}/datapoint`)
+    pub datapoint_topic: Option<String>,
+    /// Topic of the pool refreshes, `{pool}` is replaced with the pool name (default
+    /// `oracle-core/{pool}/refresh`)
+    pub refresh_topic: Option<String>,
+    /// Keep the last message of a topic on the broker for new subscribers (default true)
+    pub retain: Option<bool>,
+}
+
+#[derive(Debug, Error)]
+pub enum MqttError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serde error: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[cfg(feature = "mqtt")]
+    #[error("MQTT client error: {0}")]
+    Client(#[from] rumqttc::ClientError),
+    #[error("oracle-core was built without the `mqtt` feature")]
+    NotCompiled,
+}
+
+#[derive(Debug, Serialize)]
+struct DatapointMessage<'a> {
+    pool: &'a str,
+    height: u32,
+    rate: i64,
+    sources: &'a [SourceRate],
+}
+
+#[derive(Debug, Serialize)]
+struct RefreshMessage<'a> {
+    pool: &'a str,
+    #[serde(flatten)]
+    epoch: &'a EpochHistoryEntry,
+}
+
+fn topic(template: Option<&str>, default: &str, pool_name: &str) -> String {
+    template.unwrap_or(default).replace("{pool}", pool_name)
+}
+
+fn datapoint_payload(
+    pool_name: &str,
+    height: BlockHeight,
+    fetched: &FetchedDatapoint,
+) -> Result<String, MqttError> {
+    Ok(serde_json::to_string(&DatapointMessage {
+        pool: pool_name,
+        height: height.0,
+        rate: fetched.rate.into(),
+        sources: &fetched.sources,
+    })?)
+}
+
+fn refresh_payload(pool_name: &str, entry: &EpochHistoryEntry) -> Result<String, MqttError> {
+    Ok(serde_json::to_string(&RefreshMessage {
+        pool: pool_name,
+        epoch: entry,
+    })?)
+}
+
+pub struct MqttPublisher {
+    pool_name: String,
+    datapoint_topic: String,
+    refresh_topic: String,
+    retain: bool,
+    #[cfg(feature = "mqtt")]
+    client: Client,
+}
+
+#[cfg(feature = "mqtt")]
+impl MqttPublisher {
+    pub fn new(config: MqttConfig, pool_name: &str) -> Result<Self, MqttError> {
+        let client_id = format!(
+            "{}-{}",
+            config.client_id.as_deref().unwrap_or(DEFAULT_CLIENT_ID),
+            pool_name
+        );
+        let port = config.port.unwrap_or(DEFAULT_PORT);
+        let mut options = MqttOptions::new(client_id, config.host.clone(), port);
+        options.set_keep_alive(KEEP_ALIVE);
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+        let (client, mut connection) = Client::new(options, QUEUE_CAPACITY);
+        // drives the connection until the client is dropped, reconnecting after errors
+        thread::Builder::new()
+            .name(format!("mqtt-{}", pool_name))
+            .spawn(move || {
+                for notification in connection.iter() {
+                    if let Err(e) = notification {
+                        log::warn!("MQTT connection error: {}", e);
+                        thread::sleep(RECONNECT_DELAY);
+                    }
+                }
+            })?;
+        log::info!(
+            "Publishing the oracle data to the MQTT broker {}:{}",
+            config.host,
+            port
+        );
+        Ok(MqttPublisher {
+            pool_name: pool_name.to_string(),
+            datapoint_topic: topic(
+                config.datapoint_topic.as_deref(),
+                DEFAULT_DATAPOINT_TOPIC,
+                pool_name,
+            ),
+            refresh_topic: topic(
+                config.refresh_topic.as_deref(),
+                DEFAULT_REFRESH_TOPIC,
+                pool_name,
+            ),
+            retain: config.retain.unwrap_or(true),
+            client,
+        })
+    }
+
+    /// Queue the datapoint fetched at the given height
+    pub fn publish_datapoint(
+        &mut self,
+        height: BlockHeight,
+        fetched: &FetchedDatapoint,
+    ) -> Result<(), MqttError> {
+        let payload = datapoint_payload(&self.pool_name, height, fetched)?;
+        self.publish(self.datapoint_topic.clone(), payload)
+    }
+
+    /// Queue the epoch started by a refresh
+    pub fn publish_refresh(&mut self, entry: &EpochHistoryEntry) -> Result<(), MqttError> {
+        let payload = refresh_payload(&self.pool_name, entry)?;
+        self.publish(self.refresh_topic.clone(), payload)
+    }
+
+    fn publish(&mut self, topic: String, payload: String) -> Result<(), MqttError> {
+        // never blocks the main loop, fails if the queue is full
+        self.client
+            .try_publish(topic, QoS::AtLeastOnce, self.retain, payload)?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "mqtt"))]
+impl MqttPublisher {
+    pub fn new(_config: MqttConfig, _pool_name: &str) -> Result<Self, MqttError> {
+        Err(MqttError::NotCompiled)
+    }
+
+    pub fn publish_datapoint(
+        &mut self,
+        _height: BlockHeight,
+        _fetched: &FetchedDatapoint,
+    ) -> Result<(), MqttError> {
+        Err(MqttError::NotCompiled)
+    }
+
+    pub fn publish_refresh(&mut self, _entry: &EpochHistoryEntry) -> Result<(), MqttError> {
+        Err(MqttError::NotCompiled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic() {
+        assert_eq!(
+            topic(None, DEFAULT_DATAPOINT_TOPIC, "primary"),
+            "oracle-core/primary/datapoint"
+        );
+        assert_eq!(
+            topic(Some("ergo/{pool}/rate"), DEFAULT_REFRESH_TOPIC, "erg-usd"),
+            "ergo/erg-usd/rate"
+        );
+    }
+
+    #[test]
+    fn test_payloads() {
+        let fetched = FetchedDatapoint {
+            rate: 250.into(),
+            sources: vec![SourceRate {
+                source: "coingecko".to_string(),
+                rate: 250.5,
+            }],
+        };
+        assert_eq!(
+            datapoint_payload("primary", BlockHeight(1000), &fetched).unwrap(),
+            r#"{"pool":"primary","height":1000,"rate":250,"sources":[{"source":"coingecko","rate":250.5}]}"#
+        );
+        let entry = EpochHistoryEntry {
+            epoch_id: 7,
+            rate: 250,
+            height: 1000,
+            pool_box_id: "abcd".to_string(),
+            oracles: vec!["02ff".to_string()],
+        };
+        assert_eq!(
+            refresh_payload("primary", &entry).unwrap(),
+            r#"{"pool":"primary","epoch_id":7,"rate":250,"height":1000,"pool_box_id":"abcd","oracles":["02ff"]}"#
+        );
+    }
+}
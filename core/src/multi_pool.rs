@@ -0,0 +1,222 @@
+//! Drive several oracle pools from a single `Run` process.
+//!
+//! The primary pool is the one configured with the primary oracle/pool config files. Each
+//! additional pool is passed on the command line as `<pool_config.yaml>[,<oracle_config.yaml>]`.
+//! Without an oracle config the primary one is used (shared wallet and oracle address). With its
+//! own oracle config the pool can use a different oracle address from the node wallet. Scan IDs and the
+//! audit log of each additional pool are stored in a sub-folder of the data dir named after its
+//! pool config file. Pools with the same datapoint source can share the fetched datapoint
+//! (`share_datapoint_fetch_secs` in the oracle config, see [`crate::datapoint_source`]).
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::action_report::ActionReportStorage;
+use crate::attestation::LatestAttestation;
+use crate::audit_log::AUDIT_LOG_FILE_NAME;
+use crate::context::OracleContext;
+use crate::datapoint_source::RuntimeDataPointSource;
+use crate::datapoint_source::SourceChecks;
+use crate::epoch_history::EPOCH_HISTORY_FILE_NAME;
+use crate::node_interface::node_api::NodeApi;
+use crate::notifications::Notifier;
+use crate::oracle_config::OracleConfig;
+use crate::oracle_state::log_oracle_address_change;
+use crate::oracle_state::OraclePool;
+use crate::pool_config::PoolConfig;
+use crate::scans::registered_oracle_address;
+use crate::scans::NodeScanRegistry;
+use crate::scans::SCANS_FILE_NAME;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MultiPoolError {
+    #[error("invalid extra pool spec {0:?}, expected <pool_config.yaml>[,<oracle_config.yaml>]")]
+    InvalidSpec(String),
+    #[error("extra pool {0} uses node {1}, but all pools must use the node of the primary oracle config ({2})")]
+    DifferentNode(String, String, String),
+    #[error("pool config {0} is already used by another pool")]
+    DuplicatePoolConfig(String),
+}
+
+/// Command line description of an additional pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtraPoolSpec {
+    pub pool_config_path: PathBuf,
+    pub oracle_config_path: Option<PathBuf>,
+}
+
+impl ExtraPoolSpec {
+    /// Name of the pool used in logs and as the name of its data sub-folder
+    pub fn name(&self) -> String {
+        self.pool_config_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.pool_config_path.display().to_string())
+    }
+}
+
+impl FromStr for ExtraPoolSpec {
+    type Err = MultiPoolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ',').map(str::trim);
+        let pool_config_path = parts
+            .next()
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .ok_or_else(|| MultiPoolError::InvalidSpec(s.to_string()))?;
+        let oracle_config_path = match parts.next() {
+            Some("") => return Err(MultiPoolError::InvalidSpec(s.to_string())),
+            Some(p) => Some(PathBuf::from(p)),
+            None => None,
+        };
+        Ok(ExtraPoolSpec {
+            pool_config_path,
+            oracle_config_path,
+        })
+    }
+}
+
+/// Everything needed to run the main loop of a pool, the primary one included
+pub struct PoolRunner {
+    pub name: String,
+    pub ctx: OracleContext,
+    pub oracle_pool: Arc<OraclePool>,
+    pub datapoint_source: RuntimeDataPointSource,
+    pub node_api: NodeApi,
+    pub report_storage: Arc<RwLock<ActionReportStorage>>,
+    pub audit_log_path: PathBuf,
+    pub epoch_history_path: PathBuf,
+    pub latest_attestation: LatestAttestation,
+}
+
+impl PoolRunner {
+    /// Load the configs of the additional pool and make sure its scans are registered in the node
+    pub fn load(
+        spec: &ExtraPoolSpec,
+        data_dir: &Path,
+        primary_oracle_config: &OracleConfig,
+        primary_notifier: Arc<Notifier>,
+    ) -> Result<PoolRunner, anyhow::Error> {
+        let name = spec.name();
+        let pool_config = PoolConfig::load_from_path(&spec.pool_config_path)?;
+        let oracle_config = match spec.oracle_config_path {
+            Some(ref path) => OracleConfig::load_from_path(path)?,
+            None => primary_oracle_config.clone(),
+        };
+        // All pools share the node of the primary oracle config
+        if oracle_config.node_url != primary_oracle_config.node_url {
+            return Err(MultiPoolError::DifferentNode(
+                name,
+                oracle_config.node_url.to_string(),
+                primary_oracle_config.node_url.to_string(),
+            )
+            .into());
+        }
+        let pool_data_dir = data_dir.join(&name);
+        std::fs::create_dir_all(&pool_data_dir)?;
+        let node_api = NodeApi::new(oracle_config.node_api_key.clone(), &oracle_config.node_url);
+        let scans_file_path = pool_data_dir.join(SCANS_FILE_NAME);
+        let previous_oracle_address = registered_oracle_address(&scans_file_path)?;
+        let node_scan_registry = NodeScanRegistry::ensure_node_registered_scans_at(
+            &node_api,
+            &pool_config,
+            &oracle_config.oracle_address,
+            &scans_file_path,
+        )?;
+        let datapoint_source = RuntimeDataPointSource::new(
+            pool_config.data_point_source,
+            oracle_config.data_point_source_custom_script.clone(),
+        )?
+        .with_checks(SourceChecks::from_oracle_config(&oracle_config))
+        .shared(
+            oracle_config
+                .share_datapoint_fetch_secs
+                .map(Duration::from_secs),
+        );
+        log::info!(
+            "Loaded extra pool {} (oracle address {})",
+            name,
+            oracle_config.oracle_address.to_base58()
+        );
+        // the alerts of all pools are sent with the notifications of the primary oracle config
+        let ctx = OracleContext::new(oracle_config, pool_config).with_notifier(primary_notifier);
+        let oracle_pool = Arc::new(OraclePool::new(&node_scan_registry, &ctx)?);
+        if let Some(previous_oracle_address) = previous_oracle_address {
+            log_oracle_address_change(
+                &oracle_pool,
+                &name,
+                &previous_oracle_address,
+                &ctx.oracle_config.oracle_address,
+            );
+        }
+        Ok(PoolRunner {
+            name,
+            ctx,
+            oracle_pool,
+            datapoint_source,
+            node_api,
+            report_storage: Arc::new(RwLock::new(ActionReportStorage::new())),
+            audit_log_path: pool_data_dir.join(AUDIT_LOG_FILE_NAME),
+            epoch_history_path: pool_data_dir.join(EPOCH_HISTORY_FILE_NAME),
+            latest_attestation: LatestAttestation::default(),
+        })
+    }
+}
+
+/// Parse the extra pool specs given on the command line, rejecting pools listed twice
+pub fn parse_extra_pool_specs(specs: &[String]) -> Result<Vec<ExtraPoolSpec>, MultiPoolError> {
+    let mut parsed: Vec<ExtraPoolSpec> = Vec::new();
+    for s in specs {
+        let spec = ExtraPoolSpec::from_str(s)?;
+        if parsed.iter().any(|p| p.name() == spec.name()) {
+            return Err(MultiPoolError::DuplicatePoolConfig(
+                spec.pool_config_path.display().to_string(),
+            ));
+        }
+        parsed.push(spec);
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_extra_pool_spec() {
+        assert_eq!(
+            ExtraPoolSpec::from_str("pools/erg_xau.yaml").unwrap(),
+            ExtraPoolSpec {
+                pool_config_path: PathBuf::from("pools/erg_xau.yaml"),
+                oracle_config_path: None,
+            }
+        );
+        assert_eq!(
+            ExtraPoolSpec::from_str("erg_xau.yaml, oracle_xau.yaml").unwrap(),
+            ExtraPoolSpec {
+                pool_config_path: PathBuf::from("erg_xau.yaml"),
+                oracle_config_path: Some(PathBuf::from("oracle_xau.yaml")),
+            }
+        );
+        assert!(ExtraPoolSpec::from_str("").is_err());
+        assert!(ExtraPoolSpec::from_str("erg_xau.yaml,").is_err());
+    }
+
+    #[test]
+    fn reject_duplicate_pools() {
+        let specs = vec!["a/erg_xau.yaml".to_string(), "b/erg_xau.yaml".to_string()];
+        assert!(matches!(
+            parse_extra_pool_specs(&specs),
+            Err(MultiPoolError::DuplicatePoolConfig(_))
+        ));
+        let specs = vec!["erg_xau.yaml".to_string(), "ada_usd.yaml".to_string()];
+        assert_eq!(parse_extra_pool_specs(&specs).unwrap().len(), 2);
+    }
+}
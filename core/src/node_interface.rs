@@ -4,9 +4,15 @@ use ergo_lib::{
 };
 use ergo_node_interface::node_interface::{NodeError, NodeInterface};
 use log::debug;
-use log::error;
+use thiserror::Error;
 
+use crate::exit_status::exit_with_error;
+use crate::exit_status::ErrorCategory;
+
+#[cfg(test)]
+pub(crate) mod mock_node;
 pub mod node_api;
+pub mod snapshot;
 
 pub type Result<T> = std::result::Result<T, NodeError>;
 
@@ -62,11 +68,22 @@ impl SignTransactionWithInputs for NodeInterface {
     }
 }
 
+#[derive(Debug, Error)]
+#[error("Wallet must be unlocked for node operations")]
+pub struct WalletLockedError;
+
+/// Exit (in the `wallet_locked` error category) unless the node wallet is unlocked
 pub fn assert_wallet_unlocked(node: &NodeInterface) {
-    let unlocked = node.wallet_status().unwrap().unlocked;
+    let unlocked = match node.wallet_status() {
+        Ok(status) => status.unlocked,
+        Err(e) => exit_with_error("wallet status", e.into(), ErrorCategory::NodeConnectivity),
+    };
     if !unlocked {
-        error!("Wallet must be unlocked for node operations");
-        std::process::exit(exitcode::SOFTWARE);
+        exit_with_error(
+            "wallet status",
+            WalletLockedError.into(),
+            ErrorCategory::WalletLocked,
+        );
     } else {
         debug!("Wallet unlocked");
     }
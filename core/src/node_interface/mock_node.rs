@@ -0,0 +1,656 @@
+//! Mock of the Ergo node REST API on a local port, for testing [`NodeApi`], the scans and the
+//! run loop without a node.
+//!
+//! The mock keeps a set of unspent boxes. The registered scans return the boxes holding the token
+//! of their tracking rule, the wallet the boxes guarded by the change address. A submitted tx
+//! spends its inputs and creates its outputs right away and stays in the mempool until
+//! [`MockNodeState::mine_block`]. Txs are signed with the wallet in [`MockNodeState::signer`].
+//!
+//! Failures are injected per path prefix with [`MockNode::inject`], either as an error status
+//! code or as a delay of the response.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::Transaction;
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use ergo_lib::wallet::signing::TransactionContext;
+use ergo_lib::wallet::Wallet;
+use reqwest::Url;
+use serde_json::json;
+use sigma_test_util::force_any_val;
+
+use super::node_api::NodeApi;
+
+pub const MOCK_NODE_API_KEY: &str = "hello";
+
+/// Injected failure of the requests to a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Respond with the status code (e.g. 503) and an error body
+    Status(u16),
+    /// Wait before handling the request, to simulate a slow node
+    Delay(Duration),
+}
+
+struct InjectedFault {
+    path_prefix: String,
+    fault: Fault,
+    /// Requests left to fail, `None` for all of them
+    remaining: Option<usize>,
+}
+
+pub struct MockScan {
+    pub name: String,
+    pub tracking_rule: serde_json::Value,
+}
+
+/// Chain, wallet and scans of the mock node
+pub struct MockNodeState {
+    pub height: u32,
+    /// Height the wallet is scanned to, `/wallet/status` reports a running rescan when it is
+    /// below `height`
+    pub wallet_height: u32,
+    pub unlocked: bool,
+    pub change_address: Option<NetworkAddress>,
+    /// Wallet signing the txs, `/wallet/transaction/sign` fails without it
+    pub signer: Option<Wallet>,
+    pub unspent_boxes: Vec<ErgoBox>,
+    pub scans: BTreeMap<u64, MockScan>,
+    next_scan_id: u64,
+    /// Submitted txs that are not mined yet
+    pub mempool: Vec<Transaction>,
+    /// Mined txs with their inclusion height
+    pub mined: Vec<(Transaction, u32)>,
+    /// Heights of the requested wallet rescans
+    pub rescans: Vec<u32>,
+    /// Method and path of every request, e.g. `POST /scan/register`
+    pub requests: Vec<String>,
+    faults: Vec<InjectedFault>,
+}
+
+impl MockNodeState {
+    fn new(height: u32) -> Self {
+        MockNodeState {
+            height,
+            wallet_height: height,
+            unlocked: true,
+            change_address: None,
+            signer: None,
+            unspent_boxes: Vec::new(),
+            scans: BTreeMap::new(),
+            next_scan_id: 1,
+            mempool: Vec::new(),
+            mined: Vec::new(),
+            rescans: Vec::new(),
+            requests: Vec::new(),
+            faults: Vec::new(),
+        }
+    }
+
+    /// Mines the mempool txs in the next block
+    pub fn mine_block(&mut self) {
+        self.height += 1;
+        self.wallet_height = self.height;
+        let height = self.height;
+        self.mined
+            .extend(self.mempool.drain(..).map(|tx| (tx, height)));
+    }
+
+    /// Unspent boxes holding the token tracked by the scan
+    pub fn scan_boxes(&self, scan_id: u64) -> Option<Vec<ErgoBox>> {
+        let scan = self.scans.get(&scan_id)?;
+        let token_ids = tracked_token_ids(&scan.tracking_rule);
+        Some(
+            self.unspent_boxes
+                .iter()
+                .filter(|b| token_ids.iter().any(|token_id| holds_token(b, token_id)))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Unspent boxes guarded by the change address
+    pub fn wallet_boxes(&self) -> Vec<ErgoBox> {
+        let ergo_tree = match self
+            .change_address
+            .as_ref()
+            .and_then(|address| address.address().script().ok())
+        {
+            Some(ergo_tree) => ergo_tree,
+            None => return Vec::new(),
+        };
+        self.unspent_boxes
+            .iter()
+            .filter(|b| b.ergo_tree == ergo_tree)
+            .cloned()
+            .collect()
+    }
+
+    /// Spends the tx inputs, adds its outputs and puts it in the mempool
+    fn apply_tx(&mut self, tx: Transaction) {
+        let input_ids: Vec<String> = tx
+            .inputs
+            .iter()
+            .map(|input| String::from(input.box_id))
+            .collect();
+        self.unspent_boxes
+            .retain(|b| !input_ids.contains(&String::from(b.box_id())));
+        self.unspent_boxes.extend(tx.outputs.iter().cloned());
+        self.mempool.push(tx);
+    }
+
+    fn unspent_box(&self, box_id: &str) -> Option<ErgoBox> {
+        self.unspent_boxes
+            .iter()
+            .find(|b| String::from(b.box_id()) == box_id)
+            .cloned()
+    }
+
+    fn take_fault(&mut self, path: &str) -> Option<Fault> {
+        let pos = self.faults.iter().position(|f| {
+            path.starts_with(&f.path_prefix) && f.remaining.map_or(true, |remaining| remaining > 0)
+        })?;
+        let injected = &mut self.faults[pos];
+        if let Some(remaining) = injected.remaining.as_mut() {
+            *remaining -= 1;
+        }
+        Some(injected.fault)
+    }
+}
+
+/// Mock node serving on `127.0.0.1` until dropped
+pub struct MockNode {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockNodeState>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockNode {
+    /// Starts the mock at the given chain height on a free port
+    pub fn start(height: u32) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(MockNodeState::new(height)));
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let state = state.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        let state = state.clone();
+                        // own thread, so a delayed response doesn't hold up the other requests
+                        std::thread::spawn(move || handle_connection(stream, &state));
+                    }
+                }
+            })
+        };
+        MockNode {
+            addr,
+            state,
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}", self.addr)).unwrap()
+    }
+
+    pub fn node_api(&self) -> NodeApi {
+        NodeApi::new(MOCK_NODE_API_KEY.to_string(), &self.url())
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, MockNodeState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Fails the next `times` requests (all of them if `None`) whose path starts with
+    /// `path_prefix`
+    pub fn inject(&self, path_prefix: &str, fault: Fault, times: Option<usize>) {
+        self.state().faults.push(InjectedFault {
+            path_prefix: path_prefix.to_string(),
+            fault,
+            remaining: times,
+        });
+    }
+
+    /// Removes the injected failures
+    pub fn clear_faults(&self) {
+        self.state().faults.clear();
+    }
+}
+
+impl Drop for MockNode {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // wake up the accept loop
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    body: String,
+}
+
+struct Response {
+    status: u16,
+    body: String,
+}
+
+impl Response {
+    fn ok(body: serde_json::Value) -> Self {
+        Response {
+            status: 200,
+            body: body.to_string(),
+        }
+    }
+
+    /// Error body in the format of the node
+    fn error(status: u16, detail: &str) -> Self {
+        Response {
+            status,
+            body: json!({
+                "error": status,
+                "reason": reason_phrase(status),
+                "detail": detail,
+            })
+            .to_string(),
+        }
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, state: &Mutex<MockNodeState>) {
+    let request = match read_request(&stream) {
+        Some(request) => request,
+        None => return,
+    };
+    let fault = {
+        let mut state = state.lock().unwrap();
+        state
+            .requests
+            .push(format!("{} {}", request.method, request.path));
+        state.take_fault(&request.path)
+    };
+    let response = match fault {
+        Some(Fault::Status(status)) => Response::error(status, "injected failure"),
+        Some(Fault::Delay(delay)) => {
+            std::thread::sleep(delay);
+            route(&request, &mut state.lock().unwrap())
+        }
+        None => route(&request, &mut state.lock().unwrap()),
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason_phrase(response.status),
+        response.body.len(),
+        response.body
+    );
+}
+
+fn read_request(stream: &TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok()?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok()?;
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok()?;
+    Some(Request {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        body: String::from_utf8(body).ok()?,
+    })
+}
+
+fn route(request: &Request, state: &mut MockNodeState) -> Response {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["info"]) => Response::ok(json!({
+            "fullHeight": state.height,
+            "headersHeight": state.height,
+            "maxPeerHeight": state.height,
+        })),
+        ("GET", ["wallet", "status"]) => Response::ok(json!({
+            "isInitialized": true,
+            "isUnlocked": state.unlocked,
+            "changeAddress": state.change_address.as_ref().map(|a| a.to_base58()),
+            "walletHeight": state.wallet_height,
+        })),
+        ("GET", ["wallet", "boxes", "unspent"]) => {
+            Response::ok(wallet_boxes_json(&state.wallet_boxes()))
+        }
+        ("POST", ["wallet", "rescan"]) => {
+            let from_height = parse_body(&request.body)
+                .and_then(|body| body["fromHeight"].as_u64())
+                .unwrap_or(0) as u32;
+            state.rescans.push(from_height);
+            Response::ok(json!({}))
+        }
+        ("POST", ["wallet", "transaction", "sign"]) => sign(&request.body, state),
+        ("GET", ["wallet", "transactionById"]) => {
+            let tx_id = request
+                .query
+                .split('&')
+                .find_map(|param| param.strip_prefix("id="))
+                .unwrap_or_default();
+            match state
+                .mined
+                .iter()
+                .find(|(tx, _)| String::from(tx.id()) == tx_id)
+            {
+                Some((_, height)) => Response::ok(json!({ "inclusionHeight": height })),
+                None => Response::error(404, "tx not found"),
+            }
+        }
+        ("POST", ["scan", "register"]) => match parse_body(&request.body) {
+            Some(body) => {
+                let scan_id = state.next_scan_id;
+                state.next_scan_id += 1;
+                state.scans.insert(
+                    scan_id,
+                    MockScan {
+                        name: body["scanName"].as_str().unwrap_or_default().to_string(),
+                        tracking_rule: body["trackingRule"].clone(),
+                    },
+                );
+                Response::ok(json!({ "scanId": scan_id }))
+            }
+            None => Response::error(400, "invalid scan"),
+        },
+        ("POST", ["scan", "deregister"]) => {
+            let scan_id = parse_body(&request.body).and_then(|body| body["scanId"].as_u64());
+            match scan_id {
+                Some(scan_id) if state.scans.remove(&scan_id).is_some() => {
+                    Response::ok(json!({ "scanId": scan_id }))
+                }
+                _ => Response::error(400, "unknown scan"),
+            }
+        }
+        ("GET", ["scan", "listAll"]) => Response::ok(json!(state
+            .scans
+            .iter()
+            .map(|(scan_id, scan)| json!({
+                "scanId": scan_id,
+                "scanName": scan.name,
+                "trackingRule": scan.tracking_rule,
+            }))
+            .collect::<Vec<_>>())),
+        ("GET", ["scan", "unspentBoxes", scan_id]) => {
+            match scan_id.parse().ok().and_then(|id| state.scan_boxes(id)) {
+                Some(boxes) => Response::ok(wallet_boxes_json(&boxes)),
+                None => Response::error(400, "unknown scan"),
+            }
+        }
+        ("GET", ["blockchain", "box", "unspent", "byTokenId", token_id]) => {
+            let boxes: Vec<&ErgoBox> = state
+                .unspent_boxes
+                .iter()
+                .filter(|b| holds_token(b, token_id))
+                .collect();
+            Response::ok(serde_json::to_value(boxes).unwrap())
+        }
+        ("POST", ["blockchain", "box", "unspent", "byErgoTree"]) => {
+            let ergo_tree = request.body.trim().trim_matches('"');
+            let boxes: Vec<&ErgoBox> = state
+                .unspent_boxes
+                .iter()
+                .filter(|b| {
+                    b.ergo_tree
+                        .sigma_serialize_bytes()
+                        .map_or(false, |bytes| base16::encode_lower(&bytes) == ergo_tree)
+                })
+                .collect();
+            Response::ok(serde_json::to_value(boxes).unwrap())
+        }
+        ("GET", ["utxo", "withPool", "byId", box_id]) => match state.unspent_box(box_id) {
+            Some(b) => Response::ok(serde_json::to_value(b).unwrap()),
+            None => Response::error(404, "box not found"),
+        },
+        ("POST", ["transactions"]) => match serde_json::from_str::<Transaction>(&request.body) {
+            Ok(tx) => {
+                let tx_id = String::from(tx.id());
+                state.apply_tx(tx);
+                Response::ok(json!(tx_id))
+            }
+            Err(e) => Response::error(400, &e.to_string()),
+        },
+        ("GET", ["transactions", "unconfirmed", "byTransactionId", tx_id]) => {
+            match state
+                .mempool
+                .iter()
+                .find(|tx| String::from(tx.id()) == *tx_id)
+            {
+                Some(tx) => Response::ok(serde_json::to_value(tx).unwrap()),
+                None => Response::error(404, "tx not found"),
+            }
+        }
+        _ => Response::error(404, "unknown endpoint"),
+    }
+}
+
+/// Signs with the `signer` wallet, the inputs are taken from the request or the unspent boxes
+fn sign(body: &str, state: &MockNodeState) -> Response {
+    let signer = match &state.signer {
+        Some(signer) => signer,
+        None => return Response::error(400, "no signer set in the mock node"),
+    };
+    let body = match parse_body(body) {
+        Some(body) => body,
+        None => return Response::error(400, "invalid request"),
+    };
+    let unsigned_tx: UnsignedTransaction = match serde_json::from_value(body["tx"].clone()) {
+        Ok(tx) => tx,
+        Err(e) => return Response::error(400, &e.to_string()),
+    };
+    let inputs = match boxes_from_request(
+        &body["inputsRaw"],
+        unsigned_tx
+            .inputs
+            .iter()
+            .map(|input| String::from(input.box_id)),
+        state,
+    ) {
+        Some(inputs) => inputs,
+        None => return Response::error(400, "unknown tx input"),
+    };
+    let data_boxes = match boxes_from_request(
+        &body["dataInputsRaw"],
+        unsigned_tx
+            .data_inputs
+            .iter()
+            .flat_map(|data_inputs| data_inputs.iter())
+            .map(|data_input| String::from(data_input.box_id)),
+        state,
+    ) {
+        Some(data_boxes) => data_boxes,
+        None => return Response::error(400, "unknown tx data input"),
+    };
+    let mut state_context = force_any_val::<ErgoStateContext>();
+    state_context.pre_header.height = state.height;
+    let signed = TransactionContext::new(unsigned_tx, inputs, data_boxes)
+        .map_err(|e| e.to_string())
+        .and_then(|tx_context| {
+            signer
+                .sign_transaction(tx_context, &state_context, None)
+                .map_err(|e| e.to_string())
+        });
+    match signed {
+        Ok(tx) => Response::ok(serde_json::to_value(tx).unwrap()),
+        Err(e) => Response::error(400, &e),
+    }
+}
+
+/// Base16-encoded boxes of the request, or the unspent boxes with the given ids if not sent
+fn boxes_from_request(
+    raw_boxes: &serde_json::Value,
+    box_ids: impl Iterator<Item = String>,
+    state: &MockNodeState,
+) -> Option<Vec<ErgoBox>> {
+    match raw_boxes.as_array() {
+        Some(raw_boxes) => raw_boxes
+            .iter()
+            .map(|raw| {
+                let bytes = base16::decode(raw.as_str()?).ok()?;
+                ErgoBox::sigma_parse_bytes(&bytes).ok()
+            })
+            .collect(),
+        None => box_ids.map(|box_id| state.unspent_box(&box_id)).collect(),
+    }
+}
+
+/// Boxes in the format of the wallet and scan endpoints
+fn wallet_boxes_json(boxes: &[ErgoBox]) -> serde_json::Value {
+    serde_json::Value::Array(
+        boxes
+            .iter()
+            .map(|b| {
+                json!({
+                    "box": b,
+                    "confirmationsNum": 1,
+                    "creationTransaction": b.transaction_id,
+                    "spendingTransaction": null,
+                    "onchain": true,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Token ids of the `containsAsset` predicates of the tracking rule
+fn tracked_token_ids(tracking_rule: &serde_json::Value) -> Vec<String> {
+    if tracking_rule["predicate"] == "containsAsset" {
+        return tracking_rule["assetId"]
+            .as_str()
+            .map(|token_id| vec![token_id.to_string()])
+            .unwrap_or_default();
+    }
+    tracking_rule["args"]
+        .as_array()
+        .map(|args| args.iter().flat_map(tracked_token_ids).collect())
+        .unwrap_or_default()
+}
+
+fn holds_token(b: &ErgoBox, token_id: &str) -> bool {
+    b.tokens.as_ref().map_or(false, |tokens| {
+        tokens
+            .iter()
+            .any(|token| String::from(token.token_id) == token_id)
+    })
+}
+
+fn parse_body(body: &str) -> Option<serde_json::Value> {
+    serde_json::from_str(body).ok()
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn test_wallet_status_and_height_lag() {
+        let mock_node = MockNode::start(1000);
+        let node_api = mock_node.node_api();
+        assert!(node_api.node.wallet_status().unwrap().unlocked);
+        assert!(node_api.get_change_address().is_err());
+        assert_eq!(node_api.node.current_block_height().unwrap(), 1000);
+        assert_eq!(node_api.get_height_lag().unwrap(), 0);
+        assert_eq!(node_api.get_heights().unwrap().full_height.0, 1000);
+        assert_eq!(mock_node.state().requests[0], "GET /wallet/status");
+    }
+
+    #[test]
+    fn test_register_and_deregister_scan() {
+        let mock_node = MockNode::start(1000);
+        let node_api = mock_node.node_api();
+        let scan_id = node_api
+            .register_scan("test".to_string(), json!({"predicate": "containsAsset"}))
+            .unwrap();
+        assert_eq!(mock_node.state().scans.len(), 1);
+        assert_eq!(node_api.list_scan_ids().unwrap(), vec![1]);
+        assert!(node_api.node.scan_boxes(scan_id).unwrap().is_empty());
+        node_api.deregister_scan(scan_id).unwrap();
+        assert!(mock_node.state().scans.is_empty());
+        assert!(node_api.deregister_scan(scan_id).is_err());
+    }
+
+    #[test]
+    fn test_injected_failures() {
+        let mock_node = MockNode::start(1000);
+        let node_api = mock_node.node_api();
+        mock_node.inject("/info", Fault::Status(503), Some(2));
+        assert!(node_api.get_height_lag().is_err());
+        assert!(node_api.get_height_lag().is_err());
+        assert_eq!(node_api.get_height_lag().unwrap(), 0);
+
+        let delay = Duration::from_millis(300);
+        mock_node.inject("/wallet", Fault::Delay(delay), None);
+        let start = Instant::now();
+        assert!(node_api.node.wallet_status().unwrap().unlocked);
+        assert!(start.elapsed() >= delay);
+        mock_node.clear_faults();
+        assert_eq!(node_api.node.current_block_height().unwrap(), 1000);
+    }
+}
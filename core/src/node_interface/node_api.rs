@@ -1,5 +1,12 @@
+use std::convert::TryInto;
+
+use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+use ergo_lib::chain::transaction::reduced::ReducedTransaction;
 use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::Transaction;
 use ergo_lib::chain::transaction::TxId;
+use ergo_lib::ergo_chain_types::Header;
+use ergo_lib::ergo_chain_types::PreHeader;
 use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
 use ergo_lib::ergotree_ir::chain::address::AddressEncoderError;
 use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
@@ -12,28 +19,69 @@ use reqwest::Url;
 use serde_json::json;
 use thiserror::Error;
 
+use crate::audit_log::TxConfirmationSource;
+use crate::babel_fee::BabelBoxSource;
+use crate::babel_fee::BabelFeeError;
+use crate::cold_signing::reduce_with_boxes;
+use crate::cold_signing::ColdSigningError;
+use crate::cold_signing::ReduceTransaction;
+use crate::node_interface::snapshot::NodeHeights;
+use crate::oracle_types::BlockHeight;
+use crate::rebroadcast::RebroadcastNode;
 use crate::scans::ScanID;
 use crate::wallet::WalletDataError;
 use crate::wallet::WalletDataSource;
 
 pub struct NodeApi {
     pub node: NodeInterface,
+    pub node_url: Url,
 }
 
 impl NodeApi {
     pub fn new(api_key: String, node_url: &Url) -> Self {
         let node = NodeInterface::from_url(&api_key, node_url.clone());
-        Self { node }
+        Self {
+            node,
+            node_url: node_url.clone(),
+        }
+    }
+
+    /// Full (validated) height of the node and the blocks it is behind the best height known from
+    /// its headers and peers, from a single `/info` request
+    pub fn get_heights(&self) -> Result<NodeHeights, NodeApiError> {
+        let response = self.node.send_get_req("/info")?;
+        let json: serde_json::Value = serde_json::from_str(&response.text()?)?;
+        let full_height = json["fullHeight"]
+            .as_u64()
+            .ok_or(NodeApiError::NoFullHeight)?;
+        let best_height = json["headersHeight"]
+            .as_u64()
+            .unwrap_or(0)
+            .max(json["maxPeerHeight"].as_u64().unwrap_or(0));
+        Ok(NodeHeights {
+            full_height: BlockHeight(full_height as u32),
+            height_lag: best_height.saturating_sub(full_height) as u32,
+        })
+    }
+
+    /// Blocks the node's full (validated) height is behind the best height known from its headers
+    /// and peers, from the node `/info`
+    pub fn get_height_lag(&self) -> Result<u32, NodeApiError> {
+        Ok(self.get_heights()?.height_lag)
     }
 
     pub fn get_change_address(&self) -> Result<NetworkAddress, NodeApiError> {
-        let change_address_str = self
-            .node
-            .wallet_status()?
-            .change_address
-            .ok_or(NodeApiError::NoChangeAddressSetInNode)?;
-        let addr = AddressEncoder::unchecked_parse_network_address_from_str(&change_address_str)?;
-        Ok(addr)
+        parse_change_address(self.node.wallet_status()?.change_address)
+    }
+
+    /// Secret of the wallet address, from the node `/wallet/getPrivateKey`
+    pub fn get_private_key(&self, address: &NetworkAddress) -> Result<Vec<u8>, NodeApiError> {
+        let response = self.node.send_post_req(
+            "/wallet/getPrivateKey",
+            format!("\"{}\"", address.to_base58()),
+        )?;
+        let secret: String = serde_json::from_str(&response.text()?)?;
+        base16::decode(&secret).map_err(|_| NodeApiError::InvalidPrivateKey)
     }
 
     /// Registers a scan with the node and either returns the `scan_id` or an error
@@ -70,6 +118,21 @@ impl NodeApi {
         Ok(scan_id)
     }
 
+    /// Ids of all the scans registered with the node
+    pub fn list_scan_ids(&self) -> Result<Vec<u64>, NodeApiError> {
+        let response = self.node.send_get_req("/scan/listAll")?;
+        let json: serde_json::Value = serde_json::from_str(&response.text()?)?;
+        Ok(json
+            .as_array()
+            .map(|scans| {
+                scans
+                    .iter()
+                    .filter_map(|scan| scan["scanId"].as_u64())
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
     pub fn rescan_from_height(&self, height: u32) -> Result<(), NodeApiError> {
         log::info!("Triggering wallet rescan");
         self.node.send_post_req(
@@ -79,11 +142,88 @@ impl NodeApi {
         Ok(())
     }
 
+    /// Unspent boxes with the given base16-encoded ergo tree. Requires the node to run with
+    /// `extraIndex` enabled.
+    pub fn get_unspent_boxes_by_ergo_tree(
+        &self,
+        ergo_tree: &str,
+    ) -> Result<Vec<ErgoBox>, NodeApiError> {
+        let response = self.node.send_post_req(
+            "/blockchain/box/unspent/byErgoTree?offset=0&limit=100",
+            format!("\"{}\"", ergo_tree),
+        )?;
+        let text = response.text()?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Unspent boxes holding the given base16-encoded token id. Requires the node to run with
+    /// `extraIndex` enabled.
+    pub fn get_unspent_boxes_by_token_id(
+        &self,
+        token_id: &str,
+    ) -> Result<Vec<ErgoBox>, NodeApiError> {
+        let response = self.node.send_get_req(&format!(
+            "/blockchain/box/unspent/byTokenId/{}?offset=0&limit=100",
+            token_id
+        ))?;
+        let text = response.text()?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// EIP-4 name of the given base16-encoded token id, `None` if the token is unknown to the node.
+    /// Requires the node to run with `extraIndex` enabled.
+    pub fn get_token_name(&self, token_id: &str) -> Result<Option<String>, NodeApiError> {
+        let response = self
+            .node
+            .send_get_req(&format!("/blockchain/token/byId/{}", token_id))?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+        let json: serde_json::Value = serde_json::from_str(&response.text()?)?;
+        Ok(json["name"].as_str().map(str::to_string))
+    }
+
+    /// Unspent box (including the mempool) with the given id
+    pub fn get_box_by_id(&self, box_id: &str) -> Result<ErgoBox, NodeApiError> {
+        let response = self
+            .node
+            .send_get_req(&format!("/utxo/withPool/byId/{}", box_id))?;
+        if !response.status().is_success() {
+            return Err(NodeApiError::BoxNotFound(box_id.to_string()));
+        }
+        Ok(serde_json::from_str(&response.text()?)?)
+    }
+
+    /// State context of the next block from the last 10 headers
+    pub fn get_state_context(&self) -> Result<ErgoStateContext, NodeApiError> {
+        let response = self.node.send_get_req("/blocks/lastHeaders/10")?;
+        let mut headers: Vec<Header> = serde_json::from_str(&response.text()?)?;
+        // the node returns them from the oldest one
+        headers.sort_by_key(|header| std::cmp::Reverse(header.height));
+        let headers: [Header; 10] = headers
+            .try_into()
+            .map_err(|_| NodeApiError::NotEnoughHeaders)?;
+        let mut pre_header = PreHeader::from(headers[0].clone());
+        pre_header.parent_id = headers[0].id;
+        pre_header.height = headers[0].height + 1;
+        Ok(ErgoStateContext::new(pre_header, headers))
+    }
+
     /// Sign an `UnsignedTransaction` and then submit it to the mempool.
     pub fn sign_and_submit_transaction(
         &self,
         unsigned_tx: &UnsignedTransaction,
     ) -> Result<TxId, NodeApiError> {
+        Ok(self
+            .sign_and_submit_transaction_returning_signed(unsigned_tx)?
+            .id())
+    }
+
+    /// Sign an `UnsignedTransaction`, submit it to the mempool and return the signed tx
+    pub fn sign_and_submit_transaction_returning_signed(
+        &self,
+        unsigned_tx: &UnsignedTransaction,
+    ) -> Result<Transaction, NodeApiError> {
         log::trace!(
             "Signing transaction: {}",
             serde_json::to_string_pretty(&unsigned_tx).unwrap()
@@ -93,7 +233,93 @@ impl NodeApi {
             "Submitting signed transaction: {}",
             serde_json::to_string_pretty(&signed_tx).unwrap()
         );
-        Ok(self.node.submit_transaction(&signed_tx)?)
+        self.node.submit_transaction(&signed_tx)?;
+        Ok(signed_tx)
+    }
+}
+
+/// Change address of the `/wallet/status` of the node
+pub fn parse_change_address(
+    change_address: Option<String>,
+) -> Result<NetworkAddress, NodeApiError> {
+    let change_address_str = change_address.ok_or(NodeApiError::NoChangeAddressSetInNode)?;
+    let addr = AddressEncoder::unchecked_parse_network_address_from_str(&change_address_str)?;
+    Ok(addr)
+}
+
+impl TxConfirmationSource for NodeApi {
+    fn get_tx_inclusion_height(&self, tx_id: &str) -> Result<Option<u32>, NodeApiError> {
+        let response = self
+            .node
+            .send_get_req(&format!("/wallet/transactionById?id={}", tx_id))?;
+        if !response.status().is_success() {
+            // unknown to the wallet or still in the mempool
+            return Ok(None);
+        }
+        let json: serde_json::Value = serde_json::from_str(&response.text()?)?;
+        Ok(json["inclusionHeight"].as_u64().map(|h| h as u32))
+    }
+}
+
+impl RebroadcastNode for NodeApi {
+    fn is_tx_in_mempool(&self, tx_id: &str) -> Result<bool, NodeApiError> {
+        let response = self.node.send_get_req(&format!(
+            "/transactions/unconfirmed/byTransactionId/{}",
+            tx_id
+        ))?;
+        Ok(response.status().is_success())
+    }
+
+    fn is_box_unspent(&self, box_id: &str) -> Result<bool, NodeApiError> {
+        let response = self
+            .node
+            .send_get_req(&format!("/utxo/withPool/byId/{}", box_id))?;
+        Ok(response.status().is_success())
+    }
+
+    fn resubmit_transaction(&self, tx: &Transaction) -> Result<TxId, NodeApiError> {
+        Ok(self.node.submit_transaction(tx)?)
+    }
+
+    fn sign_and_submit(&self, tx: &UnsignedTransaction) -> Result<Transaction, NodeApiError> {
+        self.sign_and_submit_transaction_returning_signed(tx)
+    }
+}
+
+impl BabelBoxSource for NodeApi {
+    fn get_unspent_babel_boxes(
+        &self,
+        babel_box_ergo_tree: &str,
+    ) -> Result<Vec<ErgoBox>, BabelFeeError> {
+        self.get_unspent_boxes_by_ergo_tree(babel_box_ergo_tree)
+            .map_err(Into::into)
+    }
+}
+
+impl ReduceTransaction for NodeApi {
+    fn reduce_transaction(
+        &self,
+        unsigned_tx: &UnsignedTransaction,
+    ) -> Result<(ReducedTransaction, Vec<ErgoBox>), ColdSigningError> {
+        let inputs = unsigned_tx
+            .inputs
+            .iter()
+            .map(|input| self.get_box_by_id(&String::from(input.box_id)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let data_boxes = unsigned_tx
+            .data_inputs
+            .iter()
+            .flat_map(|data_inputs| data_inputs.iter())
+            .map(|data_input| self.get_box_by_id(&String::from(data_input.box_id)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let state_context = self.get_state_context()?;
+        let reduced_tx = reduce_with_boxes(
+            unsigned_tx.clone(),
+            inputs.clone(),
+            data_boxes,
+            &state_context,
+        )?;
+        Ok((reduced_tx, inputs))
     }
 }
 
@@ -117,4 +343,16 @@ pub enum NodeApiError {
     NoChangeAddressSetInNode,
     #[error("invalid scan id: {0}")]
     InvalidScanId(String),
+    #[error("reqwest error: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("serde error: {0}")]
+    SerdeError(#[from] serde_json::Error),
+    #[error("box {0} not found")]
+    BoxNotFound(String),
+    #[error("the node returned fewer than 10 last headers")]
+    NotEnoughHeaders,
+    #[error("no fullHeight in the node info, the node is still syncing the headers")]
+    NoFullHeight,
+    #[error("the node returned a private key that is not base16")]
+    InvalidPrivateKey,
 }
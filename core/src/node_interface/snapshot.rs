@@ -0,0 +1,112 @@
+//! Node state read once at the start of a main loop iteration. The height, the height lag and the
+//! change address come from a single `/info` and `/wallet/status` request and are the same for the
+//! whole iteration, even if a block arrives while the action is built. The wallet boxes are
+//! fetched at most once, when first needed.
+
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use once_cell::unsync::OnceCell;
+
+use crate::oracle_types::BlockHeight;
+use crate::wallet::WalletDataError;
+use crate::wallet::WalletDataSource;
+
+/// Heights of the node from its `/info`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeHeights {
+    /// Height of the last full (validated) block
+    pub full_height: BlockHeight,
+    /// Blocks the full height is behind the best height known from the headers and peers
+    pub height_lag: u32,
+}
+
+pub struct NodeSnapshot<'a> {
+    wallet: &'a dyn WalletDataSource,
+    pub height: BlockHeight,
+    pub height_lag: u32,
+    pub change_address: NetworkAddress,
+    wallet_boxes: OnceCell<Vec<ErgoBox>>,
+}
+
+impl<'a> NodeSnapshot<'a> {
+    pub fn new(
+        wallet: &'a dyn WalletDataSource,
+        heights: NodeHeights,
+        change_address: NetworkAddress,
+    ) -> Self {
+        NodeSnapshot {
+            wallet,
+            height: heights.full_height,
+            height_lag: heights.height_lag,
+            change_address,
+            wallet_boxes: OnceCell::new(),
+        }
+    }
+}
+
+impl<'a> WalletDataSource for NodeSnapshot<'a> {
+    fn get_unspent_wallet_boxes(&self) -> Result<Vec<ErgoBox>, WalletDataError> {
+        self.wallet_boxes
+            .get_or_try_init(|| self.wallet.get_unspent_wallet_boxes())
+            .cloned()
+    }
+
+    fn get_change_address(&self) -> Result<NetworkAddress, WalletDataError> {
+        Ok(self.change_address.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
+
+    use super::*;
+
+    struct CountingWallet {
+        change_address: NetworkAddress,
+        box_requests: Cell<u32>,
+        address_requests: Cell<u32>,
+    }
+
+    impl WalletDataSource for CountingWallet {
+        fn get_unspent_wallet_boxes(&self) -> Result<Vec<ErgoBox>, WalletDataError> {
+            self.box_requests.set(self.box_requests.get() + 1);
+            Ok(vec![])
+        }
+
+        fn get_change_address(&self) -> Result<NetworkAddress, WalletDataError> {
+            self.address_requests.set(self.address_requests.get() + 1);
+            Ok(self.change_address.clone())
+        }
+    }
+
+    #[test]
+    fn test_snapshot_queries_the_wallet_once() {
+        let change_address = AddressEncoder::unchecked_parse_network_address_from_str(
+            "9hEQHEMyY1K1vs79vJXFtNjr2dbQbtWXF99oVWGJ5c4xbcLdBsw",
+        )
+        .unwrap();
+        let wallet = CountingWallet {
+            change_address: change_address.clone(),
+            box_requests: Cell::new(0),
+            address_requests: Cell::new(0),
+        };
+        let snapshot = NodeSnapshot::new(
+            &wallet,
+            NodeHeights {
+                full_height: BlockHeight(1000),
+                height_lag: 0,
+            },
+            change_address.clone(),
+        );
+        for _ in 0..3 {
+            snapshot.get_unspent_wallet_boxes().unwrap();
+            assert_eq!(snapshot.get_change_address().unwrap(), change_address);
+        }
+        assert_eq!(wallet.box_requests.get(), 1);
+        assert_eq!(wallet.address_requests.get(), 0);
+        assert_eq!(snapshot.height, BlockHeight(1000));
+    }
+}
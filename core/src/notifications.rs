@@ -0,0 +1,962 @@
+//! Alerts sent to the operator on critical events.
+//!
+//! Backends are set in the `notifications` section of the (primary) oracle config. An alert of the
+//! same kind for the same pool is sent again only after `repeat_after_secs`, so a condition lasting
+//! for many main loop iterations does not flood the chat.
+
+mod discord;
+mod email;
+mod slack;
+mod telegram;
+mod webhook;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use reqwest::header::CONTENT_TYPE;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::datapoint_source::DataPointSource;
+use crate::datapoint_source::DataPointSourceError;
+use crate::datapoint_source::FetchedDatapoint;
+use crate::datapoint_source::SourceQuarantine;
+use crate::datapoint_source::SourceRate;
+use crate::ha::missed_epochs;
+use crate::oracle_config::OracleConfig;
+use crate::oracle_state::LiveEpochState;
+use crate::oracle_state::LocalDatapointState;
+use crate::oracle_types::BlockHeight;
+use crate::oracle_types::Rate;
+use crate::state::EpochParams;
+
+pub use self::discord::DiscordConfig;
+pub use self::email::EmailConfig;
+pub use self::slack::SlackConfig;
+pub use self::telegram::TelegramConfig;
+pub use self::webhook::WebhookConfig;
+
+use self::discord::DiscordBackend;
+#[cfg(feature = "email-notifications")]
+use self::email::EmailBackend;
+use self::slack::SlackBackend;
+use self::telegram::TelegramBackend;
+use self::webhook::WebhookBackend;
+
+pub const DEFAULT_REPEAT_AFTER_SECS: u64 = 3600;
+
+/// Epochs without a refresh, or without a local datapoint (unless `alert_after_missed_epochs` is
+/// set), after which an alert is sent
+pub const ALERT_AFTER_EPOCHS: u32 = 2;
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct NotificationsConfig {
+    pub telegram: Option<TelegramConfig>,
+    pub discord: Option<DiscordConfig>,
+    pub slack: Option<SlackConfig>,
+    pub email: Option<EmailConfig>,
+    /// URLs receiving every event as a JSON payload
+    pub webhooks: Option<Vec<WebhookConfig>>,
+    /// Wallet balance in nanoERG below which an alert is sent (no balance alerts if not set)
+    pub low_wallet_balance: Option<u64>,
+    /// Seconds after which an alert of the same kind for the same pool is sent again
+    pub repeat_after_secs: Option<u64>,
+    /// Consecutive epochs without a local datapoint after which an alert is sent
+    pub alert_after_missed_epochs: Option<u32>,
+    /// Spread between the highest and the lowest source rate of a datapoint, in percent of the
+    /// lowest one, above which an alert is sent (no spread alerts if not set)
+    pub max_source_spread_percent: Option<u32>,
+    /// `hold` (default) to not publish the datapoint while its sources diverge by more than
+    /// `max_source_spread_percent` or `warn` to only send the alert
+    #[serde(default)]
+    pub source_divergence_action: SourceDivergenceAction,
+    /// URL requested after every successful main loop iteration (healthchecks.io style dead man's
+    /// switch), so an external monitor raises the alarm when the pings stop
+    pub healthcheck_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceDivergenceAction {
+    #[default]
+    Hold,
+    Warn,
+}
+
+#[derive(Debug, Error)]
+pub enum NotificationError {
+    #[error("reqwest error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("{backend} rejected the notification with status {status}: {body}")]
+    Rejected {
+        backend: &'static str,
+        status: u16,
+        body: String,
+    },
+    #[cfg(feature = "email-notifications")]
+    #[error("email address error: {0}")]
+    EmailAddress(#[from] lettre::address::AddressError),
+    #[cfg(feature = "email-notifications")]
+    #[error("email error: {0}")]
+    Email(#[from] lettre::error::Error),
+    #[cfg(feature = "email-notifications")]
+    #[error("smtp error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// Events a webhook can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventCategory {
+    DatapointPosted,
+    RefreshExecuted,
+    Error,
+    Governance,
+}
+
+pub trait NotificationBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Alerts of a lower severity are not sent to the backend
+    fn min_severity(&self) -> Severity {
+        Severity::Info
+    }
+    fn send(&self, message: &str) -> Result<(), NotificationError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// The pool box could not be refreshed
+    EpochFailed {
+        reason: String,
+    },
+    LowWalletBalance {
+        balance: u64,
+        threshold: u64,
+    },
+    NodeUnreachable {
+        error: String,
+    },
+    DatapointSourceFailed {
+        error: String,
+    },
+    PoolUpdated {
+        tx_id: String,
+    },
+    /// The local oracle has not posted a datapoint for `missed_epochs` epochs
+    OracleMissedPosts {
+        missed_epochs: u32,
+    },
+    /// The pool box was not refreshed for `blocks` blocks
+    PoolStalled {
+        pool_box_height: BlockHeight,
+        blocks: u32,
+    },
+    /// The epoch is over but fewer oracles than needed for a refresh posted a datapoint
+    PoolUnderposted {
+        posted: u32,
+        min_data_points: u32,
+    },
+    /// The rates of the datapoint sources are further apart than `max_source_spread_percent`
+    SourceDivergence {
+        spread_percent: u32,
+        lowest_source: String,
+        highest_source: String,
+        held: bool,
+    },
+    /// The datapoint source is left out of the aggregated rate of `pair` for `cooldown_secs` by
+    /// the circuit breaker
+    SourceQuarantined {
+        source: String,
+        pair: String,
+        cooldown_secs: u64,
+    },
+    /// The fetched datapoint is `sigmas` standard deviations from the mean of the recent ones
+    DatapointAnomaly {
+        rate: i64,
+        mean: i64,
+        sigmas: u32,
+        refused: bool,
+    },
+    /// The main loop iteration ended with an error
+    LoopError {
+        error: String,
+    },
+    DatapointPosted {
+        tx_id: String,
+    },
+    RefreshExecuted {
+        tx_id: String,
+    },
+    /// The local ballot box voted for a pool update
+    VoteCast {
+        tx_id: String,
+    },
+    /// Ballot boxes started voting for a pool update not seen before
+    UpdateProposed {
+        pool_box_address_hash: String,
+        votes: u64,
+        min_votes: u32,
+    },
+    /// An update proposal is at most `QUORUM_MARGIN_VOTES` short of the votes needed, or has them
+    UpdateQuorumApproaching {
+        pool_box_address_hash: String,
+        votes: u64,
+        min_votes: u32,
+    },
+}
+
+impl NotificationEvent {
+    pub fn severity(&self) -> Severity {
+        match self {
+            NotificationEvent::NodeUnreachable { .. }
+            | NotificationEvent::OracleMissedPosts { .. }
+            | NotificationEvent::PoolStalled { .. }
+            | NotificationEvent::PoolUnderposted { .. } => Severity::Critical,
+            NotificationEvent::EpochFailed { .. }
+            | NotificationEvent::LowWalletBalance { .. }
+            | NotificationEvent::DatapointSourceFailed { .. }
+            | NotificationEvent::SourceDivergence { .. }
+            | NotificationEvent::SourceQuarantined { .. }
+            | NotificationEvent::DatapointAnomaly { .. }
+            | NotificationEvent::LoopError { .. }
+            | NotificationEvent::UpdateProposed { .. }
+            | NotificationEvent::UpdateQuorumApproaching { .. } => Severity::Warning,
+            NotificationEvent::PoolUpdated { .. }
+            | NotificationEvent::DatapointPosted { .. }
+            | NotificationEvent::RefreshExecuted { .. }
+            | NotificationEvent::VoteCast { .. } => Severity::Info,
+        }
+    }
+
+    pub fn category(&self) -> EventCategory {
+        match self {
+            NotificationEvent::EpochFailed { .. }
+            | NotificationEvent::LowWalletBalance { .. }
+            | NotificationEvent::NodeUnreachable { .. }
+            | NotificationEvent::DatapointSourceFailed { .. }
+            | NotificationEvent::OracleMissedPosts { .. }
+            | NotificationEvent::PoolStalled { .. }
+            | NotificationEvent::PoolUnderposted { .. }
+            | NotificationEvent::SourceDivergence { .. }
+            | NotificationEvent::SourceQuarantined { .. }
+            | NotificationEvent::DatapointAnomaly { .. }
+            | NotificationEvent::LoopError { .. } => EventCategory::Error,
+            NotificationEvent::PoolUpdated { .. }
+            | NotificationEvent::VoteCast { .. }
+            | NotificationEvent::UpdateProposed { .. }
+            | NotificationEvent::UpdateQuorumApproaching { .. } => EventCategory::Governance,
+            NotificationEvent::DatapointPosted { .. } => EventCategory::DatapointPosted,
+            NotificationEvent::RefreshExecuted { .. } => EventCategory::RefreshExecuted,
+        }
+    }
+
+    /// Alerts go to the chat and email backends and are rate limited, the other events only go
+    /// to the webhooks
+    pub fn is_alert(&self) -> bool {
+        !matches!(
+            self,
+            NotificationEvent::DatapointPosted { .. } | NotificationEvent::RefreshExecuted { .. }
+        )
+    }
+
+    /// Alerts of the same kind are rate limited together
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NotificationEvent::EpochFailed { .. } => "epoch_failed",
+            NotificationEvent::LowWalletBalance { .. } => "low_wallet_balance",
+            NotificationEvent::NodeUnreachable { .. } => "node_unreachable",
+            NotificationEvent::DatapointSourceFailed { .. } => "datapoint_source_failed",
+            NotificationEvent::PoolUpdated { .. } => "pool_updated",
+            NotificationEvent::OracleMissedPosts { .. } => "oracle_missed_posts",
+            NotificationEvent::PoolStalled { .. } => "pool_stalled",
+            NotificationEvent::PoolUnderposted { .. } => "pool_underposted",
+            NotificationEvent::SourceDivergence { .. } => "source_divergence",
+            NotificationEvent::SourceQuarantined { .. } => "source_quarantined",
+            NotificationEvent::DatapointAnomaly { .. } => "datapoint_anomaly",
+            NotificationEvent::LoopError { .. } => "loop_error",
+            NotificationEvent::DatapointPosted { .. } => "datapoint_posted",
+            NotificationEvent::RefreshExecuted { .. } => "refresh_executed",
+            NotificationEvent::VoteCast { .. } => "vote_cast",
+            NotificationEvent::UpdateProposed { .. } => "update_proposed",
+            NotificationEvent::UpdateQuorumApproaching { .. } => "update_quorum_approaching",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            NotificationEvent::EpochFailed { reason } => format!("refresh failed: {}", reason),
+            NotificationEvent::LowWalletBalance { balance, threshold } => format!(
+                "wallet balance {} nanoERG is below {} nanoERG, top it up to keep posting",
+                balance, threshold
+            ),
+            NotificationEvent::NodeUnreachable { error } => {
+                format!("node is unreachable: {}", error)
+            }
+            NotificationEvent::DatapointSourceFailed { error } => {
+                format!("failed to get a datapoint: {}", error)
+            }
+            NotificationEvent::PoolUpdated { tx_id } => {
+                format!("pool contract updated in tx {}", tx_id)
+            }
+            NotificationEvent::OracleMissedPosts { missed_epochs } => format!(
+                "the local oracle has not posted a datapoint for {} epochs",
+                missed_epochs
+            ),
+            NotificationEvent::PoolStalled {
+                pool_box_height,
+                blocks,
+            } => format!(
+                "the pool box was not refreshed for {} blocks (since height {})",
+                blocks, pool_box_height
+            ),
+            NotificationEvent::PoolUnderposted {
+                posted,
+                min_data_points,
+            } => format!(
+                "only {} oracles posted a datapoint in the epoch, {} are needed for a refresh",
+                posted, min_data_points
+            ),
+            NotificationEvent::SourceDivergence {
+                spread_percent,
+                lowest_source,
+                highest_source,
+                held,
+            } => format!(
+                "datapoint sources diverge by {}% ({} lowest, {} highest), {}",
+                spread_percent,
+                lowest_source,
+                highest_source,
+                if *held {
+                    "the datapoint is not published until they agree"
+                } else {
+                    "check them before the datapoint is trusted"
+                }
+            ),
+            NotificationEvent::SourceQuarantined {
+                source,
+                pair,
+                cooldown_secs,
+            } => format!(
+                "datapoint source {} ({}) fails too often, it is left out of the rate for {}s",
+                source, pair, cooldown_secs
+            ),
+            NotificationEvent::DatapointAnomaly {
+                rate,
+                mean,
+                sigmas,
+                refused,
+            } => format!(
+                "datapoint {} is {} standard deviations from the mean {} of the recent datapoints{}",
+                rate,
+                sigmas,
+                mean,
+                if *refused { ", not publishing it" } else { "" }
+            ),
+            NotificationEvent::LoopError { error } => format!("main loop error: {}", error),
+            NotificationEvent::DatapointPosted { tx_id } => {
+                format!("datapoint posted in tx {}", tx_id)
+            }
+            NotificationEvent::RefreshExecuted { tx_id } => {
+                format!("pool box refreshed in tx {}", tx_id)
+            }
+            NotificationEvent::VoteCast { tx_id } => {
+                format!("ballot box vote cast in tx {}", tx_id)
+            }
+            NotificationEvent::UpdateProposed {
+                pool_box_address_hash,
+                votes,
+                min_votes,
+            } => format!(
+                "new pool update proposal {} with {} of the {} votes needed, check it and vote \
+                 with vote-update-pool",
+                pool_box_address_hash, votes, min_votes
+            ),
+            NotificationEvent::UpdateQuorumApproaching {
+                pool_box_address_hash,
+                votes,
+                min_votes,
+            } => format!(
+                "pool update proposal {} has {} of the {} votes needed, the pool is updated with \
+                 update-pool once they are cast",
+                pool_box_address_hash, votes, min_votes
+            ),
+        }
+    }
+}
+
+pub struct Notifier {
+    backends: Vec<Box<dyn NotificationBackend>>,
+    webhooks: Vec<WebhookBackend>,
+    repeat_after: Duration,
+    last_sent: Mutex<HashMap<(String, &'static str), Instant>>,
+}
+
+impl Notifier {
+    pub fn new(backends: Vec<Box<dyn NotificationBackend>>, repeat_after: Duration) -> Self {
+        Notifier {
+            backends,
+            webhooks: Vec::new(),
+            repeat_after,
+            last_sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_webhooks(self, webhooks: Vec<WebhookBackend>) -> Self {
+        Notifier { webhooks, ..self }
+    }
+
+    /// Notifier of the `notifications` section of the oracle config, without backends if unset
+    pub fn from_oracle_config(oracle_config: &OracleConfig) -> Self {
+        oracle_config
+            .notifications
+            .as_ref()
+            .map(Notifier::from_config)
+            .unwrap_or_else(|| {
+                Notifier::new(Vec::new(), Duration::from_secs(DEFAULT_REPEAT_AFTER_SECS))
+            })
+    }
+
+    pub fn from_config(config: &NotificationsConfig) -> Self {
+        let mut backends: Vec<Box<dyn NotificationBackend>> = Vec::new();
+        if let Some(telegram) = &config.telegram {
+            backends.push(Box::new(TelegramBackend::new(telegram.clone())));
+        }
+        if let Some(discord) = &config.discord {
+            backends.push(Box::new(DiscordBackend::new(discord.clone())));
+        }
+        if let Some(slack) = &config.slack {
+            backends.push(Box::new(SlackBackend::new(slack.clone())));
+        }
+        if let Some(email) = &config.email {
+            #[cfg(feature = "email-notifications")]
+            backends.push(Box::new(EmailBackend::new(email.clone())));
+            #[cfg(not(feature = "email-notifications"))]
+            log::warn!(
+                "email alerts to {:?} are configured but oracle-core was built without the `email-notifications` feature",
+                email.to
+            );
+        }
+        let webhooks = config
+            .webhooks
+            .iter()
+            .flatten()
+            .cloned()
+            .map(WebhookBackend::new)
+            .collect();
+        Notifier::new(
+            backends,
+            Duration::from_secs(
+                config
+                    .repeat_after_secs
+                    .unwrap_or(DEFAULT_REPEAT_AFTER_SECS),
+            ),
+        )
+        .with_webhooks(webhooks)
+    }
+
+    /// Send the event to the backends and webhooks accepting it. An alert is dropped if one of
+    /// the same kind was sent for the pool within `repeat_after`. Returns whether it was sent.
+    pub fn notify(&self, pool_name: &str, event: &NotificationEvent) -> bool {
+        if self.backends.is_empty() && self.webhooks.is_empty() {
+            return false;
+        }
+        if event.is_alert() && self.is_repeated(pool_name, event) {
+            return false;
+        }
+        if event.is_alert() {
+            let message = format!("oracle-core, pool {}: {}", pool_name, event.message());
+            for backend in &self.backends {
+                if event.severity() < backend.min_severity() {
+                    continue;
+                }
+                if let Err(e) = backend.send(&message) {
+                    log::error!("Failed to send {} notification: {}", backend.name(), e);
+                }
+            }
+        }
+        for webhook in self.webhooks.iter().filter(|w| w.accepts(event)) {
+            if let Err(e) = webhook.send(pool_name, event) {
+                log::error!("Failed to send the event to a webhook: {}", e);
+            }
+        }
+        true
+    }
+
+    /// Whether an alert of the same kind was sent for the pool within `repeat_after`, records the
+    /// alert as sent otherwise
+    fn is_repeated(&self, pool_name: &str, event: &NotificationEvent) -> bool {
+        let key = (pool_name.to_string(), event.kind());
+        let mut last_sent = self.last_sent.lock().unwrap();
+        if let Some(sent) = last_sent.get(&key) {
+            if sent.elapsed() < self.repeat_after {
+                return true;
+            }
+        }
+        last_sent.insert(key, Instant::now());
+        false
+    }
+}
+
+impl std::fmt::Debug for Notifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Notifier")
+            .field(
+                "backends",
+                &self.backends.iter().map(|b| b.name()).collect::<Vec<_>>(),
+            )
+            .field("webhooks", &self.webhooks.len())
+            .field("repeat_after", &self.repeat_after)
+            .finish()
+    }
+}
+
+/// Ping the `healthcheck_url` of the oracle config, if set
+pub fn ping_healthcheck(oracle_config: &OracleConfig) {
+    let url = match oracle_config
+        .notifications
+        .as_ref()
+        .and_then(|n| n.healthcheck_url.as_ref())
+    {
+        Some(url) => url,
+        None => return,
+    };
+    let res = reqwest::blocking::Client::new()
+        .get(url)
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .send()
+        .and_then(|response| response.error_for_status());
+    if let Err(e) = res {
+        log::error!("Failed to ping the healthcheck URL: {}", e);
+    }
+}
+
+/// Wallet balance in nanoERG below which to alert, `None` if not configured
+pub fn low_wallet_balance_threshold(oracle_config: &OracleConfig) -> Option<u64> {
+    oracle_config
+        .notifications
+        .as_ref()
+        .and_then(|n| n.low_wallet_balance)
+}
+
+/// Source spread in percent above which to alert, `None` if not configured
+pub fn max_source_spread_percent(oracle_config: &OracleConfig) -> Option<u32> {
+    oracle_config
+        .notifications
+        .as_ref()
+        .and_then(|n| n.max_source_spread_percent)
+}
+
+impl From<&SourceQuarantine> for NotificationEvent {
+    fn from(quarantine: &SourceQuarantine) -> Self {
+        NotificationEvent::SourceQuarantined {
+            source: quarantine.source.clone(),
+            pair: quarantine.pair.clone(),
+            cooldown_secs: quarantine.cooldown.as_secs(),
+        }
+    }
+}
+
+/// What to do with a datapoint whose sources diverge
+pub fn source_divergence_action(oracle_config: &OracleConfig) -> SourceDivergenceAction {
+    oracle_config
+        .notifications
+        .as_ref()
+        .map(|n| n.source_divergence_action)
+        .unwrap_or_default()
+}
+
+/// Alert when the highest source rate is more than `max_spread_percent` above the lowest one,
+/// usually a broken exchange API or a market anomaly
+pub fn source_divergence_event(
+    sources: &[SourceRate],
+    max_spread_percent: u32,
+    held: bool,
+) -> Option<NotificationEvent> {
+    let lowest = sources.iter().min_by(|a, b| a.rate.total_cmp(&b.rate))?;
+    let highest = sources.iter().max_by(|a, b| a.rate.total_cmp(&b.rate))?;
+    if lowest.rate <= 0.0 {
+        return None;
+    }
+    let spread_percent = (highest.rate - lowest.rate) / lowest.rate * 100.0;
+    if spread_percent > max_spread_percent as f64 {
+        Some(NotificationEvent::SourceDivergence {
+            spread_percent: spread_percent as u32,
+            lowest_source: lowest.source.clone(),
+            highest_source: highest.source.clone(),
+            held,
+        })
+    } else {
+        None
+    }
+}
+
+/// Datapoint source alerting when the rates of its sources diverge, and failing the fetch so
+/// the datapoint is not published if the action is `hold`
+pub struct DivergenceCheckedSource<'a> {
+    pub source: &'a dyn DataPointSource,
+    pub max_spread_percent: u32,
+    pub action: SourceDivergenceAction,
+    pub pool_name: &'a str,
+    pub notifier: &'a Notifier,
+}
+
+impl<'a> DataPointSource for DivergenceCheckedSource<'a> {
+    fn get_datapoint(&self) -> Result<Rate, DataPointSourceError> {
+        Ok(self.fetch_datapoint()?.rate)
+    }
+
+    fn fetch_datapoint(&self) -> Result<FetchedDatapoint, DataPointSourceError> {
+        let fetched = self.source.fetch_datapoint()?;
+        let held = self.action == SourceDivergenceAction::Hold;
+        if let Some(event) =
+            source_divergence_event(&fetched.sources, self.max_spread_percent, held)
+        {
+            log::warn!("{}", event.message());
+            self.notifier.notify(self.pool_name, &event);
+            if let NotificationEvent::SourceDivergence {
+                spread_percent,
+                lowest_source,
+                highest_source,
+                held: true,
+            } = event
+            {
+                return Err(DataPointSourceError::SourceDivergence {
+                    spread_percent,
+                    lowest_source,
+                    highest_source,
+                });
+            }
+        }
+        Ok(fetched)
+    }
+}
+
+/// Consecutive epochs without a local datapoint after which to alert
+pub fn alert_after_missed_epochs(oracle_config: &OracleConfig) -> u32 {
+    oracle_config
+        .notifications
+        .as_ref()
+        .and_then(|n| n.alert_after_missed_epochs)
+        .unwrap_or(ALERT_AFTER_EPOCHS)
+}
+
+/// Alerts telling a broken local oracle (no datapoint for `alert_after_missed_epochs` epochs) from
+/// an unhealthy pool (not refreshed for `ALERT_AFTER_EPOCHS` epochs, or too few datapoints posted
+/// by the end of the epoch)
+pub fn epoch_progress_events(
+    live_epoch: &LiveEpochState,
+    epoch_params: &EpochParams,
+    height: BlockHeight,
+    alert_after_missed_epochs: u32,
+) -> Vec<NotificationEvent> {
+    let mut events = Vec::new();
+    if let Some(missed_epochs) = missed_epochs(live_epoch, epoch_params.epoch_length, height) {
+        if missed_epochs >= alert_after_missed_epochs.max(1) {
+            events.push(NotificationEvent::OracleMissedPosts { missed_epochs });
+        }
+    }
+    let epoch_length = epoch_params.epoch_length.0.max(1) as u32;
+    let blocks = height.0.saturating_sub(live_epoch.latest_pool_box_height.0);
+    if blocks >= ALERT_AFTER_EPOCHS * epoch_length {
+        events.push(NotificationEvent::PoolStalled {
+            pool_box_height: live_epoch.latest_pool_box_height,
+            blocks,
+        });
+    }
+    let local_posted = matches!(
+        live_epoch.local_datapoint_box_state,
+        Some(LocalDatapointState::Posted { epoch_id, .. }) if epoch_id == live_epoch.pool_box_epoch_id
+    );
+    let posted = live_epoch.other_posted_datapoint_heights.len() as u32 + local_posted as u32;
+    let min_data_points = epoch_params.min_data_points.0.max(0) as u32;
+    if blocks >= epoch_length && posted < min_data_points {
+        events.push(NotificationEvent::PoolUnderposted {
+            posted,
+            min_data_points,
+        });
+    }
+    events
+}
+
+/// POST the JSON body, a backend's response with a non-success status is an error
+fn post_json(
+    backend: &'static str,
+    url: &str,
+    body: &serde_json::Value,
+) -> Result<(), NotificationError> {
+    let response = reqwest::blocking::Client::new()
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .body(body.to_string())
+        .send()?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(NotificationError::Rejected {
+            backend,
+            status: status.as_u16(),
+            body: response.text().unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::oracle_types::EpochCounter;
+    use crate::oracle_types::EpochLength;
+    use crate::oracle_types::MinDatapoints;
+    use crate::pool_commands::test_utils::MockSourcesDatapointSource;
+
+    use super::*;
+
+    struct BackendMock {
+        sent: Arc<Mutex<Vec<String>>>,
+        min_severity: Severity,
+    }
+
+    impl NotificationBackend for BackendMock {
+        fn name(&self) -> &'static str {
+            "mock"
+        }
+
+        fn min_severity(&self) -> Severity {
+            self.min_severity
+        }
+
+        fn send(&self, message: &str) -> Result<(), NotificationError> {
+            self.sent.lock().unwrap().push(message.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_notify_rate_limit() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let notifier = Notifier::new(
+            vec![Box::new(BackendMock {
+                sent: sent.clone(),
+                min_severity: Severity::Info,
+            })],
+            Duration::from_secs(DEFAULT_REPEAT_AFTER_SECS),
+        );
+        let event = NotificationEvent::NodeUnreachable {
+            error: "connection refused".to_string(),
+        };
+        assert!(notifier.notify("primary", &event));
+        // the same kind for the same pool is rate limited, another pool or kind is not
+        assert!(!notifier.notify("primary", &event));
+        assert!(notifier.notify("extra", &event));
+        assert!(notifier.notify(
+            "primary",
+            &NotificationEvent::PoolUpdated {
+                tx_id: "abc".to_string()
+            }
+        ));
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(
+            sent[0],
+            "oracle-core, pool primary: node is unreachable: connection refused"
+        );
+
+        let no_backends = Notifier::new(Vec::new(), Duration::from_secs(0));
+        assert!(!no_backends.notify("primary", &event));
+    }
+
+    #[test]
+    fn test_notify_min_severity() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let notifier = Notifier::new(
+            vec![Box::new(BackendMock {
+                sent: sent.clone(),
+                min_severity: Severity::Warning,
+            })],
+            Duration::from_secs(DEFAULT_REPEAT_AFTER_SECS),
+        );
+        notifier.notify(
+            "primary",
+            &NotificationEvent::PoolUpdated {
+                tx_id: "abc".to_string(),
+            },
+        );
+        notifier.notify(
+            "primary",
+            &NotificationEvent::LowWalletBalance {
+                balance: 1,
+                threshold: 2,
+            },
+        );
+        assert_eq!(sent.lock().unwrap().len(), 1);
+        assert_eq!(
+            serde_yaml::from_str::<Severity>("critical").unwrap(),
+            Severity::Critical
+        );
+    }
+
+    #[test]
+    fn test_epoch_progress_events() {
+        let live_epoch = LiveEpochState {
+            pool_box_epoch_id: EpochCounter(5),
+            local_datapoint_box_state: Some(LocalDatapointState::Collected {
+                height: BlockHeight(100),
+            }),
+            latest_pool_datapoint: 200,
+            latest_pool_box_height: BlockHeight(100),
+            local_refresh_rank: None,
+            other_posted_datapoint_heights: vec![BlockHeight(105), BlockHeight(106)],
+        };
+        let mut epoch_params = EpochParams {
+            epoch_length: EpochLength(10),
+            buffer_length: 4,
+            min_data_points: MinDatapoints(2),
+            max_deviation_percent: 5,
+        };
+        assert!(epoch_progress_events(&live_epoch, &epoch_params, BlockHeight(115), 2).is_empty());
+        assert_eq!(
+            epoch_progress_events(&live_epoch, &epoch_params, BlockHeight(125), 2),
+            vec![
+                NotificationEvent::OracleMissedPosts { missed_epochs: 2 },
+                NotificationEvent::PoolStalled {
+                    pool_box_height: BlockHeight(100),
+                    blocks: 25
+                },
+            ]
+        );
+        // a stricter missed posts rule
+        assert_eq!(
+            epoch_progress_events(&live_epoch, &epoch_params, BlockHeight(115), 1),
+            vec![NotificationEvent::OracleMissedPosts { missed_epochs: 1 }]
+        );
+        // the epoch is over with fewer datapoints than needed
+        epoch_params.min_data_points = MinDatapoints(3);
+        assert_eq!(
+            epoch_progress_events(&live_epoch, &epoch_params, BlockHeight(115), 2),
+            vec![NotificationEvent::PoolUnderposted {
+                posted: 2,
+                min_data_points: 3
+            }]
+        );
+        // still collecting datapoints
+        assert!(epoch_progress_events(&live_epoch, &epoch_params, BlockHeight(105), 2).is_empty());
+    }
+
+    #[test]
+    fn test_source_divergence_event() {
+        let sources: Vec<SourceRate> = [("coincap", 100.0), ("coingecko", 104.0), ("other", 112.5)]
+            .into_iter()
+            .map(|(source, rate)| SourceRate {
+                source: source.to_string(),
+                rate,
+            })
+            .collect();
+        assert_eq!(
+            source_divergence_event(&sources, 10, false),
+            Some(NotificationEvent::SourceDivergence {
+                spread_percent: 12,
+                lowest_source: "coincap".to_string(),
+                highest_source: "other".to_string(),
+                held: false,
+            })
+        );
+        assert_eq!(source_divergence_event(&sources, 15, false), None);
+        assert_eq!(source_divergence_event(&sources[..1], 0, true), None);
+    }
+
+    #[test]
+    fn test_divergence_checked_source() {
+        let source = MockSourcesDatapointSource {
+            fetched: FetchedDatapoint {
+                rate: 100.into(),
+                sources: [("coincap", 100.0), ("other", 112.5)]
+                    .into_iter()
+                    .map(|(source, rate)| SourceRate {
+                        source: source.to_string(),
+                        rate,
+                    })
+                    .collect(),
+            },
+        };
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let notifier = Notifier::new(
+            vec![Box::new(BackendMock {
+                sent: sent.clone(),
+                min_severity: Severity::Info,
+            })],
+            Duration::from_secs(0),
+        );
+        let checked = |max_spread_percent, action| DivergenceCheckedSource {
+            source: &source,
+            max_spread_percent,
+            action,
+            pool_name: "pool",
+            notifier: &notifier,
+        };
+        assert!(matches!(
+            checked(10, SourceDivergenceAction::Hold).fetch_datapoint(),
+            Err(DataPointSourceError::SourceDivergence {
+                spread_percent: 12,
+                ..
+            })
+        ));
+        assert_eq!(
+            checked(10, SourceDivergenceAction::Warn)
+                .fetch_datapoint()
+                .unwrap(),
+            source.fetched
+        );
+        assert_eq!(sent.lock().unwrap().len(), 2);
+        // within the spread the datapoint is published without an alert
+        assert_eq!(
+            checked(15, SourceDivergenceAction::Hold)
+                .get_datapoint()
+                .unwrap(),
+            100.into()
+        );
+        assert_eq!(sent.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_source_quarantined_alert() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let notifier = Notifier::new(
+            vec![Box::new(BackendMock {
+                sent: sent.clone(),
+                min_severity: Severity::Warning,
+            })],
+            Duration::from_secs(3600),
+        );
+        let event = NotificationEvent::from(&SourceQuarantine {
+            source: "htx".to_string(),
+            pair: "ERG per USD".to_string(),
+            cooldown: Duration::from_secs(300),
+        });
+        assert_eq!(event.kind(), "source_quarantined");
+        assert!(notifier.notify("primary", &event));
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![
+                "oracle-core, pool primary: datapoint source htx (ERG per USD) fails too often, \
+                 it is left out of the rate for 300s"
+                    .to_string()
+            ]
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! Discord backend, alerts are posted to a channel through its webhook
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+
+use super::post_json;
+use super::NotificationBackend;
+use super::NotificationError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DiscordConfig {
+    /// Webhook URL from the channel integrations settings, or `keyring:<entry name>`
+    pub webhook_url: String,
+}
+
+pub struct DiscordBackend {
+    config: DiscordConfig,
+}
+
+impl DiscordBackend {
+    pub fn new(config: DiscordConfig) -> Self {
+        DiscordBackend { config }
+    }
+}
+
+impl NotificationBackend for DiscordBackend {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    fn send(&self, message: &str) -> Result<(), NotificationError> {
+        post_json(
+            self.name(),
+            &self.config.webhook_url,
+            &json!({ "content": message }),
+        )
+    }
+}
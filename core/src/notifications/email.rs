@@ -0,0 +1,96 @@
+//! Email backend, alerts are sent through an SMTP server over TLS. Only the config is compiled
+//! without the `email-notifications` feature.
+
+#[cfg(feature = "email-notifications")]
+use lettre::message::Mailbox;
+#[cfg(feature = "email-notifications")]
+use lettre::transport::smtp::authentication::Credentials;
+#[cfg(feature = "email-notifications")]
+use lettre::Message;
+#[cfg(feature = "email-notifications")]
+use lettre::SmtpTransport;
+#[cfg(feature = "email-notifications")]
+use lettre::Transport;
+use serde::Deserialize;
+use serde::Serialize;
+
+#[cfg(feature = "email-notifications")]
+use super::NotificationBackend;
+#[cfg(feature = "email-notifications")]
+use super::NotificationError;
+use super::Severity;
+
+#[cfg(feature = "email-notifications")]
+const EMAIL_SUBJECT: &str = "oracle-core alert";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    /// Default 587 with STARTTLS, 465 otherwise
+    pub smtp_port: Option<u16>,
+    /// Upgrade the connection with STARTTLS instead of connecting over TLS (default true)
+    pub starttls: Option<bool>,
+    pub username: String,
+    /// The password or `keyring:<entry name>`
+    pub password: String,
+    /// Sender address, e.g. `Oracle <oracle@example.com>`
+    pub from: String,
+    pub to: Vec<String>,
+    /// Alerts of a lower severity are not sent (default `info`, all alerts)
+    pub min_severity: Option<Severity>,
+}
+
+#[cfg(feature = "email-notifications")]
+pub struct EmailBackend {
+    config: EmailConfig,
+}
+
+#[cfg(feature = "email-notifications")]
+impl EmailBackend {
+    pub fn new(config: EmailConfig) -> Self {
+        EmailBackend { config }
+    }
+
+    fn transport(&self) -> Result<SmtpTransport, NotificationError> {
+        let starttls = self.config.starttls.unwrap_or(true);
+        let builder = if starttls {
+            SmtpTransport::starttls_relay(&self.config.smtp_host)?
+        } else {
+            SmtpTransport::relay(&self.config.smtp_host)?
+        };
+        let port = self
+            .config
+            .smtp_port
+            .unwrap_or(if starttls { 587 } else { 465 });
+        Ok(builder
+            .port(port)
+            .credentials(Credentials::new(
+                self.config.username.clone(),
+                self.config.password.clone(),
+            ))
+            .build())
+    }
+}
+
+#[cfg(feature = "email-notifications")]
+impl NotificationBackend for EmailBackend {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    fn min_severity(&self) -> Severity {
+        self.config.min_severity.unwrap_or(Severity::Info)
+    }
+
+    fn send(&self, message: &str) -> Result<(), NotificationError> {
+        let mut builder = Message::builder()
+            .from(self.config.from.parse::<Mailbox>()?)
+            .subject(EMAIL_SUBJECT);
+        for to in &self.config.to {
+            builder = builder.to(to.parse::<Mailbox>()?);
+        }
+        let email = builder.body(message.to_string())?;
+        self.transport()?.send(&email)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+//! Slack backend, alerts are posted to a channel through an incoming webhook
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+
+use super::post_json;
+use super::NotificationBackend;
+use super::NotificationError;
+use super::Severity;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SlackConfig {
+    /// Incoming webhook URL of the Slack app, or `keyring:<entry name>`
+    pub webhook_url: String,
+    /// Alerts of a lower severity are not posted (default `info`, all alerts)
+    pub min_severity: Option<Severity>,
+}
+
+pub struct SlackBackend {
+    config: SlackConfig,
+}
+
+impl SlackBackend {
+    pub fn new(config: SlackConfig) -> Self {
+        SlackBackend { config }
+    }
+}
+
+impl NotificationBackend for SlackBackend {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    fn min_severity(&self) -> Severity {
+        self.config.min_severity.unwrap_or(Severity::Info)
+    }
+
+    fn send(&self, message: &str) -> Result<(), NotificationError> {
+        post_json(
+            self.name(),
+            &self.config.webhook_url,
+            &json!({ "text": message }),
+        )
+    }
+}
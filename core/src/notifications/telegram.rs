@@ -0,0 +1,53 @@
+//! Telegram bot backend, alerts are posted to a chat with the Bot API `sendMessage` method
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+
+use super::post_json;
+use super::NotificationBackend;
+use super::NotificationError;
+
+const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TelegramConfig {
+    /// Token given by @BotFather on the bot creation, or `keyring:<entry name>`
+    pub bot_token: String,
+    /// Id of the chat (or `@channelusername`) the bot posts to
+    pub chat_id: String,
+}
+
+pub struct TelegramBackend {
+    config: TelegramConfig,
+}
+
+impl TelegramBackend {
+    pub fn new(config: TelegramConfig) -> Self {
+        TelegramBackend { config }
+    }
+
+    fn send_message_url(&self) -> String {
+        format!(
+            "{}/bot{}/sendMessage",
+            TELEGRAM_API_URL, self.config.bot_token
+        )
+    }
+}
+
+impl NotificationBackend for TelegramBackend {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    fn send(&self, message: &str) -> Result<(), NotificationError> {
+        post_json(
+            self.name(),
+            &self.send_message_url(),
+            &json!({
+                "chat_id": self.config.chat_id,
+                "text": message,
+            }),
+        )
+    }
+}
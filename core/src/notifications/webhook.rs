@@ -0,0 +1,85 @@
+//! Generic webhooks, every event they subscribe to is posted as a JSON payload:
+//!
+//! `{"pool": ..., "category": ..., "severity": ..., "message": ..., "event": {"kind": ..., ...}}`
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+
+use super::post_json;
+use super::EventCategory;
+use super::NotificationError;
+use super::NotificationEvent;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Categories of the events posted to the webhook (default all). Error events are alerts and
+    /// are rate limited like in the other backends.
+    pub events: Option<Vec<EventCategory>>,
+}
+
+pub struct WebhookBackend {
+    config: WebhookConfig,
+}
+
+impl WebhookBackend {
+    pub fn new(config: WebhookConfig) -> Self {
+        WebhookBackend { config }
+    }
+
+    pub fn accepts(&self, event: &NotificationEvent) -> bool {
+        self.config
+            .events
+            .as_ref()
+            .map_or(true, |events| events.contains(&event.category()))
+    }
+
+    pub fn send(
+        &self,
+        pool_name: &str,
+        event: &NotificationEvent,
+    ) -> Result<(), NotificationError> {
+        post_json("webhook", &self.config.url, &payload(pool_name, event))
+    }
+}
+
+fn payload(pool_name: &str, event: &NotificationEvent) -> serde_json::Value {
+    json!({
+        "pool": pool_name,
+        "category": event.category(),
+        "severity": event.severity(),
+        "message": event.message(),
+        "event": event,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_payload() {
+        let event = NotificationEvent::RefreshExecuted {
+            tx_id: "abc".to_string(),
+        };
+        assert_eq!(
+            payload("primary", &event),
+            json!({
+                "pool": "primary",
+                "category": "refresh_executed",
+                "severity": "info",
+                "message": "pool box refreshed in tx abc",
+                "event": {"kind": "refresh_executed", "tx_id": "abc"},
+            })
+        );
+        let webhook = WebhookBackend::new(WebhookConfig {
+            url: "http://localhost".to_string(),
+            events: Some(vec![EventCategory::Error]),
+        });
+        assert!(!webhook.accepts(&event));
+        assert!(webhook.accepts(&NotificationEvent::LoopError {
+            error: "e".to_string()
+        }));
+    }
+}
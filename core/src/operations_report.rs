@@ -0,0 +1,680 @@
+//! Weekly/monthly operations report of the oracle, built from the local history (the epoch history
+//! and the audit log), for sharing with the pool community.
+//!
+//! The posting success rate is the share of the epochs of the period in which the local datapoint
+//! was collected. Epochs missing from the history (the oracle was not running) count as missed,
+//! and consecutive missed epochs form the downtime windows. The rewards are the oracle rewards of
+//! the collected datapoints, without the collector rewards, and the fees are the miner fees of the
+//! confirmed txs. The divergence compares every posted datapoint with the rate of the next pool box.
+//!
+//! The report is written by the `report` command, and by `run` once per period when `report` is
+//! set in the oracle config.
+
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_lib::wallet::miner_fee::MINERS_FEE_ADDRESS;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::audit_log;
+use crate::audit_log::AuditLogEntry;
+use crate::audit_log::AuditLogError;
+use crate::audit_log::SubmissionResult;
+use crate::cli_commands::backtest::deviation_percent;
+use crate::epoch_history;
+use crate::epoch_history::public_key_base16;
+use crate::epoch_history::EpochHistoryEntry;
+use crate::epoch_history::EpochHistoryError;
+use crate::oracle_config::OracleConfig;
+use crate::oracle_config::OracleConfigFileError;
+use crate::pool_config::PoolConfig;
+
+/// Blocks mined in a day at the 2 minutes target block time
+pub const BLOCKS_PER_DAY: u32 = 720;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+const NANO_ERGS_PER_ERG: f64 = 1_000_000_000.0;
+
+#[derive(Debug, Error)]
+pub enum ReportError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serde error: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("epoch history error: {0}")]
+    EpochHistory(#[from] EpochHistoryError),
+    #[error("audit log error: {0}")]
+    AuditLog(#[from] AuditLogError),
+    #[error("oracle config error: {0}")]
+    OracleConfig(#[from] OracleConfigFileError),
+    #[error("invalid report period {0}, expected `week` or `month`")]
+    InvalidPeriod(String),
+    #[error("invalid report format {0}, expected `markdown` or `json`")]
+    InvalidFormat(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportPeriod {
+    /// The last 7 days
+    #[default]
+    Week,
+    /// The last 30 days
+    Month,
+}
+
+impl ReportPeriod {
+    pub fn days(self) -> u32 {
+        match self {
+            ReportPeriod::Week => 7,
+            ReportPeriod::Month => 30,
+        }
+    }
+}
+
+impl FromStr for ReportPeriod {
+    type Err = ReportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "week" => Ok(ReportPeriod::Week),
+            "month" => Ok(ReportPeriod::Month),
+            _ => Err(ReportError::InvalidPeriod(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ReportPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportPeriod::Week => write!(f, "week"),
+            ReportPeriod::Month => write!(f, "month"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = ReportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" => Ok(ReportFormat::Markdown),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(ReportError::InvalidFormat(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportFormat::Markdown => write!(f, "markdown"),
+            ReportFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Report written by `run` once per period
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ReportConfig {
+    /// `week` (default) or `month`
+    pub period: Option<ReportPeriod>,
+    /// `markdown` (default) or `json`
+    pub format: Option<ReportFormat>,
+    /// Directory the reports are written to, `reports` in the data dir of the pool if not set
+    pub dir: Option<PathBuf>,
+}
+
+/// Consecutive epochs in which the local datapoint was not collected
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DowntimeWindow {
+    pub from_epoch_id: u32,
+    pub to_epoch_id: u32,
+    pub missed_epochs: u32,
+    /// Creation height of the last pool box before the window
+    pub from_height: u32,
+    /// Creation height of the pool box collecting the local datapoint again, `None` if the window
+    /// lasts until the end of the period
+    pub to_height: Option<u32>,
+}
+
+/// Absolute deviation in percent of the posted datapoints from the rate of the next pool box
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DivergenceStats {
+    pub datapoints: usize,
+    pub mean_percent: f64,
+    pub max_percent: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OperationsReport {
+    pub pool: String,
+    pub period: ReportPeriod,
+    /// Unix time in seconds
+    pub from_timestamp: u64,
+    pub to_timestamp: u64,
+    pub from_height: u32,
+    pub to_height: u32,
+    /// Epochs of the period, the ones missing from the local history included
+    pub epochs: u32,
+    pub collected_epochs: u32,
+    /// `None` without epochs in the period
+    pub posting_success_percent: Option<f64>,
+    pub submitted_txs: usize,
+    pub confirmed_txs: usize,
+    pub failed_txs: usize,
+    /// Oracle rewards of the collected datapoints, without the collector rewards
+    pub rewards_earned: u64,
+    pub reward_unit: String,
+    /// Miner fees of the confirmed txs in nanoERG
+    pub fees_spent: u64,
+    pub divergence: Option<DivergenceStats>,
+    pub downtime_windows: Vec<DowntimeWindow>,
+}
+
+/// What the report needs to know about the oracle
+pub struct ReportParams<'a> {
+    pub pool_name: &'a str,
+    /// Public key (base16) of the oracle
+    pub oracle_public_key: &'a str,
+    /// Base58 miner fee address of the network
+    pub fee_address: &'a str,
+    pub reward_per_datapoint: u64,
+    pub reward_unit: &'a str,
+}
+
+/// Downtime windows of the epochs (oldest first), the gaps in the epoch ids are missed epochs
+fn downtime_windows(epochs: &[&EpochHistoryEntry], oracle_public_key: &str) -> Vec<DowntimeWindow> {
+    let mut windows = Vec::new();
+    let mut open: Option<DowntimeWindow> = None;
+    let mut previous: Option<&EpochHistoryEntry> = None;
+    for epoch in epochs.iter().copied() {
+        let first_missed = match previous {
+            Some(previous) if epoch.epoch_id > previous.epoch_id + 1 => {
+                Some((previous.epoch_id + 1, previous.height))
+            }
+            Some(_) | None => None,
+        };
+        let collected = epoch.oracles.iter().any(|o| o == oracle_public_key);
+        let missed = match (first_missed, collected) {
+            // the epochs missing from the history end before this one
+            (Some(start), true) => Some((start, epoch.epoch_id - 1)),
+            (Some(start), false) => Some((start, epoch.epoch_id)),
+            (None, false) => Some((
+                (epoch.epoch_id, previous.map_or(epoch.height, |p| p.height)),
+                epoch.epoch_id,
+            )),
+            (None, true) => None,
+        };
+        if let Some(((from_epoch_id, from_height), to_epoch_id)) = missed {
+            let window = open.get_or_insert(DowntimeWindow {
+                from_epoch_id,
+                to_epoch_id,
+                missed_epochs: 0,
+                from_height,
+                to_height: None,
+            });
+            window.to_epoch_id = to_epoch_id;
+            window.missed_epochs = to_epoch_id - window.from_epoch_id + 1;
+        }
+        if collected {
+            if let Some(mut window) = open.take() {
+                window.to_height = Some(epoch.height);
+                windows.push(window);
+            }
+        }
+        previous = Some(epoch);
+    }
+    windows.extend(open);
+    windows
+}
+
+fn divergence_stats(
+    audit_entries: &[&AuditLogEntry],
+    all_epochs: &[EpochHistoryEntry],
+) -> Option<DivergenceStats> {
+    let deviations: Vec<f64> = audit_entries
+        .iter()
+        .filter(|e| e.submission == SubmissionResult::Submitted)
+        .filter_map(|e| e.datapoint.as_ref())
+        .filter_map(|d| {
+            let pool_rate = all_epochs
+                .iter()
+                .filter(|e| e.height > d.height)
+                .min_by_key(|e| e.height)?
+                .rate;
+            deviation_percent(d.rate, pool_rate)
+        })
+        .map(f64::abs)
+        .collect();
+    if deviations.is_empty() {
+        return None;
+    }
+    Some(DivergenceStats {
+        datapoints: deviations.len(),
+        mean_percent: deviations.iter().sum::<f64>() / deviations.len() as f64,
+        max_percent: deviations.iter().cloned().fold(0.0, f64::max),
+    })
+}
+
+/// Report of the period ending at the given height and time. `epochs` and `audit_entries` are the
+/// whole local history, oldest first.
+pub fn operations_report(
+    params: &ReportParams,
+    period: ReportPeriod,
+    epochs: &[EpochHistoryEntry],
+    audit_entries: &[AuditLogEntry],
+    to_height: u32,
+    to_timestamp: u64,
+) -> OperationsReport {
+    let from_height = to_height.saturating_sub(period.days() * BLOCKS_PER_DAY);
+    let from_timestamp = to_timestamp.saturating_sub(period.days() as u64 * SECS_PER_DAY);
+    let period_epochs: Vec<&EpochHistoryEntry> = epochs
+        .iter()
+        .filter(|e| e.height >= from_height && e.height <= to_height)
+        .collect();
+    let period_entries: Vec<&AuditLogEntry> = audit_entries
+        .iter()
+        .filter(|e| e.timestamp >= from_timestamp && e.timestamp <= to_timestamp)
+        .collect();
+    let epoch_count = match (period_epochs.first(), period_epochs.last()) {
+        (Some(first), Some(last)) => match last.epoch_id.checked_sub(first.epoch_id) {
+            Some(span) => span + 1,
+            // the epoch counter was reset by a pool update
+            None => period_epochs.len() as u32,
+        },
+        (Some(_), None) | (None, _) => 0,
+    };
+    let collected_epochs = period_epochs
+        .iter()
+        .filter(|e| e.oracles.iter().any(|o| o == params.oracle_public_key))
+        .count() as u32;
+    let confirmed: Vec<&&AuditLogEntry> = period_entries
+        .iter()
+        .filter(|e| e.submission == SubmissionResult::Submitted && e.confirmation_height.is_some())
+        .collect();
+    OperationsReport {
+        pool: params.pool_name.to_string(),
+        period,
+        from_timestamp,
+        to_timestamp,
+        from_height,
+        to_height,
+        epochs: epoch_count,
+        collected_epochs,
+        posting_success_percent: if epoch_count == 0 {
+            None
+        } else {
+            Some(collected_epochs as f64 * 100.0 / epoch_count as f64)
+        },
+        submitted_txs: period_entries
+            .iter()
+            .filter(|e| e.submission == SubmissionResult::Submitted)
+            .count(),
+        confirmed_txs: confirmed.len(),
+        failed_txs: period_entries
+            .iter()
+            .filter(|e| matches!(e.submission, SubmissionResult::Failed { .. }))
+            .count(),
+        rewards_earned: collected_epochs as u64 * params.reward_per_datapoint,
+        reward_unit: params.reward_unit.to_string(),
+        fees_spent: confirmed
+            .iter()
+            .flat_map(|e| e.outputs.iter())
+            .filter(|o| o.address == params.fee_address)
+            .map(|o| o.value)
+            .sum(),
+        divergence: divergence_stats(&period_entries, epochs),
+        downtime_windows: downtime_windows(&period_epochs, params.oracle_public_key),
+    }
+}
+
+/// Report of the pool over the period ending at the given height, read from its local history
+pub fn build_report(
+    pool_name: &str,
+    pool_config: &PoolConfig,
+    oracle_config: &OracleConfig,
+    epoch_history_path: &Path,
+    audit_log_path: &Path,
+    period: ReportPeriod,
+    height: u32,
+) -> Result<OperationsReport, ReportError> {
+    let oracle_public_key = public_key_base16(&oracle_config.oracle_address_p2pk()?.h);
+    let fee_address =
+        NetworkAddress::new(oracle_config.oracle_address.network(), &MINERS_FEE_ADDRESS)
+            .to_base58();
+    let (reward_per_datapoint, reward_unit) = match &pool_config.erg_rewards {
+        Some(erg_rewards) => (erg_rewards.oracle_reward_per_datapoint, "nanoERG"),
+        None => (
+            pool_config
+                .refresh_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .oracle_reward_tokens_per_datapoint(),
+            "reward tokens",
+        ),
+    };
+    let params = ReportParams {
+        pool_name,
+        oracle_public_key: &oracle_public_key,
+        fee_address: &fee_address,
+        reward_per_datapoint,
+        reward_unit,
+    };
+    Ok(operations_report(
+        &params,
+        period,
+        &epoch_history::read_entries(epoch_history_path)?,
+        &audit_log::read_entries(audit_log_path)?,
+        height,
+        audit_log::unix_time_secs(),
+    ))
+}
+
+impl OperationsReport {
+    pub fn render(&self, format: ReportFormat) -> Result<String, ReportError> {
+        Ok(match format {
+            ReportFormat::Markdown => self.to_string(),
+            ReportFormat::Json => serde_json::to_string_pretty(self)? + "\n",
+        })
+    }
+}
+
+impl fmt::Display for OperationsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# Oracle operations report: pool {}", self.pool)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Last {} days, heights {} to {}",
+            self.period.days(),
+            self.from_height,
+            self.to_height
+        )?;
+        writeln!(f)?;
+        writeln!(f, "| | |")?;
+        writeln!(f, "|---|---|")?;
+        writeln!(f, "| Epochs | {} |", self.epochs)?;
+        let success = self
+            .posting_success_percent
+            .map_or("-".to_string(), |p| format!("{:.1}%", p));
+        writeln!(
+            f,
+            "| Datapoints collected | {} ({}) |",
+            self.collected_epochs, success
+        )?;
+        writeln!(
+            f,
+            "| Txs submitted | {} ({} confirmed, {} failed to submit) |",
+            self.submitted_txs, self.confirmed_txs, self.failed_txs
+        )?;
+        writeln!(
+            f,
+            "| Rewards earned | {} {} (without collector rewards) |",
+            self.rewards_earned, self.reward_unit
+        )?;
+        writeln!(
+            f,
+            "| Fees spent | {:.4} ERG |",
+            self.fees_spent as f64 / NANO_ERGS_PER_ERG
+        )?;
+        match &self.divergence {
+            Some(divergence) => writeln!(
+                f,
+                "| Divergence from the pool rate | mean {:.3}%, max {:.3}% ({} datapoints) |",
+                divergence.mean_percent, divergence.max_percent, divergence.datapoints
+            )?,
+            None => writeln!(f, "| Divergence from the pool rate | - |")?,
+        }
+        writeln!(f)?;
+        writeln!(f, "## Downtime windows")?;
+        writeln!(f)?;
+        if self.downtime_windows.is_empty() {
+            return writeln!(f, "No epoch was missed.");
+        }
+        writeln!(f, "| Epochs | Missed epochs | From height | To height |")?;
+        writeln!(f, "|---|---|---|---|")?;
+        for window in &self.downtime_windows {
+            writeln!(
+                f,
+                "| {}-{} | {} | {} | {} |",
+                window.from_epoch_id,
+                window.to_epoch_id,
+                window.missed_epochs,
+                window.from_height,
+                window.to_height.map_or("-".to_string(), |h| h.to_string())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a report of the pool once per period, in `run`
+pub struct ReportScheduler {
+    pool_name: String,
+    period: ReportPeriod,
+    format: ReportFormat,
+    dir: PathBuf,
+    /// Time of the last written report, read back from the file names on start
+    last_report_timestamp: Option<u64>,
+}
+
+impl ReportScheduler {
+    pub fn new(config: &ReportConfig, default_dir: PathBuf, pool_name: &str) -> Self {
+        let dir = config.dir.clone().unwrap_or(default_dir);
+        let prefix = report_file_prefix(pool_name);
+        let last_report_timestamp = std::fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter_map(|name| name.strip_prefix(&prefix)?.split('.').next()?.parse().ok())
+            .max();
+        ReportScheduler {
+            pool_name: pool_name.to_string(),
+            period: config.period.unwrap_or_default(),
+            format: config.format.unwrap_or_default(),
+            dir,
+            last_report_timestamp,
+        }
+    }
+
+    pub fn is_due(&self, now: u64) -> bool {
+        self.last_report_timestamp.map_or(true, |last| {
+            now >= last + self.period.days() as u64 * SECS_PER_DAY
+        })
+    }
+
+    /// Write the report built by `build` if a period passed since the last one
+    pub fn write_if_due(
+        &mut self,
+        now: u64,
+        build: impl FnOnce(ReportPeriod) -> Result<OperationsReport, ReportError>,
+    ) -> Result<Option<PathBuf>, ReportError> {
+        if !self.is_due(now) {
+            return Ok(None);
+        }
+        let report = build(self.period)?;
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!(
+            "{}{}.{}",
+            report_file_prefix(&self.pool_name),
+            now,
+            self.format.extension()
+        ));
+        std::fs::write(&path, report.render(self.format)?)?;
+        self.last_report_timestamp = Some(now);
+        Ok(Some(path))
+    }
+}
+
+fn report_file_prefix(pool_name: &str) -> String {
+    format!("{}-report-", pool_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit_log::AuditLogDatapoint;
+    use crate::audit_log::AuditLogOutput;
+
+    use super::*;
+
+    const LOCAL: &str = "ab";
+    const FEE_ADDRESS: &str = "fee";
+
+    fn epoch(epoch_id: u32, rate: i64, oracles: &[&str]) -> EpochHistoryEntry {
+        EpochHistoryEntry {
+            epoch_id,
+            rate,
+            height: 1000 + epoch_id * 30,
+            pool_box_id: String::new(),
+            oracles: oracles.iter().map(|o| o.to_string()).collect(),
+        }
+    }
+
+    fn entry(timestamp: u64, submission: SubmissionResult, rate: Option<i64>) -> AuditLogEntry {
+        AuditLogEntry {
+            timestamp,
+            action: "publish_datapoint".to_string(),
+            tx_id: String::new(),
+            input_box_ids: vec![],
+            outputs: vec![AuditLogOutput {
+                address: FEE_ADDRESS.to_string(),
+                value: 1_000_000,
+                tokens: vec![],
+            }],
+            submission,
+            confirmation_height: Some(1100),
+            datapoint: rate.map(|rate| AuditLogDatapoint {
+                height: 1100,
+                rate,
+                sources: vec![],
+            }),
+        }
+    }
+
+    fn params() -> ReportParams<'static> {
+        ReportParams {
+            pool_name: "primary",
+            oracle_public_key: LOCAL,
+            fee_address: FEE_ADDRESS,
+            reward_per_datapoint: 1,
+            reward_unit: "reward tokens",
+        }
+    }
+
+    #[test]
+    fn test_operations_report() {
+        // epochs 5 and 6 are missing from the history
+        let epochs = vec![
+            epoch(1, 200, &[LOCAL, "cd"]),
+            epoch(2, 200, &["cd"]),
+            epoch(3, 200, &[LOCAL]),
+            epoch(4, 200, &[LOCAL]),
+            epoch(7, 210, &["cd"]),
+            epoch(8, 220, &[LOCAL]),
+        ];
+        let now = 10 * SECS_PER_DAY;
+        let audit_entries = vec![
+            // before the period
+            entry(now - 8 * SECS_PER_DAY, SubmissionResult::Submitted, Some(1)),
+            entry(now - 100, SubmissionResult::Submitted, Some(204)),
+            entry(
+                now - 50,
+                SubmissionResult::Failed {
+                    error: "double spend".to_string(),
+                },
+                None,
+            ),
+        ];
+        let report = operations_report(
+            &params(),
+            ReportPeriod::Week,
+            &epochs,
+            &audit_entries,
+            1300,
+            now,
+        );
+        assert_eq!(report.epochs, 8);
+        assert_eq!(report.collected_epochs, 4);
+        assert_eq!(report.posting_success_percent, Some(50.0));
+        assert_eq!(report.rewards_earned, 4);
+        assert_eq!((report.submitted_txs, report.failed_txs), (1, 1));
+        assert_eq!(report.fees_spent, 1_000_000);
+        // the datapoint built at height 1100 is compared with the rate of epoch 4 (height 1120)
+        assert_eq!(report.divergence.as_ref().unwrap().max_percent, 2.0);
+        assert_eq!(
+            report.downtime_windows,
+            vec![
+                DowntimeWindow {
+                    from_epoch_id: 2,
+                    to_epoch_id: 2,
+                    missed_epochs: 1,
+                    from_height: 1030,
+                    to_height: Some(1090),
+                },
+                DowntimeWindow {
+                    from_epoch_id: 5,
+                    to_epoch_id: 7,
+                    missed_epochs: 3,
+                    from_height: 1120,
+                    to_height: Some(1240),
+                },
+            ]
+        );
+        let markdown = report.render(ReportFormat::Markdown).unwrap();
+        assert!(markdown.contains("| Datapoints collected | 4 (50.0%) |"));
+        assert!(markdown.contains("| 5-7 | 3 | 1120 | 1240 |"));
+        let json: serde_json::Value =
+            serde_json::from_str(&report.render(ReportFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["period"], "week");
+        assert_eq!(json["downtime_windows"][1]["missed_epochs"], 3);
+    }
+
+    #[test]
+    fn test_report_scheduler() {
+        let dir = std::env::temp_dir().join(format!("oracle-core-reports-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = ReportConfig {
+            dir: Some(dir.clone()),
+            ..ReportConfig::default()
+        };
+        let build = |period| Ok(operations_report(&params(), period, &[], &[], 1000, 0));
+        let mut scheduler = ReportScheduler::new(&config, PathBuf::new(), "primary");
+        let now = 100 * SECS_PER_DAY;
+        let path = scheduler.write_if_due(now, build).unwrap().unwrap();
+        assert!(path.ends_with(format!("primary-report-{}.md", now)));
+        assert!(scheduler.write_if_due(now + 1, build).unwrap().is_none());
+
+        // the last report time is read back from the written files
+        let mut restarted = ReportScheduler::new(&config, PathBuf::new(), "primary");
+        assert!(!restarted.is_due(now + SECS_PER_DAY));
+        assert!(restarted
+            .write_if_due(now + 7 * SECS_PER_DAY, build)
+            .unwrap()
+            .is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
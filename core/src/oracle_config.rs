@@ -21,20 +21,107 @@ use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::babel_fee::BabelFeeConfig;
+use crate::datapoint_anomaly::DatapointAnomalyConfig;
+use crate::datapoint_source::CircuitBreakerConfig;
+use crate::datapoint_source::StablecoinCheckConfig;
+use crate::error_reporting::SentryConfig;
+use crate::event_stream::EventStreamConfig;
+use crate::evm_relay::EvmRelayConfig;
 use crate::explorer_api::explorer_url::default_explorer_api_url;
+use crate::ha::HaConfig;
+use crate::history_db::HistoryDbConfig;
+use crate::ipfs::IpfsConfig;
+use crate::logging::LogFormat;
+use crate::mqtt::MqttConfig;
+use crate::notifications::NotificationsConfig;
+use crate::operations_report::ReportConfig;
+use crate::posting_policy::PostingPolicyConfig;
+use crate::rebroadcast::FeeBumpConfig;
+use crate::rebroadcast::DEFAULT_REBROADCAST_AFTER_BLOCKS;
+use crate::redact::register_secret;
+use crate::secrets::resolve_secret;
+use crate::secrets::OsKeyring;
+use crate::secrets::SecretStore;
+use crate::state::DEFAULT_REFRESH_DELAY_BLOCKS_PER_RANK;
+use crate::telemetry::TelemetryConfig;
 
 pub const DEFAULT_ORACLE_CONFIG_FILE_NAME: &str = "oracle_config.yaml";
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OracleConfig {
     pub node_url: Url,
+    /// The key or `keyring:<entry name>` of an OS keyring entry holding it
     pub node_api_key: String,
     pub base_fee: u64,
     pub log_level: Option<LevelFilter>,
+    /// `text` (default) or `json` for log lines that can be ingested without regex parsing
+    pub log_format: Option<LogFormat>,
     pub core_api_port: u16,
     pub oracle_address: NetworkAddress,
     pub data_point_source_custom_script: Option<String>,
     pub explorer_url: Option<Url>,
+    /// Blocks to wait per oracle rank before submitting a refresh (0 disables the delay)
+    pub refresh_delay_blocks_per_rank: Option<u32>,
+    /// Maximum random delay in seconds before submitting a publish/refresh tx (0 disables it)
+    pub submit_jitter_max_secs: Option<u64>,
+    /// Active/standby setup with another instance using the same oracle token
+    pub ha: Option<HaConfig>,
+    /// Tx fees per action, `base_fee` is used for the ones not set
+    pub fees: Option<ActionFees>,
+    /// Buy the ERG missing for a tx from babel fee boxes with tokens
+    pub babel_fee: Option<BabelFeeConfig>,
+    /// Minimum value in nanoERG of the boxes built by the oracle, including datapoint boxes whose
+    /// value is otherwise set by the contract `min_storage_rent`
+    pub min_box_value: Option<u64>,
+    /// Blocks after which a submitted publish/refresh tx that is neither confirmed nor in the
+    /// mempool is re-broadcast (0 disables it)
+    pub rebroadcast_after_blocks: Option<u32>,
+    /// Raise the fee of publish/refresh txs stuck in the mempool
+    pub fee_bump: Option<FeeBumpConfig>,
+    /// Query the explorer for datapoint boxes when the node scan returns fewer than needed for
+    /// a refresh
+    pub explorer_datapoint_fallback: Option<bool>,
+    /// Alerts on critical events
+    pub notifications: Option<NotificationsConfig>,
+    /// Export of the main loop tracing spans to an OpenTelemetry collector
+    pub telemetry: Option<TelemetryConfig>,
+    /// Report panics and errors to Sentry
+    pub sentry: Option<SentryConfig>,
+    /// Copy of the epoch history, audit log and loop health samples in a PostgreSQL database
+    pub history_db: Option<HistoryDbConfig>,
+    /// Relay of the pool rate to a contract on an EVM chain
+    pub evm_relay: Option<EvmRelayConfig>,
+    /// Publishing of the fetched datapoints and the pool refreshes to an MQTT broker
+    pub mqtt: Option<MqttConfig>,
+    /// Stream of the main loop events to Kafka or NATS
+    pub event_stream: Option<EventStreamConfig>,
+    /// Sign the fetched datapoints with the oracle key, read from the node wallet (default false)
+    pub datapoint_attestations: Option<bool>,
+    /// Pin the datapoint attestations to IPFS
+    pub ipfs: Option<IpfsConfig>,
+    /// Seconds a datapoint fetched by a pool is reused by the other pools of the process with the
+    /// same datapoint source (default not shared)
+    pub share_datapoint_fetch_secs: Option<u64>,
+    /// When to publish a datapoint in an epoch (default in every epoch)
+    pub posting_policy: Option<PostingPolicyConfig>,
+    /// Report (or refuse to publish) the datapoints far from the recent ones
+    pub datapoint_anomaly: Option<DatapointAnomalyConfig>,
+    /// Check of the exchange rates quoted in USDT against the USD price of USDT
+    pub stablecoin_check: Option<StablecoinCheckConfig>,
+    /// Quarantine of the datapoint sources failing too often (default none quarantined)
+    pub source_circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Operations report written by `run` once per period
+    pub report: Option<ReportConfig>,
+}
+
+/// Tx fees in nanoERG for the particular actions
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ActionFees {
+    pub publish: Option<u64>,
+    pub refresh: Option<u64>,
+    pub extract: Option<u64>,
+    pub update: Option<u64>,
 }
 
 impl OracleConfig {
@@ -45,14 +132,25 @@ impl OracleConfig {
         file.write_all(yaml_str.as_bytes()).unwrap();
     }
 
-    fn load() -> Result<Self, OracleConfigFileError> {
+    /// Load the config from `ORACLE_CONFIG_FILE_PATH`
+    pub fn load() -> Result<Self, OracleConfigFileError> {
         let config_file_path = ORACLE_CONFIG_FILE_PATH.get().ok_or_else(|| {
             OracleConfigFileError::IoError("ORACLE_CONFIG_FILE_PATH not set".to_string())
         })?;
+        Self::load_from_path(config_file_path)
+    }
+
+    pub fn load_from_path(config_file_path: &Path) -> Result<Self, OracleConfigFileError> {
         let config_str: &str = &std::fs::read_to_string(config_file_path)
             .map_err(|e| OracleConfigFileError::IoError(e.to_string()))?;
-        let config = Self::load_from_str(config_str)?;
+        let mut config = Self::load_from_str(config_str)?;
+        config.resolve_secrets(&OsKeyring)?;
+        config.secrets().into_iter().for_each(register_secret);
         let _ = config.oracle_address_p2pk()?;
+        if let Some(min_box_value) = config.min_box_value {
+            BoxValue::try_from(min_box_value)
+                .map_err(|e| OracleConfigFileError::InvalidMinBoxValue(e.to_string()))?;
+        }
         Ok(config)
     }
 
@@ -61,6 +159,98 @@ impl OracleConfig {
             .map_err(|e| OracleConfigFileError::ParseError(e.to_string()))
     }
 
+    /// Replaces the `keyring:<entry name>` references of the secret fields with the secrets
+    pub fn resolve_secrets(
+        &mut self,
+        store: &dyn SecretStore,
+    ) -> Result<(), OracleConfigFileError> {
+        let resolve = |value: &mut String| {
+            resolve_secret(value, store)
+                .map_err(|e| OracleConfigFileError::SecretError(e.to_string()))
+        };
+        resolve(&mut self.node_api_key)?;
+        if let Some(notifications) = self.notifications.as_mut() {
+            if let Some(telegram) = notifications.telegram.as_mut() {
+                resolve(&mut telegram.bot_token)?;
+            }
+            if let Some(discord) = notifications.discord.as_mut() {
+                resolve(&mut discord.webhook_url)?;
+            }
+            if let Some(slack) = notifications.slack.as_mut() {
+                resolve(&mut slack.webhook_url)?;
+            }
+            if let Some(email) = notifications.email.as_mut() {
+                resolve(&mut email.password)?;
+            }
+        }
+        if let Some(password) = self.mqtt.as_mut().and_then(|mqtt| mqtt.password.as_mut()) {
+            resolve(password)?;
+        }
+        if let Some(token) = self
+            .event_stream
+            .as_mut()
+            .and_then(|event_stream| event_stream.nats.as_mut())
+            .and_then(|nats| nats.token.as_mut())
+        {
+            resolve(token)?;
+        }
+        if let Some(auth_header) = self
+            .ipfs
+            .as_mut()
+            .and_then(|ipfs| ipfs.auth_header.as_mut())
+        {
+            resolve(auth_header)?;
+        }
+        Ok(())
+    }
+
+    /// Values of the secret fields, masked in the log output
+    pub fn secrets(&self) -> Vec<&str> {
+        let mut secrets = vec![self.node_api_key.as_str()];
+        if let Some(notifications) = self.notifications.as_ref() {
+            secrets.extend(
+                notifications
+                    .telegram
+                    .as_ref()
+                    .map(|telegram| telegram.bot_token.as_str()),
+            );
+            secrets.extend(
+                notifications
+                    .discord
+                    .as_ref()
+                    .map(|discord| discord.webhook_url.as_str()),
+            );
+            secrets.extend(
+                notifications
+                    .slack
+                    .as_ref()
+                    .map(|slack| slack.webhook_url.as_str()),
+            );
+            secrets.extend(
+                notifications
+                    .email
+                    .as_ref()
+                    .map(|email| email.password.as_str()),
+            );
+        }
+        if let Some(history_db) = self.history_db.as_ref() {
+            secrets.push(history_db.connection_string.as_str());
+        }
+        secrets.extend(self.mqtt.as_ref().and_then(|mqtt| mqtt.password.as_deref()));
+        secrets.extend(
+            self.event_stream
+                .as_ref()
+                .and_then(|event_stream| event_stream.nats.as_ref())
+                .and_then(|nats| nats.token.as_deref()),
+        );
+        secrets.extend(
+            self.ipfs
+                .as_ref()
+                .and_then(|ipfs| ipfs.auth_header.as_deref()),
+        );
+        secrets
+    }
+
     pub fn save(&self, path: &Path) -> Result<(), OracleConfigFileError> {
         let yaml_str = serde_yaml::to_string(self).unwrap();
         let mut file = std::fs::File::create(path).unwrap();
@@ -68,6 +258,50 @@ impl OracleConfig {
         Ok(())
     }
 
+    pub fn base_fee(&self) -> BoxValue {
+        BoxValue::try_from(self.base_fee).unwrap()
+    }
+
+    /// `min_box_value` or `BoxValue::SAFE_USER_MIN` if not set
+    pub fn min_box_value(&self) -> BoxValue {
+        self.min_box_value
+            .map(|value| BoxValue::try_from(value).unwrap())
+            .unwrap_or(BoxValue::SAFE_USER_MIN)
+    }
+
+    pub fn publish_fee(&self) -> BoxValue {
+        self.action_fee(|f| f.publish)
+    }
+
+    pub fn refresh_fee(&self) -> BoxValue {
+        self.action_fee(|f| f.refresh)
+    }
+
+    pub fn extract_fee(&self) -> BoxValue {
+        self.action_fee(|f| f.extract)
+    }
+
+    pub fn update_fee(&self) -> BoxValue {
+        self.action_fee(|f| f.update)
+    }
+
+    /// Fee configured for the action in `fees`, or `base_fee` if not set
+    fn action_fee(&self, select: fn(&ActionFees) -> Option<u64>) -> BoxValue {
+        self.fees
+            .as_ref()
+            .and_then(select)
+            .map(|fee| BoxValue::try_from(fee).unwrap())
+            .unwrap_or_else(|| self.base_fee())
+    }
+
+    /// The given fee with the configured min box value
+    pub fn tx_params(&self, fee: BoxValue) -> TxParams {
+        TxParams {
+            fee,
+            min_box_value: self.min_box_value(),
+        }
+    }
+
     pub fn oracle_address_p2pk(&self) -> Result<ProveDlog, OracleConfigFileError> {
         if let Address::P2Pk(public_key) = self.oracle_address.address() {
             Ok(public_key.clone())
@@ -85,6 +319,10 @@ pub enum OracleConfigFileError {
     ParseError(String),
     #[error("Invalid oracle address, must be P2PK")]
     InvalidOracleAddress,
+    #[error("Invalid min_box_value: {0}")]
+    InvalidMinBoxValue(String),
+    #[error("Error reading secret: {0}")]
+    SecretError(String),
 }
 
 impl Default for OracleConfig {
@@ -100,24 +338,114 @@ impl Default for OracleConfig {
             data_point_source_custom_script: None,
             base_fee: *tx_builder::SUGGESTED_TX_FEE().as_u64(),
             log_level: LevelFilter::Info.into(),
+            log_format: Some(LogFormat::Text),
             node_url: Url::parse("http://127.0.0.1:9053").unwrap(),
             explorer_url: Some(default_explorer_api_url(address.network())),
+            refresh_delay_blocks_per_rank: Some(DEFAULT_REFRESH_DELAY_BLOCKS_PER_RANK),
+            submit_jitter_max_secs: Some(10),
+            ha: None,
+            fees: None,
+            babel_fee: None,
+            min_box_value: Some(*BoxValue::SAFE_USER_MIN.as_u64()),
+            rebroadcast_after_blocks: Some(DEFAULT_REBROADCAST_AFTER_BLOCKS),
+            fee_bump: None,
+            explorer_datapoint_fallback: Some(false),
+            notifications: None,
+            telemetry: None,
+            sentry: None,
+            history_db: None,
+            evm_relay: None,
+            mqtt: None,
+            event_stream: None,
+            datapoint_attestations: None,
+            ipfs: None,
+            share_datapoint_fetch_secs: None,
+            posting_policy: None,
+            datapoint_anomaly: None,
+            stablecoin_check: None,
+            source_circuit_breaker: None,
+            report: None,
         }
     }
 }
 
 pub static ORACLE_CONFIG_FILE_PATH: sync::OnceCell<PathBuf> = sync::OnceCell::new();
 lazy_static! {
-    pub static ref ORACLE_CONFIG: OracleConfig = OracleConfig::load().unwrap();
+    /// Only for setting up the logging and the notifications before the commands run, the
+    /// commands get the config passed in an `OracleContext`
     pub static ref ORACLE_CONFIG_OPT: Result<OracleConfig, OracleConfigFileError> =
         OracleConfig::load();
-    pub static ref BASE_FEE: BoxValue = ORACLE_CONFIG_OPT
-        .as_ref()
-        .map(|c| BoxValue::try_from(c.base_fee).unwrap())
-        .unwrap_or_else(|_| SUGGESTED_TX_FEE());
 }
 
-/// Returns "core_api_port" from the config file
-pub fn get_core_api_port() -> String {
-    ORACLE_CONFIG.core_api_port.to_string()
+/// Fee and min box value the tx builders use, so the txs can be built without a config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxParams {
+    pub fee: BoxValue,
+    /// Lower bound of the value of the created boxes
+    pub min_box_value: BoxValue,
+}
+
+impl Default for TxParams {
+    /// The suggested tx fee and the safe user min box value
+    fn default() -> Self {
+        TxParams {
+            fee: SUGGESTED_TX_FEE(),
+            min_box_value: BoxValue::SAFE_USER_MIN,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_fees_fall_back_to_base_fee() {
+        let config = OracleConfig {
+            base_fee: 2_000_000,
+            fees: Some(ActionFees {
+                refresh: Some(5_000_000),
+                ..ActionFees::default()
+            }),
+            min_box_value: None,
+            ..OracleConfig::default()
+        };
+        assert_eq!(*config.publish_fee().as_u64(), 2_000_000);
+        assert_eq!(*config.refresh_fee().as_u64(), 5_000_000);
+        assert_eq!(
+            config.tx_params(config.refresh_fee()),
+            TxParams {
+                fee: config.refresh_fee(),
+                min_box_value: BoxValue::SAFE_USER_MIN,
+            }
+        );
+    }
+
+    struct TestStore;
+
+    impl SecretStore for TestStore {
+        fn get_secret(&self, entry: &str) -> Result<String, crate::secrets::SecretsError> {
+            Ok(format!("secret of {}", entry))
+        }
+    }
+
+    #[test]
+    fn test_resolve_keyring_secrets() {
+        let mut config = OracleConfig {
+            node_api_key: "keyring:node-api-key".to_string(),
+            notifications: Some(NotificationsConfig {
+                telegram: Some(crate::notifications::TelegramConfig {
+                    bot_token: "keyring:telegram".to_string(),
+                    chat_id: "@oracle".to_string(),
+                }),
+                ..NotificationsConfig::default()
+            }),
+            ..OracleConfig::default()
+        };
+        config.resolve_secrets(&TestStore).unwrap();
+        assert_eq!(config.node_api_key, "secret of node-api-key");
+        let telegram = config.notifications.unwrap().telegram.unwrap();
+        assert_eq!(telegram.bot_token, "secret of telegram");
+        assert_eq!(telegram.chat_id, "@oracle");
+    }
 }
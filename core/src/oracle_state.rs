@@ -1,21 +1,27 @@
 use crate::box_kind::{
     BallotBox, BallotBoxError, BallotBoxWrapper, BallotBoxWrapperInputs, BuybackBoxError,
-    BuybackBoxWrapper, CollectedOracleBox, OracleBox, OracleBoxError, OracleBoxWrapper,
-    OracleBoxWrapperInputs, PoolBox, PoolBoxError, PoolBoxWrapper, PoolBoxWrapperInputs,
-    PostedOracleBox, RefreshBoxError, RefreshBoxWrapper, RefreshBoxWrapperInputs, UpdateBoxError,
-    UpdateBoxWrapper, UpdateBoxWrapperInputs, VoteBallotBoxWrapper,
+    BuybackBoxWrapper, CastBallotBoxVoteParameters, CollectedOracleBox, OracleBox, OracleBoxError,
+    OracleBoxWrapper, OracleBoxWrapperInputs, PoolBox, PoolBoxError, PoolBoxWrapper,
+    PoolBoxWrapperInputs, PostedOracleBox, RefreshBoxError, RefreshBoxWrapper,
+    RefreshBoxWrapperInputs, UpdateBoxError, UpdateBoxWrapper, UpdateBoxWrapperInputs,
+    VoteBallotBoxWrapper,
 };
+use crate::context::OracleContext;
 use crate::datapoint_source::DataPointSourceError;
-use crate::oracle_config::ORACLE_CONFIG;
 use crate::oracle_types::{BlockHeight, EpochCounter};
-use crate::pool_config::POOL_CONFIG;
-use crate::scans::{GenericTokenScan, NodeScanRegistry, ScanError, ScanGetBoxes};
+use crate::scans::{GenericTokenScan, NodeScanRegistry, ScanError, ScanGetBoxes, ScanNode};
 use crate::spec_token::{
     BallotTokenId, BuybackTokenId, OracleTokenId, PoolTokenId, RefreshTokenId, RewardTokenId,
     UpdateTokenId,
 };
 use anyhow::Error;
+use once_cell::unsync::OnceCell;
 
+use ergo_lib::ergo_chain_types::EcPoint;
+use ergo_lib::ergotree_ir::chain::address::Address;
+use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
 use ergo_lib::ergotree_ir::mir::constant::TryExtractFromError;
 use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
 use thiserror::Error;
@@ -48,6 +54,8 @@ pub enum DataSourceError {
     UpdateBoxNotFoundError,
     #[error("buyback box error: {0}")]
     BuybackBoxError(#[from] BuybackBoxError),
+    #[error("fetch of the {0} panicked")]
+    FetchPanicked(String),
 }
 
 pub trait PoolBoxSource {
@@ -103,6 +111,7 @@ pub struct OraclePool {
 pub struct OracleDatapointScan {
     scan: GenericTokenScan<OracleTokenId>,
     oracle_box_wrapper_inputs: OracleBoxWrapperInputs,
+    node: ScanNode,
 }
 
 #[derive(Debug)]
@@ -110,6 +119,7 @@ pub struct LocalOracleDatapointScan {
     scan: GenericTokenScan<OracleTokenId>,
     oracle_box_wrapper_inputs: OracleBoxWrapperInputs,
     oracle_pk: ProveDlog,
+    node: ScanNode,
 }
 
 #[derive(Debug)]
@@ -117,36 +127,42 @@ pub struct LocalBallotBoxScan {
     scan: GenericTokenScan<BallotTokenId>,
     ballot_box_wrapper_inputs: BallotBoxWrapperInputs,
     ballot_token_owner_pk: ProveDlog,
+    node: ScanNode,
 }
 
 #[derive(Debug)]
 pub struct PoolBoxScan {
     scan: GenericTokenScan<PoolTokenId>,
     pool_box_wrapper_inputs: PoolBoxWrapperInputs,
+    node: ScanNode,
 }
 
 #[derive(Debug)]
 pub struct RefreshBoxScan {
     scan: GenericTokenScan<RefreshTokenId>,
     refresh_box_wrapper_inputs: RefreshBoxWrapperInputs,
+    node: ScanNode,
 }
 
 #[derive(Debug)]
 pub struct BallotBoxesScan {
     scan: GenericTokenScan<BallotTokenId>,
     ballot_box_wrapper_inputs: BallotBoxWrapperInputs,
+    node: ScanNode,
 }
 
 #[derive(Debug)]
 pub struct UpdateBoxScan {
     scan: GenericTokenScan<UpdateTokenId>,
     update_box_wrapper_inputs: UpdateBoxWrapperInputs,
+    node: ScanNode,
 }
 
 #[derive(Debug)]
 pub struct BuybackBoxScan {
     scan: GenericTokenScan<BuybackTokenId>,
     reward_token_id: RewardTokenId,
+    node: ScanNode,
 }
 
 /// The state of the oracle pool when it is in the Live Epoch stage
@@ -156,6 +172,10 @@ pub struct LiveEpochState {
     pub local_datapoint_box_state: Option<LocalDatapointState>,
     pub latest_pool_datapoint: u64,
     pub latest_pool_box_height: BlockHeight,
+    /// Position of the local oracle among the oracles that posted a datapoint in this epoch
+    pub local_refresh_rank: Option<u32>,
+    /// Creation heights of the datapoints posted in this epoch by the other oracles
+    pub other_posted_datapoint_heights: Vec<BlockHeight>,
 }
 
 /// Last posted datapoint box info by the local oracle
@@ -170,46 +190,73 @@ pub enum LocalDatapointState {
     },
 }
 
+/// Ballot box holding the ballot token of the local oracle
+#[derive(Debug, Clone)]
+pub enum LocalBallotState {
+    /// The ballot box has no vote
+    NoVote { height: BlockHeight },
+    Voted {
+        vote_parameters: CastBallotBoxVoteParameters,
+        /// Height the vote was cast at
+        height: BlockHeight,
+        /// A vote only counts for the update box created at the height it was cast for, a vote
+        /// for an older update box is stale and has to be cast again
+        is_current: bool,
+    },
+}
+
 impl OraclePool {
-    pub fn new(node_scan_registry: &NodeScanRegistry) -> std::result::Result<OraclePool, Error> {
-        let pool_config = &POOL_CONFIG;
-        let oracle_config = &ORACLE_CONFIG;
+    /// Create a new `OraclePool` for the pool and oracle configs of the context
+    pub fn new(
+        node_scan_registry: &NodeScanRegistry,
+        ctx: &OracleContext,
+    ) -> std::result::Result<OraclePool, Error> {
+        let pool_config = &ctx.pool_config;
+        let oracle_config = &ctx.oracle_config;
+        let node = ctx.scan_node();
         let oracle_pk = oracle_config.oracle_address_p2pk()?;
 
         // Create all `Scan` structs for protocol
         let oracle_datapoint_scan = OracleDatapointScan {
             scan: node_scan_registry.oracle_token_scan.clone(),
+            node: node.clone(),
             oracle_box_wrapper_inputs: pool_config.oracle_box_wrapper_inputs.clone(),
         };
         let local_oracle_datapoint_scan = LocalOracleDatapointScan {
             scan: node_scan_registry.oracle_token_scan.clone(),
+            node: node.clone(),
             oracle_box_wrapper_inputs: pool_config.oracle_box_wrapper_inputs.clone(),
             oracle_pk: oracle_pk.clone(),
         };
 
         let local_ballot_box_scan = LocalBallotBoxScan {
             scan: node_scan_registry.ballot_token_scan.clone(),
+            node: node.clone(),
             ballot_box_wrapper_inputs: pool_config.ballot_box_wrapper_inputs.clone(),
             ballot_token_owner_pk: oracle_pk.clone(),
         };
 
         let ballot_boxes_scan = BallotBoxesScan {
             scan: node_scan_registry.ballot_token_scan.clone(),
+            node: node.clone(),
             ballot_box_wrapper_inputs: pool_config.ballot_box_wrapper_inputs.clone(),
         };
 
         let pool_box_scan = PoolBoxScan {
             scan: node_scan_registry.pool_token_scan.clone(),
+            node: node.clone(),
             pool_box_wrapper_inputs: pool_config.pool_box_wrapper_inputs.clone(),
         };
 
         let refresh_box_scan = RefreshBoxScan {
             scan: node_scan_registry.refresh_token_scan.clone(),
+            node: node.clone(),
             refresh_box_wrapper_inputs: pool_config.refresh_box_wrapper_inputs.clone(),
         };
 
         let update_box_scan = UpdateBoxScan {
             scan: node_scan_registry.update_token_scan.clone(),
+            node: node.clone(),
             update_box_wrapper_inputs: pool_config.update_box_wrapper_inputs.clone(),
         };
 
@@ -219,6 +266,7 @@ impl OraclePool {
                 .clone()
                 .map(|scan| BuybackBoxScan {
                     scan,
+                    node: node.clone(),
                     reward_token_id: pool_config.token_ids.reward_token_id.clone(),
                 });
 
@@ -237,40 +285,28 @@ impl OraclePool {
     }
 
     /// Create a new `OraclePool` struct with loaded scans
-    pub fn load() -> std::result::Result<OraclePool, Error> {
+    pub fn load(ctx: &OracleContext) -> std::result::Result<OraclePool, Error> {
         let node_scan_registry = NodeScanRegistry::load()?;
-        Self::new(&node_scan_registry)
+        Self::new(&node_scan_registry, ctx)
     }
 
     /// Get the state of the current oracle pool epoch
     pub fn get_live_epoch_state(&self) -> std::result::Result<LiveEpochState, anyhow::Error> {
-        let pool_box = self.get_pool_box_source().get_pool_box()?;
-        let epoch_id = pool_box.epoch_counter();
-
-        // Whether datapoint was commit in the current Live Epoch
-        let local_datapoint_box_state = self
-            .get_local_datapoint_box_source()
-            .get_local_oracle_datapoint_box()?
-            .map(|local_data_point_box| match local_data_point_box {
-                OracleBoxWrapper::Posted(ref posted_box) => LocalDatapointState::Posted {
-                    epoch_id: posted_box.epoch_counter(),
-                    height: BlockHeight(local_data_point_box.get_box().creation_height),
-                },
-                OracleBoxWrapper::Collected(_) => LocalDatapointState::Collected {
-                    height: BlockHeight(local_data_point_box.get_box().creation_height),
-                },
-            });
-
-        let latest_pool_datapoint = pool_box.rate() as u64;
-
-        let epoch_state = LiveEpochState {
-            pool_box_epoch_id: epoch_id,
-            latest_pool_datapoint,
-            latest_pool_box_height: BlockHeight(pool_box.get_box().creation_height),
-            local_datapoint_box_state,
-        };
+        live_epoch_state(
+            self.get_pool_box_source(),
+            self.get_local_datapoint_box_source(),
+            self.get_posted_datapoint_boxes_source(),
+            &self.local_oracle_datapoint_scan.oracle_pk.h,
+        )
+    }
 
-        Ok(epoch_state)
+    /// Get the state of the local ballot box, `None` if the local oracle has no ballot box
+    pub fn get_local_ballot_state(&self) -> Result<Option<LocalBallotState>> {
+        local_ballot_state(
+            self.get_local_ballot_box_source(),
+            self.get_update_box_source(),
+            &self.local_ballot_box_scan.ballot_box_wrapper_inputs,
+        )
     }
 
     pub fn get_pool_box_source(&self) -> &dyn PoolBoxSource {
@@ -310,13 +346,446 @@ impl OraclePool {
             .as_ref()
             .map(|b| b as &dyn BuybackBoxSource)
     }
+
+    /// All the boxes of the ballot token scan, with or without a vote
+    pub fn get_all_ballot_boxes(&self) -> Result<Vec<BallotBoxWrapper>> {
+        let scan = &self.ballot_boxes_scan;
+        Ok(scan
+            .scan
+            .get_boxes(&scan.node)?
+            .into_iter()
+            .filter_map(|b| BallotBoxWrapper::new(b, &scan.ballot_box_wrapper_inputs).ok())
+            .collect())
+    }
+
+    /// Where the oracle token is after the `oracle_address` of the config was changed from the one
+    /// with the given public key
+    pub fn get_oracle_address_change(
+        &self,
+        previous_oracle_pk: &ProveDlog,
+    ) -> Result<OracleAddressChange> {
+        if self
+            .get_local_datapoint_box_source()
+            .get_local_oracle_datapoint_box()?
+            .is_some()
+        {
+            return Ok(OracleAddressChange::Transferred);
+        }
+        let scan = &self.oracle_datapoint_scan;
+        let previous_box = local_oracle_box(
+            scan.scan.get_boxes(&scan.node)?,
+            &scan.oracle_box_wrapper_inputs,
+            previous_oracle_pk,
+        );
+        Ok(match previous_box {
+            Some(_) => OracleAddressChange::OwnedByPrevious,
+            None => OracleAddressChange::NotFound,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OracleAddressChange {
+    /// The oracle box is owned by the new address, the oracle token was transferred to it
+    Transferred,
+    /// The oracle box is still owned by the previous address, no local datapoint box is found
+    OwnedByPrevious,
+    /// No oracle box is owned by either address, e.g. the transfer is not confirmed yet
+    NotFound,
+}
+
+/// Log how to finish the migration when the oracle and ballot token scans were registered for
+/// another oracle address than the one of the oracle config
+pub fn log_oracle_address_change(
+    oracle_pool: &OraclePool,
+    pool_name: &str,
+    previous_oracle_address: &str,
+    oracle_address: &NetworkAddress,
+) {
+    let oracle_address = oracle_address.to_base58();
+    if previous_oracle_address == oracle_address {
+        return;
+    }
+    let previous_oracle_pk = if let Ok(Address::P2Pk(pk)) =
+        AddressEncoder::unchecked_parse_network_address_from_str(previous_oracle_address)
+            .map(|address| address.address())
+    {
+        pk
+    } else {
+        return;
+    };
+    match oracle_pool.get_oracle_address_change(&previous_oracle_pk) {
+        Ok(OracleAddressChange::Transferred) => log::info!(
+            "pool {}: oracle_address changed from {} to {}, the oracle box is owned by the new address",
+            pool_name,
+            previous_oracle_address,
+            oracle_address
+        ),
+        Ok(OracleAddressChange::OwnedByPrevious) => log::warn!(
+            "pool {}: oracle_address changed from {} to {} but the oracle box is still owned by the previous address, \
+             so no local datapoint box is found. Either set oracle_address back to {}, or move the token with \
+             `oracle-core transfer-oracle-token {}` run with the previous oracle_address (extract the reward tokens first)",
+            pool_name,
+            previous_oracle_address,
+            oracle_address,
+            previous_oracle_address,
+            oracle_address
+        ),
+        Ok(OracleAddressChange::NotFound) => log::warn!(
+            "pool {}: oracle_address changed from {} to {} and no oracle box is owned by either address. \
+             If the oracle token was just transferred, wait for the transfer tx to be confirmed",
+            pool_name,
+            previous_oracle_address,
+            oracle_address
+        ),
+        Err(e) => log::debug!("Failed to find the oracle box after the oracle address change: {}", e),
+    }
+}
+
+/// Box sources of an `OraclePool` fetching the pool box, the refresh box, the oracle token boxes
+/// and the ballot token boxes from the node at most once. Created for every main loop iteration,
+/// so the number of node requests per iteration does not depend on how many times the boxes are
+/// used.
+pub struct OraclePoolCache<'a> {
+    oracle_pool: &'a OraclePool,
+    pool_box: OnceCell<PoolBoxWrapper>,
+    refresh_box: OnceCell<RefreshBoxWrapper>,
+    /// Decoded boxes of the oracle token scan, shared by the posted, collected and local datapoint
+    /// sources
+    oracle_boxes: OnceCell<Vec<OracleBoxWrapper>>,
+    /// Decoded boxes of the ballot token scan, shared by the local and the vote ballot box sources
+    ballot_boxes: OnceCell<Vec<BallotBoxWrapper>>,
+}
+
+impl<'a> OraclePoolCache<'a> {
+    pub fn new(oracle_pool: &'a OraclePool) -> Self {
+        OraclePoolCache {
+            oracle_pool,
+            pool_box: OnceCell::new(),
+            refresh_box: OnceCell::new(),
+            oracle_boxes: OnceCell::new(),
+            ballot_boxes: OnceCell::new(),
+        }
+    }
+
+    /// Fetch the pool box, the refresh box, the oracle token boxes and the ballot token boxes from
+    /// their scans concurrently, instead of one after the other on first use. A failed fetch is
+    /// retried on first use, which returns its error. Returns an error if a fetch thread panicked,
+    /// its boxes are fetched again on first use as well.
+    pub fn prefetch(&self) -> Result<()> {
+        let oracle_pool = self.oracle_pool;
+        let (pool_box, refresh_box, oracle_boxes, ballot_boxes) = std::thread::scope(|s| {
+            let pool_box = s.spawn(|| oracle_pool.pool_box_scan.get_pool_box());
+            let refresh_box = s.spawn(|| oracle_pool.refresh_box_scan.get_refresh_box());
+            let oracle_boxes = s.spawn(|| fetch_oracle_boxes(&oracle_pool.oracle_datapoint_scan));
+            let ballot_boxes = s.spawn(|| fetch_ballot_boxes(&oracle_pool.ballot_boxes_scan));
+            (
+                pool_box.join(),
+                refresh_box.join(),
+                oracle_boxes.join(),
+                ballot_boxes.join(),
+            )
+        });
+        let mut panicked = Vec::new();
+        if let Some(pool_box) = prefetched(pool_box, "pool box", &mut panicked) {
+            let _ = self.pool_box.set(pool_box);
+        }
+        if let Some(refresh_box) = prefetched(refresh_box, "refresh box", &mut panicked) {
+            let _ = self.refresh_box.set(refresh_box);
+        }
+        if let Some(oracle_boxes) = prefetched(oracle_boxes, "oracle boxes", &mut panicked) {
+            let _ = self.oracle_boxes.set(oracle_boxes);
+        }
+        if let Some(ballot_boxes) = prefetched(ballot_boxes, "ballot boxes", &mut panicked) {
+            let _ = self.ballot_boxes.set(ballot_boxes);
+        }
+        if panicked.is_empty() {
+            Ok(())
+        } else {
+            Err(DataSourceError::FetchPanicked(panicked.join(", ")))
+        }
+    }
+
+    pub fn get_live_epoch_state(&self) -> std::result::Result<LiveEpochState, anyhow::Error> {
+        live_epoch_state(
+            self,
+            self,
+            self,
+            &self.oracle_pool.local_oracle_datapoint_scan.oracle_pk.h,
+        )
+    }
+
+    pub fn get_pool_box_source(&self) -> &dyn PoolBoxSource {
+        self as &dyn PoolBoxSource
+    }
+
+    pub fn get_refresh_box_source(&self) -> &dyn RefreshBoxSource {
+        self as &dyn RefreshBoxSource
+    }
+
+    pub fn get_posted_datapoint_boxes_source(&self) -> &dyn PostedDatapointBoxesSource {
+        self as &dyn PostedDatapointBoxesSource
+    }
+
+    pub fn get_collected_datapoint_boxes_source(&self) -> &dyn CollectedDatapointBoxesSource {
+        self as &dyn CollectedDatapointBoxesSource
+    }
+
+    pub fn get_local_datapoint_box_source(&self) -> &dyn LocalDatapointBoxSource {
+        self as &dyn LocalDatapointBoxSource
+    }
+
+    pub fn get_buyback_box_source(&self) -> Option<&dyn BuybackBoxSource> {
+        self.oracle_pool.get_buyback_box_source()
+    }
+
+    pub fn get_update_box_source(&self) -> &dyn UpdateBoxSource {
+        self.oracle_pool.get_update_box_source()
+    }
+
+    pub fn get_local_ballot_box_source(&self) -> &dyn LocalBallotBoxSource {
+        self as &dyn LocalBallotBoxSource
+    }
+
+    pub fn get_ballot_boxes_source(&self) -> &dyn VoteBallotBoxesSource {
+        self as &dyn VoteBallotBoxesSource
+    }
+
+    fn oracle_boxes(&self) -> Result<&Vec<OracleBoxWrapper>> {
+        self.oracle_boxes
+            .get_or_try_init(|| fetch_oracle_boxes(&self.oracle_pool.oracle_datapoint_scan))
+    }
+
+    fn ballot_boxes(&self) -> Result<&Vec<BallotBoxWrapper>> {
+        self.ballot_boxes
+            .get_or_try_init(|| fetch_ballot_boxes(&self.oracle_pool.ballot_boxes_scan))
+    }
+}
+
+/// Boxes fetched on a prefetch thread, `None` if the fetch failed or the thread panicked. The
+/// boxes of a panicked thread are added to `panicked`.
+fn prefetched<T>(
+    joined: std::thread::Result<Result<T>>,
+    boxes: &str,
+    panicked: &mut Vec<String>,
+) -> Option<T> {
+    match joined {
+        Ok(fetched) => fetched.ok(),
+        Err(_) => {
+            panicked.push(boxes.to_string());
+            None
+        }
+    }
+}
+
+/// Oracle token boxes of the scan that are valid oracle boxes
+fn fetch_oracle_boxes(scan: &OracleDatapointScan) -> Result<Vec<OracleBoxWrapper>> {
+    let boxes = scan.scan.get_boxes(&scan.node)?;
+    Ok(decode_boxes_in_parallel(boxes, |b| {
+        OracleBoxWrapper::new(b, &scan.oracle_box_wrapper_inputs).ok()
+    }))
+}
+
+/// Ballot token boxes of the scan that are valid ballot boxes, with or without a vote
+fn fetch_ballot_boxes(scan: &BallotBoxesScan) -> Result<Vec<BallotBoxWrapper>> {
+    let boxes = scan.scan.get_boxes(&scan.node)?;
+    Ok(decode_boxes_in_parallel(boxes, |b| {
+        BallotBoxWrapper::new(b, &scan.ballot_box_wrapper_inputs).ok()
+    }))
+}
+
+/// Threads the boxes of a scan are decoded on at most
+const DECODE_THREADS: usize = 4;
+
+/// Fewer boxes per thread are not worth spawning it
+const MIN_BOXES_PER_DECODE_THREAD: usize = 8;
+
+/// Decode the boxes on up to `DECODE_THREADS` threads, keeping their order. Small sets of boxes
+/// are decoded on the calling thread.
+fn decode_boxes_in_parallel<T: Send>(
+    mut boxes: Vec<ErgoBox>,
+    decode: impl Fn(ErgoBox) -> Option<T> + Sync,
+) -> Vec<T> {
+    let chunk_size =
+        ((boxes.len() + DECODE_THREADS - 1) / DECODE_THREADS).max(MIN_BOXES_PER_DECODE_THREAD);
+    if boxes.len() <= chunk_size {
+        return boxes.into_iter().filter_map(decode).collect();
+    }
+    let mut chunks = Vec::new();
+    while !boxes.is_empty() {
+        let rest = boxes.split_off(chunk_size.min(boxes.len()));
+        chunks.push(boxes);
+        boxes = rest;
+    }
+    let decode = &decode;
+    std::thread::scope(|s| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| s.spawn(move || chunk.into_iter().filter_map(decode).collect::<Vec<T>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("box decoding panicked"))
+            .collect()
+    })
+}
+
+impl<'a> PoolBoxSource for OraclePoolCache<'a> {
+    fn get_pool_box(&self) -> Result<PoolBoxWrapper> {
+        self.pool_box
+            .get_or_try_init(|| self.oracle_pool.pool_box_scan.get_pool_box())
+            .cloned()
+    }
+}
+
+impl<'a> RefreshBoxSource for OraclePoolCache<'a> {
+    fn get_refresh_box(&self) -> Result<RefreshBoxWrapper> {
+        self.refresh_box
+            .get_or_try_init(|| self.oracle_pool.refresh_box_scan.get_refresh_box())
+            .cloned()
+    }
+}
+
+impl<'a> PostedDatapointBoxesSource for OraclePoolCache<'a> {
+    fn get_posted_datapoint_boxes(&self) -> Result<Vec<PostedOracleBox>> {
+        Ok(self
+            .oracle_boxes()?
+            .iter()
+            .filter_map(|b| match b {
+                OracleBoxWrapper::Posted(p) => Some(p.clone()),
+                OracleBoxWrapper::Collected(_) => None,
+            })
+            .collect())
+    }
+}
+
+impl<'a> CollectedDatapointBoxesSource for OraclePoolCache<'a> {
+    fn get_collected_datapoint_boxes(&self) -> Result<Vec<CollectedOracleBox>> {
+        Ok(self
+            .oracle_boxes()?
+            .iter()
+            .filter_map(|b| match b {
+                OracleBoxWrapper::Posted(_) => None,
+                OracleBoxWrapper::Collected(c) => Some(c.clone()),
+            })
+            .collect())
+    }
+}
+
+impl<'a> LocalBallotBoxSource for OraclePoolCache<'a> {
+    fn get_ballot_box(&self) -> Result<Option<BallotBoxWrapper>> {
+        let ballot_token_owner_pk = &self.oracle_pool.local_ballot_box_scan.ballot_token_owner_pk;
+        Ok(self
+            .ballot_boxes()?
+            .iter()
+            .find(|b| b.ballot_token_owner() == *ballot_token_owner_pk.h)
+            .cloned())
+    }
+}
+
+impl<'a> VoteBallotBoxesSource for OraclePoolCache<'a> {
+    fn get_ballot_boxes(&self) -> Result<Vec<VoteBallotBoxWrapper>> {
+        let ballot_box_wrapper_inputs =
+            &self.oracle_pool.ballot_boxes_scan.ballot_box_wrapper_inputs;
+        Ok(self
+            .ballot_boxes()?
+            .iter()
+            .filter_map(|b| {
+                VoteBallotBoxWrapper::new(b.get_box().clone(), ballot_box_wrapper_inputs).ok()
+            })
+            .collect())
+    }
+}
+
+impl<'a> LocalDatapointBoxSource for OraclePoolCache<'a> {
+    fn get_local_oracle_datapoint_box(&self) -> Result<Option<OracleBoxWrapper>> {
+        let oracle_pk = &self.oracle_pool.local_oracle_datapoint_scan.oracle_pk;
+        Ok(self
+            .oracle_boxes()?
+            .iter()
+            .find(|b| b.public_key() == *oracle_pk.h)
+            .cloned())
+    }
+}
+
+pub(crate) fn live_epoch_state(
+    pool_box_source: &dyn PoolBoxSource,
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    posted_datapoint_boxes_source: &dyn PostedDatapointBoxesSource,
+    local_pk: &EcPoint,
+) -> std::result::Result<LiveEpochState, anyhow::Error> {
+    let pool_box = pool_box_source.get_pool_box()?;
+    let epoch_id = pool_box.epoch_counter();
+
+    // Whether datapoint was commit in the current Live Epoch
+    let local_datapoint_box_state = local_datapoint_box_source
+        .get_local_oracle_datapoint_box()?
+        .map(|local_data_point_box| match local_data_point_box {
+            OracleBoxWrapper::Posted(ref posted_box) => LocalDatapointState::Posted {
+                epoch_id: posted_box.epoch_counter(),
+                height: BlockHeight(local_data_point_box.get_box().creation_height),
+            },
+            OracleBoxWrapper::Collected(_) => LocalDatapointState::Collected {
+                height: BlockHeight(local_data_point_box.get_box().creation_height),
+            },
+        });
+
+    let latest_pool_datapoint = pool_box.rate() as u64;
+
+    let posted_boxes = posted_datapoint_boxes_source.get_posted_datapoint_boxes()?;
+    let local_refresh_rank = refresh_rank(&posted_boxes, epoch_id, local_pk);
+    let other_posted_datapoint_heights = posted_boxes
+        .iter()
+        .filter(|b| b.epoch_counter() == epoch_id && &b.public_key() != local_pk)
+        .map(|b| BlockHeight(b.get_box().creation_height))
+        .collect();
+
+    let epoch_state = LiveEpochState {
+        pool_box_epoch_id: epoch_id,
+        latest_pool_datapoint,
+        latest_pool_box_height: BlockHeight(pool_box.get_box().creation_height),
+        local_datapoint_box_state,
+        local_refresh_rank,
+        other_posted_datapoint_heights,
+    };
+
+    Ok(epoch_state)
+}
+
+fn local_ballot_state(
+    local_ballot_box_source: &dyn LocalBallotBoxSource,
+    update_box_source: &dyn UpdateBoxSource,
+    ballot_box_wrapper_inputs: &BallotBoxWrapperInputs,
+) -> Result<Option<LocalBallotState>> {
+    let ballot_box = match local_ballot_box_source.get_ballot_box()? {
+        Some(ballot_box) => ballot_box,
+        None => return Ok(None),
+    };
+    let height = BlockHeight(ballot_box.get_box().creation_height);
+    // a ballot box without vote parameters in R5-R6 is not a vote
+    let state =
+        match VoteBallotBoxWrapper::new(ballot_box.get_box().clone(), ballot_box_wrapper_inputs) {
+            Ok(vote_ballot_box) => {
+                let update_box = update_box_source.get_update_box()?;
+                let vote_parameters = vote_ballot_box.vote_parameters().clone();
+                let is_current = vote_parameters.update_box_creation_height
+                    == update_box.get_box().creation_height as i32;
+                LocalBallotState::Voted {
+                    vote_parameters,
+                    height,
+                    is_current,
+                }
+            }
+            Err(_) => LocalBallotState::NoVote { height },
+        };
+    Ok(Some(state))
 }
 
 impl PoolBoxSource for PoolBoxScan {
     fn get_pool_box(&self) -> Result<PoolBoxWrapper> {
         let box_wrapper = PoolBoxWrapper::new(
             self.scan
-                .get_box()?
+                .get_box(&self.node)?
                 .ok_or(DataSourceError::PoolBoxNotFoundError)?,
             &self.pool_box_wrapper_inputs,
         )?;
@@ -328,7 +797,7 @@ impl LocalBallotBoxSource for LocalBallotBoxScan {
     fn get_ballot_box(&self) -> Result<Option<BallotBoxWrapper>> {
         Ok(self
             .scan
-            .get_boxes()?
+            .get_boxes(&self.node)?
             .into_iter()
             .filter_map(|b| BallotBoxWrapper::new(b, &self.ballot_box_wrapper_inputs).ok())
             .find(|b| b.ballot_token_owner() == *self.ballot_token_owner_pk.h))
@@ -339,7 +808,7 @@ impl RefreshBoxSource for RefreshBoxScan {
     fn get_refresh_box(&self) -> Result<RefreshBoxWrapper> {
         let box_wrapper = RefreshBoxWrapper::new(
             self.scan
-                .get_box()?
+                .get_box(&self.node)?
                 .ok_or(DataSourceError::RefreshBoxNotFoundError)?,
             &self.refresh_box_wrapper_inputs,
         )?;
@@ -349,20 +818,30 @@ impl RefreshBoxSource for RefreshBoxScan {
 
 impl LocalDatapointBoxSource for LocalOracleDatapointScan {
     fn get_local_oracle_datapoint_box(&self) -> Result<Option<OracleBoxWrapper>> {
-        Ok(self
-            .scan
-            .get_boxes()?
-            .into_iter()
-            .filter_map(|b| OracleBoxWrapper::new(b, &self.oracle_box_wrapper_inputs).ok())
-            .find(|b| b.public_key() == *self.oracle_pk.h))
+        Ok(local_oracle_box(
+            self.scan.get_boxes(&self.node)?,
+            &self.oracle_box_wrapper_inputs,
+            &self.oracle_pk,
+        ))
     }
 }
 
+pub(crate) fn local_oracle_box(
+    oracle_token_boxes: Vec<ErgoBox>,
+    oracle_box_wrapper_inputs: &OracleBoxWrapperInputs,
+    oracle_pk: &ProveDlog,
+) -> Option<OracleBoxWrapper> {
+    oracle_token_boxes
+        .into_iter()
+        .filter_map(|b| OracleBoxWrapper::new(b, oracle_box_wrapper_inputs).ok())
+        .find(|b| b.public_key() == *oracle_pk.h)
+}
+
 impl VoteBallotBoxesSource for BallotBoxesScan {
     fn get_ballot_boxes(&self) -> Result<Vec<VoteBallotBoxWrapper>> {
         Ok(self
             .scan
-            .get_boxes()?
+            .get_boxes(&self.node)?
             .into_iter()
             .filter_map(|ballot_box| {
                 VoteBallotBoxWrapper::new(ballot_box, &self.ballot_box_wrapper_inputs).ok()
@@ -375,7 +854,7 @@ impl UpdateBoxSource for UpdateBoxScan {
     fn get_update_box(&self) -> Result<UpdateBoxWrapper> {
         let box_wrapper = UpdateBoxWrapper::new(
             self.scan
-                .get_box()?
+                .get_box(&self.node)?
                 .ok_or(DataSourceError::UpdateBoxNotFoundError)?,
             &self.update_box_wrapper_inputs,
         )?;
@@ -383,43 +862,117 @@ impl UpdateBoxSource for UpdateBoxScan {
     }
 }
 
+/// Position of the local oracle among the oracles that posted a datapoint in the given epoch.
+/// The boxes are ordered by box id, so every oracle sees the same order and it changes every epoch.
+fn refresh_rank(
+    posted_boxes: &[PostedOracleBox],
+    epoch_id: EpochCounter,
+    local_pk: &EcPoint,
+) -> Option<u32> {
+    let mut epoch_boxes: Vec<&PostedOracleBox> = posted_boxes
+        .iter()
+        .filter(|b| b.epoch_counter() == epoch_id)
+        .collect();
+    epoch_boxes.sort_by_key(|b| String::from(b.get_box().box_id()));
+    epoch_boxes
+        .iter()
+        .position(|b| &b.public_key() == local_pk)
+        .map(|pos| pos as u32)
+}
+
 impl PostedDatapointBoxesSource for OracleDatapointScan {
     fn get_posted_datapoint_boxes(&self) -> Result<Vec<PostedOracleBox>> {
-        let posted_boxes = self
-            .scan
-            .get_boxes()?
-            .into_iter()
-            .filter_map(|b| OracleBoxWrapper::new(b, &self.oracle_box_wrapper_inputs).ok())
-            .filter_map(|b| match b {
-                OracleBoxWrapper::Posted(p) => Some(p),
-                OracleBoxWrapper::Collected(_) => None,
-            })
-            .collect();
-        Ok(posted_boxes)
+        Ok(posted_oracle_boxes(
+            self.scan.get_boxes(&self.node)?,
+            &self.oracle_box_wrapper_inputs,
+        ))
     }
 }
 
 impl CollectedDatapointBoxesSource for OracleDatapointScan {
     fn get_collected_datapoint_boxes(&self) -> Result<Vec<CollectedOracleBox>> {
-        let posted_boxes = self
-            .scan
-            .get_boxes()?
-            .into_iter()
-            .filter_map(|b| OracleBoxWrapper::new(b, &self.oracle_box_wrapper_inputs).ok())
-            .filter_map(|b| match b {
-                OracleBoxWrapper::Posted(_) => None,
-                OracleBoxWrapper::Collected(p) => Some(p),
-            })
-            .collect();
-        Ok(posted_boxes)
+        Ok(collected_oracle_boxes(
+            self.scan.get_boxes(&self.node)?,
+            &self.oracle_box_wrapper_inputs,
+        ))
     }
 }
 
+pub(crate) fn posted_oracle_boxes(
+    oracle_token_boxes: Vec<ErgoBox>,
+    oracle_box_wrapper_inputs: &OracleBoxWrapperInputs,
+) -> Vec<PostedOracleBox> {
+    oracle_token_boxes
+        .into_iter()
+        .filter_map(|b| OracleBoxWrapper::new(b, oracle_box_wrapper_inputs).ok())
+        .filter_map(|b| match b {
+            OracleBoxWrapper::Posted(p) => Some(p),
+            OracleBoxWrapper::Collected(_) => None,
+        })
+        .collect()
+}
+
+fn collected_oracle_boxes(
+    oracle_token_boxes: Vec<ErgoBox>,
+    oracle_box_wrapper_inputs: &OracleBoxWrapperInputs,
+) -> Vec<CollectedOracleBox> {
+    oracle_token_boxes
+        .into_iter()
+        .filter_map(|b| OracleBoxWrapper::new(b, oracle_box_wrapper_inputs).ok())
+        .filter_map(|b| match b {
+            OracleBoxWrapper::Posted(_) => None,
+            OracleBoxWrapper::Collected(p) => Some(p),
+        })
+        .collect()
+}
+
 impl BuybackBoxSource for BuybackBoxScan {
     fn get_buyback_box(&self) -> Result<Option<BuybackBoxWrapper>> {
         Ok(self
             .scan
-            .get_box()?
-            .map(|ergo_box| BuybackBoxWrapper::new(ergo_box, self.reward_token_id.clone())))
+            .get_box(&self.node)?
+            .map(|ergo_box| BuybackBoxWrapper::new(ergo_box, self.reward_token_id.clone()))
+            .transpose()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_boxes_in_parallel_keeps_order() {
+        let boxes: Vec<ErgoBox> = (0..50).map(|_| force_any_val::<ErgoBox>()).collect();
+        let box_ids: Vec<_> = boxes.iter().map(|b| b.box_id()).collect();
+        // every other box fails to decode
+        let decoded = decode_boxes_in_parallel(boxes, |b| {
+            let box_id = b.box_id();
+            box_ids
+                .iter()
+                .position(|id| *id == box_id)
+                .filter(|i| i % 2 == 0)
+                .map(|_| box_id)
+        });
+        let expected: Vec<_> = box_ids.iter().step_by(2).cloned().collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_prefetched_panic() {
+        let mut panicked = Vec::new();
+        let failed =
+            std::thread::spawn(|| -> Result<u32> { Err(DataSourceError::PoolBoxNotFoundError) });
+        assert!(prefetched(failed.join(), "pool box", &mut panicked).is_none());
+        assert!(panicked.is_empty());
+        let panicking = std::thread::spawn(|| -> Result<u32> { panic!("node client") });
+        assert!(prefetched(panicking.join(), "ballot boxes", &mut panicked).is_none());
+        assert_eq!(panicked, vec!["ballot boxes".to_string()]);
+        let fetched = std::thread::spawn(|| -> Result<u32> { Ok(1) });
+        assert_eq!(
+            prefetched(fetched.join(), "refresh box", &mut panicked),
+            Some(1)
+        );
     }
 }
@@ -47,6 +47,10 @@ pub struct EpochCounter(pub u32);
 #[serde(transparent)]
 pub struct MinDatapoints(pub i32);
 
+#[derive(PartialEq, PartialOrd, Eq, Ord, Debug, Serialize, Deserialize, Copy, Clone)]
+#[serde(transparent)]
+pub struct RewardTokensPerDatapoint(pub i32);
+
 #[derive(
     PartialEq,
     PartialOrd,
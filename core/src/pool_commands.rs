@@ -3,15 +3,25 @@ use ergo_lib::ergotree_ir::chain::address::{Address, AddressEncoderError};
 use thiserror::Error;
 
 use crate::action_report::PoolActionReport;
+use crate::action_report::PublishAndRefreshActionReport;
 use crate::actions::PoolAction;
+use crate::actions::PublishAndRefreshAction;
+use crate::babel_fee::BabelBoxSource;
+use crate::babel_fee::BabelFee;
+use crate::babel_fee::BabelFeeError;
 use crate::box_kind::PoolBox;
-use crate::datapoint_source::RuntimeDataPointSource;
-use crate::oracle_config::ORACLE_CONFIG;
-use crate::oracle_state::{DataSourceError, OraclePool};
-use crate::oracle_types::BlockHeight;
-use crate::pool_config::POOL_CONFIG;
+use crate::datapoint_source::DataPointSource;
+use crate::explorer_api::datapoint_fallback::ExplorerFallbackDatapointSource;
+use crate::explorer_api::explorer_url::default_explorer_api_url;
+use crate::explorer_api::ExplorerApi;
+use crate::node_interface::snapshot::NodeSnapshot;
+use crate::oracle_config::OracleConfig;
+use crate::oracle_state::{DataSourceError, OraclePoolCache, PostedDatapointBoxesSource};
+use crate::pool_config::PoolConfig;
+use crate::state::EpochParams;
 use crate::wallet::WalletDataSource;
 
+use self::publish_and_refresh::build_chained_refresh_action;
 use self::publish_datapoint::build_publish_first_datapoint_action;
 use self::publish_datapoint::{
     build_subsequent_publish_datapoint_action, PublishDatapointActionError,
@@ -19,16 +29,21 @@ use self::publish_datapoint::{
 use self::refresh::build_refresh_action;
 use self::refresh::RefreshActionError;
 
+pub mod publish_and_refresh;
 pub mod publish_datapoint;
 pub mod refresh;
 #[cfg(test)]
 pub(crate) mod test_utils;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PoolCommand {
     Refresh,
     PublishFirstDataPoint,
-    PublishSubsequentDataPoint { republish: bool },
+    PublishSubsequentDataPoint {
+        republish: bool,
+    },
+    /// Publish the last datapoint needed for a refresh and refresh in the same iteration
+    PublishSubsequentDataPointAndRefresh,
 }
 
 #[derive(Debug, Error)]
@@ -47,22 +62,64 @@ pub enum PoolCommandError {
     AddressEncoder(#[from] AddressEncoderError),
     #[error("Wrong oracle address type")]
     WrongOracleAddressType,
+    #[error("babel fee error: {0}")]
+    BabelFee(#[from] BabelFeeError),
 }
 
+/// Build the action of the command at the height and with the wallet of the node snapshot taken
+/// at the start of the main loop iteration
 pub fn build_action(
     cmd: PoolCommand,
-    op: &OraclePool,
-    wallet: &dyn WalletDataSource,
-    height: BlockHeight,
-    change_address: Address,
-    datapoint_source: &RuntimeDataPointSource,
+    op: &OraclePoolCache,
+    pool_config: &PoolConfig,
+    oracle_config: &OracleConfig,
+    node: &NodeSnapshot,
+    datapoint_source: &dyn DataPointSource,
+    babel_box_source: &dyn BabelBoxSource,
 ) -> Result<(PoolAction, PoolActionReport), PoolCommandError> {
+    let wallet: &dyn WalletDataSource = node;
+    let height = node.height;
+    let change_address = node.change_address.address();
     let refresh_box_source = op.get_refresh_box_source();
-    let datapoint_boxes_source = op.get_posted_datapoint_boxes_source();
     let pool_box = op.get_pool_box_source().get_pool_box()?;
     let current_epoch_counter = pool_box.epoch_counter();
+    let epoch_params = EpochParams::from(
+        pool_config
+            .refresh_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters(),
+    );
+    let explorer_api = if oracle_config.explorer_datapoint_fallback.unwrap_or(false) {
+        Some(ExplorerApi::new(
+            oracle_config.explorer_url.clone().unwrap_or_else(|| {
+                default_explorer_api_url(oracle_config.oracle_address.network())
+            }),
+        ))
+    } else {
+        None
+    };
+    let explorer_fallback_source =
+        explorer_api
+            .as_ref()
+            .map(|explorer| ExplorerFallbackDatapointSource {
+                scan: op.get_posted_datapoint_boxes_source(),
+                explorer,
+                oracle_box_wrapper_inputs: &pool_config.oracle_box_wrapper_inputs,
+                epoch_counter: current_epoch_counter,
+                min_data_points: epoch_params.min_data_points.0.max(0) as usize,
+            });
+    let datapoint_boxes_source: &dyn PostedDatapointBoxesSource =
+        match explorer_fallback_source.as_ref() {
+            Some(source) => source,
+            None => op.get_posted_datapoint_boxes_source(),
+        };
+    let babel_fee = oracle_config
+        .babel_fee
+        .as_ref()
+        .map(|config| BabelFee::new(config, babel_box_source, oracle_config.min_box_value()))
+        .transpose()?;
     let oracle_public_key =
-        if let Address::P2Pk(public_key) = ORACLE_CONFIG.oracle_address.address() {
+        if let Address::P2Pk(public_key) = oracle_config.oracle_address.address() {
             *public_key.h
         } else {
             return Err(PoolCommandError::WrongOracleAddressType);
@@ -73,8 +130,10 @@ pub fn build_action(
             height,
             change_address,
             oracle_public_key,
-            POOL_CONFIG.oracle_box_wrapper_inputs.clone(),
+            pool_config.oracle_box_wrapper_inputs.clone(),
             datapoint_source,
+            babel_fee.as_ref(),
+            &oracle_config.tx_params(oracle_config.publish_fee()),
         )
         .map_err(Into::into)
         .map(|(action, report)| (action.into(), report.into())),
@@ -91,35 +150,88 @@ pub fn build_action(
                     change_address,
                     datapoint_source,
                     new_epoch_counter,
-                    &POOL_CONFIG.token_ids.reward_token_id,
+                    &pool_config.token_ids.reward_token_id,
+                    babel_fee.as_ref(),
+                    &oracle_config.tx_params(oracle_config.publish_fee()),
                 )
                 .map_err(Into::into)
                 .map(|(action, report)| (action.into(), report.into()))
             } else {
-                Err(PoolCommandError::Unexpected(
-                    "{cmd} error: No local datapoint box found".to_string(),
-                ))
+                Err(PoolCommandError::Unexpected(format!(
+                    "{:?} error: No local datapoint box found",
+                    cmd
+                )))
+            }
+        }
+        PoolCommand::PublishSubsequentDataPointAndRefresh => {
+            if let Some(local_datapoint_box) = op
+                .get_local_datapoint_box_source()
+                .get_local_oracle_datapoint_box()?
+            {
+                let (publish, publish_report) = build_subsequent_publish_datapoint_action(
+                    &local_datapoint_box,
+                    wallet,
+                    height,
+                    change_address.clone(),
+                    datapoint_source,
+                    current_epoch_counter,
+                    &pool_config.token_ids.reward_token_id,
+                    babel_fee.as_ref(),
+                    &oracle_config.tx_params(oracle_config.publish_fee()),
+                )?;
+                match build_chained_refresh_action(
+                    &publish.tx,
+                    &pool_config.oracle_box_wrapper_inputs,
+                    op.get_pool_box_source(),
+                    refresh_box_source,
+                    datapoint_boxes_source,
+                    epoch_params.max_deviation_percent,
+                    epoch_params.min_data_points,
+                    wallet,
+                    height,
+                    change_address,
+                    &oracle_public_key,
+                    op.get_buyback_box_source(),
+                    pool_config.buyback_max_reward_tokens_per_refresh,
+                    babel_fee.as_ref(),
+                    pool_config.erg_rewards.as_ref(),
+                    &oracle_config.tx_params(oracle_config.refresh_fee()),
+                ) {
+                    Ok((refresh, refresh_report)) => Ok((
+                        PublishAndRefreshAction { publish, refresh }.into(),
+                        PublishAndRefreshActionReport {
+                            publish: publish_report,
+                            refresh: refresh_report,
+                        }
+                        .into(),
+                    )),
+                    Err(e) => {
+                        log::warn!("Failed to build a refresh tx chained on the publish tx, publishing the datapoint only: {e}");
+                        Ok((publish.into(), publish_report.into()))
+                    }
+                }
+            } else {
+                Err(PoolCommandError::Unexpected(format!(
+                    "{:?} error: No local datapoint box found",
+                    cmd
+                )))
             }
         }
         PoolCommand::Refresh => build_refresh_action(
             op.get_pool_box_source(),
             refresh_box_source,
             datapoint_boxes_source,
-            POOL_CONFIG
-                .refresh_box_wrapper_inputs
-                .contract_inputs
-                .contract_parameters()
-                .max_deviation_percent() as u32,
-            POOL_CONFIG
-                .refresh_box_wrapper_inputs
-                .contract_inputs
-                .contract_parameters()
-                .min_data_points(),
+            epoch_params.max_deviation_percent,
+            epoch_params.min_data_points,
             wallet,
             height,
             change_address,
             &oracle_public_key,
             op.get_buyback_box_source(),
+            pool_config.buyback_max_reward_tokens_per_refresh,
+            babel_fee.as_ref(),
+            pool_config.erg_rewards.as_ref(),
+            &oracle_config.tx_params(oracle_config.refresh_fee()),
         )
         .map_err(Into::into)
         .map(|(action, report)| (action.into(), report.into())),
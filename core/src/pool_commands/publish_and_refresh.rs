@@ -0,0 +1,379 @@
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::ergo_chain_types::EcPoint;
+use ergo_lib::ergotree_ir::chain::address::Address;
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::serialization::SigmaParsingError;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
+use thiserror::Error;
+
+use crate::action_report::RefreshActionReport;
+use crate::actions::RefreshAction;
+use crate::babel_fee::BabelFee;
+use crate::box_kind::OracleBoxError;
+use crate::box_kind::OracleBoxWrapper;
+use crate::box_kind::OracleBoxWrapperInputs;
+use crate::box_kind::PostedOracleBox;
+use crate::oracle_config::TxParams;
+use crate::oracle_state::BuybackBoxSource;
+use crate::oracle_state::DataSourceError;
+use crate::oracle_state::PoolBoxSource;
+use crate::oracle_state::PostedDatapointBoxesSource;
+use crate::oracle_state::RefreshBoxSource;
+use crate::oracle_types::BlockHeight;
+use crate::oracle_types::MinDatapoints;
+use crate::pool_config::ErgRewards;
+use crate::wallet::WalletDataError;
+use crate::wallet::WalletDataSource;
+
+use super::refresh::build_refresh_action;
+use super::refresh::RefreshActionError;
+
+#[derive(Debug, Error)]
+pub enum PublishAndRefreshActionError {
+    #[error("error on building RefreshAction: {0}")]
+    Refresh(#[from] RefreshActionError),
+    #[error("data source error: {0}")]
+    DataSource(#[from] DataSourceError),
+    #[error("WalletData error: {0}")]
+    WalletData(#[from] WalletDataError),
+    #[error("oracle box error: {0}")]
+    OracleBox(#[from] OracleBoxError),
+    #[error("sigma serialization error: {0}")]
+    SigmaSerialization(#[from] SigmaSerializationError),
+    #[error("sigma parsing error: {0}")]
+    SigmaParsing(#[from] SigmaParsingError),
+    #[error("publish tx does not produce a posted oracle box")]
+    NoPostedOracleBox,
+}
+
+/// Wallet and posted datapoint boxes as they will be once the publish tx is accepted
+struct AfterPublishState<'a> {
+    wallet: &'a dyn WalletDataSource,
+    wallet_boxes: Vec<ErgoBox>,
+    posted_boxes: Vec<PostedOracleBox>,
+}
+
+impl<'a> AfterPublishState<'a> {
+    fn new(
+        publish_tx: &UnsignedTransaction,
+        wallet: &'a dyn WalletDataSource,
+        change_address: &Address,
+        datapoint_src: &dyn PostedDatapointBoxesSource,
+        oracle_box_wrapper_inputs: &OracleBoxWrapperInputs,
+    ) -> Result<Self, PublishAndRefreshActionError> {
+        let tx_id = publish_tx.id();
+        let outputs = publish_tx
+            .output_candidates
+            .iter()
+            .enumerate()
+            .map(|(idx, candidate)| ErgoBox::from_box_candidate(candidate, tx_id, idx as u16))
+            .collect::<Result<Vec<ErgoBox>, SigmaSerializationError>>()?;
+        let spent: Vec<BoxId> = publish_tx.inputs.iter().map(|i| i.box_id).collect();
+        // the new oracle box is the first output of the publish tx
+        let new_oracle_box = match OracleBoxWrapper::new(
+            outputs
+                .first()
+                .cloned()
+                .ok_or(PublishAndRefreshActionError::NoPostedOracleBox)?,
+            oracle_box_wrapper_inputs,
+        )? {
+            OracleBoxWrapper::Posted(posted_box) => posted_box,
+            OracleBoxWrapper::Collected(_) => {
+                return Err(PublishAndRefreshActionError::NoPostedOracleBox)
+            }
+        };
+        let change_tree = change_address.script()?;
+        let mut wallet_boxes: Vec<ErgoBox> = wallet
+            .get_unspent_wallet_boxes()?
+            .into_iter()
+            .filter(|b| !spent.contains(&b.box_id()))
+            .collect();
+        wallet_boxes.extend(
+            outputs
+                .into_iter()
+                .skip(1)
+                .filter(|b| b.ergo_tree == change_tree),
+        );
+        let mut posted_boxes: Vec<PostedOracleBox> = datapoint_src
+            .get_posted_datapoint_boxes()?
+            .into_iter()
+            .filter(|b| !spent.contains(&b.get_box().box_id()))
+            .collect();
+        posted_boxes.push(new_oracle_box);
+        Ok(AfterPublishState {
+            wallet,
+            wallet_boxes,
+            posted_boxes,
+        })
+    }
+}
+
+impl<'a> WalletDataSource for AfterPublishState<'a> {
+    fn get_unspent_wallet_boxes(&self) -> Result<Vec<ErgoBox>, WalletDataError> {
+        Ok(self.wallet_boxes.clone())
+    }
+
+    fn get_change_address(&self) -> Result<NetworkAddress, WalletDataError> {
+        self.wallet.get_change_address()
+    }
+}
+
+impl<'a> PostedDatapointBoxesSource for AfterPublishState<'a> {
+    fn get_posted_datapoint_boxes(&self) -> crate::oracle_state::Result<Vec<PostedOracleBox>> {
+        Ok(self.posted_boxes.clone())
+    }
+}
+
+/// Build a refresh tx that spends the unconfirmed oracle box of the given publish tx, so both txs
+/// can be submitted in the same main loop iteration
+#[allow(clippy::too_many_arguments)]
+pub fn build_chained_refresh_action(
+    publish_tx: &UnsignedTransaction,
+    oracle_box_wrapper_inputs: &OracleBoxWrapperInputs,
+    pool_box_source: &dyn PoolBoxSource,
+    refresh_box_source: &dyn RefreshBoxSource,
+    datapoint_src: &dyn PostedDatapointBoxesSource,
+    max_deviation_percent: u32,
+    min_data_points: MinDatapoints,
+    wallet: &dyn WalletDataSource,
+    height: BlockHeight,
+    change_address: Address,
+    my_oracle_pk: &EcPoint,
+    buyback_box_source: Option<&dyn BuybackBoxSource>,
+    buyback_max_reward_tokens: Option<u64>,
+    babel_fee: Option<&BabelFee>,
+    erg_rewards: Option<&ErgRewards>,
+    tx_params: &TxParams,
+) -> Result<(RefreshAction, RefreshActionReport), PublishAndRefreshActionError> {
+    let after_publish = AfterPublishState::new(
+        publish_tx,
+        wallet,
+        &change_address,
+        datapoint_src,
+        oracle_box_wrapper_inputs,
+    )?;
+    let (refresh, refresh_report) = build_refresh_action(
+        pool_box_source,
+        refresh_box_source,
+        &after_publish,
+        max_deviation_percent,
+        min_data_points,
+        &after_publish,
+        height,
+        change_address,
+        my_oracle_pk,
+        buyback_box_source,
+        buyback_max_reward_tokens,
+        babel_fee,
+        erg_rewards,
+        tx_params,
+    )?;
+    Ok((refresh, refresh_report))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+    use ergo_lib::ergo_chain_types::EcPoint;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+    use ergo_lib::wallet::signing::TransactionContext;
+    use ergo_lib::wallet::tx_builder::SUGGESTED_TX_FEE;
+    use ergo_lib::wallet::Wallet;
+    use sigma_test_util::force_any_val;
+
+    use crate::box_kind::OracleBox;
+    use crate::box_kind::PoolBox;
+    use crate::box_kind::RefreshBox;
+    use crate::box_kind::RefreshBoxWrapperInputs;
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::contracts::pool::PoolContractParameters;
+    use crate::contracts::refresh::RefreshContractInputs;
+    use crate::contracts::refresh::RefreshContractParameters;
+    use crate::oracle_types::EpochCounter;
+    use crate::oracle_types::EpochLength;
+    use crate::pool_commands::publish_datapoint::build_subsequent_publish_datapoint_action;
+    use crate::pool_commands::test_utils::{
+        find_input_boxes, generate_token_ids, make_datapoint_box, make_pool_box, make_refresh_box,
+        make_wallet_unspent_box, DatapointSourceMock, MockDatapointSource, PoolBoxMock,
+        RefreshBoxMock, WalletDataMock,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_refresh_chained_on_publish() {
+        let ctx = force_any_val::<ErgoStateContext>();
+        let height = BlockHeight(ctx.pre_header.height);
+        let token_ids = generate_token_ids();
+        let pool_contract_parameters = PoolContractParameters::default();
+        let oracle_contract_parameters = OracleContractParameters::default();
+        let refresh_contract_inputs = RefreshContractInputs::build_with(
+            RefreshContractParameters::default(),
+            token_ids.oracle_token_id.clone(),
+            token_ids.pool_nft_token_id.clone(),
+        )
+        .unwrap();
+        let refresh_box_wrapper_inputs = RefreshBoxWrapperInputs {
+            refresh_nft_token_id: token_ids.refresh_nft_token_id.clone(),
+            contract_inputs: refresh_contract_inputs,
+        };
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((oracle_contract_parameters, &token_ids)).unwrap();
+        let pool_box_epoch_id = EpochCounter(1);
+        let pool_box_mock = PoolBoxMock {
+            pool_box: make_pool_box(
+                200,
+                pool_box_epoch_id,
+                SUGGESTED_TX_FEE(),
+                height - EpochLength(32), // from previous epoch
+                &pool_contract_parameters,
+                &token_ids,
+            ),
+        };
+        let refresh_box_mock = RefreshBoxMock {
+            refresh_box: make_refresh_box(
+                SUGGESTED_TX_FEE(),
+                &refresh_box_wrapper_inputs,
+                height - EpochLength(32),
+            ),
+        };
+
+        let secret = force_any_val::<DlogProverInput>();
+        let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
+        let oracle_pub_key = secret.public_image().h;
+
+        // our box is from the previous epoch, three other oracles already posted in this one
+        let local_box = OracleBoxWrapper::new(
+            make_datapoint_box(
+                *oracle_pub_key.clone(),
+                200,
+                EpochCounter(pool_box_epoch_id.0 - 1),
+                &token_ids,
+                SUGGESTED_TX_FEE().checked_mul_u32(100).unwrap(),
+                height - EpochLength(40),
+                100,
+            ),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap();
+        let other_boxes: Vec<PostedOracleBox> = vec![199, 197, 198]
+            .into_iter()
+            .map(|datapoint| {
+                PostedOracleBox::new(
+                    make_datapoint_box(
+                        force_any_val::<EcPoint>(),
+                        datapoint,
+                        pool_box_epoch_id,
+                        &token_ids,
+                        SUGGESTED_TX_FEE().checked_mul_u32(100).unwrap(),
+                        height - EpochLength(9),
+                        100,
+                    ),
+                    &oracle_box_wrapper_inputs,
+                )
+                .unwrap()
+            })
+            .collect();
+        let datapoints_mock = DatapointSourceMock {
+            datapoints: other_boxes.clone(),
+        };
+
+        // change must go back to the wallet so the refresh tx can spend it
+        let change_address = NetworkAddress::new(
+            NetworkPrefix::Mainnet,
+            &Address::P2Pk(secret.public_image()),
+        );
+        let wallet_mock = WalletDataMock {
+            unspent_boxes: vec![make_wallet_unspent_box(
+                secret.public_image(),
+                SUGGESTED_TX_FEE().checked_mul_u32(10000).unwrap(),
+                None,
+            )],
+            change_address: change_address.clone(),
+        };
+
+        let (publish, _) = build_subsequent_publish_datapoint_action(
+            &local_box,
+            &wallet_mock,
+            height,
+            change_address.address(),
+            &MockDatapointSource {
+                datapoint: 201.into(),
+            },
+            pool_box_epoch_id,
+            &token_ids.reward_token_id,
+            None,
+            &TxParams::default(),
+        )
+        .unwrap();
+        let (refresh, report) = build_chained_refresh_action(
+            &publish.tx,
+            &oracle_box_wrapper_inputs,
+            &pool_box_mock,
+            &refresh_box_mock,
+            &datapoints_mock,
+            5,
+            MinDatapoints(4),
+            &wallet_mock,
+            height,
+            change_address.address(),
+            &oracle_pub_key,
+            None,
+            None,
+            None,
+            None,
+            &TxParams::default(),
+        )
+        .unwrap();
+        assert_eq!(report.oracle_boxes_collected.len(), 4);
+
+        let publish_outputs: Vec<ErgoBox> = publish
+            .tx
+            .output_candidates
+            .iter()
+            .enumerate()
+            .map(|(idx, candidate)| {
+                ErgoBox::from_box_candidate(candidate, publish.tx.id(), idx as u16).unwrap()
+            })
+            .collect();
+        let refresh_input_ids: Vec<BoxId> = refresh.tx.inputs.iter().map(|i| i.box_id).collect();
+        assert!(refresh_input_ids.contains(&publish_outputs[0].box_id()));
+
+        let mut publish_input_boxes = vec![local_box.get_box().clone()];
+        publish_input_boxes.append(&mut wallet_mock.get_unspent_wallet_boxes().unwrap());
+        let publish_context = TransactionContext::new(
+            publish.tx.clone(),
+            find_input_boxes(publish.tx.clone(), publish_input_boxes),
+            Vec::new(),
+        )
+        .unwrap();
+        let _signed_publish = wallet
+            .sign_transaction(publish_context, &ctx, None)
+            .unwrap();
+
+        let mut refresh_input_boxes = vec![
+            pool_box_mock.get_pool_box().unwrap().get_box().clone(),
+            refresh_box_mock
+                .get_refresh_box()
+                .unwrap()
+                .get_box()
+                .clone(),
+        ];
+        refresh_input_boxes.extend(other_boxes.into_iter().map(ErgoBox::from));
+        refresh_input_boxes.extend(publish_outputs);
+        let refresh_context = TransactionContext::new(
+            refresh.tx.clone(),
+            find_input_boxes(refresh.tx, refresh_input_boxes),
+            Vec::new(),
+        )
+        .unwrap();
+        let _signed_refresh = wallet
+            .sign_transaction(refresh_context, &ctx, None)
+            .unwrap();
+    }
+}
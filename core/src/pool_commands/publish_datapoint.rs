@@ -6,7 +6,7 @@ use ergo_lib::{
     ergotree_interpreter::sigma_protocol::prover::ContextExtension,
     ergotree_ir::chain::{address::Address, token::TokenAmount},
     wallet::{
-        box_selector::{BoxSelector, BoxSelectorError, SimpleBoxSelector},
+        box_selector::BoxSelection,
         tx_builder::{TxBuilder, TxBuilderError},
     },
 };
@@ -15,10 +15,14 @@ use thiserror::Error;
 use crate::{
     action_report::PublishDatapointActionReport,
     actions::PublishDataPointAction,
-    box_kind::{make_oracle_box_candidate, OracleBox, OracleBoxWrapper, OracleBoxWrapperInputs},
+    babel_fee::{select_boxes, BabelFee, BabelFeeError},
+    box_kind::{
+        check_min_box_value, make_oracle_box_candidate, MinBoxValueError, OracleBox,
+        OracleBoxWrapper, OracleBoxWrapperInputs,
+    },
     contracts::oracle::{OracleContract, OracleContractError},
     datapoint_source::{DataPointSource, DataPointSourceError},
-    oracle_config::BASE_FEE,
+    oracle_config::TxParams,
     oracle_state::DataSourceError,
     oracle_types::{BlockHeight, EpochCounter},
     spec_token::{OracleTokenId, RewardTokenId, SpecToken},
@@ -37,14 +41,17 @@ pub enum PublishDatapointActionError {
     ErgoBoxCandidateBuilder(#[from] ErgoBoxCandidateBuilderError),
     #[error("WalletData error: {0}")]
     WalletData(#[from] WalletDataError),
-    #[error("box selector error: {0}")]
-    BoxSelector(#[from] BoxSelectorError),
+    #[error("babel fee error: {0}")]
+    BabelFee(#[from] BabelFeeError),
     #[error("datapoint source error: {0}")]
     DataPointSource(#[from] DataPointSourceError),
     #[error("oracle contract error: {0}")]
     OracleContract(#[from] OracleContractError),
+    #[error("min box value error: {0}")]
+    MinBoxValue(#[from] MinBoxValueError),
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn build_subsequent_publish_datapoint_action(
     local_datapoint_box: &OracleBoxWrapper,
     wallet: &dyn WalletDataSource,
@@ -53,8 +60,11 @@ pub fn build_subsequent_publish_datapoint_action(
     datapoint_source: &dyn DataPointSource,
     new_epoch_counter: EpochCounter,
     reward_token_id: &RewardTokenId,
+    babel_fee: Option<&BabelFee>,
+    tx_params: &TxParams,
 ) -> Result<(PublishDataPointAction, PublishDatapointActionReport), PublishDatapointActionError> {
-    let new_datapoint = datapoint_source.get_datapoint()?;
+    let fetched = datapoint_source.fetch_datapoint()?;
+    let new_datapoint = fetched.rate;
     let in_oracle_box = local_datapoint_box;
 
     let outbox_reward_tokens = if reward_token_id != &in_oracle_box.reward_token().token_id {
@@ -66,6 +76,7 @@ pub fn build_subsequent_publish_datapoint_action(
         in_oracle_box.reward_token()
     };
 
+    let out_box_value = in_oracle_box.get_box().value.max(tx_params.min_box_value);
     let output_candidate = make_oracle_box_candidate(
         in_oracle_box.contract(),
         in_oracle_box.public_key(),
@@ -73,27 +84,45 @@ pub fn build_subsequent_publish_datapoint_action(
         new_epoch_counter,
         in_oracle_box.oracle_token(),
         outbox_reward_tokens.clone(),
-        in_oracle_box.get_box().value,
+        out_box_value,
         height,
     )?;
+    check_min_box_value(&output_candidate)?;
 
     let mut unspent_boxes = wallet.get_unspent_wallet_boxes()?;
-    let tx_fee = *BASE_FEE;
-    let box_selector = SimpleBoxSelector::new();
+    let tx_fee = tx_params.fee;
     let target_tokens = vec![
         in_oracle_box.oracle_token().into(),
         outbox_reward_tokens.into(),
     ];
-    let target_balace = in_oracle_box.get_box().value.checked_add(&tx_fee).unwrap();
+    let target_balace = out_box_value.checked_add(&tx_fee).unwrap();
     unspent_boxes.push(in_oracle_box.get_box().clone());
-    let selection = box_selector.select(unspent_boxes, target_balace, target_tokens.as_slice())?;
+    let (selection, babel_swap) = select_boxes(
+        unspent_boxes,
+        target_balace,
+        target_tokens.as_slice(),
+        babel_fee,
+        height,
+    )?;
+    let mut input_boxes = selection.boxes.as_vec().clone();
+    let mut output_candidates = vec![output_candidate];
+    let babel_ctx_ext = babel_swap
+        .as_ref()
+        .map(|swap| swap.add_to_tx(&mut input_boxes, &mut output_candidates));
+    let box_selection = BoxSelection {
+        boxes: input_boxes.try_into().unwrap(),
+        change_boxes: selection.change_boxes,
+    };
     let mut tx_builder = TxBuilder::new(
-        selection,
-        vec![output_candidate],
+        box_selection,
+        output_candidates,
         height.0,
         tx_fee,
         change_address,
     );
+    if let Some((babel_box_id, babel_box_ctx_ext)) = babel_ctx_ext {
+        tx_builder.set_context_extension(babel_box_id, babel_box_ctx_ext);
+    }
 
     // The following context value ensures that `outIndex` in the oracle contract is properly set.
     let ctx_ext = ContextExtension {
@@ -103,6 +132,7 @@ pub fn build_subsequent_publish_datapoint_action(
     let tx = tx_builder.build()?;
     let report = PublishDatapointActionReport {
         posted_datapoint: new_datapoint,
+        sources: fetched.sources,
     };
     Ok((PublishDataPointAction { tx }, report))
 }
@@ -115,11 +145,13 @@ pub fn build_publish_first_datapoint_action(
     public_key: EcPoint,
     inputs: OracleBoxWrapperInputs,
     datapoint_source: &dyn DataPointSource,
+    babel_fee: Option<&BabelFee>,
+    tx_params: &TxParams,
 ) -> Result<(PublishDataPointAction, PublishDatapointActionReport), PublishDatapointActionError> {
-    let new_datapoint = datapoint_source.get_datapoint()?;
+    let fetched = datapoint_source.fetch_datapoint()?;
+    let new_datapoint = fetched.rate;
     let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
-    let tx_fee = *BASE_FEE;
-    let box_selector = SimpleBoxSelector::new();
+    let tx_fee = tx_params.fee;
     let oracle_token: SpecToken<OracleTokenId> = SpecToken {
         token_id: inputs.oracle_token_id.clone(),
         amount: TokenAmount::try_from(1).unwrap(),
@@ -130,13 +162,15 @@ pub fn build_publish_first_datapoint_action(
     };
 
     let contract = OracleContract::checked_load(&inputs.contract_inputs)?;
-    let min_storage_rent = contract.parameters().min_storage_rent;
-    let target_balance = min_storage_rent.checked_add(&tx_fee).unwrap();
+    let box_value = contract.min_storage_rent()?.max(tx_params.min_box_value);
+    let target_balance = box_value.checked_add(&tx_fee).unwrap();
 
-    let wallet_boxes_selection = box_selector.select(
+    let (wallet_boxes_selection, babel_swap) = select_boxes(
         unspent_boxes.clone(),
         target_balance,
         &[oracle_token.clone().into(), reward_token.clone().into()],
+        babel_fee,
+        height,
     )?;
 
     let output_candidate = make_oracle_box_candidate(
@@ -146,18 +180,31 @@ pub fn build_publish_first_datapoint_action(
         EpochCounter(1),
         oracle_token,
         reward_token,
-        min_storage_rent,
+        box_value,
         height,
     )?;
+    check_min_box_value(&output_candidate)?;
 
     let box_id = wallet_boxes_selection.boxes.first().box_id();
+    let mut input_boxes = wallet_boxes_selection.boxes.as_vec().clone();
+    let mut output_candidates = vec![output_candidate];
+    let babel_ctx_ext = babel_swap
+        .as_ref()
+        .map(|swap| swap.add_to_tx(&mut input_boxes, &mut output_candidates));
+    let box_selection = BoxSelection {
+        boxes: input_boxes.try_into().unwrap(),
+        change_boxes: wallet_boxes_selection.change_boxes,
+    };
     let mut tx_builder = TxBuilder::new(
-        wallet_boxes_selection,
-        vec![output_candidate],
+        box_selection,
+        output_candidates,
         height.0,
         tx_fee,
         change_address,
     );
+    if let Some((babel_box_id, babel_box_ctx_ext)) = babel_ctx_ext {
+        tx_builder.set_context_extension(babel_box_id, babel_box_ctx_ext);
+    }
 
     // The following context value ensures that `outIndex` in the oracle contract is properly set.
     let ctx_ext = ContextExtension {
@@ -167,6 +214,7 @@ pub fn build_publish_first_datapoint_action(
     let tx = tx_builder.build()?;
     let report = PublishDatapointActionReport {
         posted_datapoint: new_datapoint,
+        sources: fetched.sources,
     };
     Ok((PublishDataPointAction { tx }, report))
 }
@@ -174,16 +222,20 @@ pub fn build_publish_first_datapoint_action(
 #[cfg(test)]
 mod tests {
     use std::convert::TryInto;
+    use std::time::Duration;
 
     use super::*;
     use crate::box_kind::PoolBox;
     use crate::contracts::oracle::OracleContractParameters;
     use crate::contracts::pool::PoolContractParameters;
+    use crate::datapoint_source::{FetchedDatapoint, SourceRate};
+    use crate::notifications::{DivergenceCheckedSource, Notifier, SourceDivergenceAction};
     use crate::oracle_state::PoolBoxSource;
-    use crate::oracle_types::{EpochLength, Rate};
+    use crate::oracle_types::EpochLength;
     use crate::pool_commands::test_utils::{
         find_input_boxes, generate_token_ids, make_datapoint_box, make_pool_box,
-        make_wallet_unspent_box, PoolBoxMock, WalletDataMock,
+        make_wallet_unspent_box, MockDatapointSource, MockSourcesDatapointSource, PoolBoxMock,
+        WalletDataMock,
     };
     use crate::spec_token::TokenIdKind;
     use ergo_lib::chain::ergo_state_context::ErgoStateContext;
@@ -196,20 +248,10 @@ mod tests {
     use ergo_lib::ergotree_ir::mir::constant::Constant;
     use ergo_lib::ergotree_ir::mir::expr::Expr;
     use ergo_lib::wallet::signing::TransactionContext;
+    use ergo_lib::wallet::tx_builder::SUGGESTED_TX_FEE;
     use ergo_lib::wallet::Wallet;
     use sigma_test_util::force_any_val;
 
-    #[derive(Debug)]
-    struct MockDatapointSource {
-        datapoint: Rate,
-    }
-
-    impl DataPointSource for MockDatapointSource {
-        fn get_datapoint(&self) -> Result<Rate, DataPointSourceError> {
-            Ok(self.datapoint)
-        }
-    }
-
     #[test]
     fn test_subsequent_publish_datapoint() {
         let ctx = force_any_val::<ErgoStateContext>();
@@ -221,7 +263,7 @@ mod tests {
         let in_pool_box = make_pool_box(
             200,
             pool_box_epoch_id,
-            *BASE_FEE,
+            SUGGESTED_TX_FEE(),
             height - EpochLength(32), // from previous epoch
             &pool_contract_parameters,
             &token_ids,
@@ -260,7 +302,7 @@ mod tests {
 
         let wallet_unspent_box = make_wallet_unspent_box(
             secret.public_image(),
-            BASE_FEE.checked_mul_u32(10000).unwrap(),
+            SUGGESTED_TX_FEE().checked_mul_u32(10000).unwrap(),
             None,
         );
         let wallet_mock = WalletDataMock {
@@ -279,6 +321,8 @@ mod tests {
             &datapoint_source,
             pool_box_epoch_id,
             &token_ids.reward_token_id,
+            None,
+            &TxParams::default(),
         )
         .unwrap();
 
@@ -322,7 +366,7 @@ mod tests {
         let expr: Expr = c.into();
         let ergo_tree = ErgoTree::try_from(expr).unwrap();
 
-        let value = BASE_FEE.checked_mul_u32(10000).unwrap();
+        let value = SUGGESTED_TX_FEE().checked_mul_u32(10000).unwrap();
         let box_with_tokens = ErgoBox::new(
             value,
             ergo_tree.clone(),
@@ -336,7 +380,7 @@ mod tests {
         let unspent_boxes = vec![
             box_with_tokens.clone(),
             ErgoBox::new(
-                *BASE_FEE,
+                SUGGESTED_TX_FEE(),
                 ergo_tree.clone(),
                 None,
                 NonMandatoryRegisters::new(vec![].into_iter().collect()).unwrap(),
@@ -368,6 +412,8 @@ mod tests {
             &MockDatapointSource {
                 datapoint: 201.into(),
             },
+            None,
+            &TxParams::default(),
         )
         .unwrap();
 
@@ -382,6 +428,90 @@ mod tests {
         let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
     }
 
+    #[test]
+    fn test_publish_datapoint_held_while_sources_diverge() {
+        let ctx = force_any_val::<ErgoStateContext>();
+        let height = BlockHeight(ctx.pre_header.height);
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let c: Constant = secret.public_image().into();
+        let expr: Expr = c.into();
+        let ergo_tree = ErgoTree::try_from(expr).unwrap();
+        let tokens = BoxTokens::from_vec(vec![
+            Token {
+                token_id: token_ids.reward_token_id.token_id(),
+                amount: 100u64.try_into().unwrap(),
+            },
+            Token {
+                token_id: token_ids.oracle_token_id.token_id(),
+                amount: 1u64.try_into().unwrap(),
+            },
+        ])
+        .unwrap();
+        let unspent_boxes = vec![ErgoBox::new(
+            SUGGESTED_TX_FEE().checked_mul_u32(10000).unwrap(),
+            ergo_tree,
+            Some(tokens),
+            NonMandatoryRegisters::new(vec![].into_iter().collect()).unwrap(),
+            height.0 - 30,
+            force_any_val::<TxId>(),
+            0,
+        )
+        .unwrap()];
+        let change_address = AddressEncoder::unchecked_parse_network_address_from_str(
+            "9iHyKxXs2ZNLMp9N9gbUT9V8gTbsV7HED1C1VhttMfBUMPDyF7r",
+        )
+        .unwrap();
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let source = MockSourcesDatapointSource {
+            fetched: FetchedDatapoint {
+                rate: 201.into(),
+                sources: [("coincap", 200.0), ("coingecko", 250.0)]
+                    .into_iter()
+                    .map(|(source, rate)| SourceRate {
+                        source: source.to_string(),
+                        rate,
+                    })
+                    .collect(),
+            },
+        };
+        let notifier = Notifier::new(Vec::new(), Duration::from_secs(0));
+        let build = |action| {
+            build_publish_first_datapoint_action(
+                &WalletDataMock {
+                    unspent_boxes: unspent_boxes.clone(),
+                    change_address: change_address.clone(),
+                },
+                height,
+                change_address.address(),
+                *secret.public_image().h,
+                oracle_box_wrapper_inputs.clone(),
+                &DivergenceCheckedSource {
+                    source: &source,
+                    max_spread_percent: 10,
+                    action,
+                    pool_name: "pool",
+                    notifier: &notifier,
+                },
+                None,
+                &TxParams::default(),
+            )
+        };
+        assert!(matches!(
+            build(SourceDivergenceAction::Hold),
+            Err(PublishDatapointActionError::DataPointSource(
+                DataPointSourceError::SourceDivergence {
+                    spread_percent: 25,
+                    ..
+                }
+            ))
+        ));
+        let (_, report) = build(SourceDivergenceAction::Warn).unwrap();
+        assert_eq!(report.posted_datapoint, 201.into());
+    }
+
     #[test]
     fn test_subsequent_publish_datapoint_with_minted_reward_token() {
         let ctx = force_any_val::<ErgoStateContext>();
@@ -397,7 +527,7 @@ mod tests {
         let in_pool_box = make_pool_box(
             200,
             pool_box_epoch_id,
-            *BASE_FEE,
+            SUGGESTED_TX_FEE(),
             height - EpochLength(32), // from previous epoch
             &pool_contract_parameters,
             &token_ids,
@@ -436,7 +566,7 @@ mod tests {
 
         let wallet_unspent_box = make_wallet_unspent_box(
             secret.public_image(),
-            BASE_FEE.checked_mul_u32(10000).unwrap(),
+            SUGGESTED_TX_FEE().checked_mul_u32(10000).unwrap(),
             Some(
                 vec![Token {
                     token_id: minted_reward_token_id.token_id(),
@@ -462,6 +592,8 @@ mod tests {
             &datapoint_source,
             pool_box_epoch_id,
             &minted_reward_token_id,
+            None,
+            &TxParams::default(),
         )
         .unwrap();
 
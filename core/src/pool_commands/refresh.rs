@@ -1,5 +1,8 @@
 use crate::action_report::RefreshActionReport;
 use crate::actions::RefreshAction;
+use crate::babel_fee::select_boxes;
+use crate::babel_fee::BabelFee;
+use crate::babel_fee::BabelFeeError;
 use crate::box_kind::make_collected_oracle_box_candidate;
 use crate::box_kind::make_pool_box_candidate;
 use crate::box_kind::make_refresh_box_candidate;
@@ -8,7 +11,8 @@ use crate::box_kind::PoolBoxWrapper;
 use crate::box_kind::PostedOracleBox;
 use crate::box_kind::RefreshBox;
 use crate::box_kind::RefreshBoxWrapper;
-use crate::oracle_config::BASE_FEE;
+use crate::contracts::refresh::COLLECTOR_REWARD_PER_DATAPOINT;
+use crate::oracle_config::TxParams;
 use crate::oracle_state::BuybackBoxSource;
 use crate::oracle_state::DataSourceError;
 use crate::oracle_state::PoolBoxSource;
@@ -18,6 +22,7 @@ use crate::oracle_types::BlockHeight;
 use crate::oracle_types::EpochCounter;
 use crate::oracle_types::MinDatapoints;
 use crate::oracle_types::Rate;
+use crate::pool_config::ErgRewards;
 use crate::spec_token::RewardTokenId;
 use crate::spec_token::SpecToken;
 use crate::wallet::WalletDataError;
@@ -27,12 +32,12 @@ use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilderError;
 use ergo_lib::ergo_chain_types::EcPoint;
 use ergo_lib::ergotree_interpreter::sigma_protocol::prover::ContextExtension;
 use ergo_lib::ergotree_ir::chain::address::Address;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValueError;
 use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBoxCandidate;
 use ergo_lib::ergotree_ir::chain::token::TokenAmount;
+use ergo_lib::ergotree_ir::chain::token::TokenAmountError;
 use ergo_lib::wallet::box_selector::BoxSelection;
-use ergo_lib::wallet::box_selector::BoxSelector;
-use ergo_lib::wallet::box_selector::BoxSelectorError;
-use ergo_lib::wallet::box_selector::SimpleBoxSelector;
 use ergo_lib::wallet::tx_builder::TxBuilder;
 use ergo_lib::wallet::tx_builder::TxBuilderError;
 use thiserror::Error;
@@ -49,18 +54,34 @@ pub enum RefreshActionError {
     },
     #[error("Not enough datapoints left during the removal of the outliers")]
     NotEnoughDatapoints,
+    #[error("Pool box reward tokens ({reward_tokens}) are enough to collect {max_oracle_boxes} oracle boxes, but at least {expected} are required")]
+    NotEnoughPoolRewardTokens {
+        reward_tokens: u64,
+        max_oracle_boxes: usize,
+        expected: i32,
+    },
+    #[error("Pool box value ({value} nanoERG) is enough to pay ERG rewards for {max_oracle_boxes} oracle boxes, but at least {expected} are required")]
+    NotEnoughPoolBoxValue {
+        value: u64,
+        max_oracle_boxes: usize,
+        expected: i32,
+    },
     #[error("data source error: {0}")]
     DataSourceError(#[from] DataSourceError),
     #[error("WalletData error: {0}")]
     WalletData(#[from] WalletDataError),
-    #[error("box selector error: {0}")]
-    BoxSelectorError(#[from] BoxSelectorError),
+    #[error("babel fee error: {0}")]
+    BabelFee(#[from] BabelFeeError),
     #[error("tx builder error: {0}")]
     TxBuilderError(#[from] TxBuilderError),
     #[error("box builder error: {0}")]
     ErgoBoxCandidateBuilderError(#[from] ErgoBoxCandidateBuilderError),
     #[error("failed to found my own oracle box in the filtered posted oracle boxes")]
     MyOracleBoxNoFound,
+    #[error("token amount error: {0}")]
+    TokenAmount(#[from] TokenAmountError),
+    #[error("box value error: {0}")]
+    BoxValue(#[from] BoxValueError),
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -75,11 +96,18 @@ pub fn build_refresh_action(
     change_address: Address,
     my_oracle_pk: &EcPoint,
     buyback_box_source: Option<&dyn BuybackBoxSource>,
+    buyback_max_reward_tokens: Option<u64>,
+    babel_fee: Option<&BabelFee>,
+    erg_rewards: Option<&ErgRewards>,
+    tx_params: &TxParams,
 ) -> Result<(RefreshAction, RefreshActionReport), RefreshActionError> {
-    let tx_fee = *BASE_FEE;
+    let tx_fee = tx_params.fee;
     let in_pool_box = pool_box_source.get_pool_box()?;
     let in_refresh_box = refresh_box_source.get_refresh_box()?;
     let min_start_height = height - in_refresh_box.contract().epoch_length();
+    let oracle_reward_per_datapoint = in_refresh_box
+        .contract()
+        .oracle_reward_tokens_per_datapoint();
     let in_pool_box_epoch_id = in_pool_box.epoch_counter();
     let mut in_oracle_boxes: Vec<PostedOracleBox> = datapoint_src
         .get_posted_datapoint_boxes()?
@@ -96,7 +124,7 @@ pub fn build_refresh_action(
         in_oracle_boxes.iter().map(|b| b.rate()).collect(),
         deviation_range,
     )?;
-    let valid_in_oracle_boxes = in_oracle_boxes
+    let mut valid_in_oracle_boxes = in_oracle_boxes
         .into_iter()
         .filter(|b| valid_in_oracle_boxes_datapoints.contains(&b.rate()))
         .collect::<Vec<_>>();
@@ -110,11 +138,69 @@ pub fn build_refresh_action(
                 .collect(),
         });
     }
+    let max_oracle_boxes = if let Some(erg_rewards) = erg_rewards {
+        let pool_box_value = *in_pool_box.get_box().value.as_u64();
+        let max_oracle_boxes =
+            max_erg_rewarded_oracle_boxes(pool_box_value, erg_rewards, tx_params.min_box_value);
+        if (max_oracle_boxes as i32) < min_data_points.0 {
+            return Err(RefreshActionError::NotEnoughPoolBoxValue {
+                value: pool_box_value,
+                max_oracle_boxes,
+                expected: min_data_points.0,
+            });
+        }
+        max_oracle_boxes
+    } else {
+        let pool_reward_tokens = *in_pool_box.reward_token().amount.as_u64();
+        let max_oracle_boxes = max_collectable_oracle_boxes(
+            pool_reward_tokens,
+            pool_reward_tokens_per_datapoint(oracle_reward_per_datapoint),
+        );
+        if (max_oracle_boxes as i32) < min_data_points.0 {
+            return Err(RefreshActionError::NotEnoughPoolRewardTokens {
+                reward_tokens: pool_reward_tokens,
+                max_oracle_boxes,
+                expected: min_data_points.0,
+            });
+        }
+        max_oracle_boxes
+    };
+    if valid_in_oracle_boxes.len() > max_oracle_boxes {
+        let my_index = valid_in_oracle_boxes
+            .iter()
+            .position(|b| &b.public_key() == my_oracle_pk)
+            .ok_or(RefreshActionError::MyOracleBoxNoFound)?;
+        let rates: Vec<Rate> = valid_in_oracle_boxes.iter().map(|b| b.rate()).collect();
+        let start = narrowest_rate_window(&rates, max_oracle_boxes, my_index);
+        log::info!(
+            "Refresh: pool box rewards are enough for {} of {} valid oracle boxes, collecting the ones with the narrowest rate range",
+            max_oracle_boxes,
+            valid_in_oracle_boxes.len()
+        );
+        valid_in_oracle_boxes = valid_in_oracle_boxes
+            .into_iter()
+            .skip(start)
+            .take(max_oracle_boxes)
+            .collect();
+    }
     let rate = calc_pool_rate(valid_in_oracle_boxes.iter().map(|b| b.rate()).collect());
-    let reward_decrement = valid_in_oracle_boxes.len() as u64 * 2;
+    let num_collected = valid_in_oracle_boxes.len() as u64;
+    // in ERG reward mode the reward tokens stay in the pool box and the pool box value pays
+    let (reward_decrement, value_decrement) = match erg_rewards {
+        Some(erg_rewards) => (0, num_collected * erg_rewards.pool_reward_per_datapoint()),
+        None => (
+            num_collected * pool_reward_tokens_per_datapoint(oracle_reward_per_datapoint),
+            0,
+        ),
+    };
     let out_refresh_box = build_out_refresh_box(&in_refresh_box, height)?;
-    let mut out_oracle_boxes =
-        build_out_oracle_boxes(&valid_in_oracle_boxes, height, my_oracle_pk)?;
+    let mut out_oracle_boxes = build_out_oracle_boxes(
+        &valid_in_oracle_boxes,
+        height,
+        my_oracle_pk,
+        oracle_reward_per_datapoint,
+        erg_rewards,
+    )?;
 
     let in_buyback_box_opt = buyback_box_source
         .map(|s| s.get_buyback_box())
@@ -122,8 +208,7 @@ pub fn build_refresh_action(
         .flatten();
 
     let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
-    let box_selector = SimpleBoxSelector::new();
-    let selection = box_selector.select(unspent_boxes, tx_fee, &[])?;
+    let (selection, babel_swap) = select_boxes(unspent_boxes, tx_fee, &[], babel_fee, height)?;
 
     let mut input_boxes = vec![
         in_pool_box.get_box().clone(),
@@ -145,24 +230,42 @@ pub fn build_refresh_action(
         valid_in_oracle_boxes.len()
     );
 
-    let out_pool_box = build_out_pool_box(&in_pool_box, height, rate, reward_decrement, None)?;
+    let out_pool_box = build_out_pool_box(
+        &in_pool_box,
+        height,
+        rate,
+        reward_decrement,
+        value_decrement,
+        None,
+    )?;
     let mut output_candidates = vec![out_pool_box, out_refresh_box];
     if let Some(buyback_box) = in_buyback_box_opt {
         log::debug!("Found buyback box id {:?}", buyback_box.get_box().box_id());
-        if let Some(buyback_reward_token) = buyback_box.reward_token() {
+        if buyback_max_reward_tokens == Some(0) {
+            log::debug!("Moving buyback reward tokens is disabled in the pool config");
+        } else if let Some(buyback_reward_token) = buyback_box.reward_token() {
             log::debug!(
                 "Found reward tokens in buyback box and including it in the tx. Amount: {:?}",
                 buyback_reward_token.amount
             );
+            let available = *buyback_reward_token.amount.as_u64();
+            let taken = buyback_max_reward_tokens
+                .map(|max| max.min(available))
+                .unwrap_or(available);
             input_boxes.push(buyback_box.get_box().clone());
             let out_pool_box_w_buyback_rewards = build_out_pool_box(
                 &in_pool_box,
                 height,
                 rate,
                 reward_decrement,
-                Some(buyback_reward_token.amount),
+                value_decrement,
+                Some(taken.try_into()?),
             )?;
-            let out_buyback_box = buyback_box.new_without_reward_token();
+            let out_buyback_box = if taken < available {
+                buyback_box.new_with_reward_token_amount((available - taken).try_into()?)
+            } else {
+                buyback_box.new_without_reward_token()
+            };
             output_candidates.remove(0);
             output_candidates.insert(0, out_pool_box_w_buyback_rewards);
             // should be at index 2 (checked in the contract of the buyback input box)
@@ -174,6 +277,9 @@ pub fn build_refresh_action(
     input_boxes.append(&mut valid_in_oracle_raw_boxes);
     input_boxes.append(selection.boxes.as_vec().clone().as_mut());
     output_candidates.append(&mut out_oracle_boxes);
+    let babel_ctx_ext = babel_swap
+        .as_ref()
+        .map(|swap| swap.add_to_tx(&mut input_boxes, &mut output_candidates));
 
     let box_selection = BoxSelection {
         boxes: input_boxes.clone().try_into().unwrap(),
@@ -202,6 +308,9 @@ pub fn build_refresh_action(
             };
             b.set_context_extension(ob.get_box().box_id(), ob_ctx_ext);
         });
+    if let Some((babel_box_id, babel_box_ctx_ext)) = babel_ctx_ext {
+        b.set_context_extension(babel_box_id, babel_box_ctx_ext);
+    }
     let tx = b.build()?;
     let report = RefreshActionReport {
         oracle_boxes_collected: valid_in_oracle_boxes
@@ -212,6 +321,44 @@ pub fn build_refresh_action(
     Ok((RefreshAction { tx }, report))
 }
 
+/// Reward tokens the collector gets for each collected datapoint (on top of its own reward)
+pub const COLLECTOR_REWARD_TOKENS_PER_DATAPOINT: u64 = COLLECTOR_REWARD_PER_DATAPOINT as u64;
+
+/// Reward tokens the pool box pays for each collected datapoint, the oracle reward is set by the
+/// refresh contract (`oracle_reward_per_datapoint` of its parameters)
+pub fn pool_reward_tokens_per_datapoint(oracle_reward_per_datapoint: u64) -> u64 {
+    oracle_reward_per_datapoint + COLLECTOR_REWARD_TOKENS_PER_DATAPOINT
+}
+
+/// The pool box has to keep at least one reward token after paying for collected datapoints
+fn max_collectable_oracle_boxes(pool_reward_tokens: u64, pool_reward_per_datapoint: u64) -> usize {
+    (pool_reward_tokens.saturating_sub(1) / pool_reward_per_datapoint) as usize
+}
+
+/// The pool box has to keep at least the min box value after paying ERG rewards
+fn max_erg_rewarded_oracle_boxes(
+    pool_box_value: u64,
+    erg_rewards: &ErgRewards,
+    min_box_value: BoxValue,
+) -> usize {
+    let reward_per_datapoint = erg_rewards.pool_reward_per_datapoint();
+    if reward_per_datapoint == 0 {
+        return usize::MAX;
+    }
+    (pool_box_value.saturating_sub(*min_box_value.as_u64()) / reward_per_datapoint) as usize
+}
+
+/// Start of the window of `size` consecutive rates (sorted ascending) with the smallest spread that
+/// includes the rate at `required_index`. The contract requires the collected boxes sorted by rate
+/// and the collector's own box among them.
+fn narrowest_rate_window(sorted_rates: &[Rate], size: usize, required_index: usize) -> usize {
+    let first_start = (required_index + 1).saturating_sub(size);
+    let last_start = required_index.min(sorted_rates.len().saturating_sub(size));
+    (first_start..=last_start)
+        .min_by_key(|start| sorted_rates[start + size - 1] - sorted_rates[*start])
+        .unwrap_or(first_start)
+}
+
 fn filtered_oracle_boxes_by_rate<T>(
     oracle_boxes: Vec<T>,
     deviation_range: u32,
@@ -286,16 +433,16 @@ fn build_out_pool_box(
     creation_height: BlockHeight,
     rate: Rate,
     reward_decrement: u64,
+    value_decrement: u64,
     buyback_reward: Option<TokenAmount>,
 ) -> Result<ErgoBoxCandidate, RefreshActionError> {
     let new_epoch_counter = EpochCounter(in_pool_box.epoch_counter().0 + 1);
     let reward_token = in_pool_box.reward_token();
     let decremented = reward_token
         .amount
-        .checked_sub(&reward_decrement.try_into().unwrap())
-        .unwrap();
+        .checked_sub(&reward_decrement.try_into()?)?;
     let new_reward_amount = if let Some(buyback_reward) = buyback_reward {
-        decremented.checked_add(&buyback_reward).unwrap()
+        decremented.checked_add(&buyback_reward)?
     } else {
         decremented
     };
@@ -310,7 +457,10 @@ fn build_out_pool_box(
         new_epoch_counter,
         in_pool_box.pool_nft_token().clone(),
         new_reward_token,
-        in_pool_box.get_box().value,
+        in_pool_box
+            .get_box()
+            .value
+            .checked_sub(&BoxValue::try_from(value_decrement)?)?,
         creation_height,
     )
     .map_err(Into::into)
@@ -333,28 +483,40 @@ fn build_out_oracle_boxes(
     valid_oracle_boxes: &Vec<PostedOracleBox>,
     creation_height: BlockHeight,
     my_public_key: &EcPoint,
+    oracle_reward_per_datapoint: u64,
+    erg_rewards: Option<&ErgRewards>,
 ) -> Result<Vec<ErgoBoxCandidate>, RefreshActionError> {
+    let num_collected = valid_oracle_boxes.len() as u64;
     valid_oracle_boxes
         .iter()
         .map(|in_ob| {
+            let is_collector = &in_ob.public_key() == my_public_key;
             let mut reward_token_new = in_ob.reward_token();
-            reward_token_new.amount = if &in_ob.public_key() == my_public_key {
-                let increment: TokenAmount =
-                // additional 1 reward token per collected oracle box goes to the collector
-                    (1 + valid_oracle_boxes.len() as u64).try_into().unwrap();
-                reward_token_new.amount.checked_add(&increment).unwrap()
+            let mut value_new = in_ob.get_box().value;
+            if let Some(erg_rewards) = erg_rewards {
+                let increment = if is_collector {
+                    erg_rewards.oracle_reward_per_datapoint
+                        + erg_rewards.collector_reward_per_datapoint * num_collected
+                } else {
+                    erg_rewards.oracle_reward_per_datapoint
+                };
+                value_new = value_new.checked_add(&BoxValue::try_from(increment)?)?;
             } else {
-                reward_token_new
-                    .amount
-                    .checked_add(&1u64.try_into().unwrap())
-                    .unwrap()
-            };
+                let increment: TokenAmount = if is_collector {
+                    (oracle_reward_per_datapoint
+                        + COLLECTOR_REWARD_TOKENS_PER_DATAPOINT * num_collected)
+                        .try_into()?
+                } else {
+                    oracle_reward_per_datapoint.try_into()?
+                };
+                reward_token_new.amount = reward_token_new.amount.checked_add(&increment)?;
+            }
             make_collected_oracle_box_candidate(
                 in_ob.contract(),
                 in_ob.public_key(),
                 in_ob.oracle_token(),
                 reward_token_new,
-                in_ob.get_box().value,
+                value_new,
                 creation_height,
             )
             .map_err(Into::into)
@@ -369,30 +531,25 @@ mod tests {
     use std::vec;
 
     use ergo_lib::chain::ergo_state_context::ErgoStateContext;
-    use ergo_lib::chain::transaction::TxId;
     use ergo_lib::ergo_chain_types::EcPoint;
     use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
     use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
     use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
     use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
-    use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisters;
     use ergo_lib::ergotree_ir::chain::token::Token;
     use ergo_lib::wallet::signing::TransactionContext;
+    use ergo_lib::wallet::tx_builder::SUGGESTED_TX_FEE;
     use ergo_lib::wallet::Wallet;
     use sigma_test_util::force_any_val;
 
     use crate::box_kind::BuybackBoxWrapper;
     use crate::box_kind::OracleBoxWrapperInputs;
     use crate::box_kind::PostedOracleBox;
-    use crate::box_kind::RefreshBoxWrapper;
     use crate::box_kind::RefreshBoxWrapperInputs;
     use crate::contracts::oracle::OracleContractParameters;
     use crate::contracts::pool::PoolContractParameters;
-    use crate::contracts::refresh::RefreshContract;
     use crate::contracts::refresh::RefreshContractInputs;
     use crate::contracts::refresh::RefreshContractParameters;
-    use crate::oracle_config::BASE_FEE;
-    use crate::oracle_state::DataSourceError;
     use crate::oracle_types::EpochLength;
     use crate::pool_commands::test_utils::generate_token_ids;
     use crate::pool_commands::test_utils::BuybackBoxSourceMock;
@@ -400,64 +557,12 @@ mod tests {
         find_input_boxes, make_datapoint_box, make_pool_box, make_wallet_unspent_box, PoolBoxMock,
         WalletDataMock,
     };
+    use crate::pool_commands::test_utils::{make_refresh_box, DatapointSourceMock, RefreshBoxMock};
     use crate::pool_config::TokenIds;
     use crate::spec_token::TokenIdKind;
 
     use super::*;
 
-    #[derive(Clone)]
-    struct RefreshBoxMock {
-        refresh_box: RefreshBoxWrapper,
-    }
-
-    impl RefreshBoxSource for RefreshBoxMock {
-        fn get_refresh_box(&self) -> std::result::Result<RefreshBoxWrapper, DataSourceError> {
-            Ok(self.refresh_box.clone())
-        }
-    }
-
-    #[derive(Clone)]
-    struct DatapointSourceMock {
-        datapoints: Vec<PostedOracleBox>,
-    }
-
-    impl PostedDatapointBoxesSource for DatapointSourceMock {
-        fn get_posted_datapoint_boxes(
-            &self,
-        ) -> std::result::Result<Vec<PostedOracleBox>, DataSourceError> {
-            Ok(self.datapoints.clone())
-        }
-    }
-
-    fn make_refresh_box(
-        value: BoxValue,
-        inputs: &RefreshBoxWrapperInputs,
-        creation_height: BlockHeight,
-    ) -> RefreshBoxWrapper {
-        let tokens = vec![Token::from((
-            inputs.refresh_nft_token_id.token_id(),
-            1u64.try_into().unwrap(),
-        ))]
-        .try_into()
-        .unwrap();
-        RefreshBoxWrapper::new(
-            ErgoBox::new(
-                value,
-                RefreshContract::checked_load(&inputs.contract_inputs)
-                    .unwrap()
-                    .ergo_tree(),
-                Some(tokens),
-                NonMandatoryRegisters::empty(),
-                creation_height.0,
-                force_any_val::<TxId>(),
-                0,
-            )
-            .unwrap(),
-            inputs,
-        )
-        .unwrap()
-    }
-
     #[allow(clippy::too_many_arguments)]
     fn make_datapoint_boxes(
         pub_keys: Vec<EcPoint>,
@@ -514,11 +619,12 @@ mod tests {
             contract_inputs: refresh_contract_inputs,
         };
         let pool_box_epoch_id = EpochCounter(1);
-        let in_refresh_box = make_refresh_box(*BASE_FEE, &inputs, height - EpochLength(32));
+        let in_refresh_box =
+            make_refresh_box(SUGGESTED_TX_FEE(), &inputs, height - EpochLength(32));
         let in_pool_box = make_pool_box(
             200,
             pool_box_epoch_id,
-            *BASE_FEE,
+            SUGGESTED_TX_FEE(),
             height - EpochLength(32), // from previous epoch
             &pool_contract_parameters,
             &token_ids,
@@ -540,7 +646,7 @@ mod tests {
             oracle_pub_keys.clone(),
             vec![199, 70, 196, 197, 198, 200],
             pool_box_epoch_id,
-            BASE_FEE.checked_mul_u32(100).unwrap(),
+            SUGGESTED_TX_FEE().checked_mul_u32(100).unwrap(),
             height - EpochLength(9),
             &oracle_contract_parameters,
             &token_ids,
@@ -564,7 +670,7 @@ mod tests {
         .unwrap();
         let wallet_unspent_box = make_wallet_unspent_box(
             secret.public_image(),
-            BASE_FEE.checked_mul_u32(10000).unwrap(),
+            SUGGESTED_TX_FEE().checked_mul_u32(10000).unwrap(),
             None,
         );
         let wallet_mock = WalletDataMock {
@@ -585,6 +691,10 @@ mod tests {
             change_address.address(),
             &oracle_pub_key,
             None,
+            None,
+            None,
+            None,
+            &TxParams::default(),
         )
         .unwrap();
 
@@ -615,7 +725,7 @@ mod tests {
                 oracle_pub_keys.clone(),
                 vec![199, 70, 196, 197, 198, 200],
                 EpochCounter(pool_box_epoch_id.0 + 1),
-                BASE_FEE.checked_mul_u32(100).unwrap(),
+                SUGGESTED_TX_FEE().checked_mul_u32(100).unwrap(),
                 height - EpochLength(9),
                 &oracle_contract_parameters,
                 &token_ids,
@@ -632,6 +742,10 @@ mod tests {
             change_address.address(),
             &oracle_pub_key,
             None,
+            None,
+            None,
+            None,
+            &TxParams::default(),
         );
         dbg!(&wrong_epoch_res);
         assert!(matches!(
@@ -647,7 +761,7 @@ mod tests {
 
         let buyback_box = make_wallet_unspent_box(
             secret.public_image(),
-            *BASE_FEE,
+            SUGGESTED_TX_FEE(),
             Some(
                 vec![
                     Token {
@@ -665,7 +779,8 @@ mod tests {
         );
 
         let buyback_source = BuybackBoxSourceMock {
-            buyback_box: BuybackBoxWrapper::new(buyback_box, token_ids.reward_token_id.clone()),
+            buyback_box: BuybackBoxWrapper::new(buyback_box, token_ids.reward_token_id.clone())
+                .unwrap(),
         };
 
         let (action_with_buyback, _) = build_refresh_action(
@@ -681,6 +796,10 @@ mod tests {
             change_address.address(),
             &oracle_pub_key,
             Some(&buyback_source),
+            None,
+            None,
+            None,
+            &TxParams::default(),
         )
         .unwrap();
 
@@ -727,6 +846,59 @@ mod tests {
                 .as_u64(),
             &190,
             "reward tokens should be added to the pool box"
+        );
+
+        let (action_with_capped_buyback, _) = build_refresh_action(
+            &pool_box_mock,
+            &refresh_box_mock,
+            &(DatapointSourceMock {
+                datapoints: in_oracle_boxes.clone(),
+            }),
+            5,
+            MinDatapoints(4),
+            &wallet_mock,
+            height,
+            change_address.address(),
+            &oracle_pub_key,
+            Some(&buyback_source),
+            Some(40),
+            None,
+            None,
+            &TxParams::default(),
+        )
+        .unwrap();
+        let out_buyback_tokens = action_with_capped_buyback
+            .tx
+            .output_candidates
+            .get(2)
+            .unwrap()
+            .tokens
+            .clone()
+            .unwrap();
+        assert_eq!(
+            out_buyback_tokens.get(0).unwrap().token_id,
+            buyback_token_id
+        );
+        assert_eq!(
+            out_buyback_tokens.get(1).unwrap().amount.as_u64(),
+            &60,
+            "reward tokens above the cap should stay in the buyback box"
+        );
+        assert_eq!(
+            action_with_capped_buyback
+                .tx
+                .output_candidates
+                .get(0)
+                .unwrap()
+                .tokens
+                .as_ref()
+                .unwrap()
+                .get(1)
+                .unwrap()
+                .amount
+                .as_u64(),
+            &130,
+            "only the capped amount should be added to the pool box"
         )
     }
 
@@ -753,4 +925,79 @@ mod tests {
             vec![95, 96, 97, 98, 99]
         );
     }
+
+    #[test]
+    fn test_top_n_oracle_boxes_selection() {
+        assert_eq!(max_collectable_oracle_boxes(0, 2), 0);
+        assert_eq!(max_collectable_oracle_boxes(30, 2), 14);
+        assert_eq!(max_collectable_oracle_boxes(31, 2), 15);
+        assert_eq!(max_collectable_oracle_boxes(31, 3), 10);
+        let rates: Vec<Rate> = vec![90, 95, 96, 97, 98, 110]
+            .into_iter()
+            .map(Rate::from)
+            .collect();
+        // narrowest window of 4 is 95..98
+        assert_eq!(narrowest_rate_window(&rates, 4, 2), 1);
+        // our own box has to be collected
+        assert_eq!(narrowest_rate_window(&rates, 4, 0), 0);
+        assert_eq!(narrowest_rate_window(&rates, 4, 5), 2);
+        assert_eq!(narrowest_rate_window(&rates, 6, 3), 0);
+    }
+
+    #[test]
+    fn test_erg_rewards_value_accounting() {
+        let erg_rewards = ErgRewards {
+            oracle_reward_per_datapoint: 1_000_000,
+            collector_reward_per_datapoint: 500_000,
+        };
+        let min_box_value = BoxValue::SAFE_USER_MIN.as_u64();
+        assert_eq!(
+            max_erg_rewarded_oracle_boxes(min_box_value, &erg_rewards, BoxValue::SAFE_USER_MIN),
+            0
+        );
+        assert_eq!(
+            max_erg_rewarded_oracle_boxes(
+                min_box_value + 4_500_000,
+                &erg_rewards,
+                BoxValue::SAFE_USER_MIN
+            ),
+            3
+        );
+
+        let token_ids = generate_token_ids();
+        let my_pub_key = force_any_val::<EcPoint>();
+        let value = SUGGESTED_TX_FEE().checked_mul_u32(100).unwrap();
+        let in_oracle_boxes = make_datapoint_boxes(
+            vec![my_pub_key.clone(), force_any_val::<EcPoint>()],
+            vec![199, 200],
+            EpochCounter(1),
+            value,
+            BlockHeight(100),
+            &OracleContractParameters::default(),
+            &token_ids,
+        );
+        let out_oracle_boxes = build_out_oracle_boxes(
+            &in_oracle_boxes,
+            BlockHeight(101),
+            &my_pub_key,
+            1,
+            Some(&erg_rewards),
+        )
+        .unwrap();
+        assert_eq!(
+            *out_oracle_boxes[0].value.as_u64(),
+            value.as_u64() + 1_000_000 + 2 * 500_000
+        );
+        assert_eq!(
+            *out_oracle_boxes[1].value.as_u64(),
+            value.as_u64() + 1_000_000
+        );
+        // reward tokens are left untouched
+        for (in_ob, out_ob) in in_oracle_boxes.iter().zip(out_oracle_boxes) {
+            assert_eq!(
+                out_ob.tokens.unwrap().get(1).unwrap().amount,
+                in_ob.reward_token().amount
+            );
+        }
+    }
 }
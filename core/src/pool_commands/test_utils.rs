@@ -31,6 +31,9 @@ use crate::box_kind::OracleBoxWrapper;
 use crate::box_kind::OracleBoxWrapperInputs;
 use crate::box_kind::PoolBoxWrapper;
 use crate::box_kind::PoolBoxWrapperInputs;
+use crate::box_kind::PostedOracleBox;
+use crate::box_kind::RefreshBoxWrapper;
+use crate::box_kind::RefreshBoxWrapperInputs;
 use crate::box_kind::UpdateBoxWrapper;
 use crate::box_kind::VoteBallotBoxWrapper;
 use crate::contracts::oracle::OracleContract;
@@ -40,13 +43,21 @@ use crate::contracts::oracle::OracleContractParameters;
 use crate::contracts::pool::PoolContract;
 use crate::contracts::pool::PoolContractInputs;
 use crate::contracts::pool::PoolContractParameters;
+use crate::contracts::refresh::RefreshContract;
+use crate::datapoint_source::DataPointSource;
+use crate::datapoint_source::DataPointSourceError;
+use crate::datapoint_source::FetchedDatapoint;
 use crate::node_interface::SignTransactionWithInputs;
 use crate::oracle_state::BuybackBoxSource;
 use crate::oracle_state::LocalBallotBoxSource;
+use crate::oracle_state::PostedDatapointBoxesSource;
+use crate::oracle_state::RefreshBoxSource;
 use crate::oracle_state::UpdateBoxSource;
 use crate::oracle_state::VoteBallotBoxesSource;
 use crate::oracle_state::{DataSourceError, LocalDatapointBoxSource, PoolBoxSource};
+use crate::oracle_types::BlockHeight;
 use crate::oracle_types::EpochCounter;
+use crate::oracle_types::Rate;
 use crate::pool_config::TokenIds;
 use crate::spec_token::BallotTokenId;
 use crate::spec_token::OracleTokenId;
@@ -141,6 +152,85 @@ impl BuybackBoxSource for BuybackBoxSourceMock {
     }
 }
 
+#[derive(Clone)]
+pub(crate) struct RefreshBoxMock {
+    pub refresh_box: RefreshBoxWrapper,
+}
+
+impl RefreshBoxSource for RefreshBoxMock {
+    fn get_refresh_box(&self) -> std::result::Result<RefreshBoxWrapper, DataSourceError> {
+        Ok(self.refresh_box.clone())
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct DatapointSourceMock {
+    pub datapoints: Vec<PostedOracleBox>,
+}
+
+impl PostedDatapointBoxesSource for DatapointSourceMock {
+    fn get_posted_datapoint_boxes(
+        &self,
+    ) -> std::result::Result<Vec<PostedOracleBox>, DataSourceError> {
+        Ok(self.datapoints.clone())
+    }
+}
+
+pub(crate) fn make_refresh_box(
+    value: BoxValue,
+    inputs: &RefreshBoxWrapperInputs,
+    creation_height: BlockHeight,
+) -> RefreshBoxWrapper {
+    let tokens = vec![Token::from((
+        inputs.refresh_nft_token_id.token_id(),
+        1u64.try_into().unwrap(),
+    ))]
+    .try_into()
+    .unwrap();
+    RefreshBoxWrapper::new(
+        ErgoBox::new(
+            value,
+            RefreshContract::checked_load(&inputs.contract_inputs)
+                .unwrap()
+                .ergo_tree(),
+            Some(tokens),
+            NonMandatoryRegisters::empty(),
+            creation_height.0,
+            force_any_val::<TxId>(),
+            0,
+        )
+        .unwrap(),
+        inputs,
+    )
+    .unwrap()
+}
+
+#[derive(Debug)]
+pub(crate) struct MockDatapointSource {
+    pub datapoint: Rate,
+}
+
+impl DataPointSource for MockDatapointSource {
+    fn get_datapoint(&self) -> Result<Rate, DataPointSourceError> {
+        Ok(self.datapoint)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct MockSourcesDatapointSource {
+    pub fetched: FetchedDatapoint,
+}
+
+impl DataPointSource for MockSourcesDatapointSource {
+    fn get_datapoint(&self) -> Result<Rate, DataPointSourceError> {
+        Ok(self.fetched.rate)
+    }
+
+    fn fetch_datapoint(&self) -> Result<FetchedDatapoint, DataPointSourceError> {
+        Ok(self.fetched.clone())
+    }
+}
+
 pub(crate) fn make_pool_box(
     datapoint: i64,
     epoch_counter: EpochCounter,
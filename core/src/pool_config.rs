@@ -14,10 +14,13 @@ use crate::box_kind::RefreshBoxWrapperInputs;
 use crate::box_kind::UpdateBoxWrapperInputs;
 use crate::cli_commands::bootstrap::BootstrapConfig;
 use crate::contracts::ballot::BallotContractError;
+use crate::contracts::known_templates::find_known_template;
+use crate::contracts::known_templates::ContractKind;
 use crate::contracts::oracle::OracleContractError;
 use crate::contracts::pool::PoolContractError;
 use crate::contracts::refresh::RefreshContractError;
 use crate::contracts::update::UpdateContractError;
+use crate::djed::DjedConfig;
 use crate::spec_token::BallotTokenId;
 use crate::spec_token::BuybackTokenId;
 use crate::spec_token::OracleTokenId;
@@ -28,12 +31,6 @@ use crate::spec_token::UpdateTokenId;
 
 pub const DEFAULT_POOL_CONFIG_FILE_NAME: &str = "pool_config.yaml";
 pub static POOL_CONFIG_FILE_PATH: sync::OnceCell<PathBuf> = sync::OnceCell::new();
-lazy_static! {
-    pub static ref POOL_CONFIG: PoolConfig = PoolConfig::load().unwrap();
-    pub static ref POOL_CONFIG_OPT: Result<PoolConfig, String> =
-        PoolConfig::load().map_err(|e| e.to_string());
-}
-
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(
     try_from = "crate::serde::PoolConfigSerde",
@@ -48,6 +45,33 @@ pub struct PoolConfig {
     pub ballot_box_wrapper_inputs: BallotBoxWrapperInputs,
     pub token_ids: TokenIds,
     pub buyback_token_id: Option<BuybackTokenId>,
+    /// Maximum amount of reward tokens moved from the buyback box to the pool box on a refresh.
+    /// All of them are moved if not set.
+    pub buyback_max_reward_tokens_per_refresh: Option<u64>,
+    /// Oracle rewards paid in ERG from the pool box instead of reward tokens
+    pub erg_rewards: Option<ErgRewards>,
+    /// Djed bank the pool rate is published for, checked against the fetched datapoints
+    pub djed: Option<DjedConfig>,
+}
+
+/// Per-datapoint rewards in nanoERG paid from the pool box value on a refresh. Only for custom pool
+/// and refresh contracts paying ERG rewards, the EIP-23 contracts pay reward tokens. oracle-core
+/// has no parameters of such contracts, this only sets the value accounting of the refresh tx and
+/// is checked against the contracts only in refusing the known EIP-23 templates
+/// (`check_erg_rewards`).
+#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ErgRewards {
+    /// nanoERG added to every collected datapoint box
+    pub oracle_reward_per_datapoint: u64,
+    /// nanoERG added to the collector's box for each collected datapoint
+    pub collector_reward_per_datapoint: u64,
+}
+
+impl ErgRewards {
+    /// nanoERG the pool box pays for each collected datapoint
+    pub fn pool_reward_per_datapoint(&self) -> u64 {
+        self.oracle_reward_per_datapoint + self.collector_reward_per_datapoint
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone)]
@@ -56,6 +80,11 @@ pub enum PredefinedDataPointSource {
     NanoErgUsd,
     NanoErgXau,
     NanoAdaUsd,
+    /// nanoERG per 1 SigUSD cent (the smallest unit), as read by a Djed bank on Ergo
+    NanoErgSigUsd,
+    /// 10^-6 milkADA per 1 USD for the Djed deployment on Milkomeda C1, the median of the
+    /// exchanges and the Chainlink and Pyth ADA/USD feeds
+    MicroMilkAdaUsd,
 }
 
 /// Holds the token ids of every important token used by the oracle pool.
@@ -105,6 +134,11 @@ pub enum PoolConfigError {
     UpdateContractErro(#[from] UpdateContractError),
     #[error("Ballot contract error: {0}")]
     BallotContractErro(#[from] BallotContractError),
+    #[error(
+        "erg_rewards is set but the {0} contract is the EIP-23 one paying reward tokens, \
+        erg_rewards is only for custom contracts paying the rewards in ERG"
+    )]
+    ErgRewardsWithRewardTokenContract(ContractKind),
 }
 
 impl PoolConfig {
@@ -151,13 +185,51 @@ impl PoolConfig {
             update_box_wrapper_inputs,
             token_ids,
             buyback_token_id: None,
+            buyback_max_reward_tokens_per_refresh: None,
+            erg_rewards: None,
+            djed: None,
         })
     }
 
-    fn load() -> Result<Self, anyhow::Error> {
+    /// `erg_rewards` can't be set for the EIP-23 pool and refresh contracts, which pay reward
+    /// tokens and would reject a refresh tx paying ERG
+    pub fn check_erg_rewards(&self) -> Result<(), PoolConfigError> {
+        if self.erg_rewards.is_none() {
+            return Ok(());
+        }
+        let contracts = [
+            (
+                ContractKind::Pool,
+                self.pool_box_wrapper_inputs
+                    .contract_inputs
+                    .contract_parameters()
+                    .ergo_tree_bytes(),
+            ),
+            (
+                ContractKind::Refresh,
+                self.refresh_box_wrapper_inputs
+                    .contract_inputs
+                    .contract_parameters()
+                    .ergo_tree_bytes(),
+            ),
+        ];
+        for (kind, ergo_tree_bytes) in contracts {
+            if find_known_template(kind, &ergo_tree_bytes).is_some() {
+                return Err(PoolConfigError::ErgRewardsWithRewardTokenContract(kind));
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the config from `POOL_CONFIG_FILE_PATH`
+    pub fn load() -> Result<Self, anyhow::Error> {
         let config_file_path = POOL_CONFIG_FILE_PATH
             .get()
             .ok_or_else(|| anyhow!("Pool config file path not set"))?;
+        Self::load_from_path(config_file_path)
+    }
+
+    pub fn load_from_path(config_file_path: &Path) -> Result<Self, anyhow::Error> {
         Self::load_from_str(&std::fs::read_to_string(config_file_path)?)
     }
 
@@ -185,4 +257,24 @@ mod tests {
         let s = serde_yaml::to_string(&token_ids).unwrap();
         assert_eq!(token_ids, serde_yaml::from_str::<TokenIds>(&s).unwrap());
     }
+
+    #[test]
+    fn erg_rewards_rejected_for_eip23_contracts() {
+        let pool_config =
+            PoolConfig::create(BootstrapConfig::default(), generate_token_ids()).unwrap();
+        assert!(pool_config.check_erg_rewards().is_ok());
+        let erg_rewards_config = PoolConfig {
+            erg_rewards: Some(ErgRewards {
+                oracle_reward_per_datapoint: 1_000_000,
+                collector_reward_per_datapoint: 1_000_000,
+            }),
+            ..pool_config
+        };
+        assert!(matches!(
+            erg_rewards_config.check_erg_rewards(),
+            Err(PoolConfigError::ErgRewardsWithRewardTokenContract(
+                ContractKind::Pool
+            ))
+        ));
+    }
 }
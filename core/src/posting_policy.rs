@@ -0,0 +1,289 @@
+//! Policies deciding whether the local oracle publishes a datapoint in an epoch
+//! (`posting_policy` in the oracle config).
+//!
+//! The state machine of [`crate::state::process`] keeps the schedule (the first datapoint, the
+//! publish height of the epoch, republishing a stale datapoint, the refresh) and asks the policy at
+//! every publish opportunity whether to take it. The last datapoint needed for a refresh is always
+//! published. A policy needing the current rate gets it from [`CurrentRate`], fetched once per main
+//! loop iteration and reused by the publish action.
+
+use once_cell::unsync::OnceCell;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::datapoint_source::DataPointSource;
+use crate::datapoint_source::DataPointSourceError;
+use crate::datapoint_source::FetchedDatapoint;
+use crate::oracle_state::LiveEpochState;
+use crate::oracle_state::LocalDatapointState;
+use crate::oracle_types::BlockHeight;
+use crate::oracle_types::Rate;
+use crate::state::EpochParams;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PostingPolicyConfig {
+    /// Publish in every epoch
+    #[default]
+    Epoch,
+    /// Publish only when the current rate is at least `min_deviation_percent` away from the pool
+    /// rate
+    Deviation { min_deviation_percent: f64 },
+    /// Publish only when the local oracle box is at least `max_age_blocks` old
+    Heartbeat { max_age_blocks: u32 },
+    /// Publish when any of the policies does
+    Composite { policies: Vec<PostingPolicyConfig> },
+}
+
+impl PostingPolicyConfig {
+    pub fn build(&self) -> Box<dyn PostingPolicy> {
+        match self {
+            PostingPolicyConfig::Epoch => Box::new(EpochPolicy),
+            PostingPolicyConfig::Deviation {
+                min_deviation_percent,
+            } => Box::new(DeviationPolicy {
+                min_deviation_percent: *min_deviation_percent,
+            }),
+            PostingPolicyConfig::Heartbeat { max_age_blocks } => Box::new(HeartbeatPolicy {
+                max_age_blocks: *max_age_blocks,
+            }),
+            PostingPolicyConfig::Composite { policies } => Box::new(CompositePolicy {
+                policies: policies.iter().map(PostingPolicyConfig::build).collect(),
+            }),
+        }
+    }
+}
+
+/// State of the pool at a publish opportunity
+pub struct PostingContext<'a> {
+    pub live_epoch: &'a LiveEpochState,
+    pub epoch_params: &'a EpochParams,
+    pub current_height: BlockHeight,
+    pub current_rate: &'a CurrentRate<'a>,
+}
+
+pub trait PostingPolicy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether to publish the local datapoint at this opportunity
+    fn should_publish(&self, ctx: &PostingContext) -> bool;
+}
+
+/// Rate of the datapoint source, fetched the first time a policy asks for it
+pub struct CurrentRate<'a> {
+    source: &'a dyn DataPointSource,
+    rate: OnceCell<Option<FetchedDatapoint>>,
+}
+
+impl<'a> CurrentRate<'a> {
+    pub fn new(source: &'a dyn DataPointSource) -> Self {
+        CurrentRate {
+            source,
+            rate: OnceCell::new(),
+        }
+    }
+
+    /// The current rate, `None` if the fetch failed
+    pub fn get(&self) -> Option<Rate> {
+        self.rate
+            .get_or_init(|| match self.source.fetch_datapoint() {
+                Ok(fetched) => Some(fetched),
+                Err(e) => {
+                    log::warn!("Failed to fetch the rate for the posting policy: {}", e);
+                    None
+                }
+            })
+            .as_ref()
+            .map(|fetched| fetched.rate)
+    }
+
+    /// The datapoint if a policy fetched it
+    pub fn fetched(&self) -> Option<FetchedDatapoint> {
+        self.rate.get().cloned().flatten()
+    }
+}
+
+/// Datapoint source of the datapoint already fetched for the posting policy
+pub struct PrefetchedRate(pub FetchedDatapoint);
+
+impl DataPointSource for PrefetchedRate {
+    fn get_datapoint(&self) -> Result<Rate, DataPointSourceError> {
+        Ok(self.0.rate)
+    }
+
+    fn fetch_datapoint(&self) -> Result<FetchedDatapoint, DataPointSourceError> {
+        Ok(self.0.clone())
+    }
+}
+
+pub struct EpochPolicy;
+
+impl PostingPolicy for EpochPolicy {
+    fn name(&self) -> &'static str {
+        "epoch"
+    }
+
+    fn should_publish(&self, _ctx: &PostingContext) -> bool {
+        true
+    }
+}
+
+pub struct DeviationPolicy {
+    pub min_deviation_percent: f64,
+}
+
+impl PostingPolicy for DeviationPolicy {
+    fn name(&self) -> &'static str {
+        "deviation"
+    }
+
+    fn should_publish(&self, ctx: &PostingContext) -> bool {
+        // without the rate the publish action reports the failing sources
+        let rate = match ctx.current_rate.get() {
+            Some(rate) => i64::from(rate) as f64,
+            None => return true,
+        };
+        let pool_rate = ctx.live_epoch.latest_pool_datapoint as f64;
+        if pool_rate == 0.0 {
+            return true;
+        }
+        (rate - pool_rate).abs() * 100.0 / pool_rate >= self.min_deviation_percent
+    }
+}
+
+pub struct HeartbeatPolicy {
+    pub max_age_blocks: u32,
+}
+
+impl PostingPolicy for HeartbeatPolicy {
+    fn name(&self) -> &'static str {
+        "heartbeat"
+    }
+
+    fn should_publish(&self, ctx: &PostingContext) -> bool {
+        let box_height = match &ctx.live_epoch.local_datapoint_box_state {
+            Some(LocalDatapointState::Collected { height })
+            | Some(LocalDatapointState::Posted { height, .. }) => *height,
+            None => return true,
+        };
+        ctx.current_height.0.saturating_sub(box_height.0) >= self.max_age_blocks
+    }
+}
+
+pub struct CompositePolicy {
+    pub policies: Vec<Box<dyn PostingPolicy>>,
+}
+
+impl PostingPolicy for CompositePolicy {
+    fn name(&self) -> &'static str {
+        "composite"
+    }
+
+    fn should_publish(&self, ctx: &PostingContext) -> bool {
+        self.policies
+            .iter()
+            .any(|policy| policy.should_publish(ctx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::oracle_types::EpochCounter;
+    use crate::oracle_types::EpochLength;
+    use crate::oracle_types::MinDatapoints;
+    use crate::pool_commands::test_utils::MockDatapointSource;
+
+    use super::*;
+
+    fn should_publish(config: PostingPolicyConfig, rate: i64, height: u32) -> bool {
+        let live_epoch = LiveEpochState {
+            pool_box_epoch_id: EpochCounter(5),
+            local_datapoint_box_state: Some(LocalDatapointState::Collected {
+                height: BlockHeight(1000),
+            }),
+            latest_pool_datapoint: 200,
+            latest_pool_box_height: BlockHeight(1000),
+            local_refresh_rank: None,
+            other_posted_datapoint_heights: Vec::new(),
+        };
+        let epoch_params = EpochParams {
+            epoch_length: EpochLength(30),
+            buffer_length: 4,
+            min_data_points: MinDatapoints(4),
+            max_deviation_percent: 5,
+        };
+        let source = MockDatapointSource {
+            datapoint: rate.into(),
+        };
+        let current_rate = CurrentRate::new(&source);
+        config.build().should_publish(&PostingContext {
+            live_epoch: &live_epoch,
+            epoch_params: &epoch_params,
+            current_height: BlockHeight(height),
+            current_rate: &current_rate,
+        })
+    }
+
+    #[test]
+    fn test_deviation_and_heartbeat() {
+        let deviation = || PostingPolicyConfig::Deviation {
+            min_deviation_percent: 1.0,
+        };
+        let heartbeat = || PostingPolicyConfig::Heartbeat {
+            max_age_blocks: 100,
+        };
+        assert!(should_publish(PostingPolicyConfig::Epoch, 200, 1016));
+        assert!(!should_publish(deviation(), 201, 1016));
+        assert!(should_publish(deviation(), 202, 1016));
+        assert!(should_publish(deviation(), 198, 1016));
+        assert!(!should_publish(heartbeat(), 200, 1099));
+        assert!(should_publish(heartbeat(), 200, 1100));
+        let composite = || PostingPolicyConfig::Composite {
+            policies: vec![deviation(), heartbeat()],
+        };
+        assert!(!should_publish(composite(), 201, 1016));
+        assert!(should_publish(composite(), 202, 1016));
+        assert!(should_publish(composite(), 201, 1100));
+    }
+
+    #[test]
+    fn test_rate_fetched_once() {
+        let source = MockDatapointSource {
+            datapoint: 250.into(),
+        };
+        let current_rate = CurrentRate::new(&source);
+        assert_eq!(current_rate.fetched(), None);
+        assert_eq!(current_rate.get(), Some(250.into()));
+        assert_eq!(
+            current_rate.fetched().map(|fetched| fetched.rate),
+            Some(250.into())
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let config: PostingPolicyConfig = serde_yaml::from_str(
+            "type: composite
+policies:
+  - type: deviation
+    min_deviation_percent: 0.5
+  - type: heartbeat
+    max_age_blocks: 720
+",
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            PostingPolicyConfig::Composite {
+                policies: vec![
+                    PostingPolicyConfig::Deviation {
+                        min_deviation_percent: 0.5
+                    },
+                    PostingPolicyConfig::Heartbeat {
+                        max_age_blocks: 720
+                    },
+                ]
+            }
+        );
+    }
+}
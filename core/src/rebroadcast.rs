@@ -0,0 +1,553 @@
+//! Re-broadcast and fee-bump of submitted publish/refresh txs that do not get confirmed.
+//!
+//! A submitted tx can be dropped from the node mempool (e.g. on a node restart or when the pool is
+//! full) without ever being included in a block. The tracker keeps the signed txs submitted by the
+//! main loop and, once a tx is neither confirmed nor in the mempool for `rebroadcast_after_blocks`
+//! blocks, submits it again if all of its inputs are still unspent. If any input was spent the tx
+//! is dropped, and the main loop rebuilds the action against fresh inputs on its next iteration
+//! since the pool state on chain still calls for it.
+//!
+//! During congestion a tx can also stay in the mempool for too long. With `fee_bump` configured a
+//! tx still in the mempool after `after_blocks` blocks is replaced by a tx spending the same inputs
+//! with a higher fee, taken from the change output, up to `max_fee`. A tracked tx spending outputs
+//! of the replaced tx (chained refresh) becomes invalid and is dropped, to be rebuilt by the main
+//! loop.
+
+use std::collections::HashSet;
+
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::Transaction;
+use ergo_lib::chain::transaction::TxId;
+use ergo_lib::chain::transaction::UnsignedInput;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValueError;
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBoxCandidate;
+use ergo_lib::ergotree_ir::ergo_tree::ErgoTree;
+use ergo_lib::ergotree_ir::serialization::SigmaParsingError;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
+use ergo_lib::wallet::miner_fee::MINERS_FEE_ADDRESS;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::audit_log::TxConfirmationSource;
+use crate::metrics;
+use crate::node_interface::node_api::NodeApiError;
+use crate::oracle_types::BlockHeight;
+
+pub const DEFAULT_REBROADCAST_AFTER_BLOCKS: u32 = 3;
+
+/// Number of times a tx is re-broadcast before giving up on it
+const MAX_REBROADCASTS: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FeeBumpConfig {
+    /// Blocks a tx may stay in the mempool before its fee is raised
+    pub after_blocks: u32,
+    /// Fee increase per bump in percent of the current fee
+    pub increase_percent: u64,
+    /// Maximum fee in nanoERG a bumped tx may pay
+    pub max_fee: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum FeeBumpError {
+    #[error("no miner fee output in the tx")]
+    NoFeeOutput,
+    #[error("no change output in the tx to take the fee increase from")]
+    NoChangeOutput,
+    #[error("fee {0} is already at the configured maximum")]
+    AtMaxFee(u64),
+    #[error("change output value {0} is too low to pay the fee increase {1}")]
+    InsufficientChange(u64, u64),
+    #[error("box value error: {0}")]
+    BoxValue(#[from] BoxValueError),
+    #[error("sigma parsing error: {0}")]
+    SigmaParsing(#[from] SigmaParsingError),
+    #[error("sigma serialization error: {0}")]
+    SigmaSerialization(#[from] SigmaSerializationError),
+    #[error("node api error: {0}")]
+    NodeApi(#[from] NodeApiError),
+}
+
+pub trait RebroadcastNode: TxConfirmationSource {
+    fn is_tx_in_mempool(&self, tx_id: &str) -> Result<bool, NodeApiError>;
+    /// Whether the box is unspent, counting the mempool
+    fn is_box_unspent(&self, box_id: &str) -> Result<bool, NodeApiError>;
+    fn resubmit_transaction(&self, tx: &Transaction) -> Result<TxId, NodeApiError>;
+    fn sign_and_submit(&self, tx: &UnsignedTransaction) -> Result<Transaction, NodeApiError>;
+}
+
+#[derive(Debug)]
+struct TrackedTx {
+    action: &'static str,
+    tx: Transaction,
+    submitted_height: BlockHeight,
+    rebroadcasts: u32,
+}
+
+#[derive(Debug)]
+pub enum TxCheck {
+    Confirmed,
+    Pending,
+    Rebroadcast,
+    /// Replaced by a tx with a higher fee, the outputs of the old tx will never exist
+    FeeBumped {
+        replaced_outputs: Vec<BoxId>,
+    },
+    InputsSpent,
+    /// Spends outputs of a tx replaced by a fee bump
+    ParentReplaced,
+    GaveUp,
+}
+
+pub struct RebroadcastTracker {
+    rebroadcast_after_blocks: u32,
+    fee_bump: Option<FeeBumpConfig>,
+    txs: Vec<TrackedTx>,
+}
+
+impl RebroadcastTracker {
+    /// `rebroadcast_after_blocks` of 0 disables re-broadcasting, `fee_bump` of `None` disables
+    /// fee bumps
+    pub fn new(rebroadcast_after_blocks: u32, fee_bump: Option<FeeBumpConfig>) -> Self {
+        RebroadcastTracker {
+            rebroadcast_after_blocks,
+            fee_bump,
+            txs: Vec::new(),
+        }
+    }
+
+    pub fn track(&mut self, action: &'static str, txs: Vec<Transaction>, height: BlockHeight) {
+        if self.rebroadcast_after_blocks == 0 && self.fee_bump.is_none() {
+            return;
+        }
+        for tx in txs {
+            self.txs.push(TrackedTx {
+                action,
+                tx,
+                submitted_height: height,
+                rebroadcasts: 0,
+            });
+        }
+    }
+
+    /// Check the tracked txs in submission order (a chained refresh is checked after its
+    /// publish tx), re-broadcasting the lost ones and bumping the fee of the stuck ones.
+    /// `change_ergo_tree` is the ergo tree of the wallet change address.
+    pub fn check(
+        &mut self,
+        node: &dyn RebroadcastNode,
+        height: BlockHeight,
+        change_ergo_tree: &ErgoTree,
+    ) -> Vec<TxCheck> {
+        let mut results = Vec::new();
+        let mut still_tracked = Vec::new();
+        let mut replaced_outputs: HashSet<BoxId> = HashSet::new();
+        for mut tracked in std::mem::take(&mut self.txs) {
+            let res = if tracked
+                .tx
+                .inputs
+                .iter()
+                .any(|input| replaced_outputs.contains(&input.box_id))
+            {
+                log::info!(
+                    "{} tx {} spends outputs of a replaced tx, the action will be rebuilt",
+                    tracked.action,
+                    String::from(tracked.tx.id())
+                );
+                TxCheck::ParentReplaced
+            } else {
+                match self.check_tx(node, &mut tracked, height, change_ergo_tree) {
+                    Ok(res) => res,
+                    Err(e) => {
+                        log::debug!(
+                            "Failed to check {} tx {}: {}",
+                            tracked.action,
+                            String::from(tracked.tx.id()),
+                            e
+                        );
+                        TxCheck::Pending
+                    }
+                }
+            };
+            match &res {
+                TxCheck::Pending | TxCheck::Rebroadcast => still_tracked.push(tracked),
+                TxCheck::FeeBumped {
+                    replaced_outputs: outputs,
+                } => {
+                    replaced_outputs.extend(outputs.iter().copied());
+                    still_tracked.push(tracked);
+                }
+                TxCheck::Confirmed
+                | TxCheck::InputsSpent
+                | TxCheck::ParentReplaced
+                | TxCheck::GaveUp => (),
+            }
+            results.push(res);
+        }
+        self.txs = still_tracked;
+        results
+    }
+
+    fn check_tx(
+        &self,
+        node: &dyn RebroadcastNode,
+        tracked: &mut TrackedTx,
+        height: BlockHeight,
+        change_ergo_tree: &ErgoTree,
+    ) -> Result<TxCheck, NodeApiError> {
+        let tx_id = String::from(tracked.tx.id());
+        if node.get_tx_inclusion_height(&tx_id)?.is_some() {
+            return Ok(TxCheck::Confirmed);
+        }
+        if node.is_tx_in_mempool(&tx_id)? {
+            return match &self.fee_bump {
+                Some(config)
+                    if height.0
+                        >= tracked
+                            .submitted_height
+                            .0
+                            .saturating_add(config.after_blocks) =>
+                {
+                    Ok(replace_with_bumped_fee(
+                        node,
+                        tracked,
+                        config,
+                        height,
+                        change_ergo_tree,
+                    ))
+                }
+                _ => Ok(TxCheck::Pending),
+            };
+        }
+        if self.rebroadcast_after_blocks == 0 {
+            log::warn!(
+                "{} tx {} dropped from the mempool, re-broadcast is disabled",
+                tracked.action,
+                tx_id
+            );
+            return Ok(TxCheck::GaveUp);
+        }
+        if height.0
+            < tracked
+                .submitted_height
+                .0
+                .saturating_add(self.rebroadcast_after_blocks)
+        {
+            return Ok(TxCheck::Pending);
+        }
+        for input in tracked.tx.inputs.iter() {
+            if !node.is_box_unspent(&String::from(input.box_id))? {
+                log::info!(
+                    "{} tx {} is lost and its inputs are spent, the action will be rebuilt",
+                    tracked.action,
+                    tx_id
+                );
+                return Ok(TxCheck::InputsSpent);
+            }
+        }
+        if tracked.rebroadcasts >= MAX_REBROADCASTS {
+            log::warn!(
+                "{} tx {} is still not confirmed after {} re-broadcasts, giving up",
+                tracked.action,
+                tx_id,
+                tracked.rebroadcasts
+            );
+            return Ok(TxCheck::GaveUp);
+        }
+        log::warn!(
+            "{} tx {} is not confirmed nor in the mempool since height {}, re-broadcasting",
+            tracked.action,
+            tx_id,
+            tracked.submitted_height
+        );
+        node.resubmit_transaction(&tracked.tx)?;
+        metrics::TXS_REBROADCAST
+            .with_label_values(&[tracked.action])
+            .inc();
+        tracked.submitted_height = height;
+        tracked.rebroadcasts += 1;
+        Ok(TxCheck::Rebroadcast)
+    }
+}
+
+/// Replace the tracked tx with a tx paying a higher fee, keeping it as is if the fee cannot be
+/// raised
+fn replace_with_bumped_fee(
+    node: &dyn RebroadcastNode,
+    tracked: &mut TrackedTx,
+    config: &FeeBumpConfig,
+    height: BlockHeight,
+    change_ergo_tree: &ErgoTree,
+) -> TxCheck {
+    let old_tx_id = String::from(tracked.tx.id());
+    let res = bump_fee(&tracked.tx, config, change_ergo_tree).and_then(|unsigned_tx| {
+        node.sign_and_submit(&unsigned_tx)
+            .map_err(FeeBumpError::from)
+    });
+    match res {
+        Ok(new_tx) => {
+            log::warn!(
+                "{} tx {} is stuck in the mempool since height {}, replaced by tx {} with a higher fee",
+                tracked.action,
+                old_tx_id,
+                tracked.submitted_height,
+                String::from(new_tx.id())
+            );
+            metrics::TXS_FEE_BUMPED
+                .with_label_values(&[tracked.action])
+                .inc();
+            let replaced_outputs = tracked.tx.outputs.iter().map(|b| b.box_id()).collect();
+            tracked.tx = new_tx;
+            tracked.submitted_height = height;
+            TxCheck::FeeBumped { replaced_outputs }
+        }
+        Err(e) => {
+            log::warn!(
+                "{} tx {} is stuck in the mempool, cannot bump its fee: {}",
+                tracked.action,
+                old_tx_id,
+                e
+            );
+            TxCheck::Pending
+        }
+    }
+}
+
+/// Unsigned tx spending the same inputs as `tx` and paying a higher fee taken from its change
+/// output
+pub fn bump_fee(
+    tx: &Transaction,
+    config: &FeeBumpConfig,
+    change_ergo_tree: &ErgoTree,
+) -> Result<UnsignedTransaction, FeeBumpError> {
+    let fee_ergo_tree = MINERS_FEE_ADDRESS.script()?;
+    let outputs = tx.outputs.as_vec();
+    let fee_index = outputs
+        .iter()
+        .rposition(|b| b.ergo_tree == fee_ergo_tree)
+        .ok_or(FeeBumpError::NoFeeOutput)?;
+    let change_index = outputs
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| b.ergo_tree == *change_ergo_tree && b.tokens.is_none())
+        .max_by_key(|(_, b)| *b.value.as_u64())
+        .map(|(i, _)| i)
+        .ok_or(FeeBumpError::NoChangeOutput)?;
+    let fee = *outputs[fee_index].value.as_u64();
+    let increase = (fee.saturating_mul(config.increase_percent) / 100).max(1);
+    let new_fee = fee.saturating_add(increase).min(config.max_fee);
+    if new_fee <= fee {
+        return Err(FeeBumpError::AtMaxFee(fee));
+    }
+    let change = *outputs[change_index].value.as_u64();
+    let increase = new_fee - fee;
+    if change < increase + *BoxValue::SAFE_USER_MIN.as_u64() {
+        return Err(FeeBumpError::InsufficientChange(change, increase));
+    }
+    let new_fee_value = BoxValue::try_from(new_fee)?;
+    let new_change_value = BoxValue::try_from(change - increase)?;
+    let output_candidates = tx.outputs.clone().mapped(|b| {
+        let index = b.index as usize;
+        let mut candidate = ErgoBoxCandidate::from(b);
+        if index == fee_index {
+            candidate.value = new_fee_value;
+        } else if index == change_index {
+            candidate.value = new_change_value;
+        }
+        candidate
+    });
+    let inputs = tx
+        .inputs
+        .clone()
+        .mapped(|input| UnsignedInput::new(input.box_id, input.spending_proof.extension));
+    Ok(UnsignedTransaction::new(
+        inputs,
+        tx.data_inputs.clone(),
+        output_candidates,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+
+    use ergo_lib::chain::transaction::Input;
+    use ergo_lib::chain::transaction::TxIoVec;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::prover::ProofBytes;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::prover::ProverResult;
+    use ergo_lib::ergotree_ir::chain::address::Address;
+    use ergo_lib::ergotree_ir::chain::ergo_box::box_builder::ErgoBoxCandidateBuilder;
+    use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct NodeMock {
+        mempool: RefCell<HashSet<String>>,
+        spent_boxes: RefCell<HashSet<String>>,
+        submitted: Cell<u32>,
+    }
+
+    impl TxConfirmationSource for NodeMock {
+        fn get_tx_inclusion_height(&self, _tx_id: &str) -> Result<Option<u32>, NodeApiError> {
+            Ok(None)
+        }
+    }
+
+    impl RebroadcastNode for NodeMock {
+        fn is_tx_in_mempool(&self, tx_id: &str) -> Result<bool, NodeApiError> {
+            Ok(self.mempool.borrow().contains(tx_id))
+        }
+
+        fn is_box_unspent(&self, box_id: &str) -> Result<bool, NodeApiError> {
+            Ok(!self.spent_boxes.borrow().contains(box_id))
+        }
+
+        fn resubmit_transaction(&self, tx: &Transaction) -> Result<TxId, NodeApiError> {
+            self.submitted.set(self.submitted.get() + 1);
+            self.mempool.borrow_mut().insert(String::from(tx.id()));
+            Ok(tx.id())
+        }
+
+        fn sign_and_submit(&self, tx: &UnsignedTransaction) -> Result<Transaction, NodeApiError> {
+            let signed_tx = Transaction::new(
+                tx.inputs.clone().mapped(|input| {
+                    Input::new(
+                        input.box_id,
+                        ProverResult {
+                            proof: ProofBytes::Empty,
+                            extension: input.extension,
+                        },
+                    )
+                }),
+                tx.data_inputs.clone(),
+                tx.output_candidates.clone(),
+            )
+            .unwrap();
+            self.resubmit_transaction(&signed_tx)?;
+            Ok(signed_tx)
+        }
+    }
+
+    fn change_ergo_tree() -> ErgoTree {
+        Address::P2Pk(force_any_val::<ProveDlog>())
+            .script()
+            .unwrap()
+    }
+
+    fn tx_with_fee(change_ergo_tree: &ErgoTree, fee: u64, change: u64) -> Transaction {
+        let height = 100;
+        let fee_box = ErgoBoxCandidateBuilder::new(
+            BoxValue::try_from(fee).unwrap(),
+            MINERS_FEE_ADDRESS.script().unwrap(),
+            height,
+        )
+        .build()
+        .unwrap();
+        let change_box = ErgoBoxCandidateBuilder::new(
+            BoxValue::try_from(change).unwrap(),
+            change_ergo_tree.clone(),
+            height,
+        )
+        .build()
+        .unwrap();
+        Transaction::new(
+            TxIoVec::from_vec(vec![force_any_val::<Input>()]).unwrap(),
+            None,
+            TxIoVec::from_vec(vec![change_box, fee_box]).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rebroadcast_lost_tx() {
+        let tx = force_any_val::<Transaction>();
+        let tx_id = String::from(tx.id());
+        let node = NodeMock::default();
+        let change_ergo_tree = change_ergo_tree();
+        let mut tracker = RebroadcastTracker::new(3, None);
+        tracker.track("refresh", vec![tx], BlockHeight(100));
+
+        node.mempool.borrow_mut().insert(tx_id.clone());
+        assert!(matches!(
+            tracker.check(&node, BlockHeight(101), &change_ergo_tree)[..],
+            [TxCheck::Pending]
+        ));
+        // dropped from the mempool, but too early to re-broadcast
+        node.mempool.borrow_mut().clear();
+        assert!(matches!(
+            tracker.check(&node, BlockHeight(102), &change_ergo_tree)[..],
+            [TxCheck::Pending]
+        ));
+        assert!(matches!(
+            tracker.check(&node, BlockHeight(103), &change_ergo_tree)[..],
+            [TxCheck::Rebroadcast]
+        ));
+        assert_eq!(node.submitted.get(), 1);
+
+        // lost again while its input got spent by another tx
+        node.mempool.borrow_mut().clear();
+        for input in tracker.txs[0].tx.inputs.iter() {
+            node.spent_boxes
+                .borrow_mut()
+                .insert(String::from(input.box_id));
+        }
+        assert!(matches!(
+            tracker.check(&node, BlockHeight(106), &change_ergo_tree)[..],
+            [TxCheck::InputsSpent]
+        ));
+        assert!(tracker.txs.is_empty());
+        assert_eq!(node.submitted.get(), 1);
+    }
+
+    #[test]
+    fn test_fee_bump_stuck_tx() {
+        let change_ergo_tree = change_ergo_tree();
+        let config = FeeBumpConfig {
+            after_blocks: 2,
+            increase_percent: 50,
+            max_fee: 2_500_000,
+        };
+        let tx = tx_with_fee(&change_ergo_tree, 1_000_000, 10_000_000);
+        let old_tx_id = String::from(tx.id());
+        let node = NodeMock::default();
+        node.mempool.borrow_mut().insert(old_tx_id.clone());
+        let mut tracker = RebroadcastTracker::new(0, Some(config));
+        tracker.track("publish_subsequent_datapoint", vec![tx], BlockHeight(100));
+
+        assert!(matches!(
+            tracker.check(&node, BlockHeight(101), &change_ergo_tree)[..],
+            [TxCheck::Pending]
+        ));
+        assert!(matches!(
+            tracker.check(&node, BlockHeight(102), &change_ergo_tree)[..],
+            [TxCheck::FeeBumped { .. }]
+        ));
+        let bumped = &tracker.txs[0].tx;
+        assert_ne!(String::from(bumped.id()), old_tx_id);
+        assert_eq!(*bumped.outputs.get(0).unwrap().value.as_u64(), 9_500_000);
+        assert_eq!(*bumped.outputs.get(1).unwrap().value.as_u64(), 1_500_000);
+
+        // the next bump is capped by max_fee, the one after that is not possible
+        tracker.check(&node, BlockHeight(104), &change_ergo_tree);
+        let bumped = &tracker.txs[0].tx;
+        assert_eq!(*bumped.outputs.get(1).unwrap().value.as_u64(), 2_250_000);
+        tracker.check(&node, BlockHeight(106), &change_ergo_tree);
+        let bumped = &tracker.txs[0].tx;
+        assert_eq!(*bumped.outputs.get(1).unwrap().value.as_u64(), 2_500_000);
+        assert!(matches!(
+            bump_fee(
+                bumped,
+                tracker.fee_bump.as_ref().unwrap(),
+                &change_ergo_tree
+            ),
+            Err(FeeBumpError::AtMaxFee(2_500_000))
+        ));
+    }
+}
@@ -0,0 +1,167 @@
+//! Masking of secrets and wallet addresses in the log output, so the logs can be shared for
+//! support as they are.
+//!
+//! The registered secrets (the node API key, the tokens and passwords of the notification
+//! backends and the history database connection string) are replaced with `***` wherever they appear, e.g. in the URL of a failed request.
+//! Ergo addresses (P2PK and P2SH, base58 strings of 40 to 60 chars) are shortened to their first
+//! and last 4 chars, which is enough to tell them apart. The long P2S addresses of the contracts
+//! are kept.
+
+use std::sync::RwLock;
+
+use log::Log;
+use log::Metadata;
+use log::Record;
+
+/// Replacement of a secret
+pub const REDACTED: &str = "***";
+
+/// Shorter values are not registered, masking them would garble unrelated text
+const MIN_SECRET_LEN: usize = 4;
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+const MIN_ADDRESS_LEN: usize = 40;
+const MAX_ADDRESS_LEN: usize = 60;
+
+/// Chars kept at both ends of a masked address
+const ADDRESS_VISIBLE_CHARS: usize = 4;
+
+lazy_static! {
+    static ref SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+}
+
+/// Mask the secret in the log output from now on
+pub fn register_secret(secret: &str) {
+    if secret.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap();
+    if !secrets.iter().any(|s| s == secret) {
+        secrets.push(secret.to_string());
+        // a secret containing another one is masked first
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+}
+
+/// The text with the registered secrets and the addresses masked
+pub fn redact(text: &str) -> String {
+    let secrets = SECRETS.read().unwrap();
+    redact_with(text, &secrets)
+}
+
+fn redact_with(text: &str, secrets: &[String]) -> String {
+    let mut text = text.to_string();
+    for secret in secrets {
+        if text.contains(secret.as_str()) {
+            text = text.replace(secret.as_str(), REDACTED);
+        }
+    }
+    mask_addresses(&text)
+}
+
+fn is_base58(c: char) -> bool {
+    BASE58_ALPHABET.contains(c)
+}
+
+/// A base58 word of an address length. Tx, box and token ids are lowercase hex, so an uppercase
+/// letter is required as well.
+fn looks_like_address(word: &str) -> bool {
+    (MIN_ADDRESS_LEN..=MAX_ADDRESS_LEN).contains(&word.len())
+        && word.chars().any(|c| c.is_ascii_uppercase())
+}
+
+fn push_word(masked: &mut String, word: &str) {
+    if looks_like_address(word) {
+        masked.push_str(&word[..ADDRESS_VISIBLE_CHARS]);
+        masked.push_str("...");
+        masked.push_str(&word[word.len() - ADDRESS_VISIBLE_CHARS..]);
+    } else {
+        masked.push_str(word);
+    }
+}
+
+fn mask_addresses(text: &str) -> String {
+    let mut masked = String::with_capacity(text.len());
+    let mut word_start = None;
+    for (i, c) in text.char_indices() {
+        match (is_base58(c), word_start) {
+            (true, None) => word_start = Some(i),
+            (false, Some(start)) => {
+                push_word(&mut masked, &text[start..i]);
+                word_start = None;
+                masked.push(c);
+            }
+            (false, None) => masked.push(c),
+            (true, Some(_)) => (),
+        }
+    }
+    if let Some(start) = word_start {
+        push_word(&mut masked, &text[start..]);
+    }
+    masked
+}
+
+/// Logger passing the records with their message redacted to the wrapped logger
+pub struct RedactingLogger<L> {
+    inner: L,
+}
+
+impl<L: Log> RedactingLogger<L> {
+    pub fn new(inner: L) -> Self {
+        RedactingLogger { inner }
+    }
+}
+
+impl<L: Log> Log for RedactingLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.enabled(record.metadata()) {
+            return;
+        }
+        let message = redact(&record.args().to_string());
+        self.inner.log(
+            &Record::builder()
+                .metadata(record.metadata().clone())
+                .args(format_args!("{}", message))
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build(),
+        );
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let secrets = vec!["123456:bot-token".to_string(), "node-key".to_string()];
+        assert_eq!(
+            redact_with(
+                "error sending request for url (https://api.telegram.org/bot123456:bot-token/sendMessage)",
+                &secrets
+            ),
+            "error sending request for url (https://api.telegram.org/bot***/sendMessage)"
+        );
+        assert_eq!(
+            redact_with(
+                "Oracle address: 9hEQHEMyY1K1vs79vJXFtNjr2dbQbtWXF99oVWGJ5c4xbcLdBsw, key node-key",
+                &secrets
+            ),
+            "Oracle address: 9hEQ...dBsw, key ***"
+        );
+        // ids are kept
+        let tx_id = "a8c7e4f2b9d1c3e5f7a9b2d4c6e8f1a3b5c7d9e2f4a6b8c1d3e5f7a9b2c4d6e8";
+        assert_eq!(redact_with(tx_id, &secrets), tx_id);
+    }
+}
@@ -2,18 +2,21 @@ use crate::address_util::AddressUtilError;
 use crate::contracts::pool::PoolContractError;
 use crate::contracts::refresh::RefreshContractError;
 use crate::node_interface::node_api::{NodeApi, NodeApiError};
-use crate::oracle_config::ORACLE_CONFIG;
+use crate::oracle_config::OracleConfig;
 
 use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
 use ergo_node_interface::node_interface::NodeError;
 use ergo_node_interface::ScanId;
+use reqwest::Url;
 use thiserror::Error;
 
 mod generic_token_scan;
 mod registry;
+mod store;
 
 pub use generic_token_scan::*;
 pub use registry::*;
+pub use store::*;
 
 /// Integer which is provided by the Ergo node to reference a given scan.
 pub type ScanID = String;
@@ -42,14 +45,35 @@ pub trait NodeScanId {
     fn scan_id(&self) -> ScanId;
 }
 
+/// Node the scan boxes are fetched from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanNode {
+    pub node_url: Url,
+    pub node_api_key: String,
+}
+
+impl ScanNode {
+    pub fn node_api(&self) -> NodeApi {
+        NodeApi::new(self.node_api_key.clone(), &self.node_url)
+    }
+}
+
+impl From<&OracleConfig> for ScanNode {
+    fn from(oracle_config: &OracleConfig) -> Self {
+        ScanNode {
+            node_url: oracle_config.node_url.clone(),
+            node_api_key: oracle_config.node_api_key.clone(),
+        }
+    }
+}
+
 pub trait ScanGetBoxes: NodeScanId {
-    fn get_boxes(&self) -> Result<Vec<ErgoBox>, ScanError> {
-        let node_api = NodeApi::new(ORACLE_CONFIG.node_api_key.clone(), &ORACLE_CONFIG.node_url);
-        let boxes = node_api.node.scan_boxes(self.scan_id())?;
+    fn get_boxes(&self, node: &ScanNode) -> Result<Vec<ErgoBox>, ScanError> {
+        let boxes = node.node_api().node.scan_boxes(self.scan_id())?;
         Ok(boxes)
     }
 
-    fn get_box(&self) -> Result<Option<ErgoBox>, ScanError> {
-        Ok(self.get_boxes()?.first().cloned())
+    fn get_box(&self, node: &ScanNode) -> Result<Option<ErgoBox>, ScanError> {
+        Ok(self.get_boxes(node)?.first().cloned())
     }
 }
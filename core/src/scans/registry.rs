@@ -1,3 +1,9 @@
+//! Scans registered in the node for the pool boxes, kept in the [`ScanStore`] of the data dir.
+//!
+//! The `scanIDs.json` file of the earlier versions (including the v1 format with the extra local
+//! oracle and ballot box scans) is migrated to the store on startup.
+
+use std::path::Path;
 use std::path::PathBuf;
 
 use crate::node_interface::node_api::NodeApi;
@@ -8,23 +14,54 @@ use crate::spec_token::BuybackTokenId;
 use crate::spec_token::OracleTokenId;
 use crate::spec_token::PoolTokenId;
 use crate::spec_token::RefreshTokenId;
+use crate::spec_token::TokenIdKind;
 use crate::spec_token::UpdateTokenId;
 
 use ::serde::Deserialize;
 use ::serde::Serialize;
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_node_interface::ScanId;
 use once_cell::sync;
 use thiserror::Error;
 
 use super::generic_token_scan::GenericTokenScan;
+use super::store::ScanRecord;
+use super::store::ScanStore;
+use super::store::ScanStoreError;
 use super::NodeScanId;
 use super::ScanError;
 
 pub static SCANS_DIR_PATH: sync::OnceCell<PathBuf> = sync::OnceCell::new();
 
+pub const SCANS_FILE_NAME: &str = "scans.sqlite";
+
+/// Scan IDs file of the earlier versions, renamed to `scanIDs.json.migrated` once migrated
+pub const LEGACY_SCANS_FILE_NAME: &str = "scanIDs.json";
+
+/// Scans of the v1 `scanIDs.json` that are not used anymore, deregistered on migration
+const LEGACY_V1_LOCAL_SCANS: [&str; 2] = ["Local Oracle Datapoint Scan", "Local Ballot Box Scan"];
+
+const ORACLE_TOKEN_SCAN: &str = "oracle_token";
+const POOL_TOKEN_SCAN: &str = "pool_token";
+const BALLOT_TOKEN_SCAN: &str = "ballot_token";
+const REFRESH_TOKEN_SCAN: &str = "refresh_token";
+const UPDATE_TOKEN_SCAN: &str = "update_token";
+const BUYBACK_TOKEN_SCAN: &str = "buyback_token";
+
+/// Scans of the boxes the local oracle finds by its public key, registered again when the
+/// `oracle_address` of the config changes
+const ORACLE_ADDRESS_SCANS: [&str; 2] = [ORACLE_TOKEN_SCAN, BALLOT_TOKEN_SCAN];
+
 pub fn get_scans_file_path() -> PathBuf {
-    SCANS_DIR_PATH.get().unwrap().join("scanIDs.json")
+    SCANS_DIR_PATH.get().unwrap().join(SCANS_FILE_NAME)
 }
 
+/// Legacy `scanIDs.json` next to the scan store
+fn legacy_scans_file_path(scans_file_path: &Path) -> PathBuf {
+    scans_file_path.with_file_name(LEGACY_SCANS_FILE_NAME)
+}
+
+/// Serializes to the legacy `scanIDs.json` format
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct NodeScanRegistry {
     #[serde(rename = "All Datapoints Scan")]
@@ -50,104 +87,219 @@ impl NodeScanRegistry {
         serde_json::to_string_pretty(&self).unwrap()
     }
 
-    fn save_to_json_file(&self, file_path: &PathBuf) -> Result<(), anyhow::Error> {
-        let json_str = self.save_to_json_str();
-        log::debug!("Saving scan IDs to {}", file_path.display());
-        Ok(std::fs::write(file_path, json_str)
-            .map_err(|e| NodeScanRegistryError::Io(e.to_string()))?)
-    }
-
-    fn register_and_save_scans_inner(
-        node_api: &NodeApi,
-        pool_config: &PoolConfig,
-    ) -> std::result::Result<Self, anyhow::Error> {
-        log::info!("Registering UTXO-Set Scans");
-        let oracle_token_scan =
-            GenericTokenScan::register(node_api, &pool_config.token_ids.oracle_token_id)?;
-        let pool_token_scan =
-            GenericTokenScan::register(node_api, &pool_config.token_ids.pool_nft_token_id)?;
-        let ballot_token_scan =
-            GenericTokenScan::register(node_api, &pool_config.token_ids.ballot_token_id)?;
-        let refresh_token_scan =
-            GenericTokenScan::register(node_api, &pool_config.token_ids.refresh_nft_token_id)?;
-        let update_token_scan =
-            GenericTokenScan::register(node_api, &pool_config.token_ids.update_nft_token_id)?;
-        let buyback_token_scan =
-            if let Some(buyback_token_id) = pool_config.buyback_token_id.clone() {
-                Some(GenericTokenScan::register(node_api, &buyback_token_id)?)
-            } else {
-                None
-            };
-        let registry = Self {
+    /// `None` if a scan of the pool boxes is missing in the records
+    fn from_records(records: &[ScanRecord]) -> Result<Option<Self>, NodeScanRegistryError> {
+        let oracle_token_scan = scan_from_records(records, ORACLE_TOKEN_SCAN)?;
+        let pool_token_scan = scan_from_records(records, POOL_TOKEN_SCAN)?;
+        let ballot_token_scan = scan_from_records(records, BALLOT_TOKEN_SCAN)?;
+        let refresh_token_scan = scan_from_records(records, REFRESH_TOKEN_SCAN)?;
+        let update_token_scan = scan_from_records(records, UPDATE_TOKEN_SCAN)?;
+        if let (
+            Some(oracle_token_scan),
+            Some(pool_token_scan),
+            Some(ballot_token_scan),
+            Some(refresh_token_scan),
+            Some(update_token_scan),
+        ) = (
             oracle_token_scan,
             pool_token_scan,
             ballot_token_scan,
             refresh_token_scan,
             update_token_scan,
-            buyback_token_scan,
-        };
-        registry.save_to_json_file(&get_scans_file_path())?;
-        node_api.rescan_from_height(0)?;
-        Ok(registry)
+        ) {
+            Ok(Some(Self {
+                oracle_token_scan,
+                pool_token_scan,
+                ballot_token_scan,
+                refresh_token_scan,
+                update_token_scan,
+                buyback_token_scan: scan_from_records(records, BUYBACK_TOKEN_SCAN)?,
+            }))
+        } else {
+            Ok(None)
+        }
     }
 
-    pub fn load() -> Result<Self, anyhow::Error> {
-        let path = get_scans_file_path();
-        log::info!("Loading scan IDs from {}", path.display());
-        let json_str =
-            std::fs::read_to_string(path).map_err(|e| NodeScanRegistryError::Io(e.to_string()))?;
+    /// Records of the scans in a legacy `scanIDs.json`. The file doesn't say which tokens the
+    /// scans track, they were registered for the tokens of the pool config.
+    fn migrate_legacy_json(
+        node_api: &NodeApi,
+        pool_config: &PoolConfig,
+        legacy_file_path: &Path,
+    ) -> Result<Vec<ScanRecord>, anyhow::Error> {
+        log::info!(
+            "Migrating the scan IDs from {} to the scan store",
+            legacy_file_path.display()
+        );
+        let json_str = std::fs::read_to_string(legacy_file_path)
+            .map_err(|e| NodeScanRegistryError::Io(e.to_string()))?;
         let registry = Self::load_from_json_str(&json_str)?;
-        Ok(registry)
+        let json: serde_json::Value = serde_json::from_str(&json_str)
+            .map_err(|e| NodeScanRegistryError::Parse(e.to_string()))?;
+        for name in LEGACY_V1_LOCAL_SCANS {
+            if let Some(scan_id) = json[name].as_str().and_then(|id| id.parse::<u64>().ok()) {
+                log::info!("Deregistering the unused v1 scan \"{}\"", name);
+                if let Err(e) = node_api.deregister_scan(ScanId::from(scan_id)) {
+                    log::warn!("Failed to deregister the v1 scan \"{}\": {}", name, e);
+                }
+            }
+        }
+        let token_ids = &pool_config.token_ids;
+        let mut records = vec![
+            legacy_record(
+                ORACLE_TOKEN_SCAN,
+                registry.oracle_token_scan,
+                &token_ids.oracle_token_id,
+            ),
+            legacy_record(
+                POOL_TOKEN_SCAN,
+                registry.pool_token_scan,
+                &token_ids.pool_nft_token_id,
+            ),
+            legacy_record(
+                BALLOT_TOKEN_SCAN,
+                registry.ballot_token_scan,
+                &token_ids.ballot_token_id,
+            ),
+            legacy_record(
+                REFRESH_TOKEN_SCAN,
+                registry.refresh_token_scan,
+                &token_ids.refresh_nft_token_id,
+            ),
+            legacy_record(
+                UPDATE_TOKEN_SCAN,
+                registry.update_token_scan,
+                &token_ids.update_nft_token_id,
+            ),
+        ];
+        if let Some(buyback_token_scan) = registry.buyback_token_scan {
+            if let Some(buyback_token_id) = &pool_config.buyback_token_id {
+                records.push(legacy_record(
+                    BUYBACK_TOKEN_SCAN,
+                    buyback_token_scan,
+                    buyback_token_id,
+                ));
+            } else {
+                log::info!(
+                    "No buyback token in the pool config but scan is registered. Deregistering it"
+                );
+                node_api.deregister_scan(buyback_token_scan.scan_id())?;
+            }
+        }
+        Ok(records)
+    }
+
+    pub fn load() -> Result<Self, anyhow::Error> {
+        Self::load_at(&get_scans_file_path())
+    }
+
+    /// Scans in the given store without checking them against the pool config or registering
+    /// missing ones. A legacy `scanIDs.json` that is not migrated yet is read as is.
+    pub fn load_at(scans_file_path: &Path) -> Result<Self, anyhow::Error> {
+        log::info!("Loading scan IDs from {}", scans_file_path.display());
+        let legacy_file_path = legacy_scans_file_path(scans_file_path);
+        if !scans_file_path.exists() {
+            if legacy_file_path.exists() {
+                let json_str = std::fs::read_to_string(&legacy_file_path)
+                    .map_err(|e| NodeScanRegistryError::Io(e.to_string()))?;
+                return Self::load_from_json_str(&json_str);
+            }
+            return Err(NodeScanRegistryError::NotRegistered.into());
+        }
+        let store = ScanStore::open(scans_file_path).map_err(NodeScanRegistryError::from)?;
+        let records = store.records().map_err(NodeScanRegistryError::from)?;
+        Self::from_records(&records)?.ok_or_else(|| NodeScanRegistryError::NotRegistered.into())
     }
 
     pub fn ensure_node_registered_scans(
         node_api: &NodeApi,
         pool_config: &PoolConfig,
+        oracle_address: &NetworkAddress,
     ) -> std::result::Result<Self, anyhow::Error> {
-        let path = get_scans_file_path();
-        log::info!("Loading scan IDs from {}", path.display());
-        let registry = if let Ok(json_str) = std::fs::read_to_string(path) {
-            let loaded_registry = Self::load_from_json_str(&json_str)?;
-            if let Some(pool_config_buyback_token_id) = pool_config.buyback_token_id.clone() {
-                log::info!("Buyback token is found in pool config, checking if scan is registered");
-                if loaded_registry.buyback_token_scan.is_some() {
-                    log::info!("Buyback token scan is already registered");
-                    loaded_registry
-                } else {
-                    let buyback_token_scan =
-                        GenericTokenScan::register(node_api, &pool_config_buyback_token_id)?;
-                    node_api.rescan_from_height(0)?;
-                    let new_registry = Self {
-                        buyback_token_scan: Some(buyback_token_scan),
-                        ..loaded_registry
-                    };
-                    new_registry.save_to_json_file(&get_scans_file_path())?;
-                    new_registry
-                }
-            } else {
-                // no buyback token in pool config
-                if let Some(buy_back_token_scan) = loaded_registry.buyback_token_scan.clone() {
-                    log::info!("No buyback token in the pool config but scan is registered. Deregistering it");
-                    // but registry has buyback token scan, deregister it
-                    node_api.deregister_scan(buy_back_token_scan.scan_id())?;
-                    let new_registry = Self {
-                        buyback_token_scan: None,
-                        ..loaded_registry
-                    };
-                    new_registry.save_to_json_file(&get_scans_file_path())?;
-                    new_registry
-                } else {
-                    loaded_registry
-                }
-            }
-        } else {
+        Self::ensure_node_registered_scans_at(
+            node_api,
+            pool_config,
+            oracle_address,
+            &get_scans_file_path(),
+        )
+    }
+
+    /// Same as `ensure_node_registered_scans` but keeps the scans in the given store, so that
+    /// several pools driven by the same process don't overwrite each other's scans.
+    ///
+    /// Scans are registered for the tokens of the pool config that have no scan in the store,
+    /// a scan tracking a different token than the pool config is replaced. The oracle and ballot
+    /// token scans registered for another oracle address are replaced as well. The node rescans
+    /// from height 0 after any registration.
+    pub fn ensure_node_registered_scans_at(
+        node_api: &NodeApi,
+        pool_config: &PoolConfig,
+        oracle_address: &NetworkAddress,
+        scans_file_path: &Path,
+    ) -> std::result::Result<Self, anyhow::Error> {
+        log::info!("Loading scan IDs from {}", scans_file_path.display());
+        let mut store = ScanStore::open(scans_file_path).map_err(NodeScanRegistryError::from)?;
+        let mut records = store.records().map_err(NodeScanRegistryError::from)?;
+        let legacy_file_path = legacy_scans_file_path(scans_file_path);
+        if records.is_empty() && legacy_file_path.exists() {
+            records = Self::migrate_legacy_json(node_api, pool_config, &legacy_file_path)?;
+            store
+                .replace_all(&records)
+                .map_err(NodeScanRegistryError::from)?;
+            let mut migrated_file_path = legacy_file_path.clone().into_os_string();
+            migrated_file_path.push(".migrated");
+            std::fs::rename(&legacy_file_path, &migrated_file_path)
+                .map_err(|e| NodeScanRegistryError::Io(e.to_string()))?;
+        }
+        if records.is_empty() {
             log::info!("Scans not found");
-            Self::register_and_save_scans_inner(node_api, pool_config)?
+        }
+        let mut registrar = ScanRegistrar {
+            node_api,
+            oracle_address: oracle_address.to_base58(),
+            records,
+            registered: false,
         };
+        let token_ids = &pool_config.token_ids;
+        let registry = Self {
+            oracle_token_scan: registrar.ensure(ORACLE_TOKEN_SCAN, &token_ids.oracle_token_id)?,
+            pool_token_scan: registrar.ensure(POOL_TOKEN_SCAN, &token_ids.pool_nft_token_id)?,
+            ballot_token_scan: registrar.ensure(BALLOT_TOKEN_SCAN, &token_ids.ballot_token_id)?,
+            refresh_token_scan: registrar
+                .ensure(REFRESH_TOKEN_SCAN, &token_ids.refresh_nft_token_id)?,
+            update_token_scan: registrar
+                .ensure(UPDATE_TOKEN_SCAN, &token_ids.update_nft_token_id)?,
+            buyback_token_scan: if let Some(buyback_token_id) = &pool_config.buyback_token_id {
+                Some(registrar.ensure(BUYBACK_TOKEN_SCAN, buyback_token_id)?)
+            } else {
+                registrar.remove::<BuybackTokenId>(BUYBACK_TOKEN_SCAN)?;
+                None
+            },
+        };
+        store
+            .replace_all(&registrar.records)
+            .map_err(NodeScanRegistryError::from)?;
+        if registrar.registered {
+            node_api.rescan_from_height(0)?;
+        }
         wait_for_node_rescan(node_api)?;
         Ok(registry)
     }
 
+    pub fn scan_ids(&self) -> Vec<ScanId> {
+        let mut scan_ids = vec![
+            self.oracle_token_scan.scan_id(),
+            self.pool_token_scan.scan_id(),
+            self.ballot_token_scan.scan_id(),
+            self.refresh_token_scan.scan_id(),
+            self.update_token_scan.scan_id(),
+        ];
+        if let Some(buyback_token_scan) = &self.buyback_token_scan {
+            scan_ids.push(buyback_token_scan.scan_id());
+        }
+        scan_ids
+    }
+
     pub fn deregister_all_scans(self, node_api: &NodeApi) -> Result<(), NodeApiError> {
         node_api.deregister_scan(self.oracle_token_scan.scan_id())?;
         node_api.deregister_scan(self.pool_token_scan.scan_id())?;
@@ -159,6 +311,134 @@ impl NodeScanRegistry {
         }
         Ok(())
     }
+
+    /// Deregister all scans and clear the given store
+    pub fn deregister_all_scans_at(
+        self,
+        node_api: &NodeApi,
+        scans_file_path: &Path,
+    ) -> Result<(), NodeScanRegistryError> {
+        self.deregister_all_scans(node_api)?;
+        ScanStore::open(scans_file_path)?.replace_all(&[])?;
+        Ok(())
+    }
+}
+
+fn scan_from_records<T: TokenIdKind + Clone>(
+    records: &[ScanRecord],
+    kind: &str,
+) -> Result<Option<GenericTokenScan<T>>, ScanError> {
+    records
+        .iter()
+        .find(|record| record.kind == kind)
+        .map(|record| GenericTokenScan::try_from(record.scan_id.clone()))
+        .transpose()
+}
+
+fn legacy_record<T: TokenIdKind + Clone>(
+    kind: &str,
+    scan: GenericTokenScan<T>,
+    token_id: &T,
+) -> ScanRecord {
+    ScanRecord {
+        kind: kind.to_string(),
+        scan_id: String::from(scan),
+        token_id: String::from(token_id.token_id()),
+        registered_height: None,
+        node_url: None,
+        oracle_address: None,
+    }
+}
+
+/// Oracle address the oracle token scan in the given store was registered for, `None` if there is
+/// no store or the address was not recorded
+pub fn registered_oracle_address(
+    scans_file_path: &Path,
+) -> Result<Option<String>, NodeScanRegistryError> {
+    if !scans_file_path.exists() {
+        return Ok(None);
+    }
+    let store = ScanStore::open(scans_file_path)?;
+    Ok(store
+        .records()?
+        .into_iter()
+        .find(|record| record.kind == ORACLE_TOKEN_SCAN)
+        .and_then(|record| record.oracle_address))
+}
+
+/// Registers the missing scans while the records are checked against the pool config
+struct ScanRegistrar<'a> {
+    node_api: &'a NodeApi,
+    /// Base58 oracle address of the config
+    oracle_address: String,
+    records: Vec<ScanRecord>,
+    /// Whether any scan was registered, so the node needs a rescan
+    registered: bool,
+}
+
+impl ScanRegistrar<'_> {
+    fn ensure<T: TokenIdKind + Clone>(
+        &mut self,
+        kind: &str,
+        token_id: &T,
+    ) -> Result<GenericTokenScan<T>, NodeScanRegistryError> {
+        let token_id_str = String::from(token_id.token_id());
+        let oracle_address = self.oracle_address.clone();
+        if let Some(record) = self.records.iter_mut().find(|record| record.kind == kind) {
+            let previous_oracle_address = record
+                .oracle_address
+                .clone()
+                .filter(|address| *address != oracle_address);
+            match previous_oracle_address {
+                Some(previous_oracle_address)
+                    if record.token_id == token_id_str && ORACLE_ADDRESS_SCANS.contains(&kind) =>
+                {
+                    log::warn!(
+                        "The {} scan was registered for oracle address {} but the oracle config has {}, replacing it",
+                        kind,
+                        previous_oracle_address,
+                        oracle_address
+                    );
+                }
+                _ if record.token_id == token_id_str => {
+                    record.oracle_address = Some(oracle_address);
+                    return Ok(GenericTokenScan::try_from(record.scan_id.clone())?);
+                }
+                _ => log::info!(
+                    "The {} scan tracks token {} but the pool config has {}, replacing it",
+                    kind,
+                    record.token_id,
+                    token_id_str
+                ),
+            }
+            self.remove::<T>(kind)?;
+        }
+        let scan = GenericTokenScan::register(self.node_api, token_id)?;
+        let height = self
+            .node_api
+            .node
+            .current_block_height()
+            .map_err(NodeApiError::from)?;
+        self.records.push(ScanRecord {
+            kind: kind.to_string(),
+            scan_id: String::from(scan.clone()),
+            token_id: token_id_str,
+            registered_height: Some(height as u32),
+            node_url: Some(self.node_api.node_url.to_string()),
+            oracle_address: Some(oracle_address),
+        });
+        self.registered = true;
+        Ok(scan)
+    }
+
+    /// Deregister the scan of the given kind if there is one
+    fn remove<T: TokenIdKind + Clone>(&mut self, kind: &str) -> Result<(), NodeScanRegistryError> {
+        if let Some(pos) = self.records.iter().position(|record| record.kind == kind) {
+            let scan = GenericTokenScan::<T>::try_from(self.records.remove(pos).scan_id)?;
+            self.node_api.deregister_scan(scan.scan_id())?;
+        }
+        Ok(())
+    }
 }
 
 pub fn wait_for_node_rescan(node_api: &NodeApi) -> Result<(), NodeApiError> {
@@ -190,12 +470,21 @@ pub enum NodeScanRegistryError {
     Parse(String),
     #[error("Error reading/writing file: {0}")]
     Io(String),
+    #[error("Error in the scan store: {0}")]
+    Store(#[from] ScanStoreError),
+    #[error("No scans are registered, run the oracle first")]
+    NotRegistered,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cli_commands::bootstrap::BootstrapConfig;
+    use crate::node_interface::mock_node::Fault;
+    use crate::node_interface::mock_node::MockNode;
+    use crate::pool_commands::test_utils::generate_token_ids;
     use crate::scans::NodeScanId;
+    use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
     use ergo_node_interface::ScanId;
     use expect_test::expect;
     use pretty_assertions::assert_eq;
@@ -274,4 +563,113 @@ mod tests {
         let registry2 = NodeScanRegistry::load_from_json_str(&json_str).unwrap();
         assert_eq!(registry, registry2);
     }
+
+    #[test]
+    fn registry_from_records() {
+        let record = |kind: &str, scan_id: &str| ScanRecord {
+            kind: kind.to_string(),
+            scan_id: scan_id.to_string(),
+            token_id: String::new(),
+            registered_height: None,
+            node_url: None,
+            oracle_address: None,
+        };
+        let mut records = vec![
+            record(ORACLE_TOKEN_SCAN, "185"),
+            record(UPDATE_TOKEN_SCAN, "186"),
+            record(POOL_TOKEN_SCAN, "187"),
+            record(REFRESH_TOKEN_SCAN, "188"),
+        ];
+        assert_eq!(NodeScanRegistry::from_records(&records).unwrap(), None);
+        records.push(record(BALLOT_TOKEN_SCAN, "191"));
+        let registry = NodeScanRegistry::from_records(&records).unwrap().unwrap();
+        assert_eq!(registry.oracle_token_scan.scan_id(), ScanId::from(185));
+        assert_eq!(registry.ballot_token_scan.scan_id(), ScanId::from(191));
+        assert_eq!(registry.buyback_token_scan, None);
+        records.push(record(BUYBACK_TOKEN_SCAN, "192"));
+        let registry = NodeScanRegistry::from_records(&records).unwrap().unwrap();
+        assert_eq!(
+            registry.buyback_token_scan,
+            Some(GenericTokenScan::new(ScanId::from(192)))
+        );
+    }
+
+    const ORACLE_ADDRESS: &str = "9hEQHEMyY1K1vs79vJXFtNjr2dbQbtWXF99oVWGJ5c4xbcLdBsw";
+    const NEW_ORACLE_ADDRESS: &str = "9iHyKxXs2ZNLMp9N9gbUT9V8gTbsV7HED1C1VhttMfBUMPDyF7r";
+
+    fn address(address: &str) -> NetworkAddress {
+        AddressEncoder::unchecked_parse_network_address_from_str(address).unwrap()
+    }
+
+    #[test]
+    fn register_scans_on_mock_node() {
+        let scans_file_path = std::env::temp_dir().join(format!(
+            "oracle-core-scans-registry-{}.sqlite",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&scans_file_path);
+        let pool_config =
+            PoolConfig::create(BootstrapConfig::default(), generate_token_ids()).unwrap();
+        let mock_node = MockNode::start(1000);
+        let node_api = mock_node.node_api();
+        let oracle_address = address(ORACLE_ADDRESS);
+
+        mock_node.inject("/scan/register", Fault::Status(503), Some(1));
+        assert!(NodeScanRegistry::ensure_node_registered_scans_at(
+            &node_api,
+            &pool_config,
+            &oracle_address,
+            &scans_file_path
+        )
+        .is_err());
+
+        let registry = NodeScanRegistry::ensure_node_registered_scans_at(
+            &node_api,
+            &pool_config,
+            &oracle_address,
+            &scans_file_path,
+        )
+        .unwrap();
+        assert_eq!(mock_node.state().scans.len(), 5);
+        assert_eq!(mock_node.state().rescans, vec![0]);
+        assert_eq!(
+            NodeScanRegistry::load_at(&scans_file_path).unwrap(),
+            registry
+        );
+
+        // already registered, no new scans or rescan
+        NodeScanRegistry::ensure_node_registered_scans_at(
+            &node_api,
+            &pool_config,
+            &oracle_address,
+            &scans_file_path,
+        )
+        .unwrap();
+        assert_eq!(mock_node.state().scans.len(), 5);
+        assert_eq!(mock_node.state().rescans, vec![0]);
+        assert_eq!(
+            registered_oracle_address(&scans_file_path).unwrap(),
+            Some(ORACLE_ADDRESS.to_string())
+        );
+
+        // the oracle and ballot token scans are replaced for a new oracle address
+        let oracle_address = address(NEW_ORACLE_ADDRESS);
+        let new_registry = NodeScanRegistry::ensure_node_registered_scans_at(
+            &node_api,
+            &pool_config,
+            &oracle_address,
+            &scans_file_path,
+        )
+        .unwrap();
+        assert_ne!(new_registry.oracle_token_scan, registry.oracle_token_scan);
+        assert_ne!(new_registry.ballot_token_scan, registry.ballot_token_scan);
+        assert_eq!(new_registry.pool_token_scan, registry.pool_token_scan);
+        assert_eq!(mock_node.state().scans.len(), 5);
+        assert_eq!(mock_node.state().rescans, vec![0, 0]);
+        assert_eq!(
+            registered_oracle_address(&scans_file_path).unwrap(),
+            Some(NEW_ORACLE_ADDRESS.to_string())
+        );
+        let _ = std::fs::remove_file(&scans_file_path);
+    }
 }
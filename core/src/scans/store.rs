@@ -0,0 +1,201 @@
+//! Local SQLite store of the registered node scans, replacing the legacy `scanIDs.json` file.
+//!
+//! Every scan is recorded with the token id its tracking rule matches, the height it was
+//! registered at, the node it was registered on and the oracle address of the config it was
+//! registered for. The schema version is kept in the SQLite
+//! `user_version` and the store is migrated to the current version when opened.
+
+use std::path::Path;
+
+use rusqlite::params;
+use rusqlite::Connection;
+use thiserror::Error;
+
+/// `MIGRATIONS[n]` migrates the store from schema version `n` to `n + 1`
+const MIGRATIONS: &[&str] = &[
+    "
+CREATE TABLE scans (
+    kind TEXT PRIMARY KEY NOT NULL,
+    scan_id TEXT NOT NULL,
+    token_id TEXT NOT NULL,
+    registered_height INTEGER,
+    node_url TEXT
+);
+",
+    "
+ALTER TABLE scans ADD COLUMN oracle_address TEXT;
+",
+];
+
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanRecord {
+    /// Which pool box the scan tracks, e.g. `pool_token`
+    pub kind: String,
+    pub scan_id: String,
+    pub token_id: String,
+    /// Height and node are not known for scans migrated from `scanIDs.json`
+    pub registered_height: Option<u32>,
+    pub node_url: Option<String>,
+    /// Not known for scans registered before it was recorded
+    pub oracle_address: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum ScanStoreError {
+    #[error("sqlite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("scan store schema version {found} is newer than the supported version {supported}, it was written by a newer oracle-core")]
+    NewerSchema { found: u32, supported: u32 },
+}
+
+pub struct ScanStore {
+    conn: Connection,
+}
+
+impl ScanStore {
+    /// Creates the store if it's missing and migrates it to [`SCHEMA_VERSION`]
+    pub fn open(path: &Path) -> Result<Self, ScanStoreError> {
+        Self::migrate(Connection::open(path)?)
+    }
+
+    fn migrate(mut conn: Connection) -> Result<Self, ScanStoreError> {
+        let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(ScanStoreError::NewerSchema {
+                found: version,
+                supported: SCHEMA_VERSION,
+            });
+        }
+        for (from_version, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", from_version as u32 + 1)?;
+            tx.commit()?;
+            log::debug!(
+                "Migrated the scan store to schema version {}",
+                from_version + 1
+            );
+        }
+        Ok(ScanStore { conn })
+    }
+
+    pub fn schema_version(&self) -> Result<u32, ScanStoreError> {
+        Ok(self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?)
+    }
+
+    pub fn records(&self) -> Result<Vec<ScanRecord>, ScanStoreError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT kind, scan_id, token_id, registered_height, node_url, oracle_address FROM scans \
+             ORDER BY kind",
+        )?;
+        let records = stmt
+            .query_map([], |row| {
+                Ok(ScanRecord {
+                    kind: row.get(0)?,
+                    scan_id: row.get(1)?,
+                    token_id: row.get(2)?,
+                    registered_height: row.get(3)?,
+                    node_url: row.get(4)?,
+                    oracle_address: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(records)
+    }
+
+    /// Replace all records in one transaction
+    pub fn replace_all(&mut self, records: &[ScanRecord]) -> Result<(), ScanStoreError> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM scans", [])?;
+        for record in records {
+            tx.execute(
+                "INSERT INTO scans \
+                 (kind, scan_id, token_id, registered_height, node_url, oracle_address) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    record.kind,
+                    record.scan_id,
+                    record.token_id,
+                    record.registered_height,
+                    record.node_url,
+                    record.oracle_address
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn record(kind: &str, scan_id: &str) -> ScanRecord {
+        ScanRecord {
+            kind: kind.to_string(),
+            scan_id: scan_id.to_string(),
+            token_id: "b6d3eb2ed4ea6e2e8e2e5dbb1ee0d7bd80bbf2cb4b6c8f1d2ed9a4938dfd8ac2"
+                .to_string(),
+            registered_height: Some(1000),
+            node_url: Some("http://127.0.0.1:9053/".to_string()),
+            oracle_address: Some("9hEQHEMyY1K1vs79vJXFtNjr2dbQbtWXF99oVWGJ5c4xbcLdBsw".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_new_store_is_migrated() {
+        let store = ScanStore::migrate(Connection::open_in_memory().unwrap()).unwrap();
+        assert_eq!(store.schema_version().unwrap(), SCHEMA_VERSION);
+        assert_eq!(store.records().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_records_roundtrip() {
+        let mut store = ScanStore::migrate(Connection::open_in_memory().unwrap()).unwrap();
+        let mut migrated = record("oracle_token", "185");
+        migrated.registered_height = None;
+        migrated.node_url = None;
+        migrated.oracle_address = None;
+        store
+            .replace_all(&[record("pool_token", "187"), migrated.clone()])
+            .unwrap();
+        assert_eq!(
+            store.records().unwrap(),
+            vec![migrated, record("pool_token", "187")]
+        );
+        store.replace_all(&[record("pool_token", "190")]).unwrap();
+        assert_eq!(store.records().unwrap(), vec![record("pool_token", "190")]);
+    }
+
+    #[test]
+    fn test_v1_store_is_migrated() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.pragma_update(None, "user_version", 1).unwrap();
+        conn.execute(
+            "INSERT INTO scans (kind, scan_id, token_id) VALUES ('pool_token', '187', 'abc')",
+            [],
+        )
+        .unwrap();
+        let store = ScanStore::migrate(conn).unwrap();
+        assert_eq!(store.schema_version().unwrap(), SCHEMA_VERSION);
+        assert_eq!(store.records().unwrap()[0].oracle_address, None);
+    }
+
+    #[test]
+    fn test_newer_schema_is_rejected() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+        assert!(matches!(
+            ScanStore::migrate(conn),
+            Err(ScanStoreError::NewerSchema { .. })
+        ));
+    }
+}
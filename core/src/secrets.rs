@@ -0,0 +1,128 @@
+//! Secrets of the oracle config kept in the OS keyring (Secret Service on Linux, Keychain on macOS,
+//! Credential Manager on Windows) instead of the config file.
+//!
+//! A secret field set to `keyring:<entry name>` is replaced on the config load with the password
+//! of that entry of the `oracle-core` service. The fields resolved this way are `node_api_key`
+//! and the tokens and passwords of the notification backends (telegram `bot_token`, slack and
+//! discord `webhook_url`, email `password`), the MQTT `password`, the NATS `token` and the IPFS
+//! `auth_header`. The entries are stored with the `set-secret` command.
+//!
+//! Without the `keyring` feature a `keyring:` reference fails with [`SecretsError::NotCompiled`].
+
+use thiserror::Error;
+
+/// Prefix of a secret field referencing a keyring entry
+pub const KEYRING_PREFIX: &str = "keyring:";
+
+/// Service of the keyring entries
+pub const KEYRING_SERVICE: &str = "oracle-core";
+
+#[derive(Debug, Clone, Error)]
+pub enum SecretsError {
+    #[error("keyring entry {entry}: {reason}")]
+    Keyring { entry: String, reason: String },
+    #[error(
+        "keyring entry {0} is referenced but oracle-core was built without the `keyring` feature"
+    )]
+    NotCompiled(String),
+}
+
+/// Source of the secrets referenced from the config
+pub trait SecretStore {
+    fn get_secret(&self, entry: &str) -> Result<String, SecretsError>;
+}
+
+/// The keyring of the OS
+pub struct OsKeyring;
+
+#[cfg(feature = "keyring")]
+impl OsKeyring {
+    fn entry(name: &str) -> Result<keyring::Entry, SecretsError> {
+        keyring::Entry::new(KEYRING_SERVICE, name).map_err(|e| SecretsError::Keyring {
+            entry: name.to_string(),
+            reason: e.to_string(),
+        })
+    }
+
+    pub fn set_secret(&self, entry: &str, secret: &str) -> Result<(), SecretsError> {
+        Self::entry(entry)?
+            .set_password(secret)
+            .map_err(|e| SecretsError::Keyring {
+                entry: entry.to_string(),
+                reason: e.to_string(),
+            })
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+impl OsKeyring {
+    pub fn set_secret(&self, entry: &str, _secret: &str) -> Result<(), SecretsError> {
+        Err(SecretsError::NotCompiled(entry.to_string()))
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl SecretStore for OsKeyring {
+    fn get_secret(&self, entry: &str) -> Result<String, SecretsError> {
+        Self::entry(entry)?
+            .get_password()
+            .map_err(|e| SecretsError::Keyring {
+                entry: entry.to_string(),
+                reason: e.to_string(),
+            })
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+impl SecretStore for OsKeyring {
+    fn get_secret(&self, entry: &str) -> Result<String, SecretsError> {
+        Err(SecretsError::NotCompiled(entry.to_string()))
+    }
+}
+
+/// Name of the keyring entry the value references, if it is a `keyring:<entry name>` reference
+pub fn keyring_entry_name(value: &str) -> Option<&str> {
+    value.strip_prefix(KEYRING_PREFIX).map(str::trim)
+}
+
+/// Replaces a `keyring:<entry name>` reference with the secret, other values are left as is
+pub fn resolve_secret(value: &mut String, store: &dyn SecretStore) -> Result<(), SecretsError> {
+    if let Some(entry) = keyring_entry_name(value) {
+        *value = store.get_secret(entry)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    struct MapStore(HashMap<&'static str, &'static str>);
+
+    impl SecretStore for MapStore {
+        fn get_secret(&self, entry: &str) -> Result<String, SecretsError> {
+            self.0
+                .get(entry)
+                .map(|s| s.to_string())
+                .ok_or_else(|| SecretsError::Keyring {
+                    entry: entry.to_string(),
+                    reason: "no entry".to_string(),
+                })
+        }
+    }
+
+    #[test]
+    fn test_resolve_secret() {
+        let store = MapStore([("node", "s3cret")].into_iter().collect());
+        let mut reference = "keyring:node".to_string();
+        resolve_secret(&mut reference, &store).unwrap();
+        assert_eq!(reference, "s3cret");
+        let mut plain = "hello".to_string();
+        resolve_secret(&mut plain, &store).unwrap();
+        assert_eq!(plain, "hello");
+        let mut missing = "keyring:other".to_string();
+        assert!(resolve_secret(&mut missing, &store).is_err());
+    }
+}
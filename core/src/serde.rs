@@ -20,6 +20,7 @@ use crate::{
     },
     contracts::{
         ballot::{BallotContractParameters, BallotContractParametersError},
+        ergo_script::{compile_ergo_script, ErgoScriptError},
         oracle::{OracleContractParameters, OracleContractParametersError},
         pool::{PoolContractParameters, PoolContractParametersError},
         refresh::{
@@ -28,8 +29,10 @@ use crate::{
         },
         update::{UpdateContractParameters, UpdateContractParametersError},
     },
-    oracle_types::{EpochLength, MinDatapoints},
-    pool_config::{PoolConfig, PoolConfigError, PredefinedDataPointSource, TokenIds},
+    default_parameters::{REFRESH_ORACLE_REWARD_PER_DATAPOINT, REFRESH_REWARD_PER_DATAPOINT_INDEX},
+    djed::DjedConfig,
+    oracle_types::{EpochLength, MinDatapoints, RewardTokensPerDatapoint},
+    pool_config::{ErgRewards, PoolConfig, PoolConfigError, PredefinedDataPointSource, TokenIds},
     spec_token::{BuybackTokenId, TokenIdKind},
 };
 
@@ -43,6 +46,11 @@ pub(crate) struct PoolConfigSerde {
     ballot_contract_parameters: BallotContractParametersSerde,
     token_ids: TokenIds,
     buyback_token_id: Option<BuybackTokenId>,
+    buyback_max_reward_tokens_per_refresh: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    erg_rewards: Option<ErgRewards>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    djed: Option<DjedConfig>,
 }
 
 #[derive(Debug, Error)]
@@ -65,6 +73,8 @@ pub enum SerdeConversionError {
     UpdateContractParameters(#[from] UpdateContractParametersError),
     #[error("BoxValueError: {0}")]
     BoxValueError(#[from] BoxValueError),
+    #[error("ErgoScript error: {0}")]
+    ErgoScript(#[from] ErgoScriptError),
 }
 
 impl From<PoolConfig> for PoolConfigSerde {
@@ -109,6 +119,9 @@ impl From<PoolConfig> for PoolConfigSerde {
             token_ids: c.token_ids,
             data_point_source: c.data_point_source,
             buyback_token_id: c.buyback_token_id,
+            buyback_max_reward_tokens_per_refresh: c.buyback_max_reward_tokens_per_refresh,
+            erg_rewards: c.erg_rewards,
+            djed: c.djed,
         }
     }
 }
@@ -117,7 +130,7 @@ impl TryFrom<PoolConfigSerde> for PoolConfig {
     type Error = SerdeConversionError;
     fn try_from(c: PoolConfigSerde) -> Result<Self, Self::Error> {
         let oracle_contract_parameters = OracleContractParameters::checked_load(
-            base16::decode(c.oracle_contract_parameters.ergo_tree_bytes.as_str())?,
+            c.oracle_contract_parameters.decode_ergo_tree_bytes()?,
             c.oracle_contract_parameters.pool_nft_index,
             c.oracle_contract_parameters.min_storage_rent_index,
             c.oracle_contract_parameters.min_storage_rent.try_into()?,
@@ -132,32 +145,16 @@ impl TryFrom<PoolConfigSerde> for PoolConfig {
         .map_err(PoolConfigError::from)?;
 
         let pool_contract_parameters = PoolContractParameters::checked_load(
-            base16::decode(c.pool_contract_parameters.ergo_tree_bytes.as_str())?,
+            c.pool_contract_parameters.decode_ergo_tree_bytes()?,
             c.pool_contract_parameters.refresh_nft_index,
             c.pool_contract_parameters.update_nft_index,
         )?;
 
         let refresh_contract_parameters =
-            RefreshContractParameters::checked_load(RefreshContractParametersInputs {
-                ergo_tree_bytes: base16::decode(
-                    c.refresh_contract_parameters.ergo_tree_bytes.as_str(),
-                )?,
-                pool_nft_index: c.refresh_contract_parameters.pool_nft_index,
-                oracle_token_id_index: c.refresh_contract_parameters.oracle_token_id_index,
-                min_data_points_index: c.refresh_contract_parameters.min_data_points_index,
-                min_data_points: c.refresh_contract_parameters.min_data_points,
-                buffer_length_index: c.refresh_contract_parameters.buffer_length_index,
-                buffer_length: c.refresh_contract_parameters.buffer_length,
-                max_deviation_percent_index: c
-                    .refresh_contract_parameters
-                    .max_deviation_percent_index,
-                max_deviation_percent: c.refresh_contract_parameters.max_deviation_percent,
-                epoch_length_index: c.refresh_contract_parameters.epoch_length_index,
-                epoch_length: c.refresh_contract_parameters.epoch_length,
-            })?;
+            RefreshContractParameters::checked_load(c.refresh_contract_parameters.inputs()?)?;
 
         let update_contract_parameters = UpdateContractParameters::checked_load(
-            base16::decode(c.update_contract_parameters.ergo_tree_bytes.as_str())?,
+            c.update_contract_parameters.decode_ergo_tree_bytes()?,
             c.update_contract_parameters.pool_nft_index,
             c.update_contract_parameters.ballot_token_index,
             c.update_contract_parameters.min_votes_index,
@@ -165,7 +162,7 @@ impl TryFrom<PoolConfigSerde> for PoolConfig {
         )?;
 
         let ballot_contract_parameters = BallotContractParameters::checked_load(
-            base16::decode(c.ballot_contract_parameters.ergo_tree_bytes.as_str())?,
+            c.ballot_contract_parameters.decode_ergo_tree_bytes()?,
             c.ballot_contract_parameters.min_storage_rent.try_into()?,
             c.ballot_contract_parameters.min_storage_rent_index,
             c.ballot_contract_parameters.update_nft_index,
@@ -203,7 +200,7 @@ impl TryFrom<PoolConfigSerde> for PoolConfig {
         )
         .map_err(PoolConfigError::from)?;
 
-        Ok(PoolConfig {
+        let pool_config = PoolConfig {
             data_point_source: c.data_point_source,
             oracle_box_wrapper_inputs,
             pool_box_wrapper_inputs,
@@ -212,7 +209,12 @@ impl TryFrom<PoolConfigSerde> for PoolConfig {
             ballot_box_wrapper_inputs,
             token_ids: c.token_ids,
             buyback_token_id: c.buyback_token_id,
-        })
+            buyback_max_reward_tokens_per_refresh: c.buyback_max_reward_tokens_per_refresh,
+            erg_rewards: c.erg_rewards,
+            djed: c.djed,
+        };
+        pool_config.check_erg_rewards()?;
+        Ok(pool_config)
     }
 }
 
@@ -253,43 +255,27 @@ impl TryFrom<BootstrapConfigSerde> for BootstrapConfig {
 
     fn try_from(c: BootstrapConfigSerde) -> Result<Self, Self::Error> {
         let pool_contract_parameters = PoolContractParameters::checked_load(
-            base16::decode(c.pool_contract_parameters.ergo_tree_bytes.as_str())?,
+            c.pool_contract_parameters.decode_ergo_tree_bytes()?,
             c.pool_contract_parameters.refresh_nft_index,
             c.pool_contract_parameters.update_nft_index,
         )?;
         let refresh_contract_parameters =
-            RefreshContractParameters::build_with(RefreshContractParametersInputs {
-                ergo_tree_bytes: base16::decode(
-                    c.refresh_contract_parameters.ergo_tree_bytes.as_str(),
-                )?,
-                pool_nft_index: c.refresh_contract_parameters.pool_nft_index,
-                oracle_token_id_index: c.refresh_contract_parameters.oracle_token_id_index,
-                min_data_points_index: c.refresh_contract_parameters.min_data_points_index,
-                min_data_points: c.refresh_contract_parameters.min_data_points,
-                buffer_length_index: c.refresh_contract_parameters.buffer_length_index,
-                buffer_length: c.refresh_contract_parameters.buffer_length,
-                max_deviation_percent_index: c
-                    .refresh_contract_parameters
-                    .max_deviation_percent_index,
-                max_deviation_percent: c.refresh_contract_parameters.max_deviation_percent,
-                epoch_length_index: c.refresh_contract_parameters.epoch_length_index,
-                epoch_length: c.refresh_contract_parameters.epoch_length,
-            })?;
+            RefreshContractParameters::build_with(c.refresh_contract_parameters.inputs()?)?;
         let update_contract_parameters = UpdateContractParameters::build_with(
-            base16::decode(c.update_contract_parameters.ergo_tree_bytes.as_str())?,
+            c.update_contract_parameters.decode_ergo_tree_bytes()?,
             c.update_contract_parameters.pool_nft_index,
             c.update_contract_parameters.ballot_token_index,
             c.update_contract_parameters.min_votes_index,
             c.update_contract_parameters.min_votes,
         )?;
         let ballot_contract_parameters = BallotContractParameters::build_with(
-            base16::decode(c.ballot_contract_parameters.ergo_tree_bytes.as_str())?,
+            c.ballot_contract_parameters.decode_ergo_tree_bytes()?,
             c.ballot_contract_parameters.min_storage_rent_index,
             c.ballot_contract_parameters.min_storage_rent.try_into()?,
             c.ballot_contract_parameters.update_nft_index,
         )?;
         let oracle_contract_parameters = OracleContractParameters::build_with(
-            base16::decode(c.oracle_contract_parameters.ergo_tree_bytes.as_str())?,
+            c.oracle_contract_parameters.decode_ergo_tree_bytes()?,
             c.oracle_contract_parameters.pool_nft_index,
             c.oracle_contract_parameters.min_storage_rent_index,
             c.oracle_contract_parameters.min_storage_rent.try_into()?,
@@ -309,7 +295,11 @@ impl TryFrom<BootstrapConfigSerde> for BootstrapConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OracleContractParametersSerde {
-    ergo_tree_bytes: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ergo_tree_bytes: Option<String>,
+    /// ErgoScript source compiled instead of `ergo_tree_bytes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ergo_script: Option<String>,
     pool_nft_index: usize,
     min_storage_rent_index: usize,
     min_storage_rent: u64,
@@ -318,7 +308,8 @@ pub struct OracleContractParametersSerde {
 impl From<OracleContractParameters> for OracleContractParametersSerde {
     fn from(p: OracleContractParameters) -> Self {
         OracleContractParametersSerde {
-            ergo_tree_bytes: base16::encode_lower(p.ergo_tree_bytes().as_slice()),
+            ergo_tree_bytes: Some(base16::encode_lower(p.ergo_tree_bytes().as_slice())),
+            ergo_script: None,
             pool_nft_index: p.pool_nft_index,
             min_storage_rent_index: p.min_storage_rent_index,
             min_storage_rent: *p.min_storage_rent.as_u64(),
@@ -328,7 +319,10 @@ impl From<OracleContractParameters> for OracleContractParametersSerde {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct PoolContractParametersSerde {
-    ergo_tree_bytes: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ergo_tree_bytes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ergo_script: Option<String>,
     refresh_nft_index: usize,
     update_nft_index: usize,
 }
@@ -336,7 +330,8 @@ struct PoolContractParametersSerde {
 impl From<PoolContractParameters> for PoolContractParametersSerde {
     fn from(p: PoolContractParameters) -> Self {
         PoolContractParametersSerde {
-            ergo_tree_bytes: base16::encode_lower(p.ergo_tree_bytes().as_slice()),
+            ergo_tree_bytes: Some(base16::encode_lower(p.ergo_tree_bytes().as_slice())),
+            ergo_script: None,
             refresh_nft_index: p.refresh_nft_index(),
             update_nft_index: p.update_nft_index(),
         }
@@ -345,7 +340,10 @@ impl From<PoolContractParameters> for PoolContractParametersSerde {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct RefreshContractParametersSerde {
-    ergo_tree_bytes: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ergo_tree_bytes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ergo_script: Option<String>,
     pool_nft_index: usize,
     oracle_token_id_index: usize,
     min_data_points_index: usize,
@@ -356,12 +354,19 @@ struct RefreshContractParametersSerde {
     max_deviation_percent: i32,
     epoch_length_index: usize,
     epoch_length: EpochLength,
+    /// Missing in the configs written before the oracle reward was a parameter, the EIP-23 refresh
+    /// contract values are used then
+    #[serde(default)]
+    reward_per_datapoint_index: Option<usize>,
+    #[serde(default)]
+    oracle_reward_per_datapoint: Option<RewardTokensPerDatapoint>,
 }
 
 impl From<RefreshContractParameters> for RefreshContractParametersSerde {
     fn from(p: RefreshContractParameters) -> Self {
         RefreshContractParametersSerde {
-            ergo_tree_bytes: base16::encode_lower(p.ergo_tree_bytes().as_slice()),
+            ergo_tree_bytes: Some(base16::encode_lower(p.ergo_tree_bytes().as_slice())),
+            ergo_script: None,
             pool_nft_index: p.pool_nft_index(),
             oracle_token_id_index: p.oracle_token_id_index(),
             min_data_points_index: p.min_data_points_index(),
@@ -372,13 +377,18 @@ impl From<RefreshContractParameters> for RefreshContractParametersSerde {
             max_deviation_percent: p.max_deviation_percent(),
             epoch_length_index: p.epoch_length_index(),
             epoch_length: p.epoch_length(),
+            reward_per_datapoint_index: Some(p.reward_per_datapoint_index()),
+            oracle_reward_per_datapoint: Some(p.oracle_reward_per_datapoint()),
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct BallotContractParametersSerde {
-    ergo_tree_bytes: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ergo_tree_bytes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ergo_script: Option<String>,
     min_storage_rent_index: usize,
     min_storage_rent: u64,
     update_nft_index: usize,
@@ -387,7 +397,8 @@ struct BallotContractParametersSerde {
 impl From<BallotContractParameters> for BallotContractParametersSerde {
     fn from(c: BallotContractParameters) -> Self {
         BallotContractParametersSerde {
-            ergo_tree_bytes: base16::encode_lower(c.ergo_tree_bytes().as_slice()),
+            ergo_tree_bytes: Some(base16::encode_lower(c.ergo_tree_bytes().as_slice())),
+            ergo_script: None,
             min_storage_rent_index: c.min_storage_rent_index(),
             min_storage_rent: *c.min_storage_rent().as_u64(),
             update_nft_index: c.update_nft_index(),
@@ -398,7 +409,10 @@ impl From<BallotContractParameters> for BallotContractParametersSerde {
 /// Used to (de)serialize `OracleContractParameters` instance.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct UpdateContractParametersSerde {
-    ergo_tree_bytes: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ergo_tree_bytes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ergo_script: Option<String>,
     pool_nft_index: usize,
     ballot_token_index: usize,
     min_votes_index: usize,
@@ -408,7 +422,8 @@ struct UpdateContractParametersSerde {
 impl From<UpdateContractParameters> for UpdateContractParametersSerde {
     fn from(p: UpdateContractParameters) -> Self {
         UpdateContractParametersSerde {
-            ergo_tree_bytes: base16::encode_lower(p.ergo_tree_bytes().as_slice()),
+            ergo_tree_bytes: Some(base16::encode_lower(p.ergo_tree_bytes().as_slice())),
+            ergo_script: None,
             pool_nft_index: p.pool_nft_index(),
             ballot_token_index: p.ballot_token_index(),
             min_votes_index: p.min_votes_index(),
@@ -417,11 +432,92 @@ impl From<UpdateContractParameters> for UpdateContractParametersSerde {
     }
 }
 
+/// Contract parameters with the contract given either as base16 ergo tree bytes or as ErgoScript
+/// source
+trait ContractSerde {
+    /// The `ergo_tree_bytes` and `ergo_script` fields
+    fn contract(&self) -> (Option<&str>, Option<&str>);
+
+    fn decode_ergo_tree_bytes(&self) -> Result<Vec<u8>, SerdeConversionError> {
+        let (ergo_tree_bytes, ergo_script) = self.contract();
+        decode_contract(ergo_tree_bytes, ergo_script)
+    }
+}
+
+impl ContractSerde for OracleContractParametersSerde {
+    fn contract(&self) -> (Option<&str>, Option<&str>) {
+        (self.ergo_tree_bytes.as_deref(), self.ergo_script.as_deref())
+    }
+}
+
+impl ContractSerde for PoolContractParametersSerde {
+    fn contract(&self) -> (Option<&str>, Option<&str>) {
+        (self.ergo_tree_bytes.as_deref(), self.ergo_script.as_deref())
+    }
+}
+
+impl ContractSerde for RefreshContractParametersSerde {
+    fn contract(&self) -> (Option<&str>, Option<&str>) {
+        (self.ergo_tree_bytes.as_deref(), self.ergo_script.as_deref())
+    }
+}
+
+impl RefreshContractParametersSerde {
+    fn inputs(&self) -> Result<RefreshContractParametersInputs, SerdeConversionError> {
+        Ok(RefreshContractParametersInputs {
+            ergo_tree_bytes: self.decode_ergo_tree_bytes()?,
+            pool_nft_index: self.pool_nft_index,
+            oracle_token_id_index: self.oracle_token_id_index,
+            min_data_points_index: self.min_data_points_index,
+            min_data_points: self.min_data_points,
+            buffer_length_index: self.buffer_length_index,
+            buffer_length: self.buffer_length,
+            max_deviation_percent_index: self.max_deviation_percent_index,
+            max_deviation_percent: self.max_deviation_percent,
+            epoch_length_index: self.epoch_length_index,
+            epoch_length: self.epoch_length,
+            reward_per_datapoint_index: self
+                .reward_per_datapoint_index
+                .unwrap_or(REFRESH_REWARD_PER_DATAPOINT_INDEX),
+            oracle_reward_per_datapoint: self
+                .oracle_reward_per_datapoint
+                .unwrap_or(REFRESH_ORACLE_REWARD_PER_DATAPOINT),
+        })
+    }
+}
+
+impl ContractSerde for BallotContractParametersSerde {
+    fn contract(&self) -> (Option<&str>, Option<&str>) {
+        (self.ergo_tree_bytes.as_deref(), self.ergo_script.as_deref())
+    }
+}
+
+impl ContractSerde for UpdateContractParametersSerde {
+    fn contract(&self) -> (Option<&str>, Option<&str>) {
+        (self.ergo_tree_bytes.as_deref(), self.ergo_script.as_deref())
+    }
+}
+
+/// Contract ergo tree bytes either given in base16 or compiled from ErgoScript source
+fn decode_contract(
+    ergo_tree_bytes: Option<&str>,
+    ergo_script: Option<&str>,
+) -> Result<Vec<u8>, SerdeConversionError> {
+    match (ergo_tree_bytes, ergo_script) {
+        (Some(ergo_tree_bytes), None) => Ok(base16::decode(ergo_tree_bytes)?),
+        (None, Some(ergo_script)) => Ok(compile_ergo_script(ergo_script)?),
+        (Some(_), Some(_)) => Err(ErgoScriptError::BothContracts.into()),
+        (None, None) => Err(ErgoScriptError::NoContract.into()),
+    }
+}
+
 #[derive(Clone, Deserialize)]
 pub struct UpdateBootstrapConfigSerde {
     pool_contract_parameters: Option<PoolContractParametersSerde>,
     refresh_contract_parameters: Option<RefreshContractParametersSerde>,
     update_contract_parameters: Option<UpdateContractParametersSerde>,
+    #[serde(default)]
+    min_votes: Option<u64>,
     tokens_to_mint: UpdateTokensToMint,
 }
 
@@ -432,7 +528,7 @@ impl TryFrom<UpdateBootstrapConfigSerde> for UpdateBootstrapConfig {
     ) -> Result<UpdateBootstrapConfig, Self::Error> {
         let pool_contract_parameters = if let Some(c) = config_serde.pool_contract_parameters {
             Some(PoolContractParameters::checked_load(
-                base16::decode(c.ergo_tree_bytes.as_str())?,
+                c.decode_ergo_tree_bytes()?,
                 c.refresh_nft_index,
                 c.update_nft_index,
             )?)
@@ -442,28 +538,14 @@ impl TryFrom<UpdateBootstrapConfigSerde> for UpdateBootstrapConfig {
 
         let refresh_contract_parameters = if let Some(c) = config_serde.refresh_contract_parameters
         {
-            Some(RefreshContractParameters::build_with(
-                RefreshContractParametersInputs {
-                    ergo_tree_bytes: base16::decode(c.ergo_tree_bytes.as_str())?,
-                    pool_nft_index: c.pool_nft_index,
-                    oracle_token_id_index: c.oracle_token_id_index,
-                    min_data_points_index: c.min_data_points_index,
-                    min_data_points: c.min_data_points,
-                    buffer_length_index: c.buffer_length_index,
-                    buffer_length: c.buffer_length,
-                    max_deviation_percent_index: c.max_deviation_percent_index,
-                    max_deviation_percent: c.max_deviation_percent,
-                    epoch_length_index: c.epoch_length_index,
-                    epoch_length: c.epoch_length,
-                },
-            )?)
+            Some(RefreshContractParameters::build_with(c.inputs()?)?)
         } else {
             None
         };
 
         let update_contract_parameters = if let Some(c) = config_serde.update_contract_parameters {
             Some(UpdateContractParameters::build_with(
-                base16::decode(c.ergo_tree_bytes.as_str())?,
+                c.decode_ergo_tree_bytes()?,
                 c.pool_nft_index,
                 c.ballot_token_index,
                 c.min_votes_index,
@@ -477,6 +559,7 @@ impl TryFrom<UpdateBootstrapConfigSerde> for UpdateBootstrapConfig {
             pool_contract_parameters,
             refresh_contract_parameters,
             update_contract_parameters,
+            min_votes: config_serde.min_votes,
             tokens_to_mint: config_serde.tokens_to_mint,
         })
     }
@@ -506,3 +589,43 @@ where
             .into(),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_contract() {
+        assert_eq!(
+            decode_contract(Some("0008cd"), None).unwrap(),
+            vec![0x00, 0x08, 0xcd]
+        );
+        assert!(matches!(
+            decode_contract(Some("0008cd"), Some("HEIGHT > 100")),
+            Err(SerdeConversionError::ErgoScript(
+                ErgoScriptError::BothContracts
+            ))
+        ));
+        assert!(matches!(
+            decode_contract(None, None),
+            Err(SerdeConversionError::ErgoScript(
+                ErgoScriptError::NoContract
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_compiled_contract_without_the_expected_constants() {
+        let c: PoolContractParametersSerde = serde_yaml::from_str(
+            "ergo_script: HEIGHT > 100\nrefresh_nft_index: 0\nupdate_nft_index: 0\n",
+        )
+        .unwrap();
+        // the constant at `refresh_nft_index` is the Int 100, not a token id
+        assert!(PoolContractParameters::checked_load(
+            c.decode_ergo_tree_bytes().unwrap(),
+            c.refresh_nft_index,
+            c.update_nft_index,
+        )
+        .is_err());
+    }
+}
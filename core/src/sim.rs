@@ -0,0 +1,717 @@
+//! Simulated oracle pool on [`ergo_chain_sim::ChainSim`], for running the pool lifecycle end to
+//! end without a node: bootstrap, datapoints posted by several oracles, refresh, vote and update.
+//!
+//! The txs are built with the same builders as the `run` command (see [`crate::tx_api`]) and
+//! signed by the local wallets of the simulated oracles, so every input contract is evaluated
+//! when a tx is signed (the chain simulation itself does not verify the txs). Contract or
+//! parameter changes are tried by bootstrapping with a modified [`BootstrapConfig`] or updating to
+//! new pool contract inputs.
+//!
+//! Built in tests and with the `sim` feature.
+
+use std::cell::RefCell;
+use std::convert::TryFrom;
+
+use ergo_chain_sim::Block;
+use ergo_chain_sim::ChainSim;
+use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilder;
+use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilderError;
+use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::Transaction;
+use ergo_lib::chain::transaction::TxId;
+use ergo_lib::chain::transaction::TxIoVec;
+use ergo_lib::ergo_chain_types::blake2b256_hash;
+use ergo_lib::ergo_chain_types::Digest32;
+use ergo_lib::ergo_chain_types::EcPoint;
+use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+use ergo_lib::ergotree_ir::chain::address::Address;
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValueError;
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxTokens;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::chain::token::Token;
+use ergo_lib::ergotree_ir::chain::token::TokenAmount;
+use ergo_lib::ergotree_ir::chain::token::TokenAmountError;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+use ergo_lib::ergotree_ir::ergo_tree::ErgoTree;
+use ergo_lib::ergotree_ir::serialization::SigmaParsingError;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use ergo_lib::wallet::box_selector::BoxSelector;
+use ergo_lib::wallet::box_selector::BoxSelectorError;
+use ergo_lib::wallet::box_selector::SimpleBoxSelector;
+use ergo_lib::wallet::signing::TransactionContext;
+use ergo_lib::wallet::tx_builder::TxBuilder;
+use ergo_lib::wallet::tx_builder::TxBuilderError;
+use ergo_lib::wallet::Wallet;
+use ergo_node_interface::node_interface::NodeError;
+use sigma_test_util::force_any_val;
+use thiserror::Error;
+
+use crate::box_kind::BallotBox;
+use crate::box_kind::BallotBoxWrapper;
+use crate::box_kind::OracleBox;
+use crate::box_kind::OracleBoxWrapper;
+use crate::box_kind::PoolBox;
+use crate::box_kind::PoolBoxError;
+use crate::box_kind::PoolBoxWrapper;
+use crate::box_kind::PostedOracleBox;
+use crate::box_kind::RefreshBox;
+use crate::box_kind::RefreshBoxError;
+use crate::box_kind::RefreshBoxWrapper;
+use crate::box_kind::UpdateBoxError;
+use crate::box_kind::UpdateBoxWrapper;
+use crate::box_kind::VoteBallotBoxWrapper;
+use crate::cli_commands::bootstrap::perform_bootstrap_chained_transaction;
+use crate::cli_commands::bootstrap::BootstrapConfig;
+use crate::cli_commands::bootstrap::BootstrapError;
+use crate::cli_commands::bootstrap::BootstrapInput;
+use crate::cli_commands::update_pool::UpdatePoolError;
+use crate::cli_commands::vote_update_pool::VoteUpdatePoolError;
+use crate::contracts::pool::PoolContract;
+use crate::contracts::pool::PoolContractError;
+use crate::contracts::pool::PoolContractInputs;
+use crate::node_interface;
+use crate::node_interface::SignTransactionWithInputs;
+use crate::node_interface::SubmitTransaction;
+use crate::oracle_config::TxParams;
+use crate::oracle_types::BlockHeight;
+use crate::oracle_types::Rate;
+use crate::pool_commands::publish_datapoint::PublishDatapointActionError;
+use crate::pool_commands::refresh::RefreshActionError;
+use crate::pool_config::PoolConfig;
+use crate::spec_token::RewardTokenId;
+use crate::spec_token::SpecToken;
+use crate::spec_token::TokenIdKind;
+use crate::tx_api;
+use crate::wallet::WalletDataError;
+use crate::wallet::WalletDataSource;
+
+/// Height of the simulated chain before the first block
+pub const SIM_START_HEIGHT: u32 = 100_000;
+
+/// nanoERG every simulated oracle gets for its boxes and tx fees
+const ORACLE_FUNDS: u64 = 1_000_000_000;
+
+#[derive(Debug, Error)]
+pub enum SimError {
+    #[error("bootstrap error: {0}")]
+    Bootstrap(#[from] BootstrapError),
+    #[error("publish datapoint error: {0}")]
+    PublishDatapoint(#[from] PublishDatapointActionError),
+    #[error("refresh error: {0}")]
+    Refresh(#[from] RefreshActionError),
+    #[error("vote error: {0}")]
+    Vote(#[from] VoteUpdatePoolError),
+    #[error("update pool error: {0}")]
+    UpdatePool(#[from] UpdatePoolError),
+    #[error("pool box error: {0}")]
+    PoolBox(#[from] PoolBoxError),
+    #[error("refresh box error: {0}")]
+    RefreshBox(#[from] RefreshBoxError),
+    #[error("update box error: {0}")]
+    UpdateBox(#[from] UpdateBoxError),
+    #[error("pool contract error: {0}")]
+    PoolContract(#[from] PoolContractError),
+    #[error("tx builder error: {0}")]
+    TxBuilder(#[from] TxBuilderError),
+    #[error("box builder error: {0}")]
+    ErgoBoxCandidateBuilder(#[from] ErgoBoxCandidateBuilderError),
+    #[error("box selector error: {0}")]
+    BoxSelector(#[from] BoxSelectorError),
+    #[error("box value error: {0}")]
+    BoxValue(#[from] BoxValueError),
+    #[error("token amount error: {0}")]
+    TokenAmount(#[from] TokenAmountError),
+    #[error("SigmaParsing error: {0}")]
+    SigmaParse(#[from] SigmaParsingError),
+    #[error("signing error: {0}")]
+    Signing(String),
+    #[error("no unspent box with the token {0:?}")]
+    NoBoxWithToken(TokenId),
+    #[error("tx input {0:?} is not an unspent box")]
+    UnknownInput(BoxId),
+    #[error("no simulated oracle #{0}")]
+    UnknownOracle(usize),
+}
+
+/// Oracle operator of the simulation with a local wallet
+pub struct SimOracle {
+    secret: DlogProverInput,
+    wallet: Wallet,
+    /// P2PK address of the wallet, also the change address
+    pub address: NetworkAddress,
+}
+
+impl SimOracle {
+    fn random() -> Self {
+        let secret = DlogProverInput::random();
+        let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
+        let address = NetworkAddress::new(
+            NetworkPrefix::Mainnet,
+            &Address::P2Pk(secret.public_image()),
+        );
+        SimOracle {
+            secret,
+            wallet,
+            address,
+        }
+    }
+
+    pub fn public_key(&self) -> EcPoint {
+        *self.secret.public_image().h
+    }
+
+    fn ergo_tree(&self) -> Result<ErgoTree, SimError> {
+        Ok(self.address.address().script()?)
+    }
+}
+
+/// Submits every tx in its own block
+pub(crate) struct ChainSubmitTx<'a> {
+    pub chain: RefCell<&'a mut ChainSim>,
+}
+
+impl<'a> SubmitTransaction for ChainSubmitTx<'a> {
+    fn submit_transaction(&self, tx: &Transaction) -> node_interface::Result<TxId> {
+        self.chain
+            .borrow_mut()
+            .add_block(Block::new(vec![tx.clone()]));
+        Ok(tx.id())
+    }
+}
+
+struct LocalTxSigner<'a> {
+    wallet: &'a Wallet,
+    state_context: &'a ErgoStateContext,
+}
+
+impl<'a> SignTransactionWithInputs for LocalTxSigner<'a> {
+    fn sign_transaction_with_inputs(
+        &self,
+        unsigned_tx: &UnsignedTransaction,
+        inputs: TxIoVec<ErgoBox>,
+        data_boxes: Option<TxIoVec<ErgoBox>>,
+    ) -> node_interface::Result<Transaction> {
+        sign_tx(
+            self.wallet,
+            self.state_context,
+            unsigned_tx.clone(),
+            inputs.as_vec().clone(),
+            data_boxes.map(|bs| bs.as_vec().clone()).unwrap_or_default(),
+        )
+        .map_err(|e| NodeError::BadRequest(e.to_string()))
+    }
+}
+
+fn sign_tx(
+    wallet: &Wallet,
+    state_context: &ErgoStateContext,
+    unsigned_tx: UnsignedTransaction,
+    inputs: Vec<ErgoBox>,
+    data_boxes: Vec<ErgoBox>,
+) -> Result<Transaction, SimError> {
+    let tx_context = TransactionContext::new(unsigned_tx, inputs, data_boxes)
+        .map_err(|e| SimError::Signing(e.to_string()))?;
+    wallet
+        .sign_transaction(tx_context, state_context, None)
+        .map_err(|e| SimError::Signing(e.to_string()))
+}
+
+struct SimWallet {
+    boxes: Vec<ErgoBox>,
+    change_address: NetworkAddress,
+}
+
+impl WalletDataSource for SimWallet {
+    fn get_unspent_wallet_boxes(&self) -> Result<Vec<ErgoBox>, WalletDataError> {
+        Ok(self.boxes.clone())
+    }
+
+    fn get_change_address(&self) -> Result<NetworkAddress, WalletDataError> {
+        Ok(self.change_address.clone())
+    }
+}
+
+/// Blake2b256 hash of the pool contract, the vote for updating the pool box to it
+pub fn pool_box_address_hash(pool_contract: &PoolContract) -> Digest32 {
+    blake2b256_hash(&pool_contract.ergo_tree().sigma_serialize_bytes().unwrap())
+}
+
+fn holds_token(b: &ErgoBox, token_id: TokenId) -> bool {
+    b.tokens.as_ref().map_or(false, |tokens| {
+        tokens.iter().any(|t| t.token_id == token_id)
+    })
+}
+
+/// Oracle pool bootstrapped on a simulated chain, with oracles holding an oracle, a ballot and a
+/// reward token each. Every submitted tx is put in its own block, the height is
+/// [`SIM_START_HEIGHT`] plus the number of blocks.
+pub struct PoolSim {
+    chain: ChainSim,
+    state_context: ErgoStateContext,
+    pool_config: PoolConfig,
+    operator: SimOracle,
+    oracles: Vec<SimOracle>,
+    tx_params: TxParams,
+}
+
+impl PoolSim {
+    /// Bootstrap the pool with `config` from the wallet of an operator and send the tokens to
+    /// `oracle_count` oracles (up to the minted oracle tokens)
+    pub fn bootstrap(config: BootstrapConfig, oracle_count: usize) -> Result<Self, SimError> {
+        Self::bootstrap_with_tx_params(config, oracle_count, TxParams::default())
+    }
+
+    /// Same as [`PoolSim::bootstrap`] with the fee and min box value of `tx_params` for all txs,
+    /// e.g. the ones of an oracle config
+    pub fn bootstrap_with_tx_params(
+        config: BootstrapConfig,
+        oracle_count: usize,
+        tx_params: TxParams,
+    ) -> Result<Self, SimError> {
+        let mut chain = ChainSim::new();
+        let mut state_context = force_any_val::<ErgoStateContext>();
+        state_context.pre_header.height = SIM_START_HEIGHT;
+        let erg_value_per_box = config
+            .oracle_contract_parameters
+            .min_storage_rent
+            .max(tx_params.min_box_value);
+        let operator = SimOracle::random();
+        let oracles = (0..oracle_count).map(|_| SimOracle::random()).collect();
+        let operator_tree = operator.ergo_tree()?;
+        chain.generate_unspent_box(
+            operator_tree.clone(),
+            BoxValue::try_from(ORACLE_FUNDS * (oracle_count as u64 + 1))?,
+            None,
+        );
+        let operator_wallet = SimWallet {
+            boxes: chain.get_unspent_boxes(&operator_tree),
+            change_address: operator.address.clone(),
+        };
+        let (pool_config, _) = perform_bootstrap_chained_transaction(BootstrapInput {
+            oracle_address: operator.address.clone(),
+            config,
+            wallet: &operator_wallet,
+            tx_signer: &LocalTxSigner {
+                wallet: &operator.wallet,
+                state_context: &state_context,
+            },
+            submit_tx: &ChainSubmitTx {
+                chain: RefCell::new(&mut chain),
+            },
+            tx_fee: tx_params.fee,
+            erg_value_per_box,
+            change_address: operator.address.address(),
+            height: BlockHeight(SIM_START_HEIGHT),
+        })?;
+        let mut sim = PoolSim {
+            chain,
+            state_context,
+            pool_config,
+            operator,
+            oracles,
+            tx_params,
+        };
+        sim.distribute_tokens()?;
+        Ok(sim)
+    }
+
+    /// Send an oracle, a ballot and a reward token along with [`ORACLE_FUNDS`] to every oracle
+    fn distribute_tokens(&mut self) -> Result<TxId, SimError> {
+        let token_ids = &self.pool_config.token_ids;
+        let height = self.height();
+        let one = TokenAmount::try_from(1u64)?;
+        let oracle_tokens = [
+            token_ids.oracle_token_id.token_id(),
+            token_ids.ballot_token_id.token_id(),
+            token_ids.reward_token_id.token_id(),
+        ];
+        let mut output_candidates = vec![];
+        for oracle in &self.oracles {
+            let mut builder = ErgoBoxCandidateBuilder::new(
+                BoxValue::try_from(ORACLE_FUNDS)?,
+                oracle.ergo_tree()?,
+                height.0,
+            );
+            for token_id in oracle_tokens {
+                builder.add_token(Token {
+                    token_id,
+                    amount: one,
+                });
+            }
+            output_candidates.push(builder.build()?);
+        }
+        let oracle_count = TokenAmount::try_from(self.oracles.len() as u64)?;
+        let target_tokens: Vec<Token> = oracle_tokens
+            .iter()
+            .map(|token_id| Token {
+                token_id: *token_id,
+                amount: oracle_count,
+            })
+            .collect();
+        let target_balance = BoxValue::try_from(ORACLE_FUNDS)?
+            .checked_mul_u32(self.oracles.len() as u32)?
+            .checked_add(&self.tx_params.fee)?;
+        let selection = SimpleBoxSelector::new().select(
+            self.wallet_boxes(&self.operator)?,
+            target_balance,
+            &target_tokens,
+        )?;
+        let tx = TxBuilder::new(
+            selection,
+            output_candidates,
+            height.0,
+            self.tx_params.fee,
+            self.operator.address.address(),
+        )
+        .build()?;
+        let tx = self.sign(&self.operator.wallet, tx)?;
+        Ok(self.submit(tx))
+    }
+
+    pub fn height(&self) -> BlockHeight {
+        BlockHeight(SIM_START_HEIGHT + self.chain.height)
+    }
+
+    pub fn chain(&self) -> &ChainSim {
+        &self.chain
+    }
+
+    /// Pool config of the bootstrapped pool, with the pool contract inputs and the reward token
+    /// changed by [`PoolSim::update_pool`]
+    pub fn pool_config(&self) -> &PoolConfig {
+        &self.pool_config
+    }
+
+    pub fn oracles(&self) -> &[SimOracle] {
+        &self.oracles
+    }
+
+    /// Add `blocks` empty blocks
+    pub fn advance(&mut self, blocks: u32) {
+        for _ in 0..blocks {
+            self.chain.add_block(Block::new(vec![]));
+        }
+    }
+
+    /// Advance until the epoch of the pool box is over and the pool can be refreshed
+    pub fn advance_epoch(&mut self) -> Result<(), SimError> {
+        let epoch_length = self.refresh_box()?.contract().epoch_length().0 as u32;
+        let epoch_end = self.pool_box()?.get_box().creation_height + epoch_length;
+        let height = self.height().0;
+        if height <= epoch_end {
+            self.advance(epoch_end + 1 - height);
+        }
+        Ok(())
+    }
+
+    /// Add an unspent box to the wallet of the oracle, e.g. with the tokens of a pool update
+    pub fn fund(
+        &mut self,
+        oracle: usize,
+        value: BoxValue,
+        tokens: Option<BoxTokens>,
+    ) -> Result<(), SimError> {
+        let ergo_tree = self.oracle(oracle)?.ergo_tree()?;
+        self.chain.generate_unspent_box(ergo_tree, value, tokens);
+        Ok(())
+    }
+
+    pub fn pool_box(&self) -> Result<PoolBoxWrapper, SimError> {
+        let pool_box =
+            self.box_with_token(self.pool_config.token_ids.pool_nft_token_id.token_id())?;
+        Ok(PoolBoxWrapper::new(
+            pool_box,
+            &self.pool_config.pool_box_wrapper_inputs,
+        )?)
+    }
+
+    pub fn refresh_box(&self) -> Result<RefreshBoxWrapper, SimError> {
+        let refresh_box =
+            self.box_with_token(self.pool_config.token_ids.refresh_nft_token_id.token_id())?;
+        Ok(RefreshBoxWrapper::new(
+            refresh_box,
+            &self.pool_config.refresh_box_wrapper_inputs,
+        )?)
+    }
+
+    pub fn update_box(&self) -> Result<UpdateBoxWrapper, SimError> {
+        let update_box =
+            self.box_with_token(self.pool_config.token_ids.update_nft_token_id.token_id())?;
+        Ok(UpdateBoxWrapper::new(
+            update_box,
+            &self.pool_config.update_box_wrapper_inputs,
+        )?)
+    }
+
+    /// Oracle box (posted or collected) of the oracle, `None` before its first datapoint
+    pub fn oracle_box(&self, oracle: usize) -> Result<Option<OracleBoxWrapper>, SimError> {
+        let public_key = self.oracle(oracle)?.public_key();
+        Ok(self
+            .chain
+            .unspent_boxes()
+            .iter()
+            .filter_map(|b| {
+                OracleBoxWrapper::new(b.clone(), &self.pool_config.oracle_box_wrapper_inputs).ok()
+            })
+            .find(|b| b.public_key() == public_key))
+    }
+
+    pub fn posted_oracle_boxes(&self) -> Vec<PostedOracleBox> {
+        self.chain
+            .unspent_boxes()
+            .iter()
+            .filter_map(|b| {
+                PostedOracleBox::new(b.clone(), &self.pool_config.oracle_box_wrapper_inputs).ok()
+            })
+            .collect()
+    }
+
+    /// Ballot box of the oracle, `None` before its first vote
+    pub fn ballot_box(&self, oracle: usize) -> Result<Option<BallotBoxWrapper>, SimError> {
+        let public_key = self.oracle(oracle)?.public_key();
+        Ok(self
+            .chain
+            .unspent_boxes()
+            .iter()
+            .filter_map(|b| {
+                BallotBoxWrapper::new(b.clone(), &self.pool_config.ballot_box_wrapper_inputs).ok()
+            })
+            .find(|b| b.ballot_token_owner() == public_key))
+    }
+
+    pub fn vote_ballot_boxes(&self) -> Vec<VoteBallotBoxWrapper> {
+        self.chain
+            .unspent_boxes()
+            .iter()
+            .filter_map(|b| {
+                VoteBallotBoxWrapper::new(b.clone(), &self.pool_config.ballot_box_wrapper_inputs)
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Publish `rate` for the epoch of the pool box, creating the oracle box on the first
+    /// datapoint of the oracle
+    pub fn post_datapoint(&mut self, oracle: usize, rate: Rate) -> Result<TxId, SimError> {
+        let sim_oracle = self.oracle(oracle)?;
+        let wallet_boxes = self.wallet_boxes(sim_oracle)?;
+        let tx = match self.oracle_box(oracle)? {
+            Some(oracle_box) => tx_api::build_publish_datapoint_tx(
+                &oracle_box,
+                rate,
+                self.pool_box()?.epoch_counter(),
+                &self.pool_config.token_ids.reward_token_id,
+                wallet_boxes,
+                self.height(),
+                sim_oracle.address.clone(),
+                &self.tx_params,
+            )?,
+            None => tx_api::build_publish_first_datapoint_tx(
+                self.pool_config.oracle_box_wrapper_inputs.clone(),
+                sim_oracle.public_key(),
+                rate,
+                wallet_boxes,
+                self.height(),
+                sim_oracle.address.clone(),
+                &self.tx_params,
+            )?,
+        };
+        let tx = self.sign(&sim_oracle.wallet, tx)?;
+        Ok(self.submit(tx))
+    }
+
+    /// Publish `rates[i]` from the oracle `i`
+    pub fn post_datapoints(&mut self, rates: &[Rate]) -> Result<Vec<TxId>, SimError> {
+        rates
+            .iter()
+            .enumerate()
+            .map(|(oracle, rate)| self.post_datapoint(oracle, *rate))
+            .collect()
+    }
+
+    /// Refresh the pool box collecting the posted datapoints, the oracle box of `collector` has to
+    /// be among them
+    pub fn refresh(&mut self, collector: usize) -> Result<TxId, SimError> {
+        let sim_oracle = self.oracle(collector)?;
+        let refresh_box = self.refresh_box()?;
+        let max_deviation_percent = refresh_box.contract().max_deviation_percent() as u32;
+        let min_data_points = refresh_box.contract().min_data_points();
+        let tx = tx_api::build_refresh_tx(
+            self.pool_box()?,
+            refresh_box,
+            self.posted_oracle_boxes(),
+            None,
+            max_deviation_percent,
+            min_data_points,
+            &sim_oracle.public_key(),
+            self.pool_config.buyback_max_reward_tokens_per_refresh,
+            self.pool_config.erg_rewards.as_ref(),
+            self.wallet_boxes(sim_oracle)?,
+            self.height(),
+            sim_oracle.address.clone(),
+            &self.tx_params,
+        )?;
+        let tx = self.sign(&sim_oracle.wallet, tx)?;
+        Ok(self.submit(tx))
+    }
+
+    /// One epoch of the pool: wait for the end of the current epoch, publish `rates[i]` from
+    /// the oracle `i` and refresh by `collector`
+    pub fn run_epoch(&mut self, rates: &[Rate], collector: usize) -> Result<TxId, SimError> {
+        self.advance_epoch()?;
+        self.post_datapoints(rates)?;
+        self.refresh(collector)
+    }
+
+    /// Vote for updating the pool box to the pool contract built with `new_pool_contract_inputs`
+    /// (and `new_reward_tokens`)
+    pub fn vote(
+        &mut self,
+        oracle: usize,
+        new_pool_contract_inputs: &PoolContractInputs,
+        new_reward_tokens: Option<SpecToken<RewardTokenId>>,
+    ) -> Result<TxId, SimError> {
+        let sim_oracle = self.oracle(oracle)?;
+        let new_pool_box_address_hash =
+            pool_box_address_hash(&PoolContract::build_with(new_pool_contract_inputs)?);
+        let update_box_creation_height = BlockHeight(self.update_box()?.get_box().creation_height);
+        let wallet_boxes = self.wallet_boxes(sim_oracle)?;
+        let tx = match self.ballot_box(oracle)? {
+            Some(ballot_box) => tx_api::build_vote_tx(
+                ballot_box,
+                new_pool_box_address_hash,
+                new_reward_tokens,
+                update_box_creation_height,
+                wallet_boxes,
+                self.height(),
+                sim_oracle.address.clone(),
+                &self.tx_params,
+            )?,
+            None => tx_api::build_first_vote_tx(
+                sim_oracle.address.address(),
+                self.pool_config
+                    .ballot_box_wrapper_inputs
+                    .contract_inputs
+                    .contract_parameters(),
+                &self.pool_config.token_ids,
+                new_pool_box_address_hash,
+                new_reward_tokens,
+                update_box_creation_height,
+                wallet_boxes,
+                self.height(),
+                sim_oracle.address.clone(),
+                &self.tx_params,
+            )?,
+        };
+        let tx = self.sign(&sim_oracle.wallet, tx)?;
+        Ok(self.submit(tx))
+    }
+
+    /// Vote from every oracle
+    pub fn vote_all(
+        &mut self,
+        new_pool_contract_inputs: &PoolContractInputs,
+        new_reward_tokens: Option<SpecToken<RewardTokenId>>,
+    ) -> Result<Vec<TxId>, SimError> {
+        (0..self.oracles.len())
+            .map(|oracle| self.vote(oracle, new_pool_contract_inputs, new_reward_tokens.clone()))
+            .collect()
+    }
+
+    /// Update the pool box spending the ballot boxes voting for it, submitted by `submitter`
+    /// whose wallet holds the new reward tokens if any. The pool config of the simulation is
+    /// switched to the new pool contract and reward token.
+    pub fn update_pool(
+        &mut self,
+        submitter: usize,
+        new_pool_contract_inputs: PoolContractInputs,
+        new_reward_tokens: Option<SpecToken<RewardTokenId>>,
+    ) -> Result<TxId, SimError> {
+        let sim_oracle = self.oracle(submitter)?;
+        let tx = tx_api::build_update_pool_tx(
+            self.pool_box()?,
+            self.update_box()?,
+            self.vote_ballot_boxes(),
+            PoolContract::build_with(&new_pool_contract_inputs)?,
+            new_reward_tokens.clone(),
+            self.wallet_boxes(sim_oracle)?,
+            self.height(),
+            sim_oracle.address.clone(),
+            &self.tx_params,
+        )?;
+        let tx = self.sign(&sim_oracle.wallet, tx)?;
+        let tx_id = self.submit(tx);
+        self.pool_config.pool_box_wrapper_inputs.contract_inputs = new_pool_contract_inputs;
+        if let Some(reward_tokens) = new_reward_tokens {
+            self.pool_config.token_ids.reward_token_id = reward_tokens.token_id.clone();
+            self.pool_config.pool_box_wrapper_inputs.reward_token_id =
+                reward_tokens.token_id.clone();
+            self.pool_config.oracle_box_wrapper_inputs.reward_token_id = reward_tokens.token_id;
+        }
+        Ok(tx_id)
+    }
+
+    fn oracle(&self, oracle: usize) -> Result<&SimOracle, SimError> {
+        self.oracles
+            .get(oracle)
+            .ok_or(SimError::UnknownOracle(oracle))
+    }
+
+    fn wallet_boxes(&self, oracle: &SimOracle) -> Result<Vec<ErgoBox>, SimError> {
+        Ok(self.chain.get_unspent_boxes(&oracle.ergo_tree()?))
+    }
+
+    fn box_with_token(&self, token_id: TokenId) -> Result<ErgoBox, SimError> {
+        self.chain
+            .unspent_boxes()
+            .iter()
+            .find(|b| holds_token(b, token_id))
+            .cloned()
+            .ok_or(SimError::NoBoxWithToken(token_id))
+    }
+
+    fn unspent_box(&self, box_id: BoxId) -> Result<ErgoBox, SimError> {
+        self.chain
+            .unspent_boxes()
+            .iter()
+            .find(|b| b.box_id() == box_id)
+            .cloned()
+            .ok_or(SimError::UnknownInput(box_id))
+    }
+
+    /// Sign at the current height with the inputs and data inputs from the unspent boxes
+    fn sign(
+        &self,
+        wallet: &Wallet,
+        unsigned_tx: UnsignedTransaction,
+    ) -> Result<Transaction, SimError> {
+        let inputs = unsigned_tx
+            .inputs
+            .as_vec()
+            .iter()
+            .map(|input| self.unspent_box(input.box_id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let data_boxes = match &unsigned_tx.data_inputs {
+            Some(data_inputs) => data_inputs
+                .as_vec()
+                .iter()
+                .map(|data_input| self.unspent_box(data_input.box_id))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => vec![],
+        };
+        let mut state_context = self.state_context.clone();
+        state_context.pre_header.height = self.height().0;
+        sign_tx(wallet, &state_context, unsigned_tx, inputs, data_boxes)
+    }
+
+    fn submit(&mut self, tx: Transaction) -> TxId {
+        let tx_id = tx.id();
+        self.chain.add_block(Block::new(vec![tx]));
+        tx_id
+    }
+}
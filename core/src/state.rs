@@ -1,9 +1,14 @@
+use crate::contracts::refresh::RefreshContractParameters;
 use crate::oracle_state::LiveEpochState;
 use crate::oracle_state::LocalDatapointState::Collected;
 use crate::oracle_state::LocalDatapointState::Posted;
 use crate::oracle_types::BlockHeight;
 use crate::oracle_types::EpochLength;
+use crate::oracle_types::MinDatapoints;
 use crate::pool_commands::PoolCommand;
+use crate::posting_policy::CurrentRate;
+use crate::posting_policy::PostingContext;
+use crate::posting_policy::PostingPolicy;
 
 pub struct EpochState {
     epoch_start_height: u64,
@@ -16,12 +21,115 @@ pub enum PoolState {
     LiveEpoch(LiveEpochState),
 }
 
+/// Refresh contract parameters that define the epoch schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EpochParams {
+    pub epoch_length: EpochLength,
+    /// Max number of blocks between the tx creation height and the height it is included at
+    pub buffer_length: u32,
+    pub min_data_points: MinDatapoints,
+    pub max_deviation_percent: u32,
+}
+
+impl From<&RefreshContractParameters> for EpochParams {
+    fn from(parameters: &RefreshContractParameters) -> Self {
+        EpochParams {
+            epoch_length: parameters.epoch_length(),
+            buffer_length: parameters.buffer_length_blocks(),
+            min_data_points: parameters.min_data_points(),
+            max_deviation_percent: parameters.max_deviation_percent_value(),
+        }
+    }
+}
+
+impl EpochParams {
+    fn epoch_length_blocks(&self) -> u32 {
+        self.epoch_length.0.max(0) as u32
+    }
+
+    /// First height at which the pool box created at `pool_box_height` can be refreshed.
+    /// The refresh contract requires the pool box to be created before `HEIGHT - epoch_length`.
+    pub fn first_refresh_height(&self, pool_box_height: BlockHeight) -> BlockHeight {
+        BlockHeight(pool_box_height.0 + self.epoch_length_blocks() + 1)
+    }
+
+    /// Whether a datapoint created at `datapoint_height` can be collected by a refresh at `height`.
+    /// The refresh contract only accepts datapoints created at or after `HEIGHT - epoch_length`.
+    pub fn is_collectable(&self, datapoint_height: BlockHeight, height: BlockHeight) -> bool {
+        datapoint_height.0 + self.epoch_length_blocks() >= height.0
+    }
+
+    /// First height to publish a datapoint for the epoch of the pool box created at
+    /// `pool_box_height`. Publishing half an epoch after the pool box keeps the datapoint from going
+    /// stale right after the epoch ends, and it must stay collectable for at least `buffer_length`
+    /// blocks after the first refresh height for a refresh tx to be included in time.
+    pub fn first_publish_height(&self, pool_box_height: BlockHeight) -> BlockHeight {
+        let wait = (self.epoch_length_blocks() / 2).max(self.buffer_length);
+        BlockHeight(pool_box_height.0 + wait + 1)
+    }
+}
+
+/// Default number of blocks an oracle waits per rank before submitting a refresh
+pub const DEFAULT_REFRESH_DELAY_BLOCKS_PER_RANK: u32 = 1;
+
+/// Blocks to wait after the epoch end before submitting a refresh.
+/// Oracles that posted in the epoch get a rank each and refresh one after another instead of
+/// all racing to submit conflicting refresh txs on the same block.
+fn refresh_delay(local_refresh_rank: Option<u32>, delay_blocks_per_rank: u32) -> u32 {
+    local_refresh_rank
+        .unwrap_or(0)
+        .saturating_mul(delay_blocks_per_rank)
+}
+
+/// Whether the local datapoint is the last one needed to reach `min_data_points` in an epoch that
+/// can already be refreshed by the local oracle (after the delay of its refresh rank), so the
+/// publish and refresh txs can be submitted together
+fn is_last_needed_datapoint(
+    epoch_params: &EpochParams,
+    pool_box_height: BlockHeight,
+    other_posted_datapoint_heights: &[BlockHeight],
+    current_height: BlockHeight,
+    refresh_delay_blocks: u32,
+) -> bool {
+    let collectable_count = other_posted_datapoint_heights
+        .iter()
+        .filter(|h| epoch_params.is_collectable(**h, current_height))
+        .count() as i32;
+    current_height.0 >= epoch_params.first_refresh_height(pool_box_height).0 + refresh_delay_blocks
+        && collectable_count + 1 == epoch_params.min_data_points.0
+}
+
+/// Whether the posting policy takes the publish opportunity
+fn policy_publishes(
+    posting_policy: &dyn PostingPolicy,
+    live_epoch: &LiveEpochState,
+    epoch_params: &EpochParams,
+    current_height: BlockHeight,
+    current_rate: &CurrentRate,
+) -> bool {
+    let publishes = posting_policy.should_publish(&PostingContext {
+        live_epoch,
+        epoch_params,
+        current_height,
+        current_rate,
+    });
+    if !publishes {
+        log::debug!(
+            "Height {current_height}. The {} posting policy skips the publish",
+            posting_policy.name()
+        );
+    }
+    publishes
+}
+
 pub fn process(
     pool_state: PoolState,
-    epoch_length: EpochLength,
+    epoch_params: &EpochParams,
     current_height: BlockHeight,
+    refresh_delay_blocks_per_rank: u32,
+    posting_policy: &dyn PostingPolicy,
+    current_rate: &CurrentRate,
 ) -> Option<PoolCommand> {
-    let min_start_height = current_height - epoch_length;
     match pool_state {
         PoolState::NeedsBootstrap => {
             log::warn!(
@@ -31,14 +139,30 @@ pub fn process(
         }
         PoolState::LiveEpoch(live_epoch) => {
             log::debug!("Height {current_height}. Live epoch state: {live_epoch:?}");
-            if let Some(local_datapoint_box_state) = live_epoch.local_datapoint_box_state {
+            let last_needed_datapoint = is_last_needed_datapoint(
+                epoch_params,
+                live_epoch.latest_pool_box_height,
+                &live_epoch.other_posted_datapoint_heights,
+                current_height,
+                refresh_delay(live_epoch.local_refresh_rank, refresh_delay_blocks_per_rank),
+            );
+            let publish_allowed = || {
+                policy_publishes(
+                    posting_policy,
+                    &live_epoch,
+                    epoch_params,
+                    current_height,
+                    current_rate,
+                )
+            };
+            if let Some(local_datapoint_box_state) = &live_epoch.local_datapoint_box_state {
                 match local_datapoint_box_state {
                     Collected { height: _ } => {
-                        // publish datapoint after some blocks have passed after the pool box published
-                        // to avoid some oracle box become stale on the next refresh
-                        // (datapoint posted on the first block of the epoch go out of the epoch window too fast)
-                        if current_height.0
-                            > live_epoch.latest_pool_box_height.0 + (epoch_length.0 as u32) / 2
+                        if last_needed_datapoint {
+                            Some(PoolCommand::PublishSubsequentDataPointAndRefresh)
+                        } else if current_height
+                            >= epoch_params.first_publish_height(live_epoch.latest_pool_box_height)
+                            && publish_allowed()
                         {
                             Some(PoolCommand::PublishSubsequentDataPoint { republish: false })
                         } else {
@@ -46,10 +170,24 @@ pub fn process(
                         }
                     }
                     Posted { epoch_id, height } => {
-                        if height < min_start_height || epoch_id != live_epoch.pool_box_epoch_id {
-                            Some(PoolCommand::PublishSubsequentDataPoint { republish: true })
-                        } else if live_epoch.latest_pool_box_height < min_start_height
-                            && epoch_id == live_epoch.pool_box_epoch_id
+                        if !epoch_params.is_collectable(*height, current_height)
+                            || *epoch_id != live_epoch.pool_box_epoch_id
+                        {
+                            if last_needed_datapoint {
+                                Some(PoolCommand::PublishSubsequentDataPointAndRefresh)
+                            } else if publish_allowed() {
+                                Some(PoolCommand::PublishSubsequentDataPoint { republish: true })
+                            } else {
+                                None
+                            }
+                        } else if current_height.0
+                            >= epoch_params
+                                .first_refresh_height(live_epoch.latest_pool_box_height)
+                                .0
+                                + refresh_delay(
+                                    live_epoch.local_refresh_rank,
+                                    refresh_delay_blocks_per_rank,
+                                )
                         {
                             Some(PoolCommand::Refresh)
                         } else {
@@ -65,4 +203,248 @@ pub fn process(
     }
 }
 
-// TODO: add tests
+#[cfg(test)]
+mod tests {
+    use crate::oracle_state::LocalDatapointState;
+    use crate::oracle_types::EpochCounter;
+    use crate::pool_commands::test_utils::MockDatapointSource;
+    use crate::posting_policy::PostingPolicyConfig;
+
+    use super::*;
+
+    const POOL_BOX_HEIGHT: BlockHeight = BlockHeight(1000);
+
+    fn epoch_params() -> EpochParams {
+        EpochParams {
+            epoch_length: EpochLength(30),
+            buffer_length: 4,
+            min_data_points: MinDatapoints(4),
+            max_deviation_percent: 5,
+        }
+    }
+
+    fn live_epoch(
+        local_datapoint_box_state: Option<LocalDatapointState>,
+        local_refresh_rank: Option<u32>,
+    ) -> PoolState {
+        PoolState::LiveEpoch(LiveEpochState {
+            pool_box_epoch_id: EpochCounter(5),
+            local_datapoint_box_state,
+            latest_pool_datapoint: 1,
+            latest_pool_box_height: POOL_BOX_HEIGHT,
+            local_refresh_rank,
+            other_posted_datapoint_heights: Vec::new(),
+        })
+    }
+
+    fn posted_live_epoch(local_refresh_rank: Option<u32>) -> PoolState {
+        live_epoch(
+            Some(Posted {
+                epoch_id: EpochCounter(5),
+                height: BlockHeight(1010),
+            }),
+            local_refresh_rank,
+        )
+    }
+
+    fn run(state: PoolState, height: u32, delay_per_rank: u32) -> Option<PoolCommand> {
+        run_with_policy(state, height, delay_per_rank, PostingPolicyConfig::Epoch)
+    }
+
+    fn run_with_policy(
+        state: PoolState,
+        height: u32,
+        delay_per_rank: u32,
+        posting_policy: PostingPolicyConfig,
+    ) -> Option<PoolCommand> {
+        let source = MockDatapointSource {
+            datapoint: 1.into(),
+        };
+        process(
+            state,
+            &epoch_params(),
+            BlockHeight(height),
+            delay_per_rank,
+            posting_policy.build().as_ref(),
+            &CurrentRate::new(&source),
+        )
+    }
+
+    #[test]
+    fn test_epoch_params_heights() {
+        let params = epoch_params();
+        assert_eq!(
+            params.first_refresh_height(POOL_BOX_HEIGHT),
+            BlockHeight(1031)
+        );
+        assert_eq!(
+            params.first_publish_height(POOL_BOX_HEIGHT),
+            BlockHeight(1016)
+        );
+        assert!(params.is_collectable(BlockHeight(1000), BlockHeight(1030)));
+        assert!(!params.is_collectable(BlockHeight(1000), BlockHeight(1031)));
+        let short_epoch = EpochParams {
+            epoch_length: EpochLength(6),
+            ..params
+        };
+        // buffer is longer than half an epoch
+        assert_eq!(
+            short_epoch.first_publish_height(POOL_BOX_HEIGHT),
+            BlockHeight(1005)
+        );
+    }
+
+    #[test]
+    fn test_needs_bootstrap() {
+        assert!(run(PoolState::NeedsBootstrap, 1000, 0).is_none());
+    }
+
+    #[test]
+    fn test_publish_first_datapoint() {
+        assert!(matches!(
+            run(live_epoch(None, None), 1001, 0),
+            Some(PoolCommand::PublishFirstDataPoint)
+        ));
+    }
+
+    #[test]
+    fn test_publish_after_collected() {
+        let collected = || {
+            live_epoch(
+                Some(Collected {
+                    height: POOL_BOX_HEIGHT,
+                }),
+                None,
+            )
+        };
+        assert!(run(collected(), 1015, 0).is_none());
+        assert!(matches!(
+            run(collected(), 1016, 0),
+            Some(PoolCommand::PublishSubsequentDataPoint { republish: false })
+        ));
+    }
+
+    #[test]
+    fn test_posting_policy_skips_publish() {
+        let collected = || {
+            live_epoch(
+                Some(Collected {
+                    height: POOL_BOX_HEIGHT,
+                }),
+                None,
+            )
+        };
+        // the pool rate is the current rate
+        let deviation = || PostingPolicyConfig::Deviation {
+            min_deviation_percent: 1.0,
+        };
+        assert!(run_with_policy(collected(), 1016, 0, deviation()).is_none());
+        assert!(run_with_policy(posted_live_epoch(None), 1041, 0, deviation()).is_none());
+        assert!(matches!(
+            run_with_policy(live_epoch(None, None), 1001, 0, deviation()),
+            Some(PoolCommand::PublishFirstDataPoint)
+        ));
+        assert!(matches!(
+            run_with_policy(posted_live_epoch(Some(0)), 1031, 0, deviation()),
+            Some(PoolCommand::Refresh)
+        ));
+    }
+
+    #[test]
+    fn test_republish_stale_datapoint() {
+        // still collectable at 1040, stale at 1041
+        assert!(!matches!(
+            run(posted_live_epoch(None), 1040, 0),
+            Some(PoolCommand::PublishSubsequentDataPoint { republish: true })
+        ));
+        assert!(matches!(
+            run(posted_live_epoch(None), 1041, 0),
+            Some(PoolCommand::PublishSubsequentDataPoint { republish: true })
+        ));
+        let previous_epoch = live_epoch(
+            Some(Posted {
+                epoch_id: EpochCounter(4),
+                height: BlockHeight(1010),
+            }),
+            None,
+        );
+        assert!(matches!(
+            run(previous_epoch, 1011, 0),
+            Some(PoolCommand::PublishSubsequentDataPoint { republish: true })
+        ));
+    }
+
+    #[test]
+    fn test_refresh_at_epoch_end() {
+        assert!(run(posted_live_epoch(Some(0)), 1030, 0).is_none());
+        assert!(matches!(
+            run(posted_live_epoch(Some(0)), 1031, 0),
+            Some(PoolCommand::Refresh)
+        ));
+    }
+
+    #[test]
+    fn test_refresh_delayed_by_rank() {
+        let is_refresh =
+            |state, height| matches!(run(state, height, 2), Some(PoolCommand::Refresh));
+        assert!(!is_refresh(posted_live_epoch(Some(0)), 1030));
+        assert!(is_refresh(posted_live_epoch(Some(0)), 1031));
+        assert!(is_refresh(posted_live_epoch(None), 1031));
+        assert!(!is_refresh(posted_live_epoch(Some(2)), 1034));
+        assert!(is_refresh(posted_live_epoch(Some(2)), 1035));
+    }
+
+    #[test]
+    fn test_publish_and_refresh_when_last_needed_datapoint() {
+        let collected_with_rank = |others: Vec<u32>, local_refresh_rank: Option<u32>| {
+            PoolState::LiveEpoch(LiveEpochState {
+                pool_box_epoch_id: EpochCounter(5),
+                local_datapoint_box_state: Some(Collected {
+                    height: POOL_BOX_HEIGHT,
+                }),
+                latest_pool_datapoint: 1,
+                latest_pool_box_height: POOL_BOX_HEIGHT,
+                local_refresh_rank,
+                other_posted_datapoint_heights: others.into_iter().map(BlockHeight).collect(),
+            })
+        };
+        let collected_with_others = |others: Vec<u32>| collected_with_rank(others, None);
+        // three other datapoints, four are needed
+        assert!(matches!(
+            run(collected_with_others(vec![1016, 1017, 1020]), 1031, 0),
+            Some(PoolCommand::PublishSubsequentDataPointAndRefresh)
+        ));
+        // the epoch is not over yet
+        assert!(matches!(
+            run(collected_with_others(vec![1016, 1017, 1020]), 1030, 0),
+            Some(PoolCommand::PublishSubsequentDataPoint { republish: false })
+        ));
+        // the datapoint at 1016 is not collectable anymore
+        assert!(matches!(
+            run(collected_with_others(vec![1016, 1017, 1020]), 1047, 0),
+            Some(PoolCommand::PublishSubsequentDataPoint { republish: false })
+        ));
+        // enough datapoints without the local one
+        assert!(matches!(
+            run(collected_with_others(vec![1016, 1017, 1020, 1021]), 1031, 0),
+            Some(PoolCommand::PublishSubsequentDataPoint { republish: false })
+        ));
+        // the refresh waits for the delay of the local refresh rank, 2 blocks per rank
+        assert!(matches!(
+            run(
+                collected_with_rank(vec![1016, 1017, 1020], Some(2)),
+                1034,
+                2
+            ),
+            Some(PoolCommand::PublishSubsequentDataPoint { republish: false })
+        ));
+        assert!(matches!(
+            run(
+                collected_with_rank(vec![1016, 1017, 1020], Some(2)),
+                1035,
+                2
+            ),
+            Some(PoolCommand::PublishSubsequentDataPointAndRefresh)
+        ));
+    }
+}
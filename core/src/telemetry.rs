@@ -0,0 +1,84 @@
+//! Export of the `tracing` spans of the main loop (node calls, datapoint fetches, action building)
+//! to an OpenTelemetry collector over OTLP/HTTP.
+//!
+//! Without the `otlp` feature the config is still parsed and the spans stay no-ops.
+
+#[cfg(feature = "otlp")]
+use opentelemetry::sdk::trace;
+#[cfg(feature = "otlp")]
+use opentelemetry::sdk::Resource;
+#[cfg(feature = "otlp")]
+use opentelemetry::trace::TraceError;
+#[cfg(feature = "otlp")]
+use opentelemetry::KeyValue;
+#[cfg(feature = "otlp")]
+use opentelemetry_otlp::WithExportConfig;
+use reqwest::Url;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+#[cfg(feature = "otlp")]
+use tracing_subscriber::layer::SubscriberExt;
+#[cfg(feature = "otlp")]
+use tracing_subscriber::util::SubscriberInitExt;
+#[cfg(feature = "otlp")]
+use tracing_subscriber::util::TryInitError;
+
+#[cfg(feature = "otlp")]
+const DEFAULT_SERVICE_NAME: &str = "oracle-core";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP traces endpoint of the collector, e.g. `http://localhost:4318/v1/traces`
+    pub otlp_endpoint: Url,
+    /// `service.name` of the exported spans, `oracle-core` if not set
+    pub service_name: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum TelemetryError {
+    #[cfg(feature = "otlp")]
+    #[error("OTLP exporter error: {0}")]
+    Trace(#[from] TraceError),
+    #[cfg(feature = "otlp")]
+    #[error("tracing subscriber error: {0}")]
+    Subscriber(#[from] TryInitError),
+    #[cfg(not(feature = "otlp"))]
+    #[error("oracle-core is built without the `otlp` feature")]
+    NotBuilt,
+}
+
+/// Install the global `tracing` subscriber exporting the spans to the configured endpoint.
+/// Without it the spans are no-ops.
+#[cfg(feature = "otlp")]
+pub fn init(config: &TelemetryConfig) -> Result<(), TelemetryError> {
+    let service_name = config
+        .service_name
+        .clone()
+        .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+    // the simple processor exports every span on its own thread with a blocking client, so it
+    // does not need a tokio runtime in the main loop threads
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(config.otlp_endpoint.as_str()),
+        )
+        .with_trace_config(
+            trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                service_name,
+            )])),
+        )
+        .install_simple()?;
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "otlp"))]
+pub fn init(_config: &TelemetryConfig) -> Result<(), TelemetryError> {
+    Err(TelemetryError::NotBuilt)
+}
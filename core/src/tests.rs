@@ -1 +1,2 @@
 mod bootstrap_and_run;
+mod pool_sim;
@@ -1,42 +1,24 @@
-use std::cell::RefCell;
 use std::convert::TryInto;
 
-use ergo_chain_sim::Block;
 use ergo_chain_sim::ChainSim;
 use ergo_lib::chain::ergo_state_context::ErgoStateContext;
-use ergo_lib::chain::transaction::Transaction;
-use ergo_lib::chain::transaction::TxId;
 use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
 use ergo_lib::ergotree_ir::chain::address::Address;
 use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
 use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+use ergo_lib::wallet::tx_builder::SUGGESTED_TX_FEE;
 use ergo_lib::wallet::Wallet;
 use sigma_test_util::force_any_val;
 
 use crate::cli_commands::bootstrap::perform_bootstrap_chained_transaction;
 use crate::cli_commands::bootstrap::BootstrapConfig;
 use crate::cli_commands::bootstrap::BootstrapInput;
-use crate::node_interface;
-use crate::node_interface::SubmitTransaction;
-use crate::oracle_config::BASE_FEE;
 use crate::oracle_types::BlockHeight;
 use crate::pool_commands::test_utils::init_log_tests;
 use crate::pool_commands::test_utils::LocalTxSigner;
 use crate::pool_commands::test_utils::WalletDataMock;
 use crate::pool_config::PoolConfig;
-
-struct ChainSubmitTx<'a> {
-    chain: RefCell<&'a mut ChainSim>,
-}
-
-impl<'a> SubmitTransaction for ChainSubmitTx<'a> {
-    fn submit_transaction(&self, tx: &Transaction) -> node_interface::Result<TxId> {
-        self.chain
-            .borrow_mut()
-            .add_block(Block::new(vec![tx.clone()]));
-        Ok(tx.id())
-    }
-}
+use crate::sim::ChainSubmitTx;
 
 fn bootstrap(wallet: &Wallet, net_address: &NetworkAddress, chain: &mut ChainSim) -> PoolConfig {
     let ctx = force_any_val::<ErgoStateContext>();
@@ -58,8 +40,8 @@ fn bootstrap(wallet: &Wallet, net_address: &NetworkAddress, chain: &mut ChainSim
         },
         tx_signer: &mut LocalTxSigner { ctx: &ctx, wallet },
         submit_tx: &mut submit_tx_mock,
-        tx_fee: *BASE_FEE,
-        erg_value_per_box: *BASE_FEE,
+        tx_fee: SUGGESTED_TX_FEE(),
+        erg_value_per_box: SUGGESTED_TX_FEE(),
         change_address: net_address.address(),
         height,
     })
@@ -0,0 +1,92 @@
+use std::convert::TryInto;
+
+use ergo_lib::ergo_chain_types::Digest32;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+use sigma_test_util::force_any_val;
+
+use crate::box_kind::OracleBox;
+use crate::box_kind::PoolBox;
+use crate::cli_commands::bootstrap::BootstrapConfig;
+use crate::oracle_types::EpochCounter;
+use crate::oracle_types::Rate;
+use crate::sim::PoolSim;
+use crate::spec_token::RewardTokenId;
+use crate::spec_token::SpecToken;
+use crate::spec_token::TokenIdKind;
+
+fn rates(rates: &[i64]) -> Vec<Rate> {
+    rates.iter().map(|rate| Rate::from(*rate)).collect()
+}
+
+#[test]
+fn test_publish_and_refresh_epochs() {
+    let mut sim = PoolSim::bootstrap(BootstrapConfig::default(), 6).unwrap();
+    assert_eq!(sim.pool_box().unwrap().epoch_counter(), EpochCounter(1));
+    assert!(sim.oracle_box(0).unwrap().is_none());
+
+    // first datapoints create the oracle boxes
+    sim.run_epoch(
+        &rates(&[100_000, 100_100, 100_200, 100_300, 100_400, 100_500]),
+        0,
+    )
+    .unwrap();
+    let pool_box = sim.pool_box().unwrap();
+    assert_eq!(pool_box.epoch_counter(), EpochCounter(2));
+    assert_eq!(pool_box.rate(), 100_250);
+    assert!(sim.posted_oracle_boxes().is_empty());
+
+    // the collected oracle boxes are spent by the next datapoints, the outlier is left out
+    sim.run_epoch(
+        &rates(&[200_000, 200_000, 200_000, 200_000, 200_400, 300_000]),
+        1,
+    )
+    .unwrap();
+    let pool_box = sim.pool_box().unwrap();
+    assert_eq!(pool_box.epoch_counter(), EpochCounter(3));
+    assert_eq!(pool_box.rate(), 200_080);
+    assert_eq!(
+        sim.oracle_box(5).unwrap().unwrap().public_key(),
+        sim.oracles()[5].public_key()
+    );
+    assert_eq!(sim.posted_oracle_boxes().len(), 1);
+}
+
+#[test]
+fn test_vote_and_update_pool() {
+    let mut sim = PoolSim::bootstrap(BootstrapConfig::default(), 6).unwrap();
+    sim.run_epoch(&rates(&[100_000; 6]), 0).unwrap();
+
+    let new_pool_contract_inputs = sim
+        .pool_config()
+        .pool_box_wrapper_inputs
+        .contract_inputs
+        .clone();
+    let new_reward_tokens = SpecToken {
+        token_id: RewardTokenId::from_token_id_unchecked(force_any_val::<Digest32>().into()),
+        amount: 1_000_000.try_into().unwrap(),
+    };
+    sim.fund(
+        0,
+        BoxValue::SAFE_USER_MIN,
+        Some(vec![new_reward_tokens.clone().into()].try_into().unwrap()),
+    )
+    .unwrap();
+    sim.vote_all(&new_pool_contract_inputs, Some(new_reward_tokens.clone()))
+        .unwrap();
+    assert_eq!(sim.vote_ballot_boxes().len(), 6);
+    let update_box_id = sim.update_box().unwrap().get_box().box_id();
+    sim.update_pool(0, new_pool_contract_inputs, Some(new_reward_tokens.clone()))
+        .unwrap();
+
+    let pool_box = sim.pool_box().unwrap();
+    assert_eq!(
+        pool_box.reward_token().token_id.token_id(),
+        new_reward_tokens.token_id.token_id()
+    );
+    assert_eq!(pool_box.reward_token().amount, new_reward_tokens.amount);
+    assert_eq!(pool_box.epoch_counter(), EpochCounter(2));
+    assert_eq!(pool_box.rate(), 100_000);
+    assert_ne!(sim.update_box().unwrap().get_box().box_id(), update_box_id);
+    // the ballot boxes are recreated without the votes
+    assert!(sim.vote_ballot_boxes().is_empty());
+}
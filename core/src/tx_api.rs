@@ -0,0 +1,484 @@
+//! Building the oracle txs from explicit inputs, for integrators that sign and submit them in their
+//! own flows.
+//!
+//! Unlike [`crate::pool_commands::build_action`] these functions do not need the configs or the
+//! node scans, they take the boxes, the height, the change address and the [`TxParams`] and
+//! return the unsigned tx. The wallet boxes are the unspent boxes the inputs and the fee are
+//! selected from. Babel fee boxes are not used.
+
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::ergo_chain_types::Digest32;
+use ergo_lib::ergo_chain_types::EcPoint;
+use ergo_lib::ergotree_ir::chain::address::Address;
+use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+
+use crate::box_kind::BallotBoxWrapper;
+use crate::box_kind::BuybackBoxWrapper;
+use crate::box_kind::OracleBoxWrapper;
+use crate::box_kind::OracleBoxWrapperInputs;
+use crate::box_kind::PoolBoxWrapper;
+use crate::box_kind::PostedOracleBox;
+use crate::box_kind::RefreshBoxWrapper;
+use crate::box_kind::UpdateBoxWrapper;
+use crate::box_kind::VoteBallotBoxWrapper;
+use crate::cli_commands::extract_reward_tokens;
+use crate::cli_commands::extract_reward_tokens::ExtractRewardTokensActionError;
+use crate::cli_commands::update_pool::build_update_pool_box_tx;
+use crate::cli_commands::update_pool::UpdatePoolError;
+use crate::cli_commands::vote_update_pool::build_tx_for_first_ballot_box;
+use crate::cli_commands::vote_update_pool::build_tx_with_existing_ballot_box;
+use crate::cli_commands::vote_update_pool::VoteUpdatePoolError;
+use crate::contracts::ballot::BallotContractParameters;
+use crate::contracts::pool::PoolContract;
+use crate::datapoint_source::DataPointSource;
+use crate::datapoint_source::DataPointSourceError;
+use crate::oracle_config::TxParams;
+use crate::oracle_state;
+use crate::oracle_state::BuybackBoxSource;
+use crate::oracle_state::LocalDatapointBoxSource;
+use crate::oracle_state::PoolBoxSource;
+use crate::oracle_state::PostedDatapointBoxesSource;
+use crate::oracle_state::RefreshBoxSource;
+use crate::oracle_state::UpdateBoxSource;
+use crate::oracle_state::VoteBallotBoxesSource;
+use crate::oracle_types::BlockHeight;
+use crate::oracle_types::EpochCounter;
+use crate::oracle_types::MinDatapoints;
+use crate::oracle_types::Rate;
+use crate::pool_commands::publish_datapoint::build_publish_first_datapoint_action;
+use crate::pool_commands::publish_datapoint::build_subsequent_publish_datapoint_action;
+use crate::pool_commands::publish_datapoint::PublishDatapointActionError;
+use crate::pool_commands::refresh::build_refresh_action;
+use crate::pool_commands::refresh::RefreshActionError;
+use crate::pool_config::ErgRewards;
+use crate::pool_config::TokenIds;
+use crate::spec_token::RewardTokenId;
+use crate::spec_token::SpecToken;
+use crate::wallet::WalletDataError;
+use crate::wallet::WalletDataSource;
+
+struct WalletBoxes {
+    boxes: Vec<ErgoBox>,
+    change_address: NetworkAddress,
+}
+
+impl WalletDataSource for WalletBoxes {
+    fn get_unspent_wallet_boxes(&self) -> Result<Vec<ErgoBox>, WalletDataError> {
+        Ok(self.boxes.clone())
+    }
+
+    fn get_change_address(&self) -> Result<NetworkAddress, WalletDataError> {
+        Ok(self.change_address.clone())
+    }
+}
+
+struct FixedDatapoint(Rate);
+
+impl DataPointSource for FixedDatapoint {
+    fn get_datapoint(&self) -> Result<Rate, DataPointSourceError> {
+        Ok(self.0)
+    }
+}
+
+struct RefreshInputs {
+    pool_box: PoolBoxWrapper,
+    refresh_box: RefreshBoxWrapper,
+    oracle_boxes: Vec<PostedOracleBox>,
+    buyback_box: Option<BuybackBoxWrapper>,
+}
+
+impl PoolBoxSource for RefreshInputs {
+    fn get_pool_box(&self) -> oracle_state::Result<PoolBoxWrapper> {
+        Ok(self.pool_box.clone())
+    }
+}
+
+impl RefreshBoxSource for RefreshInputs {
+    fn get_refresh_box(&self) -> oracle_state::Result<RefreshBoxWrapper> {
+        Ok(self.refresh_box.clone())
+    }
+}
+
+impl PostedDatapointBoxesSource for RefreshInputs {
+    fn get_posted_datapoint_boxes(&self) -> oracle_state::Result<Vec<PostedOracleBox>> {
+        Ok(self.oracle_boxes.clone())
+    }
+}
+
+impl BuybackBoxSource for RefreshInputs {
+    fn get_buyback_box(&self) -> oracle_state::Result<Option<BuybackBoxWrapper>> {
+        Ok(self.buyback_box.clone())
+    }
+}
+
+struct UpdateInputs {
+    pool_box: PoolBoxWrapper,
+    update_box: UpdateBoxWrapper,
+    ballot_boxes: Vec<VoteBallotBoxWrapper>,
+}
+
+impl PoolBoxSource for UpdateInputs {
+    fn get_pool_box(&self) -> oracle_state::Result<PoolBoxWrapper> {
+        Ok(self.pool_box.clone())
+    }
+}
+
+impl UpdateBoxSource for UpdateInputs {
+    fn get_update_box(&self) -> oracle_state::Result<UpdateBoxWrapper> {
+        Ok(self.update_box.clone())
+    }
+}
+
+impl VoteBallotBoxesSource for UpdateInputs {
+    fn get_ballot_boxes(&self) -> oracle_state::Result<Vec<VoteBallotBoxWrapper>> {
+        Ok(self.ballot_boxes.clone())
+    }
+}
+
+struct LocalOracleBox(OracleBoxWrapper);
+
+impl LocalDatapointBoxSource for LocalOracleBox {
+    fn get_local_oracle_datapoint_box(&self) -> oracle_state::Result<Option<OracleBoxWrapper>> {
+        Ok(Some(self.0.clone()))
+    }
+}
+
+/// Publish `rate` by creating the first oracle box from the oracle token in the wallet
+pub fn build_publish_first_datapoint_tx(
+    oracle_box_wrapper_inputs: OracleBoxWrapperInputs,
+    public_key: EcPoint,
+    rate: Rate,
+    wallet_boxes: Vec<ErgoBox>,
+    height: BlockHeight,
+    change_address: NetworkAddress,
+    tx_params: &TxParams,
+) -> Result<UnsignedTransaction, PublishDatapointActionError> {
+    let (action, _) = build_publish_first_datapoint_action(
+        &WalletBoxes {
+            boxes: wallet_boxes,
+            change_address: change_address.clone(),
+        },
+        height,
+        change_address.address(),
+        public_key,
+        oracle_box_wrapper_inputs,
+        &FixedDatapoint(rate),
+        None,
+        tx_params,
+    )?;
+    Ok(action.tx)
+}
+
+/// Publish `rate` for the epoch `epoch_counter` by spending the oracle box
+#[allow(clippy::too_many_arguments)]
+pub fn build_publish_datapoint_tx(
+    oracle_box: &OracleBoxWrapper,
+    rate: Rate,
+    epoch_counter: EpochCounter,
+    reward_token_id: &RewardTokenId,
+    wallet_boxes: Vec<ErgoBox>,
+    height: BlockHeight,
+    change_address: NetworkAddress,
+    tx_params: &TxParams,
+) -> Result<UnsignedTransaction, PublishDatapointActionError> {
+    let (action, _) = build_subsequent_publish_datapoint_action(
+        oracle_box,
+        &WalletBoxes {
+            boxes: wallet_boxes,
+            change_address: change_address.clone(),
+        },
+        height,
+        change_address.address(),
+        &FixedDatapoint(rate),
+        epoch_counter,
+        reward_token_id,
+        None,
+        tx_params,
+    )?;
+    Ok(action.tx)
+}
+
+/// Refresh the pool box collecting the given posted oracle boxes. `my_oracle_pk` is the collector,
+/// its own oracle box has to be among `oracle_boxes`. The reward tokens of `buyback_box` are moved
+/// to the pool box (up to `buyback_max_reward_tokens` if set).
+#[allow(clippy::too_many_arguments)]
+pub fn build_refresh_tx(
+    pool_box: PoolBoxWrapper,
+    refresh_box: RefreshBoxWrapper,
+    oracle_boxes: Vec<PostedOracleBox>,
+    buyback_box: Option<BuybackBoxWrapper>,
+    max_deviation_percent: u32,
+    min_data_points: MinDatapoints,
+    my_oracle_pk: &EcPoint,
+    buyback_max_reward_tokens: Option<u64>,
+    erg_rewards: Option<&ErgRewards>,
+    wallet_boxes: Vec<ErgoBox>,
+    height: BlockHeight,
+    change_address: NetworkAddress,
+    tx_params: &TxParams,
+) -> Result<UnsignedTransaction, RefreshActionError> {
+    let inputs = RefreshInputs {
+        pool_box,
+        refresh_box,
+        oracle_boxes,
+        buyback_box,
+    };
+    let (action, _) = build_refresh_action(
+        &inputs,
+        &inputs,
+        &inputs,
+        max_deviation_percent,
+        min_data_points,
+        &WalletBoxes {
+            boxes: wallet_boxes,
+            change_address: change_address.clone(),
+        },
+        height,
+        change_address.address(),
+        my_oracle_pk,
+        Some(&inputs),
+        buyback_max_reward_tokens,
+        None,
+        erg_rewards,
+        tx_params,
+    )?;
+    Ok(action.tx)
+}
+
+/// Vote for the new pool box contract with hash `new_pool_box_address_hash` by spending the ballot
+/// box
+#[allow(clippy::too_many_arguments)]
+pub fn build_vote_tx(
+    ballot_box: BallotBoxWrapper,
+    new_pool_box_address_hash: Digest32,
+    reward_token: Option<SpecToken<RewardTokenId>>,
+    update_box_creation_height: BlockHeight,
+    wallet_boxes: Vec<ErgoBox>,
+    height: BlockHeight,
+    change_address: NetworkAddress,
+    tx_params: &TxParams,
+) -> Result<UnsignedTransaction, VoteUpdatePoolError> {
+    build_tx_with_existing_ballot_box(
+        ballot_box,
+        &WalletBoxes {
+            boxes: wallet_boxes,
+            change_address: change_address.clone(),
+        },
+        new_pool_box_address_hash,
+        reward_token,
+        update_box_creation_height,
+        height,
+        change_address.address(),
+        tx_params.fee,
+    )
+}
+
+/// Vote for the new pool box contract by creating the ballot box from the ballot token in the
+/// wallet. `ballot_token_owner` has to be a P2PK address.
+#[allow(clippy::too_many_arguments)]
+pub fn build_first_vote_tx(
+    ballot_token_owner: Address,
+    ballot_contract_parameters: &BallotContractParameters,
+    token_ids: &TokenIds,
+    new_pool_box_address_hash: Digest32,
+    reward_token: Option<SpecToken<RewardTokenId>>,
+    update_box_creation_height: BlockHeight,
+    wallet_boxes: Vec<ErgoBox>,
+    height: BlockHeight,
+    change_address: NetworkAddress,
+    tx_params: &TxParams,
+) -> Result<UnsignedTransaction, VoteUpdatePoolError> {
+    build_tx_for_first_ballot_box(
+        &WalletBoxes {
+            boxes: wallet_boxes,
+            change_address: change_address.clone(),
+        },
+        new_pool_box_address_hash,
+        reward_token,
+        update_box_creation_height,
+        ballot_token_owner,
+        ballot_contract_parameters,
+        token_ids,
+        height,
+        change_address.address(),
+        tx_params.fee,
+    )
+}
+
+/// Update the pool box to `new_pool_contract` (and `new_reward_tokens`), spending the ballot boxes
+/// voting for it. The wallet boxes must not hold ballot tokens.
+#[allow(clippy::too_many_arguments)]
+pub fn build_update_pool_tx(
+    pool_box: PoolBoxWrapper,
+    update_box: UpdateBoxWrapper,
+    ballot_boxes: Vec<VoteBallotBoxWrapper>,
+    new_pool_contract: PoolContract,
+    new_reward_tokens: Option<SpecToken<RewardTokenId>>,
+    wallet_boxes: Vec<ErgoBox>,
+    height: BlockHeight,
+    change_address: NetworkAddress,
+    tx_params: &TxParams,
+) -> Result<UnsignedTransaction, UpdatePoolError> {
+    let inputs = UpdateInputs {
+        pool_box,
+        update_box,
+        ballot_boxes,
+    };
+    let tx_context = build_update_pool_box_tx(
+        &inputs,
+        &inputs,
+        &WalletBoxes {
+            boxes: wallet_boxes,
+            change_address: change_address.clone(),
+        },
+        &inputs,
+        new_pool_contract,
+        new_reward_tokens,
+        height,
+        change_address.address(),
+        tx_params.fee,
+    )?;
+    Ok(tx_context.spending_tx)
+}
+
+/// Move all reward tokens of the oracle box but one to `rewards_destination` (a P2PK address)
+pub fn build_extract_reward_tokens_tx(
+    oracle_box: OracleBoxWrapper,
+    rewards_destination: Address,
+    wallet_boxes: Vec<ErgoBox>,
+    height: BlockHeight,
+    change_address: NetworkAddress,
+    tx_params: &TxParams,
+) -> Result<UnsignedTransaction, ExtractRewardTokensActionError> {
+    let (tx, _) = extract_reward_tokens::build_extract_reward_tokens_tx(
+        &LocalOracleBox(oracle_box),
+        &WalletBoxes {
+            boxes: wallet_boxes,
+            change_address: change_address.clone(),
+        },
+        rewards_destination,
+        extract_reward_tokens::DEFAULT_KEPT_REWARD_TOKENS,
+        height,
+        change_address.address(),
+        tx_params,
+    )?;
+    Ok(tx)
+}
+
+/// Move the ERG rewards of the oracle box (everything above the min box value) to
+/// `rewards_destination` (a P2PK address)
+pub fn build_extract_erg_rewards_tx(
+    oracle_box: OracleBoxWrapper,
+    rewards_destination: Address,
+    wallet_boxes: Vec<ErgoBox>,
+    height: BlockHeight,
+    change_address: NetworkAddress,
+    tx_params: &TxParams,
+) -> Result<UnsignedTransaction, ExtractRewardTokensActionError> {
+    let (tx, _) = extract_reward_tokens::build_extract_erg_rewards_tx(
+        &LocalOracleBox(oracle_box),
+        &WalletBoxes {
+            boxes: wallet_boxes,
+            change_address: change_address.clone(),
+        },
+        rewards_destination,
+        height,
+        change_address.address(),
+        tx_params,
+    )?;
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
+    use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+    use ergo_lib::wallet::miner_fee::MINERS_FEE_ADDRESS;
+    use ergo_lib::wallet::signing::TransactionContext;
+    use ergo_lib::wallet::Wallet;
+    use sigma_test_util::force_any_val;
+
+    use crate::box_kind::OracleBox;
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::oracle_types::EpochLength;
+    use crate::pool_commands::test_utils::{
+        find_input_boxes, generate_token_ids, make_datapoint_box, make_wallet_unspent_box,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_publish_datapoint_tx_with_explicit_params() {
+        let ctx = force_any_val::<ErgoStateContext>();
+        let height = BlockHeight(ctx.pre_header.height);
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let oracle_box = OracleBoxWrapper::new(
+            make_datapoint_box(
+                *secret.public_image().h,
+                200,
+                EpochCounter(1),
+                &token_ids,
+                oracle_box_wrapper_inputs
+                    .contract_inputs
+                    .contract_parameters()
+                    .min_storage_rent,
+                height - EpochLength(99),
+                100,
+            ),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap();
+        let change_address = AddressEncoder::unchecked_parse_network_address_from_str(
+            "9iHyKxXs2ZNLMp9N9gbUT9V8gTbsV7HED1C1VhttMfBUMPDyF7r",
+        )
+        .unwrap();
+        let wallet_boxes = vec![make_wallet_unspent_box(
+            secret.public_image(),
+            BoxValue::try_from(10_000_000_000u64).unwrap(),
+            None,
+        )];
+        let tx_params = TxParams {
+            fee: BoxValue::try_from(3_000_000u64).unwrap(),
+            min_box_value: BoxValue::try_from(*oracle_box.get_box().value.as_u64() + 1_000_000)
+                .unwrap(),
+        };
+
+        let tx = build_publish_datapoint_tx(
+            &oracle_box,
+            201.into(),
+            EpochCounter(2),
+            &token_ids.reward_token_id,
+            wallet_boxes.clone(),
+            height,
+            change_address,
+            &tx_params,
+        )
+        .unwrap();
+
+        assert_eq!(tx.output_candidates.first().value, tx_params.min_box_value);
+        let fee_tree = MINERS_FEE_ADDRESS.script().unwrap();
+        assert!(tx
+            .output_candidates
+            .iter()
+            .any(|b| b.ergo_tree == fee_tree && b.value == tx_params.fee));
+        let mut possible_input_boxes = vec![oracle_box.get_box().clone()];
+        possible_input_boxes.extend(wallet_boxes);
+        let tx_context = TransactionContext::new(
+            tx.clone(),
+            find_input_boxes(tx, possible_input_boxes),
+            Vec::new(),
+        )
+        .unwrap();
+        let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
+    }
+}
@@ -106,6 +106,11 @@ impl ChainSim {
         self.all_boxes.push(b);
     }
 
+    /// Returns all unspent boxes
+    pub fn unspent_boxes(&self) -> &[ErgoBox] {
+        &self.unspent_boxes
+    }
+
     /// Returns unspent boxes guarder by the given ErgoTree
     pub fn get_unspent_boxes(&self, ergo_tree: &ErgoTree) -> Vec<ErgoBox> {
         self.unspent_boxes
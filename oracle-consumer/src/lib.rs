@@ -0,0 +1,331 @@
+//! Reader of the current rate of an oracle pool (EIP-23), for dApps that only consume the oracle.
+//!
+//! Given the pool NFT id and a node or explorer endpoint, [`OracleConsumer::fetch`] finds the
+//! unspent box holding the pool NFT, checks that it is a pool box and returns the rate in R4 and
+//! the epoch counter in R5 along with the heights telling how fresh the rate is:
+//!
+//! ```no_run
+//! use oracle_consumer::{Endpoint, OracleConsumer};
+//!
+//! let consumer = OracleConsumer::new(
+//!     "011d3364de07e5a26f0c4eef0852cddb387039a921b7154ef3cab22c6eda887f",
+//!     Endpoint::Explorer("https://api.ergoplatform.com".parse().unwrap()),
+//! )
+//! .unwrap();
+//! let pool_rate = consumer.fetch().unwrap();
+//! // the ERG/USD pool posts a new rate every 30 blocks
+//! if pool_rate.is_fresh(60) {
+//!     println!("rate {} of epoch {}", pool_rate.rate, pool_rate.epoch_counter);
+//! }
+//! ```
+//!
+//! The node endpoint needs the blockchain indexer of the node (`extraIndex = true`). The pool
+//! contract can be pinned with [`OracleConsumer::with_pool_contract`], otherwise any box holding
+//! the NFT with a rate and an epoch counter is accepted.
+
+// Coding conventions
+#![forbid(unsafe_code)]
+#![deny(non_upper_case_globals)]
+#![deny(non_camel_case_types)]
+#![deny(non_snake_case)]
+#![deny(unused_mut)]
+#![deny(dead_code)]
+#![deny(unused_imports)]
+#![deny(missing_docs)]
+#![deny(rustdoc::broken_intra_doc_links)]
+#![deny(clippy::wildcard_enum_match_arm)]
+#![deny(clippy::unimplemented)]
+#![deny(clippy::panic)]
+
+use std::convert::TryFrom;
+
+use ergo_lib::ergo_chain_types::Digest32;
+use ergo_lib::ergo_chain_types::DigestNError;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisterId;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+use ergo_lib::ergotree_ir::ergo_tree::ErgoTree;
+use thiserror::Error;
+use url::Url;
+
+/// Errors of fetching and validating the pool box
+#[derive(Debug, Error)]
+pub enum ConsumerError {
+    /// The pool NFT id is not a base16 encoded token id
+    #[error("invalid pool NFT id: {0}")]
+    InvalidPoolNftId(#[from] DigestNError),
+    /// The endpoint url could not be joined with the request path
+    #[error("invalid endpoint url: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+    /// The request to the endpoint failed
+    #[error("request error: {0}")]
+    Request(#[from] reqwest::Error),
+    /// The endpoint answered with something else than the expected JSON
+    #[error("unexpected response: {0}")]
+    Response(String),
+    /// No unspent box holds the pool NFT
+    #[error("no unspent box holds the pool NFT")]
+    PoolBoxNotFound,
+    /// Several unspent boxes hold the pool NFT
+    #[error("{0} unspent boxes hold the pool NFT")]
+    SeveralPoolBoxes(usize),
+    /// The box holding the pool NFT is not a pool box
+    #[error("invalid pool box: {0}")]
+    InvalidPoolBox(String),
+}
+
+/// Where the pool box is read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    /// Ergo node with the blockchain indexer, e.g. `http://127.0.0.1:9053`
+    Node(Url),
+    /// Ergo explorer API, e.g. `https://api.ergoplatform.com`
+    Explorer(Url),
+}
+
+/// Rate of the current pool box
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolRate {
+    /// Rate in R4 of the pool box
+    pub rate: i64,
+    /// Epoch counter in R5 of the pool box
+    pub epoch_counter: u32,
+    /// Creation height of the pool box, the height the rate was refreshed at
+    pub pool_box_height: u32,
+    /// Height of the endpoint when the pool box was read
+    pub current_height: u32,
+    /// The pool box, to be used as a data input
+    pub pool_box: ErgoBox,
+}
+
+impl PoolRate {
+    /// Blocks since the rate was refreshed
+    pub fn age_blocks(&self) -> u32 {
+        self.current_height.saturating_sub(self.pool_box_height)
+    }
+
+    /// Whether the rate was refreshed at most `max_age_blocks` blocks ago
+    pub fn is_fresh(&self, max_age_blocks: u32) -> bool {
+        self.age_blocks() <= max_age_blocks
+    }
+}
+
+/// Reader of the rate of the pool with the given pool NFT
+#[derive(Debug, Clone)]
+pub struct OracleConsumer {
+    pool_nft_id: TokenId,
+    endpoint: Endpoint,
+    pool_contract: Option<ErgoTree>,
+}
+
+impl OracleConsumer {
+    /// Reader of the pool with the pool NFT id (base16)
+    pub fn new(pool_nft_id: &str, endpoint: Endpoint) -> Result<Self, ConsumerError> {
+        Ok(OracleConsumer {
+            pool_nft_id: Digest32::try_from(pool_nft_id.to_string())?.into(),
+            endpoint,
+            pool_contract: None,
+        })
+    }
+
+    /// Only accept a pool box guarded by this contract
+    pub fn with_pool_contract(mut self, pool_contract: ErgoTree) -> Self {
+        self.pool_contract = Some(pool_contract);
+        self
+    }
+
+    /// Fetch and validate the current pool box
+    pub fn fetch(&self) -> Result<PoolRate, ConsumerError> {
+        let (boxes, current_height) = match &self.endpoint {
+            Endpoint::Node(url) => (
+                get_json(
+                    url,
+                    &format!(
+                        "/blockchain/box/unspent/byTokenId/{}",
+                        String::from(self.pool_nft_id)
+                    ),
+                )?,
+                get_json(url, "/info")?["fullHeight"].as_u64(),
+            ),
+            Endpoint::Explorer(url) => (
+                get_json(
+                    url,
+                    &format!(
+                        "/api/v1/boxes/unspent/byTokenId/{}",
+                        String::from(self.pool_nft_id)
+                    ),
+                )?["items"]
+                    .clone(),
+                get_json(url, "/api/v1/networkState")?["height"].as_u64(),
+            ),
+        };
+        let boxes: Vec<ErgoBox> = serde_json::from_value(boxes)
+            .map_err(|e| ConsumerError::Response(format!("boxes: {}", e)))?;
+        let current_height = current_height
+            .ok_or_else(|| ConsumerError::Response("no current height".to_string()))?;
+        self.pool_rate(boxes, current_height as u32)
+    }
+
+    /// Rate of the pool box among the unspent boxes holding the pool NFT
+    pub fn pool_rate(
+        &self,
+        boxes: Vec<ErgoBox>,
+        current_height: u32,
+    ) -> Result<PoolRate, ConsumerError> {
+        let pool_box = match boxes.len() {
+            0 => return Err(ConsumerError::PoolBoxNotFound),
+            1 => boxes
+                .into_iter()
+                .next()
+                .ok_or(ConsumerError::PoolBoxNotFound)?,
+            count => return Err(ConsumerError::SeveralPoolBoxes(count)),
+        };
+        let invalid = |reason: &str| ConsumerError::InvalidPoolBox(reason.to_string());
+        let first_token = pool_box
+            .tokens
+            .as_ref()
+            .and_then(|tokens| tokens.get(0).cloned())
+            .ok_or_else(|| invalid("no tokens"))?;
+        if first_token.token_id != self.pool_nft_id || *first_token.amount.as_u64() != 1 {
+            return Err(invalid("the pool NFT is not its first token"));
+        }
+        if let Some(pool_contract) = &self.pool_contract {
+            if &pool_box.ergo_tree != pool_contract {
+                return Err(invalid("guarded by another contract"));
+            }
+        }
+        let rate = pool_box
+            .get_register(NonMandatoryRegisterId::R4.into())
+            .and_then(|c| c.try_extract_into::<i64>().ok())
+            .ok_or_else(|| invalid("no rate (Long) in R4"))?;
+        let epoch_counter = pool_box
+            .get_register(NonMandatoryRegisterId::R5.into())
+            .and_then(|c| c.try_extract_into::<i32>().ok())
+            .filter(|epoch_counter| *epoch_counter >= 0)
+            .ok_or_else(|| invalid("no epoch counter (Int) in R5"))?;
+        Ok(PoolRate {
+            rate,
+            epoch_counter: epoch_counter as u32,
+            pool_box_height: pool_box.creation_height,
+            current_height,
+            pool_box,
+        })
+    }
+}
+
+fn get_json(url: &Url, path: &str) -> Result<serde_json::Value, ConsumerError> {
+    let response = reqwest::blocking::Client::new()
+        .get(url.join(path)?)
+        .header("accept", "application/json")
+        .send()?
+        .error_for_status()?;
+    serde_json::from_str(&response.text()?)
+        .map_err(|e| ConsumerError::Response(format!("{}: {}", path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use ergo_lib::chain::transaction::TxId;
+    use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+    use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisters;
+    use ergo_lib::ergotree_ir::chain::token::Token;
+    use ergo_lib::ergotree_ir::mir::constant::Constant;
+    use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const POOL_NFT_ID: &str = "011d3364de07e5a26f0c4eef0852cddb387039a921b7154ef3cab22c6eda887f";
+
+    /// P2PK tree of the secp256k1 generator
+    const TREE: &str = "0008cd0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    fn pool_box(token_id: &str, registers: Vec<(NonMandatoryRegisterId, Constant)>) -> ErgoBox {
+        let token_id: TokenId = Digest32::try_from(token_id.to_string()).unwrap().into();
+        ErgoBox::new(
+            BoxValue::SAFE_USER_MIN,
+            ErgoTree::sigma_parse_bytes(&base16::decode(TREE).unwrap()).unwrap(),
+            Some(
+                vec![Token {
+                    token_id,
+                    amount: 1.try_into().unwrap(),
+                }]
+                .try_into()
+                .unwrap(),
+            ),
+            NonMandatoryRegisters::new(registers.into_iter().collect()).unwrap(),
+            1000,
+            TxId::zero(),
+            0,
+        )
+        .unwrap()
+    }
+
+    fn consumer() -> OracleConsumer {
+        OracleConsumer::new(
+            POOL_NFT_ID,
+            Endpoint::Node("http://127.0.0.1:9053".parse().unwrap()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_pool_rate() {
+        let registers = vec![
+            (NonMandatoryRegisterId::R4, Constant::from(250_000_000i64)),
+            (NonMandatoryRegisterId::R5, Constant::from(12i32)),
+        ];
+        let pool_rate = consumer()
+            .pool_rate(vec![pool_box(POOL_NFT_ID, registers.clone())], 1040)
+            .unwrap();
+        assert_eq!(pool_rate.rate, 250_000_000);
+        assert_eq!(pool_rate.epoch_counter, 12);
+        assert_eq!(pool_rate.age_blocks(), 40);
+        assert!(pool_rate.is_fresh(40));
+        assert!(!pool_rate.is_fresh(39));
+
+        let other_contract = ErgoTree::sigma_parse_bytes(
+            &base16::decode(
+                "0008cd02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(matches!(
+            consumer()
+                .with_pool_contract(other_contract)
+                .pool_rate(vec![pool_box(POOL_NFT_ID, registers.clone())], 1040),
+            Err(ConsumerError::InvalidPoolBox(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_pool_boxes() {
+        assert!(matches!(
+            consumer().pool_rate(vec![], 1000),
+            Err(ConsumerError::PoolBoxNotFound)
+        ));
+        let no_epoch_counter = vec![(NonMandatoryRegisterId::R4, Constant::from(1i64))];
+        assert!(matches!(
+            consumer().pool_rate(vec![pool_box(POOL_NFT_ID, no_epoch_counter.clone())], 1000),
+            Err(ConsumerError::InvalidPoolBox(_))
+        ));
+        let other_token = "02".repeat(32);
+        assert!(matches!(
+            consumer().pool_rate(vec![pool_box(&other_token, no_epoch_counter.clone())], 1000),
+            Err(ConsumerError::InvalidPoolBox(_))
+        ));
+        assert!(matches!(
+            consumer().pool_rate(
+                vec![
+                    pool_box(POOL_NFT_ID, no_epoch_counter.clone()),
+                    pool_box(POOL_NFT_ID, no_epoch_counter)
+                ],
+                1000
+            ),
+            Err(ConsumerError::SeveralPoolBoxes(2))
+        ));
+    }
+}